@@ -37,6 +37,22 @@ pub fn get_associated_token_address(
     get_associated_token_address_and_bump_seed(&wallet_address, &spl_token_mint_address, &id()).0
 }
 
+/// Derives the associated token account address for the given wallet address and token mint,
+/// for a token program other than SPL Token
+pub fn get_associated_token_address_with_program_id(
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address_and_bump_seed_internal(
+        wallet_address,
+        token_mint_address,
+        &id(),
+        token_program_id,
+    )
+    .0
+}
+
 fn get_associated_token_address_and_bump_seed_internal(
     wallet_address: &Pubkey,
     spl_token_mint_address: &Pubkey,
@@ -70,8 +86,31 @@ pub fn create_associated_token_account(
     wallet_address: &Pubkey,
     spl_token_mint_address: &Pubkey,
 ) -> Instruction {
-    let associated_account_address =
-        get_associated_token_address(wallet_address, spl_token_mint_address);
+    create_associated_token_account_with_program_id(
+        funding_address,
+        wallet_address,
+        spl_token_mint_address,
+        &spl_token::id(),
+    )
+}
+
+/// Create an associated token account for the given wallet address and token mint, owned by a
+/// token program other than SPL Token.  The token program must share SPL Token's account layout
+/// and `InitializeAccount` instruction, and own the token mint.
+///
+/// Accounts expected by this instruction are the same as `create_associated_token_account`,
+/// with the given token program in place of SPL Token.
+pub fn create_associated_token_account_with_program_id(
+    funding_address: &Pubkey,
+    wallet_address: &Pubkey,
+    token_mint_address: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    let associated_account_address = get_associated_token_address_with_program_id(
+        wallet_address,
+        token_mint_address,
+        token_program_id,
+    );
 
     Instruction {
         program_id: id(),
@@ -79,9 +118,9 @@ pub fn create_associated_token_account(
             AccountMeta::new(*funding_address, true),
             AccountMeta::new(associated_account_address, false),
             AccountMeta::new_readonly(*wallet_address, false),
-            AccountMeta::new_readonly(*spl_token_mint_address, false),
+            AccountMeta::new_readonly(*token_mint_address, false),
             AccountMeta::new_readonly(solana_program::system_program::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(*token_program_id, false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
         ],
         data: vec![],
@@ -40,6 +40,10 @@ pub fn process_instruction(
         msg!("Error: Associated address does not match seed derivation");
         return Err(ProgramError::InvalidSeeds);
     }
+    if spl_token_mint_info.owner != spl_token_program_id {
+        msg!("Error: Token mint is not owned by the token program");
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
     let associated_token_account_signer_seeds: &[&[_]] = &[
         &wallet_account_info.key.to_bytes(),
@@ -87,7 +91,7 @@ pub fn process_instruction(
         &[&associated_token_account_signer_seeds],
     )?;
 
-    msg!("Assign the associated token account to the SPL Token program");
+    msg!("Assign the associated token account to the token program");
     invoke_signed(
         &system_instruction::assign(associated_token_account_info.key, &spl_token_program_id),
         &[
@@ -10,6 +10,13 @@ use solana_sdk::{
 use spl_associated_token_account::*;
 
 fn program_test(token_mint_address: Pubkey) -> ProgramTest {
+    program_test_with_token_program(token_mint_address, spl_token::id())
+}
+
+fn program_test_with_token_program(
+    token_mint_address: Pubkey,
+    token_program_id: Pubkey,
+) -> ProgramTest {
     let mut pc = ProgramTest::new(
         "spl_associated_token_account",
         id(),
@@ -25,10 +32,19 @@ fn program_test(token_mint_address: Pubkey) -> ProgramTest {
     pc.add_account_with_file_data(
         token_mint_address,
         1461600,
-        spl_token::id(),
+        token_program_id,
         "token-mint-data.bin",
     );
 
+    // Run a copy of SPL Token under a different program id, standing in for a fork
+    if token_program_id != spl_token::id() {
+        pc.add_program(
+            "spl_token_fork",
+            token_program_id,
+            processor!(spl_token::processor::Processor::process),
+        );
+    }
+
     // Dial down the BPF compute budget to detect if the program gets bloated in the future
     pc.set_bpf_compute_max_units(50_000);
 
@@ -242,3 +258,77 @@ async fn test_create_account_mismatch() {
         TransactionError::InstructionError(0, InstructionError::InvalidSeeds)
     );
 }
+
+#[tokio::test]
+async fn test_associated_token_address_with_program_id() {
+    let wallet_address = Pubkey::new_unique();
+    let token_mint_address = Pubkey::new_unique();
+    let token_program_id = Pubkey::new_unique();
+    let associated_token_address = get_associated_token_address_with_program_id(
+        &wallet_address,
+        &token_mint_address,
+        &token_program_id,
+    );
+    assert_ne!(
+        associated_token_address,
+        get_associated_token_address(&wallet_address, &token_mint_address)
+    );
+
+    let (mut banks_client, payer, recent_blockhash) =
+        program_test_with_token_program(token_mint_address, token_program_id)
+            .start()
+            .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[create_associated_token_account_with_program_id(
+            &payer.pubkey(),
+            &wallet_address,
+            &token_mint_address,
+            &token_program_id,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let associated_account = banks_client
+        .get_account(associated_token_address)
+        .await
+        .expect("get_account")
+        .expect("associated_account not none");
+    assert_eq!(associated_account.owner, token_program_id);
+    let account = spl_token::state::Account::unpack(&associated_account.data).unwrap();
+    assert_eq!(account.mint, token_mint_address);
+    assert_eq!(account.owner, wallet_address);
+}
+
+#[tokio::test]
+async fn test_create_token_program_mismatch() {
+    let wallet_address = Pubkey::new_unique();
+    let token_mint_address = Pubkey::new_unique();
+    let token_program_id = Pubkey::new_unique();
+
+    let (mut banks_client, payer, recent_blockhash) =
+        program_test_with_token_program(token_mint_address, token_program_id)
+            .start()
+            .await;
+
+    // The mint belongs to the fork, so SPL Token can't create an account for it
+    let mut transaction = Transaction::new_with_payer(
+        &[create_associated_token_account(
+            &payer.pubkey(),
+            &wallet_address,
+            &token_mint_address,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+}
@@ -0,0 +1,11 @@
+#!/usr/bin/env bash
+
+set -e
+cd "$(dirname "$0")/.."
+source ./ci/rust-version.sh stable
+source ./ci/solana-version.sh install
+
+set -x
+cargo +"$rust_stable" build-bpf --manifest-path=stake-pool/program/Cargo.toml
+cargo +"$rust_stable" build --manifest-path=stake-pool/cli/Cargo.toml
+cargo +"$rust_stable" test --manifest-path=stake-pool/e2e/Cargo.toml --features test-e2e -- --nocapture
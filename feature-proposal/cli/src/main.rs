@@ -407,7 +407,7 @@ fn process_tally(
     println!("Feature Id: {}", feature_id_address);
     println!("Acceptance Token Address: {}", acceptance_token_address);
 
-    match feature_proposal {
+    let tally_instruction = match feature_proposal {
         FeatureProposal::Uninitialized => {
             return Err("Feature proposal is uninitialized".into());
         }
@@ -446,6 +446,35 @@ fn process_tally(
                 println!("Feature proposal pending");
                 return Ok(());
             }
+            spl_feature_proposal::instruction::tally(feature_proposal_address)
+        }
+        FeatureProposal::PendingWeighted(acceptance_criteria) => {
+            println!();
+            println!(
+                "{} weighted tokens required to accept the proposal",
+                spl_feature_proposal::amount_to_ui_amount(acceptance_criteria.tokens_required)
+            );
+            for acceptance_mint in acceptance_criteria.active_mints() {
+                println!(
+                    "Mint {} accepted with weight {} at {}",
+                    acceptance_mint.mint,
+                    acceptance_mint.weight,
+                    spl_feature_proposal::get_mint_acceptance_token_address(
+                        feature_proposal_address,
+                        &acceptance_mint.mint
+                    )
+                );
+            }
+            println!(
+                "Proposal will expire at {}",
+                unix_timestamp_to_string(acceptance_criteria.deadline)
+            );
+            println!();
+
+            spl_feature_proposal::instruction::tally_weighted(
+                feature_proposal_address,
+                &acceptance_criteria,
+            )
         }
         FeatureProposal::Accepted { .. } => {
             println!("Feature proposal accepted");
@@ -455,14 +484,10 @@ fn process_tally(
             println!("Feature proposal expired");
             return Ok(());
         }
-    }
+    };
 
-    let mut transaction = Transaction::new_with_payer(
-        &[spl_feature_proposal::instruction::tally(
-            feature_proposal_address,
-        )],
-        Some(&config.keypair.pubkey()),
-    );
+    let mut transaction =
+        Transaction::new_with_payer(&[tally_instruction], Some(&config.keypair.pubkey()));
     let blockhash = rpc_client.get_recent_blockhash()?.0;
     transaction.try_sign(&[&config.keypair], blockhash)?;
 
@@ -472,7 +497,7 @@ fn process_tally(
     let feature_proposal = get_feature_proposal(rpc_client, feature_proposal_address)?;
     match feature_proposal {
         FeatureProposal::Uninitialized => Err("Feature proposal is uninitialized".into()),
-        FeatureProposal::Pending { .. } => {
+        FeatureProposal::Pending { .. } | FeatureProposal::PendingWeighted { .. } => {
             println!("Feature proposal pending");
             Ok(())
         }
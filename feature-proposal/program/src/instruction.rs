@@ -1,6 +1,9 @@
 //! Program instructions
 
-use crate::{state::AcceptanceCriteria, *};
+use crate::{
+    state::{AcceptanceCriteria, WeightedAcceptanceCriteria},
+    *,
+};
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -71,12 +74,55 @@ pub enum FeatureProposalInstruction {
     /// 2. `[writeable]` Derived feature id account address from `get_feature_id_address`
     /// 3. `[]` System program
     /// 4. `[]` Clock sysvar
+    ///
+    /// For proposals created with `FeatureProposalInstruction::ProposeWeighted`, the following
+    /// accounts are also expected, once for each mint in use by the acceptance criteria, in order:
+    ///
+    /// 5. `[]` Acceptance token account address from `get_mint_acceptance_token_address`
     Tally,
+
+    /// Propose a new feature that is accepted by a weighted tally over several SPL Token mints.
+    ///
+    /// This instruction creates the same accounts as `FeatureProposalInstruction::Propose`, and
+    /// in addition one acceptance token account for each mint in use by the acceptance criteria,
+    /// owned by the program.  Tokens transfers to these addresses are irrevocable and permanent.
+    ///
+    /// Based on the provided acceptance criteria, if the sum of the balances of the acceptance
+    /// token accounts, each multiplied by the weight of its mint, reaches
+    /// `WeightedAcceptanceCriteria::tokens_required` before
+    /// `WeightedAcceptanceCriteria::deadline` then the proposal is eligible to be accepted.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writeable,signer]` Funding account (must be a system account)
+    /// 1. `[writeable,signer]` Unallocated feature proposal account to create
+    /// 2. `[writeable]` Token mint address from `get_mint_address`
+    /// 3. `[writeable]` Distributor token account address from `get_distributor_token_address`
+    /// 4. `[writeable]` Acceptance token account address from `get_acceptance_token_address`
+    /// 5. `[writeable]` Feature id account address from `get_feature_id_address`
+    /// 6. `[]` System program
+    /// 7. `[]` SPL Token program
+    /// 8. `[]` Rent sysvar
+    ///
+    /// Then, once for each mint in use by the acceptance criteria, in order:
+    ///
+    /// 9. `[writeable]` Acceptance token account address from `get_mint_acceptance_token_address`
+    /// 10. `[]` Acceptance mint
+    ///
+    ProposeWeighted {
+        /// Total number of tokens to mint for this proposal
+        #[allow(dead_code)] // not dead code..
+        tokens_to_mint: u64,
+
+        /// Criteria for how this proposal may be activated
+        #[allow(dead_code)] // not dead code..
+        acceptance_criteria: WeightedAcceptanceCriteria,
+    },
 }
 
 impl Sealed for FeatureProposalInstruction {}
 impl Pack for FeatureProposalInstruction {
-    const LEN: usize = 25; // see `test_get_packed_len()` for justification of "18"
+    const LEN: usize = 113; // see `test_get_packed_len()` for justification of "113"
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let data = self.pack_into_vec();
@@ -134,6 +180,55 @@ pub fn propose(
     }
 }
 
+/// Create a `FeatureProposalInstruction::ProposeWeighted` instruction
+pub fn propose_weighted(
+    funding_address: &Pubkey,
+    feature_proposal_address: &Pubkey,
+    tokens_to_mint: u64,
+    acceptance_criteria: WeightedAcceptanceCriteria,
+) -> Instruction {
+    let mut instruction = propose(
+        funding_address,
+        feature_proposal_address,
+        tokens_to_mint,
+        AcceptanceCriteria {
+            tokens_required: acceptance_criteria.tokens_required,
+            deadline: acceptance_criteria.deadline,
+        },
+    );
+    for acceptance_mint in acceptance_criteria.active_mints() {
+        instruction.accounts.push(AccountMeta::new(
+            get_mint_acceptance_token_address(feature_proposal_address, &acceptance_mint.mint),
+            false,
+        ));
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(acceptance_mint.mint, false));
+    }
+    instruction.data = FeatureProposalInstruction::ProposeWeighted {
+        tokens_to_mint,
+        acceptance_criteria,
+    }
+    .pack_into_vec();
+    instruction
+}
+
+/// Create a `FeatureProposalInstruction::Tally` instruction for a proposal created with
+/// `FeatureProposalInstruction::ProposeWeighted`
+pub fn tally_weighted(
+    feature_proposal_address: &Pubkey,
+    acceptance_criteria: &WeightedAcceptanceCriteria,
+) -> Instruction {
+    let mut instruction = tally(feature_proposal_address);
+    for acceptance_mint in acceptance_criteria.active_mints() {
+        instruction.accounts.push(AccountMeta::new_readonly(
+            get_mint_acceptance_token_address(feature_proposal_address, &acceptance_mint.mint),
+            false,
+        ));
+    }
+    instruction
+}
+
 /// Create a `FeatureProposalInstruction::Tally` instruction
 pub fn tally(feature_proposal_address: &Pubkey) -> Instruction {
     let acceptance_token_address = get_acceptance_token_address(feature_proposal_address);
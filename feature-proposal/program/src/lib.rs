@@ -36,6 +36,20 @@ pub(crate) fn get_acceptance_token_address_with_seed(
     )
 }
 
+pub(crate) fn get_mint_acceptance_token_address_with_seed(
+    feature_proposal_address: &Pubkey,
+    mint_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &feature_proposal_address.to_bytes(),
+            br"acceptance",
+            &mint_address.to_bytes(),
+        ],
+        &id(),
+    )
+}
+
 pub(crate) fn get_feature_id_address_with_seed(feature_proposal_address: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(
         &[&feature_proposal_address.to_bytes(), br"feature-id"],
@@ -60,6 +74,15 @@ pub fn get_acceptance_token_address(feature_proposal_address: &Pubkey) -> Pubkey
     get_acceptance_token_address_with_seed(feature_proposal_address).0
 }
 
+/// Derive the SPL Token token address associated with a weighted feature proposal that users send
+/// their tokens of an additional acceptance mint to
+pub fn get_mint_acceptance_token_address(
+    feature_proposal_address: &Pubkey,
+    mint_address: &Pubkey,
+) -> Pubkey {
+    get_mint_acceptance_token_address_with_seed(feature_proposal_address, mint_address).0
+}
+
 /// Derive the feature id address associated with the feature proposal
 pub fn get_feature_id_address(feature_proposal_address: &Pubkey) -> Pubkey {
     get_feature_id_address_with_seed(feature_proposal_address).0
@@ -22,7 +22,6 @@ pub fn process_instruction(
     input: &[u8],
 ) -> ProgramResult {
     let instruction = FeatureProposalInstruction::unpack_from_slice(input)?;
-    let account_info_iter = &mut accounts.iter();
 
     match instruction {
         FeatureProposalInstruction::Propose {
@@ -30,340 +29,489 @@ pub fn process_instruction(
             acceptance_criteria,
         } => {
             msg!("FeatureProposalInstruction::Propose");
+            process_propose(
+                program_id,
+                accounts,
+                tokens_to_mint,
+                FeatureProposal::Pending(acceptance_criteria),
+            )
+        }
+        FeatureProposalInstruction::ProposeWeighted {
+            tokens_to_mint,
+            acceptance_criteria,
+        } => {
+            msg!("FeatureProposalInstruction::ProposeWeighted");
+            process_propose(
+                program_id,
+                accounts,
+                tokens_to_mint,
+                FeatureProposal::PendingWeighted(acceptance_criteria),
+            )
+        }
+        FeatureProposalInstruction::Tally => {
+            msg!("FeatureProposalInstruction::Tally");
+            process_tally(accounts)
+        }
+    }
+}
 
-            let funder_info = next_account_info(account_info_iter)?;
-            let feature_proposal_info = next_account_info(account_info_iter)?;
-            let mint_info = next_account_info(account_info_iter)?;
-            let distributor_token_info = next_account_info(account_info_iter)?;
-            let acceptance_token_info = next_account_info(account_info_iter)?;
-            let feature_id_info = next_account_info(account_info_iter)?;
-            let system_program_info = next_account_info(account_info_iter)?;
-            let spl_token_program_info = next_account_info(account_info_iter)?;
-            let rent_sysvar_info = next_account_info(account_info_iter)?;
-            let rent = &Rent::from_account_info(rent_sysvar_info)?;
-
-            let (mint_address, mint_bump_seed) =
-                get_mint_address_with_seed(feature_proposal_info.key);
-            if mint_address != *mint_info.key {
-                msg!("Error: mint address derivation mismatch");
-                return Err(ProgramError::InvalidArgument);
-            }
+/// Processes `FeatureProposalInstruction::Propose` and
+/// `FeatureProposalInstruction::ProposeWeighted`
+fn process_propose(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tokens_to_mint: u64,
+    feature_proposal_state: FeatureProposal,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let feature_proposal_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let distributor_token_info = next_account_info(account_info_iter)?;
+    let acceptance_token_info = next_account_info(account_info_iter)?;
+    let feature_id_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let spl_token_program_info = next_account_info(account_info_iter)?;
+    let rent_sysvar_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_sysvar_info)?;
+
+    let (mint_address, mint_bump_seed) = get_mint_address_with_seed(feature_proposal_info.key);
+    if mint_address != *mint_info.key {
+        msg!("Error: mint address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
 
-            let (distributor_token_address, distributor_token_bump_seed) =
-                get_distributor_token_address_with_seed(feature_proposal_info.key);
-            if distributor_token_address != *distributor_token_info.key {
-                msg!("Error: distributor token address derivation mismatch");
-                return Err(ProgramError::InvalidArgument);
-            }
+    let (distributor_token_address, distributor_token_bump_seed) =
+        get_distributor_token_address_with_seed(feature_proposal_info.key);
+    if distributor_token_address != *distributor_token_info.key {
+        msg!("Error: distributor token address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (acceptance_token_address, acceptance_token_bump_seed) =
+        get_acceptance_token_address_with_seed(feature_proposal_info.key);
+    if acceptance_token_address != *acceptance_token_info.key {
+        msg!("Error: acceptance token address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (feature_id_address, feature_id_bump_seed) =
+        get_feature_id_address_with_seed(feature_proposal_info.key);
+    if feature_id_address != *feature_id_info.key {
+        msg!("Error: feature-id address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
 
-            let (acceptance_token_address, acceptance_token_bump_seed) =
-                get_acceptance_token_address_with_seed(feature_proposal_info.key);
-            if acceptance_token_address != *acceptance_token_info.key {
-                msg!("Error: acceptance token address derivation mismatch");
+    let mint_signer_seeds: &[&[_]] = &[
+        &feature_proposal_info.key.to_bytes(),
+        br"mint",
+        &[mint_bump_seed],
+    ];
+
+    let distributor_token_signer_seeds: &[&[_]] = &[
+        &feature_proposal_info.key.to_bytes(),
+        br"distributor",
+        &[distributor_token_bump_seed],
+    ];
+
+    let acceptance_token_signer_seeds: &[&[_]] = &[
+        &feature_proposal_info.key.to_bytes(),
+        br"acceptance",
+        &[acceptance_token_bump_seed],
+    ];
+
+    let feature_id_signer_seeds: &[&[_]] = &[
+        &feature_proposal_info.key.to_bytes(),
+        br"feature-id",
+        &[feature_id_bump_seed],
+    ];
+
+    msg!("Creating feature proposal account");
+    invoke(
+        &system_instruction::create_account(
+            funder_info.key,
+            feature_proposal_info.key,
+            1.max(rent.minimum_balance(FeatureProposal::get_packed_len())),
+            FeatureProposal::get_packed_len() as u64,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            feature_proposal_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+    feature_proposal_state.pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
+
+    msg!("Creating mint");
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            mint_info.key,
+            1.max(rent.minimum_balance(spl_token::state::Mint::get_packed_len())),
+            spl_token::state::Mint::get_packed_len() as u64,
+            &spl_token::id(),
+        ),
+        &[
+            funder_info.clone(),
+            mint_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&mint_signer_seeds],
+    )?;
+
+    msg!("Initializing mint");
+    invoke(
+        &spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            mint_info.key,
+            mint_info.key,
+            None,
+            spl_token::native_mint::DECIMALS,
+        )?,
+        &[
+            mint_info.clone(),
+            spl_token_program_info.clone(),
+            rent_sysvar_info.clone(),
+        ],
+    )?;
+
+    msg!("Creating distributor token account");
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            distributor_token_info.key,
+            1.max(rent.minimum_balance(spl_token::state::Account::get_packed_len())),
+            spl_token::state::Account::get_packed_len() as u64,
+            &spl_token::id(),
+        ),
+        &[
+            funder_info.clone(),
+            distributor_token_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&distributor_token_signer_seeds],
+    )?;
+
+    msg!("Initializing distributor token account");
+    invoke(
+        &spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            distributor_token_info.key,
+            mint_info.key,
+            feature_proposal_info.key,
+        )?,
+        &[
+            distributor_token_info.clone(),
+            spl_token_program_info.clone(),
+            rent_sysvar_info.clone(),
+            feature_proposal_info.clone(),
+            mint_info.clone(),
+        ],
+    )?;
+
+    msg!("Creating acceptance token account");
+    create_acceptance_token_account(
+        program_id,
+        funder_info,
+        feature_proposal_info,
+        acceptance_token_info,
+        mint_info,
+        system_program_info,
+        spl_token_program_info,
+        rent_sysvar_info,
+        rent,
+        acceptance_token_signer_seeds,
+    )?;
+
+    // Mint `tokens_to_mint` tokens into `distributor_token_account` owned by
+    // `feature_proposal`
+    msg!("Minting {} tokens", tokens_to_mint);
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint_info.key,
+            distributor_token_info.key,
+            mint_info.key,
+            &[],
+            tokens_to_mint,
+        )?,
+        &[
+            mint_info.clone(),
+            distributor_token_info.clone(),
+            spl_token_program_info.clone(),
+        ],
+        &[&mint_signer_seeds],
+    )?;
+
+    // Fully fund the feature id account so the `Tally` instruction will not require any
+    // lamports from the caller
+    msg!("Funding feature id account");
+    invoke(
+        &system_instruction::transfer(
+            funder_info.key,
+            feature_id_info.key,
+            1.max(rent.minimum_balance(Feature::size_of())),
+        ),
+        &[
+            funder_info.clone(),
+            feature_id_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+
+    msg!("Allocating feature id account");
+    invoke_signed(
+        &system_instruction::allocate(feature_id_info.key, Feature::size_of() as u64),
+        &[feature_id_info.clone(), system_program_info.clone()],
+        &[&feature_id_signer_seeds],
+    )?;
+
+    if let FeatureProposal::PendingWeighted(acceptance_criteria) = feature_proposal_state {
+        for acceptance_mint in acceptance_criteria.active_mints() {
+            let mint_acceptance_token_info = next_account_info(account_info_iter)?;
+            let acceptance_mint_info = next_account_info(account_info_iter)?;
+
+            if acceptance_mint.mint != *acceptance_mint_info.key {
+                msg!("Error: acceptance mint mismatch");
                 return Err(ProgramError::InvalidArgument);
             }
 
-            let (feature_id_address, feature_id_bump_seed) =
-                get_feature_id_address_with_seed(feature_proposal_info.key);
-            if feature_id_address != *feature_id_info.key {
-                msg!("Error: feature-id address derivation mismatch");
+            let (mint_acceptance_token_address, mint_acceptance_token_bump_seed) =
+                get_mint_acceptance_token_address_with_seed(
+                    feature_proposal_info.key,
+                    acceptance_mint_info.key,
+                );
+            if mint_acceptance_token_address != *mint_acceptance_token_info.key {
+                msg!("Error: mint acceptance token address derivation mismatch");
                 return Err(ProgramError::InvalidArgument);
             }
 
-            let mint_signer_seeds: &[&[_]] = &[
-                &feature_proposal_info.key.to_bytes(),
-                br"mint",
-                &[mint_bump_seed],
-            ];
-
-            let distributor_token_signer_seeds: &[&[_]] = &[
-                &feature_proposal_info.key.to_bytes(),
-                br"distributor",
-                &[distributor_token_bump_seed],
-            ];
-
-            let acceptance_token_signer_seeds: &[&[_]] = &[
+            let mint_acceptance_token_signer_seeds: &[&[_]] = &[
                 &feature_proposal_info.key.to_bytes(),
                 br"acceptance",
-                &[acceptance_token_bump_seed],
+                &acceptance_mint_info.key.to_bytes(),
+                &[mint_acceptance_token_bump_seed],
             ];
 
-            let feature_id_signer_seeds: &[&[_]] = &[
-                &feature_proposal_info.key.to_bytes(),
-                br"feature-id",
-                &[feature_id_bump_seed],
-            ];
-
-            msg!("Creating feature proposal account");
-            invoke(
-                &system_instruction::create_account(
-                    funder_info.key,
-                    feature_proposal_info.key,
-                    1.max(rent.minimum_balance(FeatureProposal::get_packed_len())),
-                    FeatureProposal::get_packed_len() as u64,
-                    program_id,
-                ),
-                &[
-                    funder_info.clone(),
-                    feature_proposal_info.clone(),
-                    system_program_info.clone(),
-                ],
-            )?;
-            FeatureProposal::Pending(acceptance_criteria)
-                .pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
-
-            msg!("Creating mint");
-            invoke_signed(
-                &system_instruction::create_account(
-                    funder_info.key,
-                    mint_info.key,
-                    1.max(rent.minimum_balance(spl_token::state::Mint::get_packed_len())),
-                    spl_token::state::Mint::get_packed_len() as u64,
-                    &spl_token::id(),
-                ),
-                &[
-                    funder_info.clone(),
-                    mint_info.clone(),
-                    system_program_info.clone(),
-                ],
-                &[&mint_signer_seeds],
+            msg!(
+                "Creating acceptance token account for mint {}",
+                acceptance_mint_info.key
+            );
+            create_acceptance_token_account(
+                program_id,
+                funder_info,
+                feature_proposal_info,
+                mint_acceptance_token_info,
+                acceptance_mint_info,
+                system_program_info,
+                spl_token_program_info,
+                rent_sysvar_info,
+                rent,
+                mint_acceptance_token_signer_seeds,
             )?;
+        }
+    }
 
-            msg!("Initializing mint");
-            invoke(
-                &spl_token::instruction::initialize_mint(
-                    &spl_token::id(),
-                    mint_info.key,
-                    mint_info.key,
-                    None,
-                    spl_token::native_mint::DECIMALS,
-                )?,
-                &[
-                    mint_info.clone(),
-                    spl_token_program_info.clone(),
-                    rent_sysvar_info.clone(),
-                ],
-            )?;
+    Ok(())
+}
 
-            msg!("Creating distributor token account");
-            invoke_signed(
-                &system_instruction::create_account(
-                    funder_info.key,
-                    distributor_token_info.key,
-                    1.max(rent.minimum_balance(spl_token::state::Account::get_packed_len())),
-                    spl_token::state::Account::get_packed_len() as u64,
-                    &spl_token::id(),
-                ),
-                &[
-                    funder_info.clone(),
-                    distributor_token_info.clone(),
-                    system_program_info.clone(),
-                ],
-                &[&distributor_token_signer_seeds],
-            )?;
+/// Create a program-owned token account that irrevocably receives acceptance tokens
+#[allow(clippy::too_many_arguments)]
+fn create_acceptance_token_account<'a>(
+    program_id: &Pubkey,
+    funder_info: &AccountInfo<'a>,
+    feature_proposal_info: &AccountInfo<'a>,
+    acceptance_token_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    spl_token_program_info: &AccountInfo<'a>,
+    rent_sysvar_info: &AccountInfo<'a>,
+    rent: &Rent,
+    acceptance_token_signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            acceptance_token_info.key,
+            1.max(rent.minimum_balance(spl_token::state::Account::get_packed_len())),
+            spl_token::state::Account::get_packed_len() as u64,
+            &spl_token::id(),
+        ),
+        &[
+            funder_info.clone(),
+            acceptance_token_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[acceptance_token_signer_seeds],
+    )?;
+
+    msg!("Initializing acceptance token account");
+    invoke(
+        &spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            acceptance_token_info.key,
+            mint_info.key,
+            feature_proposal_info.key,
+        )?,
+        &[
+            acceptance_token_info.clone(),
+            spl_token_program_info.clone(),
+            rent_sysvar_info.clone(),
+            feature_proposal_info.clone(),
+            mint_info.clone(),
+        ],
+    )?;
+    invoke(
+        &spl_token::instruction::set_authority(
+            &spl_token::id(),
+            acceptance_token_info.key,
+            Some(&feature_proposal_info.key),
+            spl_token::instruction::AuthorityType::CloseAccount,
+            feature_proposal_info.key,
+            &[],
+        )?,
+        &[
+            spl_token_program_info.clone(),
+            acceptance_token_info.clone(),
+            feature_proposal_info.clone(),
+        ],
+    )?;
+    invoke(
+        &spl_token::instruction::set_authority(
+            &spl_token::id(),
+            acceptance_token_info.key,
+            Some(&program_id),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            feature_proposal_info.key,
+            &[],
+        )?,
+        &[
+            spl_token_program_info.clone(),
+            acceptance_token_info.clone(),
+            feature_proposal_info.clone(),
+        ],
+    )?;
+    Ok(())
+}
 
-            msg!("Initializing distributor token account");
-            invoke(
-                &spl_token::instruction::initialize_account(
-                    &spl_token::id(),
-                    distributor_token_info.key,
-                    mint_info.key,
-                    feature_proposal_info.key,
-                )?,
-                &[
-                    distributor_token_info.clone(),
-                    spl_token_program_info.clone(),
-                    rent_sysvar_info.clone(),
-                    feature_proposal_info.clone(),
-                    mint_info.clone(),
-                ],
-            )?;
+/// Processes `FeatureProposalInstruction::Tally`
+fn process_tally(accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let feature_proposal_info = next_account_info(account_info_iter)?;
+    let feature_proposal_state =
+        FeatureProposal::unpack_from_slice(&feature_proposal_info.data.borrow())?;
+
+    let (tokens_required, deadline, weighted_acceptance_criteria) = match feature_proposal_state {
+        FeatureProposal::Pending(acceptance_criteria) => (
+            acceptance_criteria.tokens_required,
+            acceptance_criteria.deadline,
+            None,
+        ),
+        FeatureProposal::PendingWeighted(acceptance_criteria) => (
+            acceptance_criteria.tokens_required,
+            acceptance_criteria.deadline,
+            Some(acceptance_criteria),
+        ),
+        _ => {
+            msg!("Error: feature proposal account not in the pending state");
+            return Err(ProgramError::InvalidAccountData);
+        }
+    };
+
+    let acceptance_token_info = next_account_info(account_info_iter)?;
+    let feature_id_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let clock_sysvar_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(clock_sysvar_info)?;
+
+    // Re-derive the acceptance token and feature id program addresses to confirm
+    // the caller provided the correct addresses
+    let acceptance_token_address = get_acceptance_token_address(feature_proposal_info.key);
+    if acceptance_token_address != *acceptance_token_info.key {
+        msg!("Error: acceptance token address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
 
-            msg!("Creating acceptance token account");
-            invoke_signed(
-                &system_instruction::create_account(
-                    funder_info.key,
-                    acceptance_token_info.key,
-                    1.max(rent.minimum_balance(spl_token::state::Account::get_packed_len())),
-                    spl_token::state::Account::get_packed_len() as u64,
-                    &spl_token::id(),
-                ),
-                &[
-                    funder_info.clone(),
-                    acceptance_token_info.clone(),
-                    system_program_info.clone(),
-                ],
-                &[&acceptance_token_signer_seeds],
-            )?;
+    let (feature_id_address, feature_id_bump_seed) =
+        get_feature_id_address_with_seed(feature_proposal_info.key);
+    if feature_id_address != *feature_id_info.key {
+        msg!("Error: feature-id address derivation mismatch");
+        return Err(ProgramError::InvalidArgument);
+    }
 
-            msg!("Initializing acceptance token account");
-            invoke(
-                &spl_token::instruction::initialize_account(
-                    &spl_token::id(),
-                    acceptance_token_info.key,
-                    mint_info.key,
-                    feature_proposal_info.key,
-                )?,
-                &[
-                    acceptance_token_info.clone(),
-                    spl_token_program_info.clone(),
-                    rent_sysvar_info.clone(),
-                    feature_proposal_info.clone(),
-                    mint_info.clone(),
-                ],
-            )?;
-            invoke(
-                &spl_token::instruction::set_authority(
-                    &spl_token::id(),
-                    acceptance_token_info.key,
-                    Some(&feature_proposal_info.key),
-                    spl_token::instruction::AuthorityType::CloseAccount,
-                    feature_proposal_info.key,
-                    &[],
-                )?,
-                &[
-                    spl_token_program_info.clone(),
-                    acceptance_token_info.clone(),
-                    feature_proposal_info.clone(),
-                ],
-            )?;
-            invoke(
-                &spl_token::instruction::set_authority(
-                    &spl_token::id(),
-                    acceptance_token_info.key,
-                    Some(&program_id),
-                    spl_token::instruction::AuthorityType::AccountOwner,
-                    feature_proposal_info.key,
-                    &[],
-                )?,
-                &[
-                    spl_token_program_info.clone(),
-                    acceptance_token_info.clone(),
-                    feature_proposal_info.clone(),
-                ],
-            )?;
+    let feature_id_signer_seeds: &[&[_]] = &[
+        &feature_proposal_info.key.to_bytes(),
+        br"feature-id",
+        &[feature_id_bump_seed],
+    ];
 
-            // Mint `tokens_to_mint` tokens into `distributor_token_account` owned by
-            // `feature_proposal`
-            msg!("Minting {} tokens", tokens_to_mint);
-            invoke_signed(
-                &spl_token::instruction::mint_to(
-                    &spl_token::id(),
-                    mint_info.key,
-                    distributor_token_info.key,
-                    mint_info.key,
-                    &[],
-                    tokens_to_mint,
-                )?,
-                &[
-                    mint_info.clone(),
-                    distributor_token_info.clone(),
-                    spl_token_program_info.clone(),
-                ],
-                &[&mint_signer_seeds],
-            )?;
+    if clock.unix_timestamp >= deadline {
+        msg!("Feature proposal expired");
+        FeatureProposal::Expired.pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
+        return Ok(());
+    }
 
-            // Fully fund the feature id account so the `Tally` instruction will not require any
-            // lamports from the caller
-            msg!("Funding feature id account");
-            invoke(
-                &system_instruction::transfer(
-                    funder_info.key,
-                    feature_id_info.key,
-                    1.max(rent.minimum_balance(Feature::size_of())),
-                ),
-                &[
-                    funder_info.clone(),
-                    feature_id_info.clone(),
-                    system_program_info.clone(),
-                ],
-            )?;
+    msg!("Unpacking acceptance token account");
+    let acceptance_token = spl_token::state::Account::unpack(&acceptance_token_info.data.borrow())?;
+
+    let tokens_received = match weighted_acceptance_criteria {
+        None => acceptance_token.amount,
+        Some(acceptance_criteria) => {
+            let mut tokens_received = acceptance_criteria
+                .proposal_token_weight
+                .saturating_mul(acceptance_token.amount);
+            for acceptance_mint in acceptance_criteria.active_mints() {
+                let mint_acceptance_token_info = next_account_info(account_info_iter)?;
+                let mint_acceptance_token_address = get_mint_acceptance_token_address(
+                    feature_proposal_info.key,
+                    &acceptance_mint.mint,
+                );
+                if mint_acceptance_token_address != *mint_acceptance_token_info.key {
+                    msg!("Error: mint acceptance token address derivation mismatch");
+                    return Err(ProgramError::InvalidArgument);
+                }
 
-            msg!("Allocating feature id account");
-            invoke_signed(
-                &system_instruction::allocate(feature_id_info.key, Feature::size_of() as u64),
-                &[feature_id_info.clone(), system_program_info.clone()],
-                &[&feature_id_signer_seeds],
-            )?;
+                let mint_acceptance_token =
+                    spl_token::state::Account::unpack(&mint_acceptance_token_info.data.borrow())?;
+                msg!(
+                    "Received {} tokens of mint {} with weight {}",
+                    mint_acceptance_token.amount,
+                    acceptance_mint.mint,
+                    acceptance_mint.weight
+                );
+                tokens_received = tokens_received.saturating_add(
+                    acceptance_mint
+                        .weight
+                        .saturating_mul(mint_acceptance_token.amount),
+                );
+            }
+            tokens_received
         }
+    };
+
+    msg!(
+        "Feature proposal has received {} tokens, and {} tokens required for acceptance",
+        tokens_received,
+        tokens_required
+    );
+    if tokens_received < tokens_required {
+        msg!("Activation threshold has not been reached");
+        return Ok(());
+    }
 
-        FeatureProposalInstruction::Tally => {
-            msg!("FeatureProposalInstruction::Tally");
+    msg!("Assigning feature id account");
+    invoke_signed(
+        &system_instruction::assign(feature_id_info.key, &feature::id()),
+        &[feature_id_info.clone(), system_program_info.clone()],
+        &[&feature_id_signer_seeds],
+    )?;
 
-            let feature_proposal_info = next_account_info(account_info_iter)?;
-            let feature_proposal_state =
-                FeatureProposal::unpack_from_slice(&feature_proposal_info.data.borrow())?;
-
-            match feature_proposal_state {
-                FeatureProposal::Pending(acceptance_criteria) => {
-                    let acceptance_token_info = next_account_info(account_info_iter)?;
-                    let feature_id_info = next_account_info(account_info_iter)?;
-                    let system_program_info = next_account_info(account_info_iter)?;
-                    let clock_sysvar_info = next_account_info(account_info_iter)?;
-                    let clock = &Clock::from_account_info(clock_sysvar_info)?;
-
-                    // Re-derive the acceptance token and feature id program addresses to confirm
-                    // the caller provided the correct addresses
-                    let acceptance_token_address =
-                        get_acceptance_token_address(feature_proposal_info.key);
-                    if acceptance_token_address != *acceptance_token_info.key {
-                        msg!("Error: acceptance token address derivation mismatch");
-                        return Err(ProgramError::InvalidArgument);
-                    }
-
-                    let (feature_id_address, feature_id_bump_seed) =
-                        get_feature_id_address_with_seed(feature_proposal_info.key);
-                    if feature_id_address != *feature_id_info.key {
-                        msg!("Error: feature-id address derivation mismatch");
-                        return Err(ProgramError::InvalidArgument);
-                    }
-
-                    let feature_id_signer_seeds: &[&[_]] = &[
-                        &feature_proposal_info.key.to_bytes(),
-                        br"feature-id",
-                        &[feature_id_bump_seed],
-                    ];
-
-                    if clock.unix_timestamp >= acceptance_criteria.deadline {
-                        msg!("Feature proposal expired");
-                        FeatureProposal::Expired
-                            .pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
-                        return Ok(());
-                    }
-
-                    msg!("Unpacking acceptance token account");
-                    let acceptance_token =
-                        spl_token::state::Account::unpack(&acceptance_token_info.data.borrow())?;
-
-                    msg!(
-                            "Feature proposal has received {} tokens, and {} tokens required for acceptance",
-                            acceptance_token.amount, acceptance_criteria.tokens_required
-                        );
-                    if acceptance_token.amount < acceptance_criteria.tokens_required {
-                        msg!("Activation threshold has not been reached");
-                        return Ok(());
-                    }
-
-                    msg!("Assigning feature id account");
-                    invoke_signed(
-                        &system_instruction::assign(feature_id_info.key, &feature::id()),
-                        &[feature_id_info.clone(), system_program_info.clone()],
-                        &[&feature_id_signer_seeds],
-                    )?;
-
-                    msg!("Feature proposal accepted");
-                    FeatureProposal::Accepted {
-                        tokens_upon_acceptance: acceptance_token.amount,
-                    }
-                    .pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
-                }
-                _ => {
-                    msg!("Error: feature proposal account not in the pending state");
-                    return Err(ProgramError::InvalidAccountData);
-                }
-            }
-        }
+    msg!("Feature proposal accepted");
+    FeatureProposal::Accepted {
+        tokens_upon_acceptance: tokens_received,
     }
+    .pack_into_slice(&mut feature_proposal_info.data.borrow_mut());
 
     Ok(())
 }
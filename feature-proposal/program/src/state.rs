@@ -5,8 +5,12 @@ use solana_program::{
     msg,
     program_error::ProgramError,
     program_pack::{Pack, Sealed},
+    pubkey::Pubkey,
 };
 
+/// Maximum number of additional mints that may count towards a weighted acceptance
+pub const MAX_ACCEPTANCE_MINTS: usize = 2;
+
 /// Criteria for accepting a feature proposal
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub struct AcceptanceCriteria {
@@ -18,6 +22,44 @@ pub struct AcceptanceCriteria {
     pub deadline: UnixTimestamp,
 }
 
+/// An additional SPL Token mint whose tokens count towards a weighted acceptance
+#[derive(Clone, Copy, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct AcceptanceMint {
+    /// Mint of the tokens accepted
+    pub mint: Pubkey,
+
+    /// Each token transferred to the mint's acceptance token account counts `weight` times.  A
+    /// weight of zero marks an unused slot.
+    pub weight: u64,
+}
+
+/// Criteria for accepting a feature proposal by a weighted tally over several mints
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct WeightedAcceptanceCriteria {
+    /// The weighted sum of the balances of all the feature proposal's acceptance token accounts
+    /// must be greater than this amount, and tallied before the deadline for the feature to be
+    /// accepted.
+    pub tokens_required: u64,
+
+    /// If the required tokens are not tallied by this deadline then the proposal will expire.
+    pub deadline: UnixTimestamp,
+
+    /// Weight of the tokens minted by the feature proposal itself
+    pub proposal_token_weight: u64,
+
+    /// Additional mints counted towards acceptance
+    pub acceptance_mints: [AcceptanceMint; MAX_ACCEPTANCE_MINTS],
+}
+
+impl WeightedAcceptanceCriteria {
+    /// Iterate over the additional mints in use
+    pub fn active_mints(&self) -> impl Iterator<Item = &AcceptanceMint> {
+        self.acceptance_mints
+            .iter()
+            .filter(|acceptance_mint| acceptance_mint.weight > 0)
+    }
+}
+
 /// Contents of a Feature Proposal account
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub enum FeatureProposal {
@@ -33,11 +75,13 @@ pub enum FeatureProposal {
     },
     /// Feature proposal was not accepted before the deadline
     Expired,
+    /// Feature proposal is now pending, and accepted by a weighted tally
+    PendingWeighted(WeightedAcceptanceCriteria),
 }
 impl Sealed for FeatureProposal {}
 
 impl Pack for FeatureProposal {
-    const LEN: usize = 17; // see `test_get_packed_len()` for justification of "18"
+    const LEN: usize = 105; // see `test_get_packed_len()` for justification of "105"
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let data = self.try_to_vec().unwrap();
@@ -84,6 +128,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_weighted_bytes() {
+        let data = FeatureProposal::PendingWeighted(WeightedAcceptanceCriteria {
+            tokens_required: 1,
+            deadline: 2,
+            proposal_token_weight: 3,
+            acceptance_mints: [
+                AcceptanceMint {
+                    mint: Pubkey::new(&[4; 32]),
+                    weight: 5,
+                },
+                AcceptanceMint::default(),
+            ],
+        })
+        .try_to_vec()
+        .unwrap();
+
+        assert_eq!(data.len(), FeatureProposal::get_packed_len());
+        assert_eq!(data[0], 4);
+        assert_eq!(
+            &data[1..25],
+            &[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(&data[25..57], &[4; 32]);
+        assert_eq!(&data[57..65], &[5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&data[65..], &[0; 40][..]);
+    }
+
+    #[test]
+    fn test_active_mints() {
+        let criteria = WeightedAcceptanceCriteria {
+            tokens_required: 1,
+            deadline: 2,
+            proposal_token_weight: 1,
+            acceptance_mints: [
+                AcceptanceMint::default(),
+                AcceptanceMint {
+                    mint: Pubkey::new_unique(),
+                    weight: 2,
+                },
+            ],
+        };
+        assert_eq!(
+            criteria.active_mints().collect::<Vec<_>>(),
+            vec![&criteria.acceptance_mints[1]]
+        );
+    }
+
     #[test]
     fn test_serialize_large_slice() {
         let mut dst = vec![0xff; 4];
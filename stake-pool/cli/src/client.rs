@@ -1,7 +1,6 @@
 use {
     bincode::deserialize,
-    borsh::BorshDeserialize,
-    solana_account_decoder::UiAccountEncoding,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
     solana_client::{
         client_error::ClientError,
         rpc_client::RpcClient,
@@ -10,22 +9,35 @@ use {
     },
     solana_program::{program_pack::Pack, pubkey::Pubkey},
     spl_stake_pool::{
-        borsh::try_from_slice_unchecked,
-        stake_program,
-        state::{StakePool, ValidatorList},
+        find_price_oracle_address, find_rate_history_address, stake_program,
+        state::{
+            AccountType, PriceOracle, RateHistory, StakePool, ValidatorList, ValidatorListHeader,
+            VersionedStakePool,
+        },
     },
+    std::{collections::HashMap, convert::TryInto},
 };
 
 type Error = Box<dyn std::error::Error>;
 
+/// Most accounts a single `getMultipleAccounts` request may ask for
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
 pub fn get_stake_pool(
     rpc_client: &RpcClient,
     stake_pool_address: &Pubkey,
 ) -> Result<StakePool, Error> {
     let account_data = rpc_client.get_account_data(stake_pool_address)?;
-    let stake_pool = StakePool::try_from_slice(account_data.as_slice())
+    let stake_pool = StakePool::deserialize_any_version(account_data.as_slice())
         .map_err(|err| format!("Invalid stake pool {}: {}", stake_pool_address, err))?;
-    Ok(stake_pool)
+    if let VersionedStakePool::Newer { version, .. } = &stake_pool {
+        eprintln!(
+            "Warning: stake pool {} was written by a newer program, with layout version {}; \
+             fields added since are not shown",
+            stake_pool_address, version
+        );
+    }
+    Ok(stake_pool.into_stake_pool())
 }
 
 pub fn get_validator_list(
@@ -33,11 +45,35 @@ pub fn get_validator_list(
     validator_list_address: &Pubkey,
 ) -> Result<ValidatorList, Error> {
     let account_data = rpc_client.get_account_data(validator_list_address)?;
-    let validator_list = try_from_slice_unchecked::<ValidatorList>(&account_data.as_slice())
+    let validator_list = ValidatorList::deserialize_checked(account_data.as_slice())
         .map_err(|err| format!("Invalid validator list {}: {}", validator_list_address, err))?;
     Ok(validator_list)
 }
 
+pub fn get_price_oracle(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<PriceOracle, Error> {
+    let (price_oracle_address, _) =
+        find_price_oracle_address(&spl_stake_pool::id(), stake_pool_address);
+    let account_data = rpc_client.get_account_data(&price_oracle_address)?;
+    let price_oracle = PriceOracle::deserialize_checked(account_data.as_slice())
+        .map_err(|err| format!("Invalid price oracle {}: {}", price_oracle_address, err))?;
+    Ok(price_oracle)
+}
+
+pub fn get_rate_history(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<RateHistory, Error> {
+    let (rate_history_address, _) =
+        find_rate_history_address(&spl_stake_pool::id(), stake_pool_address);
+    let account_data = rpc_client.get_account_data(&rate_history_address)?;
+    let rate_history = RateHistory::deserialize_checked(account_data.as_slice())
+        .map_err(|err| format!("Invalid rate history {}: {}", rate_history_address, err))?;
+    Ok(rate_history)
+}
+
 pub fn get_token_account(
     rpc_client: &RpcClient,
     token_account_address: &Pubkey,
@@ -82,14 +118,31 @@ pub(crate) fn get_stake_state(
 pub(crate) fn get_stake_accounts_by_withdraw_authority(
     rpc_client: &RpcClient,
     withdraw_authority: &Pubkey,
+) -> Result<Vec<(Pubkey, u64, stake_program::StakeState)>, ClientError> {
+    // 44 is Withdrawer authority offset in stake account stake
+    get_stake_accounts_by_authority(rpc_client, 44, withdraw_authority)
+}
+
+pub(crate) fn get_stake_accounts_by_staker_authority(
+    rpc_client: &RpcClient,
+    staker_authority: &Pubkey,
+) -> Result<Vec<(Pubkey, u64, stake_program::StakeState)>, ClientError> {
+    // 12 is Staker authority offset in stake account stake
+    get_stake_accounts_by_authority(rpc_client, 12, staker_authority)
+}
+
+fn get_stake_accounts_by_authority(
+    rpc_client: &RpcClient,
+    offset: usize,
+    authority: &Pubkey,
 ) -> Result<Vec<(Pubkey, u64, stake_program::StakeState)>, ClientError> {
     rpc_client
         .get_program_accounts_with_config(
             &stake_program::id(),
             RpcProgramAccountsConfig {
                 filters: Some(vec![RpcFilterType::Memcmp(Memcmp {
-                    offset: 44, // 44 is Withdrawer authority offset in stake account stake
-                    bytes: MemcmpEncodedBytes::Binary(format!("{}", withdraw_authority)),
+                    offset,
+                    bytes: MemcmpEncodedBytes::Binary(format!("{}", authority)),
                     encoding: None,
                 })]),
                 account_config: RpcAccountInfoConfig {
@@ -113,3 +166,104 @@ pub(crate) fn get_stake_accounts_by_withdraw_authority(
                 .collect()
         })
 }
+
+/// Filter on the account type each account of the stake pool program starts
+/// with.  Stake pools written by later versions of the program may be longer,
+/// so there is no filter on the data size.
+fn account_type_filter(account_type: AccountType) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp {
+        offset: 0,
+        bytes: MemcmpEncodedBytes::Binary(bs58::encode([account_type as u8]).into_string()),
+        encoding: None,
+    })
+}
+
+/// Every stake pool of the program, skipping accounts that can't be read
+pub(crate) fn get_all_stake_pools(
+    rpc_client: &RpcClient,
+) -> Result<Vec<(Pubkey, StakePool)>, ClientError> {
+    rpc_client
+        .get_program_accounts_with_config(
+            &spl_stake_pool::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![account_type_filter(AccountType::StakePool)]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+            },
+        )
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .filter_map(|(address, account)| {
+                    match StakePool::deserialize_any_version(account.data.as_slice()) {
+                        Ok(stake_pool) => Some((address, stake_pool.into_stake_pool())),
+                        Err(err) => {
+                            eprintln!("Invalid stake pool data for {}: {}", address, err);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+}
+
+/// Number of validators in every validator list of the program, reading only
+/// the header and entry count of each list
+pub(crate) fn get_all_validator_counts(
+    rpc_client: &RpcClient,
+) -> Result<HashMap<Pubkey, u32>, ClientError> {
+    let count_end = ValidatorListHeader::LEN + 4;
+    rpc_client
+        .get_program_accounts_with_config(
+            &spl_stake_pool::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![account_type_filter(AccountType::ValidatorList)]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: count_end,
+                    }),
+                    ..RpcAccountInfoConfig::default()
+                },
+            },
+        )
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .filter_map(|(address, account)| {
+                    let count = account
+                        .data
+                        .get(ValidatorListHeader::LEN..count_end)
+                        .and_then(|bytes| bytes.try_into().ok())
+                        .map(u32::from_le_bytes);
+                    if count.is_none() {
+                        eprintln!("Invalid validator list data for {}", address);
+                    }
+                    count.map(|count| (address, count))
+                })
+                .collect()
+        })
+}
+
+/// Token mints by address, in batches of the most accounts an RPC request
+/// may ask for.  Mints that don't exist or can't be read are left out.
+pub(crate) fn get_token_mints(
+    rpc_client: &RpcClient,
+    token_mint_addresses: &[Pubkey],
+) -> Result<HashMap<Pubkey, spl_token::state::Mint>, ClientError> {
+    let mut token_mints = HashMap::new();
+    for addresses in token_mint_addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let accounts = rpc_client.get_multiple_accounts(addresses)?;
+        for (address, account) in addresses.iter().zip(accounts) {
+            if let Some(token_mint) = account.and_then(|account| {
+                spl_token::state::Mint::unpack_from_slice(account.data.as_slice()).ok()
+            }) {
+                token_mints.insert(*address, token_mint);
+            }
+        }
+    }
+    Ok(token_mints)
+}
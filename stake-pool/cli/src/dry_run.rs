@@ -0,0 +1,231 @@
+//! Dry runs: what simulated transactions would do to a stake pool, read from
+//! the events the program logs
+
+use {
+    solana_program::pubkey::Pubkey,
+    spl_stake_pool::{
+        event::StakePoolEvent,
+        state::{RateHistoryEntry, StakePool},
+    },
+};
+
+/// Prefix of the runtime's log lines announcing a program invocation
+const INVOKE_LOG_PREFIX: &str = "Program ";
+
+/// Compute units consumed by a transaction, added up over its top-level
+/// instructions, since the units of inner instructions are counted in the
+/// instruction invoking them
+pub fn compute_units_consumed(logs: &[String]) -> u64 {
+    let mut depth = 0;
+    let mut compute_units = 0;
+    for log in logs {
+        let words: Vec<&str> = match log.strip_prefix(INVOKE_LOG_PREFIX) {
+            Some(rest) => rest.split_whitespace().collect(),
+            None => continue,
+        };
+        // messages logged by programs, rather than by the runtime
+        if words.first().map_or(true, |word| word.ends_with(':')) {
+            continue;
+        }
+        match words.as_slice() {
+            [_, "invoke", level] => {
+                depth = level
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .parse()
+                    .unwrap_or(depth + 1);
+            }
+            [_, "consumed", units, "of", _, "compute", "units"] if depth == 1 => {
+                compute_units += units.parse::<u64>().unwrap_or(0);
+            }
+            [_, "success"] | [_, "failed:", ..] => {
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+    compute_units
+}
+
+/// Totals of the simulated transactions of one command
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DryRun {
+    pub transactions: usize,
+    pub compute_units: u64,
+    /// Lamports added to the pool by deposits
+    pub deposited_lamports: u64,
+    /// Lamports taken out of the pool by withdrawals
+    pub withdrawn_lamports: u64,
+    /// Pool tokens minted to depositors
+    pub minted_pool_tokens: u64,
+    /// Pool tokens burned by withdrawals
+    pub burned_pool_tokens: u64,
+    /// Pool tokens minted to the manager as deposit fees
+    pub deposit_fee_pool_tokens: u64,
+    /// Pool tokens transferred to the manager as withdrawal fees
+    pub withdrawal_fee_pool_tokens: u64,
+    /// Lamports moved from the reserve to validators
+    pub increased_lamports: u64,
+    /// Lamports moved from validators back to the reserve
+    pub decreased_lamports: u64,
+}
+
+impl DryRun {
+    /// Add up the events the transaction logged for the pool
+    pub fn record(&mut self, stake_pool_address: &Pubkey, logs: &[String]) {
+        self.transactions += 1;
+        self.compute_units += compute_units_consumed(logs);
+        for event in logs.iter().filter_map(|log| StakePoolEvent::from_log(log)) {
+            match event {
+                StakePoolEvent::Deposit(event) if event.stake_pool == *stake_pool_address => {
+                    self.deposited_lamports += event.lamports;
+                    self.minted_pool_tokens += event.pool_tokens;
+                    self.deposit_fee_pool_tokens += event.fee_pool_tokens;
+                }
+                StakePoolEvent::Withdraw(event) if event.stake_pool == *stake_pool_address => {
+                    self.withdrawn_lamports += event.lamports;
+                    self.burned_pool_tokens += event.pool_tokens;
+                    self.withdrawal_fee_pool_tokens += event.fee_pool_tokens;
+                }
+                StakePoolEvent::ValidatorStakeIncreased(event)
+                    if event.stake_pool == *stake_pool_address =>
+                {
+                    self.increased_lamports += event.lamports;
+                }
+                StakePoolEvent::ValidatorStakeDecreased(event)
+                    if event.stake_pool == *stake_pool_address =>
+                {
+                    self.decreased_lamports += event.lamports;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Balances of the pool once the transactions went through.  Deposit
+    /// fees are minted on top of the depositor's pool tokens, withdrawal fees
+    /// are transferred to the manager rather than burned.
+    pub fn resulting_balances(&self, stake_pool: &StakePool) -> RateHistoryEntry {
+        RateHistoryEntry {
+            epoch: stake_pool.last_update_epoch,
+            total_stake_lamports: (stake_pool.total_stake_lamports + self.deposited_lamports)
+                .saturating_sub(self.withdrawn_lamports),
+            pool_token_supply: (stake_pool.pool_token_supply
+                + self.minted_pool_tokens
+                + self.deposit_fee_pool_tokens)
+                .saturating_sub(self.burned_pool_tokens),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        borsh::BorshSerialize,
+        spl_stake_pool::event::{
+            DepositEvent, ValidatorStakeEvent, WithdrawEvent, EVENT_LOG_PREFIX,
+        },
+    };
+
+    fn event_log(event: StakePoolEvent) -> String {
+        format!(
+            "Program log: {}{}",
+            EVENT_LOG_PREFIX,
+            base64::encode(event.try_to_vec().unwrap())
+        )
+    }
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn compute_units_of_top_level_instructions() {
+        let logs = logs(&[
+            "Program SPoo1 invoke [1]",
+            "Program Stake11 invoke [2]",
+            "Program Stake11 success",
+            "Program Tokenkeg invoke [2]",
+            "Program Tokenkeg consumed 2400 of 180000 compute units",
+            "Program Tokenkeg success",
+            "Program SPoo1 consumed 31000 of 200000 compute units",
+            "Program SPoo1 success",
+            "Program SPoo1 invoke [1]",
+            "Program log: success",
+            "Program SPoo1 consumed 5000 of 200000 compute units",
+            "Program SPoo1 failed: custom program error: 0x14",
+        ]);
+        assert_eq!(compute_units_consumed(&logs), 36_000);
+        assert_eq!(compute_units_consumed(&[]), 0);
+    }
+
+    #[test]
+    fn record_events_of_the_pool() {
+        let stake_pool_address = Pubkey::new_unique();
+        let other_pool = Pubkey::new_unique();
+        let deposit = |stake_pool| {
+            event_log(StakePoolEvent::Deposit(DepositEvent {
+                stake_pool,
+                vote_account: None,
+                pool_tokens_destination: Pubkey::new_unique(),
+                lamports: 1_000,
+                pool_tokens: 495,
+                fee_pool_tokens: 5,
+            }))
+        };
+        let withdraw = event_log(StakePoolEvent::Withdraw(WithdrawEvent {
+            stake_pool: stake_pool_address,
+            vote_account: None,
+            pool_tokens_source: Pubkey::new_unique(),
+            lamports: 400,
+            pool_tokens: 198,
+            fee_pool_tokens: 2,
+        }));
+        let increase = event_log(StakePoolEvent::ValidatorStakeIncreased(
+            ValidatorStakeEvent {
+                stake_pool: stake_pool_address,
+                vote_account: Pubkey::new_unique(),
+                transient_stake_account: Pubkey::new_unique(),
+                lamports: 7_000,
+            },
+        ));
+
+        let mut dry_run = DryRun::default();
+        dry_run.record(
+            &stake_pool_address,
+            &[
+                "Program SPoo1 invoke [1]".to_string(),
+                deposit(stake_pool_address),
+                deposit(other_pool),
+                withdraw,
+                "Program SPoo1 consumed 30000 of 200000 compute units".to_string(),
+                "Program SPoo1 success".to_string(),
+            ],
+        );
+        dry_run.record(&stake_pool_address, &[increase]);
+        assert_eq!(
+            dry_run,
+            DryRun {
+                transactions: 2,
+                compute_units: 30_000,
+                deposited_lamports: 1_000,
+                withdrawn_lamports: 400,
+                minted_pool_tokens: 495,
+                burned_pool_tokens: 198,
+                deposit_fee_pool_tokens: 5,
+                withdrawal_fee_pool_tokens: 2,
+                increased_lamports: 7_000,
+                decreased_lamports: 0,
+            }
+        );
+
+        let stake_pool = StakePool {
+            total_stake_lamports: 10_000,
+            pool_token_supply: 5_000,
+            ..StakePool::default()
+        };
+        let balances = dry_run.resulting_balances(&stake_pool);
+        assert_eq!(balances.total_stake_lamports, 10_600);
+        assert_eq!(balances.pool_token_supply, 5_302);
+    }
+}
@@ -2,38 +2,68 @@
 extern crate lazy_static;
 
 mod client;
+mod dry_run;
+mod monitor;
+mod output;
+mod packer;
+mod rebalance;
+mod selection;
+mod update;
 
 use {
-    crate::client::*,
+    crate::{
+        client::*,
+        dry_run::DryRun,
+        monitor::{post_alert, Monitor, PoolSnapshot},
+        output::OutputFormat,
+        packer::TransactionPacker,
+        rebalance::{plan_rebalance, SelectionStrategy, StakeMove, Strategy},
+        selection::{EqualWeight, ProportionalToScore, StakeConcentrationAware, ValidatorScores},
+        update::{plan_update, validators_per_update, MAX_INSTRUCTION_COMPUTE_UNITS},
+    },
     clap::{
         crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings,
         Arg, ArgGroup, SubCommand,
     },
     solana_clap_utils::{
         input_parsers::pubkey_of,
-        input_validators::{is_amount, is_keypair, is_parsable, is_pubkey, is_url},
+        input_validators::{is_amount, is_parsable, is_pubkey, is_url, is_valid_signer},
         keypair::signer_from_path,
     },
     solana_client::{rpc_client::RpcClient, rpc_response::StakeActivationState},
     solana_program::{
-        borsh::get_packed_len, instruction::Instruction, program_pack::Pack, pubkey::Pubkey,
+        borsh::get_packed_len, instruction::Instruction, message::Message, program_pack::Pack,
+        pubkey::Pubkey,
     },
+    solana_remote_wallet::remote_wallet::RemoteWalletManager,
     solana_sdk::{
+        clock::DEFAULT_MS_PER_SLOT,
         commitment_config::CommitmentConfig,
         native_token::{self, Sol},
-        signature::{Keypair, Signer},
+        packet::PACKET_DATA_SIZE,
+        signature::{Keypair, Signature, Signer},
         system_instruction,
         transaction::Transaction,
     },
+    spl_associated_token_account::{create_associated_token_account, get_associated_token_address},
     spl_stake_pool::{
         self,
         borsh::get_instance_packed_len,
-        find_deposit_authority_program_address, find_stake_program_address,
+        find_deposit_authority_program_address, find_deposit_record_address,
+        find_single_validator_pool_address, find_single_validator_pool_mint_address,
+        find_stake_program_address, find_transient_stake_program_address,
         find_withdraw_authority_program_address,
+        inline_mpl_token_metadata::state::{MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH},
         stake_program::{self, StakeAuthorize, StakeState},
-        state::{StakePool, ValidatorList},
+        state::{
+            FeeOperation, Freshness, ManagerAction, RateHistory, SingleValidatorPool, StakePool,
+            StakeStatus, ValidatorList, PRICE_ORACLE_RATE_PRECISION,
+        },
+        MINIMUM_ACTIVE_STAKE,
+    },
+    std::{
+        convert::TryFrom, process::exit, str::FromStr, sync::Arc, thread::sleep, time::Duration,
     },
-    std::process::exit,
 };
 
 struct Config {
@@ -43,19 +73,35 @@ struct Config {
     staker: Box<dyn Signer>,
     token_owner: Box<dyn Signer>,
     fee_payer: Box<dyn Signer>,
+    depositor: Option<Box<dyn Signer>>,
+    vote_withdrawer: Option<Box<dyn Signer>>,
     dry_run: bool,
     no_update: bool,
+    output_format: OutputFormat,
 }
 
 type Error = Box<dyn std::error::Error>;
 type CommandResult = Result<(), Error>;
 
 const STAKE_STATE_LEN: usize = 200;
-const MAX_ACCOUNTS_TO_UPDATE: usize = 10;
+/// Attempts at sending a transaction that did not land, each with a fresh
+/// blockhash
+const MAX_SEND_ATTEMPTS: usize = 3;
 lazy_static! {
     static ref MIN_STAKE_BALANCE: u64 = native_token::sol_to_lamports(1.0);
 }
 
+/// Progress messages go to stderr when stdout is reserved for JSON output
+macro_rules! progress {
+    ($config:expr, $($arg:tt)*) => {
+        if $config.output_format.is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 macro_rules! unique_signers {
     ($vec:ident) => {
         $vec.sort_by_key(|l| l.pubkey());
@@ -81,21 +127,156 @@ fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(),
 fn send_transaction(
     config: &Config,
     transaction: Transaction,
-) -> solana_client::client_error::Result<()> {
+) -> solana_client::client_error::Result<Signature> {
     if config.dry_run {
         let result = config.rpc_client.simulate_transaction(&transaction)?;
-        println!("Simulate result: {:?}", result);
+        progress!(config, "Simulate result: {:?}", result);
+        Ok(transaction.signatures[0])
     } else {
         let signature = config
             .rpc_client
             .send_and_confirm_transaction_with_spinner(&transaction)?;
-        println!("Signature: {}", signature);
+        progress!(config, "Signature: {}", signature);
+        Ok(signature)
+    }
+}
+
+/// Sign a message with the fee payer alone and send it, with a fresh
+/// blockhash on every attempt.  Only transactions that didn't land are sent
+/// again, a transaction the program rejects fails right away.
+fn send_message_with_retry(config: &Config, message: Message) -> Result<Signature, Error> {
+    let mut attempt = 1;
+    loop {
+        let mut transaction = Transaction::new_unsigned(message.clone());
+        let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+        check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+        transaction.try_sign(&[config.fee_payer.as_ref()], recent_blockhash)?;
+        match send_transaction(config, transaction) {
+            Ok(signature) => return Ok(signature),
+            Err(err)
+                if attempt < MAX_SEND_ATTEMPTS && err.kind().get_transaction_error().is_none() =>
+            {
+                progress!(
+                    config,
+                    "Transaction not confirmed, retrying ({}/{}): {}",
+                    attempt,
+                    MAX_SEND_ATTEMPTS - 1,
+                    err
+                );
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Simulate the transaction of a dry run, failing with the program logs if
+/// it would fail
+fn simulate_transaction(config: &Config, transaction: &Transaction) -> Result<Vec<String>, Error> {
+    let result = config.rpc_client.simulate_transaction(transaction)?.value;
+    let logs = result.logs.unwrap_or_default();
+    if let Some(err) = result.err {
+        for log in &logs {
+            progress!(config, "  {}", log);
+        }
+        return Err(format!("Transaction simulation failed: {}", err).into());
+    }
+    Ok(logs)
+}
+
+/// Report what the simulated transactions of a dry run would do to the pool
+fn print_dry_run(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    dry_run: &DryRun,
+) -> CommandResult {
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let pool_tokens = |amount| spl_token::amount_to_ui_amount(amount, pool_mint.decimals);
+    let resulting_rate = dry_run.resulting_balances(stake_pool).rate();
+
+    if config.output_format.is_json() {
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "transactions": dry_run.transactions,
+                "computeUnits": dry_run.compute_units,
+                "depositedLamports": dry_run.deposited_lamports,
+                "withdrawnLamports": dry_run.withdrawn_lamports,
+                "mintedPoolTokens": dry_run.minted_pool_tokens,
+                "burnedPoolTokens": dry_run.burned_pool_tokens,
+                "depositFeePoolTokens": dry_run.deposit_fee_pool_tokens,
+                "withdrawalFeePoolTokens": dry_run.withdrawal_fee_pool_tokens,
+                "increasedLamports": dry_run.increased_lamports,
+                "decreasedLamports": dry_run.decreased_lamports,
+                "stakeDepositFee": fee_json(&stake_pool.stake_deposit_fee),
+                "withdrawalFee": fee_json(&stake_pool.withdrawal_fee),
+                "rate": rate_json(stake_pool),
+                "resultingRate": {
+                    "lamports": resulting_rate,
+                    "poolTokens": PRICE_ORACLE_RATE_PRECISION,
+                },
+            }),
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Dry run: simulated {} transaction(s), nothing was sent",
+        dry_run.transactions
+    );
+    println!("Compute Units: {}", dry_run.compute_units);
+    if dry_run.deposited_lamports > 0 {
+        println!("Deposited: {}", Sol(dry_run.deposited_lamports));
+        println!(
+            "Pool Tokens Minted: {}",
+            pool_tokens(dry_run.minted_pool_tokens)
+        );
+        println!(
+            "Deposit Fee: {} pool tokens, at {}/{}",
+            pool_tokens(dry_run.deposit_fee_pool_tokens),
+            stake_pool.stake_deposit_fee.numerator,
+            stake_pool.stake_deposit_fee.denominator
+        );
+    }
+    if dry_run.withdrawn_lamports > 0 {
+        println!("Withdrawn: {}", Sol(dry_run.withdrawn_lamports));
+        println!(
+            "Pool Tokens Burned: {}",
+            pool_tokens(dry_run.burned_pool_tokens)
+        );
+        println!(
+            "Withdrawal Fee: {} pool tokens, at {}/{}",
+            pool_tokens(dry_run.withdrawal_fee_pool_tokens),
+            stake_pool.withdrawal_fee.numerator,
+            stake_pool.withdrawal_fee.denominator
+        );
+    }
+    if dry_run.increased_lamports > 0 {
+        println!("Moved to Validators: {}", Sol(dry_run.increased_lamports));
+    }
+    if dry_run.decreased_lamports > 0 {
+        println!("Moved to Reserve: {}", Sol(dry_run.decreased_lamports));
+    }
+    if let (Some(rate), Some(resulting_rate)) = (stake_pool.price_oracle_rate(), resulting_rate) {
+        println!(
+            "Exchange Rate: {} per {} pool tokens, {} after",
+            Sol(rate),
+            pool_tokens(PRICE_ORACLE_RATE_PRECISION),
+            Sol(resulting_rate)
+        );
     }
     Ok(())
 }
 
+fn print_json(config: &Config, value: &serde_json::Value) {
+    println!("{}", config.output_format.formatted_string(value));
+}
+
 fn command_create_pool(
     config: &Config,
+    deposit_authority: Option<Pubkey>,
     fee: spl_stake_pool::instruction::Fee,
     max_validators: u32,
 ) -> CommandResult {
@@ -113,6 +294,9 @@ fn command_create_pool(
 
     let validator_list = Keypair::new();
 
+    let reserve_stake = Keypair::new();
+    println!("Creating reserve stake {}", reserve_stake.pubkey());
+
     let mint_account_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
@@ -127,10 +311,14 @@ fn command_create_pool(
     let validator_list_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(validator_list_size)?;
+    let reserve_stake_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
     let total_rent_free_balances = mint_account_balance
         + pool_fee_account_balance
         + stake_pool_account_lamports
-        + validator_list_balance;
+        + validator_list_balance
+        + reserve_stake_balance;
 
     let default_decimals = spl_token::native_mint::DECIMALS;
 
@@ -144,88 +332,119 @@ fn command_create_pool(
         println!("Stake pool withdraw authority {}", withdraw_authority);
     }
 
-    let mut transaction = Transaction::new_with_payer(
-        &[
-            // Account for the stake pool mint
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &mint_account.pubkey(),
-                mint_account_balance,
-                spl_token::state::Mint::LEN as u64,
-                &spl_token::id(),
-            ),
-            // Account for the pool fee accumulation
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &pool_fee_account.pubkey(),
-                pool_fee_account_balance,
-                spl_token::state::Account::LEN as u64,
-                &spl_token::id(),
-            ),
-            // Account for the stake pool
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &stake_pool_keypair.pubkey(),
-                stake_pool_account_lamports,
-                get_packed_len::<StakePool>() as u64,
-                &spl_stake_pool::id(),
-            ),
-            // Validator stake account list storage
-            system_instruction::create_account(
-                &config.fee_payer.pubkey(),
-                &validator_list.pubkey(),
-                validator_list_balance,
-                validator_list_size as u64,
-                &spl_stake_pool::id(),
-            ),
-            // Initialize pool token mint account
-            spl_token::instruction::initialize_mint(
-                &spl_token::id(),
-                &mint_account.pubkey(),
-                &withdraw_authority,
-                None,
-                default_decimals,
-            )?,
-            // Initialize fee receiver account
-            spl_token::instruction::initialize_account(
-                &spl_token::id(),
-                &pool_fee_account.pubkey(),
-                &mint_account.pubkey(),
-                &config.manager.pubkey(),
-            )?,
-            // Initialize stake pool account
-            spl_stake_pool::instruction::initialize(
-                &spl_stake_pool::id(),
-                &stake_pool_keypair.pubkey(),
-                &config.manager.pubkey(),
-                &config.staker.pubkey(),
-                &validator_list.pubkey(),
-                &mint_account.pubkey(),
-                &pool_fee_account.pubkey(),
-                &spl_token::id(),
-                fee,
-                max_validators,
-            )?,
-        ],
-        Some(&config.fee_payer.pubkey()),
+    // Reserve stake account, owned by the pool withdraw authority
+    let mut setup_instructions = stake_program::create_account(
+        &config.fee_payer.pubkey(),
+        &reserve_stake.pubkey(),
+        &stake_program::Authorized {
+            staker: withdraw_authority,
+            withdrawer: withdraw_authority,
+        },
+        &stake_program::Lockup::default(),
+        reserve_stake_balance,
     );
+    setup_instructions.extend(vec![
+        // Account for the stake pool mint
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &mint_account.pubkey(),
+            mint_account_balance,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        // Account for the pool fee accumulation
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &pool_fee_account.pubkey(),
+            pool_fee_account_balance,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        // Initialize pool token mint account
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint_account.pubkey(),
+            &withdraw_authority,
+            None,
+            default_decimals,
+        )?,
+        // Initialize fee receiver account
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &pool_fee_account.pubkey(),
+            &mint_account.pubkey(),
+            &config.manager.pubkey(),
+        )?,
+    ]);
+
+    // The manager only signs the second transaction, keeping the message
+    // small enough to be reviewed on a hardware wallet
+    let initialize_instructions = vec![
+        // Account for the stake pool
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &stake_pool_keypair.pubkey(),
+            stake_pool_account_lamports,
+            get_packed_len::<StakePool>() as u64,
+            &spl_stake_pool::id(),
+        ),
+        // Validator stake account list storage
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &validator_list.pubkey(),
+            validator_list_balance,
+            validator_list_size as u64,
+            &spl_stake_pool::id(),
+        ),
+        // Initialize stake pool account
+        spl_stake_pool::instruction::initialize(
+            &spl_stake_pool::id(),
+            &stake_pool_keypair.pubkey(),
+            &config.manager.pubkey(),
+            &config.staker.pubkey(),
+            &validator_list.pubkey(),
+            &reserve_stake.pubkey(),
+            &mint_account.pubkey(),
+            &pool_fee_account.pubkey(),
+            &spl_token::id(),
+            deposit_authority,
+            fee,
+            max_validators,
+        )?,
+    ];
+
+    let mut setup_transaction =
+        Transaction::new_with_payer(&setup_instructions, Some(&config.fee_payer.pubkey()));
+    let mut initialize_transaction =
+        Transaction::new_with_payer(&initialize_instructions, Some(&config.fee_payer.pubkey()));
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(
         config,
-        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+        total_rent_free_balances
+            + fee_calculator.calculate_fee(&setup_transaction.message())
+            + fee_calculator.calculate_fee(&initialize_transaction.message()),
     )?;
-    let mut signers = vec![
+
+    let mut setup_signers = vec![
         config.fee_payer.as_ref(),
-        &stake_pool_keypair,
-        &validator_list,
+        &reserve_stake,
         &mint_account,
         &pool_fee_account,
+    ];
+    unique_signers!(setup_signers);
+    setup_transaction.try_sign(&setup_signers, recent_blockhash)?;
+    send_transaction(&config, setup_transaction)?;
+
+    let mut initialize_signers = vec![
+        config.fee_payer.as_ref(),
+        &stake_pool_keypair,
+        &validator_list,
         config.manager.as_ref(),
     ];
-    unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
-    send_transaction(&config, transaction)?;
+    unique_signers!(initialize_signers);
+    initialize_transaction.try_sign(&initialize_signers, recent_blockhash)?;
+    send_transaction(&config, initialize_transaction)?;
     Ok(())
 }
 
@@ -256,10 +475,10 @@ fn command_vsa_create(
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
-    transaction.sign(
+    transaction.try_sign(
         &[config.fee_payer.as_ref(), config.staker.as_ref()],
         recent_blockhash,
-    );
+    )?;
     send_transaction(&config, transaction)?;
     Ok(())
 }
@@ -275,7 +494,7 @@ fn command_vsa_add(
     }
 
     if !config.no_update {
-        command_update(config, stake_pool_address)?;
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
@@ -346,553 +565,578 @@ fn command_vsa_add(
         total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
     )?;
     unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
+    transaction.try_sign(&signers, recent_blockhash)?;
     send_transaction(&config, transaction)?;
     Ok(())
 }
 
+/// Remove a validator on behalf of the staker, or of the validator itself if
+/// signed by the withdraw authority of its vote account
 fn command_vsa_remove(
     config: &Config,
     stake_pool_address: &Pubkey,
     stake: &Pubkey,
-    withdraw_from: &Pubkey,
-    new_authority: &Option<Pubkey>,
+    vote_withdrawer: Option<&dyn Signer>,
 ) -> CommandResult {
     if !config.no_update {
-        command_update(config, stake_pool_address)?;
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
     let pool_withdraw_authority =
         find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
 
-    let staker_pubkey = config.staker.pubkey();
-    let new_authority = new_authority.as_ref().unwrap_or(&staker_pubkey);
-
-    // Calculate amount of tokens to withdraw
     let stake_account = config.rpc_client.get_account(&stake)?;
-    let tokens_to_withdraw = stake_pool
-        .calc_pool_tokens_for_withdraw(stake_account.lamports)
-        .unwrap();
-
-    // Check balance and mint
-    let token_account =
-        get_token_account(&config.rpc_client, &withdraw_from, &stake_pool.pool_mint)?;
-
-    if token_account.amount < tokens_to_withdraw {
-        let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
-        return Err(format!(
-            "Not enough balance to burn to remove validator stake account from the pool. {} pool tokens needed.",
-            spl_token::amount_to_ui_amount(tokens_to_withdraw, pool_mint.decimals)
-        ).into());
-    }
+    println!(
+        "Deactivating stake account {}, {} will return to the reserve on the next update after deactivation",
+        stake,
+        Sol(stake_account.lamports)
+    );
 
-    let mut transaction = Transaction::new_with_payer(
-        &[
-            // Approve spending token
-            spl_token::instruction::approve(
-                &spl_token::id(),
-                &withdraw_from,
+    let vote_account = match get_stake_state(&config.rpc_client, &stake)? {
+        StakeState::Stake(_, stake) => stake.delegation.voter_pubkey,
+        _ => return Err("Wrong stake account state, must be delegated to validator".into()),
+    };
+    let (mut instruction, authority) = match vote_withdrawer {
+        Some(vote_withdrawer) => (
+            spl_stake_pool::instruction::remove_validator_by_vote_withdrawer(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &vote_withdrawer.pubkey(),
                 &pool_withdraw_authority,
-                &config.token_owner.pubkey(),
-                &[],
-                tokens_to_withdraw,
+                &stake_pool.validator_list,
+                &stake,
+                &vote_account,
             )?,
-            // Create new validator stake account address
+            vote_withdrawer,
+        ),
+        None => (
             spl_stake_pool::instruction::remove_validator_from_pool(
                 &spl_stake_pool::id(),
                 &stake_pool_address,
                 &config.staker.pubkey(),
                 &pool_withdraw_authority,
-                &new_authority,
                 &stake_pool.validator_list,
                 &stake,
-                &withdraw_from,
-                &stake_pool.pool_mint,
-                &spl_token::id(),
             )?,
-        ],
-        Some(&config.fee_payer.pubkey()),
-    );
+            config.staker.as_ref(),
+        ),
+    };
+
+    // Any stake still in the transient account is deactivated along with it
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    if let Some(validator) = validator_list.find(&vote_account) {
+        if u64::from(validator.transient_stake_lamports) > 0 {
+            let (transient_stake_address, _) = find_transient_stake_program_address(
+                &spl_stake_pool::id(),
+                &vote_account,
+                stake_pool_address,
+            );
+            println!(
+                "Deactivating transient stake account {}, {} will return to the reserve after deactivation",
+                transient_stake_address,
+                Sol(validator.transient_stake_lamports.into())
+            );
+            spl_stake_pool::instruction::add_transient_stake_account(
+                &mut instruction,
+                &transient_stake_address,
+            );
+        }
+    }
+
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&config.fee_payer.pubkey()));
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
-    transaction.sign(
-        &[config.fee_payer.as_ref(), config.staker.as_ref()],
-        recent_blockhash,
-    );
+    transaction.try_sign(&[config.fee_payer.as_ref(), authority], recent_blockhash)?;
     send_transaction(&config, transaction)?;
     Ok(())
 }
 
-fn unwrap_create_token_account<F>(
+fn command_leave_pool(
     config: &Config,
-    token_optional: &Option<Pubkey>,
-    keypair: &Keypair,
-    mint: &Pubkey,
-    instructions: &mut Vec<Instruction>,
-    handler: F,
-) -> Result<Pubkey, Error>
-where
-    F: FnOnce(u64),
-{
-    let result = match token_optional {
-        Some(value) => *value,
-        None => {
-            // Account for tokens not specified, creating one
-            println!("Creating account to receive tokens {}", keypair.pubkey());
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+) -> CommandResult {
+    let (stake_account, _) =
+        find_stake_program_address(&spl_stake_pool::id(), vote_account, stake_pool_address);
+    let vote_withdrawer = config
+        .vote_withdrawer
+        .as_ref()
+        .unwrap_or(&config.fee_payer)
+        .as_ref();
+    command_vsa_remove(
+        config,
+        stake_pool_address,
+        &stake_account,
+        Some(vote_withdrawer),
+    )
+}
 
-            let min_account_balance = config
-                .rpc_client
-                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+fn command_increase_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+    amount: f64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(amount);
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
+    }
 
-            instructions.extend(vec![
-                // Creating new account
-                system_instruction::create_account(
-                    &config.fee_payer.pubkey(),
-                    &keypair.pubkey(),
-                    min_account_balance,
-                    spl_token::state::Account::LEN as u64,
-                    &spl_token::id(),
-                ),
-                // Initialize token receiver account
-                spl_token::instruction::initialize_account(
-                    &spl_token::id(),
-                    &keypair.pubkey(),
-                    mint,
-                    &config.token_owner.pubkey(),
-                )?,
-            ]);
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (transient_stake_address, _) = find_transient_stake_program_address(
+        &spl_stake_pool::id(),
+        &vote_account,
+        stake_pool_address,
+    );
 
-            handler(min_account_balance);
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::increase_validator_stake(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.staker.pubkey(),
+            &stake_pool.validator_list,
+            &pool_withdraw_authority,
+            &stake_pool.reserve_stake,
+            &transient_stake_address,
+            &vote_account,
+            lamports,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
 
-            keypair.pubkey()
-        }
-    };
-    Ok(result)
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
 }
 
-fn command_deposit(
+fn command_decrease_validator_stake(
     config: &Config,
     stake_pool_address: &Pubkey,
-    stake: &Pubkey,
-    token_receiver: &Option<Pubkey>,
+    vote_account: &Pubkey,
+    amount: f64,
 ) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(amount);
     if !config.no_update {
-        command_update(config, stake_pool_address)?;
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
-    let stake_state = get_stake_state(&config.rpc_client, &stake)?;
-
-    if config.verbose {
-        println!("Depositing stake account {:?}", stake_state);
-    }
-    let vote_account = match stake_state {
-        StakeState::Stake(_, stake) => Ok(stake.delegation.voter_pubkey),
-        _ => Err("Wrong stake account state, must be delegated to validator"),
-    }?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+    let (transient_stake_address, _) = find_transient_stake_program_address(
+        &spl_stake_pool::id(),
+        &vote_account,
+        stake_pool_address,
+    );
 
-    // Check if this vote account has staking account in the pool
-    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-    if !validator_list.contains(&vote_account) {
-        return Err("Stake account for this validator does not exist in the pool.".into());
-    }
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::decrease_validator_stake(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.staker.pubkey(),
+            &stake_pool.validator_list,
+            &pool_withdraw_authority,
+            &validator_stake_address,
+            &transient_stake_address,
+            lamports,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
 
-    // Calculate validator stake account address linked to the pool
-    let (validator_stake_account, _) =
-        find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
 
-    let validator_stake_state = get_stake_state(&config.rpc_client, &validator_stake_account)?;
-    println!("Depositing into stake account {}", validator_stake_account);
-    if config.verbose {
-        println!("{:?}", validator_stake_state);
+fn command_decrease_delinquent_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+) -> CommandResult {
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
-    let mut instructions: Vec<Instruction> = vec![];
-    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
-
-    let mut total_rent_free_balances: u64 = 0;
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+    let (transient_stake_address, _) = find_transient_stake_program_address(
+        &spl_stake_pool::id(),
+        &vote_account,
+        stake_pool_address,
+    );
 
-    let token_receiver_account = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            spl_stake_pool::instruction::decrease_delinquent_validator_stake(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &vote_account,
+                &stake_pool.validator_list,
+                &pool_withdraw_authority,
+                &validator_stake_address,
+                &transient_stake_address,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
 
-    // Create token account if not specified
-    let token_receiver = unwrap_create_token_account(
-        &config,
-        &token_receiver,
-        &token_receiver_account,
-        &stake_pool.pool_mint,
-        &mut instructions,
-        |balance| {
-            signers.push(&token_receiver_account);
-            total_rent_free_balances += balance;
-        },
-    )?;
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    transaction.try_sign(&[config.fee_payer.as_ref()], recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
 
-    // Calculate Deposit and Withdraw stake pool authorities
-    let pool_deposit_authority =
-        find_deposit_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+fn command_apply_targets(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+) -> CommandResult {
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
+    }
 
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
     let pool_withdraw_authority =
         find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+    let (transient_stake_address, _) = find_transient_stake_program_address(
+        &spl_stake_pool::id(),
+        &vote_account,
+        stake_pool_address,
+    );
 
-    instructions.extend(vec![
-        // Set Withdrawer on stake account to Deposit authority of the stake pool
-        stake_program::authorize(
-            &stake,
-            &config.staker.pubkey(),
-            &pool_deposit_authority,
-            StakeAuthorize::Withdrawer,
-        ),
-        // Set Staker on stake account to Deposit authority of the stake pool
-        stake_program::authorize(
-            &stake,
-            &config.staker.pubkey(),
-            &pool_deposit_authority,
-            StakeAuthorize::Staker,
-        ),
-        // Add stake account to the pool
-        spl_stake_pool::instruction::deposit(
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::apply_targets(
             &spl_stake_pool::id(),
             &stake_pool_address,
             &stake_pool.validator_list,
-            &pool_deposit_authority,
             &pool_withdraw_authority,
-            &stake,
-            &validator_stake_account,
-            &token_receiver,
-            &stake_pool.pool_mint,
-            &spl_token::id(),
-        )?,
-    ]);
-
-    let mut transaction =
-        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+            &stake_pool.reserve_stake,
+            &validator_stake_address,
+            &transient_stake_address,
+            &vote_account,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
-    check_fee_payer_balance(
-        config,
-        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
-    )?;
-    unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    transaction.try_sign(&[config.fee_payer.as_ref()], recent_blockhash)?;
     send_transaction(&config, transaction)?;
     Ok(())
 }
 
-fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
-    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
-    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
-    let epoch_info = config.rpc_client.get_epoch_info()?;
-
-    for validator in validator_list.validators {
-        println!(
-            "Validator Vote Account: {}\tBalance: {}\tLast Update Epoch: {}{}",
-            validator.vote_account,
-            Sol(validator.stake_lamports),
-            validator.last_update_epoch,
-            if validator.last_update_epoch != epoch_info.epoch {
-                " [UPDATE REQUIRED]"
-            } else {
-                ""
-            }
-        );
+/// Scores of the cluster's validators, from a validators.app style score file
+/// or else from the cluster's vote accounts
+fn get_validator_scores(
+    config: &Config,
+    scores_file: Option<&str>,
+) -> Result<ValidatorScores, Error> {
+    match scores_file {
+        Some(scores_file) => std::fs::read_to_string(scores_file)
+            .map_err(|err| Error::from(format!("Unable to read {}: {}", scores_file, err)))
+            .and_then(|json| ValidatorScores::from_json(&json)),
+        None => ValidatorScores::from_vote_accounts(&config.rpc_client.get_vote_accounts()?),
     }
+}
 
-    println!(
-        "Total Pool Stake: {}{}",
-        Sol(stake_pool.total_stake_lamports),
-        if stake_pool.last_update_epoch != epoch_info.epoch {
-            " [UPDATE REQUIRED]"
-        } else {
-            ""
-        }
-    );
-    println!(
-        "Total Pool Tokens: {}",
-        spl_token::amount_to_ui_amount(stake_pool.pool_token_supply, pool_mint.decimals)
-    );
-
-    if config.verbose {
-        println!();
-
-        let pool_withdraw_authority =
-            find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
-
-        let accounts =
-            get_stake_accounts_by_withdraw_authority(&config.rpc_client, &pool_withdraw_authority)?;
-        if accounts.is_empty() {
-            return Err(format!("No stake accounts found for {}", pool_withdraw_authority).into());
-        }
-
-        let mut total_stake_lamports: u64 = 0;
-        for (pubkey, stake_lamports, stake_state) in accounts {
-            total_stake_lamports += stake_lamports;
-            println!(
-                "Stake Account: {}\tVote Account: {}\t{}",
-                pubkey,
-                stake_state.delegation().expect("delegation").voter_pubkey,
-                Sol(stake_lamports)
-            );
-        }
-        println!("Total Stake Account Balance: {}", Sol(total_stake_lamports));
-
-        if pool_mint.supply != stake_pool.pool_token_supply {
-            println!(
-                "BUG! Pool Tokens supply mismatch.  Pool mint reports {}",
-                spl_token::amount_to_ui_amount(pool_mint.supply, pool_mint.decimals)
-            );
-        }
+fn command_rebalance(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    strategy: &dyn SelectionStrategy,
+) -> CommandResult {
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
-    Ok(())
-}
-
-fn command_update(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
-    let epoch_info = config.rpc_client.get_epoch_info()?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let stake_rent = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let reserve_lamports = config
+        .rpc_client
+        .get_balance(&stake_pool.reserve_stake)?
+        .saturating_sub(stake_rent);
 
-    if stake_pool.last_update_epoch == epoch_info.epoch {
-        println!("Update not required");
+    let plan = plan_rebalance(
+        &validator_list.validators,
+        reserve_lamports,
+        strategy,
+        stake_rent,
+        stake_rent + MINIMUM_ACTIVE_STAKE,
+    )?;
+    for vote_account in &plan.busy_validators {
+        println!(
+            "Skipping validator {}, its transient stake account is in use",
+            vote_account
+        );
+    }
+    if plan.moves.is_empty() {
+        println!("Nothing to rebalance");
         return Ok(());
     }
 
-    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
-
-    let accounts_to_update: Vec<Pubkey> = validator_list
-        .validators
-        .iter()
-        .filter_map(|item| {
-            if item.last_update_epoch >= epoch_info.epoch {
-                None
-            } else {
-                let (stake_account, _) = find_stake_program_address(
+    let mut instructions: Vec<Instruction> = vec![];
+    for stake_move in &plan.moves {
+        let vote_account = match stake_move {
+            StakeMove::Increase { vote_account, .. } | StakeMove::Decrease { vote_account, .. } => {
+                vote_account
+            }
+        };
+        let (transient_stake_address, _) = find_transient_stake_program_address(
+            &spl_stake_pool::id(),
+            vote_account,
+            stake_pool_address,
+        );
+        let instruction = match stake_move {
+            StakeMove::Increase { lamports, .. } => {
+                println!(
+                    "Increasing stake of validator {} by {}",
+                    vote_account,
+                    Sol(*lamports)
+                );
+                spl_stake_pool::instruction::increase_validator_stake(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &config.staker.pubkey(),
+                    &stake_pool.validator_list,
+                    &pool_withdraw_authority,
+                    &stake_pool.reserve_stake,
+                    &transient_stake_address,
+                    vote_account,
+                    *lamports,
+                )?
+            }
+            StakeMove::Decrease { lamports, .. } => {
+                println!(
+                    "Decreasing stake of validator {} by {}",
+                    vote_account,
+                    Sol(*lamports)
+                );
+                let (validator_stake_address, _) = find_stake_program_address(
                     &spl_stake_pool::id(),
-                    &item.vote_account,
-                    &stake_pool_address,
+                    vote_account,
+                    stake_pool_address,
                 );
-                Some(stake_account)
+                spl_stake_pool::instruction::decrease_validator_stake(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &config.staker.pubkey(),
+                    &stake_pool.validator_list,
+                    &pool_withdraw_authority,
+                    &validator_stake_address,
+                    &transient_stake_address,
+                    *lamports,
+                )?
             }
-        })
-        .collect();
-
-    println!("Updating stake pool...");
-
-    let mut instructions: Vec<Instruction> = vec![];
-    for accounts_chunk in accounts_to_update.chunks(MAX_ACCOUNTS_TO_UPDATE) {
-        instructions.push(spl_stake_pool::instruction::update_validator_list_balance(
-            &spl_stake_pool::id(),
-            &stake_pool.validator_list,
-            &accounts_chunk,
-        )?);
+        };
+        instructions.push(instruction);
     }
 
-    let (withdraw_authority, _) =
-        find_withdraw_authority_program_address(&spl_stake_pool::id(), &stake_pool_address);
-
-    instructions.push(spl_stake_pool::instruction::update_stake_pool_balance(
-        &spl_stake_pool::id(),
-        stake_pool_address,
-        &stake_pool.validator_list,
-        &withdraw_authority,
-        &stake_pool.manager_fee_account,
-        &stake_pool.pool_mint,
-    )?);
-
-    // TODO: A faster solution would be to send all the `update_validator_list_balance` instructions concurrently
-    for instruction in instructions {
-        let mut transaction =
-            Transaction::new_with_payer(&[instruction], Some(&config.fee_payer.pubkey()));
+    let messages = TransactionPacker::new(config.fee_payer.pubkey()).pack(instructions)?;
+    let mut dry_run = DryRun::default();
+    for message in messages {
+        let mut transaction = Transaction::new_unsigned(message);
 
         let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
         check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
-        transaction.sign(&[config.fee_payer.as_ref()], recent_blockhash);
-        send_transaction(&config, transaction)?;
+        let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+        unique_signers!(signers);
+        transaction.try_sign(&signers, recent_blockhash)?;
+        if config.dry_run {
+            // simulations don't see each other's effects, which is fine
+            // since every move touches its own validator and the plan keeps
+            // the reserve covering all of them
+            dry_run.record(
+                stake_pool_address,
+                &simulate_transaction(config, &transaction)?,
+            );
+        } else {
+            send_transaction(&config, transaction)?;
+        }
+    }
+    if config.dry_run {
+        print_dry_run(config, stake_pool_address, &stake_pool, &dry_run)?;
     }
     Ok(())
 }
 
-#[derive(PartialEq, Debug)]
-struct WithdrawAccount {
-    address: Pubkey,
-    pool_amount: u64,
-}
-
-fn prepare_withdraw_accounts(
-    rpc_client: &RpcClient,
-    stake_pool: &StakePool,
-    pool_withdraw_authority: &Pubkey,
-    pool_amount: u64,
-) -> Result<Vec<WithdrawAccount>, Error> {
-    let mut accounts =
-        get_stake_accounts_by_withdraw_authority(rpc_client, &pool_withdraw_authority)?;
-    if accounts.is_empty() {
-        return Err("No accounts found.".to_string().into());
-    }
-    let min_balance = rpc_client.get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)? + 1;
-    let pool_mint = get_token_mint(rpc_client, &stake_pool.pool_mint)?;
-
-    // Sort from highest to lowest balance
-    accounts.sort_by(|a, b| b.1.cmp(&a.1));
+fn unwrap_create_token_account<F>(
+    config: &Config,
+    token_optional: &Option<Pubkey>,
+    keypair: &Keypair,
+    mint: &Pubkey,
+    instructions: &mut Vec<Instruction>,
+    handler: F,
+) -> Result<Pubkey, Error>
+where
+    F: FnOnce(u64),
+{
+    let result = match token_optional {
+        Some(value) => *value,
+        None => {
+            // Account for tokens not specified, creating one
+            progress!(
+                config,
+                "Creating account to receive tokens {}",
+                keypair.pubkey()
+            );
 
-    // Prepare the list of accounts to withdraw from
-    let mut withdraw_from: Vec<WithdrawAccount> = vec![];
-    let mut remaining_amount = pool_amount;
+            let min_account_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
 
-    // Go through available accounts and withdraw from largest to smallest
-    for (address, lamports, _) in accounts {
-        if lamports <= min_balance {
-            continue;
-        }
-        let available_for_withdrawal = stake_pool
-            .calc_lamports_withdraw_amount(lamports - *MIN_STAKE_BALANCE)
-            .unwrap();
-        let pool_amount = u64::min(available_for_withdrawal, remaining_amount);
+            instructions.extend(vec![
+                // Creating new account
+                system_instruction::create_account(
+                    &config.fee_payer.pubkey(),
+                    &keypair.pubkey(),
+                    min_account_balance,
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                // Initialize token receiver account
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &keypair.pubkey(),
+                    mint,
+                    &config.token_owner.pubkey(),
+                )?,
+            ]);
 
-        // Those accounts will be withdrawn completely with `claim` instruction
-        withdraw_from.push(WithdrawAccount {
-            address,
-            pool_amount,
-        });
-        remaining_amount -= pool_amount;
+            handler(min_account_balance);
 
-        if remaining_amount == 0 {
-            break;
+            keypair.pubkey()
         }
-    }
-
-    // Not enough stake to withdraw the specified amount
-    if remaining_amount > 0 {
-        return Err(format!(
-            "No stake accounts found in this pool with enough balance to withdraw {} pool tokens.",
-            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals)
-        )
-        .into());
-    }
-
-    Ok(withdraw_from)
+    };
+    Ok(result)
 }
 
-fn command_withdraw(
+fn command_deposit(
     config: &Config,
     stake_pool_address: &Pubkey,
-    pool_amount: f64,
-    withdraw_from: &Pubkey,
-    stake_receiver_param: &Option<Pubkey>,
+    stake: &Pubkey,
+    token_receiver: &Option<Pubkey>,
 ) -> CommandResult {
     if !config.no_update {
-        command_update(config, stake_pool_address)?;
+        update_stake_pool(config, stake_pool_address, false)?;
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
-    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
-    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
-
-    let pool_withdraw_authority =
-        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
-
-    // Check withdraw_from account type
-    let token_account =
-        get_token_account(&config.rpc_client, &withdraw_from, &stake_pool.pool_mint)?;
+    check_pool_fresh(config, stake_pool_address, &stake_pool)?;
+    let stake_state = get_stake_state(&config.rpc_client, &stake)?;
 
-    // Check withdraw_from balance
-    if token_account.amount < pool_amount {
-        return Err(format!(
-            "Not enough token balance to withdraw {} pool tokens.\nMaximum withdraw amount is {} pool tokens.",
-            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
-            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
-        )
-        .into());
+    if config.verbose {
+        progress!(config, "Depositing stake account {:?}", stake_state);
     }
+    let vote_account = match stake_state {
+        StakeState::Stake(_, stake) => Ok(stake.delegation.voter_pubkey),
+        _ => Err("Wrong stake account state, must be delegated to validator"),
+    }?;
 
-    // Get the list of accounts to withdraw from
-    let withdraw_accounts = prepare_withdraw_accounts(
-        &config.rpc_client,
-        &stake_pool,
-        &pool_withdraw_authority,
-        pool_amount,
-    )?;
+    // Check if this vote account has staking account in the pool
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    if !validator_list.contains(&vote_account) {
+        return Err("Stake account for this validator does not exist in the pool.".into());
+    }
+    if let Some(preferred_deposit_validator) = stake_pool.preferred_deposit_validator() {
+        if preferred_deposit_validator != vote_account {
+            return Err(format!(
+                "Stake pool only accepts deposits delegated to validator {}",
+                preferred_deposit_validator
+            )
+            .into());
+        }
+    }
 
-    // Construct transaction to withdraw from withdraw_accounts account list
-    let mut instructions: Vec<Instruction> = vec![];
-    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
-    let stake_receiver_account = Keypair::new(); // Will be added to signers if creating new account
+    // Calculate validator stake account address linked to the pool
+    let (validator_stake_account, _) =
+        find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
 
-    instructions.push(
-        // Approve spending token
-        spl_token::instruction::approve(
-            &spl_token::id(),
-            &withdraw_from,
-            &pool_withdraw_authority,
-            &config.token_owner.pubkey(),
-            &[],
-            pool_amount,
-        )?,
+    let validator_stake_state = get_stake_state(&config.rpc_client, &validator_stake_account)?;
+    progress!(
+        config,
+        "Depositing into stake account {}",
+        validator_stake_account
     );
+    if config.verbose {
+        progress!(config, "{:?}", validator_stake_state);
+    }
 
-    // Use separate mutable variable because withdraw might create a new account
-    let mut stake_receiver: Option<Pubkey> = *stake_receiver_param;
-
-    let mut total_rent_free_balances = 0;
-
-    // Go through prepared accounts and withdraw/claim them
-    for withdraw_account in withdraw_accounts {
-        // Convert pool tokens amount to lamports
-        let sol_withdraw_amount = stake_pool
-            .calc_lamports_withdraw_amount(withdraw_account.pool_amount)
-            .unwrap();
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
 
-        println!(
-            "Withdrawing from account {}, amount {}, {} pool tokens",
-            withdraw_account.address,
-            Sol(sol_withdraw_amount),
-            spl_token::amount_to_ui_amount(withdraw_account.pool_amount, pool_mint.decimals),
-        );
+    let mut total_rent_free_balances: u64 = 0;
 
-        if stake_receiver.is_none() {
-            // Account for tokens not specified, creating one
-            println!(
-                "Creating account to receive stake {}",
-                stake_receiver_account.pubkey()
-            );
+    let token_receiver_account = Keypair::new();
 
-            let stake_receiver_account_balance = config
+    let token_receiver = if stake_pool.deposit_lock_epochs > 0 {
+        let (escrow, deposit_record) =
+            deposit_escrow(config, stake_pool_address, &stake_pool, token_receiver)?;
+        if config
+            .rpc_client
+            .get_account_with_commitment(&escrow, CommitmentConfig::confirmed())?
+            .value
+            .is_none()
+        {
+            progress!(config, "Creating escrow to receive tokens {}", escrow);
+            instructions.push(create_associated_token_account(
+                &config.fee_payer.pubkey(),
+                &deposit_record,
+                &stake_pool.pool_mint,
+            ));
+            total_rent_free_balances += config
                 .rpc_client
-                .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
-
-            instructions.push(
-                // Creating new account
-                system_instruction::create_account(
-                    &config.fee_payer.pubkey(),
-                    &stake_receiver_account.pubkey(),
-                    stake_receiver_account_balance,
-                    STAKE_STATE_LEN as u64,
-                    &stake_program::id(),
-                ),
-            );
-
-            signers.push(&stake_receiver_account);
-
-            total_rent_free_balances += stake_receiver_account_balance;
-
-            stake_receiver = Some(stake_receiver_account.pubkey());
+                .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
         }
-
-        instructions.push(spl_stake_pool::instruction::withdraw(
-            &spl_stake_pool::id(),
-            &stake_pool_address,
-            &stake_pool.validator_list,
-            &pool_withdraw_authority,
-            &withdraw_account.address,
-            &stake_receiver.unwrap(), // Cannot be none at this point
-            &config.staker.pubkey(),
-            &withdraw_from,
+        escrow
+    } else {
+        // Create token account if not specified
+        unwrap_create_token_account(
+            &config,
+            &token_receiver,
+            &token_receiver_account,
             &stake_pool.pool_mint,
-            &spl_token::id(),
-            withdraw_account.pool_amount,
-        )?);
-    }
+            &mut instructions,
+            |balance| {
+                signers.push(&token_receiver_account);
+                total_rent_free_balances += balance;
+            },
+        )?
+    };
+
+    instructions.extend(deposit_instructions(
+        config,
+        stake_pool_address,
+        &stake_pool,
+        stake,
+        &validator_stake_account,
+        &token_receiver,
+        &mut signers,
+    )?);
 
     let mut transaction =
         Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
@@ -903,217 +1147,3879 @@ fn command_withdraw(
         total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
     )?;
     unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
-    send_transaction(&config, transaction)?;
+    transaction.try_sign(&signers, recent_blockhash)?;
+    if config.dry_run {
+        let mut dry_run = DryRun::default();
+        dry_run.record(
+            stake_pool_address,
+            &simulate_transaction(config, &transaction)?,
+        );
+        return print_dry_run(config, stake_pool_address, &stake_pool, &dry_run);
+    }
+    let stake_lamports = config.rpc_client.get_balance(stake)?;
+    let signature = send_transaction(&config, transaction)?;
+    if config.output_format.is_json() {
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "stakeAccount": stake.to_string(),
+                "stakeLamports": stake_lamports,
+                "validatorStakeAccount": validator_stake_account.to_string(),
+                "tokenReceiver": token_receiver.to_string(),
+                "rate": rate_json(&stake_pool),
+                "signature": signature.to_string(),
+            }),
+        );
+    }
     Ok(())
 }
 
-fn command_set_manager(
+fn command_deposit_all(
     config: &Config,
     stake_pool_address: &Pubkey,
-    new_manager: &Option<Pubkey>,
-    new_fee_receiver: &Option<Pubkey>,
+    from: &Option<Pubkey>,
+    token_receiver_param: &Option<Pubkey>,
 ) -> CommandResult {
+    let staker_authority = from.unwrap_or_else(|| config.staker.pubkey());
+    if staker_authority != config.staker.pubkey() {
+        return Err(format!(
+            "Stake accounts of {} must be signed for, use --staker",
+            staker_authority
+        )
+        .into());
+    }
+
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
+    }
+
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    check_pool_fresh(config, stake_pool_address, &stake_pool)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
 
-    // If new accounts are missing in the arguments use the old ones
-    let new_manager = match new_manager {
-        None => stake_pool.manager,
-        Some(value) => *value,
-    };
-    let new_fee_receiver = match new_fee_receiver {
-        None => stake_pool.manager_fee_account,
-        Some(value) => {
-            // Check for fee receiver being a valid token account and have to same mint as the stake pool
-            let token_account =
-                get_token_account(&config.rpc_client, value, &stake_pool.pool_mint)?;
-            if token_account.mint != stake_pool.pool_mint {
-                return Err("Fee receiver account belongs to a different mint"
-                    .to_string()
-                    .into());
-            }
-            *value
-        }
+    // Depositing hands over both authorities, so the staker must also be the
+    // withdrawer, and the stake must be delegated to a validator of the pool
+    let stake_accounts: Vec<(Pubkey, u64, Pubkey)> =
+        get_stake_accounts_by_staker_authority(&config.rpc_client, &staker_authority)?
+            .into_iter()
+            .filter(|(_, _, stake_state)| {
+                stake_state
+                    .meta()
+                    .map_or(false, |meta| meta.authorized.withdrawer == staker_authority)
+            })
+            .filter_map(|(address, lamports, stake_state)| {
+                let vote_account = stake_state.delegation()?.voter_pubkey;
+                let accepted = validator_list.contains(&vote_account)
+                    && stake_pool
+                        .preferred_deposit_validator()
+                        .map_or(true, |preferred| preferred == vote_account);
+                if accepted {
+                    Some((address, lamports, vote_account))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    if stake_accounts.is_empty() {
+        return Err(format!(
+            "No stake accounts of {} are delegated to validators of the pool.",
+            staker_authority
+        )
+        .into());
+    }
+
+    // Pool tokens go to the associated token account of the token owner,
+    // unless a token receiver is given, or to its escrow while deposits are
+    // locked
+    let (token_receiver, token_receiver_owner) = if stake_pool.deposit_lock_epochs > 0 {
+        deposit_escrow(
+            config,
+            stake_pool_address,
+            &stake_pool,
+            token_receiver_param,
+        )?
+    } else {
+        (
+            token_receiver_param.unwrap_or_else(|| {
+                get_associated_token_address(&config.token_owner.pubkey(), &stake_pool.pool_mint)
+            }),
+            config.token_owner.pubkey(),
+        )
     };
+    let mut create_token_receiver = token_receiver_param.is_none()
+        && config
+            .rpc_client
+            .get_account_with_commitment(&token_receiver, CommitmentConfig::confirmed())?
+            .value
+            .is_none();
+    let token_receiver_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
 
-    let mut transaction = Transaction::new_with_payer(
-        &[spl_stake_pool::instruction::set_manager(
-            &spl_stake_pool::id(),
-            &stake_pool_address,
-            &config.manager.pubkey(),
-            &new_manager,
-            &new_fee_receiver,
-        )?],
-        Some(&config.fee_payer.pubkey()),
-    );
+    let mut deposits = vec![];
+    let mut failures = vec![];
+    for (stake, stake_lamports, vote_account) in stake_accounts {
+        let (validator_stake_account, _) =
+            find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+        progress!(
+            config,
+            "Depositing stake account {}, {} delegated to {}",
+            stake,
+            Sol(stake_lamports),
+            vote_account
+        );
 
-    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
-    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
-    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
-    unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
-    send_transaction(&config, transaction)?;
-    Ok(())
+        let mut instructions: Vec<Instruction> = vec![];
+        let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+        let mut total_rent_free_balances: u64 = 0;
+        if create_token_receiver {
+            progress!(
+                config,
+                "Creating associated token account to receive tokens {}",
+                token_receiver
+            );
+            instructions.push(create_associated_token_account(
+                &config.fee_payer.pubkey(),
+                &token_receiver_owner,
+                &stake_pool.pool_mint,
+            ));
+            total_rent_free_balances += token_receiver_balance;
+        }
+        instructions.extend(deposit_instructions(
+            config,
+            stake_pool_address,
+            &stake_pool,
+            &stake,
+            &validator_stake_account,
+            &token_receiver,
+            &mut signers,
+        )?);
+
+        // Deposits are sent one by one, so a rejected stake account doesn't
+        // hold back the others
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+        let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+        check_fee_payer_balance(
+            config,
+            total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+        )?;
+        unique_signers!(signers);
+        transaction.try_sign(&signers, recent_blockhash)?;
+        match send_transaction(&config, transaction) {
+            Ok(signature) => {
+                // A simulation doesn't create the token account
+                if !config.dry_run {
+                    create_token_receiver = false;
+                }
+                deposits.push(serde_json::json!({
+                    "stakeAccount": stake.to_string(),
+                    "stakeLamports": stake_lamports,
+                    "validatorStakeAccount": validator_stake_account.to_string(),
+                    "signature": signature.to_string(),
+                }));
+            }
+            Err(err) => {
+                progress!(config, "Failed to deposit stake account {}: {}", stake, err);
+                failures.push(serde_json::json!({
+                    "stakeAccount": stake.to_string(),
+                    "stakeLamports": stake_lamports,
+                    "error": err.to_string(),
+                }));
+            }
+        }
+    }
+
+    if config.output_format.is_json() {
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "stakerAuthority": staker_authority.to_string(),
+                "tokenReceiver": token_receiver.to_string(),
+                "deposits": deposits,
+                "failures": failures,
+                "rate": rate_json(&stake_pool),
+            }),
+        );
+    } else {
+        println!(
+            "Deposited {} of {} stake accounts into {}",
+            deposits.len(),
+            deposits.len() + failures.len(),
+            token_receiver
+        );
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} stake accounts could not be deposited", failures.len()).into())
+    }
 }
 
-fn command_set_staker(
+/// Escrow receiving the pool tokens of the token owner while the pool locks
+/// deposits, with the deposit record owning it
+fn deposit_escrow(
     config: &Config,
     stake_pool_address: &Pubkey,
-    new_staker: &Pubkey,
-) -> CommandResult {
-    let mut transaction = Transaction::new_with_payer(
-        &[spl_stake_pool::instruction::set_staker(
+    stake_pool: &StakePool,
+    token_receiver: &Option<Pubkey>,
+) -> Result<(Pubkey, Pubkey), Error> {
+    if token_receiver.is_some() {
+        return Err(format!(
+            "Stake pool locks pool tokens for {} epochs after a deposit, \
+            they cannot go to a token receiver",
+            stake_pool.deposit_lock_epochs
+        )
+        .into());
+    }
+    let (deposit_record, _) = find_deposit_record_address(
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &config.token_owner.pubkey(),
+    );
+    Ok((
+        get_associated_token_address(&deposit_record, &stake_pool.pool_mint),
+        deposit_record,
+    ))
+}
+
+/// Instructions moving a stake account into the pool, signed by the staker,
+/// the stake deposit authority if the pool has one, and the token owner if the
+/// pool locks deposits
+fn deposit_instructions<'a>(
+    config: &'a Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    stake: &Pubkey,
+    validator_stake_account: &Pubkey,
+    token_receiver: &Pubkey,
+    signers: &mut Vec<&'a dyn Signer>,
+) -> Result<Vec<Instruction>, Error> {
+    // Calculate Deposit and Withdraw stake pool authorities
+    let pool_deposit_authority =
+        find_deposit_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    let mut deposit_instruction = if stake_pool.stake_deposit_authority == pool_deposit_authority {
+        spl_stake_pool::instruction::deposit(
             &spl_stake_pool::id(),
             &stake_pool_address,
-            &config.manager.pubkey(),
-            &new_staker,
-        )?],
-        Some(&config.fee_payer.pubkey()),
+            &stake_pool.validator_list,
+            &pool_deposit_authority,
+            &pool_withdraw_authority,
+            &stake,
+            &validator_stake_account,
+            &token_receiver,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &spl_token::id(),
+        )?
+    } else {
+        let depositor = config
+            .depositor
+            .as_ref()
+            .filter(|depositor| depositor.pubkey() == stake_pool.stake_deposit_authority)
+            .ok_or_else(|| {
+                format!(
+                    "Stake pool deposits must be signed by deposit authority {}, use --depositor",
+                    stake_pool.stake_deposit_authority
+                )
+            })?;
+        signers.push(depositor.as_ref());
+        spl_stake_pool::instruction::deposit_with_authority(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &stake_pool.validator_list,
+            &stake_pool.stake_deposit_authority,
+            &pool_withdraw_authority,
+            &stake,
+            &validator_stake_account,
+            &token_receiver,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &spl_token::id(),
+        )?
+    };
+    if stake_pool.deposit_lock_epochs > 0 {
+        progress!(
+            config,
+            "Pool tokens of {} will be held in escrow for {} epochs, \
+            then released by claim-locked-deposit",
+            config.token_owner.pubkey(),
+            stake_pool.deposit_lock_epochs
+        );
+        signers.push(config.token_owner.as_ref());
+        spl_stake_pool::instruction::add_deposit_record_accounts(
+            &mut deposit_instruction,
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.token_owner.pubkey(),
+        );
+    }
+    if let Some(deposit_interceptor) = stake_pool.deposit_interceptor() {
+        progress!(
+            config,
+            "Deposit will be passed to interceptor {}",
+            deposit_interceptor
+        );
+        spl_stake_pool::instruction::add_deposit_interceptor_accounts(
+            &mut deposit_instruction,
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &deposit_interceptor,
+        );
+    }
+
+    Ok(vec![
+        // Set Withdrawer on stake account to Deposit authority of the stake pool
+        stake_program::authorize(
+            &stake,
+            &config.staker.pubkey(),
+            &stake_pool.stake_deposit_authority,
+            StakeAuthorize::Withdrawer,
+        ),
+        // Set Staker on stake account to Deposit authority of the stake pool
+        stake_program::authorize(
+            &stake,
+            &config.staker.pubkey(),
+            &stake_pool.stake_deposit_authority,
+            StakeAuthorize::Staker,
+        ),
+        // Add stake account to the pool
+        deposit_instruction,
+    ])
+}
+
+/// Annualized yield of the pool tokens between the oldest and the latest
+/// entries of the pool's rate history, along with the epochs they span
+fn trailing_apy(rate_history: &RateHistory, slots_per_epoch: u64) -> Option<(f64, u64)> {
+    const MS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0 * 1000.0;
+    let latest = rate_history.latest()?;
+    let oldest = rate_history.iter().last()?;
+    let epochs = latest.epoch.saturating_sub(oldest.epoch);
+    if epochs == 0 {
+        return None;
+    }
+    let growth = latest.rate()? as f64 / oldest.rate()? as f64;
+    let epochs_per_year = MS_PER_YEAR / (slots_per_epoch * DEFAULT_MS_PER_SLOT) as f64;
+    Some((growth.powf(epochs_per_year / epochs as f64) - 1.0, epochs))
+}
+
+fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+
+    if config.output_format.is_json() {
+        let validators: Vec<serde_json::Value> = validator_list
+            .validators
+            .iter()
+            .map(|validator| {
+                serde_json::json!({
+                    "voteAccount": validator.vote_account.to_string(),
+                    "activeStakeLamports": u64::from(validator.active_stake_lamports),
+                    "transientStakeLamports": u64::from(validator.transient_stake_lamports),
+                    "targetWeightBps": u16::from(validator.target_weight_bps),
+                    "lastUpdateEpoch": u64::from(validator.last_update_epoch),
+                    "updateRequired": validator.is_stale(epoch_info.epoch),
+                    "status": match StakeStatus::try_from(validator.status) {
+                        Ok(StakeStatus::Active) => "active",
+                        Ok(StakeStatus::DeactivatingTransient) => "deactivatingTransient",
+                        _ => "readyForRemoval",
+                    },
+                    "eligibleEpoch": u64::from(validator.eligible_epoch),
+                })
+            })
+            .collect();
+        let trailing_apy = get_rate_history(&config.rpc_client, stake_pool_address)
+            .ok()
+            .and_then(|rate_history| trailing_apy(&rate_history, epoch_info.slots_in_epoch))
+            .map(|(apy, epochs)| serde_json::json!({ "apy": apy, "epochs": epochs }));
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "epoch": epoch_info.epoch,
+                "lastUpdateEpoch": stake_pool.last_update_epoch,
+                "updateRequired": stake_pool.last_update_epoch != epoch_info.epoch,
+                "freshness": freshness_json(stake_pool.freshness(epoch_info.epoch)),
+                "priceOracleFreshness": get_price_oracle(&config.rpc_client, stake_pool_address)
+                    .ok()
+                    .map(|price_oracle| freshness_json(price_oracle.freshness(epoch_info.epoch))),
+                "totalStakeLamports": stake_pool.total_stake_lamports,
+                "poolTokenSupply": stake_pool.pool_token_supply,
+                "poolMintDecimals": pool_mint.decimals,
+                "rate": rate_json(&stake_pool),
+                "trailingApy": trailing_apy,
+                "paused": stake_pool.paused,
+                "decommissioning": stake_pool.decommissioning,
+                "validators": validators,
+            }),
+        );
+        return Ok(());
+    }
+
+    for validator in validator_list.validators {
+        println!(
+            "Validator Vote Account: {}\tBalance: {}\tTransient Balance: {}\tTarget: {}bps\tLast Update Epoch: {}{}{}{}",
+            validator.vote_account,
+            Sol(validator.active_stake_lamports.into()),
+            Sol(validator.transient_stake_lamports.into()),
+            u16::from(validator.target_weight_bps),
+            u64::from(validator.last_update_epoch),
+            if validator.last_update_epoch != epoch_info.epoch {
+                " [UPDATE REQUIRED]"
+            } else {
+                ""
+            },
+            match StakeStatus::try_from(validator.status) {
+                Ok(StakeStatus::Active) => "",
+                Ok(StakeStatus::DeactivatingTransient) => " [REMOVING, TRANSIENT DEACTIVATING]",
+                _ => " [REMOVING]",
+            },
+            if u64::from(validator.eligible_epoch) > epoch_info.epoch {
+                format!(
+                    " [ONBOARDING UNTIL EPOCH {}]",
+                    u64::from(validator.eligible_epoch)
+                )
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    println!(
+        "Total Pool Stake: {}{}",
+        Sol(stake_pool.total_stake_lamports),
+        if stake_pool.last_update_epoch != epoch_info.epoch {
+            " [UPDATE REQUIRED]"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "Total Pool Tokens: {}",
+        spl_token::amount_to_ui_amount(stake_pool.pool_token_supply, pool_mint.decimals)
     );
+    if stake_pool.paused {
+        println!("Paused: deposits, withdrawals and rebalancing are blocked");
+    }
+    if stake_pool.decommissioning {
+        println!("Decommissioning: deposits are blocked, the pool is winding down");
+    }
+    if stake_pool.deposit_lock_epochs > 0 {
+        println!("Deposit Lock: {} epochs", stake_pool.deposit_lock_epochs);
+    }
+    if let Some(max_total_lamports) = stake_pool.deposit_cap() {
+        println!("Deposit Cap: {}", Sol(max_total_lamports));
+    }
+    if let Some(max_net_outflow_bps) = stake_pool.epoch_net_outflow_cap() {
+        println!(
+            "Epoch Net Outflow Cap: {}bps\tWithdrawn This Epoch: {}\tDeposited This Epoch: {}",
+            max_net_outflow_bps,
+            Sol(stake_pool.epoch_withdrawn_lamports),
+            Sol(stake_pool.epoch_deposited_lamports),
+        );
+    }
+    if stake_pool.delinquent_validator_epochs > 0 {
+        println!(
+            "Delinquent Validators: anyone may decrease stake after {} epochs without vote credits",
+            stake_pool.delinquent_validator_epochs
+        );
+    }
+    if stake_pool.validator_onboarding_epochs > 0 {
+        println!(
+            "Validator Onboarding: new validators take stake after {} epochs",
+            stake_pool.validator_onboarding_epochs
+        );
+    }
+    if let Some(manager_timelock_epochs) = stake_pool.manager_timelock() {
+        println!(
+            "Manager Timelock: fee, manager and funding authority changes wait {} epochs",
+            manager_timelock_epochs
+        );
+    }
+    if let Some((action, executable_epoch)) = stake_pool.pending_manager_action() {
+        println!(
+            "Pending Manager Action: {}, executable from epoch {}",
+            display_manager_action(&action),
+            executable_epoch
+        );
+    }
+    if let Ok(price_oracle) = get_price_oracle(&config.rpc_client, stake_pool_address) {
+        println!(
+            "Published Price: {} per {} pool tokens, epoch {}{}",
+            Sol(price_oracle.rate),
+            spl_token::amount_to_ui_amount(PRICE_ORACLE_RATE_PRECISION, pool_mint.decimals),
+            price_oracle.last_update_epoch,
+            if price_oracle.is_stale(epoch_info.epoch) {
+                " [UPDATE REQUIRED]"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Ok(rate_history) = get_rate_history(&config.rpc_client, stake_pool_address) {
+        if let Some((apy, epochs)) = trailing_apy(&rate_history, epoch_info.slots_in_epoch) {
+            println!(
+                "Trailing APY: {:.2}% over the last {} epochs",
+                apy * 100.0,
+                epochs
+            );
+        }
+    }
+
+    if config.verbose {
+        println!();
+
+        let pool_withdraw_authority =
+            find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+        let accounts =
+            get_stake_accounts_by_withdraw_authority(&config.rpc_client, &pool_withdraw_authority)?;
+        if accounts.is_empty() {
+            return Err(format!("No stake accounts found for {}", pool_withdraw_authority).into());
+        }
+
+        let mut total_stake_lamports: u64 = 0;
+        for (pubkey, stake_lamports, stake_state) in accounts {
+            total_stake_lamports += stake_lamports;
+            println!(
+                "Stake Account: {}\tVote Account: {}\t{}",
+                pubkey,
+                stake_state.delegation().expect("delegation").voter_pubkey,
+                Sol(stake_lamports)
+            );
+        }
+        println!("Total Stake Account Balance: {}", Sol(total_stake_lamports));
+
+        if pool_mint.supply != stake_pool.pool_token_supply {
+            println!(
+                "BUG! Pool Tokens supply mismatch.  Pool mint reports {}",
+                spl_token::amount_to_ui_amount(pool_mint.supply, pool_mint.decimals)
+            );
+        }
+    }
 
-    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
-    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
-    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
-    unique_signers!(signers);
-    transaction.sign(&signers, recent_blockhash);
-    send_transaction(&config, transaction)?;
     Ok(())
 }
 
-fn main() {
-    solana_logger::setup_with_default("solana=info");
+fn command_list_all(config: &Config) -> CommandResult {
+    let mut stake_pools = get_all_stake_pools(&config.rpc_client)?;
+    let validator_counts = get_all_validator_counts(&config.rpc_client)?;
+    let pool_mint_addresses: Vec<Pubkey> = stake_pools
+        .iter()
+        .map(|(_, stake_pool)| stake_pool.pool_mint)
+        .collect();
+    let pool_mints = get_token_mints(&config.rpc_client, &pool_mint_addresses)?;
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
 
-    let matches = App::new(crate_name!())
-        .about(crate_description!())
-        .version(crate_version!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .arg({
-            let arg = Arg::with_name("config_file")
-                .short("C")
-                .long("config")
-                .value_name("PATH")
-                .takes_value(true)
-                .global(true)
-                .help("Configuration file to use");
-            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
-                arg.default_value(&config_file)
+    // biggest pools first
+    stake_pools.sort_by(|(_, a), (_, b)| b.total_stake_lamports.cmp(&a.total_stake_lamports));
+    let validator_count = |stake_pool: &StakePool| {
+        validator_counts
+            .get(&stake_pool.validator_list)
+            .copied()
+            .unwrap_or(0)
+    };
+    let total_stake_lamports: u64 = stake_pools
+        .iter()
+        .map(|(_, stake_pool)| stake_pool.total_stake_lamports)
+        .sum();
+    let total_validators: u64 = stake_pools
+        .iter()
+        .map(|(_, stake_pool)| u64::from(validator_count(stake_pool)))
+        .sum();
+    let update_required = stake_pools
+        .iter()
+        .filter(|(_, stake_pool)| stake_pool.last_update_epoch != epoch)
+        .count();
+
+    if config.output_format.is_json() {
+        let pools: Vec<serde_json::Value> = stake_pools
+            .iter()
+            .map(|(address, stake_pool)| {
+                serde_json::json!({
+                    "stakePool": address.to_string(),
+                    "manager": stake_pool.manager.to_string(),
+                    "poolMint": stake_pool.pool_mint.to_string(),
+                    "poolMintDecimals": pool_mints
+                        .get(&stake_pool.pool_mint)
+                        .map(|pool_mint| pool_mint.decimals),
+                    "totalStakeLamports": stake_pool.total_stake_lamports,
+                    "poolTokenSupply": stake_pool.pool_token_supply,
+                    "rate": rate_json(stake_pool),
+                    "epochFee": fee_json(&stake_pool.epoch_fee),
+                    "stakeDepositFee": fee_json(&stake_pool.stake_deposit_fee),
+                    "solDepositFee": fee_json(&stake_pool.sol_deposit_fee),
+                    "withdrawalFee": fee_json(&stake_pool.withdrawal_fee),
+                    "validators": validator_count(stake_pool),
+                    "lastUpdateEpoch": stake_pool.last_update_epoch,
+                    "updateRequired": stake_pool.last_update_epoch != epoch,
+                    "freshness": freshness_json(stake_pool.freshness(epoch)),
+                })
+            })
+            .collect();
+        print_json(
+            config,
+            &serde_json::json!({
+                "epoch": epoch,
+                "stakePools": pools,
+                "summary": {
+                    "stakePools": stake_pools.len(),
+                    "totalStakeLamports": total_stake_lamports,
+                    "validators": total_validators,
+                    "updateRequired": update_required,
+                },
+            }),
+        );
+        return Ok(());
+    }
+
+    let display_fee =
+        |fee: &spl_stake_pool::instruction::Fee| format!("{}/{}", fee.numerator, fee.denominator);
+    for (address, stake_pool) in &stake_pools {
+        // raw amounts if the mint can't be read
+        let pool_tokens = |amount| {
+            pool_mints.get(&stake_pool.pool_mint).map_or_else(
+                || amount.to_string(),
+                |pool_mint| spl_token::amount_to_ui_amount(amount, pool_mint.decimals).to_string(),
+            )
+        };
+        println!(
+            "Stake Pool: {}\tManager: {}\tStake: {}\tPool Tokens: {}\tRate: {}\tEpoch Fee: {}\tWithdrawal Fee: {}\tValidators: {}{}",
+            address,
+            stake_pool.manager,
+            Sol(stake_pool.total_stake_lamports),
+            pool_tokens(stake_pool.pool_token_supply),
+            stake_pool.price_oracle_rate().map_or_else(
+                || "none".to_string(),
+                |rate| format!(
+                    "{} per {} pool tokens",
+                    Sol(rate),
+                    pool_tokens(PRICE_ORACLE_RATE_PRECISION)
+                )
+            ),
+            display_fee(&stake_pool.epoch_fee),
+            display_fee(&stake_pool.withdrawal_fee),
+            validator_count(stake_pool),
+            if stake_pool.last_update_epoch != epoch {
+                " [UPDATE REQUIRED]"
             } else {
-                arg
+                ""
             }
-        })
-        .arg(
-            Arg::with_name("verbose")
-                .long("verbose")
-                .short("v")
-                .takes_value(false)
-                .global(true)
-                .help("Show additional information"),
-        )
-        .arg(
-            Arg::with_name("dry_run")
-                .long("dry-run")
+        );
+    }
+    println!("Stake Pools: {}", stake_pools.len());
+    println!("Total Stake: {}", Sol(total_stake_lamports));
+    println!("Total Validators: {}", total_validators);
+    if update_required > 0 {
+        println!("Pools Requiring Update: {}", update_required);
+    }
+    Ok(())
+}
+
+/// Lamports backing `PRICE_ORACLE_RATE_PRECISION` pool tokens, as of the
+/// pool's last update
+fn rate_json(stake_pool: &StakePool) -> serde_json::Value {
+    serde_json::json!({
+        "lamports": stake_pool.price_oracle_rate(),
+        "poolTokens": PRICE_ORACLE_RATE_PRECISION,
+    })
+}
+
+fn fee_json(fee: &spl_stake_pool::instruction::Fee) -> serde_json::Value {
+    serde_json::json!({
+        "numerator": fee.numerator,
+        "denominator": fee.denominator,
+    })
+}
+
+fn display_manager_action(action: &ManagerAction) -> String {
+    use spl_stake_pool::instruction::{FeeType, FundingType};
+    match action {
+        ManagerAction::SetFee(fee_type) => {
+            let (name, fee) = match fee_type {
+                FeeType::Epoch(fee) => ("epoch", fee),
+                FeeType::StakeDeposit(fee) => ("stake deposit", fee),
+                FeeType::SolDeposit(fee) => ("SOL deposit", fee),
+                FeeType::Withdrawal(fee) => ("withdrawal", fee),
+            };
+            format!(
+                "set the {} fee to {}/{}",
+                name, fee.numerator, fee.denominator
+            )
+        }
+        ManagerAction::SetManager {
+            manager,
+            manager_fee_account,
+        } => format!(
+            "set the manager to {} with fee account {}",
+            manager, manager_fee_account
+        ),
+        ManagerAction::SetFundingAuthority(FundingType::StakeDeposit, authority) => {
+            format!("set the stake deposit authority to {}", authority)
+        }
+        ManagerAction::SetFundingAuthority(FundingType::SolDeposit, authority) => {
+            if *authority == Pubkey::default() {
+                "make SOL deposits permissionless".to_string()
+            } else {
+                format!("set the SOL deposit authority to {}", authority)
+            }
+        }
+        ManagerAction::SetManagerTimelock(epochs) => {
+            format!("set the manager timelock to {} epochs", epochs)
+        }
+    }
+}
+
+fn manager_action_json(stake_pool: &StakePool) -> Option<serde_json::Value> {
+    stake_pool
+        .pending_manager_action()
+        .map(|(action, executable_epoch)| {
+            serde_json::json!({
+                "action": display_manager_action(&action),
+                "executableEpoch": executable_epoch,
+            })
+        })
+}
+
+fn command_params(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let reserve_lamports = config.rpc_client.get_balance(&stake_pool.reserve_stake)?;
+    let reserve_available_lamports = reserve_lamports.saturating_sub(
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?,
+    );
+    let optional_pubkey = |pubkey: Option<Pubkey>| pubkey.map(|pubkey| pubkey.to_string());
+
+    if config.output_format.is_json() {
+        let params = serde_json::json!({
+            "stakePool": stake_pool_address.to_string(),
+            "poolMint": stake_pool.pool_mint.to_string(),
+            "validatorList": stake_pool.validator_list.to_string(),
+            "reserveStake": stake_pool.reserve_stake.to_string(),
+            "managerFeeAccount": stake_pool.manager_fee_account.to_string(),
+            "authorities": {
+                "manager": stake_pool.manager.to_string(),
+                "staker": stake_pool.staker.to_string(),
+                "withdrawAuthority": pool_withdraw_authority.to_string(),
+                "stakeDepositAuthority": stake_pool.stake_deposit_authority.to_string(),
+                "solDepositAuthority": optional_pubkey(stake_pool.sol_deposit_authority()),
+            },
+            "fees": {
+                "epoch": fee_json(&stake_pool.epoch_fee),
+                "nextEpoch": fee_json(&stake_pool.next_epoch_fee),
+                "stakeDeposit": fee_json(&stake_pool.stake_deposit_fee),
+                "solDeposit": fee_json(&stake_pool.sol_deposit_fee),
+                "withdrawal": fee_json(&stake_pool.withdrawal_fee),
+                "nextWithdrawal": fee_json(&stake_pool.next_withdrawal_fee),
+                "secondaryFeeAccount": optional_pubkey(stake_pool.secondary_fee_account()),
+                "secondaryFeeSplit": fee_json(&stake_pool.secondary_fee_split),
+                "solDepositFeeTreasury": optional_pubkey(stake_pool.sol_deposit_fee_treasury()),
+            },
+            "preferredValidators": {
+                "deposit": optional_pubkey(stake_pool.preferred_deposit_validator()),
+                "withdraw": optional_pubkey(stake_pool.preferred_withdraw_validator()),
+            },
+            "depositLockEpochs": stake_pool.deposit_lock_epochs,
+            "proportionalWithdrawals": stake_pool.proportional_withdrawals,
+            "paused": stake_pool.paused,
+            "decommissioning": stake_pool.decommissioning,
+            "maxTotalLamports": stake_pool.deposit_cap(),
+            "maxEpochNetOutflowBps": stake_pool.epoch_net_outflow_cap(),
+            "delinquentValidatorEpochs": stake_pool.delinquent_validator_epochs,
+            "validatorOnboardingEpochs": stake_pool.validator_onboarding_epochs,
+            "managerTimelockEpochs": stake_pool.manager_timelock_epochs,
+            "pendingManagerAction": manager_action_json(&stake_pool),
+            "depositInterceptor": stake_pool.deposit_interceptor().map(|deposit_interceptor| {
+                serde_json::json!({
+                    "program": deposit_interceptor.to_string(),
+                    "computeUnits": stake_pool.deposit_interceptor_compute_units,
+                    "strict": stake_pool.deposit_interceptor_strict,
+                })
+            }),
+            "validators": validator_list.validators.len(),
+            "maxValidators": validator_list.max_validators,
+            "reserveLamports": reserve_lamports,
+            "reserveAvailableLamports": reserve_available_lamports,
+            "totalStakeLamports": stake_pool.total_stake_lamports,
+            "poolTokenSupply": stake_pool.pool_token_supply,
+            "lastUpdateEpoch": stake_pool.last_update_epoch,
+        });
+        print_json(config, &params);
+        return Ok(());
+    }
+
+    let display_fee =
+        |fee: &spl_stake_pool::instruction::Fee| format!("{}/{}", fee.numerator, fee.denominator);
+    let display_pubkey = |pubkey: Option<Pubkey>| {
+        pubkey.map_or_else(|| "none".to_string(), |pubkey| pubkey.to_string())
+    };
+    println!("Stake Pool: {}", stake_pool_address);
+    println!("Pool Mint: {}", stake_pool.pool_mint);
+    println!("Manager: {}", stake_pool.manager);
+    println!("Staker: {}", stake_pool.staker);
+    println!("Withdraw Authority: {}", pool_withdraw_authority);
+    println!(
+        "Stake Deposit Authority: {}",
+        stake_pool.stake_deposit_authority
+    );
+    println!(
+        "SOL Deposit Authority: {}",
+        display_pubkey(stake_pool.sol_deposit_authority())
+    );
+    println!(
+        "Epoch Fee: {} (next epoch: {})",
+        display_fee(&stake_pool.epoch_fee),
+        display_fee(&stake_pool.next_epoch_fee)
+    );
+    println!(
+        "Stake Deposit Fee: {}",
+        display_fee(&stake_pool.stake_deposit_fee)
+    );
+    println!(
+        "SOL Deposit Fee: {}{}",
+        display_fee(&stake_pool.sol_deposit_fee),
+        stake_pool
+            .sol_deposit_fee_treasury()
+            .map_or_else(String::new, |treasury| format!(
+                " (in SOL, to treasury {})",
+                treasury
+            ))
+    );
+    println!(
+        "Withdrawal Fee: {} (next epoch: {})",
+        display_fee(&stake_pool.withdrawal_fee),
+        display_fee(&stake_pool.next_withdrawal_fee)
+    );
+    if let Some(secondary_fee_account) = stake_pool.secondary_fee_account() {
+        println!(
+            "Epoch Fee Split: {} to {}",
+            display_fee(&stake_pool.secondary_fee_split),
+            secondary_fee_account
+        );
+    }
+    println!(
+        "Preferred Deposit Validator: {}",
+        display_pubkey(stake_pool.preferred_deposit_validator())
+    );
+    println!(
+        "Preferred Withdraw Validator: {}",
+        display_pubkey(stake_pool.preferred_withdraw_validator())
+    );
+    println!("Deposit Lock: {} epochs", stake_pool.deposit_lock_epochs);
+    println!(
+        "Deposit Interceptor: {}",
+        stake_pool.deposit_interceptor().map_or_else(
+            || "none".to_string(),
+            |deposit_interceptor| format!(
+                "{} ({} compute units, {})",
+                deposit_interceptor,
+                stake_pool.deposit_interceptor_compute_units,
+                if stake_pool.deposit_interceptor_strict {
+                    "strict"
+                } else {
+                    "optional"
+                }
+            )
+        )
+    );
+    println!(
+        "Deposit Cap: {}",
+        stake_pool
+            .deposit_cap()
+            .map_or_else(|| "none".to_string(), |lamports| Sol(lamports).to_string())
+    );
+    println!(
+        "Epoch Net Outflow Cap: {}",
+        stake_pool
+            .epoch_net_outflow_cap()
+            .map_or_else(|| "none".to_string(), |bps| format!("{}bps", bps))
+    );
+    println!(
+        "Proportional Withdrawals: {}",
+        if stake_pool.proportional_withdrawals {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    println!("Paused: {}", if stake_pool.paused { "yes" } else { "no" });
+    println!(
+        "Decommissioning: {}",
+        if stake_pool.decommissioning {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+    println!(
+        "Delinquent Validator Decrease: {}",
+        if stake_pool.delinquent_validator_epochs > 0 {
+            format!("after {} epochs", stake_pool.delinquent_validator_epochs)
+        } else {
+            "disabled".to_string()
+        }
+    );
+    println!(
+        "Validator Onboarding Delay: {}",
+        if stake_pool.validator_onboarding_epochs > 0 {
+            format!("{} epochs", stake_pool.validator_onboarding_epochs)
+        } else {
+            "none".to_string()
+        }
+    );
+    println!(
+        "Manager Timelock: {}",
+        stake_pool
+            .manager_timelock()
+            .map_or_else(|| "none".to_string(), |epochs| format!("{} epochs", epochs))
+    );
+    if let Some((action, executable_epoch)) = stake_pool.pending_manager_action() {
+        println!(
+            "Pending Manager Action: {}, executable from epoch {}",
+            display_manager_action(&action),
+            executable_epoch
+        );
+    }
+    println!(
+        "Validators: {}/{}",
+        validator_list.validators.len(),
+        validator_list.max_validators
+    );
+    println!(
+        "Reserve: {} ({} available)",
+        Sol(reserve_lamports),
+        Sol(reserve_available_lamports)
+    );
+    println!("Total Pool Stake: {}", Sol(stake_pool.total_stake_lamports));
+    println!("Last Update Epoch: {}", stake_pool.last_update_epoch);
+    Ok(())
+}
+
+/// Take a snapshot of the pool for the monitor
+fn monitor_snapshot(config: &Config, stake_pool_address: &Pubkey) -> Result<PoolSnapshot, Error> {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+
+    let delinquent_validators = config
+        .rpc_client
+        .get_vote_accounts()?
+        .delinquent
+        .iter()
+        .filter_map(|vote_account| Pubkey::from_str(&vote_account.vote_pubkey).ok())
+        .filter(|vote_account| {
+            validator_list
+                .find(vote_account)
+                .map_or(false, |validator| validator.is_active())
+        })
+        .collect();
+
+    PoolSnapshot::new(&stake_pool, epoch_info.epoch, delinquent_validators)
+        .ok_or_else(|| "Pool token rate overflows".into())
+}
+
+fn command_monitor(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    webhook: Option<&str>,
+    interval: Duration,
+    max_rate_deviation_bps: u64,
+) -> CommandResult {
+    let http_client = reqwest::blocking::Client::new();
+    let mut monitor = Monitor::new(max_rate_deviation_bps);
+
+    println!("Monitoring stake pool {}", stake_pool_address);
+    loop {
+        // An unreachable RPC node must not stop the monitor
+        match monitor_snapshot(config, stake_pool_address) {
+            Ok(snapshot) => {
+                for alert in monitor.check(snapshot) {
+                    println!("[{}] {}", alert.kind(), alert);
+                    if let Some(webhook) = webhook {
+                        if let Err(err) =
+                            post_alert(&http_client, webhook, stake_pool_address, &alert)
+                        {
+                            eprintln!("Failed to post alert to {}: {}", webhook, err);
+                        }
+                    }
+                }
+            }
+            Err(err) => eprintln!("Failed to read stake pool {}: {}", stake_pool_address, err),
+        }
+        sleep(interval);
+    }
+}
+
+fn command_update(config: &Config, stake_pool_address: &Pubkey, no_merge: bool) -> CommandResult {
+    let signatures = update_stake_pool(config, stake_pool_address, no_merge)?;
+    if config.output_format.is_json() {
+        let epoch_info = config.rpc_client.get_epoch_info()?;
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "epoch": epoch_info.epoch,
+                "updated": !signatures.is_empty(),
+                "signatures": signatures
+                    .iter()
+                    .map(|signature| signature.to_string())
+                    .collect::<Vec<_>>(),
+            }),
+        );
+    }
+    Ok(())
+}
+
+/// Refuse to price deposits and withdrawals on balances that miss the
+/// rewards of the current epoch.  The pool is left stale by `--no-update`, or
+/// by a dry run, whose update is only simulated.
+fn check_pool_fresh(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+) -> CommandResult {
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    if let Freshness::Stale {
+        last_update_epoch,
+        epochs_behind,
+    } = stake_pool.freshness(epoch)
+    {
+        return Err(format!(
+            "Stake pool {} was last updated in epoch {}, {} epoch(s) behind; \
+             run `update` first",
+            stake_pool_address, last_update_epoch, epochs_behind
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn freshness_json(freshness: Freshness) -> serde_json::Value {
+    serde_json::json!({
+        "fresh": freshness.is_fresh(),
+        "epochsBehind": freshness.epochs_behind(),
+    })
+}
+
+/// Update the pool for the current epoch if needed, returning the signatures
+/// of the update transactions
+fn update_stake_pool(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    no_merge: bool,
+) -> Result<Vec<Signature>, Error> {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+
+    if stake_pool.last_update_epoch == epoch_info.epoch {
+        progress!(config, "Update not required");
+        return Ok(vec![]);
+    }
+
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+
+    progress!(config, "Updating stake pool...");
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &stake_pool_address);
+
+    // Each instruction updates a contiguous slice of the list, as large as
+    // the compute budget and the transaction size allow, skipping the slices
+    // that are already up to date
+    let validators_per_update =
+        validators_per_update(&config.fee_payer.pubkey(), MAX_INSTRUCTION_COMPUTE_UNITS);
+    let chunks = plan_update(
+        &validator_list.validators,
+        epoch_info.epoch,
+        validators_per_update,
+    );
+    if config.verbose {
+        progress!(
+            config,
+            "Updating {} validator(s) in {} instruction(s) of up to {} validators",
+            chunks
+                .iter()
+                .map(|chunk| chunk.vote_accounts.len())
+                .sum::<usize>(),
+            chunks.len(),
+            validators_per_update
+        );
+    }
+    let mut instructions: Vec<Instruction> = vec![];
+    for chunk in chunks {
+        instructions.push(spl_stake_pool::instruction::update_validator_list_balance(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &withdraw_authority,
+            &stake_pool.validator_list,
+            &stake_pool.reserve_stake,
+            &chunk.vote_accounts,
+            chunk.start_index,
+            no_merge,
+        )?);
+    }
+
+    let mut update_balance_instruction = spl_stake_pool::instruction::update_stake_pool_balance(
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &stake_pool.validator_list,
+        &stake_pool.reserve_stake,
+        &withdraw_authority,
+        &stake_pool.manager_fee_account,
+        &stake_pool.pool_mint,
+    )?;
+    if let Some(secondary_fee_account) = stake_pool.secondary_fee_account() {
+        spl_stake_pool::instruction::add_secondary_fee_account(
+            &mut update_balance_instruction,
+            &secondary_fee_account,
+        );
+    }
+    spl_stake_pool::instruction::add_price_oracle_accounts(
+        &mut update_balance_instruction,
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &config.fee_payer.pubkey(),
+    );
+    spl_stake_pool::instruction::add_rate_history_accounts(
+        &mut update_balance_instruction,
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &config.fee_payer.pubkey(),
+    );
+
+    // TODO: A faster solution would be to send all the `update_validator_list_balance` instructions concurrently
+    let mut signatures = vec![];
+    for message in TransactionPacker::new(config.fee_payer.pubkey()).pack(instructions)? {
+        signatures.push(send_message_with_retry(config, message)?);
+    }
+    // The pool balance can only be updated once the whole list is, so it
+    // goes last, on its own
+    signatures.push(send_message_with_retry(
+        config,
+        Message::new(
+            &[update_balance_instruction],
+            Some(&config.fee_payer.pubkey()),
+        ),
+    )?);
+    Ok(signatures)
+}
+
+#[derive(PartialEq, Debug)]
+struct WithdrawAccount {
+    address: Pubkey,
+    pool_amount: u64,
+}
+
+/// Split a withdrawal the way a pool with proportional withdrawals requires,
+/// always taking from the validator furthest above its target weight
+fn prepare_proportional_withdraw_accounts(
+    rpc_client: &RpcClient,
+    stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
+    pool_amount: u64,
+) -> Result<Vec<WithdrawAccount>, Error> {
+    let mut validator_list = get_validator_list(rpc_client, &stake_pool.validator_list)?;
+    let stake_rent = rpc_client.get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let pool_mint = get_token_mint(rpc_client, &stake_pool.pool_mint)?;
+
+    // Track the pool as each withdrawal lands, since the next one is checked
+    // against the updated balances
+    let mut stake_pool = stake_pool.clone();
+    let withdraw_amounts = |stake_pool: &StakePool, pool_amount| {
+        let (burn_amount, _) =
+            stake_pool.split_pool_tokens(FeeOperation::Withdrawal, pool_amount)?;
+        let lamports = stake_pool.calc_lamports_withdraw_amount_floor(burn_amount)?;
+        Some((burn_amount, lamports))
+    };
+    let mut withdraw_from: Vec<WithdrawAccount> = vec![];
+    let mut remaining_amount = pool_amount;
+    while remaining_amount > 0 {
+        let (_, lamports) = withdraw_amounts(&stake_pool, remaining_amount)
+            .ok_or("Pool token amount is too large")?;
+
+        // Withdraw everything from the most overweight validator that can
+        // cover it, or else as much as possible from the most overweight one
+        let (vote_account, pool_amount) = match validator_list.most_overweight(
+            stake_pool.total_stake_lamports,
+            lamports,
+            stake_rent,
+        ) {
+            Some(item) => (item.vote_account, remaining_amount),
+            None => {
+                let pool_amount = validator_list
+                    .most_overweight(stake_pool.total_stake_lamports, 1, stake_rent)
+                    .and_then(|item| {
+                        stake_pool
+                            .calc_pool_tokens_for_deposit_floor(
+                                u64::from(item.active_stake_lamports) - stake_rent,
+                            )
+                            .map(|pool_amount| {
+                                (item.vote_account, u64::min(pool_amount, remaining_amount))
+                            })
+                    })
+                    .filter(|(_, pool_amount)| *pool_amount > 0);
+                match pool_amount {
+                    Some(pool_amount) => pool_amount,
+                    None => break,
+                }
+            }
+        };
+
+        let (burn_amount, lamports) =
+            withdraw_amounts(&stake_pool, pool_amount).ok_or("Pool token amount is too large")?;
+        let item = validator_list.find_mut(&vote_account).unwrap();
+        item.active_stake_lamports = (u64::from(item.active_stake_lamports) - lamports).into();
+        stake_pool.total_stake_lamports -= lamports;
+        stake_pool.pool_token_supply -= burn_amount;
+
+        let (address, _) =
+            find_stake_program_address(&spl_stake_pool::id(), &vote_account, stake_pool_address);
+        withdraw_from.push(WithdrawAccount {
+            address,
+            pool_amount,
+        });
+        remaining_amount -= pool_amount;
+    }
+
+    if remaining_amount > 0 {
+        return Err(format!(
+            "No stake accounts found in this pool with enough balance to withdraw {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    Ok(withdraw_from)
+}
+
+/// Withdraw everything from the reserve, which the pool only allows once no
+/// validator holds more than the minimum stake
+fn prepare_reserve_withdraw_account(
+    rpc_client: &RpcClient,
+    stake_pool: &StakePool,
+    pool_amount: u64,
+) -> Result<Option<WithdrawAccount>, Error> {
+    let validator_list = get_validator_list(rpc_client, &stake_pool.validator_list)?;
+    let stake_rent = rpc_client.get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    if validator_list.validators.iter().any(|item| {
+        item.is_active()
+            && (u64::from(item.active_stake_lamports) > stake_rent + MINIMUM_ACTIVE_STAKE
+                || u64::from(item.transient_stake_lamports) > 0)
+    }) {
+        return Ok(None);
+    }
+
+    let lamports = stake_pool
+        .split_pool_tokens(FeeOperation::Withdrawal, pool_amount)
+        .and_then(|(burn_amount, _)| stake_pool.calc_lamports_withdraw_amount_floor(burn_amount))
+        .ok_or("Pool token amount is too large")?;
+    let reserve_lamports = rpc_client
+        .get_balance(&stake_pool.reserve_stake)?
+        .saturating_sub(stake_rent);
+    if lamports > reserve_lamports {
+        return Ok(None);
+    }
+
+    Ok(Some(WithdrawAccount {
+        address: stake_pool.reserve_stake,
+        pool_amount,
+    }))
+}
+
+fn prepare_withdraw_accounts(
+    rpc_client: &RpcClient,
+    stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
+    pool_amount: u64,
+) -> Result<Vec<WithdrawAccount>, Error> {
+    if let Some(reserve_account) =
+        prepare_reserve_withdraw_account(rpc_client, stake_pool, pool_amount)?
+    {
+        return Ok(vec![reserve_account]);
+    }
+
+    if stake_pool.proportional_withdrawals {
+        return prepare_proportional_withdraw_accounts(
+            rpc_client,
+            stake_pool,
+            stake_pool_address,
+            pool_amount,
+        );
+    }
+
+    // The validator list was just updated, so its balances match the validator
+    // stake accounts
+    let validator_list = get_validator_list(rpc_client, &stake_pool.validator_list)?;
+    let mut accounts: Vec<(Pubkey, u64)> = validator_list
+        .validators
+        .iter()
+        .filter(|item| item.is_active())
+        .map(|item| {
+            let (address, _) = find_stake_program_address(
+                &spl_stake_pool::id(),
+                &item.vote_account,
+                stake_pool_address,
+            );
+            (address, u64::from(item.active_stake_lamports))
+        })
+        .collect();
+    if accounts.is_empty() {
+        return Err("No accounts found.".to_string().into());
+    }
+    // Stake accounts kept by the pool must hold rent-exemption plus the
+    // minimum active stake after a partial withdrawal
+    let stake_rent = rpc_client.get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let minimum_lamports = stake_rent + *MIN_STAKE_BALANCE;
+    let pool_mint = get_token_mint(rpc_client, &stake_pool.pool_mint)?;
+
+    // Sort from highest to lowest balance
+    accounts.sort_by(|a, b| b.1.cmp(&a.1));
+
+    // The pool requires withdrawing from the preferred validator first
+    if let Some(preferred_withdraw_validator) = stake_pool.preferred_withdraw_validator() {
+        let (preferred_stake_account, _) = find_stake_program_address(
+            &spl_stake_pool::id(),
+            &preferred_withdraw_validator,
+            stake_pool_address,
+        );
+        if let Some(index) = accounts
+            .iter()
+            .position(|(address, _)| *address == preferred_stake_account)
+        {
+            let preferred_account = accounts.remove(index);
+            accounts.insert(0, preferred_account);
+        }
+    }
+
+    // Prepare the list of accounts to withdraw from
+    let mut withdraw_from: Vec<WithdrawAccount> = vec![];
+    let mut remaining_amount = pool_amount;
+
+    // Go through available accounts and withdraw from largest to smallest
+    for (address, lamports) in accounts {
+        if lamports <= minimum_lamports {
+            continue;
+        }
+        let available_for_withdrawal = stake_pool
+            .calc_lamports_withdraw_amount_floor(lamports - minimum_lamports)
+            .unwrap();
+        let pool_amount = u64::min(available_for_withdrawal, remaining_amount);
+
+        // Those accounts will be withdrawn completely with `claim` instruction
+        withdraw_from.push(WithdrawAccount {
+            address,
+            pool_amount,
+        });
+        remaining_amount -= pool_amount;
+
+        if remaining_amount == 0 {
+            break;
+        }
+    }
+
+    // Once every validator is down to its minimum, the pool falls back to
+    // transient stake
+    if remaining_amount > 0 {
+        for item in validator_list
+            .validators
+            .iter()
+            .filter(|item| u64::from(item.transient_stake_lamports) > 0)
+        {
+            let (transient_stake_address, _) = find_transient_stake_program_address(
+                &spl_stake_pool::id(),
+                &item.vote_account,
+                stake_pool_address,
+            );
+            // A transient account is either withdrawn completely, or must keep
+            // the minimum after a partial split
+            let lamports = rpc_client.get_balance(&transient_stake_address)?;
+            let full_amount = stake_pool
+                .calc_lamports_withdraw_amount_floor(lamports)
+                .unwrap();
+            let pool_amount = if full_amount <= remaining_amount {
+                full_amount
+            } else if lamports > minimum_lamports {
+                stake_pool
+                    .calc_lamports_withdraw_amount_floor(lamports - minimum_lamports)
+                    .unwrap()
+                    .min(remaining_amount)
+            } else {
+                continue;
+            };
+            if pool_amount == 0 {
+                continue;
+            }
+
+            withdraw_from.push(WithdrawAccount {
+                address: transient_stake_address,
+                pool_amount,
+            });
+            remaining_amount -= pool_amount;
+
+            if remaining_amount == 0 {
+                break;
+            }
+        }
+    }
+
+    // Not enough stake to withdraw the specified amount
+    if remaining_amount > 0 {
+        return Err(format!(
+            "No stake accounts found in this pool with enough balance to withdraw {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    Ok(withdraw_from)
+}
+
+fn command_withdraw(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    pool_amount: f64,
+    withdraw_from: &Pubkey,
+    stake_receiver_param: &Option<Pubkey>,
+) -> CommandResult {
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    check_pool_fresh(config, stake_pool_address, &stake_pool)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+
+    // Check withdraw_from account type
+    let token_account =
+        get_token_account(&config.rpc_client, &withdraw_from, &stake_pool.pool_mint)?;
+
+    // Check withdraw_from balance
+    if token_account.amount < pool_amount {
+        return Err(format!(
+            "Not enough token balance to withdraw {} pool tokens.\nMaximum withdraw amount is {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    // Get the list of accounts to withdraw from
+    let withdraw_accounts = prepare_withdraw_accounts(
+        &config.rpc_client,
+        &stake_pool,
+        stake_pool_address,
+        pool_amount,
+    )?;
+
+    // Each withdrawal splits into its own uninitialized stake account, so a
+    // given receiver can only take one of them
+    if stake_receiver_param.is_some() && withdraw_accounts.len() > 1 {
+        return Err(format!(
+            "Withdrawal is split across {} stake accounts, but only one stake receiver was given.\n\
+             Omit --stake-receiver to create a new stake account for each of them.",
+            withdraw_accounts.len()
+        )
+        .into());
+    }
+
+    // Construct transaction to withdraw from withdraw_accounts account list
+    let mut instructions: Vec<Instruction> = vec![];
+    let stake_receiver_accounts: Vec<Keypair> = if stake_receiver_param.is_some() {
+        vec![]
+    } else {
+        withdraw_accounts.iter().map(|_| Keypair::new()).collect()
+    };
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+
+    instructions.push(
+        // Approve spending token
+        spl_token::instruction::approve(
+            &spl_token::id(),
+            &withdraw_from,
+            &pool_withdraw_authority,
+            &config.token_owner.pubkey(),
+            &[],
+            pool_amount,
+        )?,
+    );
+
+    let stake_receiver_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let mut total_rent_free_balances = 0;
+    let mut withdrawals = vec![];
+
+    // Go through prepared accounts and withdraw/claim them
+    for (index, withdraw_account) in withdraw_accounts.iter().enumerate() {
+        // Convert pool tokens amount to lamports
+        let sol_withdraw_amount = stake_pool
+            .calc_lamports_withdraw_amount_floor(withdraw_account.pool_amount)
+            .unwrap();
+
+        progress!(
+            config,
+            "Withdrawing from account {}, amount {}, {} pool tokens",
+            withdraw_account.address,
+            Sol(sol_withdraw_amount),
+            spl_token::amount_to_ui_amount(withdraw_account.pool_amount, pool_mint.decimals),
+        );
+
+        let stake_receiver = match stake_receiver_param {
+            Some(stake_receiver) => *stake_receiver,
+            None => {
+                // Account for tokens not specified, creating one
+                let stake_receiver_account = &stake_receiver_accounts[index];
+                progress!(
+                    config,
+                    "Creating account to receive stake {}",
+                    stake_receiver_account.pubkey()
+                );
+
+                instructions.push(
+                    // Creating new account
+                    system_instruction::create_account(
+                        &config.fee_payer.pubkey(),
+                        &stake_receiver_account.pubkey(),
+                        stake_receiver_account_balance,
+                        STAKE_STATE_LEN as u64,
+                        &stake_program::id(),
+                    ),
+                );
+
+                signers.push(stake_receiver_account);
+
+                total_rent_free_balances += stake_receiver_account_balance;
+
+                stake_receiver_account.pubkey()
+            }
+        };
+
+        let withdraw_instruction = spl_stake_pool::instruction::withdraw(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &stake_pool.validator_list,
+            &pool_withdraw_authority,
+            &withdraw_account.address,
+            &stake_receiver,
+            &config.staker.pubkey(),
+            &withdraw_from,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &spl_token::id(),
+            withdraw_account.pool_amount,
+        )?;
+        instructions.push(withdraw_instruction);
+        withdrawals.push(serde_json::json!({
+            "stakeAccount": withdraw_account.address.to_string(),
+            "stakeReceiver": stake_receiver.to_string(),
+            "lamports": sol_withdraw_amount,
+            "poolTokens": withdraw_account.pool_amount,
+        }));
+    }
+
+    // All withdrawals go in one transaction, so the user never ends up with
+    // only part of the amount withdrawn
+    let mut transaction =
+        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+    let transaction_size = bincode::serialized_size(&transaction)? as usize;
+    if transaction_size > PACKET_DATA_SIZE {
+        return Err(format!(
+            "Withdrawal is split across {} stake accounts, which doesn't fit in one transaction.\n\
+             Withdraw a smaller amount at a time.",
+            withdraw_accounts.len()
+        )
+        .into());
+    }
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    if config.dry_run {
+        let mut dry_run = DryRun::default();
+        dry_run.record(
+            stake_pool_address,
+            &simulate_transaction(config, &transaction)?,
+        );
+        return print_dry_run(config, stake_pool_address, &stake_pool, &dry_run);
+    }
+    let signature = send_transaction(&config, transaction)?;
+    if config.output_format.is_json() {
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "withdrawFrom": withdraw_from.to_string(),
+                "withdrawals": withdrawals,
+                "rate": rate_json(&stake_pool),
+                "signature": signature.to_string(),
+            }),
+        );
+    }
+    Ok(())
+}
+
+fn command_set_manager(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    new_manager: &Option<Pubkey>,
+    new_fee_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+
+    // If new accounts are missing in the arguments use the old ones
+    let new_manager = match new_manager {
+        None => stake_pool.manager,
+        Some(value) => *value,
+    };
+    let new_fee_receiver = match new_fee_receiver {
+        None => stake_pool.manager_fee_account,
+        Some(value) => {
+            // Check for fee receiver being a valid token account and have to same mint as the stake pool
+            let token_account =
+                get_token_account(&config.rpc_client, value, &stake_pool.pool_mint)?;
+            if token_account.mint != stake_pool.pool_mint {
+                return Err("Fee receiver account belongs to a different mint"
+                    .to_string()
+                    .into());
+            }
+            *value
+        }
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_manager(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            &new_manager,
+            &new_fee_receiver,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_staker(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    new_staker: &Pubkey,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_staker(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            &new_staker,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_burn(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    pool_amount: f64,
+    burn_from: &Pubkey,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+
+    let token_account = get_token_account(&config.rpc_client, &burn_from, &stake_pool.pool_mint)?;
+    if token_account.amount < pool_amount {
+        return Err(format!(
+            "Not enough token balance to burn {} pool tokens.\nMaximum burn amount is {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::burn_pool_tokens(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &burn_from,
+            &config.token_owner.pubkey(),
+            &stake_pool.pool_mint,
+            &spl_token::id(),
+            pool_amount,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_fee(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    fee_type: spl_stake_pool::instruction::FeeType,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_fee(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            fee_type,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_fee_split(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    secondary_fee_account: Option<Pubkey>,
+    split: spl_stake_pool::instruction::Fee,
+) -> CommandResult {
+    if let Some(secondary_fee_account) = secondary_fee_account {
+        let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+        get_token_account(
+            &config.rpc_client,
+            &secondary_fee_account,
+            &stake_pool.pool_mint,
+        )?;
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_fee_split(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            secondary_fee_account.as_ref(),
+            split,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_sol_deposit_fee_treasury(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    treasury: Option<Pubkey>,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_sol_deposit_fee_treasury(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            treasury.as_ref(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_deposit_interceptor(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    deposit_interceptor: Option<Pubkey>,
+    compute_units: u32,
+    strict: bool,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_deposit_interceptor(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            deposit_interceptor.as_ref(),
+            compute_units,
+            strict,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_preferred_validator(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    validator_type: spl_stake_pool::instruction::PreferredValidatorType,
+    vote_account: Option<Pubkey>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_preferred_validator(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.staker.pubkey(),
+            &stake_pool.validator_list,
+            validator_type,
+            vote_account,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_funding_authority(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    new_authority: Option<Pubkey>,
+    funding_type: spl_stake_pool::instruction::FundingType,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_funding_authority(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            new_authority.as_ref(),
+            funding_type,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_deposit_lock(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    deposit_lock_epochs: u64,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_deposit_lock(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            deposit_lock_epochs,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_claim_locked_deposit(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    token_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let (deposit_record, _) = find_deposit_record_address(
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &config.token_owner.pubkey(),
+    );
+    let escrow = get_associated_token_address(&deposit_record, &stake_pool.pool_mint);
+    let pool_tokens = get_token_account(&config.rpc_client, &escrow, &stake_pool.pool_mint)?.amount;
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut total_rent_free_balances: u64 = 0;
+    let token_receiver = match token_receiver {
+        Some(token_receiver) => *token_receiver,
+        None => {
+            let token_receiver =
+                get_associated_token_address(&config.token_owner.pubkey(), &stake_pool.pool_mint);
+            if config
+                .rpc_client
+                .get_account_with_commitment(&token_receiver, CommitmentConfig::confirmed())?
+                .value
+                .is_none()
+            {
+                progress!(
+                    config,
+                    "Creating associated token account to receive tokens {}",
+                    token_receiver
+                );
+                instructions.push(create_associated_token_account(
+                    &config.fee_payer.pubkey(),
+                    &config.token_owner.pubkey(),
+                    &stake_pool.pool_mint,
+                ));
+                total_rent_free_balances += config
+                    .rpc_client
+                    .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+            }
+            token_receiver
+        }
+    };
+    instructions.push(spl_stake_pool::instruction::claim_locked_pool_tokens(
+        &spl_stake_pool::id(),
+        stake_pool_address,
+        &config.token_owner.pubkey(),
+        &escrow,
+        &token_receiver,
+        &spl_token::id(),
+    )?);
+
+    let mut transaction =
+        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    let signature = send_transaction(&config, transaction)?;
+    if config.output_format.is_json() {
+        print_json(
+            config,
+            &serde_json::json!({
+                "stakePool": stake_pool_address.to_string(),
+                "escrow": escrow.to_string(),
+                "tokenReceiver": token_receiver.to_string(),
+                "poolTokens": pool_tokens,
+                "signature": signature.to_string(),
+            }),
+        );
+    }
+    Ok(())
+}
+
+fn command_set_max_epoch_outflow(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_epoch_net_outflow_bps: u16,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_max_epoch_net_outflow(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            max_epoch_net_outflow_bps,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_deposit_cap(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_total_sol: f64,
+) -> CommandResult {
+    let max_total_lamports = native_token::sol_to_lamports(max_total_sol);
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_deposit_cap(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            max_total_lamports,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_delinquent_validator_epochs(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    delinquent_validator_epochs: u64,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            spl_stake_pool::instruction::set_delinquent_validator_epochs(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &config.manager.pubkey(),
+                delinquent_validator_epochs,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_validator_onboarding_epochs(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    validator_onboarding_epochs: u64,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            spl_stake_pool::instruction::set_validator_onboarding_epochs(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &config.manager.pubkey(),
+                validator_onboarding_epochs,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_manager_timelock(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    manager_timelock_epochs: u64,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_manager_timelock(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            manager_timelock_epochs,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_execute_manager_action(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let (action, executable_epoch) = stake_pool
+        .pending_manager_action()
+        .ok_or("No manager action is pending")?;
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    if epoch < executable_epoch {
+        return Err(format!(
+            "Pending manager action can be executed from epoch {}, current epoch is {}",
+            executable_epoch, epoch
+        )
+        .into());
+    }
+    println!("Executing: {}", display_manager_action(&action));
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::execute_manager_action(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    transaction.try_sign(&[config.fee_payer.as_ref()], recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_cancel_manager_action(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::cancel_manager_action(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_increase_validator_list_size(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_validators: u32,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+
+    let new_validator_list = Keypair::new();
+    println!("Moving validator list to {}", new_validator_list.pubkey());
+
+    let validator_list_size = get_instance_packed_len(&ValidatorList::new(max_validators))?;
+    let validator_list_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(validator_list_size)?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &new_validator_list.pubkey(),
+                validator_list_balance,
+                validator_list_size as u64,
+                &spl_stake_pool::id(),
+            ),
+            spl_stake_pool::instruction::increase_validator_list_size(
+                &spl_stake_pool::id(),
+                &stake_pool_address,
+                &config.staker.pubkey(),
+                &stake_pool.validator_list,
+                &new_validator_list.pubkey(),
+                &config.fee_payer.pubkey(),
+                max_validators,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        validator_list_balance + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.staker.as_ref(),
+        &new_validator_list,
+    ];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_validator_target(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: Pubkey,
+    weight_bps: u16,
+) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_validator_targets(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.staker.pubkey(),
+            &stake_pool.validator_list,
+            vec![spl_stake_pool::instruction::ValidatorTarget {
+                vote_account,
+                weight_bps,
+            }],
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_proportional_withdrawals(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    enabled: bool,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_proportional_withdrawals(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            enabled,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_set_paused(config: &Config, stake_pool_address: &Pubkey, paused: bool) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::set_paused(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            paused,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_decommission_pool(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::decommission_stake_pool(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_close_pool(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    lamports_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    if !config.no_update {
+        update_stake_pool(config, stake_pool_address, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
+    let lamports_receiver = lamports_receiver.unwrap_or_else(|| config.manager.pubkey());
+
+    let mut lamports = 0;
+    for address in &[
+        *stake_pool_address,
+        stake_pool.validator_list,
+        stake_pool.reserve_stake,
+    ] {
+        lamports += config.rpc_client.get_balance(address)?;
+    }
+    println!(
+        "Closing stake pool {}, {} will go to {}",
+        stake_pool_address,
+        Sol(lamports),
+        lamports_receiver
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::close_stake_pool(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            &stake_pool.validator_list,
+            &pool_withdraw_authority,
+            &stake_pool.reserve_stake,
+            &lamports_receiver,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_create_single_validator_pool(config: &Config, vote_account: &Pubkey) -> CommandResult {
+    let (pool_address, _) = find_single_validator_pool_address(&spl_stake_pool::id(), vote_account);
+    if config.rpc_client.get_account(&pool_address).is_ok() {
+        return Err(format!(
+            "Single-validator pool {} already exists for vote account {}",
+            pool_address, vote_account
+        )
+        .into());
+    }
+    let (pool_mint, _) =
+        find_single_validator_pool_mint_address(&spl_stake_pool::id(), &pool_address);
+    println!("Creating single-validator pool {}", pool_address);
+    println!("Pool token mint: {}", pool_mint);
+
+    let seed_stake_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?
+        + MINIMUM_ACTIVE_STAKE;
+    let pool_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(get_packed_len::<SingleValidatorPool>())?;
+    let mint_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
+    let total_rent_free_balances = seed_stake_balance + pool_account_balance + mint_account_balance;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            spl_stake_pool::instruction::initialize_single_validator_pool(
+                &spl_stake_pool::id(),
+                vote_account,
+                &config.fee_payer.pubkey(),
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![config.fee_payer.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_deposit_single_validator(
+    config: &Config,
+    vote_account: &Pubkey,
+    stake: &Pubkey,
+    token_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let (pool_address, _) = find_single_validator_pool_address(&spl_stake_pool::id(), vote_account);
+    let (pool_mint, _) =
+        find_single_validator_pool_mint_address(&spl_stake_pool::id(), &pool_address);
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &pool_address).0;
+
+    match get_stake_state(&config.rpc_client, stake)? {
+        StakeState::Stake(_, delegated) if delegated.delegation.voter_pubkey == *vote_account => {}
+        _ => {
+            return Err(format!(
+                "Stake account {} must be delegated to {} to join its single-validator pool",
+                stake, vote_account
+            )
+            .into())
+        }
+    }
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+
+    let mut total_rent_free_balances: u64 = 0;
+
+    let token_receiver_account = Keypair::new();
+
+    // Create token account if not specified
+    let token_receiver = unwrap_create_token_account(
+        &config,
+        &token_receiver,
+        &token_receiver_account,
+        &pool_mint,
+        &mut instructions,
+        |balance| {
+            signers.push(&token_receiver_account);
+            total_rent_free_balances += balance;
+        },
+    )?;
+
+    println!(
+        "Depositing {} into single-validator pool {}",
+        stake, pool_address
+    );
+    instructions.extend(vec![
+        // Set Withdrawer on stake account to the withdraw authority of the pool
+        stake_program::authorize(
+            &stake,
+            &config.staker.pubkey(),
+            &pool_withdraw_authority,
+            StakeAuthorize::Withdrawer,
+        ),
+        // Set Staker on stake account to the withdraw authority of the pool
+        stake_program::authorize(
+            &stake,
+            &config.staker.pubkey(),
+            &pool_withdraw_authority,
+            StakeAuthorize::Staker,
+        ),
+        spl_stake_pool::instruction::deposit_single_validator(
+            &spl_stake_pool::id(),
+            vote_account,
+            stake,
+            &token_receiver,
+        )?,
+    ]);
+
+    let mut transaction =
+        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_withdraw_single_validator(
+    config: &Config,
+    vote_account: &Pubkey,
+    pool_amount: f64,
+    withdraw_from: &Pubkey,
+    stake_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    let (pool_address, _) = find_single_validator_pool_address(&spl_stake_pool::id(), vote_account);
+    let (pool_mint_address, _) =
+        find_single_validator_pool_mint_address(&spl_stake_pool::id(), &pool_address);
+    let pool_withdraw_authority =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &pool_address).0;
+
+    let pool_mint = get_token_mint(&config.rpc_client, &pool_mint_address)?;
+    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+    let token_account = get_token_account(&config.rpc_client, &withdraw_from, &pool_mint_address)?;
+    if token_account.amount < pool_amount {
+        return Err(format!(
+            "Not enough token balance to withdraw {} pool tokens.\nMaximum withdraw amount is {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let stake_receiver_account = Keypair::new(); // Will be added to signers if creating new account
+
+    let mut total_rent_free_balances = 0;
+
+    instructions.push(
+        // Approve spending token
+        spl_token::instruction::approve(
+            &spl_token::id(),
+            &withdraw_from,
+            &pool_withdraw_authority,
+            &config.token_owner.pubkey(),
+            &[],
+            pool_amount,
+        )?,
+    );
+
+    let stake_receiver = match stake_receiver {
+        Some(stake_receiver) => *stake_receiver,
+        None => {
+            println!(
+                "Creating account to receive stake {}",
+                stake_receiver_account.pubkey()
+            );
+            let stake_receiver_account_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+            instructions.push(system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &stake_receiver_account.pubkey(),
+                stake_receiver_account_balance,
+                STAKE_STATE_LEN as u64,
+                &stake_program::id(),
+            ));
+            signers.push(&stake_receiver_account);
+            total_rent_free_balances += stake_receiver_account_balance;
+            stake_receiver_account.pubkey()
+        }
+    };
+
+    println!(
+        "Withdrawing {} pool tokens from single-validator pool {}",
+        spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+        pool_address
+    );
+    instructions.push(spl_stake_pool::instruction::withdraw_single_validator(
+        &spl_stake_pool::id(),
+        vote_account,
+        &stake_receiver,
+        &config.staker.pubkey(),
+        withdraw_from,
+        pool_amount,
+    )?);
+
+    let mut transaction =
+        Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        total_rent_free_balances + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn check_token_metadata(name: &str, symbol: &str, uri: &str) -> Result<(), Error> {
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(format!("Token name must be at most {} bytes", MAX_NAME_LENGTH).into());
+    }
+    if symbol.len() > MAX_SYMBOL_LENGTH {
+        return Err(format!("Token symbol must be at most {} bytes", MAX_SYMBOL_LENGTH).into());
+    }
+    if uri.len() > MAX_URI_LENGTH {
+        return Err(format!("Metadata URI must be at most {} bytes", MAX_URI_LENGTH).into());
+    }
+    Ok(())
+}
+
+fn command_create_token_metadata(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> CommandResult {
+    check_token_metadata(&name, &symbol, &uri)?;
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::create_token_metadata(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            &stake_pool.pool_mint,
+            &config.fee_payer.pubkey(),
+            name,
+            symbol,
+            uri,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn command_update_token_metadata(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> CommandResult {
+    check_token_metadata(&name, &symbol, &uri)?;
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_stake_pool::instruction::update_token_metadata(
+            &spl_stake_pool::id(),
+            &stake_pool_address,
+            &config.manager.pubkey(),
+            &stake_pool.pool_mint,
+            name,
+            symbol,
+            uri,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+    send_transaction(&config, transaction)?;
+    Ok(())
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transactions instead of executing them. \
+                       `deposit`, `withdraw` and `rebalance` report the pool tokens, \
+                       fees, exchange rate and compute units they would use."),
+        )
+        .arg(
+            Arg::with_name("no_update")
+                .long("no-update")
                 .takes_value(false)
                 .global(true)
-                .help("Simluate transaction instead of executing"),
+                .help("Do not automatically update the stake pool if needed"),
+        )
+        .arg(
+            Arg::with_name("output_format")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["display", "json", "json-compact"])
+                .help("Return information in specified output format, json for `list`, `params`, `deposit`, `withdraw` and `update`"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            Arg::with_name("staker")
+                .long("staker")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the stake pool staker. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("manager")
+                .long("manager")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the stake pool manager. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("token_owner")
+                .long("token-owner")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the owner of the pool token account. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("depositor")
+                .long("depositor")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the stake deposit authority of a private stake pool. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger.",
+                ),
+        )
+        .arg(
+            Arg::with_name("vote_withdrawer")
+                .long("vote-withdrawer")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .help(
+                    "Specify the withdraw authority of a validator's vote account, \
+                     to leave a pool without the staker. \
+                     This may be a keypair file, the ASK keyword \
+                     or a hardware wallet URL such as usb://ledger. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .subcommand(SubCommand::with_name("create-pool")
+            .about("Create a new stake pool")
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .long("fee-numerator")
+                    .short("n")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .long("fee-denominator")
+                    .short("d")
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("max_validators")
+                    .long("max-validators")
+                    .short("m")
+                    .validator(is_parsable::<u32>)
+                    .value_name("NUMBER")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Max number of validators included in the stake pool"),
+            )
+            .arg(
+                Arg::with_name("deposit_authority")
+                    .long("deposit-authority")
+                    .short("a")
+                    .validator(is_pubkey)
+                    .value_name("DEPOSIT_AUTHORITY_ADDRESS")
+                    .takes_value(true)
+                    .help("Deposit authority required to sign all deposits into the stake pool"),
+            )
+        )
+        .subcommand(SubCommand::with_name("create-validator-stake")
+            .about("Create a new stake account to use with the pool. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("The validator vote account that this stake will be delegated to"),
+            )
+        )
+        .subcommand(SubCommand::with_name("add-validator")
+            .about("Add validator account to the stake pool. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("stake_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake account to add to the pool"),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                          Defaults to the new pool token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("remove-validator")
+            .about("Remove validator account from the stake pool, returning its stake to the reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("stake_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake account to remove from the pool"),
+            )
+        )
+        .subcommand(SubCommand::with_name("leave-pool")
+            .about("Remove a validator from the stake pool, returning its stake to the reserve. Must be signed by the withdraw authority of the validator's vote account.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account of the validator leaving the pool"),
+            )
+        )
+        .subcommand(SubCommand::with_name("increase-validator-stake")
+            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to increase stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to move from the reserve to the validator"),
+            )
+        )
+        .subcommand(SubCommand::with_name("decrease-validator-stake")
+            .about("Decrease stake to a validator, moving it back to the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to decrease stake from"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to move from the validator to the reserve"),
+            )
+        )
+        .subcommand(SubCommand::with_name("decrease-delinquent-validator-stake")
+            .about("Decrease all stake on a delinquent validator, moving it back to the stake pool reserve. Anyone can run this once the pool allows it.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the delinquent validator"),
+            )
+        )
+        .subcommand(SubCommand::with_name("apply-targets")
+            .about("Rebalance a validator toward its target weight, from its stake or the stake pool reserve. Anyone can run this once the staker has set target weights.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to rebalance"),
+            )
+        )
+        .subcommand(SubCommand::with_name("rebalance")
+            .about("Move stake between the stake pool reserve and the validators to match a strategy, rebalancing every validator whose transient stake account is free. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("proportional")
+                    .long("proportional")
+                    .takes_value(false)
+                    .help("Follow the target weights set by the staker, keeping the rest in the reserve"),
+            )
+            .arg(
+                Arg::with_name("equalize")
+                    .long("equalize")
+                    .takes_value(false)
+                    .help("Give every active validator the same stake"),
+            )
+            .arg(
+                Arg::with_name("weights")
+                    .long("weights")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("JSON file mapping vote accounts to relative weights, validators left out keep only their minimum stake"),
+            )
+            .arg(
+                Arg::with_name("score_strategy")
+                    .long("score-strategy")
+                    .value_name("STRATEGY")
+                    .possible_values(&["proportional-to-score", "equal-weight", "stake-concentration-aware"])
+                    .takes_value(true)
+                    .help("Weight validators by score: in proportion to it, equally above --min-score, \
+                          or in proportion to it while avoiding validators holding a large share of the cluster's stake"),
+            )
+            .arg(
+                Arg::with_name("scores")
+                    .long("scores")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .requires("score_strategy")
+                    .help("validators.app style JSON score file for --score-strategy. \
+                          Defaults to scoring the cluster's vote accounts by their latest vote credits, less commission."),
+            )
+            .arg(
+                Arg::with_name("min_score")
+                    .long("min-score")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SCORE")
+                    .takes_value(true)
+                    .default_value("1")
+                    .help("Lowest score getting stake with the equal-weight strategy."),
+            )
+            .arg(
+                Arg::with_name("max_network_share")
+                    .long("max-network-share")
+                    .validator(is_parsable::<u64>)
+                    .value_name("BASIS_POINTS")
+                    .takes_value(true)
+                    .default_value("300")
+                    .help("Share of the cluster's stake at which the stake-concentration-aware strategy stops giving stake to a validator, in basis points."),
+            )
+            .group(ArgGroup::with_name("strategy")
+                .arg("proportional")
+                .arg("equalize")
+                .arg("weights")
+                .arg("score_strategy")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit")
+            .about("Add stake account to the stake pool")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("stake_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake address to join the pool"),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                          Defaults to the new pool token account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit-all")
+            .about("Add every stake account of a staker authority that is delegated to a validator of the pool")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("from")
+                    .long("from")
+                    .validator(is_pubkey)
+                    .value_name("STAKER_AUTHORITY_ADDRESS")
+                    .takes_value(true)
+                    .help("Staker authority of the stake accounts, which must also be their withdraw authority. \
+                          Must be signed for with --staker, and defaults to it."),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                          Defaults to the associated token account of the token owner, created if needed."),
+            )
+        )
+        .subcommand(SubCommand::with_name("list")
+            .about("List stake accounts managed by this pool")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("list-all")
+            .about("List every stake pool of the program, with its stake, exchange rate and fees")
+        )
+        .subcommand(SubCommand::with_name("params")
+            .about("Show the pool's parameters: fees, authorities, preferred validators and reserve balance")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("monitor")
+            .about("Watch the pool for stale updates, pool token rate moves, delinquent validators and authority changes, printing alerts and posting them to a webhook")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("webhook")
+                    .long("webhook")
+                    .validator(is_url)
+                    .value_name("URL")
+                    .takes_value(true)
+                    .help("URL receiving each alert as a JSON POST request."),
+            )
+            .arg(
+                Arg::with_name("interval")
+                    .long("interval")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .default_value("60")
+                    .help("Seconds between two checks of the pool."),
+            )
+            .arg(
+                Arg::with_name("max_rate_deviation")
+                    .long("max-rate-deviation")
+                    .validator(is_parsable::<u64>)
+                    .value_name("BASIS_POINTS")
+                    .takes_value(true)
+                    .default_value("100")
+                    .help("Largest move of the pool token rate between two checks before alerting, in basis points."),
+            )
+        )
+        .subcommand(SubCommand::with_name("update")
+            .about("Updates all balances in the pool after validator stake accounts receive rewards.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("no_merge")
+                    .long("no-merge")
+                    .takes_value(false)
+                    .help("Only record balances, without merging any transient stake accounts."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw")
+            .about("Withdraw amount from the stake pool")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw for activated stake."),
+            )
+            .arg(
+                Arg::with_name("withdraw_from")
+                    .long("withdraw-from")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Account to withdraw tokens from. Must be owned by the client."),
+            )
+            .arg(
+                Arg::with_name("stake_receiver")
+                    .long("stake-receiver")
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("Stake account to receive SOL from the stake pool. Defaults to a new stake account for each validator withdrawn from. Only usable when the withdrawal comes from a single stake account."),
+            )
+        )
+        .subcommand(SubCommand::with_name("burn")
+            .about("Burn pool tokens without withdrawing, donating their value to the other pool token holders")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to burn."),
+            )
+            .arg(
+                Arg::with_name("burn_from")
+                    .long("burn-from")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Account to burn tokens from. Must be owned by the client."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-manager")
+            .about("Change manager or fee receiver account for the stake pool. Must be signed by the current manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("new_manager")
+                    .long("new-manager")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new stake pool manager."),
+            )
+            .arg(
+                Arg::with_name("new_fee_receiver")
+                    .long("new-fee-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new account to set as the stake pool fee receiver."),
+            )
+            .group(ArgGroup::with_name("new_accounts")
+                .arg("new_manager")
+                .arg("new_fee_receiver")
+                .required(true)
+                .multiple(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-staker")
+            .about("Change staker account for the stake pool. Must be signed by the manager or current staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("new_staker")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new stake pool staker."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-fee")
+            .about("Change one of the stake pool fees. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("fee_type")
+                    .index(2)
+                    .value_name("FEE_TYPE")
+                    .possible_values(&["epoch", "stake-deposit", "sol-deposit", "withdrawal"])
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee to change. Epoch and withdrawal fees take effect in the next epoch."),
+            )
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .index(3)
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .index(4)
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-fee-split")
+            .about("Send part of the epoch fee to a secondary fee account. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("fee_numerator")
+                    .index(2)
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMERATOR")
+                    .takes_value(true)
+                    .help("Split numerator, the secondary share is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("fee_denominator")
+                    .index(3)
+                    .validator(is_parsable::<u64>)
+                    .value_name("DENOMINATOR")
+                    .takes_value(true)
+                    .help("Split denominator, the secondary share is numerator divided by denominator."),
+            )
+            .arg(
+                Arg::with_name("secondary_fee_account")
+                    .long("secondary-fee-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .requires_all(&["fee_numerator", "fee_denominator"])
+                    .help("Pool token account receiving its share of the epoch fee."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Send the whole epoch fee to the manager fee account again."),
+            )
+            .group(ArgGroup::with_name("destination")
+                .arg("secondary_fee_account")
+                .arg("unset")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-sol-deposit-fee-treasury")
+            .about("Collect the SOL deposit fee in SOL into a treasury account, instead of pool tokens. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("treasury")
+                    .long("treasury")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account receiving the SOL deposit fee."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Collect the SOL deposit fee in pool tokens again."),
+            )
+            .group(ArgGroup::with_name("destination")
+                .arg("treasury")
+                .arg("unset")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-deposit-interceptor")
+            .about("Invoke a program after every deposit with the deposit details. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("interceptor")
+                    .long("interceptor")
+                    .validator(is_pubkey)
+                    .value_name("PROGRAM_ID")
+                    .takes_value(true)
+                    .help("Program invoked after every deposit."),
+            )
+            .arg(
+                Arg::with_name("compute_units")
+                    .long("compute-units")
+                    .validator(is_parsable::<u32>)
+                    .value_name("UNITS")
+                    .takes_value(true)
+                    .default_value("50000")
+                    .help("Compute units for the interceptor, passed along to it. Not enforced by the pool."),
+            )
+            .arg(
+                Arg::with_name("strict")
+                    .long("strict")
+                    .takes_value(false)
+                    .requires("interceptor")
+                    .help("Require every deposit to invoke the interceptor, failing along with it. \
+                           Otherwise depositors may leave the interceptor out."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Stop intercepting deposits."),
+            )
+            .group(ArgGroup::with_name("target")
+                .arg("interceptor")
+                .arg("unset")
+                .required(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set-preferred-validator")
+            .about("Set the preferred validator for deposits or withdrawals. Must be signed by the staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("preferred_type")
+                    .index(2)
+                    .value_name("OPERATION")
+                    .possible_values(&["deposit", "withdraw"])
+                    .takes_value(true)
+                    .required(true)
+                    .help("Operation for which to restrict the validator"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .long("vote-account")
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("The validator vote account that deposits or withdrawals must go through."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Unset the preferred validator."),
+            )
+            .group(ArgGroup::with_name("validator")
+                .arg("vote_account")
+                .arg("unset")
+                .required(true)
+            )
         )
-        .arg(
-            Arg::with_name("no_update")
-                .long("no-update")
-                .takes_value(false)
-                .global(true)
-                .help("Do not automatically update the stake pool if needed"),
+        .subcommand(SubCommand::with_name("set-funding-authority")
+            .about("Set or unset the authority gating stake or SOL deposits. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("funding_type")
+                    .index(2)
+                    .value_name("FUNDING_TYPE")
+                    .possible_values(&["stake-deposit", "sol-deposit"])
+                    .takes_value(true)
+                    .required(true)
+                    .help("Funding type to be updated."),
+            )
+            .arg(
+                Arg::with_name("new_authority")
+                    .long("new-authority")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Public key for the new funding authority."),
+            )
+            .arg(
+                Arg::with_name("unset")
+                    .long("unset")
+                    .takes_value(false)
+                    .help("Unset the funding authority, making the funding type permissionless."),
+            )
+            .group(ArgGroup::with_name("authority")
+                .arg("new_authority")
+                .arg("unset")
+                .required(true)
+            )
         )
-        .arg(
-            Arg::with_name("json_rpc_url")
-                .long("url")
-                .value_name("URL")
-                .takes_value(true)
-                .validator(is_url)
-                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        .subcommand(SubCommand::with_name("set-deposit-lock")
+            .about("Set the number of epochs pool tokens are locked after a deposit. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("epochs")
+                    .index(2)
+                    .validator(is_parsable::<u64>)
+                    .value_name("EPOCHS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lock-in period in epochs, 0 disables the lock."),
+            )
         )
-        .arg(
-            Arg::with_name("staker")
-                .long("staker")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the stake pool staker. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .subcommand(SubCommand::with_name("claim-locked-deposit")
+            .about("Move the pool tokens of the token owner out of their escrow once the deposit lock is over. \
+                   Must be signed by the token owner.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("token_receiver")
+                    .long("token-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                          Defaults to the associated token account of the token owner, created if needed."),
+            )
         )
-        .arg(
-            Arg::with_name("manager")
-                .long("manager")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the stake pool manager. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .subcommand(SubCommand::with_name("set-deposit-cap")
+            .about("Set the maximum total stake the pool accepts through deposits. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Maximum total stake in SOL, 0 removes the cap."),
+            )
         )
-        .arg(
-            Arg::with_name("token_owner")
-                .long("token-owner")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the owner of the pool token account. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .subcommand(SubCommand::with_name("set-max-epoch-outflow")
+            .about("Cap the net withdrawals of each epoch to a share of the pool's total stake. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("bps")
+                    .index(2)
+                    .validator(is_parsable::<u16>)
+                    .value_name("BASIS_POINTS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Maximum net outflow per epoch, in basis points of the total stake at the start of the epoch, 0 removes the cap."),
+            )
         )
-        .arg(
-            Arg::with_name("fee_payer")
-                .long("fee-payer")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the fee-payer account. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .subcommand(SubCommand::with_name("set-delinquent-validator-epochs")
+            .about("Set the number of epochs without vote credits after which anyone can decrease a validator's stake. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("epochs")
+                    .index(2)
+                    .validator(is_parsable::<u64>)
+                    .value_name("EPOCHS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Number of epochs, 0 disables permissionless decreases."),
+            )
         )
-        .subcommand(SubCommand::with_name("create-pool")
-            .about("Create a new stake pool")
+        .subcommand(SubCommand::with_name("set-validator-onboarding-epochs")
+            .about("Set the number of epochs newly added validators wait before accepting deposits and stake increases. Must be signed by the manager.")
             .arg(
-                Arg::with_name("fee_numerator")
-                    .long("fee-numerator")
-                    .short("n")
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("epochs")
+                    .index(2)
                     .validator(is_parsable::<u64>)
-                    .value_name("NUMERATOR")
+                    .value_name("EPOCHS")
                     .takes_value(true)
                     .required(true)
-                    .help("Fee numerator, fee amount is numerator divided by denominator."),
+                    .help("Number of epochs, 0 lets new validators take stake right away."),
             )
+        )
+        .subcommand(SubCommand::with_name("set-manager-timelock")
+            .about("Set the number of epochs fee, manager and funding authority changes wait before anyone can execute them. Must be signed by the manager.")
             .arg(
-                Arg::with_name("fee_denominator")
-                    .long("fee-denominator")
-                    .short("d")
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("epochs")
+                    .index(2)
                     .validator(is_parsable::<u64>)
-                    .value_name("DENOMINATOR")
+                    .value_name("EPOCHS")
                     .takes_value(true)
                     .required(true)
-                    .help("Fee denominator, fee amount is numerator divided by denominator."),
+                    .help("Number of epochs, 0 applies changes right away. A shorter timelock waits for the current one."),
+            )
+        )
+        .subcommand(SubCommand::with_name("execute-manager-action")
+            .about("Apply the pending manager action once its timelock has elapsed. Anyone may execute it.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("cancel-manager-action")
+            .about("Drop the pending manager action. Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("increase-validator-list-size")
+            .about("Move the validator list to a larger account, refunding the old one to the fee payer. Must be signed by the staker or manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
             )
             .arg(
                 Arg::with_name("max_validators")
-                    .long("max-validators")
-                    .short("m")
+                    .index(2)
                     .validator(is_parsable::<u32>)
                     .value_name("NUMBER")
                     .takes_value(true)
                     .required(true)
-                    .help("Max number of validators included in the stake pool"),
+                    .help("New maximum number of validators in the pool."),
             )
         )
-        .subcommand(SubCommand::with_name("create-validator-stake")
-            .about("Create a new stake account to use with the pool. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("set-validator-target")
+            .about("Set the target share of the pool's stake for a validator. Must be signed by the staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("The validator vote account to set the target for."),
+            )
+            .arg(
+                Arg::with_name("weight_bps")
+                    .index(3)
+                    .validator(is_parsable::<u16>)
+                    .value_name("BASIS_POINTS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Target share of the pool's stake, in basis points. All targets must add up to at most 10000."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-proportional-withdrawals")
+            .about("Require stake withdrawals to come from the validator furthest above its target. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -1121,20 +5027,28 @@ fn main() {
                     .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address"),
+                    .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("vote_account")
-                    .index(2)
-                    .validator(is_pubkey)
-                    .value_name("VOTE_ACCOUNT_ADDRESS")
-                    .takes_value(true)
-                    .required(true)
-                    .help("The validator vote account that this stake will be delegated to"),
+                Arg::with_name("enable")
+                    .long("enable")
+                    .takes_value(false)
+                    .help("Enable proportional withdrawals."),
+            )
+            .arg(
+                Arg::with_name("disable")
+                    .long("disable")
+                    .takes_value(false)
+                    .help("Disable proportional withdrawals."),
+            )
+            .group(ArgGroup::with_name("mode")
+                .arg("enable")
+                .arg("disable")
+                .required(true)
             )
         )
-        .subcommand(SubCommand::with_name("add-validator")
-            .about("Add validator account to the stake pool. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("set-paused")
+            .about("Pause or resume deposits, withdrawals and rebalancing, for example during an incident. Updates still go through. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -1142,29 +5056,28 @@ fn main() {
                     .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address"),
+                    .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("stake_account")
-                    .index(2)
-                    .validator(is_pubkey)
-                    .value_name("STAKE_ACCOUNT_ADDRESS")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Stake account to add to the pool"),
+                Arg::with_name("pause")
+                    .long("pause")
+                    .takes_value(false)
+                    .help("Pause the pool."),
             )
             .arg(
-                Arg::with_name("token_receiver")
-                    .long("token-receiver")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
-                    .takes_value(true)
-                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
-                          Defaults to the new pool token account."),
+                Arg::with_name("resume")
+                    .long("resume")
+                    .takes_value(false)
+                    .help("Resume the pool."),
+            )
+            .group(ArgGroup::with_name("mode")
+                .arg("pause")
+                .arg("resume")
+                .required(true)
             )
         )
-        .subcommand(SubCommand::with_name("remove-validator")
-            .about("Remove validator account from the stake pool. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("decommission-pool")
+            .about("Start winding the pool down, for good: deposits and new stake are blocked, and withdrawals are free of fees and outflow caps. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -1172,47 +5085,51 @@ fn main() {
                     .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address"),
+                    .help("Stake pool address."),
             )
+        )
+        .subcommand(SubCommand::with_name("close-pool")
+            .about("Close a decommissioned pool with no validators and no pool tokens left, reclaiming the rent of its accounts and the rest of its reserve. Must be signed by the manager.")
             .arg(
-                Arg::with_name("stake_account")
-                    .index(2)
+                Arg::with_name("pool")
+                    .index(1)
                     .validator(is_pubkey)
-                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake account to remove from the pool"),
+                    .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("withdraw_from")
-                    .long("withdraw-from")
+                Arg::with_name("lamports_receiver")
+                    .long("lamports-receiver")
                     .validator(is_pubkey)
                     .value_name("ADDRESS")
                     .takes_value(true)
-                    .required(true)
-                    .help("Token account to withdraw pool token from. \
-                          Must have enough tokens for the full stake address balance."),
+                    .help("Account to receive the lamports of the closed accounts. Defaults to the manager."),
             )
+        )
+        .subcommand(SubCommand::with_name("create-single-validator-pool")
+            .about("Create the single-validator pool of a vote account. Anyone can create it; the fee payer funds the pool accounts and the seed stake.")
             .arg(
-                Arg::with_name("new_authority")
-                    .long("new-authority")
+                Arg::with_name("vote_account")
+                    .index(1)
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
-                    .help("New authority to set as Staker and Withdrawer in the stake account removed from the pool.
-                          Defaults to the wallet owner pubkey."),
+                    .required(true)
+                    .help("Vote account of the validator."),
             )
         )
-        .subcommand(SubCommand::with_name("deposit")
-            .about("Add stake account to the stake pool")
+        .subcommand(SubCommand::with_name("deposit-single-validator")
+            .about("Add a stake account delegated to the validator to its single-validator pool")
             .arg(
-                Arg::with_name("pool")
+                Arg::with_name("vote_account")
                     .index(1)
                     .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address"),
+                    .help("Vote account of the validator."),
             )
             .arg(
                 Arg::with_name("stake_account")
@@ -1229,44 +5146,20 @@ fn main() {
                     .validator(is_pubkey)
                     .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Account to receive pool token. Must be initialized account of the stake pool token. \
+                    .help("Account to receive pool token. Must be initialized account of the pool token. \
                           Defaults to the new pool token account."),
             )
         )
-        .subcommand(SubCommand::with_name("list")
-            .about("List stake accounts managed by this pool")
-            .arg(
-                Arg::with_name("pool")
-                    .index(1)
-                    .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Stake pool address."),
-            )
-        )
-        .subcommand(SubCommand::with_name("update")
-            .about("Updates all balances in the pool after validator stake accounts receive rewards.")
-            .arg(
-                Arg::with_name("pool")
-                    .index(1)
-                    .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Stake pool address."),
-            )
-        )
-        .subcommand(SubCommand::with_name("withdraw")
-            .about("Withdraw amount from the stake pool")
+        .subcommand(SubCommand::with_name("withdraw-single-validator")
+            .about("Withdraw stake from a single-validator pool")
             .arg(
-                Arg::with_name("pool")
+                Arg::with_name("vote_account")
                     .index(1)
                     .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address."),
+                    .help("Vote account of the validator."),
             )
             .arg(
                 Arg::with_name("amount")
@@ -1275,7 +5168,7 @@ fn main() {
                     .value_name("AMOUNT")
                     .takes_value(true)
                     .required(true)
-                    .help("Amount of pool tokens to withdraw for activated stake."),
+                    .help("Amount of pool tokens to withdraw."),
             )
             .arg(
                 Arg::with_name("withdraw_from")
@@ -1292,11 +5185,11 @@ fn main() {
                     .validator(is_pubkey)
                     .value_name("STAKE_ACCOUNT_ADDRESS")
                     .takes_value(true)
-                    .help("Stake account to receive SOL from the stake pool. Defaults to a new stake account."),
+                    .help("Uninitialized stake account to receive the stake. Defaults to a new stake account."),
             )
         )
-        .subcommand(SubCommand::with_name("set-manager")
-            .about("Change manager or fee receiver account for the stake pool. Must be signed by the current manager.")
+        .subcommand(SubCommand::with_name("create-token-metadata")
+            .about("Create the token metadata of the pool token, so that wallets display its name and symbol. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -1307,30 +5200,32 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("new_manager")
-                    .long("new-manager")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("name")
+                    .index(2)
+                    .value_name("TOKEN_NAME")
                     .takes_value(true)
-                    .help("Public key for the new stake pool manager."),
+                    .required(true)
+                    .help("Name of the pool token."),
             )
             .arg(
-                Arg::with_name("new_fee_receiver")
-                    .long("new-fee-receiver")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("symbol")
+                    .index(3)
+                    .value_name("TOKEN_SYMBOL")
                     .takes_value(true)
-                    .help("Public key for the new account to set as the stake pool fee receiver."),
+                    .required(true)
+                    .help("Symbol of the pool token."),
             )
-            .group(ArgGroup::with_name("new_accounts")
-                .arg("new_manager")
-                .arg("new_fee_receiver")
-                .required(true)
-                .multiple(true)
+            .arg(
+                Arg::with_name("uri")
+                    .index(4)
+                    .value_name("TOKEN_URI")
+                    .takes_value(true)
+                    .required(true)
+                    .help("URI of the JSON metadata of the pool token."),
             )
         )
-        .subcommand(SubCommand::with_name("set-staker")
-            .about("Change staker account for the stake pool. Must be signed by the manager or current staker.")
+        .subcommand(SubCommand::with_name("update-token-metadata")
+            .about("Update the token metadata of the pool token. Must be signed by the manager.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -1341,17 +5236,33 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("new_staker")
+                Arg::with_name("name")
                     .index(2)
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .value_name("TOKEN_NAME")
                     .takes_value(true)
-                    .help("Public key for the new stake pool staker."),
+                    .required(true)
+                    .help("Name of the pool token."),
+            )
+            .arg(
+                Arg::with_name("symbol")
+                    .index(3)
+                    .value_name("TOKEN_SYMBOL")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Symbol of the pool token."),
+            )
+            .arg(
+                Arg::with_name("uri")
+                    .index(4)
+                    .value_name("TOKEN_URI")
+                    .takes_value(true)
+                    .required(true)
+                    .help("URI of the JSON metadata of the pool token."),
             )
         )
         .get_matches();
 
-    let mut wallet_manager = None;
+    let mut wallet_manager: Option<Arc<RemoteWalletManager>> = None;
     let config = {
         let cli_config = if let Some(config_file) = matches.value_of("config_file") {
             solana_cli_config::Config::load(config_file).unwrap_or_default()
@@ -1401,9 +5312,42 @@ fn main() {
             eprintln!("error: {}", e);
             exit(1);
         });
+        let depositor = if matches.is_present("depositor") {
+            Some(
+                signer_from_path(
+                    &matches,
+                    &cli_config.keypair_path,
+                    "depositor",
+                    &mut wallet_manager,
+                )
+                .unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                }),
+            )
+        } else {
+            None
+        };
+        let vote_withdrawer = if matches.is_present("vote_withdrawer") {
+            Some(
+                signer_from_path(
+                    &matches,
+                    &cli_config.keypair_path,
+                    "vote_withdrawer",
+                    &mut wallet_manager,
+                )
+                .unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                }),
+            )
+        } else {
+            None
+        };
         let verbose = matches.is_present("verbose");
         let dry_run = matches.is_present("dry_run");
         let no_update = matches.is_present("no_update");
+        let output_format = OutputFormat::from_name(matches.value_of("output_format"));
 
         Config {
             rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
@@ -1412,8 +5356,11 @@ fn main() {
             staker,
             token_owner,
             fee_payer,
+            depositor,
+            vote_withdrawer,
             dry_run,
             no_update,
+            output_format,
         }
     };
 
@@ -1422,8 +5369,10 @@ fn main() {
             let numerator = value_t_or_exit!(arg_matches, "fee_numerator", u64);
             let denominator = value_t_or_exit!(arg_matches, "fee_denominator", u64);
             let max_validators = value_t_or_exit!(arg_matches, "max_validators", u32);
+            let deposit_authority = pubkey_of(arg_matches, "deposit_authority");
             command_create_pool(
                 &config,
+                deposit_authority,
                 spl_stake_pool::instruction::Fee {
                     denominator,
                     numerator,
@@ -1450,15 +5399,77 @@ fn main() {
         ("remove-validator", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
-            let withdraw_from = pubkey_of(arg_matches, "withdraw_from").unwrap();
-            let new_authority: Option<Pubkey> = pubkey_of(arg_matches, "new_authority");
-            command_vsa_remove(
-                &config,
-                &stake_pool_address,
-                &stake_account,
-                &withdraw_from,
-                &new_authority,
-            )
+            command_vsa_remove(&config, &stake_pool_address, &stake_account, None)
+        }
+        ("leave-pool", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            command_leave_pool(&config, &stake_pool_address, &vote_account)
+        }
+        ("increase-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_increase_validator_stake(&config, &stake_pool_address, &vote_account, amount)
+        }
+        ("decrease-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_decrease_validator_stake(&config, &stake_pool_address, &vote_account, amount)
+        }
+        ("decrease-delinquent-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            command_decrease_delinquent_validator_stake(&config, &stake_pool_address, &vote_account)
+        }
+        ("apply-targets", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            command_apply_targets(&config, &stake_pool_address, &vote_account)
+        }
+        ("rebalance", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let strategy: Box<dyn SelectionStrategy> = if arg_matches.is_present("proportional") {
+                Box::new(Strategy::Proportional)
+            } else if arg_matches.is_present("equalize") {
+                Box::new(Strategy::Equalize)
+            } else if let Some(score_strategy) = arg_matches.value_of("score_strategy") {
+                let scores = get_validator_scores(&config, arg_matches.value_of("scores"))
+                    .unwrap_or_else(|err| {
+                        eprintln!("error: {}", err);
+                        exit(1);
+                    });
+                match score_strategy {
+                    "proportional-to-score" => Box::new(ProportionalToScore { scores }),
+                    "equal-weight" => Box::new(EqualWeight {
+                        scores,
+                        min_score: value_t_or_exit!(arg_matches, "min_score", u64),
+                    }),
+                    _ => Box::new(StakeConcentrationAware {
+                        scores,
+                        max_network_share_bps: value_t_or_exit!(
+                            arg_matches,
+                            "max_network_share",
+                            u64
+                        ),
+                    }),
+                }
+            } else {
+                let weights_file = arg_matches.value_of("weights").unwrap();
+                Box::new(
+                    std::fs::read_to_string(weights_file)
+                        .map_err(|err| {
+                            Error::from(format!("Unable to read {}: {}", weights_file, err))
+                        })
+                        .and_then(|json| Strategy::weights_from_json(&json))
+                        .unwrap_or_else(|err| {
+                            eprintln!("error: {}", err);
+                            exit(1);
+                        }),
+                )
+            };
+            command_rebalance(&config, &stake_pool_address, strategy.as_ref())
         }
         ("deposit", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
@@ -1471,13 +5482,38 @@ fn main() {
                 &token_receiver,
             )
         }
+        ("deposit-all", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let from: Option<Pubkey> = pubkey_of(arg_matches, "from");
+            let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
+            command_deposit_all(&config, &stake_pool_address, &from, &token_receiver)
+        }
+        ("list-all", _) => command_list_all(&config),
         ("list", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             command_list(&config, &stake_pool_address)
         }
+        ("params", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_params(&config, &stake_pool_address)
+        }
+        ("monitor", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let webhook = arg_matches.value_of("webhook");
+            let interval = value_t_or_exit!(arg_matches, "interval", u64);
+            let max_rate_deviation_bps = value_t_or_exit!(arg_matches, "max_rate_deviation", u64);
+            command_monitor(
+                &config,
+                &stake_pool_address,
+                webhook,
+                Duration::from_secs(interval),
+                max_rate_deviation_bps,
+            )
+        }
         ("update", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
-            command_update(&config, &stake_pool_address)
+            let no_merge = arg_matches.is_present("no_merge");
+            command_update(&config, &stake_pool_address, no_merge)
         }
         ("withdraw", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
@@ -1492,6 +5528,12 @@ fn main() {
                 &stake_receiver,
             )
         }
+        ("burn", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let burn_from = pubkey_of(arg_matches, "burn_from").unwrap();
+            let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_burn(&config, &stake_pool_address, pool_amount, &burn_from)
+        }
         ("set-manager", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let new_manager: Option<Pubkey> = pubkey_of(arg_matches, "new_manager");
@@ -1508,6 +5550,202 @@ fn main() {
             let new_staker = pubkey_of(arg_matches, "new_staker").unwrap();
             command_set_staker(&config, &stake_pool_address, &new_staker)
         }
+        ("set-fee", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let numerator = value_t_or_exit!(arg_matches, "fee_numerator", u64);
+            let denominator = value_t_or_exit!(arg_matches, "fee_denominator", u64);
+            let fee = spl_stake_pool::instruction::Fee {
+                denominator,
+                numerator,
+            };
+            let fee_type = match arg_matches.value_of("fee_type").unwrap() {
+                "epoch" => spl_stake_pool::instruction::FeeType::Epoch(fee),
+                "stake-deposit" => spl_stake_pool::instruction::FeeType::StakeDeposit(fee),
+                "sol-deposit" => spl_stake_pool::instruction::FeeType::SolDeposit(fee),
+                "withdrawal" => spl_stake_pool::instruction::FeeType::Withdrawal(fee),
+                _ => unreachable!(),
+            };
+            command_set_fee(&config, &stake_pool_address, fee_type)
+        }
+        ("set-fee-split", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let secondary_fee_account = pubkey_of(arg_matches, "secondary_fee_account");
+            let split = if secondary_fee_account.is_some() {
+                spl_stake_pool::instruction::Fee {
+                    denominator: value_t_or_exit!(arg_matches, "fee_denominator", u64),
+                    numerator: value_t_or_exit!(arg_matches, "fee_numerator", u64),
+                }
+            } else {
+                spl_stake_pool::instruction::Fee::default()
+            };
+            command_set_fee_split(&config, &stake_pool_address, secondary_fee_account, split)
+        }
+        ("set-sol-deposit-fee-treasury", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let treasury = pubkey_of(arg_matches, "treasury");
+            command_set_sol_deposit_fee_treasury(&config, &stake_pool_address, treasury)
+        }
+        ("set-deposit-interceptor", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let deposit_interceptor = pubkey_of(arg_matches, "interceptor");
+            let compute_units = value_t_or_exit!(arg_matches, "compute_units", u32);
+            let strict = arg_matches.is_present("strict");
+            command_set_deposit_interceptor(
+                &config,
+                &stake_pool_address,
+                deposit_interceptor,
+                compute_units,
+                strict,
+            )
+        }
+        ("set-preferred-validator", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let preferred_type = match arg_matches.value_of("preferred_type").unwrap() {
+                "deposit" => spl_stake_pool::instruction::PreferredValidatorType::Deposit,
+                "withdraw" => spl_stake_pool::instruction::PreferredValidatorType::Withdraw,
+                _ => unreachable!(),
+            };
+            let vote_account = pubkey_of(arg_matches, "vote_account");
+            command_set_preferred_validator(
+                &config,
+                &stake_pool_address,
+                preferred_type,
+                vote_account,
+            )
+        }
+        ("set-funding-authority", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let new_authority = pubkey_of(arg_matches, "new_authority");
+            let funding_type = match arg_matches.value_of("funding_type").unwrap() {
+                "stake-deposit" => spl_stake_pool::instruction::FundingType::StakeDeposit,
+                "sol-deposit" => spl_stake_pool::instruction::FundingType::SolDeposit,
+                _ => unreachable!(),
+            };
+            command_set_funding_authority(&config, &stake_pool_address, new_authority, funding_type)
+        }
+        ("set-deposit-lock", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let deposit_lock_epochs = value_t_or_exit!(arg_matches, "epochs", u64);
+            command_set_deposit_lock(&config, &stake_pool_address, deposit_lock_epochs)
+        }
+        ("claim-locked-deposit", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
+            command_claim_locked_deposit(&config, &stake_pool_address, &token_receiver)
+        }
+        ("set-deposit-cap", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_set_deposit_cap(&config, &stake_pool_address, amount)
+        }
+        ("set-max-epoch-outflow", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let bps = value_t_or_exit!(arg_matches, "bps", u16);
+            command_set_max_epoch_outflow(&config, &stake_pool_address, bps)
+        }
+        ("set-delinquent-validator-epochs", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let delinquent_validator_epochs = value_t_or_exit!(arg_matches, "epochs", u64);
+            command_set_delinquent_validator_epochs(
+                &config,
+                &stake_pool_address,
+                delinquent_validator_epochs,
+            )
+        }
+        ("set-validator-onboarding-epochs", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let validator_onboarding_epochs = value_t_or_exit!(arg_matches, "epochs", u64);
+            command_set_validator_onboarding_epochs(
+                &config,
+                &stake_pool_address,
+                validator_onboarding_epochs,
+            )
+        }
+        ("set-manager-timelock", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let manager_timelock_epochs = value_t_or_exit!(arg_matches, "epochs", u64);
+            command_set_manager_timelock(&config, &stake_pool_address, manager_timelock_epochs)
+        }
+        ("execute-manager-action", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_execute_manager_action(&config, &stake_pool_address)
+        }
+        ("cancel-manager-action", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_cancel_manager_action(&config, &stake_pool_address)
+        }
+        ("increase-validator-list-size", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let max_validators = value_t_or_exit!(arg_matches, "max_validators", u32);
+            command_increase_validator_list_size(&config, &stake_pool_address, max_validators)
+        }
+        ("set-validator-target", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let weight_bps = value_t_or_exit!(arg_matches, "weight_bps", u16);
+            command_set_validator_target(&config, &stake_pool_address, vote_account, weight_bps)
+        }
+        ("set-proportional-withdrawals", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let enabled = arg_matches.is_present("enable");
+            command_set_proportional_withdrawals(&config, &stake_pool_address, enabled)
+        }
+        ("set-paused", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let paused = arg_matches.is_present("pause");
+            command_set_paused(&config, &stake_pool_address, paused)
+        }
+        ("decommission-pool", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_decommission_pool(&config, &stake_pool_address)
+        }
+        ("close-pool", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let lamports_receiver = pubkey_of(arg_matches, "lamports_receiver");
+            command_close_pool(&config, &stake_pool_address, &lamports_receiver)
+        }
+        ("create-single-validator-pool", Some(arg_matches)) => {
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            command_create_single_validator_pool(&config, &vote_account)
+        }
+        ("deposit-single-validator", Some(arg_matches)) => {
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
+            let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
+            command_deposit_single_validator(
+                &config,
+                &vote_account,
+                &stake_account,
+                &token_receiver,
+            )
+        }
+        ("withdraw-single-validator", Some(arg_matches)) => {
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let withdraw_from = pubkey_of(arg_matches, "withdraw_from").unwrap();
+            let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let stake_receiver: Option<Pubkey> = pubkey_of(arg_matches, "stake_receiver");
+            command_withdraw_single_validator(
+                &config,
+                &vote_account,
+                pool_amount,
+                &withdraw_from,
+                &stake_receiver,
+            )
+        }
+        ("create-token-metadata", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let name = value_t_or_exit!(arg_matches, "name", String);
+            let symbol = value_t_or_exit!(arg_matches, "symbol", String);
+            let uri = value_t_or_exit!(arg_matches, "uri", String);
+            command_create_token_metadata(&config, &stake_pool_address, name, symbol, uri)
+        }
+        ("update-token-metadata", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let name = value_t_or_exit!(arg_matches, "name", String);
+            let symbol = value_t_or_exit!(arg_matches, "symbol", String);
+            let uri = value_t_or_exit!(arg_matches, "uri", String);
+            command_update_token_metadata(&config, &stake_pool_address, name, symbol, uri)
+        }
         _ => unreachable!(),
     }
     .map_err(|err| {
@@ -0,0 +1,352 @@
+//! Watching a stake pool for conditions its operators should hear about
+
+use {
+    solana_program::{clock::Epoch, pubkey::Pubkey},
+    spl_stake_pool::state::StakePool,
+    std::fmt,
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Basis points in 100%, for rate deviations
+const MAX_BPS: u128 = 10_000;
+
+/// What the monitor sees of a pool at one point in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolSnapshot {
+    /// Current epoch of the cluster
+    pub epoch: Epoch,
+    /// Last epoch the pool balances were updated
+    pub last_update_epoch: Epoch,
+    /// Lamports backing `PRICE_ORACLE_RATE_PRECISION` pool tokens
+    pub rate: u64,
+    /// Pool authorities, by name
+    pub authorities: Vec<(&'static str, Pubkey)>,
+    /// Vote accounts of the pool's validators the cluster sees as delinquent
+    pub delinquent_validators: Vec<Pubkey>,
+}
+
+impl PoolSnapshot {
+    /// Snapshot of `stake_pool` at `epoch`, `None` if its rate overflows
+    pub fn new(
+        stake_pool: &StakePool,
+        epoch: Epoch,
+        delinquent_validators: Vec<Pubkey>,
+    ) -> Option<Self> {
+        Some(Self {
+            epoch,
+            last_update_epoch: stake_pool.last_update_epoch,
+            rate: stake_pool.price_oracle_rate()?,
+            authorities: vec![
+                ("manager", stake_pool.manager),
+                ("staker", stake_pool.staker),
+                ("managerFeeAccount", stake_pool.manager_fee_account),
+                ("stakeDepositAuthority", stake_pool.stake_deposit_authority),
+                ("solDepositAuthority", stake_pool.sol_deposit_authority),
+            ],
+            delinquent_validators,
+        })
+    }
+}
+
+/// Condition reported by the monitor
+#[derive(Clone, Debug, PartialEq)]
+pub enum Alert {
+    /// The pool has not been updated in the current epoch
+    StaleUpdate {
+        /// Current epoch
+        epoch: Epoch,
+        /// Last epoch the pool was updated
+        last_update_epoch: Epoch,
+    },
+    /// The pool token rate moved by more than the allowed deviation
+    RateDeviation {
+        /// Rate at the previous check
+        previous_rate: u64,
+        /// Current rate
+        rate: u64,
+        /// Size of the move, in basis points of the previous rate
+        deviation_bps: u64,
+    },
+    /// One of the pool's validators became delinquent
+    ValidatorDelinquent {
+        /// Vote account of the validator
+        vote_account: Pubkey,
+    },
+    /// A pool authority changed
+    AuthorityChanged {
+        /// Name of the authority
+        authority: &'static str,
+        /// Previous authority
+        previous: Pubkey,
+        /// New authority
+        current: Pubkey,
+    },
+}
+
+impl Alert {
+    /// Short machine-readable kind of the alert
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Alert::StaleUpdate { .. } => "staleUpdate",
+            Alert::RateDeviation { .. } => "rateDeviation",
+            Alert::ValidatorDelinquent { .. } => "validatorDelinquent",
+            Alert::AuthorityChanged { .. } => "authorityChanged",
+        }
+    }
+
+    /// Body POSTed to the webhook
+    pub fn to_json(&self, stake_pool_address: &Pubkey) -> serde_json::Value {
+        let details = match self {
+            Alert::StaleUpdate {
+                epoch,
+                last_update_epoch,
+            } => serde_json::json!({
+                "epoch": epoch,
+                "lastUpdateEpoch": last_update_epoch,
+            }),
+            Alert::RateDeviation {
+                previous_rate,
+                rate,
+                deviation_bps,
+            } => serde_json::json!({
+                "previousRate": previous_rate,
+                "rate": rate,
+                "deviationBps": deviation_bps,
+            }),
+            Alert::ValidatorDelinquent { vote_account } => serde_json::json!({
+                "voteAccount": vote_account.to_string(),
+            }),
+            Alert::AuthorityChanged {
+                authority,
+                previous,
+                current,
+            } => serde_json::json!({
+                "authority": authority,
+                "previous": previous.to_string(),
+                "current": current.to_string(),
+            }),
+        };
+        serde_json::json!({
+            "stakePool": stake_pool_address.to_string(),
+            "kind": self.kind(),
+            "message": self.to_string(),
+            "details": details,
+        })
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Alert::StaleUpdate {
+                epoch,
+                last_update_epoch,
+            } => write!(
+                f,
+                "Pool not updated in epoch {}, last update in epoch {}",
+                epoch, last_update_epoch
+            ),
+            Alert::RateDeviation {
+                previous_rate,
+                rate,
+                deviation_bps,
+            } => write!(
+                f,
+                "Pool token rate moved {}bps, from {} to {}",
+                deviation_bps, previous_rate, rate
+            ),
+            Alert::ValidatorDelinquent { vote_account } => {
+                write!(f, "Validator {} is delinquent", vote_account)
+            }
+            Alert::AuthorityChanged {
+                authority,
+                previous,
+                current,
+            } => write!(f, "{} changed from {} to {}", authority, previous, current),
+        }
+    }
+}
+
+/// Compares successive snapshots of a pool, reporting each condition once
+pub struct Monitor {
+    max_rate_deviation_bps: u64,
+    previous: Option<PoolSnapshot>,
+}
+
+impl Monitor {
+    /// Create a monitor alerting on rate moves above `max_rate_deviation_bps`
+    pub fn new(max_rate_deviation_bps: u64) -> Self {
+        Self {
+            max_rate_deviation_bps,
+            previous: None,
+        }
+    }
+
+    /// Alerts raised by `snapshot` that were not raised by the previous one
+    pub fn check(&mut self, snapshot: PoolSnapshot) -> Vec<Alert> {
+        let mut alerts = vec![];
+        let previous = self.previous.as_ref();
+
+        let stale = |snapshot: &PoolSnapshot| snapshot.last_update_epoch < snapshot.epoch;
+        let already_stale = previous
+            .map(|previous| stale(previous) && previous.epoch == snapshot.epoch)
+            .unwrap_or(false);
+        if stale(&snapshot) && !already_stale {
+            alerts.push(Alert::StaleUpdate {
+                epoch: snapshot.epoch,
+                last_update_epoch: snapshot.last_update_epoch,
+            });
+        }
+
+        if let Some(previous) = previous {
+            let deviation_bps = rate_deviation_bps(previous.rate, snapshot.rate);
+            if deviation_bps > self.max_rate_deviation_bps {
+                alerts.push(Alert::RateDeviation {
+                    previous_rate: previous.rate,
+                    rate: snapshot.rate,
+                    deviation_bps,
+                });
+            }
+
+            for ((authority, previous), (_, current)) in
+                previous.authorities.iter().zip(snapshot.authorities.iter())
+            {
+                if previous != current {
+                    alerts.push(Alert::AuthorityChanged {
+                        authority: *authority,
+                        previous: *previous,
+                        current: *current,
+                    });
+                }
+            }
+        }
+
+        for vote_account in &snapshot.delinquent_validators {
+            let already_delinquent = previous
+                .map(|previous| previous.delinquent_validators.contains(vote_account))
+                .unwrap_or(false);
+            if !already_delinquent {
+                alerts.push(Alert::ValidatorDelinquent {
+                    vote_account: *vote_account,
+                });
+            }
+        }
+
+        self.previous = Some(snapshot);
+        alerts
+    }
+}
+
+/// Size of the move from `previous_rate` to `rate`, in basis points of
+/// `previous_rate`, saturating at `u64::MAX`
+fn rate_deviation_bps(previous_rate: u64, rate: u64) -> u64 {
+    if previous_rate == 0 {
+        return if rate == 0 { 0 } else { u64::MAX };
+    }
+    let difference = rate.max(previous_rate) - rate.min(previous_rate);
+    let deviation_bps = difference as u128 * MAX_BPS / previous_rate as u128;
+    deviation_bps.min(u64::MAX as u128) as u64
+}
+
+/// POST `alert` as JSON to `webhook`
+pub fn post_alert(
+    http_client: &reqwest::blocking::Client,
+    webhook: &str,
+    stake_pool_address: &Pubkey,
+    alert: &Alert,
+) -> Result<(), Error> {
+    http_client
+        .post(webhook)
+        .json(&alert.to_json(stake_pool_address))
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(epoch: Epoch, last_update_epoch: Epoch, rate: u64) -> PoolSnapshot {
+        PoolSnapshot {
+            epoch,
+            last_update_epoch,
+            rate,
+            authorities: vec![("manager", Pubkey::default())],
+            delinquent_validators: vec![],
+        }
+    }
+
+    #[test]
+    fn stale_update_reported_once_per_epoch() {
+        let mut monitor = Monitor::new(100);
+        assert_eq!(monitor.check(snapshot(10, 10, 1_000)), vec![]);
+        let stale = Alert::StaleUpdate {
+            epoch: 11,
+            last_update_epoch: 10,
+        };
+        assert_eq!(monitor.check(snapshot(11, 10, 1_000)), vec![stale]);
+        assert_eq!(monitor.check(snapshot(11, 10, 1_000)), vec![]);
+        assert_eq!(monitor.check(snapshot(11, 11, 1_000)), vec![]);
+        let stale = Alert::StaleUpdate {
+            epoch: 12,
+            last_update_epoch: 11,
+        };
+        assert_eq!(monitor.check(snapshot(12, 11, 1_000)), vec![stale]);
+    }
+
+    #[test]
+    fn rate_deviation_in_both_directions() {
+        let mut monitor = Monitor::new(100);
+        assert_eq!(monitor.check(snapshot(1, 1, 10_000)), vec![]);
+        // 1% is within the allowed deviation
+        assert_eq!(monitor.check(snapshot(1, 1, 10_100)), vec![]);
+        assert_eq!(
+            monitor.check(snapshot(1, 1, 9_000)),
+            vec![Alert::RateDeviation {
+                previous_rate: 10_100,
+                rate: 9_000,
+                deviation_bps: 1_089,
+            }]
+        );
+        assert_eq!(rate_deviation_bps(0, 0), 0);
+        assert_eq!(rate_deviation_bps(0, 1), u64::MAX);
+        assert_eq!(rate_deviation_bps(1, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn authority_changes_and_new_delinquencies() {
+        let mut monitor = Monitor::new(100);
+        let validator = Pubkey::new_unique();
+        let mut first = snapshot(1, 1, 1_000);
+        first.delinquent_validators = vec![validator];
+        assert_eq!(
+            monitor.check(first.clone()),
+            vec![Alert::ValidatorDelinquent {
+                vote_account: validator
+            }]
+        );
+
+        let manager = Pubkey::new_unique();
+        let other_validator = Pubkey::new_unique();
+        let second = PoolSnapshot {
+            authorities: vec![("manager", manager)],
+            delinquent_validators: vec![validator, other_validator],
+            ..first
+        };
+        assert_eq!(
+            monitor.check(second),
+            vec![
+                Alert::AuthorityChanged {
+                    authority: "manager",
+                    previous: Pubkey::default(),
+                    current: manager,
+                },
+                Alert::ValidatorDelinquent {
+                    vote_account: other_validator
+                },
+            ]
+        );
+    }
+}
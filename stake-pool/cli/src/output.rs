@@ -0,0 +1,60 @@
+//! Output formats, following the `--output` flag of the Solana CLI
+
+use serde_json::Value;
+
+/// How commands print their results
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Display,
+    /// Pretty-printed JSON document
+    Json,
+    /// JSON document on a single line
+    JsonCompact,
+}
+
+impl OutputFormat {
+    /// Format named on the command line, `display` if none was given
+    pub fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("json") => OutputFormat::Json,
+            Some("json-compact") => OutputFormat::JsonCompact,
+            _ => OutputFormat::Display,
+        }
+    }
+
+    /// Whether stdout is reserved for a JSON document
+    pub fn is_json(self) -> bool {
+        self != OutputFormat::Display
+    }
+
+    /// The JSON document in this format, empty for `Display`
+    pub fn formatted_string(self, value: &Value) -> String {
+        match self {
+            OutputFormat::Display => String::new(),
+            OutputFormat::Json => serde_json::to_string_pretty(value).unwrap_or_default(),
+            OutputFormat::JsonCompact => serde_json::to_string(value).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let value = serde_json::json!({ "signature": "abc" });
+        assert_eq!(OutputFormat::from_name(None), OutputFormat::Display);
+        assert!(!OutputFormat::Display.is_json());
+        assert_eq!(OutputFormat::Display.formatted_string(&value), "");
+        assert_eq!(
+            OutputFormat::from_name(Some("json-compact")).formatted_string(&value),
+            "{\"signature\":\"abc\"}"
+        );
+        assert_eq!(
+            OutputFormat::from_name(Some("json")).formatted_string(&value),
+            "{\n  \"signature\": \"abc\"\n}"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Packing of instruction batches into as few transactions as possible
+
+use {
+    solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey},
+    solana_sdk::{packet::PACKET_DATA_SIZE, signature::Signature, transaction::Transaction},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Maximum number of distinct accounts a single transaction may lock
+pub const MAX_TRANSACTION_ACCOUNT_LOCKS: usize = 64;
+
+/// Greedily packs instructions, in order, into transaction messages that stay
+/// under the transaction size and account lock limits
+pub struct TransactionPacker {
+    fee_payer: Pubkey,
+    max_transaction_size: usize,
+    max_account_locks: usize,
+}
+
+impl TransactionPacker {
+    /// Create a packer for messages paid by `fee_payer`, using the cluster limits
+    pub fn new(fee_payer: Pubkey) -> Self {
+        Self {
+            fee_payer,
+            max_transaction_size: PACKET_DATA_SIZE,
+            max_account_locks: MAX_TRANSACTION_ACCOUNT_LOCKS,
+        }
+    }
+
+    /// Override the maximum serialized size of a signed transaction
+    pub fn max_transaction_size(mut self, max_transaction_size: usize) -> Self {
+        self.max_transaction_size = max_transaction_size;
+        self
+    }
+
+    /// Override the maximum number of accounts in a transaction
+    pub fn max_account_locks(mut self, max_account_locks: usize) -> Self {
+        self.max_account_locks = max_account_locks;
+        self
+    }
+
+    /// Pack `instructions` into messages ready to be signed, keeping their
+    /// order both across and within messages
+    pub fn pack(&self, instructions: Vec<Instruction>) -> Result<Vec<Message>, Error> {
+        let mut messages = vec![];
+        let mut batch: Vec<Instruction> = vec![];
+        for (index, instruction) in instructions.into_iter().enumerate() {
+            batch.push(instruction);
+            if self.fits(&batch) {
+                continue;
+            }
+            let instruction = batch.pop().unwrap();
+            if batch.is_empty() {
+                return Err(format!("Instruction {} does not fit in a transaction", index).into());
+            }
+            messages.push(Message::new(&batch, Some(&self.fee_payer)));
+            batch = vec![instruction];
+            if !self.fits(&batch) {
+                return Err(format!("Instruction {} does not fit in a transaction", index).into());
+            }
+        }
+        if !batch.is_empty() {
+            messages.push(Message::new(&batch, Some(&self.fee_payer)));
+        }
+        Ok(messages)
+    }
+
+    /// Check if `instructions` fit in a single transaction
+    pub fn fits(&self, instructions: &[Instruction]) -> bool {
+        let message = Message::new(instructions, Some(&self.fee_payer));
+        if message.account_keys.len() > self.max_account_locks {
+            return false;
+        }
+        let transaction = Transaction {
+            signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+            message,
+        };
+        bincode::serialized_size(&transaction)
+            .map_or(false, |size| size as usize <= self.max_transaction_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_program::instruction::AccountMeta};
+
+    fn instruction(accounts: usize, data_len: usize) -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: (0..accounts)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+            data: vec![0; data_len],
+        }
+    }
+
+    #[test]
+    fn packs_in_order_under_size_limit() {
+        let fee_payer = Pubkey::new_unique();
+        let instructions: Vec<Instruction> = (0..60).map(|_| instruction(8, 16)).collect();
+        let messages = TransactionPacker::new(fee_payer)
+            .pack(instructions.clone())
+            .unwrap();
+        assert!(messages.len() > 1);
+        assert!(messages.len() < instructions.len());
+
+        let mut program_ids = vec![];
+        for message in &messages {
+            assert_eq!(message.account_keys[0], fee_payer);
+            let transaction = Transaction {
+                signatures: vec![Signature::default()],
+                message: message.clone(),
+            };
+            assert!(bincode::serialized_size(&transaction).unwrap() as usize <= PACKET_DATA_SIZE);
+            for compiled in &message.instructions {
+                program_ids.push(message.account_keys[compiled.program_id_index as usize]);
+            }
+        }
+        let expected: Vec<Pubkey> = instructions.iter().map(|ix| ix.program_id).collect();
+        assert_eq!(program_ids, expected);
+    }
+
+    #[test]
+    fn respects_account_locks() {
+        let messages = TransactionPacker::new(Pubkey::new_unique())
+            .max_account_locks(20)
+            .pack((0..5).map(|_| instruction(8, 0)).collect())
+            .unwrap();
+        // The fee payer, plus 8 accounts and the program id per instruction
+        assert_eq!(messages.len(), 3);
+        assert!(messages
+            .iter()
+            .all(|message| message.account_keys.len() <= 20));
+    }
+
+    #[test]
+    fn fails_on_oversized_instruction() {
+        let result = TransactionPacker::new(Pubkey::new_unique()).pack(vec![instruction(1, 2_000)]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Planning the stake moves that bring a pool's validators to a target
+//! distribution
+
+use {
+    solana_program::pubkey::Pubkey,
+    spl_stake_pool::{state::ValidatorStakeInfo, MAX_TARGET_WEIGHT_BPS},
+    std::{collections::HashMap, convert::TryFrom, str::FromStr},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Decides the weight of each active validator in a rebalance, their share of
+/// the stake being their weight over the total weight
+pub trait SelectionStrategy {
+    /// Weight of each validator, and the total weight they are shares of
+    fn weights(&self, validators: &[&ValidatorStakeInfo]) -> Result<(Vec<u64>, u64), Error>;
+}
+
+/// How the stake of the pool should be spread across its validators
+#[derive(Clone, Debug, PartialEq)]
+pub enum Strategy {
+    /// Follow the target weights the staker set on-chain, keeping the rest of
+    /// the pool in the reserve
+    Proportional,
+    /// Give every active validator the same stake
+    Equalize,
+    /// Follow relative weights, by vote account, validators left out get
+    /// only their minimum stake
+    Weights(HashMap<Pubkey, u64>),
+}
+
+impl Strategy {
+    /// Read weights from a JSON object mapping vote accounts to weights
+    pub fn weights_from_json(json: &str) -> Result<Self, Error> {
+        let raw_weights: HashMap<String, u64> = serde_json::from_str(json)?;
+        let mut weights = HashMap::new();
+        for (vote_account, weight) in raw_weights {
+            let vote_account = Pubkey::from_str(&vote_account)
+                .map_err(|_| format!("Invalid vote account address {}", vote_account))?;
+            weights.insert(vote_account, weight);
+        }
+        if weights.values().all(|weight| *weight == 0) {
+            return Err("Weights file gives no weight to any validator".into());
+        }
+        Ok(Strategy::Weights(weights))
+    }
+}
+
+impl SelectionStrategy for Strategy {
+    fn weights(&self, validators: &[&ValidatorStakeInfo]) -> Result<(Vec<u64>, u64), Error> {
+        match self {
+            Strategy::Proportional => {
+                let weights: Vec<u64> = validators
+                    .iter()
+                    .map(|item| u16::from(item.target_weight_bps) as u64)
+                    .collect();
+                if weights.iter().all(|weight| *weight == 0) {
+                    return Err("The staker has not set target weights for this pool".into());
+                }
+                Ok((weights, MAX_TARGET_WEIGHT_BPS as u64))
+            }
+            Strategy::Equalize => Ok((vec![1; validators.len()], validators.len() as u64)),
+            Strategy::Weights(weights) => {
+                for vote_account in weights.keys() {
+                    if !validators
+                        .iter()
+                        .any(|item| item.vote_account == *vote_account)
+                    {
+                        return Err(format!(
+                            "Validator {} is not an active validator of the pool",
+                            vote_account
+                        )
+                        .into());
+                    }
+                }
+                let weights: Vec<u64> = validators
+                    .iter()
+                    .map(|item| weights.get(&item.vote_account).copied().unwrap_or(0))
+                    .collect();
+                let total_weight = weights
+                    .iter()
+                    .try_fold(0u64, |total, weight| total.checked_add(*weight))
+                    .ok_or("Weights add up to more than a u64")?;
+                Ok((weights, total_weight))
+            }
+        }
+    }
+}
+
+/// A single stake move, for one validator
+#[derive(Clone, Debug, PartialEq)]
+pub enum StakeMove {
+    /// Move lamports from the reserve to the validator
+    Increase { vote_account: Pubkey, lamports: u64 },
+    /// Move lamports from the validator back to the reserve
+    Decrease { vote_account: Pubkey, lamports: u64 },
+}
+
+/// Stake moves bringing the validators closer to the strategy, and the
+/// validators left out because a transient stake account is in use
+#[derive(Debug, Default, PartialEq)]
+pub struct RebalancePlan {
+    pub moves: Vec<StakeMove>,
+    pub busy_validators: Vec<Pubkey>,
+}
+
+/// Plan the increases and decreases that move the active validators toward
+/// their share of the stake held by them and the reserve.
+///
+/// `reserve_lamports` is what the reserve holds above its rent-exempt
+/// reserve.  Validators keep `minimum_lamports` in their stake account, and
+/// moves too small to fund a transient stake account are dropped.  Decreased
+/// stake only reaches the reserve after an epoch, so increases are limited to
+/// the current reserve, going to the validators furthest below target first.
+pub fn plan_rebalance(
+    validators: &[ValidatorStakeInfo],
+    reserve_lamports: u64,
+    strategy: &dyn SelectionStrategy,
+    stake_rent: u64,
+    minimum_lamports: u64,
+) -> Result<RebalancePlan, Error> {
+    let active: Vec<&ValidatorStakeInfo> = validators.iter().filter(|v| v.is_active()).collect();
+    if active.is_empty() {
+        return Err("The pool has no active validators".into());
+    }
+    let (weights, total_weight) = strategy.weights(&active)?;
+    if total_weight == 0 {
+        return Err("No validator has a weight".into());
+    }
+    let total_lamports = active
+        .iter()
+        .try_fold(reserve_lamports as u128, |total, item| {
+            Some(total + item.total_lamports()? as u128)
+        })
+        .ok_or("Validator balances overflow")?;
+
+    let mut plan = RebalancePlan::default();
+    let mut increases = vec![];
+    for (item, weight) in active.iter().zip(weights) {
+        if u64::from(item.transient_stake_lamports) > 0 {
+            plan.busy_validators.push(item.vote_account);
+            continue;
+        }
+        let target_lamports = total_lamports * weight as u128 / total_weight as u128;
+        let active_lamports = u64::from(item.active_stake_lamports);
+        if target_lamports < active_lamports as u128 {
+            let lamports = std::cmp::min(
+                active_lamports - target_lamports as u64,
+                active_lamports.saturating_sub(minimum_lamports),
+            );
+            if lamports > stake_rent {
+                plan.moves.push(StakeMove::Decrease {
+                    vote_account: item.vote_account,
+                    lamports,
+                });
+            }
+        } else {
+            let lamports = u64::try_from(target_lamports - active_lamports as u128)?;
+            increases.push((item.vote_account, lamports));
+        }
+    }
+
+    increases.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut available_lamports = reserve_lamports;
+    for (vote_account, lamports) in increases {
+        let lamports = std::cmp::min(lamports, available_lamports);
+        if lamports < minimum_lamports {
+            continue;
+        }
+        available_lamports -= lamports;
+        plan.moves.push(StakeMove::Increase {
+            vote_account,
+            lamports,
+        });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_program::native_token::LAMPORTS_PER_SOL};
+
+    const STAKE_RENT: u64 = 2_282_880;
+    const MINIMUM_LAMPORTS: u64 = STAKE_RENT + LAMPORTS_PER_SOL;
+
+    fn validator(active_sol: u64, target_weight_bps: u16) -> ValidatorStakeInfo {
+        ValidatorStakeInfo {
+            vote_account: Pubkey::new_unique(),
+            active_stake_lamports: (active_sol * LAMPORTS_PER_SOL).into(),
+            target_weight_bps: target_weight_bps.into(),
+            ..ValidatorStakeInfo::default()
+        }
+    }
+
+    fn plan(
+        validators: &[ValidatorStakeInfo],
+        reserve_sol: u64,
+        strategy: &Strategy,
+    ) -> RebalancePlan {
+        plan_rebalance(
+            validators,
+            reserve_sol * LAMPORTS_PER_SOL,
+            strategy,
+            STAKE_RENT,
+            MINIMUM_LAMPORTS,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn equalize() {
+        let validators = vec![validator(10, 0), validator(2, 0), validator(3, 0)];
+        let plan = plan(&validators, 6, &Strategy::Equalize);
+        // the reserve runs out before the last increase is large enough
+        assert_eq!(
+            plan.moves,
+            vec![
+                StakeMove::Decrease {
+                    vote_account: validators[0].vote_account,
+                    lamports: 3 * LAMPORTS_PER_SOL,
+                },
+                StakeMove::Increase {
+                    vote_account: validators[1].vote_account,
+                    lamports: 5 * LAMPORTS_PER_SOL,
+                },
+            ]
+        );
+        assert!(plan.busy_validators.is_empty());
+    }
+
+    #[test]
+    fn proportional_keeps_the_rest_in_the_reserve() {
+        let validators = vec![validator(10, 2_000), validator(10, 3_000)];
+        let plan = plan(&validators, 20, &Strategy::Proportional);
+        assert_eq!(
+            plan.moves,
+            vec![
+                StakeMove::Decrease {
+                    vote_account: validators[0].vote_account,
+                    lamports: 2 * LAMPORTS_PER_SOL,
+                },
+                StakeMove::Increase {
+                    vote_account: validators[1].vote_account,
+                    lamports: 2 * LAMPORTS_PER_SOL,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn weights_keep_the_minimum_and_skip_busy_validators() {
+        let mut validators = vec![validator(10, 0), validator(5, 0), validator(5, 0)];
+        validators[2].transient_stake_lamports = LAMPORTS_PER_SOL.into();
+        let weights = [
+            (validators[1].vote_account, 1),
+            (validators[2].vote_account, 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let plan = plan(&validators, 0, &Strategy::Weights(weights));
+        assert_eq!(
+            plan.moves,
+            vec![StakeMove::Decrease {
+                vote_account: validators[0].vote_account,
+                lamports: 10 * LAMPORTS_PER_SOL - MINIMUM_LAMPORTS,
+            }]
+        );
+        assert_eq!(plan.busy_validators, vec![validators[2].vote_account]);
+    }
+
+    #[test]
+    fn weights_from_json() {
+        let vote_account = Pubkey::new_unique();
+        let strategy =
+            Strategy::weights_from_json(&format!("{{\"{}\": 3}}", vote_account)).unwrap();
+        assert_eq!(
+            strategy,
+            Strategy::Weights([(vote_account, 3)].iter().cloned().collect())
+        );
+        assert!(Strategy::weights_from_json("{\"not a key\": 3}").is_err());
+        assert!(Strategy::weights_from_json(&format!("{{\"{}\": 0}}", vote_account)).is_err());
+
+        // validators outside the pool are rejected
+        let validators = vec![validator(10, 0)];
+        assert!(plan_rebalance(&validators, 0, &strategy, STAKE_RENT, MINIMUM_LAMPORTS).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Validator selection strategies weighting the validators of a pool by
+//! score, read from validators.app style score files or derived from the
+//! cluster's vote accounts
+
+use {
+    crate::rebalance::SelectionStrategy,
+    solana_client::rpc_response::RpcVoteAccountStatus,
+    solana_program::pubkey::Pubkey,
+    spl_stake_pool::{state::ValidatorStakeInfo, MAX_TARGET_WEIGHT_BPS},
+    std::{collections::HashMap, str::FromStr},
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// What the scoring source knows about a validator
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ValidatorScore {
+    /// Higher is better, zero for validators that shouldn't get stake
+    pub score: u64,
+    /// Stake delegated to the validator across the whole cluster
+    pub network_stake_lamports: u64,
+}
+
+/// Scores of the cluster's validators, by vote account
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidatorScores {
+    scores: HashMap<Pubkey, ValidatorScore>,
+    total_network_stake_lamports: u64,
+}
+
+impl ValidatorScores {
+    /// Read a validators.app style score file, a JSON array of validators
+    /// with their `vote_account`, `total_score` and `active_stake`.  Negative
+    /// scores count as zero, other fields are ignored.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let entries: Vec<serde_json::Value> = serde_json::from_str(json)?;
+        let mut scores = Self::default();
+        for entry in entries {
+            let vote_account = entry["vote_account"]
+                .as_str()
+                .ok_or("Score entry has no vote_account")?;
+            let vote_account = Pubkey::from_str(vote_account)
+                .map_err(|_| format!("Invalid vote account address {}", vote_account))?;
+            let score = match &entry["total_score"] {
+                serde_json::Value::Number(score) => score
+                    .as_u64()
+                    .or_else(|| score.as_i64().map(|_| 0))
+                    .ok_or_else(|| format!("Invalid total_score for {}", vote_account))?,
+                _ => return Err(format!("Score entry {} has no total_score", vote_account).into()),
+            };
+            let network_stake_lamports = entry["active_stake"].as_u64().unwrap_or(0);
+            scores.insert(
+                vote_account,
+                ValidatorScore {
+                    score,
+                    network_stake_lamports,
+                },
+            )?;
+        }
+        Ok(scores)
+    }
+
+    /// Score the cluster's vote accounts by the credits they earned in the
+    /// latest epoch, less their commission.  Delinquent validators score zero.
+    pub fn from_vote_accounts(vote_accounts: &RpcVoteAccountStatus) -> Result<Self, Error> {
+        let mut scores = Self::default();
+        for (vote_account, is_delinquent) in vote_accounts
+            .current
+            .iter()
+            .map(|vote_account| (vote_account, false))
+            .chain(
+                vote_accounts
+                    .delinquent
+                    .iter()
+                    .map(|vote_account| (vote_account, true)),
+            )
+        {
+            let score = if is_delinquent {
+                0
+            } else {
+                let credits = vote_account
+                    .epoch_credits
+                    .last()
+                    .map_or(0, |(_, credits, previous_credits)| {
+                        credits.saturating_sub(*previous_credits)
+                    });
+                credits * (100 - u64::from(vote_account.commission.min(100))) / 100
+            };
+            let vote_pubkey = Pubkey::from_str(&vote_account.vote_pubkey).map_err(|_| {
+                format!("Invalid vote account address {}", vote_account.vote_pubkey)
+            })?;
+            scores.insert(
+                vote_pubkey,
+                ValidatorScore {
+                    score,
+                    network_stake_lamports: vote_account.activated_stake,
+                },
+            )?;
+        }
+        Ok(scores)
+    }
+
+    fn insert(&mut self, vote_account: Pubkey, score: ValidatorScore) -> Result<(), Error> {
+        if self.scores.insert(vote_account, score).is_some() {
+            return Err(format!("Validator {} is scored twice", vote_account).into());
+        }
+        self.total_network_stake_lamports = self
+            .total_network_stake_lamports
+            .checked_add(score.network_stake_lamports)
+            .ok_or("Network stake adds up to more than a u64")?;
+        Ok(())
+    }
+
+    /// Score of the validator, zero if it wasn't scored
+    pub fn score(&self, vote_account: &Pubkey) -> u64 {
+        self.scores
+            .get(vote_account)
+            .map_or(0, |validator| validator.score)
+    }
+
+    /// Share of the cluster's stake delegated to the validator, in basis points
+    pub fn network_share_bps(&self, vote_account: &Pubkey) -> u64 {
+        if self.total_network_stake_lamports == 0 {
+            return 0;
+        }
+        self.scores.get(vote_account).map_or(0, |validator| {
+            (validator.network_stake_lamports as u128 * MAX_TARGET_WEIGHT_BPS as u128
+                / self.total_network_stake_lamports as u128) as u64
+        })
+    }
+}
+
+/// Add up weights, failing on overflow
+fn total_weight(weights: &[u64]) -> Result<u64, Error> {
+    weights
+        .iter()
+        .try_fold(0u64, |total, weight| total.checked_add(*weight))
+        .ok_or_else(|| "Weights add up to more than a u64".into())
+}
+
+/// Stake in proportion to each validator's score
+pub struct ProportionalToScore {
+    pub scores: ValidatorScores,
+}
+
+impl SelectionStrategy for ProportionalToScore {
+    fn weights(&self, validators: &[&ValidatorStakeInfo]) -> Result<(Vec<u64>, u64), Error> {
+        let weights: Vec<u64> = validators
+            .iter()
+            .map(|item| self.scores.score(&item.vote_account))
+            .collect();
+        let total_weight = total_weight(&weights)?;
+        Ok((weights, total_weight))
+    }
+}
+
+/// The same stake for every validator scoring at least `min_score`, the
+/// others get only their minimum stake
+pub struct EqualWeight {
+    pub scores: ValidatorScores,
+    pub min_score: u64,
+}
+
+impl SelectionStrategy for EqualWeight {
+    fn weights(&self, validators: &[&ValidatorStakeInfo]) -> Result<(Vec<u64>, u64), Error> {
+        let min_score = self.min_score.max(1);
+        let weights: Vec<u64> = validators
+            .iter()
+            .map(|item| (self.scores.score(&item.vote_account) >= min_score) as u64)
+            .collect();
+        let total_weight = total_weight(&weights)?;
+        Ok((weights, total_weight))
+    }
+}
+
+/// Stake in proportion to score, tapering off as a validator's share of the
+/// cluster's stake grows, down to nothing at `max_network_share_bps`, so the
+/// pool doesn't add to the stake concentration of the cluster
+pub struct StakeConcentrationAware {
+    pub scores: ValidatorScores,
+    pub max_network_share_bps: u64,
+}
+
+impl SelectionStrategy for StakeConcentrationAware {
+    fn weights(&self, validators: &[&ValidatorStakeInfo]) -> Result<(Vec<u64>, u64), Error> {
+        if self.max_network_share_bps == 0 {
+            return Err("Maximum network stake share must be above zero".into());
+        }
+        let weights: Vec<u64> = validators
+            .iter()
+            .map(|item| {
+                let score = self.scores.score(&item.vote_account) as u128;
+                let headroom_bps = self
+                    .max_network_share_bps
+                    .saturating_sub(self.scores.network_share_bps(&item.vote_account));
+                (score * headroom_bps as u128 / self.max_network_share_bps as u128) as u64
+            })
+            .collect();
+        let total_weight = total_weight(&weights)?;
+        Ok((weights, total_weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, solana_client::rpc_response::RpcVoteAccountInfo,
+        solana_program::native_token::LAMPORTS_PER_SOL,
+    };
+
+    fn validator() -> ValidatorStakeInfo {
+        ValidatorStakeInfo {
+            vote_account: Pubkey::new_unique(),
+            active_stake_lamports: LAMPORTS_PER_SOL.into(),
+            ..ValidatorStakeInfo::default()
+        }
+    }
+
+    fn scores(entries: &[(&ValidatorStakeInfo, u64, u64)]) -> ValidatorScores {
+        let mut scores = ValidatorScores::default();
+        for (item, score, network_stake_sol) in entries {
+            scores
+                .insert(
+                    item.vote_account,
+                    ValidatorScore {
+                        score: *score,
+                        network_stake_lamports: network_stake_sol * LAMPORTS_PER_SOL,
+                    },
+                )
+                .unwrap();
+        }
+        scores
+    }
+
+    #[test]
+    fn from_json() {
+        let vote_accounts = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let json = format!(
+            "[{{\"vote_account\": \"{}\", \"total_score\": 9, \"active_stake\": 300}},
+              {{\"vote_account\": \"{}\", \"total_score\": -2, \"name\": \"ignored\"}}]",
+            vote_accounts[0], vote_accounts[1]
+        );
+        let scores = ValidatorScores::from_json(&json).unwrap();
+        assert_eq!(scores.score(&vote_accounts[0]), 9);
+        assert_eq!(scores.score(&vote_accounts[1]), 0);
+        assert_eq!(scores.score(&Pubkey::new_unique()), 0);
+        assert_eq!(scores.network_share_bps(&vote_accounts[0]), 10_000);
+
+        assert!(ValidatorScores::from_json("[{\"total_score\": 1}]").is_err());
+        let twice = format!(
+            "[{{\"vote_account\": \"{0}\", \"total_score\": 1}}, {{\"vote_account\": \"{0}\", \"total_score\": 1}}]",
+            vote_accounts[0]
+        );
+        assert!(ValidatorScores::from_json(&twice).is_err());
+    }
+
+    #[test]
+    fn from_vote_accounts() {
+        let vote_account = |commission, epoch_credits| RpcVoteAccountInfo {
+            vote_pubkey: Pubkey::new_unique().to_string(),
+            node_pubkey: Pubkey::new_unique().to_string(),
+            activated_stake: LAMPORTS_PER_SOL,
+            commission,
+            epoch_vote_account: true,
+            epoch_credits,
+            last_vote: 0,
+            root_slot: 0,
+        };
+        let status = RpcVoteAccountStatus {
+            current: vec![
+                vote_account(10, vec![(1, 1_000, 0), (2, 1_400, 1_000)]),
+                vote_account(100, vec![(2, 400, 0)]),
+            ],
+            delinquent: vec![vote_account(0, vec![(2, 400, 0)])],
+        };
+        let scores = ValidatorScores::from_vote_accounts(&status).unwrap();
+        let score = |vote_account: &RpcVoteAccountInfo| {
+            scores.score(&Pubkey::from_str(&vote_account.vote_pubkey).unwrap())
+        };
+        assert_eq!(score(&status.current[0]), 360);
+        assert_eq!(score(&status.current[1]), 0);
+        assert_eq!(score(&status.delinquent[0]), 0);
+    }
+
+    #[test]
+    fn proportional_to_score() {
+        let validators = vec![validator(), validator(), validator()];
+        let strategy = ProportionalToScore {
+            scores: scores(&[(&validators[0], 30, 0), (&validators[1], 10, 0)]),
+        };
+        let active: Vec<&ValidatorStakeInfo> = validators.iter().collect();
+        assert_eq!(strategy.weights(&active).unwrap(), (vec![30, 10, 0], 40));
+    }
+
+    #[test]
+    fn equal_weight_above_min_score() {
+        let validators = vec![validator(), validator(), validator()];
+        let strategy = EqualWeight {
+            scores: scores(&[
+                (&validators[0], 30, 0),
+                (&validators[1], 10, 0),
+                (&validators[2], 0, 0),
+            ]),
+            min_score: 20,
+        };
+        let active: Vec<&ValidatorStakeInfo> = validators.iter().collect();
+        assert_eq!(strategy.weights(&active).unwrap(), (vec![1, 0, 0], 1));
+
+        // without a minimum, validators scoring zero are still left out
+        let strategy = EqualWeight {
+            min_score: 0,
+            ..strategy
+        };
+        assert_eq!(strategy.weights(&active).unwrap(), (vec![1, 1, 0], 2));
+    }
+
+    #[test]
+    fn stake_concentration_aware() {
+        let validators = vec![validator(), validator(), validator()];
+        // 1%, 5% and 94% of the cluster's stake
+        let strategy = StakeConcentrationAware {
+            scores: scores(&[
+                (&validators[0], 100, 1),
+                (&validators[1], 100, 5),
+                (&validators[2], 100, 94),
+            ]),
+            max_network_share_bps: 1_000,
+        };
+        let active: Vec<&ValidatorStakeInfo> = validators.iter().collect();
+        assert_eq!(strategy.weights(&active).unwrap(), (vec![90, 50, 0], 140));
+
+        let strategy = StakeConcentrationAware {
+            max_network_share_bps: 0,
+            ..strategy
+        };
+        assert!(strategy.weights(&active).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Splitting a pool update into `UpdateValidatorListBalance` instructions
+//! that each fit a transaction and the compute budget
+
+use {
+    crate::packer::TransactionPacker,
+    solana_program::{clock::Epoch, pubkey::Pubkey},
+    spl_stake_pool::{find_withdraw_authority_program_address, state::ValidatorStakeInfo},
+};
+
+/// Compute units a single instruction may consume
+pub const MAX_INSTRUCTION_COMPUTE_UNITS: u64 = 200_000;
+
+/// Compute units `UpdateValidatorListBalance` needs per validator at worst,
+/// when both its stake accounts are merged, as bounded by the program's
+/// compute unit tests
+pub const UPDATE_COMPUTE_UNITS_PER_VALIDATOR: u64 = 30_000;
+
+/// One `UpdateValidatorListBalance` instruction: the validators it updates
+/// and the index of the first one in the list
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateChunk {
+    pub start_index: u32,
+    pub vote_accounts: Vec<Pubkey>,
+}
+
+/// Most validators a single `UpdateValidatorListBalance` instruction can
+/// update, limited by the compute budget and by the accounts that fit in a
+/// transaction paid by `fee_payer`, two per validator
+pub fn validators_per_update(fee_payer: &Pubkey, max_compute_units: u64) -> usize {
+    let by_compute_units = (max_compute_units / UPDATE_COMPUTE_UNITS_PER_VALIDATOR) as usize;
+    let packer = TransactionPacker::new(*fee_payer);
+    let stake_pool = Pubkey::new_unique();
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), &stake_pool);
+    (1..=by_compute_units)
+        .rev()
+        .find(|&count| {
+            let vote_accounts: Vec<Pubkey> = (0..count).map(|_| Pubkey::new_unique()).collect();
+            spl_stake_pool::instruction::update_validator_list_balance(
+                &spl_stake_pool::id(),
+                &stake_pool,
+                &withdraw_authority,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &vote_accounts,
+                0,
+                false,
+            )
+            .map_or(false, |instruction| packer.fits(&[instruction]))
+        })
+        .unwrap_or(1)
+}
+
+/// Split the validator list into chunks of at most `validators_per_update`
+/// consecutive validators, leaving out the chunks already updated in `epoch`
+pub fn plan_update(
+    validators: &[ValidatorStakeInfo],
+    epoch: Epoch,
+    validators_per_update: usize,
+) -> Vec<UpdateChunk> {
+    validators
+        .chunks(validators_per_update.max(1))
+        .enumerate()
+        .filter(|(_, chunk)| chunk.iter().any(|item| item.is_stale(epoch)))
+        .map(|(i, chunk)| UpdateChunk {
+            start_index: (i * validators_per_update.max(1)) as u32,
+            vote_accounts: chunk.iter().map(|item| item.vote_account).collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validator(last_update_epoch: Epoch) -> ValidatorStakeInfo {
+        ValidatorStakeInfo {
+            vote_account: Pubkey::new_unique(),
+            last_update_epoch: last_update_epoch.into(),
+            ..ValidatorStakeInfo::default()
+        }
+    }
+
+    #[test]
+    fn validators_per_update_within_limits() {
+        let fee_payer = Pubkey::new_unique();
+        let count = validators_per_update(&fee_payer, MAX_INSTRUCTION_COMPUTE_UNITS);
+        assert_eq!(
+            count as u64,
+            MAX_INSTRUCTION_COMPUTE_UNITS / UPDATE_COMPUTE_UNITS_PER_VALIDATOR
+        );
+
+        // a generous budget is capped by the transaction size instead
+        let count = validators_per_update(&fee_payer, 100 * MAX_INSTRUCTION_COMPUTE_UNITS);
+        assert!(count > 6);
+        assert!(count < 100);
+
+        assert_eq!(validators_per_update(&fee_payer, 0), 1);
+    }
+
+    #[test]
+    fn plan_skips_updated_chunks() {
+        // 32 validators, those from 10 to 19 already updated in epoch 5
+        let validators: Vec<ValidatorStakeInfo> = (0..32)
+            .map(|i| validator(if (10..20).contains(&i) { 5 } else { 4 }))
+            .collect();
+        let plan = plan_update(&validators, 5, 6);
+        let start_indices: Vec<u32> = plan.iter().map(|chunk| chunk.start_index).collect();
+        // [12, 18) is the only chunk entirely up to date
+        assert_eq!(start_indices, vec![0, 6, 18, 24, 30]);
+        assert_eq!(plan[1].vote_accounts.len(), 6);
+        assert_eq!(plan[4].vote_accounts.len(), 2);
+        assert_eq!(
+            plan[2].vote_accounts,
+            validators[18..24]
+                .iter()
+                .map(|item| item.vote_account)
+                .collect::<Vec<_>>()
+        );
+
+        assert!(plan_update(&validators, 4, 6).is_empty());
+    }
+}
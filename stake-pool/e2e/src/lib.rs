@@ -0,0 +1,319 @@
+//! Harness for end-to-end tests of the stake pool: runs `solana-test-validator`
+//! with the stake pool program deployed, and the real CLI binary against it.
+//!
+//! The validator and the CLI are located with the `SOLANA_TEST_VALIDATOR`,
+//! `SPL_STAKE_POOL_PROGRAM` and `SPL_STAKE_POOL_CLI` environment variables,
+//! defaulting to the validator on the `PATH` and the workspace build outputs.
+
+use {
+    solana_client::rpc_client::RpcClient,
+    solana_program::{program_pack::Pack, pubkey::Pubkey},
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        signature::{write_keypair_file, Keypair, Signer},
+        transaction::Transaction,
+    },
+    spl_stake_pool::{
+        stake_program,
+        state::{StakePool, ValidatorList},
+    },
+    std::{
+        env,
+        path::PathBuf,
+        process::{Child, Command, Stdio},
+        sync::{Mutex, MutexGuard},
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+    tempfile::TempDir,
+};
+
+lazy_static::lazy_static! {
+    /// Held by the running validator, tests in the same process take turns
+    static ref VALIDATOR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub type Error = Box<dyn std::error::Error>;
+
+/// Slots per epoch of the local cluster, short enough for stake to activate
+/// within a test
+pub const SLOTS_PER_EPOCH: u64 = 32;
+
+/// How long to wait for the validator to start, or for an epoch to pass
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+const RPC_URL: &str = "http://127.0.0.1:8899";
+
+fn workspace_path(relative: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .join(relative)
+}
+
+fn path_from_env(var: &str, default: PathBuf) -> PathBuf {
+    env::var_os(var).map_or(default, PathBuf::from)
+}
+
+/// A `solana-test-validator` running with a fresh ledger, killed on drop.
+/// Only one can run at a time since it listens on the default ports.
+pub struct LocalValidator {
+    child: Child,
+    _ledger: TempDir,
+    pub rpc_client: RpcClient,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl LocalValidator {
+    /// Start the validator with the stake pool program deployed at its
+    /// declared address, and wait until it serves RPC requests
+    pub fn start() -> Result<Self, Error> {
+        let program = path_from_env(
+            "SPL_STAKE_POOL_PROGRAM",
+            workspace_path("target/deploy/spl_stake_pool.so"),
+        );
+        if !program.exists() {
+            return Err(format!(
+                "Stake pool program not found at {}, run `cargo build-bpf` first",
+                program.display()
+            )
+            .into());
+        }
+
+        // a test that panicked still killed its validator on the way out
+        let lock = VALIDATOR_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let ledger = TempDir::new()?;
+        let child = Command::new(path_from_env(
+            "SOLANA_TEST_VALIDATOR",
+            PathBuf::from("solana-test-validator"),
+        ))
+        .arg("--reset")
+        .arg("--quiet")
+        .arg("--ledger")
+        .arg(ledger.path())
+        .arg("--slots-per-epoch")
+        .arg(SLOTS_PER_EPOCH.to_string())
+        .arg("--bpf-program")
+        .arg(spl_stake_pool::id().to_string())
+        .arg(&program)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Unable to start solana-test-validator: {}", err))?;
+
+        let mut validator = Self {
+            child,
+            _ledger: ledger,
+            rpc_client: RpcClient::new_with_commitment(
+                RPC_URL.to_string(),
+                CommitmentConfig::confirmed(),
+            ),
+            _lock: lock,
+        };
+        validator.wait_until_ready()?;
+        Ok(validator)
+    }
+
+    fn wait_until_ready(&mut self) -> Result<(), Error> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Err(format!("solana-test-validator exited with {}", status).into());
+            }
+            if self.rpc_client.get_slot().map_or(false, |slot| slot > 0) {
+                return Ok(());
+            }
+            if start.elapsed() > TIMEOUT {
+                return Err("Timed out waiting for solana-test-validator to start".into());
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        RPC_URL
+    }
+
+    /// Vote account of the validator itself, the only one in the cluster
+    pub fn vote_account(&self) -> Result<Pubkey, Error> {
+        let vote_accounts = self.rpc_client.get_vote_accounts()?;
+        let vote_account = vote_accounts
+            .current
+            .first()
+            .ok_or("The local validator has no vote account")?;
+        Ok(vote_account.vote_pubkey.parse()?)
+    }
+
+    /// Fund an account from the faucet and wait for the lamports to land
+    pub fn airdrop(&self, to: &Pubkey, lamports: u64) -> Result<(), Error> {
+        let expected_balance = self.rpc_client.get_balance(to)? + lamports;
+        self.rpc_client.request_airdrop(to, lamports)?;
+        let start = Instant::now();
+        while self.rpc_client.get_balance(to)? < expected_balance {
+            if start.elapsed() > TIMEOUT {
+                return Err(format!("Timed out waiting for the airdrop to {}", to).into());
+            }
+            sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Block until the cluster enters the next epoch
+    pub fn wait_for_next_epoch(&self) -> Result<u64, Error> {
+        let epoch = self.rpc_client.get_epoch_info()?.epoch;
+        let start = Instant::now();
+        loop {
+            let current_epoch = self.rpc_client.get_epoch_info()?.epoch;
+            if current_epoch > epoch {
+                return Ok(current_epoch);
+            }
+            if start.elapsed() > TIMEOUT {
+                return Err(format!("Timed out waiting for epoch {}", epoch + 1).into());
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Create a stake account owned by `authority` and delegate it
+    pub fn create_delegated_stake(
+        &self,
+        payer: &Keypair,
+        authority: &Keypair,
+        vote_account: &Pubkey,
+        lamports: u64,
+    ) -> Result<Pubkey, Error> {
+        let stake = Keypair::new();
+        let mut instructions = stake_program::create_account(
+            &payer.pubkey(),
+            &stake.pubkey(),
+            &stake_program::Authorized {
+                staker: authority.pubkey(),
+                withdrawer: authority.pubkey(),
+            },
+            &stake_program::Lockup::default(),
+            lamports,
+        );
+        instructions.push(stake_program::delegate_stake(
+            &stake.pubkey(),
+            &authority.pubkey(),
+            vote_account,
+        ));
+        let (recent_blockhash, _) = self.rpc_client.get_recent_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer, &stake, authority],
+            recent_blockhash,
+        );
+        self.rpc_client.send_and_confirm_transaction(&transaction)?;
+        Ok(stake.pubkey())
+    }
+
+    pub fn get_stake_pool(&self, stake_pool_address: &Pubkey) -> Result<StakePool, Error> {
+        let account_data = self.rpc_client.get_account_data(stake_pool_address)?;
+        Ok(StakePool::deserialize_any_version(account_data.as_slice())?.into_stake_pool())
+    }
+
+    pub fn get_validator_list(
+        &self,
+        validator_list_address: &Pubkey,
+    ) -> Result<ValidatorList, Error> {
+        let account_data = self.rpc_client.get_account_data(validator_list_address)?;
+        Ok(ValidatorList::deserialize_checked(account_data.as_slice())?)
+    }
+
+    pub fn get_stake_state(
+        &self,
+        stake_address: &Pubkey,
+    ) -> Result<stake_program::StakeState, Error> {
+        let account_data = self.rpc_client.get_account_data(stake_address)?;
+        Ok(bincode::deserialize(account_data.as_slice())?)
+    }
+
+    pub fn get_token_balance(&self, token_account_address: &Pubkey) -> Result<u64, Error> {
+        let account_data = self.rpc_client.get_account_data(token_account_address)?;
+        Ok(spl_token::state::Account::unpack(account_data.as_slice())?.amount)
+    }
+
+    pub fn get_token_supply(&self, mint_address: &Pubkey) -> Result<u64, Error> {
+        let account_data = self.rpc_client.get_account_data(mint_address)?;
+        Ok(spl_token::state::Mint::unpack(account_data.as_slice())?.supply)
+    }
+}
+
+impl Drop for LocalValidator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs the `spl-stake-pool` binary against a validator, with a CLI config
+/// file whose default keypair signs and pays for everything
+pub struct Cli {
+    binary: PathBuf,
+    config_file: PathBuf,
+    _config_dir: TempDir,
+}
+
+impl Cli {
+    pub fn new(validator: &LocalValidator, keypair: &Keypair) -> Result<Self, Error> {
+        let config_dir = TempDir::new()?;
+        let keypair_path = config_dir.path().join("id.json");
+        write_keypair_file(keypair, &keypair_path)?;
+
+        let config_file = config_dir.path().join("config.yml");
+        let config = solana_cli_config::Config {
+            json_rpc_url: validator.rpc_url().to_string(),
+            keypair_path: keypair_path.to_string_lossy().to_string(),
+            ..solana_cli_config::Config::default()
+        };
+        config.save(&config_file.to_string_lossy())?;
+
+        Ok(Self {
+            binary: path_from_env(
+                "SPL_STAKE_POOL_CLI",
+                workspace_path("target/debug/spl-stake-pool"),
+            ),
+            config_file,
+            _config_dir: config_dir,
+        })
+    }
+
+    /// Run a command, returning its standard output, or its standard error
+    /// if it fails
+    pub fn run(&self, args: &[&str]) -> Result<String, Error> {
+        let output = Command::new(&self.binary)
+            .arg("--config")
+            .arg(&self.config_file)
+            .args(args)
+            .output()
+            .map_err(|err| {
+                format!(
+                    "Unable to run {}, build the CLI first: {}",
+                    self.binary.display(),
+                    err
+                )
+            })?;
+        if !output.status.success() {
+            return Err(format!(
+                "`spl-stake-pool {}` failed with {}:\n{}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Run a command with JSON output and parse it
+    pub fn run_json(&self, args: &[&str]) -> Result<serde_json::Value, Error> {
+        let mut json_args = vec!["--output", "json"];
+        json_args.extend_from_slice(args);
+        Ok(serde_json::from_str(&self.run(&json_args)?)?)
+    }
+}
@@ -0,0 +1,201 @@
+// Needs solana-test-validator, the BPF program and the CLI binary
+#![cfg(feature = "test-e2e")]
+
+use {
+    solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey},
+    solana_sdk::signature::{Keypair, Signer},
+    spl_stake_pool::{find_stake_program_address, stake_program},
+    spl_stake_pool_e2e::{Cli, LocalValidator},
+};
+
+const DEPOSIT_LAMPORTS: u64 = 10 * LAMPORTS_PER_SOL;
+
+fn parse_created_stake_pool(output: &str) -> Pubkey {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("Creating stake pool "))
+        .expect("create-pool prints the stake pool address")
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn success_create_deposit_update_withdraw() {
+    let validator = LocalValidator::start().unwrap();
+    let payer = Keypair::new();
+    validator
+        .airdrop(&payer.pubkey(), 100 * LAMPORTS_PER_SOL)
+        .unwrap();
+    let cli = Cli::new(&validator, &payer).unwrap();
+
+    // create
+    let output = cli
+        .run(&[
+            "create-pool",
+            "--fee-numerator",
+            "0",
+            "--fee-denominator",
+            "100",
+            "--max-validators",
+            "10",
+        ])
+        .unwrap();
+    let stake_pool_address = parse_created_stake_pool(&output);
+    let pool = stake_pool_address.to_string();
+    let stake_pool = validator.get_stake_pool(&stake_pool_address).unwrap();
+    assert_eq!(stake_pool.manager, payer.pubkey());
+    assert_eq!(stake_pool.staker, payer.pubkey());
+    assert_eq!(stake_pool.pool_token_supply, 0);
+
+    // add the local validator, once its stake account is active
+    let vote_account = validator.vote_account().unwrap();
+    cli.run(&["create-validator-stake", &pool, &vote_account.to_string()])
+        .unwrap();
+    let (validator_stake_address, _) =
+        find_stake_program_address(&spl_stake_pool::id(), &vote_account, &stake_pool_address);
+    validator.wait_for_next_epoch().unwrap();
+    cli.run(&["add-validator", &pool, &validator_stake_address.to_string()])
+        .unwrap();
+    let validator_list = validator
+        .get_validator_list(&stake_pool.validator_list)
+        .unwrap();
+    assert!(validator_list.contains(&vote_account));
+
+    // deposit
+    let stake_address = validator
+        .create_delegated_stake(&payer, &payer, &vote_account, DEPOSIT_LAMPORTS)
+        .unwrap();
+    validator.wait_for_next_epoch().unwrap();
+
+    // a dry run only simulates the update, so the pool must be current
+    let error = cli
+        .run(&["--dry-run", "deposit", &pool, &stake_address.to_string()])
+        .unwrap_err();
+    assert!(error.to_string().contains("run `update` first"));
+    cli.run(&["update", &pool]).unwrap();
+
+    // a dry run sends nothing, but predicts the pool tokens minted
+    let dry_run = cli
+        .run_json(&["--dry-run", "deposit", &pool, &stake_address.to_string()])
+        .unwrap();
+    assert_eq!(dry_run["transactions"].as_u64().unwrap(), 1);
+    assert!(dry_run["computeUnits"].as_u64().unwrap() > 0);
+    assert_eq!(
+        validator.rpc_client.get_balance(&stake_address).unwrap(),
+        DEPOSIT_LAMPORTS
+    );
+
+    let deposit = cli
+        .run_json(&["deposit", &pool, &stake_address.to_string()])
+        .unwrap();
+    let token_receiver: Pubkey = deposit["tokenReceiver"].as_str().unwrap().parse().unwrap();
+    assert_eq!(
+        deposit["validatorStakeAccount"].as_str().unwrap(),
+        validator_stake_address.to_string()
+    );
+
+    // the deposited stake is merged into the validator stake account
+    assert_eq!(validator.rpc_client.get_balance(&stake_address).unwrap(), 0);
+    let pool_tokens = validator.get_token_balance(&token_receiver).unwrap();
+    assert!(pool_tokens >= DEPOSIT_LAMPORTS);
+    // rewards may have come in between, if an epoch went by
+    let predicted_pool_tokens = dry_run["mintedPoolTokens"].as_u64().unwrap();
+    assert!(predicted_pool_tokens >= pool_tokens);
+    assert!(predicted_pool_tokens - pool_tokens <= pool_tokens / 100);
+    let stake_pool = validator.get_stake_pool(&stake_pool_address).unwrap();
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        validator.get_token_supply(&stake_pool.pool_mint).unwrap()
+    );
+    let total_stake_lamports = stake_pool.total_stake_lamports;
+    assert!(total_stake_lamports >= DEPOSIT_LAMPORTS);
+
+    // update
+    let epoch = validator.wait_for_next_epoch().unwrap();
+    cli.run(&["update", &pool]).unwrap();
+    let stake_pool = validator.get_stake_pool(&stake_pool_address).unwrap();
+    assert!(stake_pool.last_update_epoch >= epoch);
+    assert!(stake_pool.total_stake_lamports >= total_stake_lamports);
+
+    // withdraw
+    let withdraw = cli
+        .run_json(&[
+            "withdraw",
+            &pool,
+            "--amount",
+            "5",
+            "--withdraw-from",
+            &token_receiver.to_string(),
+        ])
+        .unwrap();
+    let withdrawals = withdraw["withdrawals"].as_array().unwrap();
+    assert_eq!(withdrawals.len(), 1);
+    let stake_receiver: Pubkey = withdrawals[0]["stakeReceiver"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    match validator.get_stake_state(&stake_receiver).unwrap() {
+        stake_program::StakeState::Stake(_, stake) => {
+            assert_eq!(stake.delegation.voter_pubkey, vote_account)
+        }
+        state => panic!("Unexpected stake receiver state {:?}", state),
+    }
+    assert_eq!(
+        validator.rpc_client.get_balance(&stake_receiver).unwrap(),
+        withdrawals[0]["lamports"].as_u64().unwrap()
+    );
+    assert_eq!(
+        validator.get_token_balance(&token_receiver).unwrap(),
+        pool_tokens - 5 * LAMPORTS_PER_SOL
+    );
+    let stake_pool = validator.get_stake_pool(&stake_pool_address).unwrap();
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        validator.get_token_supply(&stake_pool.pool_mint).unwrap()
+    );
+}
+
+#[test]
+fn fail_deposit_to_unknown_validator() {
+    let validator = LocalValidator::start().unwrap();
+    let payer = Keypair::new();
+    validator
+        .airdrop(&payer.pubkey(), 100 * LAMPORTS_PER_SOL)
+        .unwrap();
+    let cli = Cli::new(&validator, &payer).unwrap();
+
+    let output = cli
+        .run(&[
+            "create-pool",
+            "--fee-numerator",
+            "0",
+            "--fee-denominator",
+            "100",
+            "--max-validators",
+            "10",
+        ])
+        .unwrap();
+    let stake_pool_address = parse_created_stake_pool(&output);
+
+    // the CLI refuses the deposit before sending anything
+    let vote_account = validator.vote_account().unwrap();
+    let stake_address = validator
+        .create_delegated_stake(&payer, &payer, &vote_account, DEPOSIT_LAMPORTS)
+        .unwrap();
+    let error = cli
+        .run(&[
+            "deposit",
+            &stake_pool_address.to_string(),
+            &stake_address.to_string(),
+        ])
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("Stake account for this validator does not exist in the pool"));
+    assert_eq!(
+        validator.rpc_client.get_balance(&stake_address).unwrap(),
+        DEPOSIT_LAMPORTS
+    );
+}
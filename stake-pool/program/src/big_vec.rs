@@ -0,0 +1,157 @@
+//! Zero-copy access to a Borsh-serialized `Vec` of fixed-size entries at the
+//! end of account data
+
+use {bytemuck::Pod, solana_program::program_error::ProgramError, std::mem};
+
+/// Size of the `u32` length prefix of a Borsh-serialized `Vec`
+const LENGTH_SIZE: usize = 4;
+
+/// Borsh-serialized `Vec<T>` of `Pod` entries, read and written in place: a
+/// `u32` length followed by the entries, with room for more entries after
+/// them up to the end of the data
+#[derive(Debug)]
+pub struct BigVec<'data> {
+    /// Underlying data, starting at the length prefix
+    pub data: &'data mut [u8],
+}
+
+impl<'data> BigVec<'data> {
+    /// Wrap `data`, which must hold at least the length prefix
+    pub fn new(data: &'data mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < LENGTH_SIZE {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Ok(Self { data })
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> u32 {
+        read_len(self.data)
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Entries, in order
+    pub fn as_slice<T: Pod>(&self) -> Result<&[T], ProgramError> {
+        deserialize_slice(self.data)
+    }
+
+    /// Mutable entries, in order, written straight to the underlying data
+    pub fn as_mut_slice<T: Pod>(&mut self) -> Result<&mut [T], ProgramError> {
+        let end = entries_end::<T>(self.data)?;
+        bytemuck::try_cast_slice_mut(&mut self.data[LENGTH_SIZE..end])
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Append `entry`, failing with `AccountDataTooSmall` if the data has no
+    /// room left for it
+    pub fn push<T: Pod>(&mut self, entry: T) -> Result<(), ProgramError> {
+        let start = entries_end::<T>(self.data)?;
+        let end = start
+            .checked_add(mem::size_of::<T>())
+            .filter(|end| *end <= self.data.len())
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        self.data[start..end].copy_from_slice(bytemuck::bytes_of(&entry));
+        self.set_len(self.len() + 1);
+        Ok(())
+    }
+
+    /// Keep only the entries matching `predicate`, in order, zeroing the
+    /// space freed after them.  Returns the number of entries removed
+    pub fn retain<T: Pod>(&mut self, predicate: impl Fn(&T) -> bool) -> Result<u32, ProgramError> {
+        let entries = self.as_mut_slice::<T>()?;
+        let mut kept = 0;
+        for index in 0..entries.len() {
+            if predicate(&entries[index]) {
+                entries[kept] = entries[index];
+                kept += 1;
+            }
+        }
+        let removed = entries.len() - kept;
+        for entry in &mut entries[kept..] {
+            *entry = T::zeroed();
+        }
+        self.set_len(kept as u32);
+        Ok(removed as u32)
+    }
+
+    fn set_len(&mut self, len: u32) {
+        self.data[..LENGTH_SIZE].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+/// Read-only counterpart of `BigVec::as_slice`, for data starting at the
+/// length prefix
+pub fn deserialize_slice<T: Pod>(data: &[u8]) -> Result<&[T], ProgramError> {
+    let end = entries_end::<T>(data)?;
+    bytemuck::try_cast_slice(&data[LENGTH_SIZE..end]).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+fn read_len(data: &[u8]) -> u32 {
+    let mut len = [0u8; LENGTH_SIZE];
+    len.copy_from_slice(&data[..LENGTH_SIZE]);
+    u32::from_le_bytes(len)
+}
+
+/// Offset of the end of the entries, checking that they fit in `data`
+fn entries_end<T>(data: &[u8]) -> Result<usize, ProgramError> {
+    if data.len() < LENGTH_SIZE {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    (read_len(data) as usize)
+        .checked_mul(mem::size_of::<T>())
+        .and_then(|len| len.checked_add(LENGTH_SIZE))
+        .filter(|end| *end <= data.len())
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, bytemuck::Zeroable};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct Entry([u8; 3]);
+    unsafe impl Zeroable for Entry {}
+    unsafe impl Pod for Entry {}
+
+    #[test]
+    fn push_and_retain_in_place() {
+        let mut data = [0u8; LENGTH_SIZE + 3 * 3 + 2];
+        let mut big_vec = BigVec::new(&mut data).unwrap();
+        assert!(big_vec.is_empty());
+        for index in 1..=3 {
+            big_vec.push(Entry([index; 3])).unwrap();
+        }
+        assert_eq!(
+            big_vec.push(Entry([4; 3])),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+        assert_eq!(big_vec.len(), 3);
+
+        big_vec.as_mut_slice::<Entry>().unwrap()[0].0[0] = 9;
+        assert_eq!(big_vec.retain(|entry: &Entry| entry.0[1] != 2), Ok(1));
+        assert_eq!(
+            big_vec.as_slice::<Entry>().unwrap(),
+            &[Entry([9, 1, 1]), Entry([3; 3])]
+        );
+        assert_eq!(data, [2, 0, 0, 0, 9, 1, 1, 3, 3, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(deserialize_slice::<Entry>(&data).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn length_past_the_data() {
+        let data = [3u8, 0, 0, 0, 1, 1, 1, 2, 2, 2];
+        assert_eq!(
+            deserialize_slice::<Entry>(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            deserialize_slice::<Entry>(&data[..2]),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+    }
+}
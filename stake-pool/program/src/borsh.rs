@@ -1,18 +1,10 @@
 //! Extra borsh utils
-//! TODO delete once try_from_slice_unchecked has been published
 
 use {
     borsh::{maybestd::io::Error, BorshDeserialize, BorshSerialize},
     std::io::{self, Write},
 };
 
-/// Deserializes something and allows for incomplete reading
-pub fn try_from_slice_unchecked<T: BorshDeserialize>(data: &[u8]) -> Result<T, Error> {
-    let mut data_mut = data;
-    let result = T::deserialize(&mut data_mut)?;
-    Ok(result)
-}
-
 /// Helper struct which to count how much data would be written during serialization
 #[derive(Default)]
 struct WriteCounter {
@@ -37,3 +29,10 @@ pub fn get_instance_packed_len<T: BorshSerialize>(instance: &T) -> Result<usize,
     instance.serialize(&mut counter)?;
     Ok(counter.count)
 }
+
+/// Deserializes a `T` from the start of `data`, ignoring any bytes after it,
+/// so that account data with fields appended by a later version still reads
+pub fn try_from_slice_unchecked<T: BorshDeserialize>(data: &[u8]) -> Result<T, Error> {
+    let mut data = data;
+    T::deserialize(&mut data)
+}
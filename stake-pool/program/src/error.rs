@@ -1,7 +1,7 @@
 //! Error types
 
 use num_derive::FromPrimitive;
-use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use solana_program::{decode_error::DecodeError, msg, program_error::ProgramError};
 use thiserror::Error;
 
 /// Errors that may be returned by the StakePool program.
@@ -85,6 +85,146 @@ pub enum StakePoolError {
     /// Pool token supply is not zero on initialization
     #[error("NonZeroPoolTokenSupply")]
     NonZeroPoolTokenSupply,
+    /// Provided reserve stake account does not match the stake pool's reserve
+    #[error("InvalidReserveStakeAccount")]
+    InvalidReserveStakeAccount,
+    /// Not enough lamports in the reserve to cover the SOL withdrawal
+    #[error("SolWithdrawalTooLarge")]
+    SolWithdrawalTooLarge,
+
+    // 25.
+    /// Withdrawal fee increased by more than the maximum allowed in one epoch
+    #[error("FeeIncreaseTooHigh")]
+    FeeIncreaseTooHigh,
+    /// The deposited stake is delegated to a different vote account than the
+    /// validator stake account
+    #[error("IncorrectDepositVoteAddress")]
+    IncorrectDepositVoteAddress,
+    /// Withdrawals must come from the preferred withdraw validator while it can
+    /// cover them
+    #[error("IncorrectWithdrawVoteAddress")]
+    IncorrectWithdrawVoteAddress,
+    /// Provided stake deposit authority does not match the pool's
+    #[error("InvalidStakeDepositAuthority")]
+    InvalidStakeDepositAuthority,
+
+    // 30.
+    /// Provided SOL deposit authority does not match the pool's
+    #[error("InvalidSolDepositAuthority")]
+    InvalidSolDepositAuthority,
+    /// The validator's transient stake account is already in use by a rebalance
+    #[error("TransientAccountInUse")]
+    TransientAccountInUse,
+    /// Pool tokens are still locked after the depositor's last deposit
+    #[error("DepositLocked")]
+    DepositLocked,
+    /// Provided deposit record does not match the depositor's program address
+    #[error("InvalidDepositRecord")]
+    InvalidDepositRecord,
+    /// The validator is being removed from the pool
+    #[error("ValidatorNotActive")]
+    ValidatorNotActive,
+
+    // 35.
+    /// Validator target weights add up to more than `MAX_TARGET_WEIGHT_BPS`
+    #[error("InvalidTargetWeights")]
+    InvalidTargetWeights,
+    /// The reserve can only be withdrawn from once every validator stake
+    /// account is down to the minimum it must hold
+    #[error("StakeLamportsNotEqualToMinimum")]
+    StakeLamportsNotEqualToMinimum,
+    /// Deposit or withdrawal amount is below the minimum requested by the user
+    #[error("ExceededSlippage")]
+    ExceededSlippage,
+    /// Provided metadata account does not match the pool mint's metadata
+    /// account
+    #[error("InvalidMetadataAccount")]
+    InvalidMetadataAccount,
+    /// Deposit would take the pool's total stake above its deposit cap
+    #[error("DepositCapExceeded")]
+    DepositCapExceeded,
+    /// Deposit is too small to mint any pool tokens
+    #[error("DepositTooSmall")]
+    DepositTooSmall,
+    /// Account is not of the type expected by the instruction
+    #[error("WrongAccountType")]
+    WrongAccountType,
+    /// Account layout version is not supported by this program
+    #[error("UnsupportedAccountVersion")]
+    UnsupportedAccountVersion,
+    /// Provided price oracle account is not the pool's price oracle
+    #[error("InvalidPriceOracle")]
+    InvalidPriceOracle,
+    /// Pool has not opted in to permissionless decreases on delinquent validators
+    #[error("DelinquentDecreaseDisabled")]
+    DelinquentDecreaseDisabled,
+    /// Validator has earned vote credits within the pool's delinquency threshold
+    #[error("ValidatorNotDelinquent")]
+    ValidatorNotDelinquent,
+    /// Validator entry has not been updated this epoch, so its balances are
+    /// out of date
+    #[error("ValidatorEntryStale")]
+    ValidatorEntryStale,
+    /// Stake split would leave one of the stake accounts below its minimum
+    /// balance
+    #[error("StakeSplitBelowMinimum")]
+    StakeSplitBelowMinimum,
+    /// Provided deposit interceptor is not the pool's, or a strict pool's
+    /// interceptor is missing
+    #[error("InvalidDepositInterceptor")]
+    InvalidDepositInterceptor,
+    /// Deposited stake account has a lockup still in force
+    #[error("StakeLockupInForce")]
+    StakeLockupInForce,
+    /// Withdrawal would take the epoch's net outflow above the pool's cap
+    #[error("EpochNetOutflowExceeded")]
+    EpochNetOutflowExceeded,
+    /// Stake pool is paused by its manager
+    #[error("StakePoolPaused")]
+    StakePoolPaused,
+    /// Account data is shorter than the layout of its account type
+    #[error("AccountDataTooShort")]
+    AccountDataTooShort,
+    /// Account data is longer than the layout of its account type
+    #[error("AccountDataTooLong")]
+    AccountDataTooLong,
+    /// None of the pool's validators has a target weight
+    #[error("TargetWeightsNotSet")]
+    TargetWeightsNotSet,
+    /// Validator is as close to its target weight as rebalancing can take it
+    #[error("ValidatorAtTarget")]
+    ValidatorAtTarget,
+    /// Stake pool is being decommissioned by its manager
+    #[error("StakePoolDecommissioning")]
+    StakePoolDecommissioning,
+    /// Stake pool is not being decommissioned
+    #[error("StakePoolNotDecommissioning")]
+    StakePoolNotDecommissioning,
+    /// Stake pool still has validators or outstanding pool tokens
+    #[error("StakePoolNotEmpty")]
+    StakePoolNotEmpty,
+    /// Validator is still onboarding and does not accept stake yet
+    #[error("ValidatorOnboarding")]
+    ValidatorOnboarding,
+    /// Provided rate history account is not the pool's rate history
+    #[error("InvalidRateHistory")]
+    InvalidRateHistory,
+    /// A manager action is already waiting for the timelock
+    #[error("ManagerActionPending")]
+    ManagerActionPending,
+    /// No manager action is waiting for the timelock
+    #[error("NoPendingManagerAction")]
+    NoPendingManagerAction,
+    /// Pending manager action is still timelocked
+    #[error("ManagerActionTimelocked")]
+    ManagerActionTimelocked,
+    /// Signer is not the withdraw authority of the validator's vote account
+    #[error("WrongVoteWithdrawer")]
+    WrongVoteWithdrawer,
+    /// Pool tokens of a locked deposit must go to a token account owned by
+    /// the depositor's deposit record
+    #[error("InvalidDepositEscrow")]
+    InvalidDepositEscrow,
 }
 impl From<StakePoolError> for ProgramError {
     fn from(e: StakePoolError) -> Self {
@@ -96,3 +236,19 @@ impl<T> DecodeError<T> for StakePoolError {
         "Stake Pool Error"
     }
 }
+
+/// Turns the `None` of a checked calculation into `CalculationFailure`,
+/// logging which calculation failed, since the error code alone does not
+/// tell one overflow from another
+pub(crate) trait CalculationResult<T> {
+    /// Fail with `CalculationFailure` if the calculation returned `None`
+    fn or_calculation_failure(self, calculation: &str) -> Result<T, ProgramError>;
+}
+impl<T> CalculationResult<T> for Option<T> {
+    fn or_calculation_failure(self, calculation: &str) -> Result<T, ProgramError> {
+        self.ok_or_else(|| {
+            msg!("Calculation failed: {}", calculation);
+            StakePoolError::CalculationFailure.into()
+        })
+    }
+}
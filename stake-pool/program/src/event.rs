@@ -0,0 +1,383 @@
+//! Structured events logged by the program for indexers
+//!
+//! Every state-changing instruction logs one or more events as a line of the
+//! form `Program log: StakePoolEvent: <data>`, where `<data>` is the base64
+//! encoding of the Borsh-serialized `StakePoolEvent`.  The first byte of the
+//! payload is the event discriminant.
+
+use {
+    crate::{
+        instruction::{Fee, FeeType, FundingType, PreferredValidatorType, ValidatorTarget},
+        state::{FeeOperation, ManagerAction},
+    },
+    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    solana_program::{msg, pubkey::Pubkey},
+};
+
+/// Prefix of the log lines carrying an event
+pub const EVENT_LOG_PREFIX: &str = "StakePoolEvent: ";
+
+/// Prefix added by the runtime to messages logged by a program
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// Events logged by the program, one variant per kind of state change
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum StakePoolEvent {
+    /// A new stake pool was initialized
+    Initialize(InitializeEvent),
+    /// A validator stake account was created for the pool's staker
+    ValidatorStakeAccountCreated(ValidatorEvent),
+    /// A validator was added to the pool
+    ValidatorAdded(ValidatorEvent),
+    /// A validator was removed from the pool
+    ValidatorRemoved(ValidatorEvent),
+    /// Stake was moved from the reserve to a validator's transient stake account
+    ValidatorStakeIncreased(ValidatorStakeEvent),
+    /// Stake was split off a validator into its transient stake account
+    ValidatorStakeDecreased(ValidatorStakeEvent),
+    /// Balances of a range of the validator list were updated
+    ValidatorListBalanceUpdated(ValidatorListBalanceUpdatedEvent),
+    /// The pool's total stake was updated
+    StakePoolBalanceUpdated(StakePoolBalanceUpdatedEvent),
+    /// Stake or SOL was deposited in exchange for pool tokens
+    Deposit(DepositEvent),
+    /// Pool tokens were burned in exchange for stake or SOL
+    Withdraw(WithdrawEvent),
+    /// Pool tokens were minted or transferred to the manager as a fee
+    FeeCollected(FeeCollectedEvent),
+    /// Pool tokens were burned without withdrawing anything
+    PoolTokensBurned(PoolTokensBurnedEvent),
+    /// A pool setting or authority was changed
+    ConfigUpdated(ConfigUpdatedEvent),
+    /// Lamports were transferred to a treasury as a fee
+    TreasuryFeeCollected(TreasuryFeeCollectedEvent),
+    /// A decommissioned stake pool was closed
+    StakePoolClosed(StakePoolClosedEvent),
+    /// A single-validator pool was created for a vote account
+    SingleValidatorPoolInitialized(ValidatorEvent),
+}
+
+impl StakePoolEvent {
+    /// Log the event
+    pub fn log(&self) {
+        match self.try_to_vec() {
+            Ok(data) => msg!("{}{}", EVENT_LOG_PREFIX, base64::encode(data)),
+            Err(_) => msg!("Failed to serialize event"),
+        }
+    }
+
+    /// Parse an event out of a transaction log line, returning `None` for
+    /// lines that do not carry an event
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(log);
+        let data = base64::decode(log.strip_prefix(EVENT_LOG_PREFIX)?).ok()?;
+        Self::try_from_slice(&data).ok()
+    }
+}
+
+/// Data logged on `Initialize`
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct InitializeEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Manager authority
+    pub manager: Pubkey,
+    /// Staker authority
+    pub staker: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Validator list storage account
+    pub validator_list: Pubkey,
+    /// Maximum number of validators in the pool
+    pub max_validators: u32,
+    /// Epoch fee
+    pub epoch_fee: Fee,
+}
+
+/// Data logged when a validator's stake account is created, added or removed
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ValidatorEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Vote account of the validator
+    pub vote_account: Pubkey,
+    /// Validator stake account
+    pub stake_account: Pubkey,
+}
+
+/// Data logged when a validator's stake is increased or decreased
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ValidatorStakeEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Vote account of the validator
+    pub vote_account: Pubkey,
+    /// Transient stake account holding the activating or deactivating stake
+    pub transient_stake_account: Pubkey,
+    /// Lamports moved
+    pub lamports: u64,
+}
+
+/// Data logged on `UpdateValidatorListBalance`
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ValidatorListBalanceUpdatedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Epoch of the update
+    pub epoch: u64,
+    /// Number of validators updated
+    pub validators_updated: u32,
+}
+
+/// Data logged on `UpdateStakePoolBalance`
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct StakePoolBalanceUpdatedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Epoch of the update
+    pub epoch: u64,
+    /// Total stake lamports after the update
+    pub total_stake_lamports: u64,
+    /// Pool token supply after the update, including the epoch fee
+    pub pool_token_supply: u64,
+    /// Lamports earned since the previous update
+    pub reward_lamports: u64,
+}
+
+/// Data logged on stake and SOL deposits
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct DepositEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Validator the stake was deposited to, or `None` for SOL deposits and
+    /// batch deposits spread over several validators
+    pub vote_account: Option<Pubkey>,
+    /// Pool token account receiving the pool tokens
+    pub pool_tokens_destination: Pubkey,
+    /// Lamports deposited
+    pub lamports: u64,
+    /// Pool tokens received by the depositor
+    pub pool_tokens: u64,
+    /// Pool tokens collected as a deposit fee
+    pub fee_pool_tokens: u64,
+}
+
+/// Data logged on stake and SOL withdrawals
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct WithdrawEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Validator the stake was withdrawn from, or `None` for withdrawals from
+    /// the reserve and SOL withdrawals
+    pub vote_account: Option<Pubkey>,
+    /// Pool token account the pool tokens were burned from
+    pub pool_tokens_source: Pubkey,
+    /// Lamports withdrawn
+    pub lamports: u64,
+    /// Pool tokens burned
+    pub pool_tokens: u64,
+    /// Pool tokens collected as a withdrawal fee
+    pub fee_pool_tokens: u64,
+}
+
+/// Data logged whenever the manager collects a fee
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct FeeCollectedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Operation charged
+    pub operation: FeeOperation,
+    /// Manager fee account, or secondary fee account for its share of the
+    /// epoch fee, receiving the pool tokens
+    pub manager_fee_account: Pubkey,
+    /// Pool tokens collected
+    pub pool_tokens: u64,
+}
+
+/// Data logged whenever a fee is collected in lamports
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct TreasuryFeeCollectedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Operation charged
+    pub operation: FeeOperation,
+    /// Treasury receiving the lamports
+    pub treasury: Pubkey,
+    /// Lamports collected
+    pub lamports: u64,
+}
+
+/// Data logged on `BurnPoolTokens`
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct PoolTokensBurnedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Pool token account the pool tokens were burned from
+    pub pool_tokens_source: Pubkey,
+    /// Pool tokens burned
+    pub pool_tokens: u64,
+}
+
+/// Data logged on `CloseStakePool`
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct StakePoolClosedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// Account receiving the lamports of the closed accounts
+    pub lamports_destination: Pubkey,
+    /// Lamports reclaimed from the stake pool, validator list and reserve
+    pub lamports: u64,
+}
+
+/// Data logged when a pool setting or authority changes
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ConfigUpdatedEvent {
+    /// Stake pool
+    pub stake_pool: Pubkey,
+    /// New setting
+    pub update: ConfigUpdate,
+}
+
+/// Pool setting or authority changed by an instruction
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum ConfigUpdate {
+    /// New manager and manager fee account, through `SetManager`
+    Manager {
+        /// Manager authority
+        manager: Pubkey,
+        /// Manager fee account
+        manager_fee_account: Pubkey,
+    },
+    /// New staker, through `SetStaker`
+    Staker(Pubkey),
+    /// New fee, through `SetFee`
+    Fee(FeeType),
+    /// New preferred validator, through `SetPreferredValidator`
+    PreferredValidator(PreferredValidatorType, Option<Pubkey>),
+    /// New funding authority, through `SetFundingAuthority`
+    FundingAuthority(FundingType, Pubkey),
+    /// New deposit lock, in epochs, through `SetDepositLock`
+    DepositLock(u64),
+    /// New deposit cap, in lamports, through `SetDepositCap`
+    DepositCap(u64),
+    /// New validator targets, through `SetValidatorTargets`
+    ValidatorTargets(Vec<ValidatorTarget>),
+    /// Validator list moved to a larger account, through
+    /// `IncreaseValidatorListSize`
+    ValidatorList {
+        /// New validator list storage account
+        validator_list: Pubkey,
+        /// New maximum number of validators
+        max_validators: u32,
+    },
+    /// Proportional withdrawals toggled, through `SetProportionalWithdrawals`
+    ProportionalWithdrawals(bool),
+    /// Pool token metadata created or updated
+    TokenMetadata {
+        /// Token name
+        name: String,
+        /// Token symbol
+        symbol: String,
+        /// Metadata URI
+        uri: String,
+    },
+    /// New delinquency threshold, in epochs, through
+    /// `SetDelinquentValidatorEpochs`
+    DelinquentValidatorEpochs(u64),
+    /// New epoch fee split, through `SetFeeSplit`
+    FeeSplit {
+        /// Secondary fee account, or `None` if the manager fee account
+        /// receives the whole epoch fee
+        secondary_fee_account: Option<Pubkey>,
+        /// Share of the epoch fee minted to the secondary fee account
+        split: Fee,
+    },
+    /// New SOL deposit fee treasury, or `None` if the fee is collected in
+    /// pool tokens, through `SetSolDepositFeeTreasury`
+    SolDepositFeeTreasury(Option<Pubkey>),
+    /// New deposit interceptor, or `None` if deposits are no longer
+    /// intercepted, through `SetDepositInterceptor`
+    DepositInterceptor {
+        /// Program invoked after every deposit
+        deposit_interceptor: Option<Pubkey>,
+        /// Compute units set for the interceptor, not enforced by the pool
+        compute_units: u32,
+        /// Whether deposits must invoke the interceptor
+        strict: bool,
+    },
+    /// New maximum net outflow per epoch, in basis points, through
+    /// `SetMaxEpochNetOutflow`
+    MaxEpochNetOutflow(u16),
+    /// Pool paused or resumed, through `SetPaused`
+    Paused(bool),
+    /// Pool wound down, through `DecommissionStakePool`
+    Decommissioning,
+    /// New validator onboarding delay, in epochs, through
+    /// `SetValidatorOnboardingEpochs`
+    ValidatorOnboardingEpochs(u64),
+    /// New manager timelock, in epochs, through `SetManagerTimelock`
+    ManagerTimelock(u64),
+    /// Manager action recorded to wait for the timelock
+    ManagerActionQueued {
+        /// Pending action
+        action: ManagerAction,
+        /// First epoch in which anyone can execute the action
+        executable_epoch: u64,
+    },
+    /// Pending manager action dropped, through `CancelManagerAction`
+    ManagerActionCancelled(ManagerAction),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn event_log_round_trip() {
+        let event = StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: Pubkey::new_unique(),
+            vote_account: None,
+            pool_tokens_destination: Pubkey::new_unique(),
+            lamports: 1_000_000_000,
+            pool_tokens: 990_000_000,
+            fee_pool_tokens: 10_000_000,
+        });
+        let data = event.try_to_vec().unwrap();
+        // the first byte is the event discriminant
+        assert_eq!(data[0], 9);
+
+        let log = format!(
+            "{}{}{}",
+            PROGRAM_LOG_PREFIX,
+            EVENT_LOG_PREFIX,
+            base64::encode(&data)
+        );
+        assert_eq!(StakePoolEvent::from_log(&log), Some(event.clone()));
+        assert_eq!(
+            StakePoolEvent::from_log(&log[PROGRAM_LOG_PREFIX.len()..]),
+            Some(event)
+        );
+
+        assert_eq!(
+            StakePoolEvent::from_log("Program log: Instruction: Deposit"),
+            None
+        );
+        assert_eq!(
+            StakePoolEvent::from_log("Program log: StakePoolEvent: not base64"),
+            None
+        );
+    }
+
+    #[test]
+    fn config_update_round_trip() {
+        let event = StakePoolEvent::ConfigUpdated(ConfigUpdatedEvent {
+            stake_pool: Pubkey::new_unique(),
+            update: ConfigUpdate::ValidatorTargets(vec![ValidatorTarget {
+                vote_account: Pubkey::new_unique(),
+                weight_bps: 10_000,
+            }]),
+        });
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(StakePoolEvent::try_from_slice(&data).unwrap(), event);
+    }
+}
@@ -0,0 +1,204 @@
+//! Client helpers to lock pool tokens in an spl-governance realm
+//!
+//! Realms using pool tokens as their community or council mint count the
+//! tokens deposited into the realm as voting weight.  `deposit_sol_and_lock`
+//! builds the `DepositSol` and governance `DepositGoverningTokens`
+//! instructions together, so SOL goes into the pool and the resulting pool
+//! tokens into the realm in a single transaction.
+//!
+//! The governance program deposits the whole balance of the source token
+//! account, so the freshly minted pool tokens are locked without knowing the
+//! exchange rate when the transaction is built.  Any tokens already held by
+//! the source account are locked along with them.
+
+#![allow(clippy::too_many_arguments)]
+
+use {
+    crate::instruction::deposit_sol,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+};
+
+/// Seed prefix of the governance program addresses
+const GOVERNANCE_SEED: &[u8] = b"governance";
+
+/// Tag of the governance `DepositGoverningTokens` instruction
+const DEPOSIT_GOVERNING_TOKENS: u8 = 1;
+
+/// Generates the address of the realm account holding deposited governing
+/// tokens
+pub fn find_governing_token_holding_address(
+    governance_program_id: &Pubkey,
+    realm: &Pubkey,
+    governing_token_mint: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            GOVERNANCE_SEED,
+            realm.as_ref(),
+            governing_token_mint.as_ref(),
+        ],
+        governance_program_id,
+    )
+    .0
+}
+
+/// Generates the address of the record tracking an owner's governing tokens
+/// in a realm
+pub fn find_token_owner_record_address(
+    governance_program_id: &Pubkey,
+    realm: &Pubkey,
+    governing_token_mint: &Pubkey,
+    governing_token_owner: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            GOVERNANCE_SEED,
+            realm.as_ref(),
+            governing_token_mint.as_ref(),
+            governing_token_owner.as_ref(),
+        ],
+        governance_program_id,
+    )
+    .0
+}
+
+/// Creates a governance `DepositGoverningTokens` instruction, depositing the
+/// whole balance of `governing_token_source` into the realm
+pub fn deposit_governing_tokens(
+    governance_program_id: &Pubkey,
+    realm: &Pubkey,
+    governing_token_mint: &Pubkey,
+    governing_token_source: &Pubkey,
+    governing_token_owner: &Pubkey,
+    payer: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    let governing_token_holding =
+        find_governing_token_holding_address(governance_program_id, realm, governing_token_mint);
+    let token_owner_record = find_token_owner_record_address(
+        governance_program_id,
+        realm,
+        governing_token_mint,
+        governing_token_owner,
+    );
+    Instruction {
+        program_id: *governance_program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*realm, false),
+            AccountMeta::new(governing_token_holding, false),
+            AccountMeta::new(*governing_token_source, false),
+            AccountMeta::new_readonly(*governing_token_owner, true),
+            // the owner also authorizes the transfer out of the source
+            AccountMeta::new_readonly(*governing_token_owner, true),
+            AccountMeta::new(token_owner_record, false),
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program_id, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: vec![DEPOSIT_GOVERNING_TOKENS],
+    }
+}
+
+/// Creates the instructions depositing `amount` lamports into the stake pool
+/// and locking the pool tokens received in `realm`, whose governing token
+/// mint must be the pool mint.
+///
+/// `pool_tokens_to` must be an existing pool token account owned by
+/// `governing_token_owner`, who signs along with `lamports_from` and `payer`.
+pub fn deposit_sol_and_lock(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    governance_program_id: &Pubkey,
+    realm: &Pubkey,
+    governing_token_owner: &Pubkey,
+    payer: &Pubkey,
+    amount: u64,
+) -> Result<Vec<Instruction>, ProgramError> {
+    Ok(vec![
+        deposit_sol(
+            program_id,
+            stake_pool,
+            stake_pool_withdraw,
+            reserve_stake,
+            lamports_from,
+            pool_tokens_to,
+            manager_fee_account,
+            pool_mint,
+            token_program_id,
+            amount,
+        )?,
+        deposit_governing_tokens(
+            governance_program_id,
+            realm,
+            pool_mint,
+            pool_tokens_to,
+            governing_token_owner,
+            payer,
+            token_program_id,
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deposit_sol_and_lock_sources_governing_tokens_from_deposit() {
+        let pool_tokens_to = Pubkey::new_unique();
+        let pool_mint = Pubkey::new_unique();
+        let governance_program_id = Pubkey::new_unique();
+        let realm = Pubkey::new_unique();
+        let governing_token_owner = Pubkey::new_unique();
+        let instructions = deposit_sol_and_lock(
+            &crate::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &governing_token_owner,
+            &pool_tokens_to,
+            &Pubkey::new_unique(),
+            &pool_mint,
+            &spl_token::id(),
+            &governance_program_id,
+            &realm,
+            &governing_token_owner,
+            &governing_token_owner,
+            1_000_000_000,
+        )
+        .unwrap();
+
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].accounts[4].pubkey, pool_tokens_to);
+        let lock = &instructions[1];
+        assert_eq!(lock.program_id, governance_program_id);
+        assert_eq!(lock.data, vec![DEPOSIT_GOVERNING_TOKENS]);
+        assert_eq!(lock.accounts[2].pubkey, pool_tokens_to);
+        assert_eq!(
+            lock.accounts[1].pubkey,
+            find_governing_token_holding_address(&governance_program_id, &realm, &pool_mint)
+        );
+        assert_eq!(
+            lock.accounts[5].pubkey,
+            find_token_owner_record_address(
+                &governance_program_id,
+                &realm,
+                &pool_mint,
+                &governing_token_owner
+            )
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Inlined Metaplex token-metadata types, to avoid a direct dependency on the
+//! token-metadata crate and its pinned sdk version
+
+#![allow(clippy::too_many_arguments)]
+
+solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Instruction builders for the token-metadata program
+pub mod instruction {
+    use {
+        super::state::DataV2,
+        borsh::{BorshDeserialize, BorshSerialize},
+        solana_program::{
+            instruction::{AccountMeta, Instruction},
+            pubkey::Pubkey,
+            system_program,
+        },
+    };
+
+    /// Index of `CreateMetadataAccountV3` in the token-metadata instruction enum
+    const CREATE_METADATA_ACCOUNT_V3: u8 = 33;
+    /// Index of `UpdateMetadataAccountV2` in the token-metadata instruction enum
+    const UPDATE_METADATA_ACCOUNT_V2: u8 = 15;
+
+    #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+    struct CreateMetadataAccountArgsV3 {
+        data: DataV2,
+        is_mutable: bool,
+        /// Collection details are never set, so `None` is all that's encoded
+        collection_details: Option<u8>,
+    }
+
+    #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+    struct UpdateMetadataAccountArgsV2 {
+        data: Option<DataV2>,
+        update_authority: Option<Pubkey>,
+        primary_sale_happened: Option<bool>,
+        is_mutable: Option<bool>,
+    }
+
+    /// Creates a `CreateMetadataAccountV3` instruction for a mutable metadata
+    /// account, without creators, collection or royalties
+    pub fn create_metadata_accounts_v3(
+        program_id: Pubkey,
+        metadata_account: Pubkey,
+        mint: Pubkey,
+        mint_authority: Pubkey,
+        payer: Pubkey,
+        update_authority: Pubkey,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Instruction {
+        let mut data = vec![CREATE_METADATA_ACCOUNT_V3];
+        data.append(
+            &mut CreateMetadataAccountArgsV3 {
+                data: DataV2 {
+                    name,
+                    symbol,
+                    uri,
+                    ..DataV2::default()
+                },
+                is_mutable: true,
+                collection_details: None,
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(metadata_account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(mint_authority, true),
+                AccountMeta::new(payer, true),
+                AccountMeta::new_readonly(update_authority, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data,
+        }
+    }
+
+    /// Creates an `UpdateMetadataAccountV2` instruction
+    pub fn update_metadata_accounts_v2(
+        program_id: Pubkey,
+        metadata_account: Pubkey,
+        update_authority: Pubkey,
+        new_update_authority: Option<Pubkey>,
+        metadata: Option<DataV2>,
+        primary_sale_happened: Option<bool>,
+        is_mutable: Option<bool>,
+    ) -> Instruction {
+        let mut data = vec![UPDATE_METADATA_ACCOUNT_V2];
+        data.append(
+            &mut UpdateMetadataAccountArgsV2 {
+                data: metadata,
+                update_authority: new_update_authority,
+                primary_sale_happened,
+                is_mutable,
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(metadata_account, false),
+                AccountMeta::new_readonly(update_authority, true),
+            ],
+            data,
+        }
+    }
+}
+
+/// Token-metadata account state
+pub mod state {
+    use borsh::{BorshDeserialize, BorshSerialize};
+
+    /// Maximum length of a token name, in bytes
+    pub const MAX_NAME_LENGTH: usize = 32;
+    /// Maximum length of a token symbol, in bytes
+    pub const MAX_SYMBOL_LENGTH: usize = 10;
+    /// Maximum length of a metadata URI, in bytes
+    pub const MAX_URI_LENGTH: usize = 200;
+
+    /// Token metadata, with creators, collection and uses left unset, which
+    /// encodes the same as the full token-metadata type
+    #[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+    pub struct DataV2 {
+        /// The name of the asset
+        pub name: String,
+        /// The symbol for the asset
+        pub symbol: String,
+        /// URI pointing to JSON representing the asset
+        pub uri: String,
+        /// Royalty basis points that goes to creators in secondary sales
+        pub seller_fee_basis_points: u16,
+        /// Always `None`
+        pub creators: Option<u8>,
+        /// Always `None`
+        pub collection: Option<u8>,
+        /// Always `None`
+        pub uses: Option<u8>,
+    }
+}
+
+/// Token-metadata program addresses
+pub mod pda {
+    use solana_program::pubkey::Pubkey;
+
+    const PREFIX: &str = "metadata";
+
+    /// Finds the metadata account address for a mint
+    pub fn find_metadata_account(mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[PREFIX.as_bytes(), super::id().as_ref(), mint.as_ref()],
+            &super::id(),
+        )
+    }
+}
@@ -3,7 +3,15 @@
 #![allow(clippy::too_many_arguments)]
 
 use {
-    crate::stake_program,
+    crate::{
+        find_deposit_record_address, find_interceptor_authority_program_address,
+        find_price_oracle_address, find_rate_history_address, find_single_validator_pool_address,
+        find_single_validator_pool_mint_address, find_stake_program_address,
+        find_transient_stake_program_address, find_withdraw_authority_program_address,
+        inline_mpl_token_metadata::{self, pda::find_metadata_account},
+        math::Ratio,
+        stake_program,
+    },
     borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
     solana_program::{
         instruction::{AccountMeta, Instruction},
@@ -11,10 +19,23 @@ use {
         pubkey::Pubkey,
         system_program, sysvar,
     },
+    std::convert::TryFrom,
+};
+
+/// Maximum factor by which the withdrawal fee may increase in a single epoch
+pub const MAX_WITHDRAWAL_FEE_INCREASE: Fee = Fee {
+    numerator: 3,
+    denominator: 2,
+};
+
+/// Withdrawal fee against which increases are measured when no withdrawal fee
+/// is currently charged
+pub const WITHDRAWAL_BASELINE_FEE: Fee = Fee {
+    numerator: 1,
+    denominator: 1000,
 };
 
-/// Fee rate as a ratio, minted on `UpdateStakePoolBalance` as a proportion of
-/// the rewards
+/// Fee rate as a ratio, applied to an amount of pool tokens
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
 pub struct Fee {
@@ -24,6 +45,98 @@ pub struct Fee {
     pub numerator: u64,
 }
 
+impl Fee {
+    /// Check that the fee is at most 100%
+    pub fn is_valid(&self) -> bool {
+        self.numerator <= self.denominator
+    }
+
+    /// Check that the fee is at most `MAX_WITHDRAWAL_FEE_INCREASE` times
+    /// `old_fee`, using `WITHDRAWAL_BASELINE_FEE` in place of a zero `old_fee`
+    pub fn is_valid_withdrawal_increase(&self, old_fee: &Fee) -> bool {
+        if self.numerator == 0 || self.denominator == 0 {
+            return true;
+        }
+        let old_fee = if old_fee.numerator == 0 || old_fee.denominator == 0 {
+            &WITHDRAWAL_BASELINE_FEE
+        } else {
+            old_fee
+        };
+        // new / old <= max  <=>  new.num * old.den * max.den <= max.num * old.num * new.den
+        let new_scaled = (self.numerator as u128)
+            .checked_mul(old_fee.denominator as u128)
+            .and_then(|x| x.checked_mul(MAX_WITHDRAWAL_FEE_INCREASE.denominator as u128));
+        let max_scaled = (old_fee.numerator as u128)
+            .checked_mul(self.denominator as u128)
+            .and_then(|x| x.checked_mul(MAX_WITHDRAWAL_FEE_INCREASE.numerator as u128));
+        match (new_scaled, max_scaled) {
+            (Some(new_scaled), Some(max_scaled)) => new_scaled <= max_scaled,
+            _ => false,
+        }
+    }
+
+    /// Fee as an exact ratio, where a zero denominator means no fee
+    pub fn ratio(&self) -> Ratio {
+        if self.denominator == 0 {
+            Ratio::new(0, 1)
+        } else {
+            Ratio::new(self.numerator, self.denominator)
+        }
+    }
+
+    /// Calculate the fee portion of `amount`, rounding down
+    pub fn apply(&self, amount: u64) -> Option<u64> {
+        self.ratio().checked_apply_floor(amount)
+    }
+}
+
+/// The type of fees that can be set on the stake pool
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum FeeType {
+    /// Fee minted on `UpdateStakePoolBalance` as a proportion of the rewards,
+    /// taking effect from the next epoch
+    Epoch(Fee),
+    /// Fee taken from the pool tokens minted on `Deposit`
+    StakeDeposit(Fee),
+    /// Fee taken from the pool tokens minted on `DepositSol`
+    SolDeposit(Fee),
+    /// Fee taken from the pool tokens burned on `Withdraw` and `WithdrawSol`,
+    /// taking effect from the next epoch
+    Withdrawal(Fee),
+}
+
+/// Kind of flow steered to a preferred validator
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum PreferredValidatorType {
+    /// All stake deposits must go to the preferred validator
+    Deposit,
+    /// Stake withdrawals must come from the preferred validator while it can
+    /// cover them
+    Withdraw,
+}
+
+/// Target share of the pool's stake for a single validator
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ValidatorTarget {
+    /// Vote account of the validator
+    pub vote_account: Pubkey,
+    /// Share of the pool's total stake, in basis points
+    pub weight_bps: u16,
+}
+
+/// Kind of deposit gated by a funding authority
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum FundingType {
+    /// Stake deposits through `Deposit`
+    StakeDeposit,
+    /// SOL deposits through `DepositSol`
+    SolDeposit,
+}
+
 /// Instructions supported by the StakePool program.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
@@ -41,6 +154,8 @@ pub enum StakePoolInstruction {
     ///   7. `[]` Clock sysvar
     ///   8. `[]` Rent sysvar
     ///   9. `[]` Token program id
+    ///   10. `[]` (Optional) Stake deposit authority that must sign all stake deposits.
+    ///       Defaults to the program-derived deposit authority, which allows anyone to deposit.
     Initialize {
         /// Fee assessed as percentage of perceived rewards
         #[allow(dead_code)] // but it's not
@@ -80,19 +195,27 @@ pub enum StakePoolInstruction {
     ///  11. `[]` Stake program id,
     AddValidatorToPool,
 
-    ///   (Staker only) Removes validator from the pool
+    ///   (Staker only, or manager of a decommissioning pool) Removes
+    ///   validator from the pool
+    ///
+    ///   The validator stake account is deactivated and its entry marked
+    ///   `ReadyForRemoval`. Its lamports stay in the pool: once deactivated,
+    ///   `UpdateValidatorListBalance` merges the stake account into the
+    ///   reserve and `UpdateStakePoolBalance` drops the entry from the list.
+    ///
+    ///   If the transient stake account is in use, it must be provided: stake
+    ///   still activating is deactivated as well, and the entry is marked
+    ///   `DeactivatingTransient` until the transient stake is back in the
+    ///   reserve.
     ///
     ///   0. `[w]` Stake pool
-    ///   1. `[s]` Staker
+    ///   1. `[s]` Staker, or manager of a decommissioning pool
     ///   2. `[]` Stake pool withdraw authority
-    ///   3. `[]` New withdraw/staker authority to set in the stake account
-    ///   4. `[w]` Validator stake list storage account
-    ///   5. `[w]` Stake account to remove from the pool
-    ///   6. `[w]` User account with pool tokens to burn from
-    ///   7. `[w]` Pool token mint account
-    ///   8. '[]' Sysvar clock account (required)
-    ///   9. `[]` Pool token program id
-    ///  10. `[]` Stake program id,
+    ///   3. `[w]` Validator stake list storage account
+    ///   4. `[w]` Stake account to remove from the pool
+    ///   5. '[]' Sysvar clock account (required)
+    ///   6. `[]` Stake program id,
+    ///   7. `[w]` (Optional) Transient stake account, required if in use
     RemoveValidatorFromPool,
 
     /// (Staker only) Decrease active stake on a validator, eventually moving it to the reserve
@@ -107,19 +230,21 @@ pub enum StakePoolInstruction {
     ///
     /// ```ignore
     /// Pubkey::find_program_address(
-    ///     &[&stake_account_address.to_bytes()[..32],], program_id,
+    ///     &[b"transient", &vote_account_address.to_bytes()[..32], &stake_pool_address.to_bytes()[..32]],
+    ///     program_id,
     /// )
     /// ```
     ///
     /// The instruction only succeeds if the transient stake account does not
     /// exist. The amount of lamports to move must be at least rent-exemption
-    /// plus 1 lamport.
+    /// plus 1 lamport, and the canonical stake account must keep at least
+    /// rent-exemption plus `MINIMUM_ACTIVE_STAKE`.
     ///
     ///  0. `[]` Stake pool
     ///  1. `[s]` Stake pool staker
-    ///  2. `[]` Validator list
+    ///  2. `[w]` Validator list
     ///  3. `[]` Stake pool withdraw authority
-    ///  5. `[w]` Canonical stake account to split from
+    ///  4. `[w]` Canonical stake account to split from
     ///  5. `[w]` Transient stake account to receive split
     ///  6. `[]` Clock sysvar
     ///  7. `[]` Rent sysvar
@@ -135,18 +260,24 @@ pub enum StakePoolInstruction {
     /// will do the work of merging once it's ready.
     ///
     /// This instruction only succeeds if the transient stake account does not exist.
-    /// The minimum amount to move is rent-exemption plus 1 SOL in order to avoid
-    /// issues on credits observed when merging active stakes later.
+    /// The minimum amount to move is rent-exemption plus `MINIMUM_ACTIVE_STAKE`
+    /// (1 SOL) in order to avoid issues on credits observed when merging active
+    /// stakes later.
     ///
     ///  0. `[]` Stake pool
     ///  1. `[s]` Stake pool staker
-    ///  2. `[]` Validator list
+    ///  2. `[w]` Validator list
     ///  3. `[]` Stake pool withdraw authority
     ///  4. `[w]` Stake pool reserve stake
     ///  5. `[w]` Transient stake account
-    ///  6. `[]` Canonical stake account
+    ///  6. `[]` Validator vote account to delegate to
     ///  7. '[]' Clock sysvar
-    ///  8. `[]` Stake program
+    ///  8. '[]' Rent sysvar
+    ///  9. `[]` Stake History sysvar
+    /// 10. `[]` Stake Config sysvar
+    /// 11. `[]` System program
+    /// 12. `[]` Stake program
+    ///  userdata: amount of lamports to move into the transient stake account
     IncreaseValidatorStake(u64),
 
     ///  Updates balances of validator and transient stake accounts in the pool
@@ -156,73 +287,141 @@ pub enum StakePoolInstruction {
     ///  account.  If the transient stake is active and has matching credits
     ///  observed, it is merged into the canonical validator stake account. In
     ///  all other states, nothing is done, and the balance is simply added to
-    ///  the canonical stake account balance.
+    ///  the canonical stake account balance.  Validators marked
+    ///  `DeactivatingTransient` become `ReadyForRemoval` once their transient
+    ///  stake is back in the reserve.  Validators marked `ReadyForRemoval`
+    ///  whose stake is fully deactivated are merged into the reserve, and
+    ///  their entries dropped by the next `UpdateStakePoolBalance`.
+    ///
+    ///  The N pairs of accounts must belong to the N validators starting at
+    ///  `start_index` in the validator list, so that big pools can be updated
+    ///  over several transactions.
     ///
     ///  0. `[]` Stake pool
     ///  1. `[w]` Validator stake list storage account
     ///  2. `[w]` Reserve stake account
     ///  3. `[]` Stake pool withdraw authority
     ///  4. `[]` Sysvar clock account
-    ///  5. `[]` Stake program
-    ///  6. ..6+N ` [] N pairs of validator and transient stake accounts
-    UpdateValidatorListBalance,
+    ///  5. `[]` Sysvar stake history account
+    ///  6. `[]` Stake program
+    ///  7. ..7+2N ` [w] N pairs of validator and transient stake accounts
+    UpdateValidatorListBalance {
+        /// Index of the first validator in the list to update
+        #[allow(dead_code)] // but it's not
+        start_index: u32,
+        /// If true, only record balances without merging any stake account,
+        /// useful if a stake account is in an unexpected state
+        #[allow(dead_code)] // but it's not
+        no_merge: bool,
+    },
 
-    ///   Updates total pool balance based on balances in the reserve and validator list
+    ///   Updates total pool balance based on balances in the reserve and validator list,
+    ///   and drops the entries of removed validators whose stake is back in the reserve
     ///
     ///   0. `[w]` Stake pool
-    ///   1. `[]` Validator stake list storage account
+    ///   1. `[w]` Validator stake list storage account
     ///   2. `[]` Reserve stake account
     ///   3. `[]` Stake pool withdraw authority
     ///   4. `[w]` Account to receive pool fee tokens
     ///   5. `[w]` Pool mint account
     ///   6. `[]` Sysvar clock account
     ///   7. `[]` Pool token program
+    ///   8. `[w]` (Optional) Secondary fee account, required if the pool has one
+    ///   9. `[w]` (Optional) Price oracle of the pool, created on first use,
+    ///      receiving the updated pool token rate
+    ///   10. `[ws]` Funding account for the price oracle, required with it
+    ///   11. `[]` System program id, required with the price oracle
+    ///   12. `[w]` (Optional) Rate history of the pool, created on first use,
+    ///      recording the pool's balances for the epoch.  Requires the price
+    ///      oracle accounts.
+    ///   13. `[ws]` Funding account for the rate history, required with it
+    ///   14. `[]` System program id, required with the rate history
     UpdateStakePoolBalance,
 
     ///   Deposit some stake into the pool.  The output is a "pool" token representing ownership
-    ///   into the pool. Inputs are converted to the current ratio.
+    ///   into the pool. Inputs are converted to the current ratio, so the pool
+    ///   must have been updated in the current epoch.
     ///
     ///   0. `[w]` Stake pool
     ///   1. `[w]` Validator stake list storage account
-    ///   2. `[]` Stake pool deposit authority
+    ///   2. `[]` Stake pool deposit authority, must sign unless it is the program address
     ///   3. `[]` Stake pool withdraw authority
     ///   4. `[w]` Stake account to join the pool (withdraw should be set to stake pool deposit)
     ///   5. `[w]` Validator stake account for the stake account to be merged with
     ///   6. `[w]` User account to receive pool tokens
+    ///   7. `[w]` Manager fee account to receive the stake deposit fee
     ///   8. `[w]` Pool token mint account
     ///   9. '[]' Sysvar clock account (required)
     ///   10. '[]' Sysvar stake history account
     ///   11. `[]` Pool token program id,
     ///   12. `[]` Stake program id,
+    ///   13. `[w]` (Optional) Deposit record of the depositor, required if the
+    ///       pool has a deposit lock, in which case the pool tokens must go
+    ///       to a token account owned by the deposit record
+    ///   14. `[ws]` (Optional) Depositor, funding the deposit record
+    ///   15. `[]` (Optional) System program id
+    ///   16. `[]` (Optional) Deposit interceptor program, required if the pool
+    ///       has a strict interceptor
+    ///   17. `[]` (Optional) Interceptor authority, required along with the
+    ///       deposit interceptor
+    ///
+    ///   Stake accounts with a lockup still in force are rejected, expired
+    ///   lockups are cleared before the merge.
+    ///
+    ///   See `DepositWithSlippage` to bound the pool tokens received.
     Deposit,
 
     ///   Withdraw the token from the pool at the current ratio.
     ///   The amount withdrawn is the MIN(u64, stake size)
     ///
-    ///   A validator stake account can be withdrawn from freely, and the reserve
-    ///   can only be drawn from if there is no active stake left, where all
-    ///   validator accounts are left with 1 lamport.
+    ///   Stake is withdrawn in order: the preferred validator, the other
+    ///   validator stake accounts, which must keep rent-exemption plus
+    ///   `MINIMUM_ACTIVE_STAKE`, then transient stake accounts once every
+    ///   validator is down to that minimum, and the reserve last, once no
+    ///   transient stake is left either.
+    ///   A partial withdrawal from a transient stake account must also leave
+    ///   it with that minimum, and the split stake must be rent-exempt.
+    ///   Removed validators take no part in that order: their deactivating
+    ///   stake can be withdrawn at any time, down to rent-exemption.
+    ///
+    ///   Like deposits, withdrawals fail until the pool has been updated in the
+    ///   current epoch.
     ///
     ///   0. `[w]` Stake pool
     ///   1. `[w]` Validator stake list storage account
     ///   2. `[]` Stake pool withdraw authority
-    ///   3. `[w]` Validator or reserve stake account to split
+    ///   3. `[w]` Validator, transient or reserve stake account to split
     ///   4. `[w]` Unitialized stake account to receive withdrawal
     ///   5. `[]` User account to set as a new withdraw authority
     ///   6. `[w]` User account with pool tokens to burn from
-    ///   7. `[w]` Pool token mint account
-    ///   8. '[]' Sysvar clock account (required)
-    ///   9. `[]` Pool token program id
-    ///   10. `[]` Stake program id,
-    ///   userdata: amount to withdraw
+    ///   7. `[w]` Manager fee account to receive the withdrawal fee
+    ///   8. `[w]` Pool token mint account
+    ///   9. '[]' Sysvar clock account (required)
+    ///   10. `[]` Pool token program id
+    ///   11. `[]` Stake program id,
+    ///   12. `[w]` (Optional) Reserve stake account
+    ///   13. `[w]` (Optional) Transient stake account of a validator in the
+    ///       list, to sweep into the reserve
+    ///   14. `[]` (Optional) Sysvar stake history account
+    ///   userdata: amount of pool tokens to withdraw, including the withdrawal fee
+    ///
+    ///   If the sweep accounts are given and the transient stake account is
+    ///   fully deactivated, it is merged into the reserve along the way, which
+    ///   saves the next update from doing it.  A transient stake account that
+    ///   is still activating or deactivating is left alone, without failing
+    ///   the withdrawal.
+    ///
+    ///   See `WithdrawWithSlippage` to bound the lamports received.
     Withdraw(u64),
 
     ///  (Manager only) Update manager
     ///
+    ///  Timelocked if the pool has a manager timelock, see `SetManagerTimelock`.
+    ///
     ///  0. `[w]` StakePool
     ///  1. `[s]` Manager
     ///  2. '[]` New manager pubkey
-    ///  3. '[]` New manager fee account
+    ///  3. '[]` New manager fee account, a token account of the pool mint
     SetManager,
 
     ///  (Manager or staker only) Update staker
@@ -231,6 +430,530 @@ pub enum StakePoolInstruction {
     ///  1. `[s]` Manager or current staker
     ///  2. '[]` New staker pubkey
     SetStaker,
+
+    ///   Deposit SOL directly into the pool's reserve stake account. The output is a "pool"
+    ///   token representing ownership into the pool. Inputs are converted to the current ratio.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[]` Stake pool withdraw authority
+    ///   2. `[w]` Reserve stake account, to receive the lamports
+    ///   3. `[ws]` Funding account (must be a system account)
+    ///   4. `[w]` User account to receive pool tokens
+    ///   5. `[w]` Manager fee account to receive the SOL deposit fee
+    ///   6. `[w]` Pool token mint account
+    ///   7. '[]' Sysvar clock account (required)
+    ///   8. `[]` System program id
+    ///   9. `[]` Pool token program id
+    ///   10. `[s]` (Optional) SOL deposit authority, required if the pool has one
+    ///   11. `[w]` (Optional) SOL deposit fee treasury, required if the pool
+    ///       collects the SOL deposit fee in lamports
+    ///   12. `[w]` (Optional) Deposit record of the depositor, required if the
+    ///       pool has a deposit lock, in which case the pool tokens must go
+    ///       to a token account owned by the deposit record
+    ///   13. `[ws]` (Optional) Depositor, funding the deposit record
+    ///   14. `[]` (Optional) System program id
+    ///   15. `[]` (Optional) Deposit interceptor program, required if the pool
+    ///       has a strict interceptor
+    ///   16. `[]` (Optional) Interceptor authority, required along with the
+    ///       deposit interceptor
+    ///   userdata: amount of lamports to deposit
+    DepositSol(u64),
+
+    ///   Withdraw SOL directly from the pool's reserve stake account at the current ratio.
+    ///   Fails with `SolWithdrawalTooLarge` if the reserve does not hold enough lamports
+    ///   above its rent-exempt reserve to cover the withdrawal.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[]` Stake pool withdraw authority
+    ///   2. `[w]` User account with pool tokens to burn from
+    ///   3. `[w]` Manager fee account to receive the withdrawal fee
+    ///   4. `[w]` Reserve stake account, to withdraw the lamports from
+    ///   5. `[w]` Account receiving the lamports
+    ///   6. `[w]` Pool token mint account
+    ///   7. '[]' Sysvar clock account (required)
+    ///   8. '[]' Sysvar stake history account
+    ///   9. `[]` Stake program id
+    ///   10. `[]` Pool token program id
+    ///   userdata: amount of pool tokens to withdraw, including the withdrawal fee
+    WithdrawSol(u64),
+
+    ///  (Manager only) Update one of the stake pool fees
+    ///
+    ///  Deposit fees change immediately.  Epoch and withdrawal fees only take
+    ///  effect on the first `UpdateStakePoolBalance` of the next epoch, and the
+    ///  withdrawal fee may grow by at most `MAX_WITHDRAWAL_FEE_INCREASE` per epoch.
+    ///  Timelocked if the pool has a manager timelock, see `SetManagerTimelock`.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    SetFee {
+        /// Type of fee to update, along with its new value
+        #[allow(dead_code)] // but it's not
+        fee: FeeType,
+    },
+
+    ///  (Staker only) Set or unset the preferred deposit or withdraw validator
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Staker
+    ///  2. `[]` Validator list, must contain the preferred validator
+    SetPreferredValidator {
+        /// Whether to set the deposit or withdraw preference
+        #[allow(dead_code)] // but it's not
+        validator_type: PreferredValidatorType,
+        /// Vote account of the preferred validator, or `None` to unset
+        #[allow(dead_code)] // but it's not
+        validator_vote_address: Option<Pubkey>,
+    },
+
+    ///   Burn pool tokens without receiving anything in exchange, donating their
+    ///   share of the pool to all other pool token holders
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[w]` Token account with pool tokens to burn from
+    ///   2. `[s]` Owner or delegate of the token account
+    ///   3. `[w]` Pool token mint account
+    ///   4. `[]` Pool token program id
+    ///   userdata: amount of pool tokens to burn
+    BurnPoolTokens(u64),
+
+    ///  (Manager only) Set or unset the authority gating stake or SOL deposits
+    ///
+    ///  Timelocked if the pool has a manager timelock, see `SetManagerTimelock`.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[]` (Optional) New funding authority.  If omitted, stake deposits
+    ///     go back to the program-derived deposit authority and SOL deposits
+    ///     become permissionless.
+    SetFundingAuthority(FundingType),
+
+    ///  (Manager only) Set the number of epochs pool tokens stay locked in
+    ///  the depositor's escrow after a deposit, or 0 to disable the lock.
+    ///  Deposits made while the lock is enabled keep the lock they were made
+    ///  with, but disabling it lets depositors claim their escrow at once.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: number of epochs
+    SetDepositLock(u64),
+
+    ///  (Staker only) Set the target weights of validators in the pool.
+    ///  Validators not listed keep their current target, and all targets must
+    ///  add up to at most `MAX_TARGET_WEIGHT_BPS`.
+    ///
+    ///  0. `[]` StakePool
+    ///  1. `[s]` Staker
+    ///  2. `[w]` Validator list
+    ///  userdata: new target weights
+    SetValidatorTargets(Vec<ValidatorTarget>),
+
+    ///  (Manager only) Enable or disable proportional withdrawals.  While
+    ///  enabled, stake withdrawals must come from the validator furthest above
+    ///  its target weight, and the preferred withdraw validator is ignored.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: true to enable
+    SetProportionalWithdrawals(bool),
+
+    ///   Deposit some stake into the pool like `Deposit`, failing if the user
+    ///   would receive fewer pool tokens than expected once fees are taken.
+    ///
+    ///   Accounts are the same as `Deposit`.
+    ///   userdata: minimum pool tokens the user must receive
+    DepositWithSlippage {
+        /// Minimum amount of pool tokens the user must receive
+        minimum_pool_tokens_out: u64,
+    },
+
+    ///   Withdraw the token from the pool like `Withdraw`, failing if the split
+    ///   stake account would receive fewer lamports than expected.
+    ///
+    ///   Accounts are the same as `Withdraw`.
+    ///   userdata: pool tokens to withdraw and minimum lamports to receive
+    WithdrawWithSlippage {
+        /// Amount of pool tokens to withdraw, including the withdrawal fee
+        pool_tokens_in: u64,
+        /// Minimum amount of lamports the split stake account must receive
+        minimum_lamports_out: u64,
+    },
+
+    ///   (Manager only) Create the token metadata of the pool mint in the
+    ///   Metaplex token-metadata program, with the withdraw authority as both
+    ///   mint and update authority.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Manager
+    ///   2. `[]` Stake pool withdraw authority
+    ///   3. `[]` Pool token mint account
+    ///   4. `[s, w]` Payer for the creation of the token metadata account
+    ///   5. `[w]` Token metadata account
+    ///   6. `[]` Metadata program id
+    ///   7. `[]` System program id
+    CreateTokenMetadata {
+        /// Token name
+        name: String,
+        /// Token symbol e.g. stkSOL
+        symbol: String,
+        /// URI of the uploaded metadata of the pool token
+        uri: String,
+    },
+
+    ///   (Manager only) Update the token metadata of the pool mint in the
+    ///   Metaplex token-metadata program.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Manager
+    ///   2. `[]` Stake pool withdraw authority
+    ///   3. `[w]` Token metadata account
+    ///   4. `[]` Metadata program id
+    UpdateTokenMetadata {
+        /// Token name
+        name: String,
+        /// Token symbol e.g. stkSOL
+        symbol: String,
+        /// URI of the uploaded metadata of the pool token
+        uri: String,
+    },
+
+    ///  (Manager only) Set the maximum total stake lamports the pool accepts,
+    ///  or 0 to remove the cap.  `Deposit` and `DepositSol` fail if they would
+    ///  take the pool's total stake above the cap, but stake already in the
+    ///  pool is never affected, even if the cap is lowered below it.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: maximum total stake lamports
+    SetDepositCap(u64),
+
+    ///  (Manager or staker only) Move the validator list to a larger account,
+    ///  raising the maximum number of validators in the pool.
+    ///
+    ///  The new account must be created beforehand, rent-exempt, owned by the
+    ///  stake pool program and sized for the new maximum, as for `Initialize`.
+    ///  All entries are copied over, the pool points to the new account, and
+    ///  the old account is emptied with its lamports refunded to the funder,
+    ///  so that the funder only pays the difference in rent.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager or staker
+    ///  2. `[w]` Current validator list storage account
+    ///  3. `[w]` New, uninitialized validator list storage account
+    ///  4. `[w]` Funder, receiving the lamports of the current validator list
+    ///  userdata: new maximum number of validators
+    IncreaseValidatorListSize(u32),
+
+    ///  (Manager only) Set the number of epochs a validator may go without
+    ///  earning vote credits before anyone can decrease its stake through
+    ///  `DecreaseDelinquentValidatorStake`, or 0 to disable permissionless
+    ///  decreases.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: number of epochs
+    SetDelinquentValidatorEpochs(u64),
+
+    /// Decrease the stake on a delinquent validator, callable by anyone.
+    ///
+    /// Works like `DecreaseValidatorStake`, but instead of the staker's
+    /// signature it requires the validator's vote account, which must not
+    /// have earned vote credits for more than the pool's
+    /// `delinquent_validator_epochs`.  All stake above rent-exemption plus
+    /// `MINIMUM_ACTIVE_STAKE` is moved to the transient stake account, and
+    /// the staker can merge it back into the reserve with
+    /// `UpdateValidatorListBalance` once it is deactivated.
+    ///
+    /// Fails if the pool has not opted in by setting
+    /// `SetDelinquentValidatorEpochs`.
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[]` Validator vote account
+    ///  2. `[w]` Validator list
+    ///  3. `[]` Stake pool withdraw authority
+    ///  4. `[w]` Canonical stake account to split from
+    ///  5. `[w]` Transient stake account to receive split
+    ///  6. `[]` Clock sysvar
+    ///  7. `[]` Rent sysvar
+    ///  8. `[]` System program
+    ///  9. `[]` Stake program
+    DecreaseDelinquentValidatorStake,
+
+    ///  (Manager only) Route part of the epoch fee to a secondary fee account,
+    ///  for example an operations treasury.  `UpdateStakePoolBalance` mints
+    ///  the given share of the epoch fee to the secondary account and the rest
+    ///  to the manager fee account.  Omitting the secondary fee account sends
+    ///  the whole epoch fee to the manager fee account again.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[]` (Optional) Secondary fee account, a token account of the pool mint
+    ///  userdata: share of the epoch fee minted to the secondary fee account
+    SetFeeSplit(Fee),
+
+    ///  (Manager only) Collect the SOL deposit fee in lamports, transferred from
+    ///  the depositor to a treasury account, instead of minting it to the
+    ///  manager fee account in pool tokens.  Omitting the treasury goes back
+    ///  to collecting the fee in pool tokens.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[]` (Optional) Treasury account receiving the SOL deposit fee
+    SetSolDepositFeeTreasury,
+
+    ///  (Manager only) Register a program invoked after every deposit with
+    ///  the deposit details, see the `interceptor` module.  Omitting the
+    ///  interceptor stops intercepting deposits.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[]` (Optional) Deposit interceptor program
+    SetDepositInterceptor {
+        /// Compute units for the interceptor, passed along to it but not
+        /// enforced
+        #[allow(dead_code)] // but it's not
+        compute_units: u32,
+        /// If true, deposits must invoke the interceptor and fail with it,
+        /// otherwise depositors may leave it out
+        #[allow(dead_code)] // but it's not
+        strict: bool,
+    },
+
+    ///  (Manager only) Cap the net outflow of each epoch, withdrawals minus
+    ///  deposits since the epoch's `UpdateStakePoolBalance`, to a share of the
+    ///  pool's total stake at the start of the epoch, or 0 to remove the cap.
+    ///  `Withdraw` and `WithdrawSol` fail beyond the cap, giving the staker
+    ///  time to rebalance during a run on the pool.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: maximum net outflow, in basis points of the total stake
+    SetMaxEpochNetOutflow(u16),
+
+    ///  (Manager only) Pause or resume the pool while an incident is
+    ///  investigated.  While paused, deposits, withdrawals and stake
+    ///  rebalancing fail, but the pool can still be updated every epoch.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: true to pause, false to resume
+    SetPaused(bool),
+
+    /// Rebalance a validator toward its target weight, callable by anyone.
+    ///
+    /// Compares the validator's stake with its `target_weight_bps` share of
+    /// the pool's total stake, set through `SetValidatorTargets`, and moves
+    /// the difference to the validator's transient stake account: like
+    /// `DecreaseValidatorStake` if the validator is above its target, or
+    /// like `IncreaseValidatorStake`, as far as the reserve allows, if it is
+    /// below.  Decreases always leave rent-exemption plus
+    /// `MINIMUM_ACTIVE_STAKE` on the validator.
+    ///
+    /// Fails if the pool has no target weights, if the validator already has
+    /// transient stake, or if the difference is too small to be moved.
+    ///
+    ///  0. `[]` Stake pool
+    ///  1. `[w]` Validator list
+    ///  2. `[]` Stake pool withdraw authority
+    ///  3. `[w]` Stake pool reserve stake
+    ///  4. `[w]` Canonical stake account of the validator
+    ///  5. `[w]` Transient stake account of the validator
+    ///  6. `[]` Validator vote account
+    ///  7. `[]` Clock sysvar
+    ///  8. `[]` Rent sysvar
+    ///  9. `[]` Stake history sysvar
+    /// 10. `[]` Stake config sysvar
+    /// 11. `[]` System program
+    /// 12. `[]` Stake program
+    ApplyTargets,
+
+    ///   Deposit several stake accounts into the pool at once, like `Deposit`
+    ///   for each of them, minting the pool tokens once for their total.
+    ///
+    ///   Each stake account is merged into the validator stake account that
+    ///   follows it, so a batch can spread over several validators.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[w]` Validator stake list storage account
+    ///   2. `[]` Stake pool deposit authority, must sign unless it is the program address
+    ///   3. `[]` Stake pool withdraw authority
+    ///   4. `[w]` User account to receive pool tokens
+    ///   5. `[w]` Manager fee account to receive the stake deposit fee
+    ///   6. `[w]` Pool token mint account
+    ///   7. `[]` Sysvar clock account
+    ///   8. `[]` Sysvar stake history account
+    ///   9. `[]` Pool token program id
+    ///   10. `[]` Stake program id
+    ///   11. ..11+2N `[w]` N pairs of stake accounts to join the pool (withdraw
+    ///       authority set to the stake pool deposit authority) and validator
+    ///       stake accounts to merge them with
+    ///   11+2N. `[w]` (Optional) Deposit record, depositor and system program,
+    ///       as in `Deposit`
+    ///   14+2N. `[]` (Optional) Deposit interceptor program and interceptor
+    ///       authority, as in `Deposit`
+    ///   userdata: number of stake accounts N
+    DepositMultiple(u8),
+
+    ///  (Manager only) Start winding the pool down, for good.
+    ///
+    ///  Deposits, new validators and stake increases fail from then on, and
+    ///  withdrawals are free of fees and of the epoch outflow cap.  The
+    ///  manager may remove validators in place of the staker, deactivating
+    ///  all stake back into the reserve, from which the remaining holders
+    ///  redeem their pool tokens through `WithdrawSol`.  Once the pool is
+    ///  empty, `CloseStakePool` reclaims its accounts.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    DecommissionStakePool,
+
+    ///  (Manager only) Close a decommissioned pool with no validators left
+    ///  and no pool tokens outstanding, sending the lamports of the stake
+    ///  pool, validator list and reserve stake accounts to the destination.
+    ///  The pool must be up to date, so that removed validators are dropped
+    ///  from the list.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  2. `[w]` Validator list
+    ///  3. `[]` Stake pool withdraw authority
+    ///  4. `[w]` Reserve stake account
+    ///  5. `[w]` Account receiving the lamports
+    ///  6. `[]` Sysvar clock account
+    ///  7. `[]` Sysvar stake history account
+    ///  8. `[]` Stake program
+    CloseStakePool,
+
+    ///  (Manager only) Set the number of epochs validators added from then on
+    ///  wait before accepting deposits and stake increases, or 0 to let them
+    ///  take stake right away.  The delay gives pool token holders time to
+    ///  exit if they disagree with the staker's choice of validator.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: number of epochs
+    SetValidatorOnboardingEpochs(u64),
+
+    ///  Create the single-validator pool of a vote account, callable by
+    ///  anyone.
+    ///
+    ///  A single-validator pool has no validator list, reserve, manager or
+    ///  fees: all of its accounts are program addresses derived from the vote
+    ///  account, and its only stake account is delegated to it.  The funder
+    ///  pays for the accounts and seeds the stake account with
+    ///  `MINIMUM_ACTIVE_STAKE`, which stays in the pool for good.
+    ///
+    ///  0. `[w]` Single-validator pool, derived from the vote account
+    ///  1. `[]` Validator vote account
+    ///  2. `[w]` Pool stake account, derived from the vote account and pool
+    ///  3. `[w]` Pool token mint, derived from the pool
+    ///  4. `[]` Pool withdraw authority
+    ///  5. `[ws]` Funder
+    ///  6. `[]` Rent sysvar
+    ///  7. `[]` Clock sysvar
+    ///  8. `[]` Stake history sysvar
+    ///  9. `[]` Stake config sysvar
+    ///  10. `[]` System program
+    ///  11. `[]` Token program
+    ///  12. `[]` Stake program
+    InitializeSingleValidatorPool,
+
+    ///  Deposit a stake account into a single-validator pool, merging it into
+    ///  the pool's stake account and minting pool tokens for all of its
+    ///  lamports.
+    ///
+    ///  The stake account must be delegated to the pool's validator and in
+    ///  the same activation state as the pool's stake account, with its staker
+    ///  and withdrawer set to the pool withdraw authority.
+    ///
+    ///  0. `[]` Single-validator pool
+    ///  1. `[w]` Pool stake account
+    ///  2. `[w]` Pool token mint
+    ///  3. `[]` Pool withdraw authority
+    ///  4. `[w]` Stake account to join the pool
+    ///  5. `[w]` User account to receive pool tokens
+    ///  6. `[]` Clock sysvar
+    ///  7. `[]` Stake history sysvar
+    ///  8. `[]` Token program
+    ///  9. `[]` Stake program
+    DepositSingleValidator,
+
+    ///  Withdraw stake from a single-validator pool, burning pool tokens and
+    ///  splitting the lamports backing them into an uninitialized stake
+    ///  account owned by the user.  The pool withdraw authority must be
+    ///  approved as delegate of the burned pool tokens.
+    ///
+    ///  0. `[]` Single-validator pool
+    ///  1. `[w]` Pool stake account
+    ///  2. `[w]` Pool token mint
+    ///  3. `[]` Pool withdraw authority
+    ///  4. `[w]` Uninitialized stake account to receive the withdrawal
+    ///  5. `[]` User account to set as the staker and withdrawer of the new
+    ///     stake account
+    ///  6. `[w]` User account with pool tokens to burn from
+    ///  7. `[]` Clock sysvar
+    ///  8. `[]` Token program
+    ///  9. `[]` Stake program
+    ///  userdata: amount of pool tokens to burn
+    WithdrawSingleValidator(u64),
+
+    ///  (Manager only) Set the number of epochs that `SetFee`, `SetManager`
+    ///  and `SetFundingAuthority` wait before taking effect, or 0 to apply
+    ///  them right away.
+    ///
+    ///  While the timelock is set, those instructions record the change as
+    ///  pending instead of applying it, and anyone can apply it through
+    ///  `ExecuteManagerAction` once the timelock has elapsed, giving pool
+    ///  token holders time to exit if they disagree with it.  Only one
+    ///  action may be pending at a time.  Lengthening the timelock applies
+    ///  right away, while shortening it waits for the current timelock.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    ///  userdata: number of epochs
+    SetManagerTimelock(u64),
+
+    ///  Apply the pending manager action once its timelock has elapsed,
+    ///  callable by anyone.
+    ///
+    ///  0. `[w]` StakePool
+    ExecuteManagerAction,
+
+    ///  (Manager only) Drop the pending manager action.
+    ///
+    ///  0. `[w]` StakePool
+    ///  1. `[s]` Manager
+    CancelManagerAction,
+
+    ///   (Vote account withdrawer only) Removes the validator from the pool
+    ///
+    ///   Lets a validator leave a pool without the staker's help.  Works like
+    ///   `RemoveValidatorFromPool`, but signed by the withdraw authority of
+    ///   the validator's vote account instead of the staker.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[s]` Withdraw authority of the validator's vote account
+    ///   2. `[]` Stake pool withdraw authority
+    ///   3. `[w]` Validator stake list storage account
+    ///   4. `[w]` Stake account to remove from the pool
+    ///   5. '[]' Sysvar clock account (required)
+    ///   6. `[]` Stake program id,
+    ///   7. `[]` Validator vote account
+    ///   8. `[w]` (Optional) Transient stake account, required if in use
+    RemoveValidatorByVoteWithdrawer,
+
+    ///   (Depositor only) Moves the pool tokens of locked deposits out of the
+    ///   depositor's escrow, once the deposit lock is over or the manager
+    ///   disabled it.  See `state::DepositRecord`.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[]` Deposit record of the depositor
+    ///   2. `[s]` Depositor
+    ///   3. `[w]` Escrow token account, owned by the deposit record
+    ///   4. `[w]` Token account to receive the pool tokens
+    ///   5. '[]' Sysvar clock account
+    ///   6. `[]` Pool token program id
+    ClaimLockedPoolTokens,
 }
 
 /// Creates an 'initialize' instruction.
@@ -240,9 +963,11 @@ pub fn initialize(
     manager: &Pubkey,
     staker: &Pubkey,
     validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
     pool_mint: &Pubkey,
     manager_pool_account: &Pubkey,
     token_program_id: &Pubkey,
+    deposit_authority: Option<Pubkey>,
     fee: Fee,
     max_validators: u32,
 ) -> Result<Instruction, ProgramError> {
@@ -251,17 +976,21 @@ pub fn initialize(
         max_validators,
     };
     let data = init_data.try_to_vec()?;
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*stake_pool, true),
         AccountMeta::new_readonly(*manager, true),
         AccountMeta::new_readonly(*staker, false),
         AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*reserve_stake, false),
         AccountMeta::new_readonly(*pool_mint, false),
         AccountMeta::new_readonly(*manager_pool_account, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
+    if let Some(deposit_authority) = deposit_authority {
+        accounts.push(AccountMeta::new_readonly(deposit_authority, false));
+    }
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -338,24 +1067,16 @@ pub fn remove_validator_from_pool(
     stake_pool: &Pubkey,
     staker: &Pubkey,
     stake_pool_withdraw: &Pubkey,
-    new_stake_authority: &Pubkey,
     validator_list: &Pubkey,
     stake_account: &Pubkey,
-    burn_from: &Pubkey,
-    pool_mint: &Pubkey,
-    token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let accounts = vec![
         AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(*staker, true),
         AccountMeta::new_readonly(*stake_pool_withdraw, false),
-        AccountMeta::new_readonly(*new_stake_authority, false),
         AccountMeta::new(*validator_list, false),
         AccountMeta::new(*stake_account, false),
-        AccountMeta::new(*burn_from, false),
-        AccountMeta::new(*pool_mint, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(*token_program_id, false),
         AccountMeta::new_readonly(stake_program::id(), false),
     ];
     Ok(Instruction {
@@ -365,54 +1086,222 @@ pub fn remove_validator_from_pool(
     })
 }
 
-/// Creates `DecreaseValidatorStake` instruction (rebalance from validator account to
-/// transient account)
-pub fn decrease_validator_stake() -> Result<Instruction, ProgramError> {
-    Err(ProgramError::IncorrectProgramId)
-}
-
-/// Creates `IncreaseValidatorStake` instruction (rebalance from reserve account to
-/// transient account)
-pub fn increase_validator_stake() -> Result<Instruction, ProgramError> {
-    Err(ProgramError::IncorrectProgramId)
-}
-
-/// Creates `UpdateValidatorListBalance` instruction (update validator stake account balances)
-pub fn update_validator_list_balance(
+/// Creates `RemoveValidatorByVoteWithdrawer` instruction (remove validator
+/// stake account from the pool, signed by the validator)
+pub fn remove_validator_by_vote_withdrawer(
     program_id: &Pubkey,
-    validator_list_storage: &Pubkey,
-    validator_list: &[Pubkey],
+    stake_pool: &Pubkey,
+    vote_withdrawer: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    validator_list: &Pubkey,
+    stake_account: &Pubkey,
+    vote_account: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let mut accounts: Vec<AccountMeta> = validator_list
-        .iter()
-        .map(|pubkey| AccountMeta::new_readonly(*pubkey, false))
-        .collect();
-    accounts.insert(0, AccountMeta::new(*validator_list_storage, false));
-    accounts.insert(1, AccountMeta::new_readonly(sysvar::clock::id(), false));
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*vote_withdrawer, true),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*stake_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+        AccountMeta::new_readonly(*vote_account, false),
+    ];
     Ok(Instruction {
         program_id: *program_id,
         accounts,
-        data: StakePoolInstruction::UpdateValidatorListBalance.try_to_vec()?,
+        data: StakePoolInstruction::RemoveValidatorByVoteWithdrawer.try_to_vec()?,
     })
 }
 
-/// Creates `UpdateStakePoolBalance` instruction (pool balance from the stake account list balances)
-pub fn update_stake_pool_balance(
+/// Creates `DecreaseValidatorStake` instruction (rebalance from validator account to
+/// transient account)
+pub fn decrease_validator_stake(
     program_id: &Pubkey,
     stake_pool: &Pubkey,
-    validator_list_storage: &Pubkey,
-    withdraw_authority: &Pubkey,
-    manager_fee_account: &Pubkey,
-    stake_pool_mint: &Pubkey,
+    staker: &Pubkey,
+    validator_list: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_stake: &Pubkey,
+    transient_stake: &Pubkey,
+    lamports: u64,
 ) -> Result<Instruction, ProgramError> {
     let accounts = vec![
-        AccountMeta::new(*stake_pool, false),
-        AccountMeta::new(*validator_list_storage, false),
-        AccountMeta::new_readonly(*withdraw_authority, false),
-        AccountMeta::new(*manager_fee_account, false),
-        AccountMeta::new(*stake_pool_mint, false),
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_stake, false),
+        AccountMeta::new(*transient_stake, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DecreaseValidatorStake(lamports).try_to_vec()?,
+    })
+}
+
+/// Creates `DecreaseDelinquentValidatorStake` instruction (rebalance from a
+/// delinquent validator's account to its transient account)
+pub fn decrease_delinquent_validator_stake(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    vote_account: &Pubkey,
+    validator_list: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_stake: &Pubkey,
+    transient_stake: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*vote_account, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_stake, false),
+        AccountMeta::new(*transient_stake, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DecreaseDelinquentValidatorStake.try_to_vec()?,
+    })
+}
+
+/// Creates `IncreaseValidatorStake` instruction (rebalance from reserve account to
+/// transient account)
+pub fn increase_validator_stake(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    validator_list: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    reserve_stake: &Pubkey,
+    transient_stake: &Pubkey,
+    validator_vote: &Pubkey,
+    lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*transient_stake, false),
+        AccountMeta::new_readonly(*validator_vote, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::config_id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::IncreaseValidatorStake(lamports).try_to_vec()?,
+    })
+}
+
+/// Creates `ApplyTargets` instruction (rebalance a validator toward its target
+/// weight, from its account or the reserve to its transient account)
+pub fn apply_targets(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    reserve_stake: &Pubkey,
+    validator_stake: &Pubkey,
+    transient_stake: &Pubkey,
+    validator_vote: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*validator_stake, false),
+        AccountMeta::new(*transient_stake, false),
+        AccountMeta::new_readonly(*validator_vote, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::config_id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::ApplyTargets.try_to_vec()?,
+    })
+}
+
+/// Creates `UpdateValidatorListBalance` instruction (update validator stake account balances)
+pub fn update_validator_list_balance(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw_authority: &Pubkey,
+    validator_list: &Pubkey,
+    reserve_stake: &Pubkey,
+    validator_vote_accounts: &[Pubkey],
+    start_index: u32,
+    no_merge: bool,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    for vote_account_address in validator_vote_accounts {
+        let (validator_stake_account, _) =
+            find_stake_program_address(program_id, vote_account_address, stake_pool);
+        accounts.push(AccountMeta::new(validator_stake_account, false));
+        let (transient_stake_account, _) =
+            find_transient_stake_program_address(program_id, vote_account_address, stake_pool);
+        accounts.push(AccountMeta::new(transient_stake_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::UpdateValidatorListBalance {
+            start_index,
+            no_merge,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates `UpdateStakePoolBalance` instruction (pool balance from the stake account list balances)
+pub fn update_stake_pool_balance(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    reserve_stake: &Pubkey,
+    withdraw_authority: &Pubkey,
+    manager_fee_account: &Pubkey,
+    stake_pool_mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*validator_list_storage, false),
+        AccountMeta::new_readonly(*reserve_stake, false),
+        AccountMeta::new_readonly(*withdraw_authority, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*stake_pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
     Ok(Instruction {
         program_id: *program_id,
@@ -431,6 +1320,7 @@ pub fn deposit(
     stake_to_join: &Pubkey,
     validator_stake_accont: &Pubkey,
     pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
     pool_mint: &Pubkey,
     token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
@@ -442,6 +1332,7 @@ pub fn deposit(
         AccountMeta::new(*stake_to_join, false),
         AccountMeta::new(*validator_stake_accont, false),
         AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*manager_fee_account, false),
         AccountMeta::new(*pool_mint, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(sysvar::stake_history::id(), false),
@@ -455,6 +1346,114 @@ pub fn deposit(
     })
 }
 
+/// Creates a 'Deposit' instruction for a pool whose stake deposit authority
+/// must sign.
+pub fn deposit_with_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_deposit_authority: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    stake_to_join: &Pubkey,
+    validator_stake_accont: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        program_id,
+        stake_pool,
+        validator_list_storage,
+        stake_pool_deposit_authority,
+        stake_pool_withdraw,
+        stake_to_join,
+        validator_stake_accont,
+        pool_tokens_to,
+        manager_fee_account,
+        pool_mint,
+        token_program_id,
+    )?;
+    instruction.accounts[2].is_signer = true;
+    Ok(instruction)
+}
+
+/// Creates a 'DepositWithSlippage' instruction, which fails if fewer than
+/// `minimum_pool_tokens_out` pool tokens would be minted to the user.
+pub fn deposit_with_slippage(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_deposit: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    stake_to_join: &Pubkey,
+    validator_stake_accont: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    minimum_pool_tokens_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        program_id,
+        stake_pool,
+        validator_list_storage,
+        stake_pool_deposit,
+        stake_pool_withdraw,
+        stake_to_join,
+        validator_stake_accont,
+        pool_tokens_to,
+        manager_fee_account,
+        pool_mint,
+        token_program_id,
+    )?;
+    instruction.data = StakePoolInstruction::DepositWithSlippage {
+        minimum_pool_tokens_out,
+    }
+    .try_to_vec()?;
+    Ok(instruction)
+}
+
+/// Creates a 'DepositMultiple' instruction, depositing each stake account
+/// into the validator stake account paired with it.
+pub fn deposit_multiple(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_deposit: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    stakes_to_join: &[(Pubkey, Pubkey)],
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let stake_account_count =
+        u8::try_from(stakes_to_join.len()).or(Err(ProgramError::InvalidArgument))?;
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*validator_list_storage, false),
+        AccountMeta::new_readonly(*stake_pool_deposit, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    for (stake_to_join, validator_stake_account) in stakes_to_join {
+        accounts.push(AccountMeta::new(*stake_to_join, false));
+        accounts.push(AccountMeta::new(*validator_stake_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DepositMultiple(stake_account_count).try_to_vec()?,
+    })
+}
+
 /// Creates a 'withdraw' instruction.
 pub fn withdraw(
     program_id: &Pubkey,
@@ -465,6 +1464,7 @@ pub fn withdraw(
     stake_to_receive: &Pubkey,
     user_withdrawer: &Pubkey,
     burn_from: &Pubkey,
+    manager_fee_account: &Pubkey,
     pool_mint: &Pubkey,
     token_program_id: &Pubkey,
     amount: u64,
@@ -477,6 +1477,7 @@ pub fn withdraw(
         AccountMeta::new(*stake_to_receive, false),
         AccountMeta::new_readonly(*user_withdrawer, false),
         AccountMeta::new(*burn_from, false),
+        AccountMeta::new(*manager_fee_account, false),
         AccountMeta::new(*pool_mint, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(*token_program_id, false),
@@ -489,6 +1490,45 @@ pub fn withdraw(
     })
 }
 
+/// Creates a 'WithdrawWithSlippage' instruction, which fails if fewer than
+/// `minimum_lamports_out` lamports would be split off to the user.
+pub fn withdraw_with_slippage(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    stake_to_split: &Pubkey,
+    stake_to_receive: &Pubkey,
+    user_withdrawer: &Pubkey,
+    burn_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_tokens_in: u64,
+    minimum_lamports_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = withdraw(
+        program_id,
+        stake_pool,
+        validator_list_storage,
+        stake_pool_withdraw,
+        stake_to_split,
+        stake_to_receive,
+        user_withdrawer,
+        burn_from,
+        manager_fee_account,
+        pool_mint,
+        token_program_id,
+        pool_tokens_in,
+    )?;
+    instruction.data = StakePoolInstruction::WithdrawWithSlippage {
+        pool_tokens_in,
+        minimum_lamports_out,
+    }
+    .try_to_vec()?;
+    Ok(instruction)
+}
+
 /// Creates a 'set manager' instruction.
 pub fn set_manager(
     program_id: &Pubkey,
@@ -528,3 +1568,834 @@ pub fn set_staker(
         data: StakePoolInstruction::SetStaker.try_to_vec()?,
     })
 }
+
+/// Creates a 'DepositSol' instruction.
+pub fn deposit_sol(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*lamports_from, true),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DepositSol(amount).try_to_vec()?,
+    })
+}
+
+/// Creates a 'DepositSol' instruction for a pool whose SOL deposit authority
+/// must sign.
+pub fn deposit_sol_with_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    sol_deposit_authority: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit_sol(
+        program_id,
+        stake_pool,
+        stake_pool_withdraw,
+        reserve_stake,
+        lamports_from,
+        pool_tokens_to,
+        manager_fee_account,
+        pool_mint,
+        token_program_id,
+        amount,
+    )?;
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*sol_deposit_authority, true));
+    Ok(instruction)
+}
+
+/// Creates a 'WithdrawSol' instruction.
+pub fn withdraw_sol(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    burn_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_to: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
+        AccountMeta::new(*burn_from, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*lamports_to, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::WithdrawSol(amount).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetFee' instruction.
+pub fn set_fee(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    fee: FeeType,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetFee { fee }.try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetPreferredValidator' instruction.
+pub fn set_preferred_validator(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    validator_list: &Pubkey,
+    validator_type: PreferredValidatorType,
+    validator_vote_address: Option<Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new_readonly(*validator_list, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetPreferredValidator {
+            validator_type,
+            validator_vote_address,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a 'BurnPoolTokens' instruction.
+pub fn burn_pool_tokens(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    burn_from: &Pubkey,
+    burn_authority: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*burn_from, false),
+        AccountMeta::new_readonly(*burn_authority, true),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::BurnPoolTokens(amount).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetFundingAuthority' instruction.
+pub fn set_funding_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    new_funding_authority: Option<&Pubkey>,
+    funding_type: FundingType,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    if let Some(new_funding_authority) = new_funding_authority {
+        accounts.push(AccountMeta::new_readonly(*new_funding_authority, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetFundingAuthority(funding_type).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetDepositLock' instruction.
+pub fn set_deposit_lock(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    deposit_lock_epochs: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetDepositLock(deposit_lock_epochs).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetDepositCap' instruction.
+pub fn set_deposit_cap(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    max_total_lamports: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetDepositCap(max_total_lamports).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetDelinquentValidatorEpochs' instruction.
+pub fn set_delinquent_validator_epochs(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    delinquent_validator_epochs: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetDelinquentValidatorEpochs(delinquent_validator_epochs)
+            .try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetFeeSplit' instruction.
+pub fn set_fee_split(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    secondary_fee_account: Option<&Pubkey>,
+    split: Fee,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    if let Some(secondary_fee_account) = secondary_fee_account {
+        accounts.push(AccountMeta::new_readonly(*secondary_fee_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetFeeSplit(split).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetDepositInterceptor' instruction.
+pub fn set_deposit_interceptor(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    deposit_interceptor: Option<&Pubkey>,
+    compute_units: u32,
+    strict: bool,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    if let Some(deposit_interceptor) = deposit_interceptor {
+        accounts.push(AccountMeta::new_readonly(*deposit_interceptor, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetDepositInterceptor {
+            compute_units,
+            strict,
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetMaxEpochNetOutflow' instruction.
+pub fn set_max_epoch_net_outflow(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    max_epoch_net_outflow_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetMaxEpochNetOutflow(max_epoch_net_outflow_bps)
+            .try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetPaused' instruction.
+pub fn set_paused(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetPaused(paused).try_to_vec()?,
+    })
+}
+
+/// Creates a 'DecommissionStakePool' instruction.
+pub fn decommission_stake_pool(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DecommissionStakePool.try_to_vec()?,
+    })
+}
+
+/// Creates a 'CloseStakePool' instruction.
+pub fn close_stake_pool(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_list: &Pubkey,
+    stake_pool_withdraw: &Pubkey,
+    reserve_stake: &Pubkey,
+    lamports_to: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(*lamports_to, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::CloseStakePool.try_to_vec()?,
+    })
+}
+
+/// Creates an 'InitializeSingleValidatorPool' instruction.
+pub fn initialize_single_validator_pool(
+    program_id: &Pubkey,
+    vote_account: &Pubkey,
+    funder: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (single_validator_pool, _) = find_single_validator_pool_address(program_id, vote_account);
+    let (stake_account, _) =
+        find_stake_program_address(program_id, vote_account, &single_validator_pool);
+    let (pool_mint, _) =
+        find_single_validator_pool_mint_address(program_id, &single_validator_pool);
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, &single_validator_pool);
+    let accounts = vec![
+        AccountMeta::new(single_validator_pool, false),
+        AccountMeta::new_readonly(*vote_account, false),
+        AccountMeta::new(stake_account, false),
+        AccountMeta::new(pool_mint, false),
+        AccountMeta::new_readonly(withdraw_authority, false),
+        AccountMeta::new(*funder, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake_program::config_id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::InitializeSingleValidatorPool.try_to_vec()?,
+    })
+}
+
+/// Creates a 'DepositSingleValidator' instruction.
+pub fn deposit_single_validator(
+    program_id: &Pubkey,
+    vote_account: &Pubkey,
+    stake_to_join: &Pubkey,
+    pool_tokens_to: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (single_validator_pool, _) = find_single_validator_pool_address(program_id, vote_account);
+    let (stake_account, _) =
+        find_stake_program_address(program_id, vote_account, &single_validator_pool);
+    let (pool_mint, _) =
+        find_single_validator_pool_mint_address(program_id, &single_validator_pool);
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, &single_validator_pool);
+    let accounts = vec![
+        AccountMeta::new_readonly(single_validator_pool, false),
+        AccountMeta::new(stake_account, false),
+        AccountMeta::new(pool_mint, false),
+        AccountMeta::new_readonly(withdraw_authority, false),
+        AccountMeta::new(*stake_to_join, false),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::DepositSingleValidator.try_to_vec()?,
+    })
+}
+
+/// Creates a 'WithdrawSingleValidator' instruction.
+pub fn withdraw_single_validator(
+    program_id: &Pubkey,
+    vote_account: &Pubkey,
+    stake_to_receive: &Pubkey,
+    user_withdrawer: &Pubkey,
+    burn_from: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let (single_validator_pool, _) = find_single_validator_pool_address(program_id, vote_account);
+    let (stake_account, _) =
+        find_stake_program_address(program_id, vote_account, &single_validator_pool);
+    let (pool_mint, _) =
+        find_single_validator_pool_mint_address(program_id, &single_validator_pool);
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, &single_validator_pool);
+    let accounts = vec![
+        AccountMeta::new_readonly(single_validator_pool, false),
+        AccountMeta::new(stake_account, false),
+        AccountMeta::new(pool_mint, false),
+        AccountMeta::new_readonly(withdraw_authority, false),
+        AccountMeta::new(*stake_to_receive, false),
+        AccountMeta::new_readonly(*user_withdrawer, false),
+        AccountMeta::new(*burn_from, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(stake_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::WithdrawSingleValidator(amount).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetValidatorOnboardingEpochs' instruction.
+pub fn set_validator_onboarding_epochs(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    validator_onboarding_epochs: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetValidatorOnboardingEpochs(validator_onboarding_epochs)
+            .try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetSolDepositFeeTreasury' instruction.
+pub fn set_sol_deposit_fee_treasury(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    sol_deposit_fee_treasury: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    if let Some(sol_deposit_fee_treasury) = sol_deposit_fee_treasury {
+        accounts.push(AccountMeta::new_readonly(*sol_deposit_fee_treasury, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetSolDepositFeeTreasury.try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetValidatorTargets' instruction.
+pub fn set_validator_targets(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    validator_list: &Pubkey,
+    targets: Vec<ValidatorTarget>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(*validator_list, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetValidatorTargets(targets).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetProportionalWithdrawals' instruction.
+pub fn set_proportional_withdrawals(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    enabled: bool,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetProportionalWithdrawals(enabled).try_to_vec()?,
+    })
+}
+
+/// Creates a 'CreateTokenMetadata' instruction.
+pub fn create_token_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Result<Instruction, ProgramError> {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+    let (token_metadata, _) = find_metadata_account(pool_mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new_readonly(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(token_metadata, false),
+        AccountMeta::new_readonly(inline_mpl_token_metadata::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::CreateTokenMetadata { name, symbol, uri }.try_to_vec()?,
+    })
+}
+
+/// Creates an 'UpdateTokenMetadata' instruction.
+pub fn update_token_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Result<Instruction, ProgramError> {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+    let (token_metadata, _) = find_metadata_account(pool_mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(token_metadata, false),
+        AccountMeta::new_readonly(inline_mpl_token_metadata::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::UpdateTokenMetadata { name, symbol, uri }.try_to_vec()?,
+    })
+}
+
+/// Adds the depositor's deposit record to a 'Deposit', 'DepositSol' or
+/// 'DepositMultiple' instruction, required for pools with a deposit lock.
+/// The depositor signs and funds the record, and the pool tokens must go to
+/// an escrow token account owned by the record.
+pub fn add_deposit_record_accounts(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    depositor: &Pubkey,
+) {
+    let (deposit_record, _) = find_deposit_record_address(program_id, stake_pool, depositor);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(deposit_record, false),
+        AccountMeta::new(*depositor, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+}
+
+/// Creates a 'ClaimLockedPoolTokens' instruction, moving all the pool tokens
+/// of `escrow` to `pool_tokens_to`
+pub fn claim_locked_pool_tokens(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    depositor: &Pubkey,
+    escrow: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (deposit_record, _) = find_deposit_record_address(program_id, stake_pool, depositor);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(deposit_record, false),
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*pool_tokens_to, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::ClaimLockedPoolTokens.try_to_vec()?,
+    })
+}
+
+/// Adds a validator's transient stake account to a 'Withdraw' instruction, so
+/// that it is merged into the reserve if fully deactivated.
+pub fn add_withdraw_sweep_accounts(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    reserve_stake: &Pubkey,
+    vote_account: &Pubkey,
+) {
+    let (transient_stake, _) =
+        find_transient_stake_program_address(program_id, vote_account, stake_pool);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new(transient_stake, false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+    ]);
+}
+
+/// Adds the pool's secondary fee account to an 'UpdateStakePoolBalance'
+/// instruction, required for pools with a fee split.  Must be added before
+/// the price oracle accounts.
+pub fn add_secondary_fee_account(instruction: &mut Instruction, secondary_fee_account: &Pubkey) {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*secondary_fee_account, false));
+}
+
+/// Adds the pool's SOL deposit fee treasury to a 'DepositSol' instruction,
+/// required for pools collecting the SOL deposit fee in lamports.  Must be
+/// added after the SOL deposit authority and before the deposit record
+/// accounts.
+pub fn add_sol_deposit_fee_treasury_account(
+    instruction: &mut Instruction,
+    sol_deposit_fee_treasury: &Pubkey,
+) {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*sol_deposit_fee_treasury, false));
+}
+
+/// Adds the pool's deposit interceptor and interceptor authority to a
+/// 'Deposit', 'DepositMultiple' or 'DepositSol' instruction, required for
+/// pools with a strict interceptor.  Must be added last, after the deposit
+/// record accounts.
+pub fn add_deposit_interceptor_accounts(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    deposit_interceptor: &Pubkey,
+) {
+    let (interceptor_authority, _) =
+        find_interceptor_authority_program_address(program_id, stake_pool);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new_readonly(*deposit_interceptor, false),
+        AccountMeta::new_readonly(interceptor_authority, false),
+    ]);
+}
+
+/// Adds the validator's transient stake account to a 'RemoveValidatorFromPool'
+/// or 'RemoveValidatorByVoteWithdrawer' instruction, required if the transient
+/// stake account is in use
+pub fn add_transient_stake_account(instruction: &mut Instruction, transient_stake: &Pubkey) {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*transient_stake, false));
+}
+
+/// Adds the pool's price oracle to an 'UpdateStakePoolBalance' instruction,
+/// so that it receives the updated pool token rate.  The funder pays for the
+/// oracle account if it does not exist yet.
+pub fn add_price_oracle_accounts(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    funder: &Pubkey,
+) {
+    let (price_oracle, _) = find_price_oracle_address(program_id, stake_pool);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(price_oracle, false),
+        AccountMeta::new(*funder, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+}
+
+/// Adds the pool's rate history to an 'UpdateStakePoolBalance' instruction,
+/// after its price oracle accounts, so that it records the pool's balances
+/// for the epoch.  The funder pays for the history account if it does not
+/// exist yet.
+pub fn add_rate_history_accounts(
+    instruction: &mut Instruction,
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    funder: &Pubkey,
+) {
+    let (rate_history, _) = find_rate_history_address(program_id, stake_pool);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(rate_history, false),
+        AccountMeta::new(*funder, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+}
+
+/// Creates an 'IncreaseValidatorListSize' instruction.
+pub fn increase_validator_list_size(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    authority: &Pubkey,
+    validator_list: &Pubkey,
+    new_validator_list: &Pubkey,
+    funder: &Pubkey,
+    max_validators: u32,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*validator_list, false),
+        AccountMeta::new(*new_validator_list, false),
+        AccountMeta::new(*funder, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::IncreaseValidatorListSize(max_validators).try_to_vec()?,
+    })
+}
+
+/// Creates a 'SetManagerTimelock' instruction.
+pub fn set_manager_timelock(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    manager_timelock_epochs: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::SetManagerTimelock(manager_timelock_epochs).try_to_vec()?,
+    })
+}
+
+/// Creates an 'ExecuteManagerAction' instruction.
+pub fn execute_manager_action(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![AccountMeta::new(*stake_pool, false)];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::ExecuteManagerAction.try_to_vec()?,
+    })
+}
+
+/// Creates a 'CancelManagerAction' instruction.
+pub fn cancel_manager_action(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: StakePoolInstruction::CancelManagerAction.try_to_vec()?,
+    })
+}
@@ -0,0 +1,71 @@
+//! Interface of deposit interceptor programs
+//!
+//! The manager can register an interceptor program through
+//! `SetDepositInterceptor`, for example to award loyalty points or check KYC
+//! attestations.  Once a deposit has gone through and the pool state is
+//! saved, the pool invokes the interceptor with an `InterceptDeposit`
+//! carrying the deposit details.
+//!
+//! Anyone can invoke the interceptor directly with made-up details, so an
+//! interceptor must check that the pool's interceptor authority signed the
+//! instruction, as derived by `find_interceptor_authority_program_address`
+//! from the stake pool account, before acting on it.  That authority signs
+//! nothing but these calls: it has no say over the pool's stake accounts or
+//! its mint.
+//!
+//! A failing program aborts the whole transaction, so the pool cannot shrug
+//! off an interceptor error once it is invoked.  Instead, unless the pool is
+//! strict, the interceptor account is optional on deposits: depositors can
+//! always leave a misbehaving interceptor out.
+//!
+//! The pool does not bound the compute the interceptor uses, which comes out
+//! of the deposit transaction's budget.  The compute units set by the manager
+//! are only passed along, for interceptors that choose to honor them.
+
+use {
+    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+/// Details of a deposit, passed to the interceptor as its instruction data
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct InterceptDeposit {
+    /// Vote account of the validator receiving a stake deposit, `None` for
+    /// SOL deposits and batch deposits spread over several validators
+    pub vote_account: Option<Pubkey>,
+    /// Lamports added to the pool
+    pub lamports: u64,
+    /// Pool tokens minted to the depositor
+    pub pool_tokens: u64,
+    /// Compute units set by the manager for the interceptor, not enforced by
+    /// the pool
+    pub compute_units: u32,
+}
+
+/// Creates the instruction invoked on the deposit interceptor
+///
+///   0. `[]` Stake pool, with the deposit applied
+///   1. `[s]` Stake pool interceptor authority, proving the pool sent the
+///      instruction
+///   2. `[]` Token account receiving the depositor's pool tokens
+pub fn intercept_deposit(
+    deposit_interceptor: &Pubkey,
+    stake_pool: &Pubkey,
+    interceptor_authority: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    deposit: &InterceptDeposit,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *deposit_interceptor,
+        accounts: vec![
+            AccountMeta::new_readonly(*stake_pool, false),
+            AccountMeta::new_readonly(*interceptor_authority, true),
+            AccountMeta::new_readonly(*pool_tokens_to, false),
+        ],
+        data: deposit.try_to_vec()?,
+    })
+}
@@ -2,19 +2,26 @@
 
 //! A program for creating and managing pools of stake
 
+pub mod big_vec;
 pub mod borsh;
 pub mod error;
+pub mod event;
+pub mod governance;
+pub mod inline_mpl_token_metadata;
 pub mod instruction;
+pub mod interceptor;
+pub mod math;
 pub mod processor;
 pub mod stake_program;
 pub mod state;
+pub mod vote_program;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
-use solana_program::pubkey::Pubkey;
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
 
 /// Seed for deposit authority seed
 const AUTHORITY_DEPOSIT: &[u8] = b"deposit";
@@ -22,6 +29,37 @@ const AUTHORITY_DEPOSIT: &[u8] = b"deposit";
 /// Seed for withdraw authority seed
 const AUTHORITY_WITHDRAW: &[u8] = b"withdraw";
 
+/// Seed for deposit interceptor authority seed
+const AUTHORITY_INTERCEPTOR: &[u8] = b"interceptor";
+
+/// Seed for transient stake account
+const TRANSIENT_STAKE_SEED: &[u8] = b"transient";
+
+/// Seed for deposit record account
+const DEPOSIT_RECORD_SEED: &[u8] = b"deposit_record";
+
+/// Seed for price oracle account
+const PRICE_ORACLE_SEED: &[u8] = b"price_oracle";
+
+/// Seed for rate history account
+const RATE_HISTORY_SEED: &[u8] = b"rate_history";
+
+/// Seed for single-validator pool account
+const SINGLE_VALIDATOR_POOL_SEED: &[u8] = b"single_validator_pool";
+
+/// Seed for single-validator pool token mint
+const SINGLE_VALIDATOR_POOL_MINT_SEED: &[u8] = b"mint";
+
+/// Minimum amount of staked SOL required in a validator stake account to allow
+/// for merges without a mismatch on credits observed
+pub const MINIMUM_ACTIVE_STAKE: u64 = LAMPORTS_PER_SOL;
+
+/// Target weights of all validators, in basis points, add up to at most this value
+pub const MAX_TARGET_WEIGHT_BPS: u16 = 10_000;
+
+/// Maximum net outflow per epoch, in basis points, letting the whole pool out
+pub const MAX_EPOCH_NET_OUTFLOW_BPS: u16 = 10_000;
+
 /// Generates the deposit authority program address for the stake pool
 pub fn find_deposit_authority_program_address(
     program_id: &Pubkey,
@@ -44,6 +82,18 @@ pub fn find_withdraw_authority_program_address(
     )
 }
 
+/// Generates the authority program address signing the stake pool's calls to
+/// its deposit interceptor
+pub fn find_interceptor_authority_program_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&stake_pool_address.to_bytes()[..32], AUTHORITY_INTERCEPTOR],
+        program_id,
+    )
+}
+
 /// Generates the stake program address for a validator's vote account
 pub fn find_stake_program_address(
     program_id: &Pubkey,
@@ -59,4 +109,83 @@ pub fn find_stake_program_address(
     )
 }
 
+/// Generates the transient stake program address for a validator's vote account
+pub fn find_transient_stake_program_address(
+    program_id: &Pubkey,
+    vote_account_address: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            TRANSIENT_STAKE_SEED,
+            &vote_account_address.to_bytes()[..32],
+            &stake_pool_address.to_bytes()[..32],
+        ],
+        program_id,
+    )
+}
+
+/// Generates the deposit record address tracking a depositor's lock-in
+pub fn find_deposit_record_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    depositor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            DEPOSIT_RECORD_SEED,
+            &stake_pool_address.to_bytes()[..32],
+            &depositor.to_bytes()[..32],
+        ],
+        program_id,
+    )
+}
+
+/// Generates the price oracle address publishing the pool token rate
+pub fn find_price_oracle_address(program_id: &Pubkey, stake_pool_address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[PRICE_ORACLE_SEED, &stake_pool_address.to_bytes()[..32]],
+        program_id,
+    )
+}
+
+/// Generates the rate history address recording the pool's balances per epoch
+pub fn find_rate_history_address(program_id: &Pubkey, stake_pool_address: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RATE_HISTORY_SEED, &stake_pool_address.to_bytes()[..32]],
+        program_id,
+    )
+}
+
+/// Generates the address of the single-validator pool for a validator's vote
+/// account.  The pool's stake account and withdraw authority are derived from
+/// it with `find_stake_program_address` and
+/// `find_withdraw_authority_program_address`, as for any other pool.
+pub fn find_single_validator_pool_address(
+    program_id: &Pubkey,
+    vote_account_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SINGLE_VALIDATOR_POOL_SEED,
+            &vote_account_address.to_bytes()[..32],
+        ],
+        program_id,
+    )
+}
+
+/// Generates the pool token mint address of a single-validator pool
+pub fn find_single_validator_pool_mint_address(
+    program_id: &Pubkey,
+    single_validator_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SINGLE_VALIDATOR_POOL_MINT_SEED,
+            &single_validator_pool_address.to_bytes()[..32],
+        ],
+        program_id,
+    )
+}
+
 solana_program::declare_id!("poo1B9L9nR3CrcaziKVYVpRX6A9Y1LAXYasjjfCbApj");
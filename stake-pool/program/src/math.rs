@@ -0,0 +1,151 @@
+//! Checked fixed-point math for conversions between lamports, pool tokens and fees
+
+use {spl_math::uint::U256, std::convert::TryFrom};
+
+/// Ratio between two quantities, kept as an exact u128 fraction so that
+/// chained conversions only round once, when applied to an amount
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ratio {
+    numerator: u128,
+    denominator: u128,
+}
+
+impl Ratio {
+    /// Create a ratio of two u64 quantities
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        Self {
+            numerator: numerator as u128,
+            denominator: denominator as u128,
+        }
+    }
+
+    /// Ratio converting amounts 1:1
+    pub fn one() -> Self {
+        Self::new(1, 1)
+    }
+
+    /// Product of two ratios, or `None` if either term overflows u128
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.checked_mul(other.numerator)?,
+            denominator: self.denominator.checked_mul(other.denominator)?,
+        })
+    }
+
+    /// Apply the ratio to `amount`, rounding down.  Returns `None` if the
+    /// denominator is 0 or the result does not fit in a u64
+    pub fn checked_apply_floor(&self, amount: u64) -> Option<u64> {
+        let (quotient, _) = self.checked_apply(amount)?;
+        u64::try_from(quotient).ok()
+    }
+
+    /// Apply the ratio to `amount`, rounding up.  Returns `None` if the
+    /// denominator is 0 or the result does not fit in a u64
+    pub fn checked_apply_ceil(&self, amount: u64) -> Option<u64> {
+        let (quotient, remainder) = self.checked_apply(amount)?;
+        let quotient = if remainder.is_zero() {
+            quotient
+        } else {
+            quotient.checked_add(U256::one())?
+        };
+        u64::try_from(quotient).ok()
+    }
+
+    /// Quotient and remainder of `amount * numerator / denominator`, computed
+    /// with a u256 intermediate, which cannot overflow for a u64 amount
+    fn checked_apply(&self, amount: u64) -> Option<(U256, U256)> {
+        if self.denominator == 0 {
+            return None;
+        }
+        let product = U256::from(amount).checked_mul(U256::from(self.numerator))?;
+        Some(product.div_mod(U256::from(self.denominator)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, proptest::prelude::*};
+
+    #[test]
+    fn apply_rounding() {
+        let ratio = Ratio::new(2, 3);
+        assert_eq!(ratio.checked_apply_floor(10), Some(6));
+        assert_eq!(ratio.checked_apply_ceil(10), Some(7));
+        assert_eq!(ratio.checked_apply_floor(9), Some(6));
+        assert_eq!(ratio.checked_apply_ceil(9), Some(6));
+        assert_eq!(ratio.checked_apply_floor(0), Some(0));
+        assert_eq!(ratio.checked_apply_ceil(0), Some(0));
+        assert_eq!(ratio.checked_apply_floor(1), Some(0));
+        assert_eq!(ratio.checked_apply_ceil(1), Some(1));
+    }
+
+    #[test]
+    fn apply_zero_denominator() {
+        assert_eq!(Ratio::new(1, 0).checked_apply_floor(1), None);
+        assert_eq!(Ratio::new(0, 0).checked_apply_ceil(0), None);
+    }
+
+    #[test]
+    fn apply_extreme_values() {
+        // the u128 product of two u64s cannot overflow the intermediate
+        let ratio = Ratio::new(u64::MAX, u64::MAX);
+        assert_eq!(ratio.checked_apply_floor(u64::MAX), Some(u64::MAX));
+        assert_eq!(ratio.checked_apply_ceil(u64::MAX), Some(u64::MAX));
+
+        let ratio = Ratio::new(u64::MAX, 1);
+        assert_eq!(ratio.checked_apply_floor(1), Some(u64::MAX));
+        assert_eq!(ratio.checked_apply_floor(2), None);
+        assert_eq!(
+            Ratio::new(u64::MAX, u64::MAX - 1).checked_apply_ceil(u64::MAX),
+            None
+        );
+
+        let ratio = Ratio::new(1, u64::MAX);
+        assert_eq!(ratio.checked_apply_floor(u64::MAX - 1), Some(0));
+        assert_eq!(ratio.checked_apply_ceil(1), Some(1));
+    }
+
+    #[test]
+    fn chained_ratios_round_once() {
+        // 3 * (2 / 3) * (3 / 2) is exactly 3, but rounding each step gives 2
+        let ratio = Ratio::new(2, 3).checked_mul(&Ratio::new(3, 2)).unwrap();
+        assert_eq!(ratio.checked_apply_floor(3), Some(3));
+        let stepwise = Ratio::new(3, 2)
+            .checked_apply_floor(Ratio::new(2, 3).checked_apply_floor(1).unwrap())
+            .unwrap();
+        assert_eq!(stepwise, 0);
+        assert_eq!(ratio.checked_apply_floor(1), Some(1));
+
+        let ratio = Ratio::new(u64::MAX, 1)
+            .checked_mul(&Ratio::new(u64::MAX, 1))
+            .unwrap();
+        assert_eq!(ratio.checked_mul(&Ratio::new(2, 1)), None);
+    }
+
+    proptest! {
+        #[test]
+        fn floor_and_ceil_bracket_exact_result(
+            amount in 0..=u64::MAX,
+            numerator in 0..=u64::MAX,
+            denominator in 1..=u64::MAX,
+        ) {
+            let ratio = Ratio::new(numerator, denominator);
+            let exact = amount as u128 * numerator as u128;
+            let denominator = denominator as u128;
+            match (ratio.checked_apply_floor(amount), ratio.checked_apply_ceil(amount)) {
+                (Some(floor), Some(ceil)) => {
+                    assert!(floor as u128 * denominator <= exact);
+                    assert!(ceil as u128 * denominator >= exact);
+                    assert!(ceil - floor <= 1);
+                    assert_eq!(ceil == floor, exact % denominator == 0);
+                }
+                (Some(floor), None) => {
+                    assert_eq!(floor, u64::MAX);
+                    assert!(exact % denominator != 0);
+                }
+                (None, None) => assert!(exact / denominator > u64::MAX as u128),
+                (None, Some(_)) => panic!("ceil fits where floor does not"),
+            }
+        }
+    }
+}
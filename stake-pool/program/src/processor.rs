@@ -2,12 +2,32 @@
 
 use {
     crate::{
-        borsh::try_from_slice_unchecked,
-        error::StakePoolError,
-        instruction::{Fee, StakePoolInstruction},
+        borsh::get_instance_packed_len,
+        error::{CalculationResult, StakePoolError},
+        event::{
+            ConfigUpdate, ConfigUpdatedEvent, DepositEvent, FeeCollectedEvent, InitializeEvent,
+            PoolTokensBurnedEvent, StakePoolBalanceUpdatedEvent, StakePoolClosedEvent,
+            StakePoolEvent, TreasuryFeeCollectedEvent, ValidatorEvent,
+            ValidatorListBalanceUpdatedEvent, ValidatorStakeEvent, WithdrawEvent,
+        },
+        inline_mpl_token_metadata::{self, pda::find_metadata_account, state::DataV2},
+        instruction::{
+            Fee, FeeType, FundingType, PreferredValidatorType, StakePoolInstruction,
+            ValidatorTarget,
+        },
+        interceptor::{self, InterceptDeposit},
         stake_program,
-        state::{AccountType, StakePool, ValidatorList, ValidatorStakeInfo},
-        AUTHORITY_DEPOSIT, AUTHORITY_WITHDRAW,
+        state::{
+            AccountType, DepositRecord, FeeOperation, ManagerAction, PendingManagerAction,
+            PriceOracle, RateHistory, SingleValidatorPool, StakePool, StakeStatus, ValidatorList,
+            ValidatorListEntries, ValidatorListHeader, ValidatorStakeInfo, STAKE_POOL_VERSION,
+            VALIDATOR_LIST_VERSION,
+        },
+        vote_program::{self, VoteStateVersions},
+        AUTHORITY_DEPOSIT, AUTHORITY_INTERCEPTOR, AUTHORITY_WITHDRAW, DEPOSIT_RECORD_SEED,
+        MAX_EPOCH_NET_OUTFLOW_BPS, MAX_TARGET_WEIGHT_BPS, MINIMUM_ACTIVE_STAKE, PRICE_ORACLE_SEED,
+        RATE_HISTORY_SEED, SINGLE_VALIDATOR_POOL_MINT_SEED, SINGLE_VALIDATOR_POOL_SEED,
+        TRANSIENT_STAKE_SEED,
     },
     bincode::deserialize,
     borsh::{BorshDeserialize, BorshSerialize},
@@ -33,6 +53,18 @@ use {
     spl_token::state::Mint,
 };
 
+/// Lamports moved by a decrease or increase of validator stake, and who
+/// chose them
+#[derive(Clone, Copy, Debug)]
+enum RebalanceAmount {
+    /// Chosen by the staker, who must sign
+    Staker(u64),
+    /// All stake above the minimum of a delinquent validator
+    Delinquent,
+    /// Computed toward the validator's target weight by `ApplyTargets`
+    Target(u64),
+}
+
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
@@ -59,6 +91,21 @@ impl Processor {
         stake_address == *stake_account_info.key
     }
 
+    /// Checks if a stake account is the transient stake account of a validator
+    fn is_transient_stake_address(
+        vote_account: &Pubkey,
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo,
+        stake_account_info: &AccountInfo,
+    ) -> bool {
+        let (transient_stake_address, _) = crate::find_transient_stake_program_address(
+            &program_id,
+            &vote_account,
+            &stake_pool_info.key,
+        );
+        transient_stake_address == *stake_account_info.key
+    }
+
     /// Returns validator address for a particular stake account and checks its validity
     fn get_validator_checked(
         program_id: &Pubkey,
@@ -78,6 +125,60 @@ impl Processor {
         Ok(vote_account)
     }
 
+    /// Returns the lamports held by the reserve stake account on top of its
+    /// rent-exempt reserve
+    fn get_reserve_lamports(reserve_stake_info: &AccountInfo) -> Result<u64, ProgramError> {
+        let stake_state: stake_program::StakeState = deserialize(&reserve_stake_info.data.borrow())
+            .or(Err(ProgramError::InvalidAccountData))?;
+        match stake_state {
+            stake_program::StakeState::Initialized(meta)
+            | stake_program::StakeState::Stake(meta, _) => reserve_stake_info
+                .lamports()
+                .checked_sub(meta.rent_exempt_reserve)
+                .or_calculation_failure("reserve lamports above its rent-exempt reserve"),
+            _ => Err(StakePoolError::WrongStakeState.into()),
+        }
+    }
+
+    /// Check that splitting `lamports` out of `stake_account` leaves it either
+    /// empty or with at least `stake_minimum` lamports, and that
+    /// `split_stake` ends up with at least `split_minimum` lamports
+    fn check_stake_split(
+        stake_account: &AccountInfo,
+        stake_minimum: u64,
+        lamports: u64,
+        split_stake: &AccountInfo,
+        split_minimum: u64,
+    ) -> Result<(), ProgramError> {
+        let remaining_lamports = stake_account
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if remaining_lamports != 0 && remaining_lamports < stake_minimum {
+            msg!(
+                "Split would leave {} lamports in stake account {}, minimum is {}",
+                remaining_lamports,
+                stake_account.key,
+                stake_minimum
+            );
+            return Err(StakePoolError::StakeSplitBelowMinimum.into());
+        }
+        let split_lamports = split_stake
+            .lamports()
+            .checked_add(lamports)
+            .or_calculation_failure("lamports of the split stake")?;
+        if split_lamports < split_minimum {
+            msg!(
+                "Split would leave {} lamports in stake account {}, minimum is {}",
+                split_lamports,
+                split_stake.key,
+                split_minimum
+            );
+            return Err(StakePoolError::StakeSplitBelowMinimum.into());
+        }
+        Ok(())
+    }
+
     /// Issue a stake_split instruction.
     fn stake_split<'a>(
         stake_pool: &Pubkey,
@@ -131,6 +232,41 @@ impl Processor {
         )
     }
 
+    /// Issue a stake_withdraw instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn stake_withdraw<'a>(
+        stake_pool: &Pubkey,
+        stake_account: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        destination: AccountInfo<'a>,
+        clock: AccountInfo<'a>,
+        stake_history: AccountInfo<'a>,
+        stake_program_info: AccountInfo<'a>,
+        lamports: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix =
+            stake_program::withdraw(stake_account.key, authority.key, destination.key, lamports);
+
+        invoke_signed(
+            &ix,
+            &[
+                stake_account,
+                destination,
+                clock,
+                stake_history,
+                authority,
+                stake_program_info,
+            ],
+            signers,
+        )
+    }
+
     /// Issue a stake_set_manager instruction.
     #[allow(clippy::too_many_arguments)]
     fn stake_authorize<'a>(
@@ -158,903 +294,4737 @@ impl Processor {
         )
     }
 
-    /// Issue a spl_token `Burn` instruction.
-    #[allow(clippy::too_many_arguments)]
-    fn token_burn<'a>(
+    /// Issue a stake_set_lockup instruction clearing an expired lockup, so that
+    /// the stake account can be merged into one without lockup.
+    fn stake_clear_lockup<'a>(
         stake_pool: &Pubkey,
-        token_program: AccountInfo<'a>,
-        burn_account: AccountInfo<'a>,
-        mint: AccountInfo<'a>,
+        stake_account: AccountInfo<'a>,
         authority: AccountInfo<'a>,
         authority_type: &[u8],
         bump_seed: u8,
-        amount: u64,
+        stake_program_info: AccountInfo<'a>,
     ) -> Result<(), ProgramError> {
         let me_bytes = stake_pool.to_bytes();
         let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
         let signers = &[&authority_signature_seeds[..]];
 
-        let ix = spl_token::instruction::burn(
-            token_program.key,
-            burn_account.key,
-            mint.key,
-            authority.key,
-            &[],
-            amount,
-        )?;
+        let lockup = stake_program::LockupArgs {
+            unix_timestamp: Some(0),
+            epoch: Some(0),
+            custodian: Some(Pubkey::default()),
+        };
+        let ix = stake_program::set_lockup(stake_account.key, &lockup, authority.key);
 
         invoke_signed(
             &ix,
-            &[burn_account, mint, authority, token_program],
+            &[stake_account, authority, stake_program_info],
             signers,
         )
     }
 
-    /// Issue a spl_token `MintTo` instruction.
+    /// Issue a stake_deactivate instruction.
+    fn stake_deactivate<'a>(
+        stake_pool: &Pubkey,
+        stake_account: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        clock: AccountInfo<'a>,
+        stake_program_info: AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = stake_program::deactivate_stake(stake_account.key, authority.key);
+
+        invoke_signed(
+            &ix,
+            &[stake_account, clock, authority, stake_program_info],
+            signers,
+        )
+    }
+
+    /// Issue a stake_delegate instruction.
     #[allow(clippy::too_many_arguments)]
-    fn token_mint_to<'a>(
+    fn stake_delegate<'a>(
         stake_pool: &Pubkey,
-        token_program: AccountInfo<'a>,
-        mint: AccountInfo<'a>,
-        destination: AccountInfo<'a>,
+        stake_account: AccountInfo<'a>,
         authority: AccountInfo<'a>,
         authority_type: &[u8],
         bump_seed: u8,
-        amount: u64,
+        vote_account: AccountInfo<'a>,
+        clock: AccountInfo<'a>,
+        stake_history: AccountInfo<'a>,
+        stake_config: AccountInfo<'a>,
+        stake_program_info: AccountInfo<'a>,
     ) -> Result<(), ProgramError> {
         let me_bytes = stake_pool.to_bytes();
         let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
         let signers = &[&authority_signature_seeds[..]];
 
-        let ix = spl_token::instruction::mint_to(
-            token_program.key,
-            mint.key,
-            destination.key,
-            authority.key,
-            &[],
-            amount,
-        )?;
+        let ix = stake_program::delegate_stake(stake_account.key, authority.key, vote_account.key);
 
-        invoke_signed(&ix, &[mint, destination, authority, token_program], signers)
+        invoke_signed(
+            &ix,
+            &[
+                stake_account,
+                vote_account,
+                clock,
+                stake_history,
+                stake_config,
+                authority,
+                stake_program_info,
+            ],
+            signers,
+        )
     }
 
-    /// Processes `Initialize` instruction.
-    fn process_initialize(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        fee: Fee,
-        max_validators: u32,
-    ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let stake_pool_info = next_account_info(account_info_iter)?;
-        let manager_info = next_account_info(account_info_iter)?;
-        let staker_info = next_account_info(account_info_iter)?;
-        let validator_list_info = next_account_info(account_info_iter)?;
-        let pool_mint_info = next_account_info(account_info_iter)?;
-        let manager_fee_info = next_account_info(account_info_iter)?;
-        let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
-        let rent_info = next_account_info(account_info_iter)?;
-        let rent = &Rent::from_account_info(rent_info)?;
-        let token_program_info = next_account_info(account_info_iter)?;
+    /// Allocates and assigns a program address to the stake program, so that
+    /// it can receive stake
+    fn allocate_stake_account<'a>(
+        stake_account_info: &AccountInfo<'a>,
+        stake_account_signer_seeds: &[&[u8]],
+        system_program_info: &AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        invoke_signed(
+            &system_instruction::allocate(
+                stake_account_info.key,
+                std::mem::size_of::<stake_program::StakeState>() as u64,
+            ),
+            &[stake_account_info.clone(), system_program_info.clone()],
+            &[stake_account_signer_seeds],
+        )?;
 
-        if !manager_info.is_signer {
-            return Err(StakePoolError::SignatureMissing.into());
-        }
+        invoke_signed(
+            &system_instruction::assign(stake_account_info.key, &stake_program::id()),
+            &[stake_account_info.clone(), system_program_info.clone()],
+            &[stake_account_signer_seeds],
+        )
+    }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_uninitialized() {
-            return Err(StakePoolError::AlreadyInUse.into());
+    /// Allocates and assigns a validator's transient stake account to the stake
+    /// program, so that it can receive a split
+    fn create_transient_stake_account<'a>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        vote_account: &Pubkey,
+        transient_stake_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+    ) -> Result<(), ProgramError> {
+        let (transient_stake_address, bump_seed) = crate::find_transient_stake_program_address(
+            program_id,
+            vote_account,
+            stake_pool_info.key,
+        );
+        if transient_stake_address != *transient_stake_account_info.key {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
         }
-
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_uninitialized() {
-            return Err(StakePoolError::AlreadyInUse.into());
+        if transient_stake_account_info.lamports() > 0 {
+            return Err(StakePoolError::TransientAccountInUse.into());
         }
 
-        let data_length = validator_list_info.data_len();
-        let expected_max_validators = ValidatorList::calculate_max_validators(data_length);
-        if expected_max_validators != max_validators as usize || max_validators == 0 {
-            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
-        }
-        validator_list.account_type = AccountType::ValidatorList;
-        validator_list.validators.clear();
-        validator_list.max_validators = max_validators;
+        let transient_stake_account_signer_seeds: &[&[_]] = &[
+            TRANSIENT_STAKE_SEED,
+            &vote_account.to_bytes()[..32],
+            &stake_pool_info.key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        Self::allocate_stake_account(
+            transient_stake_account_info,
+            transient_stake_account_signer_seeds,
+            system_program_info,
+        )
+    }
 
-        if !rent.is_exempt(stake_pool_info.lamports(), stake_pool_info.data_len()) {
-            msg!("Stake pool not rent-exempt");
-            return Err(ProgramError::AccountNotRentExempt);
+    /// Creates an account owned by the program at a program address, topping
+    /// it up rather than creating it since the address may already hold
+    /// lamports
+    fn create_program_account<'a>(
+        program_id: &Pubkey,
+        new_account_info: &AccountInfo<'a>,
+        funder_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        space: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let required_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(new_account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    funder_info.key,
+                    new_account_info.key,
+                    required_lamports,
+                ),
+                &[
+                    funder_info.clone(),
+                    new_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
         }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, space as u64),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, program_id),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )
+    }
 
-        if !rent.is_exempt(
-            validator_list_info.lamports(),
-            validator_list_info.data_len(),
-        ) {
-            msg!("Validator stake list not rent-exempt");
-            return Err(ProgramError::AccountNotRentExempt);
-        }
+    /// Checks that a stake account can be deposited into a validator stake
+    /// account of the pool, returning the validator's entry and the lockup of
+    /// the deposited stake
+    fn check_deposited_stake(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo,
+        stake_pool: &StakePool,
+        validator_list_info: &AccountInfo,
+        stake_info: &AccountInfo,
+        validator_stake_account_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Result<(ValidatorStakeInfo, stake_program::Lockup), ProgramError> {
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, validator_stake_account_info)?;
 
-        // Numerator should be smaller than or equal to denominator (fee <= 1)
-        if fee.numerator > fee.denominator {
-            return Err(StakePoolError::FeeTooHigh.into());
+        if let Some(preferred_deposit_validator) = stake_pool.preferred_deposit_validator() {
+            if preferred_deposit_validator != vote_account {
+                msg!(
+                    "Validator vote address {} is preferred for deposits",
+                    preferred_deposit_validator
+                );
+                return Err(StakePoolError::IncorrectDepositVoteAddress.into());
+            }
         }
 
-        if manager_fee_info.owner != token_program_info.key {
-            return Err(ProgramError::IncorrectProgramId);
+        let validator_list_item =
+            ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+        if !validator_list_item.is_active() {
+            return Err(StakePoolError::ValidatorNotActive.into());
         }
-
-        if pool_mint_info.owner != token_program_info.key {
-            return Err(ProgramError::IncorrectProgramId);
+        validator_list_item.check_up_to_date(clock.epoch)?;
+        validator_list_item.check_eligible(clock.epoch)?;
+
+        // A delegated stake can only be merged into a validator stake account
+        // delegated to the same vote account
+        let stake_state: stake_program::StakeState =
+            deserialize(&stake_info.data.borrow()).or(Err(ProgramError::InvalidAccountData))?;
+        let lockup = stake_state
+            .meta()
+            .ok_or(StakePoolError::WrongStakeState)?
+            .lockup;
+        if lockup.is_in_force(clock) {
+            msg!(
+                "Deposited stake is locked up until epoch {} and timestamp {}",
+                lockup.epoch,
+                lockup.unix_timestamp
+            );
+            return Err(StakePoolError::StakeLockupInForce.into());
         }
-
-        if *pool_mint_info.key
-            != spl_token::state::Account::unpack_from_slice(&manager_fee_info.data.borrow())?.mint
-        {
-            return Err(StakePoolError::WrongAccountMint.into());
+        if let Some(delegation) = stake_state.delegation() {
+            if delegation.voter_pubkey != validator_list_item.vote_account {
+                msg!(
+                    "Deposited stake delegated to {}, but validator stake account delegated to {}",
+                    delegation.voter_pubkey,
+                    validator_list_item.vote_account
+                );
+                return Err(StakePoolError::IncorrectDepositVoteAddress.into());
+            }
         }
 
-        let (_, deposit_bump_seed) =
-            crate::find_deposit_authority_program_address(program_id, stake_pool_info.key);
-        let (withdraw_authority_key, withdraw_bump_seed) =
-            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
+        Ok((validator_list_item, lockup))
+    }
 
-        let pool_mint = Mint::unpack_from_slice(&pool_mint_info.data.borrow())?;
+    /// Hands a deposited stake account over to the pool's withdraw authority
+    /// and merges it into the validator stake account
+    fn merge_deposited_stake<'a>(
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        stake_info: &AccountInfo<'a>,
+        lockup: &stake_program::Lockup,
+        deposit_info: &AccountInfo<'a>,
+        withdraw_info: &AccountInfo<'a>,
+        validator_stake_account_info: &AccountInfo<'a>,
+        clock_info: &AccountInfo<'a>,
+        stake_history_info: &AccountInfo<'a>,
+        stake_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        Self::stake_authorize(
+            stake_pool_info.key,
+            stake_info.clone(),
+            deposit_info.clone(),
+            AUTHORITY_DEPOSIT,
+            stake_pool.deposit_bump_seed,
+            withdraw_info.key,
+            stake_program::StakeAuthorize::Withdrawer,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
 
-        if pool_mint.supply != 0 {
-            return Err(StakePoolError::NonZeroPoolTokenSupply.into());
-        }
+        Self::stake_authorize(
+            stake_pool_info.key,
+            stake_info.clone(),
+            deposit_info.clone(),
+            AUTHORITY_DEPOSIT,
+            stake_pool.deposit_bump_seed,
+            withdraw_info.key,
+            stake_program::StakeAuthorize::Staker,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
 
-        if !pool_mint.mint_authority.contains(&withdraw_authority_key) {
-            return Err(StakePoolError::WrongMintingAuthority.into());
+        // An expired lockup would still prevent the merge into the validator
+        // stake account, which has none
+        if *lockup != stake_program::Lockup::default() {
+            Self::stake_clear_lockup(
+                stake_pool_info.key,
+                stake_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                stake_program_info.clone(),
+            )?;
         }
 
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
-
-        msg!("Clock data: {:?}", clock_info.data.borrow());
-        msg!("Epoch: {}", clock.epoch);
-
-        stake_pool.account_type = AccountType::StakePool;
-        stake_pool.manager = *manager_info.key;
-        stake_pool.staker = *staker_info.key;
-        stake_pool.deposit_bump_seed = deposit_bump_seed;
-        stake_pool.withdraw_bump_seed = withdraw_bump_seed;
-        stake_pool.validator_list = *validator_list_info.key;
-        stake_pool.pool_mint = *pool_mint_info.key;
-        stake_pool.manager_fee_account = *manager_fee_info.key;
-        stake_pool.token_program_id = *token_program_info.key;
-        stake_pool.last_update_epoch = clock.epoch;
-        stake_pool.fee = fee;
+        Self::stake_merge(
+            stake_pool_info.key,
+            stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            validator_stake_account_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_program_info.clone(),
+        )
+    }
+
+    /// Records a deposit for its signing depositor when the pool has a deposit
+    /// lock, creating their deposit record on first use.  The pool tokens must
+    /// have been minted into an escrow owned by the record, where they stay
+    /// until the depositor claims them.
+    fn record_deposit<'a, 'b>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        dest_user_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        clock: &Clock,
+    ) -> ProgramResult {
+        if stake_pool.deposit_lock_epochs == 0 {
+            return Ok(());
+        }
+        let deposit_record_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !depositor_info.is_signer {
+            msg!("Depositor signature missing");
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        let depositor = *depositor_info.key;
+        let (deposit_record_address, bump_seed) =
+            crate::find_deposit_record_address(program_id, stake_pool_info.key, &depositor);
+        if deposit_record_address != *deposit_record_info.key {
+            return Err(StakePoolError::InvalidDepositRecord.into());
+        }
 
-        stake_pool
-            .serialize(&mut *stake_pool_info.data.borrow_mut())
+        // Pool tokens anywhere else could be moved to another wallet and
+        // withdrawn right away
+        let escrow = spl_token::state::Account::unpack(&dest_user_info.data.borrow())?;
+        if escrow.owner != deposit_record_address {
+            msg!(
+                "Pool tokens of {} must go to a token account owned by {}",
+                depositor,
+                deposit_record_address
+            );
+            return Err(StakePoolError::InvalidDepositEscrow.into());
+        }
+
+        let mut deposit_record = if deposit_record_info.data_is_empty() {
+            let deposit_record = DepositRecord {
+                account_type: AccountType::DepositRecord,
+                stake_pool: *stake_pool_info.key,
+                depositor,
+                locked_until_epoch: 0,
+            };
+            let space = get_instance_packed_len(&deposit_record)?;
+            let deposit_record_signer_seeds: &[&[_]] = &[
+                DEPOSIT_RECORD_SEED,
+                &stake_pool_info.key.to_bytes()[..32],
+                &depositor.to_bytes()[..32],
+                &[bump_seed],
+            ];
+            Self::create_program_account(
+                program_id,
+                deposit_record_info,
+                depositor_info,
+                system_program_info,
+                space,
+                deposit_record_signer_seeds,
+            )?;
+            deposit_record
+        } else {
+            if deposit_record_info.owner != program_id {
+                return Err(StakePoolError::InvalidDepositRecord.into());
+            }
+            let deposit_record = DepositRecord::try_from_slice(&deposit_record_info.data.borrow())?;
+            if !deposit_record.is_valid() {
+                return Err(StakePoolError::InvalidDepositRecord.into());
+            }
+            deposit_record
+        };
+
+        let locked_until_epoch = clock
+            .epoch
+            .checked_add(stake_pool.deposit_lock_epochs)
+            .or_calculation_failure("deposit lock expiry epoch")?;
+        deposit_record.locked_until_epoch =
+            std::cmp::max(deposit_record.locked_until_epoch, locked_until_epoch);
+        deposit_record
+            .serialize(&mut *deposit_record_info.data.borrow_mut())
             .map_err(|e| e.into())
     }
 
-    /// Processes `CreateValidatorStakeAccount` instruction.
-    fn process_create_validator_stake_account(
+    /// Invokes the pool's deposit interceptor with the details of a deposit,
+    /// if it was passed after the other deposit accounts, signed by the
+    /// pool's interceptor authority.  Must come after the pool state is saved.
+    #[allow(clippy::too_many_arguments)]
+    fn intercept_deposit<'a, 'b>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        dest_user_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        vote_account: Option<Pubkey>,
+        lamports: u64,
+        pool_tokens: u64,
+    ) -> ProgramResult {
+        if stake_pool.deposit_interceptor().is_none() {
+            return Ok(());
+        }
+        let deposit_interceptor_info = next_account_info(account_info_iter).ok();
+        if !stake_pool.check_deposit_interceptor(deposit_interceptor_info)? {
+            msg!("Deposit interceptor left out");
+            return Ok(());
+        }
+        let deposit_interceptor_info = deposit_interceptor_info.unwrap();
+        let interceptor_authority_info = next_account_info(account_info_iter)?;
+        // The interceptor gets a signer of its own, with no authority over
+        // the pool's stake accounts or mint
+        let (interceptor_authority, bump_seed) =
+            crate::find_interceptor_authority_program_address(program_id, stake_pool_info.key);
+        if interceptor_authority != *interceptor_authority_info.key {
+            msg!(
+                "Interceptor authority {} expected, {} provided",
+                interceptor_authority,
+                interceptor_authority_info.key
+            );
+            return Err(StakePoolError::InvalidProgramAddress.into());
+        }
+
+        let ix = interceptor::intercept_deposit(
+            deposit_interceptor_info.key,
+            stake_pool_info.key,
+            interceptor_authority_info.key,
+            dest_user_info.key,
+            &InterceptDeposit {
+                vote_account,
+                lamports,
+                pool_tokens,
+                compute_units: stake_pool.deposit_interceptor_compute_units,
+            },
+        )?;
+        let me_bytes = stake_pool_info.key.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], AUTHORITY_INTERCEPTOR, &[bump_seed]];
+        invoke_signed(
+            &ix,
+            &[
+                stake_pool_info.clone(),
+                interceptor_authority_info.clone(),
+                dest_user_info.clone(),
+                deposit_interceptor_info.clone(),
+            ],
+            &[&authority_signature_seeds[..]],
+        )
+    }
+
+    /// Takes the next account, if any, as the pool's program account at
+    /// `address`, followed by its funder and the system program.  The account
+    /// is created with `space` bytes if it does not exist yet, in which case
+    /// `true` is returned along with it.
+    fn next_optional_program_account<'a, 'b>(
+        program_id: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        address: &Pubkey,
+        signer_seeds: &[&[u8]],
+        space: usize,
+        invalid_account_error: StakePoolError,
+    ) -> Result<Option<(&'b AccountInfo<'a>, bool)>, ProgramError> {
+        let account_info = match next_account_info(account_info_iter) {
+            Ok(account_info) => account_info,
+            Err(_) => return Ok(None),
+        };
+        if *account_info.key != *address {
+            return Err(invalid_account_error.into());
+        }
+        let funder_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if account_info.data_is_empty() {
+            Self::create_program_account(
+                program_id,
+                account_info,
+                funder_info,
+                system_program_info,
+                space,
+                signer_seeds,
+            )?;
+            Ok(Some((account_info, true)))
+        } else if account_info.owner != program_id {
+            Err(invalid_account_error.into())
+        } else {
+            Ok(Some((account_info, false)))
+        }
+    }
+
+    /// Publishes the pool token rate to the pool's price oracle, if it was
+    /// passed after the required accounts, creating the oracle on first use
+    fn publish_price_oracle<'a, 'b>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        clock: &Clock,
+    ) -> ProgramResult {
+        let price_oracle = PriceOracle {
+            account_type: AccountType::PriceOracle,
+            stake_pool: *stake_pool_info.key,
+            rate: stake_pool
+                .price_oracle_rate()
+                .or_calculation_failure("price oracle rate")?,
+            confidence: 0,
+            last_update_epoch: clock.epoch,
+        };
+        let (price_oracle_address, bump_seed) =
+            crate::find_price_oracle_address(program_id, stake_pool_info.key);
+        let price_oracle_signer_seeds: &[&[_]] = &[
+            PRICE_ORACLE_SEED,
+            &stake_pool_info.key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        let (price_oracle_info, created) = match Self::next_optional_program_account(
+            program_id,
+            account_info_iter,
+            &price_oracle_address,
+            price_oracle_signer_seeds,
+            get_instance_packed_len(&price_oracle)?,
+            StakePoolError::InvalidPriceOracle,
+        )? {
+            Some(price_oracle_account) => price_oracle_account,
+            None => return Ok(()),
+        };
+        if !created {
+            PriceOracle::deserialize_checked(&price_oracle_info.data.borrow())?;
+        }
+
+        price_oracle
+            .serialize(&mut *price_oracle_info.data.borrow_mut())
+            .map_err(|e| e.into())
+    }
+
+    /// Records the pool's balances in its rate history, if it was passed after
+    /// the price oracle accounts, creating the history on first use
+    fn record_rate_history<'a, 'b>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> ProgramResult {
+        let new_rate_history = RateHistory::new(*stake_pool_info.key);
+        let (rate_history_address, bump_seed) =
+            crate::find_rate_history_address(program_id, stake_pool_info.key);
+        let rate_history_signer_seeds: &[&[_]] = &[
+            RATE_HISTORY_SEED,
+            &stake_pool_info.key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        let (rate_history_info, created) = match Self::next_optional_program_account(
+            program_id,
+            account_info_iter,
+            &rate_history_address,
+            rate_history_signer_seeds,
+            get_instance_packed_len(&new_rate_history)?,
+            StakePoolError::InvalidRateHistory,
+        )? {
+            Some(rate_history_account) => rate_history_account,
+            None => return Ok(()),
+        };
+        let mut rate_history = if created {
+            new_rate_history
+        } else {
+            RateHistory::deserialize_checked(&rate_history_info.data.borrow())?
+        };
+
+        rate_history.record(stake_pool.rate_history_entry());
+        rate_history
+            .serialize(&mut *rate_history_info.data.borrow_mut())
+            .map_err(|e| e.into())
+    }
+
+    /// Issue a spl_token `Burn` instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn token_burn<'a>(
+        stake_pool: &Pubkey,
+        token_program: AccountInfo<'a>,
+        burn_account: AccountInfo<'a>,
+        mint: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::burn(
+            token_program.key,
+            burn_account.key,
+            mint.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[burn_account, mint, authority, token_program],
+            signers,
+        )
+    }
+
+    /// Issue a spl_token `Transfer` instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn token_transfer<'a>(
+        stake_pool: &Pubkey,
+        token_program: AccountInfo<'a>,
+        source: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[source, destination, authority, token_program],
+            signers,
+        )
+    }
+
+    /// Issue a spl_token `MintTo` instruction.
+    #[allow(clippy::too_many_arguments)]
+    fn token_mint_to<'a>(
+        stake_pool: &Pubkey,
+        token_program: AccountInfo<'a>,
+        mint: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        authority_type: &[u8],
+        bump_seed: u8,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        let me_bytes = stake_pool.to_bytes();
+        let authority_signature_seeds = [&me_bytes[..32], authority_type, &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::mint_to(
+            token_program.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(&ix, &[mint, destination, authority, token_program], signers)
+    }
+
+    /// Processes `Initialize` instruction.
+    fn process_initialize(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        fee: Fee,
+        max_validators: u32,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
         let staker_info = next_account_info(account_info_iter)?;
-        let funder_info = next_account_info(account_info_iter)?;
-        let stake_account_info = next_account_info(account_info_iter)?;
-        let validator_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
         let rent_info = next_account_info(account_info_iter)?;
         let rent = &Rent::from_account_info(rent_info)?;
-        let clock_info = next_account_info(account_info_iter)?;
-        let stake_history_info = next_account_info(account_info_iter)?;
-        let stake_config_info = next_account_info(account_info_iter)?;
-        let system_program_info = next_account_info(account_info_iter)?;
-        let stake_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_deposit_authority_info = next_account_info(account_info_iter).ok();
 
-        if stake_pool_info.owner != program_id {
-            return Err(ProgramError::IncorrectProgramId);
+        if !manager_info.is_signer {
+            return Err(StakePoolError::SignatureMissing.into());
         }
-        let stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+
+        let mut stake_pool = StakePool::deserialize_uninitialized(&stake_pool_info.data.borrow())?;
+
+        ValidatorList::check_uninitialized(&validator_list_info.data.borrow())?;
+
+        let data_length = validator_list_info.data_len();
+        let expected_max_validators = ValidatorList::calculate_max_validators(data_length);
+        if expected_max_validators != max_validators as usize || max_validators == 0 {
+            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
         }
-        stake_pool.check_staker(staker_info)?;
+        let validator_list = ValidatorList {
+            account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
+            max_validators,
+            validators: vec![],
+        };
 
-        if *system_program_info.key != solana_program::system_program::id() {
+        if !rent.is_exempt(stake_pool_info.lamports(), stake_pool_info.data_len()) {
+            msg!("Stake pool not rent-exempt");
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        if !rent.is_exempt(
+            validator_list_info.lamports(),
+            validator_list_info.data_len(),
+        ) {
+            msg!("Validator stake list not rent-exempt");
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        // Numerator should be smaller than or equal to denominator (fee <= 1)
+        if !fee.is_valid() {
+            return Err(StakePoolError::FeeTooHigh.into());
+        }
+
+        if manager_fee_info.owner != token_program_info.key {
             return Err(ProgramError::IncorrectProgramId);
         }
-        if *stake_program_info.key != stake_program::id() {
+
+        if pool_mint_info.owner != token_program_info.key {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let (stake_address, bump_seed) = crate::find_stake_program_address(
-            &program_id,
-            &validator_info.key,
-            &stake_pool_info.key,
-        );
-        if stake_address != *stake_account_info.key {
-            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        if *pool_mint_info.key
+            != spl_token::state::Account::unpack_from_slice(&manager_fee_info.data.borrow())?.mint
+        {
+            return Err(StakePoolError::WrongAccountMint.into());
         }
 
-        let stake_account_signer_seeds: &[&[_]] = &[
-            &validator_info.key.to_bytes()[..32],
-            &stake_pool_info.key.to_bytes()[..32],
-            &[bump_seed],
-        ];
+        let (deposit_authority_key, deposit_bump_seed) =
+            crate::find_deposit_authority_program_address(program_id, stake_pool_info.key);
+        let (withdraw_authority_key, withdraw_bump_seed) =
+            crate::find_withdraw_authority_program_address(program_id, stake_pool_info.key);
 
-        // Fund the stake account with 1 SOL + rent-exempt balance
-        let required_lamports = sol_to_lamports(1.0)
-            + rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let pool_mint = Mint::unpack_from_slice(&pool_mint_info.data.borrow())?;
 
-        // Create new stake account
-        invoke_signed(
-            &system_instruction::create_account(
-                &funder_info.key,
-                &stake_account_info.key,
-                required_lamports,
-                std::mem::size_of::<stake_program::StakeState>() as u64,
+        if pool_mint.supply != 0 {
+            return Err(StakePoolError::NonZeroPoolTokenSupply.into());
+        }
+
+        if !pool_mint.mint_authority.contains(&withdraw_authority_key) {
+            return Err(StakePoolError::WrongMintingAuthority.into());
+        }
+
+        if *reserve_stake_info.owner != stake_program::id() {
+            msg!("Reserve stake account not owned by stake program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_state: stake_program::StakeState = deserialize(&reserve_stake_info.data.borrow())
+            .or(Err(ProgramError::InvalidAccountData))?;
+        if let stake_program::StakeState::Initialized(meta) = stake_state {
+            if meta.authorized.staker != withdraw_authority_key
+                || meta.authorized.withdrawer != withdraw_authority_key
+            {
+                msg!("Reserve stake account not authorized to the pool withdraw authority");
+                return Err(StakePoolError::WrongStakeState.into());
+            }
+            if meta.lockup != stake_program::Lockup::default() {
+                msg!("Reserve stake account has a lockup");
+                return Err(StakePoolError::WrongStakeState.into());
+            }
+            if reserve_stake_info.lamports() != meta.rent_exempt_reserve {
+                msg!("Reserve stake account must not hold more than its rent-exempt reserve");
+                return Err(StakePoolError::WrongStakeState.into());
+            }
+        } else {
+            msg!("Reserve stake account not in the initialized state");
+            return Err(StakePoolError::WrongStakeState.into());
+        }
+
+        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+
+        msg!("Epoch: {}", clock.epoch);
+
+        stake_pool.account_type = AccountType::StakePool;
+        stake_pool.version = STAKE_POOL_VERSION;
+        stake_pool.manager = *manager_info.key;
+        stake_pool.staker = *staker_info.key;
+        stake_pool.deposit_bump_seed = deposit_bump_seed;
+        stake_pool.stake_deposit_authority = stake_deposit_authority_info
+            .map(|stake_deposit_authority_info| *stake_deposit_authority_info.key)
+            .unwrap_or(deposit_authority_key);
+        stake_pool.withdraw_bump_seed = withdraw_bump_seed;
+        stake_pool.validator_list = *validator_list_info.key;
+        stake_pool.reserve_stake = *reserve_stake_info.key;
+        stake_pool.pool_mint = *pool_mint_info.key;
+        stake_pool.manager_fee_account = *manager_fee_info.key;
+        stake_pool.token_program_id = *token_program_info.key;
+        stake_pool.last_update_epoch = clock.epoch;
+        stake_pool.epoch_fee = fee;
+        stake_pool.next_epoch_fee = fee;
+
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        StakePoolEvent::Initialize(InitializeEvent {
+            stake_pool: *stake_pool_info.key,
+            manager: stake_pool.manager,
+            staker: stake_pool.staker,
+            pool_mint: stake_pool.pool_mint,
+            validator_list: stake_pool.validator_list,
+            max_validators,
+            epoch_fee: fee,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes `CreateValidatorStakeAccount` instruction.
+    fn process_create_validator_stake_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let funder_info = next_account_info(account_info_iter)?;
+        let stake_account_info = next_account_info(account_info_iter)?;
+        let validator_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_config_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+        stake_pool.check_staker(staker_info)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let (stake_address, bump_seed) = crate::find_stake_program_address(
+            &program_id,
+            &validator_info.key,
+            &stake_pool_info.key,
+        );
+        if stake_address != *stake_account_info.key {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        }
+
+        let stake_account_signer_seeds: &[&[_]] = &[
+            &validator_info.key.to_bytes()[..32],
+            &stake_pool_info.key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+
+        // Fund the stake account with 1 SOL + rent-exempt balance
+        let required_lamports = sol_to_lamports(1.0)
+            + rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+
+        // Create new stake account
+        invoke_signed(
+            &system_instruction::create_account(
+                &funder_info.key,
+                &stake_account_info.key,
+                required_lamports,
+                std::mem::size_of::<stake_program::StakeState>() as u64,
                 &stake_program::id(),
             ),
             &[funder_info.clone(), stake_account_info.clone()],
             &[&stake_account_signer_seeds],
         )?;
 
-        invoke(
-            &stake_program::initialize(
-                &stake_account_info.key,
-                &stake_program::Authorized {
-                    staker: *staker_info.key,
-                    withdrawer: *staker_info.key,
-                },
-                &stake_program::Lockup::default(),
-            ),
-            &[
-                stake_account_info.clone(),
-                rent_info.clone(),
-                stake_program_info.clone(),
-            ],
-        )?;
+        invoke(
+            &stake_program::initialize(
+                &stake_account_info.key,
+                &stake_program::Authorized {
+                    staker: *staker_info.key,
+                    withdrawer: *staker_info.key,
+                },
+                &stake_program::Lockup::default(),
+            ),
+            &[
+                stake_account_info.clone(),
+                rent_info.clone(),
+                stake_program_info.clone(),
+            ],
+        )?;
+
+        invoke(
+            &stake_program::delegate_stake(
+                &stake_account_info.key,
+                &staker_info.key,
+                &validator_info.key,
+            ),
+            &[
+                stake_account_info.clone(),
+                validator_info.clone(),
+                clock_info.clone(),
+                stake_history_info.clone(),
+                stake_config_info.clone(),
+                staker_info.clone(),
+            ],
+        )?;
+
+        StakePoolEvent::ValidatorStakeAccountCreated(ValidatorEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account: *validator_info.key,
+            stake_account: *stake_account_info.key,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes `AddValidatorToPool` instruction.
+    fn process_add_validator_to_pool(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let deposit_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let stake_account_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_authority_deposit(deposit_info.key, program_id, stake_pool_info.key)?;
+
+        stake_pool.check_staker(staker_info)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_not_decommissioning()?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        {
+            let validator_list_data = validator_list_info.data.borrow();
+            let (header, validator_list) =
+                ValidatorListHeader::deserialize_slice(&validator_list_data)?;
+            if header.max_validators as usize == validator_list.len() {
+                return Err(ProgramError::AccountDataTooSmall);
+            }
+        }
+
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, stake_account_info)?;
+
+        if ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+            .is_some()
+        {
+            return Err(StakePoolError::ValidatorAlreadyAdded.into());
+        }
+
+        // Update Withdrawer and Staker authority to the program withdraw authority
+        for authority in &[
+            stake_program::StakeAuthorize::Withdrawer,
+            stake_program::StakeAuthorize::Staker,
+        ] {
+            Self::stake_authorize(
+                stake_pool_info.key,
+                stake_account_info.clone(),
+                deposit_info.clone(),
+                AUTHORITY_DEPOSIT,
+                stake_pool.deposit_bump_seed,
+                withdraw_info.key,
+                *authority,
+                clock_info.clone(),
+                stake_program_info.clone(),
+            )?;
+        }
+
+        // Calculate and mint tokens
+        let stake_lamports = **stake_account_info.lamports.borrow();
+        // Round down, in favor of the pool
+        let pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit_floor(stake_lamports)
+            .or_calculation_failure("pool tokens for the validator's stake")?;
+        stake_pool.credit_deposit(stake_lamports, pool_tokens)?;
+        Self::token_mint_to(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_user_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            pool_tokens,
+        )?;
+
+        // Check if stake is warmed up
+        Self::check_stake_activation(stake_account_info, clock, stake_history_info)?;
+
+        let mut validator_list_data = validator_list_info.data.borrow_mut();
+        let (_, mut validator_list) =
+            ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+        validator_list.push(ValidatorStakeInfo {
+            vote_account,
+            status: StakeStatus::Active.into(),
+            target_weight_bps: 0.into(),
+            active_stake_lamports: stake_lamports.into(),
+            transient_stake_lamports: 0.into(),
+            last_update_epoch: clock.epoch.into(),
+            eligible_epoch: clock
+                .epoch
+                .checked_add(stake_pool.validator_onboarding_epochs)
+                .or_calculation_failure("validator onboarding epoch")?
+                .into(),
+        })?;
+
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        StakePoolEvent::ValidatorAdded(ValidatorEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            stake_account: *stake_account_info.key,
+        })
+        .log();
+        StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account: Some(vote_account),
+            pool_tokens_destination: *dest_user_info.key,
+            lamports: stake_lamports,
+            pool_tokens,
+            fee_pool_tokens: 0,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes `RemoveValidatorFromPool` instruction, as well as
+    /// `RemoveValidatorByVoteWithdrawer`.
+    fn process_remove_validator_from_pool(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        by_vote_withdrawer: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        // staker for `RemoveValidatorFromPool`, vote account withdrawer for
+        // `RemoveValidatorByVoteWithdrawer`
+        let authority_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let stake_account_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let vote_account_info = if by_vote_withdrawer {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        let transient_stake_info = next_account_info(account_info_iter).ok();
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        // The manager winding the pool down needs no help from the staker.
+        // The validator's own signature is checked once its vote account is
+        // known.
+        if vote_account_info.is_none() {
+            if stake_pool.decommissioning && *authority_info.key == stake_pool.manager {
+                stake_pool.check_manager(authority_info)?;
+            } else {
+                stake_pool.check_staker(authority_info)?;
+            }
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, stake_account_info)?;
+        if let Some(vote_account_info) = vote_account_info {
+            Self::check_vote_withdrawer(&vote_account, vote_account_info, authority_info)?;
+        }
+
+        let mut validator_stake_record =
+            ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+        if !validator_stake_record.is_active() {
+            return Err(StakePoolError::ValidatorNotActive.into());
+        }
+
+        // The lamports stay in the pool: the stake deactivates in place and
+        // `UpdateValidatorListBalance` moves it to the reserve once inactive
+        Self::stake_deactivate(
+            stake_pool_info.key,
+            stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
+
+        let status = if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+            let transient_stake_info = transient_stake_info.ok_or_else(|| {
+                msg!("Validator has transient stake, its transient stake account is required");
+                StakePoolError::TransientAccountInUse
+            })?;
+            if !Self::is_transient_stake_address(
+                &vote_account,
+                program_id,
+                stake_pool_info,
+                transient_stake_info,
+            ) {
+                return Err(StakePoolError::InvalidStakeAccountAddress.into());
+            }
+            // Stake still activating from an increase must come back as well
+            if Self::is_deactivation_pending(transient_stake_info) {
+                Self::stake_deactivate(
+                    stake_pool_info.key,
+                    transient_stake_info.clone(),
+                    withdraw_info.clone(),
+                    AUTHORITY_WITHDRAW,
+                    stake_pool.withdraw_bump_seed,
+                    clock_info.clone(),
+                    stake_program_info.clone(),
+                )?;
+            }
+            StakeStatus::DeactivatingTransient
+        } else {
+            StakeStatus::ReadyForRemoval
+        };
+
+        validator_stake_record.status = status.into();
+        ValidatorListHeader::update_entry(
+            &mut validator_list_info.data.borrow_mut(),
+            &validator_stake_record,
+        )?;
+
+        let mut changes = false;
+        if stake_pool.preferred_deposit_validator() == Some(vote_account) {
+            stake_pool.preferred_deposit_validator_vote_address = Pubkey::default();
+            changes = true;
+        }
+        if stake_pool.preferred_withdraw_validator() == Some(vote_account) {
+            stake_pool.preferred_withdraw_validator_vote_address = Pubkey::default();
+            changes = true;
+        }
+        if changes {
+            stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        }
+
+        StakePoolEvent::ValidatorRemoved(ValidatorEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            stake_account: *stake_account_info.key,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes `DecreaseValidatorStake` instruction, as well as
+    /// `DecreaseDelinquentValidatorStake` and decreases from `ApplyTargets`.
+    fn process_decrease_validator_stake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: RebalanceAmount,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        // staker for `DecreaseValidatorStake`, validator vote account for
+        // `DecreaseDelinquentValidatorStake` and `ApplyTargets`
+        let authority_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let validator_stake_account_info = next_account_info(account_info_iter)?;
+        let transient_stake_account_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        if let RebalanceAmount::Staker(_) = amount {
+            stake_pool.check_staker(authority_info)?;
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, validator_stake_account_info)?;
+
+        let mut validator_stake_record =
+            ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+        if !validator_stake_record.is_active() {
+            return Err(StakePoolError::ValidatorNotActive.into());
+        }
+        if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+            return Err(StakePoolError::TransientAccountInUse.into());
+        }
+
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let required_lamports = stake_program::minimum_stake_lamports(rent);
+        let lamports = match amount {
+            RebalanceAmount::Staker(lamports) | RebalanceAmount::Target(lamports) => lamports,
+            RebalanceAmount::Delinquent => {
+                Self::check_validator_delinquent(
+                    &stake_pool,
+                    &vote_account,
+                    authority_info,
+                    clock,
+                )?;
+                validator_stake_account_info
+                    .lamports()
+                    .saturating_sub(required_lamports)
+            }
+        };
+        if lamports <= stake_rent {
+            msg!(
+                "Need more than {} lamports for transient stake to be rent-exempt, {} provided",
+                stake_rent,
+                lamports
+            );
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let remaining_lamports = validator_stake_account_info
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if remaining_lamports < required_lamports {
+            msg!(
+                "Need at least {} lamports to remain in the validator stake account, {} would remain",
+                required_lamports,
+                remaining_lamports
+            );
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        Self::check_stake_split(
+            validator_stake_account_info,
+            required_lamports,
+            lamports,
+            transient_stake_account_info,
+            required_lamports,
+        )?;
+
+        Self::create_transient_stake_account(
+            program_id,
+            stake_pool_info,
+            &vote_account,
+            transient_stake_account_info,
+            system_program_info,
+        )?;
+
+        Self::stake_split(
+            stake_pool_info.key,
+            validator_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            lamports,
+            transient_stake_account_info.clone(),
+        )?;
+
+        Self::stake_deactivate(
+            stake_pool_info.key,
+            transient_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
+
+        validator_stake_record.active_stake_lamports =
+            validator_stake_account_info.lamports().into();
+        validator_stake_record.transient_stake_lamports = lamports.into();
+        ValidatorListHeader::update_entry(
+            &mut validator_list_info.data.borrow_mut(),
+            &validator_stake_record,
+        )?;
+
+        StakePoolEvent::ValidatorStakeDecreased(ValidatorStakeEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            transient_stake_account: *transient_stake_account_info.key,
+            lamports,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Check that the provided vote account belongs to the validator and that
+    /// the validator is delinquent enough for the pool to allow a
+    /// permissionless decrease
+    fn check_validator_delinquent(
+        stake_pool: &StakePool,
+        vote_account: &Pubkey,
+        vote_account_info: &AccountInfo,
+        clock: &Clock,
+    ) -> ProgramResult {
+        if stake_pool.delinquent_validator_epochs == 0 {
+            return Err(StakePoolError::DelinquentDecreaseDisabled.into());
+        }
+        if vote_account_info.key != vote_account {
+            msg!(
+                "Provided vote account {} does not match the validator's vote account {}",
+                vote_account_info.key,
+                vote_account
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *vote_account_info.owner != vote_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let vote_state = VoteStateVersions::deserialize(&vote_account_info.data.borrow())?;
+        let last_credited_epoch = vote_state.last_credited_epoch();
+        if !stake_pool.is_validator_delinquent(last_credited_epoch, clock.epoch) {
+            msg!(
+                "Validator {} last earned vote credits in epoch {:?}, not delinquent for more than {} epochs",
+                vote_account,
+                last_credited_epoch,
+                stake_pool.delinquent_validator_epochs
+            );
+            return Err(StakePoolError::ValidatorNotDelinquent.into());
+        }
+        Ok(())
+    }
+
+    /// Check that the withdraw authority of the validator's vote account
+    /// signed
+    fn check_vote_withdrawer(
+        vote_account: &Pubkey,
+        vote_account_info: &AccountInfo,
+        vote_withdrawer_info: &AccountInfo,
+    ) -> ProgramResult {
+        if vote_account_info.key != vote_account {
+            msg!(
+                "Provided vote account {} does not match the validator's vote account {}",
+                vote_account_info.key,
+                vote_account
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *vote_account_info.owner != vote_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !vote_withdrawer_info.is_signer {
+            msg!("Vote account withdraw authority signature missing");
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        let vote_state = VoteStateVersions::deserialize(&vote_account_info.data.borrow())?;
+        if vote_state.authorized_withdrawer() != vote_withdrawer_info.key {
+            msg!(
+                "Incorrect vote account withdraw authority provided, expected {}, received {}",
+                vote_state.authorized_withdrawer(),
+                vote_withdrawer_info.key
+            );
+            return Err(StakePoolError::WrongVoteWithdrawer.into());
+        }
+        Ok(())
+    }
+
+    /// Processes `IncreaseValidatorStake` instruction, as well as increases
+    /// from `ApplyTargets`.
+    fn process_increase_validator_stake(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: RebalanceAmount,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        // staker for `IncreaseValidatorStake`, validator vote account for
+        // `ApplyTargets`
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let transient_stake_account_info = next_account_info(account_info_iter)?;
+        let validator_vote_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_config_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_not_decommissioning()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        let lamports = match amount {
+            RebalanceAmount::Staker(lamports) => {
+                stake_pool.check_staker(staker_info)?;
+                lamports
+            }
+            RebalanceAmount::Target(lamports) => lamports,
+            RebalanceAmount::Delinquent => return Err(ProgramError::InvalidArgument),
+        };
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let vote_account = *validator_vote_info.key;
+        let mut validator_stake_record =
+            ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+        if !validator_stake_record.is_active() {
+            return Err(StakePoolError::ValidatorNotActive.into());
+        }
+        validator_stake_record.check_eligible(clock.epoch)?;
+        if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+            return Err(StakePoolError::TransientAccountInUse.into());
+        }
+
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let minimum_lamports = stake_program::minimum_stake_lamports(rent);
+        if lamports < minimum_lamports {
+            msg!(
+                "Need at least {} lamports for transient stake to be rent-exempt and mergeable, {} provided",
+                minimum_lamports,
+                lamports
+            );
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let reserve_lamports = Self::get_reserve_lamports(reserve_stake_info)?;
+        if reserve_lamports < lamports {
+            msg!(
+                "Reserve stake account only has {} lamports available, {} requested",
+                reserve_lamports,
+                lamports
+            );
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        Self::check_stake_split(
+            reserve_stake_info,
+            stake_rent,
+            lamports,
+            transient_stake_account_info,
+            minimum_lamports,
+        )?;
+
+        Self::create_transient_stake_account(
+            program_id,
+            stake_pool_info,
+            &vote_account,
+            transient_stake_account_info,
+            system_program_info,
+        )?;
+
+        Self::stake_split(
+            stake_pool_info.key,
+            reserve_stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            lamports,
+            transient_stake_account_info.clone(),
+        )?;
+
+        Self::stake_delegate(
+            stake_pool_info.key,
+            transient_stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            validator_vote_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_config_info.clone(),
+            stake_program_info.clone(),
+        )?;
+
+        validator_stake_record.transient_stake_lamports = lamports.into();
+        ValidatorListHeader::update_entry(
+            &mut validator_list_info.data.borrow_mut(),
+            &validator_stake_record,
+        )?;
+
+        StakePoolEvent::ValidatorStakeIncreased(ValidatorStakeEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            transient_stake_account: *transient_stake_account_info.key,
+            lamports,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes `ApplyTargets` instruction.
+    fn process_apply_targets(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let validator_stake_account_info = next_account_info(account_info_iter)?;
+        let transient_stake_account_info = next_account_info(account_info_iter)?;
+        let validator_vote_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_config_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+
+        // targets are shares of the total stake, which must be current
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let vote_account =
+            Self::get_validator_checked(program_id, stake_pool_info, validator_stake_account_info)?;
+        if *validator_vote_info.key != vote_account {
+            msg!(
+                "Provided vote account {} does not match the validator's vote account {}",
+                validator_vote_info.key,
+                vote_account
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let overweight_lamports = {
+            let validator_list_data = validator_list_info.data.borrow();
+            let (_, validator_list) = ValidatorListHeader::deserialize_slice(&validator_list_data)?;
+            if validator_list.total_target_weight_bps() == 0 {
+                return Err(StakePoolError::TargetWeightsNotSet.into());
+            }
+            let validator_stake_record = validator_list
+                .find(&vote_account)
+                .ok_or(StakePoolError::ValidatorNotFound)?;
+            if !validator_stake_record.is_active() {
+                return Err(StakePoolError::ValidatorNotActive.into());
+            }
+            if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+                return Err(StakePoolError::TransientAccountInUse.into());
+            }
+            validator_stake_record
+                .overweight_lamports(stake_pool.total_stake_lamports)
+                .or_calculation_failure("validator lamports above target")?
+        };
+
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let minimum_lamports = stake_program::minimum_stake_lamports(rent);
+        if overweight_lamports > 0 {
+            let lamports = std::cmp::min(
+                overweight_lamports as u64,
+                validator_stake_account_info
+                    .lamports()
+                    .saturating_sub(minimum_lamports),
+            );
+            if lamports <= stake_rent {
+                msg!(
+                    "Validator is {} lamports above its target, but only {} can be moved",
+                    overweight_lamports,
+                    lamports
+                );
+                return Err(StakePoolError::ValidatorAtTarget.into());
+            }
+            Self::process_decrease_validator_stake(
+                program_id,
+                &[
+                    stake_pool_info.clone(),
+                    validator_vote_info.clone(),
+                    validator_list_info.clone(),
+                    withdraw_info.clone(),
+                    validator_stake_account_info.clone(),
+                    transient_stake_account_info.clone(),
+                    clock_info.clone(),
+                    rent_info.clone(),
+                    system_program_info.clone(),
+                    stake_program_info.clone(),
+                ],
+                RebalanceAmount::Target(lamports),
+            )
+        } else {
+            let underweight_lamports = (-overweight_lamports) as u64;
+            let lamports = std::cmp::min(
+                underweight_lamports,
+                Self::get_reserve_lamports(reserve_stake_info)?,
+            );
+            if lamports < minimum_lamports {
+                msg!(
+                    "Validator is {} lamports below its target, but only {} can be moved",
+                    underweight_lamports,
+                    lamports
+                );
+                return Err(StakePoolError::ValidatorAtTarget.into());
+            }
+            Self::process_increase_validator_stake(
+                program_id,
+                &[
+                    stake_pool_info.clone(),
+                    validator_vote_info.clone(),
+                    validator_list_info.clone(),
+                    withdraw_info.clone(),
+                    reserve_stake_info.clone(),
+                    transient_stake_account_info.clone(),
+                    validator_vote_info.clone(),
+                    clock_info.clone(),
+                    rent_info.clone(),
+                    stake_history_info.clone(),
+                    stake_config_info.clone(),
+                    system_program_info.clone(),
+                    stake_program_info.clone(),
+                ],
+                RebalanceAmount::Target(lamports),
+            )
+        }
+    }
+
+    /// Processes `UpdateValidatorListBalance` instruction.
+    fn process_update_validator_list_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        start_index: u32,
+        no_merge: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let validator_stake_accounts = account_info_iter.as_slice();
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        // Only the entries being updated are copied out of the list, and
+        // written back in place at the end
+        let start_index = start_index as usize;
+        let mut validator_stake_records: Vec<ValidatorStakeInfo> = {
+            let validator_list_data = validator_list_info.data.borrow();
+            let (_, validator_list) = ValidatorListHeader::deserialize_slice(&validator_list_data)?;
+            validator_list
+                .iter()
+                .skip(start_index)
+                .take(validator_stake_accounts.len() / 2)
+                .copied()
+                .collect()
+        };
+
+        let mut validators_updated: u32 = 0;
+        let mut stake_history_cache = None;
+        let validator_iter = validator_stake_records
+            .iter_mut()
+            .zip(validator_stake_accounts.chunks_exact(2));
+        for (validator_stake_record, validator_stakes) in validator_iter {
+            let validator_stake_info = &validator_stakes[0];
+            let transient_stake_info = &validator_stakes[1];
+
+            // Entries already updated this epoch are left alone, whatever
+            // accounts were passed for them, so their addresses are not
+            // worth deriving
+            if !validator_stake_record.is_stale(clock.epoch) {
+                continue;
+            }
+
+            let (validator_stake_address, _) = crate::find_stake_program_address(
+                program_id,
+                &validator_stake_record.vote_account,
+                stake_pool_info.key,
+            );
+            let (transient_stake_address, _) = crate::find_transient_stake_program_address(
+                program_id,
+                &validator_stake_record.vote_account,
+                stake_pool_info.key,
+            );
+            if validator_stake_address != *validator_stake_info.key
+                || transient_stake_address != *transient_stake_info.key
+            {
+                msg!(
+                    "Stake accounts {} and {} do not belong to validator {} in the list",
+                    validator_stake_info.key,
+                    transient_stake_info.key,
+                    validator_stake_record.vote_account
+                );
+                return Err(StakePoolError::InvalidStakeAccountAddress.into());
+            }
+
+            let mut transient_stake_lamports = 0;
+            if no_merge {
+                transient_stake_lamports = transient_stake_info.lamports();
+            } else if transient_stake_info.lamports() > 0 {
+                let transient_stake_state: Option<stake_program::StakeState> =
+                    deserialize(&transient_stake_info.data.borrow()).ok();
+                match transient_stake_state {
+                    Some(stake_program::StakeState::Initialized(_)) => {
+                        // Delegation never went through, send everything back
+                        Self::stake_merge(
+                            stake_pool_info.key,
+                            transient_stake_info.clone(),
+                            withdraw_info.clone(),
+                            AUTHORITY_WITHDRAW,
+                            stake_pool.withdraw_bump_seed,
+                            reserve_stake_info.clone(),
+                            clock_info.clone(),
+                            stake_history_info.clone(),
+                            stake_program_info.clone(),
+                        )?;
+                    }
+                    Some(stake_program::StakeState::Stake(_, transient_stake)) => {
+                        let stake_history =
+                            Self::load_stake_history(&mut stake_history_cache, stake_history_info)?;
+                        let (effective, activating, deactivating) = transient_stake
+                            .delegation
+                            .stake_activating_and_deactivating(
+                                clock.epoch,
+                                Some(stake_history),
+                                true,
+                            );
+                        if effective == 0 && activating == 0 && deactivating == 0 {
+                            // Fully deactivated, the lamports go back to the reserve
+                            Self::stake_merge(
+                                stake_pool_info.key,
+                                transient_stake_info.clone(),
+                                withdraw_info.clone(),
+                                AUTHORITY_WITHDRAW,
+                                stake_pool.withdraw_bump_seed,
+                                reserve_stake_info.clone(),
+                                clock_info.clone(),
+                                stake_history_info.clone(),
+                                stake_program_info.clone(),
+                            )?;
+                        } else if validator_stake_record.is_active()
+                            && activating == 0
+                            && deactivating == 0
+                            && Self::is_mergeable_into(
+                                &transient_stake,
+                                validator_stake_info,
+                                clock,
+                                stake_history,
+                            )
+                        {
+                            // Fully active, the lamports join the validator stake
+                            Self::stake_merge(
+                                stake_pool_info.key,
+                                transient_stake_info.clone(),
+                                withdraw_info.clone(),
+                                AUTHORITY_WITHDRAW,
+                                stake_pool.withdraw_bump_seed,
+                                validator_stake_info.clone(),
+                                clock_info.clone(),
+                                stake_history_info.clone(),
+                                stake_program_info.clone(),
+                            )?;
+                        } else {
+                            // Still activating or deactivating, check again next epoch
+                            transient_stake_lamports = transient_stake_info.lamports();
+                        }
+                    }
+                    _ => {
+                        msg!(
+                            "Transient stake account {} is in an unexpected state",
+                            transient_stake_info.key
+                        );
+                        transient_stake_lamports = transient_stake_info.lamports();
+                    }
+                }
+            }
+
+            if validator_stake_record.status == StakeStatus::DeactivatingTransient
+                && transient_stake_lamports == 0
+            {
+                // The transient stake is back in the reserve, only the
+                // validator stake account is left to reclaim
+                validator_stake_record.status = StakeStatus::ReadyForRemoval.into();
+            }
+            if !no_merge
+                && validator_stake_record.status == StakeStatus::ReadyForRemoval
+                && transient_stake_lamports == 0
+                && Self::is_fully_deactivated(
+                    validator_stake_info,
+                    clock,
+                    Self::load_stake_history(&mut stake_history_cache, stake_history_info)?,
+                )
+            {
+                // Removed validator is done deactivating, reclaim its lamports
+                Self::stake_merge(
+                    stake_pool_info.key,
+                    validator_stake_info.clone(),
+                    withdraw_info.clone(),
+                    AUTHORITY_WITHDRAW,
+                    stake_pool.withdraw_bump_seed,
+                    reserve_stake_info.clone(),
+                    clock_info.clone(),
+                    stake_history_info.clone(),
+                    stake_program_info.clone(),
+                )?;
+            }
+
+            validator_stake_record.last_update_epoch = clock.epoch.into();
+            validator_stake_record.active_stake_lamports = validator_stake_info.lamports().into();
+            validator_stake_record.transient_stake_lamports = transient_stake_lamports.into();
+            validators_updated += 1;
+        }
+
+        if validators_updated > 0 {
+            let mut validator_list_data = validator_list_info.data.borrow_mut();
+            let (_, mut validator_list) =
+                ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+            let end_index = start_index + validator_stake_records.len();
+            validator_list.as_mut_slice::<ValidatorStakeInfo>()?[start_index..end_index]
+                .copy_from_slice(&validator_stake_records);
+        }
+
+        StakePoolEvent::ValidatorListBalanceUpdated(ValidatorListBalanceUpdatedEvent {
+            stake_pool: *stake_pool_info.key,
+            epoch: clock.epoch,
+            validators_updated,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Merges a validator's transient stake account into the reserve if it
+    /// is fully deactivated, returning the validator's vote account if it was
+    #[allow(clippy::too_many_arguments)]
+    fn sweep_transient_stake<'a>(
+        program_id: &Pubkey,
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        validator_list_info: &AccountInfo<'a>,
+        reserve_stake_info: &AccountInfo<'a>,
+        transient_stake_info: &AccountInfo<'a>,
+        withdraw_info: &AccountInfo<'a>,
+        clock_info: &AccountInfo<'a>,
+        stake_history_info: &AccountInfo<'a>,
+        stake_program_info: &AccountInfo<'a>,
+        clock: &Clock,
+    ) -> Result<Option<Pubkey>, ProgramError> {
+        let vote_account = match Self::get_validator(transient_stake_info) {
+            Ok(vote_account) => vote_account,
+            Err(_) => {
+                msg!(
+                    "Stake account {} holds no delegated stake, nothing to sweep",
+                    transient_stake_info.key
+                );
+                return Ok(None);
+            }
+        };
+        if !Self::is_transient_stake_address(
+            &vote_account,
+            program_id,
+            stake_pool_info,
+            transient_stake_info,
+        ) {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        }
+        if ValidatorListHeader::find_entry(&validator_list_info.data.borrow(), &vote_account)?
+            .is_none()
+        {
+            return Err(StakePoolError::ValidatorNotFound.into());
+        }
+
+        let stake_history = StakeHistory::from_account_info(stake_history_info)?;
+        if !Self::is_fully_deactivated(transient_stake_info, clock, &stake_history) {
+            msg!(
+                "Transient stake account {} is not fully deactivated, left for the update",
+                transient_stake_info.key
+            );
+            return Ok(None);
+        }
+
+        Self::stake_merge(
+            stake_pool_info.key,
+            transient_stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            reserve_stake_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_program_info.clone(),
+        )?;
+        Ok(Some(vote_account))
+    }
+
+    /// Deserializes the stake history on first use: it is only needed for
+    /// stake accounts that may be activating or deactivating, and is by far
+    /// the largest sysvar
+    fn load_stake_history<'a>(
+        stake_history_cache: &'a mut Option<StakeHistory>,
+        stake_history_info: &AccountInfo,
+    ) -> Result<&'a StakeHistory, ProgramError> {
+        if stake_history_cache.is_none() {
+            *stake_history_cache = Some(StakeHistory::from_account_info(stake_history_info)?);
+        }
+        Ok(stake_history_cache.as_ref().unwrap())
+    }
+
+    /// Checks that a stake account is delegated and not deactivating yet
+    fn is_deactivation_pending(stake_info: &AccountInfo) -> bool {
+        let stake_state: Option<stake_program::StakeState> =
+            deserialize(&stake_info.data.borrow()).ok();
+        match stake_state {
+            Some(stake_program::StakeState::Stake(_, stake)) => {
+                stake.delegation.deactivation_epoch == u64::MAX
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that a stake account holds no effective, activating or
+    /// deactivating stake
+    fn is_fully_deactivated(
+        stake_info: &AccountInfo,
+        clock: &Clock,
+        stake_history: &StakeHistory,
+    ) -> bool {
+        let stake_state: Option<stake_program::StakeState> =
+            deserialize(&stake_info.data.borrow()).ok();
+        match stake_state {
+            Some(stake_program::StakeState::Stake(_, stake)) => {
+                let (effective, activating, deactivating) = stake
+                    .delegation
+                    .stake_activating_and_deactivating(clock.epoch, Some(stake_history), true);
+                effective == 0 && activating == 0 && deactivating == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that an active transient stake can be merged into the validator
+    /// stake account: both must be fully active, delegated to the same vote
+    /// account, and have observed the same credits
+    fn is_mergeable_into(
+        transient_stake: &stake_program::Stake,
+        validator_stake_info: &AccountInfo,
+        clock: &Clock,
+        stake_history: &StakeHistory,
+    ) -> bool {
+        let validator_stake_state: Option<stake_program::StakeState> =
+            deserialize(&validator_stake_info.data.borrow()).ok();
+        match validator_stake_state {
+            Some(stake_program::StakeState::Stake(_, validator_stake)) => {
+                let (_, activating, deactivating) = validator_stake
+                    .delegation
+                    .stake_activating_and_deactivating(clock.epoch, Some(stake_history), true);
+                activating == 0
+                    && deactivating == 0
+                    && validator_stake.delegation.voter_pubkey
+                        == transient_stake.delegation.voter_pubkey
+                    && validator_stake.credits_observed == transient_stake.credits_observed
+            }
+            _ => false,
+        }
+    }
+
+    /// Processes `UpdateStakePoolBalance` instruction.
+    fn process_update_stake_pool_balance(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+        let secondary_fee_info = if stake_pool.secondary_fee_account().is_some() {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        stake_pool.check_secondary_fee_info(secondary_fee_info)?;
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // Removed validators whose stake was merged back into the reserve
+        let is_removable = |item: &ValidatorStakeInfo| {
+            item.status == StakeStatus::ReadyForRemoval && item.total_lamports() == Some(0)
+        };
+
+        let previous_lamports = stake_pool.total_stake_lamports;
+        let mut total_stake_lamports = Self::get_reserve_lamports(reserve_stake_info)?;
+        let mut validators_removed = false;
+        {
+            let validator_list_data = validator_list_info.data.borrow();
+            let (_, validator_list) = ValidatorListHeader::deserialize_slice(&validator_list_data)?;
+            for validator_stake_record in validator_list {
+                if validator_stake_record.is_stale(clock.epoch) {
+                    return Err(StakePoolError::StakeListOutOfDate.into());
+                }
+                total_stake_lamports = validator_stake_record
+                    .total_lamports()
+                    .and_then(|lamports| total_stake_lamports.checked_add(lamports))
+                    .or_calculation_failure("total stake lamports")?;
+                validators_removed |= is_removable(validator_stake_record);
+            }
+        }
+
+        stake_pool.total_stake_lamports = total_stake_lamports;
+
+        let reward_lamports = total_stake_lamports.saturating_sub(previous_lamports);
+        let fee = stake_pool
+            .calc_fee_amount(reward_lamports)
+            .or_calculation_failure("epoch fee")?;
+
+        let (manager_fee, secondary_fee) = stake_pool
+            .split_epoch_fee(fee)
+            .or_calculation_failure("epoch fee split")?;
+        let fee_destinations = std::iter::once((manager_fee_info, manager_fee))
+            .chain(secondary_fee_info.map(|info| (info, secondary_fee)));
+        for (fee_info, pool_tokens) in fee_destinations {
+            if pool_tokens == 0 {
+                continue;
+            }
+            Self::token_mint_to(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                pool_tokens,
+            )?;
+            Self::log_fee_collected(
+                stake_pool_info.key,
+                FeeOperation::Epoch,
+                fee_info.key,
+                pool_tokens,
+            );
+        }
+        stake_pool.pool_token_supply = stake_pool
+            .pool_token_supply
+            .checked_add(fee)
+            .or_calculation_failure("pool token supply after the epoch fee")?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            stake_pool.epoch_fee = stake_pool.next_epoch_fee;
+            stake_pool.withdrawal_fee = stake_pool.next_withdrawal_fee;
+            stake_pool.epoch_withdrawn_lamports = 0;
+            stake_pool.epoch_deposited_lamports = 0;
+        }
+        stake_pool.last_update_epoch = clock.epoch;
+
+        Self::publish_price_oracle(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            account_info_iter,
+            clock,
+        )?;
+        Self::record_rate_history(program_id, stake_pool_info, &stake_pool, account_info_iter)?;
+
+        // Pool state is only written once every CPI has gone through
+        if validators_removed {
+            let mut validator_list_data = validator_list_info.data.borrow_mut();
+            let (_, mut validator_list) =
+                ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+            validator_list.retain(|item: &ValidatorStakeInfo| !is_removable(item))?;
+        }
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        StakePoolEvent::StakePoolBalanceUpdated(StakePoolBalanceUpdatedEvent {
+            stake_pool: *stake_pool_info.key,
+            epoch: clock.epoch,
+            total_stake_lamports: stake_pool.total_stake_lamports,
+            pool_token_supply: stake_pool.pool_token_supply,
+            reward_lamports,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Check stake activation status
+    #[allow(clippy::unnecessary_wraps)]
+    fn check_stake_activation(
+        _stake_info: &AccountInfo,
+        _clock: &Clock,
+        _stake_history_info: &AccountInfo,
+    ) -> ProgramResult {
+        // TODO: remove conditional compilation when time travel in tests is possible
+        //#[cfg(not(feature = "test-bpf"))]
+        // This check is commented to make tests run without special command line arguments
+        /*{
+            let stake_history = &StakeHistory::from_account_info(stake_history_info)?;
+            let stake_acc_state: stake_program::StakeState =
+                deserialize(&stake_info.data.borrow()).unwrap();
+            let delegation = stake_acc_state.delegation();
+            if let Some(delegation) = delegation {
+                let target_epoch = clock.epoch;
+                let history = Some(stake_history);
+                let fix_stake_deactivate = true;
+                let (effective, activating, deactivating) = delegation
+                    .stake_activating_and_deactivating(target_epoch, history, fix_stake_deactivate);
+                if activating != 0 || deactivating != 0 || effective == 0 {
+                    return Err(StakePoolError::UserStakeNotActive.into());
+                }
+            } else {
+                return Err(StakePoolError::WrongStakeState.into());
+            }
+        }*/
+        Ok(())
+    }
+
+    /// Processes [Deposit](enum.Instruction.html).
+    fn process_deposit(
+        program_id: &Pubkey,
+        minimum_pool_tokens_out: Option<u64>,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let deposit_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let stake_info = next_account_info(account_info_iter)?;
+        let validator_stake_account_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_not_decommissioning()?;
+
+        Self::check_stake_activation(stake_info, clock, stake_history_info)?;
+
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_stake_deposit_authority(deposit_info, program_id, stake_pool_info.key)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let (mut validator_list_item, lockup) = Self::check_deposited_stake(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            validator_list_info,
+            stake_info,
+            validator_stake_account_info,
+            clock,
+        )?;
+        let vote_account = validator_list_item.vote_account;
+
+        let stake_lamports = **stake_info.lamports.borrow();
+        stake_pool.check_deposit_cap(stake_lamports)?;
+        stake_pool.record_epoch_inflow(stake_lamports)?;
+        // Round down, in favor of the pool
+        let new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit_floor(stake_lamports)
+            .or_calculation_failure("pool tokens for the deposited stake")?;
+        if new_pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall.into());
+        }
+        let (user_pool_tokens, fee_pool_tokens) = stake_pool
+            .split_pool_tokens(FeeOperation::StakeDeposit, new_pool_tokens)
+            .or_calculation_failure("stake deposit fee")?;
+        if let Some(minimum_pool_tokens_out) = minimum_pool_tokens_out {
+            if user_pool_tokens < minimum_pool_tokens_out {
+                msg!(
+                    "Deposit would mint {} pool tokens, below the minimum of {}",
+                    user_pool_tokens,
+                    minimum_pool_tokens_out
+                );
+                return Err(StakePoolError::ExceededSlippage.into());
+            }
+        }
+        stake_pool.credit_deposit(stake_lamports, new_pool_tokens)?;
+
+        Self::merge_deposited_stake(
+            stake_pool_info,
+            &stake_pool,
+            stake_info,
+            &lockup,
+            deposit_info,
+            withdraw_info,
+            validator_stake_account_info,
+            clock_info,
+            stake_history_info,
+            stake_program_info,
+        )?;
+
+        Self::token_mint_to(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_user_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            user_pool_tokens,
+        )?;
+
+        if fee_pool_tokens > 0 {
+            Self::token_mint_to(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        Self::record_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            clock,
+        )?;
+
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        validator_list_item.active_stake_lamports =
+            (**validator_stake_account_info.lamports.borrow()).into();
+        ValidatorListHeader::update_entry(
+            &mut validator_list_info.data.borrow_mut(),
+            &validator_list_item,
+        )?;
+
+        Self::intercept_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            Some(vote_account),
+            stake_lamports,
+            user_pool_tokens,
+        )?;
+
+        StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account: Some(vote_account),
+            pool_tokens_destination: *dest_user_info.key,
+            lamports: stake_lamports,
+            pool_tokens: user_pool_tokens,
+            fee_pool_tokens,
+        })
+        .log();
+        Self::log_fee_collected(
+            stake_pool_info.key,
+            FeeOperation::StakeDeposit,
+            manager_fee_info.key,
+            fee_pool_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Processes [DepositMultiple](enum.Instruction.html).
+    fn process_deposit_multiple(
+        program_id: &Pubkey,
+        stake_account_count: u8,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let deposit_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_account_count == 0 {
+            msg!("No stake account to deposit");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_not_decommissioning()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_stake_deposit_authority(deposit_info, program_id, stake_pool_info.key)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let mut deposits = Vec::with_capacity(stake_account_count as usize);
+        let mut stake_lamports = 0u64;
+        for _ in 0..stake_account_count {
+            let stake_info = next_account_info(account_info_iter)?;
+            let validator_stake_account_info = next_account_info(account_info_iter)?;
+
+            Self::check_stake_activation(stake_info, clock, stake_history_info)?;
+            let (validator_list_item, lockup) = Self::check_deposited_stake(
+                program_id,
+                stake_pool_info,
+                &stake_pool,
+                validator_list_info,
+                stake_info,
+                validator_stake_account_info,
+                clock,
+            )?;
+            stake_lamports = stake_lamports
+                .checked_add(**stake_info.lamports.borrow())
+                .or_calculation_failure("total deposited stake lamports")?;
+            deposits.push((
+                stake_info,
+                validator_stake_account_info,
+                validator_list_item,
+                lockup,
+            ));
+        }
+
+        // The deposit event and interceptor only name a validator if every
+        // stake account went to the same one
+        let first_vote_account = deposits[0].2.vote_account;
+        let vote_account = if deposits.iter().all(|(_, _, validator_list_item, _)| {
+            validator_list_item.vote_account == first_vote_account
+        }) {
+            Some(first_vote_account)
+        } else {
+            None
+        };
+
+        stake_pool.check_deposit_cap(stake_lamports)?;
+        stake_pool.record_epoch_inflow(stake_lamports)?;
+        // Pool tokens are computed once on the total, rounding down in favor
+        // of the pool
+        let new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit_floor(stake_lamports)
+            .or_calculation_failure("pool tokens for the deposited stake")?;
+        if new_pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall.into());
+        }
+        let (user_pool_tokens, fee_pool_tokens) = stake_pool
+            .split_pool_tokens(FeeOperation::StakeDeposit, new_pool_tokens)
+            .or_calculation_failure("stake deposit fee")?;
+        stake_pool.credit_deposit(stake_lamports, new_pool_tokens)?;
+
+        for (stake_info, validator_stake_account_info, _, lockup) in deposits.iter() {
+            Self::merge_deposited_stake(
+                stake_pool_info,
+                &stake_pool,
+                stake_info,
+                lockup,
+                deposit_info,
+                withdraw_info,
+                validator_stake_account_info,
+                clock_info,
+                stake_history_info,
+                stake_program_info,
+            )?;
+        }
+
+        Self::token_mint_to(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_user_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            user_pool_tokens,
+        )?;
+
+        if fee_pool_tokens > 0 {
+            Self::token_mint_to(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        Self::record_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            clock,
+        )?;
+
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        for (_, validator_stake_account_info, validator_list_item, _) in deposits.iter_mut() {
+            validator_list_item.active_stake_lamports =
+                (**validator_stake_account_info.lamports.borrow()).into();
+            ValidatorListHeader::update_entry(
+                &mut validator_list_info.data.borrow_mut(),
+                validator_list_item,
+            )?;
+        }
+
+        Self::intercept_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            vote_account,
+            stake_lamports,
+            user_pool_tokens,
+        )?;
+
+        StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            pool_tokens_destination: *dest_user_info.key,
+            lamports: stake_lamports,
+            pool_tokens: user_pool_tokens,
+            fee_pool_tokens,
+        })
+        .log();
+        Self::log_fee_collected(
+            stake_pool_info.key,
+            FeeOperation::StakeDeposit,
+            manager_fee_info.key,
+            fee_pool_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Processes [Withdraw](enum.Instruction.html).
+    fn process_withdraw(
+        program_id: &Pubkey,
+        pool_tokens: u64,
+        minimum_lamports_out: Option<u64>,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let stake_split_from = next_account_info(account_info_iter)?;
+        let stake_split_to = next_account_info(account_info_iter)?;
+        let user_stake_authority = next_account_info(account_info_iter)?;
+        let burn_from_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let sweep_infos = match next_account_info(account_info_iter).ok() {
+            Some(reserve_stake_info) => {
+                stake_pool.check_reserve_stake(reserve_stake_info)?;
+                let transient_stake_info = next_account_info(account_info_iter)?;
+                let stake_history_info = next_account_info(account_info_iter)?;
+                Some((reserve_stake_info, transient_stake_info, stake_history_info))
+            }
+            None => None,
+        };
+
+        let withdrawing_from_reserve = *stake_split_from.key == stake_pool.reserve_stake;
+        let mut withdrawing_from_transient = false;
+        let vote_account = if withdrawing_from_reserve {
+            None
+        } else {
+            let vote_account = Self::get_validator(stake_split_from)?;
+            if Self::is_transient_stake_address(
+                &vote_account,
+                program_id,
+                stake_pool_info,
+                stake_split_from,
+            ) {
+                withdrawing_from_transient = true;
+            } else if !Self::is_validator_stake_address(
+                &vote_account,
+                program_id,
+                stake_pool_info,
+                stake_split_from,
+            ) {
+                return Err(StakePoolError::InvalidStakeAccountAddress.into());
+            }
+            Some(vote_account)
+        };
+
+        let (burn_pool_tokens, fee_pool_tokens) = stake_pool
+            .split_pool_tokens(FeeOperation::Withdrawal, pool_tokens)
+            .or_calculation_failure("withdrawal fee")?;
+        // Round down, in favor of the pool
+        let stake_lamports = stake_pool
+            .calc_lamports_withdraw_amount_floor(burn_pool_tokens)
+            .or_calculation_failure("lamports for the withdrawn pool tokens")?;
+        stake_pool.record_epoch_outflow(stake_lamports)?;
+        if let Some(minimum_lamports_out) = minimum_lamports_out {
+            if stake_lamports < minimum_lamports_out {
+                msg!(
+                    "Withdrawal would split {} lamports, below the minimum of {}",
+                    stake_lamports,
+                    minimum_lamports_out
+                );
+                return Err(StakePoolError::ExceededSlippage.into());
+            }
+        }
+
+        let validator_list_data = validator_list_info.data.borrow();
+        let (_, validator_list) = ValidatorListHeader::deserialize_slice(&validator_list_data)?;
+        let validator_list_item = match vote_account {
+            Some(vote_account) => Some(
+                *validator_list
+                    .find(&vote_account)
+                    .ok_or(StakePoolError::ValidatorNotFound)?,
+            ),
+            None => None,
+        };
+
+        // Removed validators are on their way back to the reserve, their
+        // deactivating stake can be withdrawn in any order
+        let withdrawing_from_removed = validator_list_item.map_or(false, |item| !item.is_active());
+
+        // Withdrawals drain the preferred validator, then the other validators
+        // down to the minimum they must keep, then transient stake, and the
+        // reserve last
+        let rent = Rent::get()?;
+        let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        let minimum_lamports = stake_program::minimum_stake_lamports(&rent);
+        if withdrawing_from_removed {
+            msg!("Withdrawing deactivating stake from a removed validator");
+        } else if withdrawing_from_reserve {
+            if let Some(item) = validator_list.iter().find(|item| {
+                item.is_active()
+                    && (u64::from(item.active_stake_lamports) > minimum_lamports
+                        || u64::from(item.transient_stake_lamports) > 0)
+            }) {
+                msg!(
+                    "Validator vote address {} still holds {} lamports and must be withdrawn from before the reserve",
+                    item.vote_account,
+                    item.total_lamports().unwrap_or(u64::MAX)
+                );
+                return Err(StakePoolError::StakeLamportsNotEqualToMinimum.into());
+            }
+        } else if withdrawing_from_transient {
+            if let Some(item) = validator_list.iter().find(|item| {
+                item.is_active() && u64::from(item.active_stake_lamports) > minimum_lamports
+            }) {
+                msg!(
+                    "Validator vote address {} still holds {} lamports of active stake and must be withdrawn from before transient stake",
+                    item.vote_account,
+                    u64::from(item.active_stake_lamports)
+                );
+                return Err(StakePoolError::StakeLamportsNotEqualToMinimum.into());
+            }
+        } else if stake_split_from.lamports().saturating_sub(stake_lamports) < minimum_lamports {
+            msg!(
+                "Need at least {} lamports to remain in the validator stake account, {} would remain",
+                minimum_lamports,
+                stake_split_from.lamports().saturating_sub(stake_lamports)
+            );
+            return Err(ProgramError::InsufficientFunds);
+        } else if stake_pool.proportional_withdrawals {
+            let vote_account = vote_account.unwrap();
+            let overweight_lamports = |item: &ValidatorStakeInfo| {
+                item.overweight_lamports(stake_pool.total_stake_lamports)
+                    .or_calculation_failure("validator lamports above target")
+            };
+            if let Some(most_overweight_item) = validator_list.most_overweight(
+                stake_pool.total_stake_lamports,
+                stake_lamports,
+                stake_rent,
+            ) {
+                let validator_item = validator_list
+                    .find(&vote_account)
+                    .ok_or(StakePoolError::ValidatorNotFound)?;
+                if overweight_lamports(validator_item)? < overweight_lamports(most_overweight_item)?
+                {
+                    msg!(
+                        "Validator vote address {} is the furthest above its target and must be withdrawn from first",
+                        most_overweight_item.vote_account
+                    );
+                    return Err(StakePoolError::IncorrectWithdrawVoteAddress.into());
+                }
+            }
+        } else if let Some(preferred_withdraw_validator) = stake_pool.preferred_withdraw_validator()
+        {
+            if Some(preferred_withdraw_validator) != vote_account {
+                let preferred_validator_item = validator_list
+                    .find(&preferred_withdraw_validator)
+                    .ok_or(StakePoolError::ValidatorNotFound)?;
+                if u64::from(preferred_validator_item.active_stake_lamports) > stake_lamports {
+                    msg!(
+                        "Validator vote address {} is preferred for withdrawals and holds {} lamports",
+                        preferred_withdraw_validator,
+                        u64::from(preferred_validator_item.active_stake_lamports)
+                    );
+                    return Err(StakePoolError::IncorrectWithdrawVoteAddress.into());
+                }
+            }
+        }
+        drop(validator_list_data);
+
+        // The user's stake account leaves the pool, so it only needs to be
+        // rent-exempt
+        let source_minimum = if withdrawing_from_reserve || withdrawing_from_removed {
+            stake_rent
+        } else {
+            minimum_lamports
+        };
+        Self::check_stake_split(
+            stake_split_from,
+            source_minimum,
+            stake_lamports,
+            stake_split_to,
+            stake_rent,
+        )?;
+
+        if let Some(validator_list_item) = &validator_list_item {
+            validator_list_item.check_up_to_date(clock.epoch)?;
+        }
+
+        Self::stake_split(
+            stake_pool_info.key,
+            stake_split_from.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            stake_lamports,
+            stake_split_to.clone(),
+        )?;
+
+        Self::stake_authorize(
+            stake_pool_info.key,
+            stake_split_to.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            user_stake_authority.key,
+            stake_program::StakeAuthorize::Withdrawer,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
+
+        Self::stake_authorize(
+            stake_pool_info.key,
+            stake_split_to.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            user_stake_authority.key,
+            stake_program::StakeAuthorize::Staker,
+            clock_info.clone(),
+            stake_program_info.clone(),
+        )?;
+
+        Self::token_burn(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            burn_from_info.clone(),
+            pool_mint_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            burn_pool_tokens,
+        )?;
+
+        if fee_pool_tokens > 0 {
+            Self::token_transfer(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                burn_from_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        let swept_vote_account = match sweep_infos {
+            Some((reserve_stake_info, transient_stake_info, stake_history_info)) => {
+                Self::sweep_transient_stake(
+                    program_id,
+                    stake_pool_info,
+                    &stake_pool,
+                    validator_list_info,
+                    reserve_stake_info,
+                    transient_stake_info,
+                    withdraw_info,
+                    clock_info,
+                    stake_history_info,
+                    stake_program_info,
+                    clock,
+                )?
+            }
+            None => None,
+        };
+
+        stake_pool.debit_withdrawal(stake_lamports, burn_pool_tokens)?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        if let Some(mut validator_list_item) = validator_list_item {
+            if withdrawing_from_transient {
+                validator_list_item.transient_stake_lamports = stake_split_from.lamports().into();
+            } else {
+                validator_list_item.active_stake_lamports = stake_split_from.lamports().into();
+            }
+            ValidatorListHeader::update_entry(
+                &mut validator_list_info.data.borrow_mut(),
+                &validator_list_item,
+            )?;
+        }
+        if let Some(swept_vote_account) = swept_vote_account {
+            // The swept lamports stay in the pool, they only move from the
+            // validator's entry to the reserve
+            let mut swept_item = ValidatorListHeader::find_entry(
+                &validator_list_info.data.borrow(),
+                &swept_vote_account,
+            )?
+            .ok_or(StakePoolError::ValidatorNotFound)?;
+            swept_item.transient_stake_lamports = 0.into();
+            if swept_item.status == StakeStatus::DeactivatingTransient {
+                swept_item.status = StakeStatus::ReadyForRemoval.into();
+            }
+            ValidatorListHeader::update_entry(
+                &mut validator_list_info.data.borrow_mut(),
+                &swept_item,
+            )?;
+        }
+
+        StakePoolEvent::Withdraw(WithdrawEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account,
+            pool_tokens_source: *burn_from_info.key,
+            lamports: stake_lamports,
+            pool_tokens: burn_pool_tokens,
+            fee_pool_tokens,
+        })
+        .log();
+        Self::log_fee_collected(
+            stake_pool_info.key,
+            FeeOperation::Withdrawal,
+            manager_fee_info.key,
+            fee_pool_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Processes [DepositSol](enum.Instruction.html).
+    fn process_deposit_sol(
+        program_id: &Pubkey,
+        lamports: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let lamports_from_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_not_decommissioning()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if !lamports_from_info.is_signer {
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        let sol_deposit_authority_info = if stake_pool.sol_deposit_authority().is_some() {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        stake_pool.check_sol_deposit_authority(sol_deposit_authority_info)?;
+
+        let sol_deposit_fee_treasury_info = if stake_pool.sol_deposit_fee_treasury().is_some() {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        stake_pool.check_sol_deposit_fee_treasury(sol_deposit_fee_treasury_info)?;
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        // A fee collected in lamports never enters the pool, and no pool
+        // tokens are minted for it
+        let (deposit_lamports, fee_lamports) = stake_pool
+            .split_sol_deposit_lamports(lamports)
+            .or_calculation_failure("SOL deposit fee lamports")?;
+        stake_pool.check_deposit_cap(deposit_lamports)?;
+        stake_pool.record_epoch_inflow(deposit_lamports)?;
+
+        // Round down, in favor of the pool
+        let new_pool_tokens = stake_pool
+            .calc_pool_tokens_for_deposit_floor(deposit_lamports)
+            .or_calculation_failure("pool tokens for the deposited lamports")?;
+        if new_pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall.into());
+        }
+        let (user_pool_tokens, fee_pool_tokens) = if sol_deposit_fee_treasury_info.is_some() {
+            (new_pool_tokens, 0)
+        } else {
+            stake_pool
+                .split_pool_tokens(FeeOperation::SolDeposit, new_pool_tokens)
+                .or_calculation_failure("SOL deposit fee")?
+        };
+        stake_pool.credit_deposit(deposit_lamports, new_pool_tokens)?;
+
+        invoke(
+            &system_instruction::transfer(
+                lamports_from_info.key,
+                reserve_stake_info.key,
+                deposit_lamports,
+            ),
+            &[
+                lamports_from_info.clone(),
+                reserve_stake_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+
+        if let Some(sol_deposit_fee_treasury_info) = sol_deposit_fee_treasury_info {
+            if fee_lamports > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        lamports_from_info.key,
+                        sol_deposit_fee_treasury_info.key,
+                        fee_lamports,
+                    ),
+                    &[
+                        lamports_from_info.clone(),
+                        sol_deposit_fee_treasury_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
+                StakePoolEvent::TreasuryFeeCollected(TreasuryFeeCollectedEvent {
+                    stake_pool: *stake_pool_info.key,
+                    operation: FeeOperation::SolDeposit,
+                    treasury: *sol_deposit_fee_treasury_info.key,
+                    lamports: fee_lamports,
+                })
+                .log();
+            }
+        }
+
+        Self::token_mint_to(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_user_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            user_pool_tokens,
+        )?;
+
+        if fee_pool_tokens > 0 {
+            Self::token_mint_to(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        Self::record_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            clock,
+        )?;
+
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        Self::intercept_deposit(
+            program_id,
+            stake_pool_info,
+            &stake_pool,
+            dest_user_info,
+            account_info_iter,
+            None,
+            deposit_lamports,
+            user_pool_tokens,
+        )?;
+
+        StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account: None,
+            pool_tokens_destination: *dest_user_info.key,
+            lamports: deposit_lamports,
+            pool_tokens: user_pool_tokens,
+            fee_pool_tokens,
+        })
+        .log();
+        Self::log_fee_collected(
+            stake_pool_info.key,
+            FeeOperation::SolDeposit,
+            manager_fee_info.key,
+            fee_pool_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Processes [WithdrawSol](enum.Instruction.html).
+    fn process_withdraw_sol(
+        program_id: &Pubkey,
+        pool_tokens: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let burn_from_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let lamports_to_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::get()?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_not_paused()?;
+        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
+        stake_pool.check_mint(pool_mint_info)?;
+        stake_pool.check_manager_fee_info(manager_fee_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        let (burn_pool_tokens, fee_pool_tokens) = stake_pool
+            .split_pool_tokens(FeeOperation::Withdrawal, pool_tokens)
+            .or_calculation_failure("withdrawal fee")?;
+        // Round down, in favor of the pool
+        let lamports = stake_pool
+            .calc_lamports_withdraw_amount_floor(burn_pool_tokens)
+            .or_calculation_failure("lamports for the withdrawn pool tokens")?;
+        stake_pool.record_epoch_outflow(lamports)?;
+
+        if lamports > Self::get_reserve_lamports(reserve_stake_info)? {
+            msg!("Reserve cannot cover withdrawal of {} lamports", lamports);
+            return Err(StakePoolError::SolWithdrawalTooLarge.into());
+        }
+
+        Self::token_burn(
+            stake_pool_info.key,
+            token_program_info.clone(),
+            burn_from_info.clone(),
+            pool_mint_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            burn_pool_tokens,
+        )?;
+
+        if fee_pool_tokens > 0 {
+            Self::token_transfer(
+                stake_pool_info.key,
+                token_program_info.clone(),
+                burn_from_info.clone(),
+                manager_fee_info.clone(),
+                withdraw_info.clone(),
+                AUTHORITY_WITHDRAW,
+                stake_pool.withdraw_bump_seed,
+                fee_pool_tokens,
+            )?;
+        }
+
+        Self::stake_withdraw(
+            stake_pool_info.key,
+            reserve_stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            stake_pool.withdraw_bump_seed,
+            lamports_to_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_program_info.clone(),
+            lamports,
+        )?;
+
+        stake_pool.debit_withdrawal(lamports, burn_pool_tokens)?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        StakePoolEvent::Withdraw(WithdrawEvent {
+            stake_pool: *stake_pool_info.key,
+            vote_account: None,
+            pool_tokens_source: *burn_from_info.key,
+            lamports,
+            pool_tokens: burn_pool_tokens,
+            fee_pool_tokens,
+        })
+        .log();
+        Self::log_fee_collected(
+            stake_pool_info.key,
+            FeeOperation::Withdrawal,
+            manager_fee_info.key,
+            fee_pool_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Processes [BurnPoolTokens](enum.Instruction.html).
+    fn process_burn_pool_tokens(
+        _program_id: &Pubkey,
+        pool_tokens: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let burn_from_info = next_account_info(account_info_iter)?;
+        let burn_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_mint(pool_mint_info)?;
+
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if !burn_authority_info.is_signer {
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        // The token program checks that the authority owns or was delegated
+        // the tokens
+        invoke(
+            &spl_token::instruction::burn(
+                token_program_info.key,
+                burn_from_info.key,
+                pool_mint_info.key,
+                burn_authority_info.key,
+                &[],
+                pool_tokens,
+            )?,
+            &[
+                burn_from_info.clone(),
+                pool_mint_info.clone(),
+                burn_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        stake_pool.pool_token_supply = stake_pool
+            .pool_token_supply
+            .checked_sub(pool_tokens)
+            .or_calculation_failure("pool token supply after the burn")?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+
+        StakePoolEvent::PoolTokensBurned(PoolTokensBurnedEvent {
+            stake_pool: *stake_pool_info.key,
+            pool_tokens_source: *burn_from_info.key,
+            pool_tokens,
+        })
+        .log();
+
+        Ok(())
+    }
+
+    /// Processes [SetFundingAuthority](enum.Instruction.html).
+    fn process_set_funding_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        funding_type: FundingType,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let new_funding_authority = next_account_info(account_info_iter)
+            .ok()
+            .map(|new_funding_authority_info| *new_funding_authority_info.key);
+
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        let funding_authority = match funding_type {
+            FundingType::StakeDeposit => new_funding_authority.unwrap_or_else(|| {
+                crate::find_deposit_authority_program_address(program_id, stake_pool_info.key).0
+            }),
+            FundingType::SolDeposit => new_funding_authority.unwrap_or_default(),
+        };
+
+        Self::request_manager_action(
+            stake_pool_info,
+            stake_pool,
+            ManagerAction::SetFundingAuthority(funding_type, funding_authority),
+        )
+    }
+
+    /// Processes [SetDepositLock](enum.Instruction.html).
+    fn process_set_deposit_lock(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        deposit_lock_epochs: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.deposit_lock_epochs = deposit_lock_epochs;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::DepositLock(deposit_lock_epochs),
+        );
+        Ok(())
+    }
+
+    /// Processes [ClaimLockedPoolTokens](enum.Instruction.html).
+    fn process_claim_locked_pool_tokens(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let deposit_record_info = next_account_info(account_info_iter)?;
+        let depositor_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let dest_user_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !depositor_info.is_signer {
+            msg!("Depositor signature missing");
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        let (deposit_record_address, bump_seed) =
+            crate::find_deposit_record_address(program_id, stake_pool_info.key, depositor_info.key);
+        if deposit_record_address != *deposit_record_info.key
+            || deposit_record_info.owner != program_id
+        {
+            return Err(StakePoolError::InvalidDepositRecord.into());
+        }
+        let deposit_record = DepositRecord::try_from_slice(&deposit_record_info.data.borrow())?;
+        if !deposit_record.is_valid() {
+            return Err(StakePoolError::InvalidDepositRecord.into());
+        }
+        let escrow = spl_token::state::Account::unpack(&escrow_info.data.borrow())?;
+        if escrow.owner != deposit_record_address {
+            return Err(StakePoolError::InvalidDepositEscrow.into());
+        }
+
+        // Disabling the lock releases earlier deposits as well
+        if stake_pool.deposit_lock_epochs > 0 && !deposit_record.is_unlocked(clock.epoch) {
+            msg!(
+                "Pool tokens of {} are locked until epoch {}",
+                depositor_info.key,
+                deposit_record.locked_until_epoch
+            );
+            return Err(StakePoolError::DepositLocked.into());
+        }
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            escrow_info.key,
+            dest_user_info.key,
+            deposit_record_info.key,
+            &[],
+            escrow.amount,
+        )?;
+        let deposit_record_signer_seeds: &[&[_]] = &[
+            DEPOSIT_RECORD_SEED,
+            &stake_pool_info.key.to_bytes()[..32],
+            &depositor_info.key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        invoke_signed(
+            &ix,
+            &[
+                escrow_info.clone(),
+                dest_user_info.clone(),
+                deposit_record_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[deposit_record_signer_seeds],
+        )
+    }
+
+    /// Processes [SetDepositCap](enum.Instruction.html).
+    fn process_set_deposit_cap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_total_lamports: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.max_total_lamports = max_total_lamports;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::DepositCap(max_total_lamports),
+        );
+        Ok(())
+    }
+
+    /// Processes [SetDelinquentValidatorEpochs](enum.Instruction.html).
+    fn process_set_delinquent_validator_epochs(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        delinquent_validator_epochs: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.delinquent_validator_epochs = delinquent_validator_epochs;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::DelinquentValidatorEpochs(delinquent_validator_epochs),
+        );
+        Ok(())
+    }
+
+    /// Processes [SetFeeSplit](enum.Instruction.html).
+    fn process_set_fee_split(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        split: Fee,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let secondary_fee_info = next_account_info(account_info_iter).ok();
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        match secondary_fee_info {
+            Some(secondary_fee_info) => {
+                if !split.is_valid() {
+                    return Err(StakePoolError::FeeTooHigh.into());
+                }
+                if *secondary_fee_info.owner != stake_pool.token_program_id {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                if stake_pool.pool_mint
+                    != spl_token::state::Account::unpack_from_slice(
+                        &secondary_fee_info.data.borrow(),
+                    )?
+                    .mint
+                {
+                    return Err(StakePoolError::WrongAccountMint.into());
+                }
+                stake_pool.secondary_fee_account = *secondary_fee_info.key;
+                stake_pool.secondary_fee_split = split;
+            }
+            None => {
+                stake_pool.secondary_fee_account = Pubkey::default();
+                stake_pool.secondary_fee_split = Fee::default();
+            }
+        }
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::FeeSplit {
+                secondary_fee_account: stake_pool.secondary_fee_account(),
+                split: stake_pool.secondary_fee_split,
+            },
+        );
+        Ok(())
+    }
+
+    /// Processes [SetSolDepositFeeTreasury](enum.Instruction.html).
+    fn process_set_sol_deposit_fee_treasury(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let sol_deposit_fee_treasury_info = next_account_info(account_info_iter).ok();
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.sol_deposit_fee_treasury = sol_deposit_fee_treasury_info
+            .map(|info| *info.key)
+            .unwrap_or_default();
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::SolDepositFeeTreasury(stake_pool.sol_deposit_fee_treasury()),
+        );
+        Ok(())
+    }
+
+    /// Processes [SetDepositInterceptor](enum.Instruction.html).
+    fn process_set_deposit_interceptor(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        compute_units: u32,
+        strict: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let deposit_interceptor_info = next_account_info(account_info_iter).ok();
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        match deposit_interceptor_info {
+            Some(deposit_interceptor_info) => {
+                if !deposit_interceptor_info.executable {
+                    msg!(
+                        "Deposit interceptor {} is not a program",
+                        deposit_interceptor_info.key
+                    );
+                    return Err(StakePoolError::InvalidDepositInterceptor.into());
+                }
+                stake_pool.deposit_interceptor = *deposit_interceptor_info.key;
+                stake_pool.deposit_interceptor_compute_units = compute_units;
+                stake_pool.deposit_interceptor_strict = strict;
+            }
+            None => {
+                stake_pool.deposit_interceptor = Pubkey::default();
+                stake_pool.deposit_interceptor_compute_units = 0;
+                stake_pool.deposit_interceptor_strict = false;
+            }
+        }
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::DepositInterceptor {
+                deposit_interceptor: stake_pool.deposit_interceptor(),
+                compute_units: stake_pool.deposit_interceptor_compute_units,
+                strict: stake_pool.deposit_interceptor_strict,
+            },
+        );
+        Ok(())
+    }
+
+    /// Processes [SetMaxEpochNetOutflow](enum.Instruction.html).
+    fn process_set_max_epoch_net_outflow(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_epoch_net_outflow_bps: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        if max_epoch_net_outflow_bps > MAX_EPOCH_NET_OUTFLOW_BPS {
+            msg!(
+                "Maximum net outflow of {} basis points is above {}",
+                max_epoch_net_outflow_bps,
+                MAX_EPOCH_NET_OUTFLOW_BPS
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        stake_pool.max_epoch_net_outflow_bps = max_epoch_net_outflow_bps;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::MaxEpochNetOutflow(max_epoch_net_outflow_bps),
+        );
+        Ok(())
+    }
+
+    /// Empties an account owned by the program and moves its lamports to
+    /// `lamports_to_info`, so that the account gets garbage collected,
+    /// returning the lamports moved
+    fn close_program_account(
+        account_info: &AccountInfo,
+        lamports_to_info: &AccountInfo,
+    ) -> Result<u64, ProgramError> {
+        for byte in account_info.data.borrow_mut().iter_mut() {
+            *byte = 0;
+        }
+        let lamports = account_info.lamports();
+        let destination_lamports = lamports_to_info
+            .lamports()
+            .checked_add(lamports)
+            .or_calculation_failure("lamports of the closed account's destination")?;
+        **lamports_to_info.lamports.borrow_mut() = destination_lamports;
+        **account_info.lamports.borrow_mut() = 0;
+        Ok(lamports)
+    }
+
+    /// Processes [IncreaseValidatorListSize](enum.Instruction.html).
+    fn process_increase_validator_list_size(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_validators: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let new_validator_list_info = next_account_info(account_info_iter)?;
+        let funder_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        let staker_signed = stake_pool.check_staker(authority_info);
+        let manager_signed = stake_pool.check_manager(authority_info);
+        if staker_signed.is_err() && manager_signed.is_err() {
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+        if validator_list_info.key == new_validator_list_info.key {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+        let validator_list =
+            ValidatorList::deserialize_checked(&validator_list_info.data.borrow())?;
+
+        if new_validator_list_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        ValidatorList::check_uninitialized(&new_validator_list_info.data.borrow())?;
+        let expected_max_validators =
+            ValidatorList::calculate_max_validators(new_validator_list_info.data_len());
+        if expected_max_validators != max_validators as usize
+            || max_validators <= validator_list.max_validators
+        {
+            msg!(
+                "New validator list must hold more than {} validators, and its size fits {} instead of {}",
+                validator_list.max_validators,
+                expected_max_validators,
+                max_validators
+            );
+            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+        }
+        if !Rent::get()?.is_exempt(
+            new_validator_list_info.lamports(),
+            new_validator_list_info.data_len(),
+        ) {
+            msg!("Validator stake list not rent-exempt");
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let new_validator_list = ValidatorList {
+            account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
+            max_validators,
+            validators: validator_list.validators,
+        };
+        new_validator_list.serialize(&mut *new_validator_list_info.data.borrow_mut())?;
+
+        Self::close_program_account(validator_list_info, funder_info)?;
+
+        stake_pool.validator_list = *new_validator_list_info.key;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::ValidatorList {
+                validator_list: stake_pool.validator_list,
+                max_validators,
+            },
+        );
+        Ok(())
+    }
+
+    /// Processes [SetValidatorTargets](enum.Instruction.html).
+    fn process_set_validator_targets(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        targets: Vec<ValidatorTarget>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_staker(staker_info)?;
+
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
+
+        let mut validator_list_data = validator_list_info.data.borrow_mut();
+        let (_, mut validator_list) =
+            ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+        let validator_list = validator_list.as_mut_slice::<ValidatorStakeInfo>()?;
+
+        for target in &targets {
+            let validator_list_item =
+                validator_list
+                    .find_mut(&target.vote_account)
+                    .ok_or_else(|| {
+                        msg!(
+                            "Validator vote address {} not found in the pool",
+                            target.vote_account
+                        );
+                        StakePoolError::ValidatorNotFound
+                    })?;
+            validator_list_item.target_weight_bps = target.weight_bps.into();
+        }
+
+        let total_target_weight_bps = validator_list.total_target_weight_bps();
+        if total_target_weight_bps > MAX_TARGET_WEIGHT_BPS as u64 {
+            msg!(
+                "Target weights add up to {} basis points, more than the maximum of {}",
+                total_target_weight_bps,
+                MAX_TARGET_WEIGHT_BPS
+            );
+            return Err(StakePoolError::InvalidTargetWeights.into());
+        }
+
+        Self::log_config_update(stake_pool_info.key, ConfigUpdate::ValidatorTargets(targets));
+        Ok(())
+    }
+
+    /// Processes [SetProportionalWithdrawals](enum.Instruction.html).
+    fn process_set_proportional_withdrawals(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        enabled: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.proportional_withdrawals = enabled;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::ProportionalWithdrawals(enabled),
+        );
+        Ok(())
+    }
+
+    /// Processes [SetPaused](enum.Instruction.html).
+    fn process_set_paused(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        paused: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
-        invoke(
-            &stake_program::delegate_stake(
-                &stake_account_info.key,
-                &staker_info.key,
-                &validator_info.key,
-            ),
-            &[
-                stake_account_info.clone(),
-                validator_info.clone(),
-                clock_info.clone(),
-                stake_history_info.clone(),
-                stake_config_info.clone(),
-                staker_info.clone(),
-            ],
-        )
+        stake_pool.check_manager(manager_info)?;
+
+        stake_pool.paused = paused;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(stake_pool_info.key, ConfigUpdate::Paused(paused));
+        Ok(())
     }
 
-    /// Processes `AddValidatorToPool` instruction.
-    fn process_add_validator_to_pool(
+    /// Processes [DecommissionStakePool](enum.Instruction.html).
+    fn process_decommission_stake_pool(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let staker_info = next_account_info(account_info_iter)?;
-        let deposit_info = next_account_info(account_info_iter)?;
-        let withdraw_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_not_decommissioning()?;
+
+        stake_pool.decommissioning = true;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(stake_pool_info.key, ConfigUpdate::Decommissioning);
+        Ok(())
+    }
+
+    /// Processes [CloseStakePool](enum.Instruction.html).
+    fn process_close_stake_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
         let validator_list_info = next_account_info(account_info_iter)?;
-        let stake_account_info = next_account_info(account_info_iter)?;
-        let dest_user_info = next_account_info(account_info_iter)?;
-        let pool_mint_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let reserve_stake_info = next_account_info(account_info_iter)?;
+        let lamports_to_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
+        let clock = &Clock::get()?;
         let stake_history_info = next_account_info(account_info_iter)?;
-        let stake_history = &StakeHistory::from_account_info(stake_history_info)?;
-        let token_program_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
 
         if *stake_program_info.key != stake_program::id() {
             return Err(ProgramError::IncorrectProgramId);
         }
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
+        stake_pool.check_manager(manager_info)?;
         stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
-        stake_pool.check_authority_deposit(deposit_info.key, program_id, stake_pool_info.key)?;
+        stake_pool.check_reserve_stake(reserve_stake_info)?;
 
-        stake_pool.check_staker(staker_info)?;
-        stake_pool.check_mint(pool_mint_info)?;
+        if !stake_pool.decommissioning {
+            return Err(StakePoolError::StakePoolNotDecommissioning.into());
+        }
 
+        // Removed validators only leave the list on the epoch's update
         if stake_pool.last_update_epoch < clock.epoch {
             return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
         }
 
-        if stake_pool.token_program_id != *token_program_info.key {
-            return Err(ProgramError::IncorrectProgramId);
-        }
-
         if *validator_list_info.key != stake_pool.validator_list {
             return Err(StakePoolError::InvalidValidatorStakeList.into());
         }
-
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
-        if validator_list.max_validators as usize == validator_list.validators.len() {
-            return Err(ProgramError::AccountDataTooSmall);
-        }
-
-        let vote_account =
-            Self::get_validator_checked(program_id, stake_pool_info, stake_account_info)?;
-
-        if validator_list.contains(&vote_account) {
-            return Err(StakePoolError::ValidatorAlreadyAdded.into());
+        if validator_list_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-
-        // Update Withdrawer and Staker authority to the program withdraw authority
-        for authority in &[
-            stake_program::StakeAuthorize::Withdrawer,
-            stake_program::StakeAuthorize::Staker,
-        ] {
-            Self::stake_authorize(
-                stake_pool_info.key,
-                stake_account_info.clone(),
-                deposit_info.clone(),
-                AUTHORITY_DEPOSIT,
-                stake_pool.deposit_bump_seed,
-                withdraw_info.key,
-                *authority,
-                clock_info.clone(),
-                stake_program_info.clone(),
-            )?;
+        let validator_count =
+            ValidatorListHeader::deserialize_slice(&validator_list_info.data.borrow())?
+                .1
+                .len();
+        if validator_count > 0 || stake_pool.pool_token_supply > 0 {
+            msg!(
+                "Stake pool still has {} validators and {} pool tokens",
+                validator_count,
+                stake_pool.pool_token_supply
+            );
+            return Err(StakePoolError::StakePoolNotEmpty.into());
         }
 
-        // Calculate and mint tokens
-        let stake_lamports = **stake_account_info.lamports.borrow();
-        let pool_tokens = stake_pool
-            .calc_pool_tokens_for_deposit(stake_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        Self::token_mint_to(
+        // Nobody holds a claim on the reserve anymore, rent-exempt reserve
+        // and leftover rewards included
+        let reserve_lamports = reserve_stake_info.lamports();
+        Self::stake_withdraw(
             stake_pool_info.key,
-            token_program_info.clone(),
-            pool_mint_info.clone(),
-            dest_user_info.clone(),
+            reserve_stake_info.clone(),
             withdraw_info.clone(),
             AUTHORITY_WITHDRAW,
             stake_pool.withdraw_bump_seed,
-            pool_tokens,
+            lamports_to_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_program_info.clone(),
+            reserve_lamports,
         )?;
 
-        // Check if stake is warmed up
-        Self::check_stake_activation(stake_account_info, clock, stake_history)?;
+        let validator_list_lamports =
+            Self::close_program_account(validator_list_info, lamports_to_info)?;
+        let stake_pool_lamports = Self::close_program_account(stake_pool_info, lamports_to_info)?;
+        let lamports = reserve_lamports
+            .checked_add(validator_list_lamports)
+            .and_then(|lamports| lamports.checked_add(stake_pool_lamports))
+            .or_calculation_failure("lamports recovered from the decommissioned pool")?;
+
+        StakePoolEvent::StakePoolClosed(StakePoolClosedEvent {
+            stake_pool: *stake_pool_info.key,
+            lamports_destination: *lamports_to_info.key,
+            lamports,
+        })
+        .log();
 
-        validator_list.validators.push(ValidatorStakeInfo {
-            vote_account,
-            stake_lamports,
-            last_update_epoch: clock.epoch,
-        });
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        Ok(())
+    }
 
-        stake_pool.pool_token_supply += pool_tokens;
-        stake_pool.total_stake_lamports += stake_lamports;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+    /// Processes [SetValidatorOnboardingEpochs](enum.Instruction.html).
+    fn process_set_validator_onboarding_epochs(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        validator_onboarding_epochs: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
 
+        stake_pool.validator_onboarding_epochs = validator_onboarding_epochs;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::ValidatorOnboardingEpochs(validator_onboarding_epochs),
+        );
         Ok(())
     }
 
-    /// Processes `RemoveValidatorFromPool` instruction.
-    fn process_remove_validator_from_pool(
+    /// Checks the accounts derived from a single-validator pool, returning the
+    /// pool and the bump seed of its withdraw authority
+    fn check_single_validator_pool(
+        program_id: &Pubkey,
+        single_validator_pool_info: &AccountInfo,
+        stake_account_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        withdraw_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+        stake_program_info: &AccountInfo,
+    ) -> Result<(SingleValidatorPool, u8), ProgramError> {
+        if single_validator_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let single_validator_pool =
+            SingleValidatorPool::deserialize_checked(&single_validator_pool_info.data.borrow())?;
+
+        let (stake_address, _) = crate::find_stake_program_address(
+            program_id,
+            &single_validator_pool.vote_account,
+            single_validator_pool_info.key,
+        );
+        if stake_address != *stake_account_info.key {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        }
+        let (pool_mint_address, _) = crate::find_single_validator_pool_mint_address(
+            program_id,
+            single_validator_pool_info.key,
+        );
+        if pool_mint_address != *pool_mint_info.key {
+            return Err(StakePoolError::WrongPoolMint.into());
+        }
+        let (withdraw_authority, withdraw_bump_seed) =
+            crate::find_withdraw_authority_program_address(
+                program_id,
+                single_validator_pool_info.key,
+            );
+        if withdraw_authority != *withdraw_info.key {
+            return Err(StakePoolError::InvalidProgramAddress.into());
+        }
+        Ok((single_validator_pool, withdraw_bump_seed))
+    }
+
+    /// Lamports backing the pool tokens of a single-validator pool, out of its
+    /// stake account
+    fn get_single_validator_pool_lamports(
+        stake_account_info: &AccountInfo,
+    ) -> Result<u64, ProgramError> {
+        let stake_state: stake_program::StakeState = deserialize(&stake_account_info.data.borrow())
+            .or(Err(ProgramError::InvalidAccountData))?;
+        let meta = stake_state.meta().ok_or(StakePoolError::WrongStakeState)?;
+        SingleValidatorPool::calc_pool_lamports(
+            stake_account_info.lamports(),
+            meta.rent_exempt_reserve,
+        )
+        .or_calculation_failure("pool lamports above the rent-exempt reserve and seed stake")
+    }
+
+    /// Processes [InitializeSingleValidatorPool](enum.Instruction.html).
+    fn process_initialize_single_validator_pool(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let stake_pool_info = next_account_info(account_info_iter)?;
-        let staker_info = next_account_info(account_info_iter)?;
-        let withdraw_info = next_account_info(account_info_iter)?;
-        let new_stake_authority_info = next_account_info(account_info_iter)?;
-        let validator_list_info = next_account_info(account_info_iter)?;
+        let single_validator_pool_info = next_account_info(account_info_iter)?;
+        let validator_vote_info = next_account_info(account_info_iter)?;
         let stake_account_info = next_account_info(account_info_iter)?;
-        let burn_from_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let funder_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(rent_info)?;
         let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let stake_config_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
 
-        if *stake_program_info.key != stake_program::id() {
+        if *system_program_info.key != solana_program::system_program::id() {
             return Err(ProgramError::IncorrectProgramId);
         }
-
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
         }
-
-        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
-        stake_pool.check_staker(staker_info)?;
-
-        if stake_pool.last_update_epoch < clock.epoch {
-            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        if *stake_program_info.key != stake_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
         }
-
-        if stake_pool.token_program_id != *token_program_info.key {
+        if *validator_vote_info.owner != vote_program::id() {
             return Err(ProgramError::IncorrectProgramId);
         }
-        stake_pool.check_mint(pool_mint_info)?;
+        let vote_account = *validator_vote_info.key;
 
-        if *validator_list_info.key != stake_pool.validator_list {
-            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        let (single_validator_pool_address, pool_bump_seed) =
+            crate::find_single_validator_pool_address(program_id, &vote_account);
+        if single_validator_pool_address != *single_validator_pool_info.key {
+            return Err(StakePoolError::InvalidProgramAddress.into());
         }
-
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        if !single_validator_pool_info.data_is_empty() {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+        let (stake_address, stake_bump_seed) = crate::find_stake_program_address(
+            program_id,
+            &vote_account,
+            single_validator_pool_info.key,
+        );
+        if stake_address != *stake_account_info.key {
+            return Err(StakePoolError::InvalidStakeAccountAddress.into());
+        }
+        let (pool_mint_address, mint_bump_seed) = crate::find_single_validator_pool_mint_address(
+            program_id,
+            single_validator_pool_info.key,
+        );
+        if pool_mint_address != *pool_mint_info.key {
+            return Err(StakePoolError::WrongPoolMint.into());
+        }
+        let (withdraw_authority, withdraw_bump_seed) =
+            crate::find_withdraw_authority_program_address(
+                program_id,
+                single_validator_pool_info.key,
+            );
+        if withdraw_authority != *withdraw_info.key {
+            return Err(StakePoolError::InvalidProgramAddress.into());
         }
 
-        let vote_account =
-            Self::get_validator_checked(program_id, stake_pool_info, stake_account_info)?;
+        let single_validator_pool = SingleValidatorPool {
+            account_type: AccountType::SingleValidatorPool,
+            vote_account,
+        };
+        // Every address is known in advance, so top up anything sent to them
+        // rather than failing to create the accounts
+        let pool_signer_seeds: &[&[_]] = &[
+            SINGLE_VALIDATOR_POOL_SEED,
+            &vote_account.to_bytes()[..32],
+            &[pool_bump_seed],
+        ];
+        Self::create_program_account(
+            program_id,
+            single_validator_pool_info,
+            funder_info,
+            system_program_info,
+            get_instance_packed_len(&single_validator_pool)?,
+            pool_signer_seeds,
+        )?;
 
-        if !validator_list.contains(&vote_account) {
-            return Err(StakePoolError::ValidatorNotFound.into());
-        }
+        let pool_mint_signer_seeds: &[&[_]] = &[
+            SINGLE_VALIDATOR_POOL_MINT_SEED,
+            &single_validator_pool_info.key.to_bytes()[..32],
+            &[mint_bump_seed],
+        ];
+        Self::create_program_account(
+            token_program_info.key,
+            pool_mint_info,
+            funder_info,
+            system_program_info,
+            Mint::LEN,
+            pool_mint_signer_seeds,
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_mint(
+                token_program_info.key,
+                pool_mint_info.key,
+                withdraw_info.key,
+                None,
+                spl_token::native_mint::DECIMALS,
+            )?,
+            &[
+                pool_mint_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
 
-        for authority in &[
-            stake_program::StakeAuthorize::Withdrawer,
-            stake_program::StakeAuthorize::Staker,
-        ] {
-            Self::stake_authorize(
-                stake_pool_info.key,
-                stake_account_info.clone(),
-                withdraw_info.clone(),
-                AUTHORITY_WITHDRAW,
-                stake_pool.withdraw_bump_seed,
-                new_stake_authority_info.key,
-                *authority,
-                clock_info.clone(),
-                stake_program_info.clone(),
+        // Fund the stake account with the minimum stake and rent, counting
+        // anything already sent to the address
+        let stake_space = std::mem::size_of::<stake_program::StakeState>();
+        let seed_lamports = rent
+            .minimum_balance(stake_space)
+            .saturating_add(MINIMUM_ACTIVE_STAKE)
+            .saturating_sub(stake_account_info.lamports());
+        if seed_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    funder_info.key,
+                    stake_account_info.key,
+                    seed_lamports,
+                ),
+                &[
+                    funder_info.clone(),
+                    stake_account_info.clone(),
+                    system_program_info.clone(),
+                ],
             )?;
         }
-
-        // Calculate and burn tokens
-        let stake_lamports = **stake_account_info.lamports.borrow();
-        let pool_tokens = stake_pool
-            .calc_pool_tokens_for_withdraw(stake_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-        Self::token_burn(
-            stake_pool_info.key,
-            token_program_info.clone(),
-            burn_from_info.clone(),
-            pool_mint_info.clone(),
+        let stake_account_signer_seeds: &[&[_]] = &[
+            &vote_account.to_bytes()[..32],
+            &single_validator_pool_info.key.to_bytes()[..32],
+            &[stake_bump_seed],
+        ];
+        Self::create_program_account(
+            &stake_program::id(),
+            stake_account_info,
+            funder_info,
+            system_program_info,
+            stake_space,
+            stake_account_signer_seeds,
+        )?;
+        invoke(
+            &stake_program::initialize(
+                stake_account_info.key,
+                &stake_program::Authorized {
+                    staker: withdraw_authority,
+                    withdrawer: withdraw_authority,
+                },
+                &stake_program::Lockup::default(),
+            ),
+            &[
+                stake_account_info.clone(),
+                rent_info.clone(),
+                stake_program_info.clone(),
+            ],
+        )?;
+        Self::stake_delegate(
+            single_validator_pool_info.key,
+            stake_account_info.clone(),
             withdraw_info.clone(),
             AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            pool_tokens,
+            withdraw_bump_seed,
+            validator_vote_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_config_info.clone(),
+            stake_program_info.clone(),
         )?;
 
-        validator_list
-            .validators
-            .retain(|item| item.vote_account != vote_account);
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        single_validator_pool.serialize(&mut *single_validator_pool_info.data.borrow_mut())?;
 
-        stake_pool.pool_token_supply -= pool_tokens;
-        stake_pool.total_stake_lamports -= stake_lamports;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        StakePoolEvent::SingleValidatorPoolInitialized(ValidatorEvent {
+            stake_pool: *single_validator_pool_info.key,
+            vote_account,
+            stake_account: *stake_account_info.key,
+        })
+        .log();
 
         Ok(())
     }
 
-    /// Processes `UpdateValidatorListBalance` instruction.
-    fn process_update_validator_list_balance(
-        _program_id: &Pubkey,
+    /// Processes [DepositSingleValidator](enum.Instruction.html).
+    fn process_deposit_single_validator(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let validator_list_info = next_account_info(account_info_iter)?;
+        let single_validator_pool_info = next_account_info(account_info_iter)?;
+        let stake_account_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let stake_info = next_account_info(account_info_iter)?;
+        let dest_user_pool_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
-        let validator_stake_accounts = account_info_iter.as_slice();
+        let stake_history_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
+
+        let (single_validator_pool, withdraw_bump_seed) = Self::check_single_validator_pool(
+            program_id,
+            single_validator_pool_info,
+            stake_account_info,
+            pool_mint_info,
+            withdraw_info,
+            token_program_info,
+            stake_program_info,
+        )?;
 
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        let pool_lamports = Self::get_single_validator_pool_lamports(stake_account_info)?;
+        let pool_token_supply = Mint::unpack_from_slice(&pool_mint_info.data.borrow())?.supply;
+        let stake_lamports = stake_info.lamports();
+        // Round down, in favor of the pool
+        let pool_tokens = SingleValidatorPool::calc_pool_tokens_for_deposit(
+            pool_lamports,
+            pool_token_supply,
+            stake_lamports,
+        )
+        .or_calculation_failure("pool tokens for the deposited stake")?;
+        if pool_tokens == 0 {
+            return Err(StakePoolError::DepositTooSmall.into());
         }
 
-        let vote_accounts: Vec<Option<Pubkey>> = validator_stake_accounts
-            .iter()
-            .map(|stake| Self::get_validator(stake).ok())
-            .collect();
+        // The stake program rejects stake delegated elsewhere or in another
+        // activation state
+        Self::stake_merge(
+            single_validator_pool_info.key,
+            stake_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            withdraw_bump_seed,
+            stake_account_info.clone(),
+            clock_info.clone(),
+            stake_history_info.clone(),
+            stake_program_info.clone(),
+        )?;
 
-        let mut changes = false;
-        // Do a brute iteration through the list, optimize if necessary
-        for validator_stake_record in &mut validator_list.validators {
-            if validator_stake_record.last_update_epoch >= clock.epoch {
-                continue;
-            }
-            for (validator_stake_account, vote_account) in
-                validator_stake_accounts.iter().zip(vote_accounts.iter())
-            {
-                if validator_stake_record.vote_account
-                    != vote_account.ok_or(StakePoolError::WrongStakeState)?
-                {
-                    continue;
-                }
-                validator_stake_record.last_update_epoch = clock.epoch;
-                validator_stake_record.stake_lamports = **validator_stake_account.lamports.borrow();
-                changes = true;
-            }
-        }
+        Self::token_mint_to(
+            single_validator_pool_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_user_pool_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            withdraw_bump_seed,
+            pool_tokens,
+        )?;
 
-        if changes {
-            validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
-        }
+        StakePoolEvent::Deposit(DepositEvent {
+            stake_pool: *single_validator_pool_info.key,
+            vote_account: Some(single_validator_pool.vote_account),
+            pool_tokens_destination: *dest_user_pool_info.key,
+            lamports: stake_lamports,
+            pool_tokens,
+            fee_pool_tokens: 0,
+        })
+        .log();
 
         Ok(())
     }
 
-    /// Processes `UpdateStakePoolBalance` instruction.
-    fn process_update_stake_pool_balance(
+    /// Processes [WithdrawSingleValidator](enum.Instruction.html).
+    fn process_withdraw_single_validator(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        pool_tokens: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let stake_pool_info = next_account_info(account_info_iter)?;
-        let validator_list_info = next_account_info(account_info_iter)?;
-        let withdraw_info = next_account_info(account_info_iter)?;
-        let manager_fee_info = next_account_info(account_info_iter)?;
+        let single_validator_pool_info = next_account_info(account_info_iter)?;
+        let stake_account_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
+        let withdraw_info = next_account_info(account_info_iter)?;
+        let stake_split_to = next_account_info(account_info_iter)?;
+        let user_stake_authority = next_account_info(account_info_iter)?;
+        let burn_from_info = next_account_info(account_info_iter)?;
         let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let stake_program_info = next_account_info(account_info_iter)?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
-        stake_pool.check_mint(pool_mint_info)?;
-        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
-        if stake_pool.manager_fee_account != *manager_fee_info.key {
-            return Err(StakePoolError::InvalidFeeAccount.into());
-        }
-
-        if *validator_list_info.key != stake_pool.validator_list {
-            return Err(StakePoolError::InvalidValidatorStakeList.into());
-        }
-        if stake_pool.token_program_id != *token_program_info.key {
-            return Err(ProgramError::IncorrectProgramId);
-        }
-
-        let validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
+        let (single_validator_pool, withdraw_bump_seed) = Self::check_single_validator_pool(
+            program_id,
+            single_validator_pool_info,
+            stake_account_info,
+            pool_mint_info,
+            withdraw_info,
+            token_program_info,
+            stake_program_info,
+        )?;
 
-        let previous_lamports = stake_pool.total_stake_lamports;
-        let mut total_stake_lamports = 0;
-        for validator_stake_record in validator_list.validators {
-            if validator_stake_record.last_update_epoch < clock.epoch {
-                return Err(StakePoolError::StakeListOutOfDate.into());
-            }
-            total_stake_lamports += validator_stake_record.stake_lamports;
-        }
+        let pool_lamports = Self::get_single_validator_pool_lamports(stake_account_info)?;
+        let pool_token_supply = Mint::unpack_from_slice(&pool_mint_info.data.borrow())?.supply;
+        // Round down, in favor of the pool
+        let stake_lamports = SingleValidatorPool::calc_lamports_for_withdraw(
+            pool_lamports,
+            pool_token_supply,
+            pool_tokens,
+        )
+        .or_calculation_failure("lamports for the withdrawn pool tokens")?;
 
-        stake_pool.total_stake_lamports = total_stake_lamports;
+        // The pool's stake account keeps at least its seed, since it does not
+        // back any pool tokens
+        let stake_rent =
+            Rent::get()?.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+        Self::check_stake_split(
+            stake_account_info,
+            stake_rent.saturating_add(MINIMUM_ACTIVE_STAKE),
+            stake_lamports,
+            stake_split_to,
+            stake_rent,
+        )?;
 
-        let reward_lamports = total_stake_lamports.saturating_sub(previous_lamports);
-        let fee = stake_pool
-            .calc_fee_amount(reward_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
+        Self::stake_split(
+            single_validator_pool_info.key,
+            stake_account_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            withdraw_bump_seed,
+            stake_lamports,
+            stake_split_to.clone(),
+        )?;
 
-        if fee > 0 {
-            Self::token_mint_to(
-                stake_pool_info.key,
-                token_program_info.clone(),
-                pool_mint_info.clone(),
-                manager_fee_info.clone(),
+        for authority in &[
+            stake_program::StakeAuthorize::Withdrawer,
+            stake_program::StakeAuthorize::Staker,
+        ] {
+            Self::stake_authorize(
+                single_validator_pool_info.key,
+                stake_split_to.clone(),
                 withdraw_info.clone(),
                 AUTHORITY_WITHDRAW,
-                stake_pool.withdraw_bump_seed,
-                fee,
+                withdraw_bump_seed,
+                user_stake_authority.key,
+                *authority,
+                clock_info.clone(),
+                stake_program_info.clone(),
             )?;
-
-            stake_pool.pool_token_supply = stake_pool
-                .pool_token_supply
-                .checked_add(fee)
-                .ok_or(StakePoolError::CalculationFailure)?;
         }
-        stake_pool.last_update_epoch = clock.epoch;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
 
-        Ok(())
-    }
+        Self::token_burn(
+            single_validator_pool_info.key,
+            token_program_info.clone(),
+            burn_from_info.clone(),
+            pool_mint_info.clone(),
+            withdraw_info.clone(),
+            AUTHORITY_WITHDRAW,
+            withdraw_bump_seed,
+            pool_tokens,
+        )?;
+
+        StakePoolEvent::Withdraw(WithdrawEvent {
+            stake_pool: *single_validator_pool_info.key,
+            vote_account: Some(single_validator_pool.vote_account),
+            pool_tokens_source: *burn_from_info.key,
+            lamports: stake_lamports,
+            pool_tokens,
+            fee_pool_tokens: 0,
+        })
+        .log();
 
-    /// Check stake activation status
-    #[allow(clippy::unnecessary_wraps)]
-    fn check_stake_activation(
-        _stake_info: &AccountInfo,
-        _clock: &Clock,
-        _stake_history: &StakeHistory,
-    ) -> ProgramResult {
-        // TODO: remove conditional compilation when time travel in tests is possible
-        //#[cfg(not(feature = "test-bpf"))]
-        // This check is commented to make tests run without special command line arguments
-        /*{
-            let stake_acc_state: stake_program::StakeState =
-                deserialize(&stake_info.data.borrow()).unwrap();
-            let delegation = stake_acc_state.delegation();
-            if let Some(delegation) = delegation {
-                let target_epoch = clock.epoch;
-                let history = Some(stake_history);
-                let fix_stake_deactivate = true;
-                let (effective, activating, deactivating) = delegation
-                    .stake_activating_and_deactivating(target_epoch, history, fix_stake_deactivate);
-                if activating != 0 || deactivating != 0 || effective == 0 {
-                    return Err(StakePoolError::UserStakeNotActive.into());
-                }
-            } else {
-                return Err(StakePoolError::WrongStakeState.into());
-            }
-        }*/
         Ok(())
     }
 
-    /// Processes [Deposit](enum.Instruction.html).
-    fn process_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    /// Processes [CreateTokenMetadata](enum.Instruction.html).
+    fn process_create_pool_token_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let validator_list_info = next_account_info(account_info_iter)?;
-        let deposit_info = next_account_info(account_info_iter)?;
-        let withdraw_info = next_account_info(account_info_iter)?;
-        let stake_info = next_account_info(account_info_iter)?;
-        let validator_stake_account_info = next_account_info(account_info_iter)?;
-        let dest_user_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
-        let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
-        let stake_history_info = next_account_info(account_info_iter)?;
-        let stake_history = &StakeHistory::from_account_info(stake_history_info)?;
-        let token_program_info = next_account_info(account_info_iter)?;
-        let stake_program_info = next_account_info(account_info_iter)?;
-
-        if *stake_program_info.key != stake_program::id() {
-            return Err(ProgramError::IncorrectProgramId);
-        }
-
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
-
-        Self::check_stake_activation(stake_info, clock, stake_history)?;
-
-        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
-        stake_pool.check_authority_deposit(deposit_info.key, program_id, stake_pool_info.key)?;
-        stake_pool.check_mint(pool_mint_info)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let metadata_info = next_account_info(account_info_iter)?;
+        let mpl_token_metadata_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
-        if stake_pool.token_program_id != *token_program_info.key {
+        if stake_pool_info.owner != program_id {
             return Err(ProgramError::IncorrectProgramId);
         }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
-        if *validator_list_info.key != stake_pool.validator_list {
-            return Err(StakePoolError::InvalidValidatorStakeList.into());
-        }
-
-        if stake_pool.last_update_epoch < clock.epoch {
-            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
-        }
-
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
-
-        let vote_account =
-            Self::get_validator_checked(program_id, stake_pool_info, validator_stake_account_info)?;
-
-        let validator_list_item = validator_list
-            .find_mut(&vote_account)
-            .ok_or(StakePoolError::ValidatorNotFound)?;
-
-        let stake_lamports = **stake_info.lamports.borrow();
-        let new_pool_tokens = stake_pool
-            .calc_pool_tokens_for_deposit(stake_lamports)
-            .ok_or(StakePoolError::CalculationFailure)?;
-
-        Self::stake_authorize(
-            stake_pool_info.key,
-            stake_info.clone(),
-            deposit_info.clone(),
-            AUTHORITY_DEPOSIT,
-            stake_pool.deposit_bump_seed,
-            withdraw_info.key,
-            stake_program::StakeAuthorize::Withdrawer,
-            clock_info.clone(),
-            stake_program_info.clone(),
-        )?;
-
-        Self::stake_authorize(
-            stake_pool_info.key,
-            stake_info.clone(),
-            deposit_info.clone(),
-            AUTHORITY_DEPOSIT,
-            stake_pool.deposit_bump_seed,
-            withdraw_info.key,
-            stake_program::StakeAuthorize::Staker,
-            clock_info.clone(),
-            stake_program_info.clone(),
-        )?;
-
-        Self::stake_merge(
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
             stake_pool_info.key,
-            stake_info.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            validator_stake_account_info.clone(),
-            clock_info.clone(),
-            stake_history_info.clone(),
-            stake_program_info.clone(),
         )?;
-
-        Self::token_mint_to(
-            stake_pool_info.key,
-            token_program_info.clone(),
-            pool_mint_info.clone(),
-            dest_user_info.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            new_pool_tokens,
+        stake_pool.check_mint(pool_mint_info)?;
+        Self::check_pool_token_metadata(
+            pool_mint_info.key,
+            metadata_info,
+            mpl_token_metadata_program_info,
         )?;
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
-        stake_pool.pool_token_supply += new_pool_tokens;
-        stake_pool.total_stake_lamports += stake_lamports;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        let me_bytes = stake_pool_info.key.to_bytes();
+        let authority_signature_seeds = [
+            &me_bytes[..32],
+            AUTHORITY_WITHDRAW,
+            &[stake_pool.withdraw_bump_seed],
+        ];
+        let signers = &[&authority_signature_seeds[..]];
 
-        validator_list_item.stake_lamports = **validator_stake_account_info.lamports.borrow();
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        let update = ConfigUpdate::TokenMetadata {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+        };
+        let ix = inline_mpl_token_metadata::instruction::create_metadata_accounts_v3(
+            *mpl_token_metadata_program_info.key,
+            *metadata_info.key,
+            *pool_mint_info.key,
+            *withdraw_authority_info.key,
+            *payer_info.key,
+            *withdraw_authority_info.key,
+            name,
+            symbol,
+            uri,
+        );
+        invoke_signed(
+            &ix,
+            &[
+                metadata_info.clone(),
+                pool_mint_info.clone(),
+                withdraw_authority_info.clone(),
+                payer_info.clone(),
+                system_program_info.clone(),
+                mpl_token_metadata_program_info.clone(),
+            ],
+            signers,
+        )?;
 
+        Self::log_config_update(stake_pool_info.key, update);
         Ok(())
     }
 
-    /// Processes [Withdraw](enum.Instruction.html).
-    fn process_withdraw(
+    /// Processes [UpdateTokenMetadata](enum.Instruction.html).
+    fn process_update_pool_token_metadata(
         program_id: &Pubkey,
-        pool_tokens: u64,
         accounts: &[AccountInfo],
+        name: String,
+        symbol: String,
+        uri: String,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let validator_list_info = next_account_info(account_info_iter)?;
-        let withdraw_info = next_account_info(account_info_iter)?;
-        let stake_split_from = next_account_info(account_info_iter)?;
-        let stake_split_to = next_account_info(account_info_iter)?;
-        let user_stake_authority = next_account_info(account_info_iter)?;
-        let burn_from_info = next_account_info(account_info_iter)?;
-        let pool_mint_info = next_account_info(account_info_iter)?;
-        let clock_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(clock_info)?;
-        let token_program_info = next_account_info(account_info_iter)?;
-        let stake_program_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let metadata_info = next_account_info(account_info_iter)?;
+        let mpl_token_metadata_program_info = next_account_info(account_info_iter)?;
 
-        if *stake_program_info.key != stake_program::id() {
+        if stake_pool_info.owner != program_id {
             return Err(ProgramError::IncorrectProgramId);
         }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
+            stake_pool_info.key,
+        )?;
+        Self::check_pool_token_metadata(
+            &stake_pool.pool_mint,
+            metadata_info,
+            mpl_token_metadata_program_info,
+        )?;
 
-        stake_pool.check_authority_withdraw(withdraw_info.key, program_id, stake_pool_info.key)?;
-        stake_pool.check_mint(pool_mint_info)?;
+        let me_bytes = stake_pool_info.key.to_bytes();
+        let authority_signature_seeds = [
+            &me_bytes[..32],
+            AUTHORITY_WITHDRAW,
+            &[stake_pool.withdraw_bump_seed],
+        ];
+        let signers = &[&authority_signature_seeds[..]];
 
-        if stake_pool.token_program_id != *token_program_info.key {
+        let update = ConfigUpdate::TokenMetadata {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+        };
+        let ix = inline_mpl_token_metadata::instruction::update_metadata_accounts_v2(
+            *mpl_token_metadata_program_info.key,
+            *metadata_info.key,
+            *withdraw_authority_info.key,
+            None,
+            Some(DataV2 {
+                name,
+                symbol,
+                uri,
+                ..DataV2::default()
+            }),
+            None,
+            Some(true),
+        );
+        invoke_signed(
+            &ix,
+            &[
+                metadata_info.clone(),
+                withdraw_authority_info.clone(),
+                mpl_token_metadata_program_info.clone(),
+            ],
+            signers,
+        )?;
+
+        Self::log_config_update(stake_pool_info.key, update);
+        Ok(())
+    }
+
+    /// Checks that the metadata program and account are the ones for the pool mint
+    fn check_pool_token_metadata(
+        pool_mint: &Pubkey,
+        metadata_info: &AccountInfo,
+        mpl_token_metadata_program_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        if *mpl_token_metadata_program_info.key != inline_mpl_token_metadata::id() {
             return Err(ProgramError::IncorrectProgramId);
         }
-
-        if *validator_list_info.key != stake_pool.validator_list {
-            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        let (metadata_address, _) = find_metadata_account(pool_mint);
+        if *metadata_info.key != metadata_address {
+            msg!(
+                "Incorrect metadata account for pool mint {}, expected {}, received {}",
+                pool_mint,
+                metadata_address,
+                metadata_info.key
+            );
+            return Err(StakePoolError::InvalidMetadataAccount.into());
         }
+        Ok(())
+    }
 
-        if stake_pool.last_update_epoch < clock.epoch {
-            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+    /// Logs a `FeeCollected` event, unless no fee was collected
+    fn log_fee_collected(
+        stake_pool: &Pubkey,
+        operation: FeeOperation,
+        manager_fee_account: &Pubkey,
+        pool_tokens: u64,
+    ) {
+        if pool_tokens > 0 {
+            StakePoolEvent::FeeCollected(FeeCollectedEvent {
+                stake_pool: *stake_pool,
+                operation,
+                manager_fee_account: *manager_fee_account,
+                pool_tokens,
+            })
+            .log();
         }
+    }
 
-        let mut validator_list =
-            try_from_slice_unchecked::<ValidatorList>(&validator_list_info.data.borrow())?;
-        if !validator_list.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
-        }
+    /// Logs a `ConfigUpdated` event
+    fn log_config_update(stake_pool: &Pubkey, update: ConfigUpdate) {
+        StakePoolEvent::ConfigUpdated(ConfigUpdatedEvent {
+            stake_pool: *stake_pool,
+            update,
+        })
+        .log();
+    }
 
-        let vote_account =
-            Self::get_validator_checked(program_id, stake_pool_info, stake_split_from)?;
+    /// Processes [SetFee](enum.Instruction.html).
+    fn process_set_fee(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee: FeeType,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
 
-        let validator_list_item = validator_list
-            .find_mut(&vote_account)
-            .ok_or(StakePoolError::ValidatorNotFound)?;
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
-        let stake_lamports = stake_pool
-            .calc_lamports_withdraw_amount(pool_tokens)
-            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.check_manager(manager_info)?;
 
-        Self::stake_split(
-            stake_pool_info.key,
-            stake_split_from.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            stake_lamports,
-            stake_split_to.clone(),
-        )?;
+        Self::request_manager_action(stake_pool_info, stake_pool, ManagerAction::SetFee(fee))
+    }
 
-        Self::stake_authorize(
-            stake_pool_info.key,
-            stake_split_to.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            user_stake_authority.key,
-            stake_program::StakeAuthorize::Withdrawer,
-            clock_info.clone(),
-            stake_program_info.clone(),
-        )?;
+    /// Processes [SetPreferredValidator](enum.Instruction.html).
+    fn process_set_preferred_validator(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        validator_type: PreferredValidatorType,
+        validator_vote_address: Option<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
 
-        Self::stake_authorize(
-            stake_pool_info.key,
-            stake_split_to.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            user_stake_authority.key,
-            stake_program::StakeAuthorize::Staker,
-            clock_info.clone(),
-            stake_program_info.clone(),
-        )?;
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
-        Self::token_burn(
-            stake_pool_info.key,
-            token_program_info.clone(),
-            burn_from_info.clone(),
-            pool_mint_info.clone(),
-            withdraw_info.clone(),
-            AUTHORITY_WITHDRAW,
-            stake_pool.withdraw_bump_seed,
-            pool_tokens,
-        )?;
+        stake_pool.check_staker(staker_info)?;
 
-        stake_pool.pool_token_supply -= pool_tokens;
-        stake_pool.total_stake_lamports -= stake_lamports;
-        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        if *validator_list_info.key != stake_pool.validator_list {
+            return Err(StakePoolError::InvalidValidatorStakeList.into());
+        }
 
-        validator_list_item.stake_lamports = **stake_split_from.lamports.borrow();
-        validator_list.serialize(&mut *validator_list_info.data.borrow_mut())?;
+        if let Some(vote_address) = validator_vote_address {
+            match ValidatorListHeader::find_entry(
+                &validator_list_info.data.borrow(),
+                &vote_address,
+            )? {
+                None => {
+                    msg!(
+                        "Validator vote address {} not found in the pool",
+                        vote_address
+                    );
+                    return Err(StakePoolError::ValidatorNotFound.into());
+                }
+                Some(validator_stake_record) if !validator_stake_record.is_active() => {
+                    return Err(StakePoolError::ValidatorNotActive.into());
+                }
+                _ => {}
+            }
+        }
+
+        let vote_address = validator_vote_address.unwrap_or_default();
+        match validator_type {
+            PreferredValidatorType::Deposit => {
+                stake_pool.preferred_deposit_validator_vote_address = vote_address
+            }
+            PreferredValidatorType::Withdraw => {
+                stake_pool.preferred_withdraw_validator_vote_address = vote_address
+            }
+        }
 
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::PreferredValidator(validator_type, validator_vote_address),
+        );
         Ok(())
     }
 
     /// Processes [SetManager](enum.Instruction.html).
-    fn process_set_manager(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    fn process_set_manager(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
         let manager_info = next_account_info(account_info_iter)?;
         let new_manager_info = next_account_info(account_info_iter)?;
         let new_manager_fee_info = next_account_info(account_info_iter)?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
+        let stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
         stake_pool.check_manager(manager_info)?;
 
+        if *new_manager_fee_info.owner != stake_pool.token_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
         if stake_pool.pool_mint
             != spl_token::state::Account::unpack_from_slice(&new_manager_fee_info.data.borrow())?
                 .mint
@@ -1062,23 +5032,151 @@ impl Processor {
             return Err(StakePoolError::WrongAccountMint.into());
         }
 
-        stake_pool.manager = *new_manager_info.key;
-        stake_pool.manager_fee_account = *new_manager_fee_info.key;
+        Self::request_manager_action(
+            stake_pool_info,
+            stake_pool,
+            ManagerAction::SetManager {
+                manager: *new_manager_info.key,
+                manager_fee_account: *new_manager_fee_info.key,
+            },
+        )
+    }
+
+    /// Applies a manager action right away, or records it to wait for the
+    /// pool's timelock.  Either way, the action must apply to the pool as it
+    /// is now.
+    fn request_manager_action(
+        stake_pool_info: &AccountInfo,
+        mut stake_pool: StakePool,
+        action: ManagerAction,
+    ) -> ProgramResult {
+        if stake_pool.manager_timelock().is_none() {
+            stake_pool.apply_manager_action(&action)?;
+            stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+            Self::log_config_update(stake_pool_info.key, Self::manager_action_update(&action));
+            return Ok(());
+        }
+
+        stake_pool.clone().apply_manager_action(&action)?;
+        let executable_epoch = stake_pool.queue_manager_action(&action, Clock::get()?.epoch)?;
         stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::ManagerActionQueued {
+                action,
+                executable_epoch,
+            },
+        );
         Ok(())
     }
 
-    /// Processes [SetManager](enum.Instruction.html).
-    fn process_set_staker(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    /// Config update logged once a manager action is applied
+    fn manager_action_update(action: &ManagerAction) -> ConfigUpdate {
+        match *action {
+            ManagerAction::SetFee(fee) => ConfigUpdate::Fee(fee),
+            ManagerAction::SetManager {
+                manager,
+                manager_fee_account,
+            } => ConfigUpdate::Manager {
+                manager,
+                manager_fee_account,
+            },
+            ManagerAction::SetFundingAuthority(funding_type, funding_authority) => {
+                ConfigUpdate::FundingAuthority(funding_type, funding_authority)
+            }
+            ManagerAction::SetManagerTimelock(manager_timelock_epochs) => {
+                ConfigUpdate::ManagerTimelock(manager_timelock_epochs)
+            }
+        }
+    }
+
+    /// Processes [SetManagerTimelock](enum.Instruction.html).
+    fn process_set_manager_timelock(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        manager_timelock_epochs: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        let action = ManagerAction::SetManagerTimelock(manager_timelock_epochs);
+        // a longer timelock only protects holders more, so it needs no wait
+        if manager_timelock_epochs >= stake_pool.manager_timelock_epochs {
+            stake_pool.apply_manager_action(&action)?;
+            stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+            Self::log_config_update(stake_pool_info.key, Self::manager_action_update(&action));
+            return Ok(());
+        }
+        Self::request_manager_action(stake_pool_info, stake_pool, action)
+    }
+
+    /// Processes [ExecuteManagerAction](enum.Instruction.html).
+    fn process_execute_manager_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        let action = stake_pool.take_executable_manager_action(Clock::get()?.epoch)?;
+        stake_pool.apply_manager_action(&action)?;
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(stake_pool_info.key, Self::manager_action_update(&action));
+        Ok(())
+    }
+
+    /// Processes [CancelManagerAction](enum.Instruction.html).
+    fn process_cancel_manager_action(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
+
+        stake_pool.check_manager(manager_info)?;
+
+        let (action, _) = stake_pool
+            .pending_manager_action()
+            .ok_or(StakePoolError::NoPendingManagerAction)?;
+        stake_pool.pending_manager_action = PendingManagerAction::default();
+        stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(
+            stake_pool_info.key,
+            ConfigUpdate::ManagerActionCancelled(action),
+        );
+        Ok(())
+    }
+
+    /// Processes [SetStaker](enum.Instruction.html).
+    fn process_set_staker(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
         let set_staker_authority_info = next_account_info(account_info_iter)?;
         let new_staker_info = next_account_info(account_info_iter)?;
 
-        let mut stake_pool = StakePool::try_from_slice(&stake_pool_info.data.borrow())?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        if stake_pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
+        let mut stake_pool = StakePool::deserialize_checked(&stake_pool_info.data.borrow())?;
 
         let staker_signed = stake_pool.check_staker(set_staker_authority_info);
         let manager_signed = stake_pool.check_manager(set_staker_authority_info);
@@ -1087,6 +5185,7 @@ impl Processor {
         }
         stake_pool.staker = *new_staker_info.key;
         stake_pool.serialize(&mut *stake_pool_info.data.borrow_mut())?;
+        Self::log_config_update(stake_pool_info.key, ConfigUpdate::Staker(stake_pool.staker));
         Ok(())
     }
 
@@ -1111,19 +5210,35 @@ impl Processor {
             }
             StakePoolInstruction::RemoveValidatorFromPool => {
                 msg!("Instruction: RemoveValidatorFromPool");
-                Self::process_remove_validator_from_pool(program_id, accounts)
+                Self::process_remove_validator_from_pool(program_id, accounts, false)
             }
-            StakePoolInstruction::DecreaseValidatorStake(_amount) => {
+            StakePoolInstruction::DecreaseValidatorStake(lamports) => {
                 msg!("Instruction: DecreaseValidatorStake");
-                Ok(())
+                Self::process_decrease_validator_stake(
+                    program_id,
+                    accounts,
+                    RebalanceAmount::Staker(lamports),
+                )
             }
-            StakePoolInstruction::IncreaseValidatorStake(_amount) => {
+            StakePoolInstruction::IncreaseValidatorStake(lamports) => {
                 msg!("Instruction: IncreaseValidatorStake");
-                Ok(())
+                Self::process_increase_validator_stake(
+                    program_id,
+                    accounts,
+                    RebalanceAmount::Staker(lamports),
+                )
             }
-            StakePoolInstruction::UpdateValidatorListBalance => {
+            StakePoolInstruction::UpdateValidatorListBalance {
+                start_index,
+                no_merge,
+            } => {
                 msg!("Instruction: UpdateValidatorListBalance");
-                Self::process_update_validator_list_balance(program_id, accounts)
+                Self::process_update_validator_list_balance(
+                    program_id,
+                    accounts,
+                    start_index,
+                    no_merge,
+                )
             }
             StakePoolInstruction::UpdateStakePoolBalance => {
                 msg!("Instruction: UpdateStakePoolBalance");
@@ -1131,11 +5246,11 @@ impl Processor {
             }
             StakePoolInstruction::Deposit => {
                 msg!("Instruction: Deposit");
-                Self::process_deposit(program_id, accounts)
+                Self::process_deposit(program_id, None, accounts)
             }
             StakePoolInstruction::Withdraw(amount) => {
                 msg!("Instruction: Withdraw");
-                Self::process_withdraw(program_id, amount, accounts)
+                Self::process_withdraw(program_id, amount, None, accounts)
             }
             StakePoolInstruction::SetManager => {
                 msg!("Instruction: SetManager");
@@ -1145,6 +5260,183 @@ impl Processor {
                 msg!("Instruction: SetStaker");
                 Self::process_set_staker(program_id, accounts)
             }
+            StakePoolInstruction::DepositSol(lamports) => {
+                msg!("Instruction: DepositSol");
+                Self::process_deposit_sol(program_id, lamports, accounts)
+            }
+            StakePoolInstruction::WithdrawSol(pool_tokens) => {
+                msg!("Instruction: WithdrawSol");
+                Self::process_withdraw_sol(program_id, pool_tokens, accounts)
+            }
+            StakePoolInstruction::SetFee { fee } => {
+                msg!("Instruction: SetFee");
+                Self::process_set_fee(program_id, accounts, fee)
+            }
+            StakePoolInstruction::SetPreferredValidator {
+                validator_type,
+                validator_vote_address,
+            } => {
+                msg!("Instruction: SetPreferredValidator");
+                Self::process_set_preferred_validator(
+                    program_id,
+                    accounts,
+                    validator_type,
+                    validator_vote_address,
+                )
+            }
+            StakePoolInstruction::BurnPoolTokens(pool_tokens) => {
+                msg!("Instruction: BurnPoolTokens");
+                Self::process_burn_pool_tokens(program_id, pool_tokens, accounts)
+            }
+            StakePoolInstruction::SetFundingAuthority(funding_type) => {
+                msg!("Instruction: SetFundingAuthority");
+                Self::process_set_funding_authority(program_id, accounts, funding_type)
+            }
+            StakePoolInstruction::SetDepositLock(deposit_lock_epochs) => {
+                msg!("Instruction: SetDepositLock");
+                Self::process_set_deposit_lock(program_id, accounts, deposit_lock_epochs)
+            }
+            StakePoolInstruction::SetValidatorTargets(targets) => {
+                msg!("Instruction: SetValidatorTargets");
+                Self::process_set_validator_targets(program_id, accounts, targets)
+            }
+            StakePoolInstruction::SetProportionalWithdrawals(enabled) => {
+                msg!("Instruction: SetProportionalWithdrawals");
+                Self::process_set_proportional_withdrawals(program_id, accounts, enabled)
+            }
+            StakePoolInstruction::DepositWithSlippage {
+                minimum_pool_tokens_out,
+            } => {
+                msg!("Instruction: DepositWithSlippage");
+                Self::process_deposit(program_id, Some(minimum_pool_tokens_out), accounts)
+            }
+            StakePoolInstruction::WithdrawWithSlippage {
+                pool_tokens_in,
+                minimum_lamports_out,
+            } => {
+                msg!("Instruction: WithdrawWithSlippage");
+                Self::process_withdraw(
+                    program_id,
+                    pool_tokens_in,
+                    Some(minimum_lamports_out),
+                    accounts,
+                )
+            }
+            StakePoolInstruction::CreateTokenMetadata { name, symbol, uri } => {
+                msg!("Instruction: CreateTokenMetadata");
+                Self::process_create_pool_token_metadata(program_id, accounts, name, symbol, uri)
+            }
+            StakePoolInstruction::UpdateTokenMetadata { name, symbol, uri } => {
+                msg!("Instruction: UpdateTokenMetadata");
+                Self::process_update_pool_token_metadata(program_id, accounts, name, symbol, uri)
+            }
+            StakePoolInstruction::SetDepositCap(max_total_lamports) => {
+                msg!("Instruction: SetDepositCap");
+                Self::process_set_deposit_cap(program_id, accounts, max_total_lamports)
+            }
+            StakePoolInstruction::IncreaseValidatorListSize(max_validators) => {
+                msg!("Instruction: IncreaseValidatorListSize");
+                Self::process_increase_validator_list_size(program_id, accounts, max_validators)
+            }
+            StakePoolInstruction::SetDelinquentValidatorEpochs(delinquent_validator_epochs) => {
+                msg!("Instruction: SetDelinquentValidatorEpochs");
+                Self::process_set_delinquent_validator_epochs(
+                    program_id,
+                    accounts,
+                    delinquent_validator_epochs,
+                )
+            }
+            StakePoolInstruction::DecreaseDelinquentValidatorStake => {
+                msg!("Instruction: DecreaseDelinquentValidatorStake");
+                Self::process_decrease_validator_stake(
+                    program_id,
+                    accounts,
+                    RebalanceAmount::Delinquent,
+                )
+            }
+            StakePoolInstruction::SetFeeSplit(split) => {
+                msg!("Instruction: SetFeeSplit");
+                Self::process_set_fee_split(program_id, accounts, split)
+            }
+            StakePoolInstruction::SetSolDepositFeeTreasury => {
+                msg!("Instruction: SetSolDepositFeeTreasury");
+                Self::process_set_sol_deposit_fee_treasury(program_id, accounts)
+            }
+            StakePoolInstruction::SetDepositInterceptor {
+                compute_units,
+                strict,
+            } => {
+                msg!("Instruction: SetDepositInterceptor");
+                Self::process_set_deposit_interceptor(program_id, accounts, compute_units, strict)
+            }
+            StakePoolInstruction::SetMaxEpochNetOutflow(max_epoch_net_outflow_bps) => {
+                msg!("Instruction: SetMaxEpochNetOutflow");
+                Self::process_set_max_epoch_net_outflow(
+                    program_id,
+                    accounts,
+                    max_epoch_net_outflow_bps,
+                )
+            }
+            StakePoolInstruction::SetPaused(paused) => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(program_id, accounts, paused)
+            }
+            StakePoolInstruction::ApplyTargets => {
+                msg!("Instruction: ApplyTargets");
+                Self::process_apply_targets(program_id, accounts)
+            }
+            StakePoolInstruction::DepositMultiple(stake_account_count) => {
+                msg!("Instruction: DepositMultiple");
+                Self::process_deposit_multiple(program_id, stake_account_count, accounts)
+            }
+            StakePoolInstruction::DecommissionStakePool => {
+                msg!("Instruction: DecommissionStakePool");
+                Self::process_decommission_stake_pool(program_id, accounts)
+            }
+            StakePoolInstruction::CloseStakePool => {
+                msg!("Instruction: CloseStakePool");
+                Self::process_close_stake_pool(program_id, accounts)
+            }
+            StakePoolInstruction::SetValidatorOnboardingEpochs(validator_onboarding_epochs) => {
+                msg!("Instruction: SetValidatorOnboardingEpochs");
+                Self::process_set_validator_onboarding_epochs(
+                    program_id,
+                    accounts,
+                    validator_onboarding_epochs,
+                )
+            }
+            StakePoolInstruction::InitializeSingleValidatorPool => {
+                msg!("Instruction: InitializeSingleValidatorPool");
+                Self::process_initialize_single_validator_pool(program_id, accounts)
+            }
+            StakePoolInstruction::DepositSingleValidator => {
+                msg!("Instruction: DepositSingleValidator");
+                Self::process_deposit_single_validator(program_id, accounts)
+            }
+            StakePoolInstruction::WithdrawSingleValidator(pool_tokens) => {
+                msg!("Instruction: WithdrawSingleValidator");
+                Self::process_withdraw_single_validator(program_id, accounts, pool_tokens)
+            }
+            StakePoolInstruction::SetManagerTimelock(manager_timelock_epochs) => {
+                msg!("Instruction: SetManagerTimelock");
+                Self::process_set_manager_timelock(program_id, accounts, manager_timelock_epochs)
+            }
+            StakePoolInstruction::ExecuteManagerAction => {
+                msg!("Instruction: ExecuteManagerAction");
+                Self::process_execute_manager_action(program_id, accounts)
+            }
+            StakePoolInstruction::CancelManagerAction => {
+                msg!("Instruction: CancelManagerAction");
+                Self::process_cancel_manager_action(program_id, accounts)
+            }
+            StakePoolInstruction::RemoveValidatorByVoteWithdrawer => {
+                msg!("Instruction: RemoveValidatorByVoteWithdrawer");
+                Self::process_remove_validator_from_pool(program_id, accounts, true)
+            }
+            StakePoolInstruction::ClaimLockedPoolTokens => {
+                msg!("Instruction: ClaimLockedPoolTokens");
+                Self::process_claim_locked_pool_tokens(program_id, accounts)
+            }
         }
     }
 }
@@ -1180,6 +5472,48 @@ impl PrintProgramError for StakePoolError {
             StakePoolError::UnexpectedValidatorListAccountSize=> msg!("Error: The size of the given validator stake list does match the expected amount"),
             StakePoolError::WrongStaker=> msg!("Error: Wrong pool staker account"),
             StakePoolError::NonZeroPoolTokenSupply => msg!("Error: Pool token supply is not zero on initialization"),
+            StakePoolError::InvalidReserveStakeAccount => msg!("Error: Provided reserve stake account does not match the stake pool's reserve"),
+            StakePoolError::SolWithdrawalTooLarge => msg!("Error: Not enough lamports in the reserve to cover the SOL withdrawal"),
+            StakePoolError::FeeIncreaseTooHigh => msg!("Error: Withdrawal fee increased too much in one epoch"),
+            StakePoolError::IncorrectDepositVoteAddress => msg!("Error: Deposited stake is delegated to the wrong vote account"),
+            StakePoolError::IncorrectWithdrawVoteAddress => msg!("Error: Withdrawal must come from the preferred withdraw validator"),
+            StakePoolError::InvalidStakeDepositAuthority => msg!("Error: Provided stake deposit authority does not match the pool's"),
+            StakePoolError::InvalidSolDepositAuthority => msg!("Error: Provided SOL deposit authority does not match the pool's"),
+            StakePoolError::TransientAccountInUse => msg!("Error: Transient stake account is already in use"),
+            StakePoolError::DepositLocked => msg!("Error: Pool tokens are locked until the deposit lock expires"),
+            StakePoolError::InvalidDepositRecord => msg!("Error: Invalid deposit record account"),
+            StakePoolError::ValidatorNotActive => msg!("Error: Validator is being removed from the pool"),
+            StakePoolError::InvalidTargetWeights => msg!("Error: Validator target weights add up to more than 100%"),
+            StakePoolError::StakeLamportsNotEqualToMinimum => msg!("Error: The reserve can only be withdrawn from once every validator holds the minimum stake"),
+            StakePoolError::ExceededSlippage => msg!("Error: Deposit or withdrawal amount is below the requested minimum"),
+            StakePoolError::InvalidMetadataAccount => msg!("Error: Metadata account does not match the pool mint's metadata account"),
+            StakePoolError::DepositCapExceeded => msg!("Error: Deposit would take the pool's total stake above its deposit cap"),
+            StakePoolError::DepositTooSmall => msg!("Error: Deposit is too small to mint any pool tokens"),
+            StakePoolError::WrongAccountType => msg!("Error: Account is not of the type expected by the instruction"),
+            StakePoolError::UnsupportedAccountVersion => msg!("Error: Account layout version is not supported by this program"),
+            StakePoolError::InvalidPriceOracle => msg!("Error: Provided price oracle does not match the pool's price oracle"),
+            StakePoolError::DelinquentDecreaseDisabled => msg!("Error: Pool does not allow permissionless decreases on delinquent validators"),
+            StakePoolError::ValidatorNotDelinquent => msg!("Error: Validator has earned vote credits too recently to be considered delinquent"),
+            StakePoolError::ValidatorEntryStale => msg!("Error: Validator entry has not been updated this epoch"),
+            StakePoolError::StakeSplitBelowMinimum => msg!("Error: Stake split would leave a stake account below its minimum balance"),
+            StakePoolError::InvalidDepositInterceptor => msg!("Error: Provided deposit interceptor is not the pool's, or is missing from a strict pool"),
+            StakePoolError::StakeLockupInForce => msg!("Error: Deposited stake account is still locked up"),
+            StakePoolError::EpochNetOutflowExceeded => msg!("Error: Withdrawal would take the epoch's net outflow above the pool's cap"),
+            StakePoolError::StakePoolPaused => msg!("Error: Stake pool is paused, only updates are allowed"),
+            StakePoolError::AccountDataTooShort => msg!("Error: Account data is shorter than the layout of its account type"),
+            StakePoolError::AccountDataTooLong => msg!("Error: Account data is longer than the layout of its account type"),
+            StakePoolError::TargetWeightsNotSet => msg!("Error: No validator in the pool has a target weight"),
+            StakePoolError::ValidatorAtTarget => msg!("Error: Validator stake is already as close to its target as rebalancing allows"),
+            StakePoolError::StakePoolDecommissioning => msg!("Error: Stake pool is being decommissioned, it takes no more deposits or stake"),
+            StakePoolError::StakePoolNotDecommissioning => msg!("Error: Stake pool must be decommissioned first"),
+            StakePoolError::StakePoolNotEmpty => msg!("Error: Stake pool still has validators or outstanding pool tokens"),
+            StakePoolError::ValidatorOnboarding => msg!("Error: Validator is still onboarding and does not accept deposits or stake increases yet"),
+            StakePoolError::InvalidRateHistory => msg!("Error: Provided rate history does not match the pool's rate history"),
+            StakePoolError::ManagerActionPending => msg!("Error: A manager action is already waiting for the timelock, cancel it first"),
+            StakePoolError::NoPendingManagerAction => msg!("Error: No manager action is waiting for the timelock"),
+            StakePoolError::ManagerActionTimelocked => msg!("Error: Pending manager action cannot be executed until the timelock elapses"),
+            StakePoolError::WrongVoteWithdrawer => msg!("Error: Signer is not the withdraw authority of the validator's vote account"),
+            StakePoolError::InvalidDepositEscrow => msg!("Error: Pool tokens of a locked deposit must go to an escrow account owned by the depositor's deposit record"),
         }
     }
 }
@@ -2,9 +2,10 @@
 
 use serde_derive::{Deserialize, Serialize};
 use solana_program::{
-    clock::{Epoch, UnixTimestamp},
+    clock::{Clock, Epoch, UnixTimestamp},
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
+    rent::Rent,
     stake_history::StakeHistory,
     system_instruction, sysvar,
 };
@@ -90,7 +91,7 @@ pub enum StakeInstruction {
     /// # Account references
     ///   0. [WRITE] Initialized stake account
     ///   1. [SIGNER] Lockup authority
-    SetLockup,
+    SetLockup(LockupArgs),
 
     /// Merge two stake accounts. Both accounts must be deactivated and have identical lockup and
     /// authority keys.
@@ -191,6 +192,26 @@ pub struct Lockup {
     pub custodian: Pubkey,
 }
 
+/// FIXME copied from the stake program
+#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct LockupArgs {
+    /// FIXME copied from the stake program
+    pub unix_timestamp: Option<UnixTimestamp>,
+    /// FIXME copied from the stake program
+    pub epoch: Option<Epoch>,
+    /// FIXME copied from the stake program
+    pub custodian: Option<Pubkey>,
+}
+
+/// Lockup checks for stake accounts deposited into the pool
+impl Lockup {
+    /// Checks if the lockup still restricts withdrawals at `clock`, assuming
+    /// the custodian does not sign
+    pub fn is_in_force(&self, clock: &Clock) -> bool {
+        self.unix_timestamp > clock.unix_timestamp || self.epoch > clock.epoch
+    }
+}
+
 /// FIXME copied from the stake program
 impl StakeState {
     /// Get Delegation
@@ -200,6 +221,14 @@ impl StakeState {
             _ => None,
         }
     }
+
+    /// Get Meta
+    pub fn meta(&self) -> Option<Meta> {
+        match self {
+            StakeState::Initialized(meta) | StakeState::Stake(meta, _) => Some(*meta),
+            _ => None,
+        }
+    }
 }
 
 /// FIXME copied from the stake program
@@ -397,6 +426,14 @@ impl Delegation {
     }
 }
 
+/// Minimum number of lamports in a delegated stake account held by the pool:
+/// its rent-exempt reserve plus `MINIMUM_ACTIVE_STAKE`.  Splits must never
+/// leave a delegated stake account with less, or it can no longer be merged.
+pub fn minimum_stake_lamports(rent: &Rent) -> u64 {
+    rent.minimum_balance(std::mem::size_of::<StakeState>())
+        .saturating_add(crate::MINIMUM_ACTIVE_STAKE)
+}
+
 /// FIXME copied from the stake program
 pub fn split_only(
     stake_pubkey: &Pubkey,
@@ -433,6 +470,19 @@ pub fn authorize(
     )
 }
 
+/// FIXME copied from the stake program
+pub fn set_lockup(
+    stake_pubkey: &Pubkey,
+    lockup: &LockupArgs,
+    custodian_pubkey: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*stake_pubkey, false),
+        AccountMeta::new_readonly(*custodian_pubkey, true),
+    ];
+    Instruction::new_with_bincode(id(), &StakeInstruction::SetLockup(*lockup), account_metas)
+}
+
 /// FIXME copied from the stake program
 pub fn merge(
     destination_stake_pubkey: &Pubkey,
@@ -450,6 +500,24 @@ pub fn merge(
     Instruction::new_with_bincode(id(), &StakeInstruction::Merge, account_metas)
 }
 
+/// FIXME copied from the stake program
+pub fn withdraw(
+    stake_pubkey: &Pubkey,
+    withdrawer_pubkey: &Pubkey,
+    to_pubkey: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*stake_pubkey, false),
+        AccountMeta::new(*to_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(*withdrawer_pubkey, true),
+    ];
+
+    Instruction::new_with_bincode(id(), &StakeInstruction::Withdraw(lamports), account_metas)
+}
+
 /// FIXME copied from the stake program
 pub fn create_account(
     from_pubkey: &Pubkey,
@@ -1,13 +1,53 @@
 //! State transition types
 
 use {
-    crate::{error::StakePoolError, instruction::Fee},
+    crate::{
+        big_vec::{self, BigVec},
+        borsh::try_from_slice_unchecked,
+        error::{CalculationResult, StakePoolError},
+        instruction::{Fee, FeeType, FundingType},
+        math::Ratio,
+        MAX_EPOCH_NET_OUTFLOW_BPS, MAX_TARGET_WEIGHT_BPS, MINIMUM_ACTIVE_STAKE,
+    },
     borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
-    solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey},
-    spl_math::checked_ceil_div::CheckedCeilDiv,
-    std::convert::TryFrom,
+    bytemuck::{Pod, Zeroable},
+    solana_program::{
+        account_info::AccountInfo, clock::Epoch, msg, program_error::ProgramError, pubkey::Pubkey,
+    },
+    std::{convert::TryFrom, fmt},
 };
 
+/// Pool operations on which the manager collects a fee
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum FeeOperation {
+    /// Pool tokens minted on `UpdateStakePoolBalance` for the epoch's rewards
+    Epoch,
+    /// Pool tokens minted on `Deposit`
+    StakeDeposit,
+    /// Pool tokens minted on `DepositSol`
+    SolDeposit,
+    /// Pool tokens burned on `Withdraw` and `WithdrawSol`
+    Withdrawal,
+}
+
+/// Strategy used to collect a fee out of the pool tokens moved by an operation
+pub trait FeeCollector {
+    /// Calculate the fee, in pool tokens, collected out of `pool_tokens`
+    fn fee_pool_tokens(&self, pool_tokens: u64) -> Option<u64>;
+
+    /// Split `pool_tokens` into the portion left to the user and the fee
+    fn split_pool_tokens(&self, pool_tokens: u64) -> Option<(u64, u64)> {
+        let fee_pool_tokens = self.fee_pool_tokens(pool_tokens)?;
+        Some((pool_tokens.checked_sub(fee_pool_tokens)?, fee_pool_tokens))
+    }
+}
+
+impl FeeCollector for Fee {
+    fn fee_pool_tokens(&self, pool_tokens: u64) -> Option<u64> {
+        self.apply(pool_tokens)
+    }
+}
+
 /// Enum representing the account type managed by the program
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub enum AccountType {
@@ -17,6 +57,14 @@ pub enum AccountType {
     StakePool,
     /// Validator stake list
     ValidatorList,
+    /// Deposit record of a single depositor
+    DepositRecord,
+    /// Published pool token price of a stake pool
+    PriceOracle,
+    /// Pool of the stake of a single validator
+    SingleValidatorPool,
+    /// Balances of a stake pool over its last updated epochs
+    RateHistory,
 }
 
 impl Default for AccountType {
@@ -25,6 +73,67 @@ impl Default for AccountType {
     }
 }
 
+/// Current layout version of the `StakePool` account
+pub const STAKE_POOL_VERSION: u8 = 1;
+
+/// Current layout version of the `ValidatorList` account
+pub const VALIDATOR_LIST_VERSION: u8 = 1;
+
+/// Read the account type and layout version from the first two bytes of an
+/// account's data
+fn read_account_header(data: &[u8]) -> Result<(AccountType, u8), ProgramError> {
+    let mut header = data.get(..2).ok_or(StakePoolError::AccountDataTooShort)?;
+    let account_type = AccountType::deserialize(&mut header)?;
+    let version = u8::deserialize(&mut header)?;
+    Ok((account_type, version))
+}
+
+/// Check that account data of `data_len` bytes is `expected_len` long,
+/// failing with `AccountDataTooShort` or `AccountDataTooLong`
+fn check_data_len(data_len: usize, expected_len: usize) -> Result<(), ProgramError> {
+    if data_len == expected_len {
+        return Ok(());
+    }
+    msg!(
+        "Account data holds {} bytes, expected {}",
+        data_len,
+        expected_len
+    );
+    if data_len < expected_len {
+        Err(StakePoolError::AccountDataTooShort.into())
+    } else {
+        Err(StakePoolError::AccountDataTooLong.into())
+    }
+}
+
+/// Check the account type and layout version read from an account's header,
+/// logging what was found on mismatch
+fn check_account_header(
+    account_type: &AccountType,
+    version: u8,
+    expected_account_type: AccountType,
+    expected_version: u8,
+) -> Result<(), ProgramError> {
+    if *account_type != expected_account_type {
+        msg!(
+            "Expected a {:?} account, found {:?}",
+            expected_account_type,
+            account_type
+        );
+        return Err(StakePoolError::WrongAccountType.into());
+    }
+    if version != expected_version {
+        msg!(
+            "Unsupported {:?} layout version {}, expected {}",
+            account_type,
+            version,
+            expected_version
+        );
+        return Err(StakePoolError::UnsupportedAccountVersion.into());
+    }
+    Ok(())
+}
+
 /// Initialized program details.
 #[repr(C)]
 #[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -32,6 +141,9 @@ pub struct StakePool {
     /// Account type, must be StakePool currently
     pub account_type: AccountType,
 
+    /// Layout version, must be `STAKE_POOL_VERSION` currently
+    pub version: u8,
+
     /// Manager authority, allows for updating the staker, manager, and fee account
     pub manager: Pubkey,
 
@@ -73,51 +185,450 @@ pub struct StakePool {
     /// Last epoch the `total_stake_lamports` field was updated
     pub last_update_epoch: u64,
 
-    /// Fee applied to deposits
-    pub fee: Fee,
+    /// Fee taken as a proportion of rewards each epoch
+    pub epoch_fee: Fee,
+
+    /// Fee taken from pool tokens minted for stake deposits
+    pub stake_deposit_fee: Fee,
+
+    /// Fee taken from pool tokens minted for SOL deposits
+    pub sol_deposit_fee: Fee,
+
+    /// Fee taken from pool tokens on stake and SOL withdrawals
+    pub withdrawal_fee: Fee,
+
+    /// Epoch fee that becomes active on the next epoch's update
+    pub next_epoch_fee: Fee,
+
+    /// Withdrawal fee that becomes active on the next epoch's update
+    pub next_withdrawal_fee: Fee,
+
+    /// Vote account of the validator that must receive all stake deposits, or
+    /// the default pubkey if deposits may go to any validator
+    pub preferred_deposit_validator_vote_address: Pubkey,
+
+    /// Vote account of the validator that stake withdrawals must come from while
+    /// it can cover them, or the default pubkey if there is no preference
+    pub preferred_withdraw_validator_vote_address: Pubkey,
+
+    /// Authority that stake deposits must be authorized to.  Only this
+    /// authority's signature allows a deposit, unless it is the pool's
+    /// program-derived deposit authority, which makes stake deposits permissionless
+    pub stake_deposit_authority: Pubkey,
+
+    /// Authority that must sign all SOL deposits, or the default pubkey if SOL
+    /// deposits are permissionless
+    pub sol_deposit_authority: Pubkey,
+
+    /// Number of epochs that the pool tokens of a deposit stay in the
+    /// depositor's escrow, or 0 if they go straight to any token account
+    pub deposit_lock_epochs: u64,
+
+    /// If true, stake withdrawals must come from the validator furthest above
+    /// its target weight, as set by the staker through `SetValidatorTargets`
+    pub proportional_withdrawals: bool,
+
+    /// Maximum total stake lamports the pool accepts through deposits, or 0
+    /// if deposits are uncapped
+    pub max_total_lamports: u64,
+
+    /// Number of epochs a validator may go without earning vote credits before
+    /// anyone can decrease its stake through `DecreaseDelinquentValidatorStake`,
+    /// or 0 if only the staker may decrease stake
+    pub delinquent_validator_epochs: u64,
+
+    /// Token account of the pool mint receiving `secondary_fee_split` of the
+    /// epoch fee, or the default pubkey if the manager fee account receives
+    /// all of it
+    pub secondary_fee_account: Pubkey,
+
+    /// Share of the epoch fee minted to `secondary_fee_account`
+    pub secondary_fee_split: Fee,
+
+    /// Account receiving the SOL deposit fee in lamports, or the default
+    /// pubkey if the fee is minted to the manager fee account in pool tokens
+    pub sol_deposit_fee_treasury: Pubkey,
+
+    /// Program invoked after every deposit with the deposit details, or the
+    /// default pubkey if deposits are not intercepted
+    pub deposit_interceptor: Pubkey,
+
+    /// Compute units set by the manager for the deposit interceptor, handed
+    /// to it with the deposit details.  Not enforced by the pool.
+    pub deposit_interceptor_compute_units: u32,
+
+    /// If true, every deposit must invoke the deposit interceptor and fails
+    /// with it, otherwise depositors may leave the interceptor out
+    pub deposit_interceptor_strict: bool,
+
+    /// Maximum net outflow per epoch, in basis points of the pool's total
+    /// stake at the start of the epoch, or 0 if withdrawals are uncapped
+    pub max_epoch_net_outflow_bps: u16,
+
+    /// Lamports withdrawn from the pool since the epoch's balance update
+    pub epoch_withdrawn_lamports: u64,
+
+    /// Lamports deposited into the pool since the epoch's balance update
+    pub epoch_deposited_lamports: u64,
+
+    /// If true, deposits, withdrawals and rebalancing are blocked by the
+    /// manager, while updates still go through
+    pub paused: bool,
+
+    /// If true, the manager is winding the pool down: deposits and new stake
+    /// are blocked, withdrawals are free of fees and caps, and the pool can be
+    /// closed once every validator is removed and every pool token redeemed
+    pub decommissioning: bool,
+
+    /// Number of epochs newly added validators wait before accepting deposits
+    /// and stake increases, giving holders time to exit, or 0 if they accept
+    /// them right away
+    pub validator_onboarding_epochs: u64,
+
+    /// Number of epochs that fee, manager and funding authority changes wait
+    /// between being requested by the manager and taking effect, or 0 if they
+    /// take effect right away
+    pub manager_timelock_epochs: u64,
+
+    /// Manager action waiting for the timelock to elapse
+    pub pending_manager_action: PendingManagerAction,
 }
 impl StakePool {
-    /// calculate the pool tokens that should be minted for a deposit of `stake_lamports`
-    pub fn calc_pool_tokens_for_deposit(&self, stake_lamports: u64) -> Option<u64> {
-        if self.total_stake_lamports == 0 {
-            return Some(stake_lamports);
-        }
-        u64::try_from(
-            (stake_lamports as u128)
-                .checked_mul(self.pool_token_supply as u128)?
-                .checked_div(self.total_stake_lamports as u128)?,
-        )
-        .ok()
+    /// Length of the serialized stake pool, which is also the size of its
+    /// account
+    pub const LEN: usize = 751;
+
+    /// Vote account of the preferred deposit validator, if set
+    pub fn preferred_deposit_validator(&self) -> Option<Pubkey> {
+        Some(self.preferred_deposit_validator_vote_address)
+            .filter(|vote_address| *vote_address != Pubkey::default())
+    }
+
+    /// Vote account of the preferred withdraw validator, if set
+    pub fn preferred_withdraw_validator(&self) -> Option<Pubkey> {
+        Some(self.preferred_withdraw_validator_vote_address)
+            .filter(|vote_address| *vote_address != Pubkey::default())
+    }
+
+    /// Authority that must sign SOL deposits, if any
+    pub fn sol_deposit_authority(&self) -> Option<Pubkey> {
+        Some(self.sol_deposit_authority).filter(|authority| *authority != Pubkey::default())
+    }
+
+    /// Secondary account receiving a share of the epoch fee, if set
+    pub fn secondary_fee_account(&self) -> Option<Pubkey> {
+        Some(self.secondary_fee_account).filter(|account| *account != Pubkey::default())
+    }
+
+    /// Account receiving the SOL deposit fee in lamports, if set
+    pub fn sol_deposit_fee_treasury(&self) -> Option<Pubkey> {
+        Some(self.sol_deposit_fee_treasury).filter(|treasury| *treasury != Pubkey::default())
+    }
+
+    /// Program invoked after every deposit, if set
+    pub fn deposit_interceptor(&self) -> Option<Pubkey> {
+        Some(self.deposit_interceptor).filter(|interceptor| *interceptor != Pubkey::default())
+    }
+
+    /// Maximum net outflow per epoch, in basis points, if capped
+    pub fn epoch_net_outflow_cap(&self) -> Option<u16> {
+        Some(self.max_epoch_net_outflow_bps).filter(|bps| *bps != 0)
+    }
+
+    /// Maximum total stake lamports accepted through deposits, if capped
+    pub fn deposit_cap(&self) -> Option<u64> {
+        Some(self.max_total_lamports).filter(|max_total_lamports| *max_total_lamports != 0)
+    }
+
+    /// Number of epochs manager actions wait before taking effect, if they
+    /// are timelocked
+    pub fn manager_timelock(&self) -> Option<u64> {
+        Some(self.manager_timelock_epochs).filter(|epochs| *epochs != 0)
+    }
+
+    /// Manager action waiting for the timelock, along with the first epoch in
+    /// which it can be executed
+    pub fn pending_manager_action(&self) -> Option<(ManagerAction, Epoch)> {
+        self.pending_manager_action
+            .action()
+            .map(|action| (action, self.pending_manager_action.executable_epoch))
+    }
+
+    /// Record `action` to be executed once the timelock elapses after
+    /// `current_epoch`, returning the first epoch in which it can be executed.
+    /// Only one action may wait at a time.
+    pub(crate) fn queue_manager_action(
+        &mut self,
+        action: &ManagerAction,
+        current_epoch: Epoch,
+    ) -> Result<Epoch, ProgramError> {
+        if let Some((pending_action, _)) = self.pending_manager_action() {
+            msg!("Manager action {:?} is already pending", pending_action);
+            return Err(StakePoolError::ManagerActionPending.into());
+        }
+        let executable_epoch = current_epoch
+            .checked_add(self.manager_timelock_epochs)
+            .or_calculation_failure("manager action executable epoch")?;
+        self.pending_manager_action = PendingManagerAction::new(action, executable_epoch);
+        Ok(executable_epoch)
+    }
+
+    /// Take the pending manager action out of the pool, failing with
+    /// `ManagerActionTimelocked` while `current_epoch` is before its
+    /// executable epoch
+    pub(crate) fn take_executable_manager_action(
+        &mut self,
+        current_epoch: Epoch,
+    ) -> Result<ManagerAction, ProgramError> {
+        let (action, executable_epoch) = self
+            .pending_manager_action()
+            .ok_or(StakePoolError::NoPendingManagerAction)?;
+        if current_epoch < executable_epoch {
+            msg!(
+                "Manager action {:?} can be executed from epoch {}",
+                action,
+                executable_epoch
+            );
+            return Err(StakePoolError::ManagerActionTimelocked.into());
+        }
+        self.pending_manager_action = PendingManagerAction::default();
+        Ok(action)
+    }
+
+    /// Apply a fee, manager or funding authority change, checking it against
+    /// the pool's current settings
+    pub(crate) fn apply_manager_action(
+        &mut self,
+        action: &ManagerAction,
+    ) -> Result<(), ProgramError> {
+        match *action {
+            ManagerAction::SetFee(fee) => {
+                let (new_fee, pool_fee) = match fee {
+                    FeeType::Epoch(fee) => (fee, &mut self.next_epoch_fee),
+                    FeeType::StakeDeposit(fee) => (fee, &mut self.stake_deposit_fee),
+                    FeeType::SolDeposit(fee) => (fee, &mut self.sol_deposit_fee),
+                    FeeType::Withdrawal(fee) => (fee, &mut self.next_withdrawal_fee),
+                };
+                // Numerator should be smaller than or equal to denominator (fee <= 1)
+                if !new_fee.is_valid() {
+                    return Err(StakePoolError::FeeTooHigh.into());
+                }
+                *pool_fee = new_fee;
+
+                // Only allow a bounded withdrawal fee increase each epoch, so
+                // that the manager cannot front-run withdrawals
+                if let FeeType::Withdrawal(fee) = fee {
+                    if !fee.is_valid_withdrawal_increase(&self.withdrawal_fee) {
+                        msg!(
+                            "Withdrawal fee {}/{} increases too much from {}/{}",
+                            fee.numerator,
+                            fee.denominator,
+                            self.withdrawal_fee.numerator,
+                            self.withdrawal_fee.denominator
+                        );
+                        return Err(StakePoolError::FeeIncreaseTooHigh.into());
+                    }
+                }
+            }
+            ManagerAction::SetManager {
+                manager,
+                manager_fee_account,
+            } => {
+                self.manager = manager;
+                self.manager_fee_account = manager_fee_account;
+            }
+            ManagerAction::SetFundingAuthority(funding_type, funding_authority) => {
+                match funding_type {
+                    FundingType::StakeDeposit => self.stake_deposit_authority = funding_authority,
+                    FundingType::SolDeposit => self.sol_deposit_authority = funding_authority,
+                }
+            }
+            ManagerAction::SetManagerTimelock(manager_timelock_epochs) => {
+                self.manager_timelock_epochs = manager_timelock_epochs;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if a validator that last earned vote credits in
+    /// `last_credited_epoch` is delinquent, always false if delinquent
+    /// decreases are disabled
+    pub fn is_validator_delinquent(
+        &self,
+        last_credited_epoch: Option<Epoch>,
+        current_epoch: Epoch,
+    ) -> bool {
+        if self.delinquent_validator_epochs == 0 {
+            return false;
+        }
+        match last_credited_epoch {
+            Some(last_credited_epoch) => {
+                current_epoch.saturating_sub(last_credited_epoch) > self.delinquent_validator_epochs
+            }
+            None => true,
+        }
+    }
+
+    /// Fee collector charged on the given pool operation
+    pub fn fee_collector(&self, operation: FeeOperation) -> &dyn FeeCollector {
+        match operation {
+            FeeOperation::Epoch => &self.epoch_fee,
+            FeeOperation::StakeDeposit => &self.stake_deposit_fee,
+            FeeOperation::SolDeposit => &self.sol_deposit_fee,
+            FeeOperation::Withdrawal => &self.withdrawal_fee,
+        }
+    }
+
+    /// split `pool_tokens` moved by `operation` into the user's portion and the
+    /// manager's fee
+    pub fn split_pool_tokens(
+        &self,
+        operation: FeeOperation,
+        pool_tokens: u64,
+    ) -> Option<(u64, u64)> {
+        // remaining holders redeem pro-rata while the pool winds down
+        if self.decommissioning && operation == FeeOperation::Withdrawal {
+            return Some((pool_tokens, 0));
+        }
+        self.fee_collector(operation).split_pool_tokens(pool_tokens)
+    }
+
+    /// split the epoch fee `fee_pool_tokens` into the portions minted to the
+    /// manager fee account and to the secondary fee account
+    pub fn split_epoch_fee(&self, fee_pool_tokens: u64) -> Option<(u64, u64)> {
+        if self.secondary_fee_account().is_none() {
+            return Some((fee_pool_tokens, 0));
+        }
+        let secondary_pool_tokens = self.secondary_fee_split.apply(fee_pool_tokens)?;
+        Some((
+            fee_pool_tokens.checked_sub(secondary_pool_tokens)?,
+            secondary_pool_tokens,
+        ))
+    }
+
+    /// split the `lamports` of a SOL deposit into the portion deposited in the
+    /// pool and the fee sent to the SOL deposit fee treasury, which is 0 if the
+    /// fee is collected in pool tokens instead
+    pub fn split_sol_deposit_lamports(&self, lamports: u64) -> Option<(u64, u64)> {
+        if self.sol_deposit_fee_treasury().is_none() {
+            return Some((lamports, 0));
+        }
+        let fee_lamports = self.sol_deposit_fee.apply(lamports)?;
+        Some((lamports.checked_sub(fee_lamports)?, fee_lamports))
+    }
+
+    /// Pool tokens minted per lamport deposited, at 1:1 while the pool has no
+    /// pool tokens.  There is no rate while pool tokens are outstanding
+    /// without any stake backing them, since new deposits would then be
+    /// shared with the existing holders.
+    fn pool_tokens_per_lamport(&self) -> Option<Ratio> {
+        if self.pool_token_supply == 0 {
+            Some(Ratio::one())
+        } else if self.total_stake_lamports == 0 {
+            None
+        } else {
+            Some(Ratio::new(
+                self.pool_token_supply,
+                self.total_stake_lamports,
+            ))
+        }
+    }
+
+    /// Lamports backing `PRICE_ORACLE_RATE_PRECISION` pool tokens, rounded
+    /// down, at 1:1 while the pool has no pool tokens
+    pub fn price_oracle_rate(&self) -> Option<u64> {
+        self.rate_history_entry().rate()
+    }
+
+    /// Current balances of the pool, as recorded in its rate history
+    pub fn rate_history_entry(&self) -> RateHistoryEntry {
+        RateHistoryEntry {
+            epoch: self.last_update_epoch,
+            total_stake_lamports: self.total_stake_lamports,
+            pool_token_supply: self.pool_token_supply,
+        }
+    }
+
+    /// calculate the pool tokens worth a deposit of `stake_lamports`, rounded
+    /// down, which is what the pool mints for it
+    pub fn calc_pool_tokens_for_deposit_floor(&self, stake_lamports: u64) -> Option<u64> {
+        self.pool_tokens_per_lamport()?
+            .checked_apply_floor(stake_lamports)
+    }
+    /// calculate the pool tokens worth a deposit of `stake_lamports`, rounded
+    /// up
+    pub fn calc_pool_tokens_for_deposit_ceil(&self, stake_lamports: u64) -> Option<u64> {
+        self.pool_tokens_per_lamport()?
+            .checked_apply_ceil(stake_lamports)
     }
     /// calculate the pool tokens that should be burned for a withdrawal of `stake_lamports`
     pub fn calc_pool_tokens_for_withdraw(&self, stake_lamports: u64) -> Option<u64> {
-        let (quotient, _) = (stake_lamports as u128)
-            .checked_mul(self.pool_token_supply as u128)?
-            .checked_ceil_div(self.total_stake_lamports as u128)?;
-        u64::try_from(quotient).ok()
-    }
-
-    /// calculate lamports amount on withdrawal
-    pub fn calc_lamports_withdraw_amount(&self, pool_tokens: u64) -> Option<u64> {
-        u64::try_from(
-            (pool_tokens as u128)
-                .checked_mul(self.total_stake_lamports as u128)?
-                .checked_div(self.pool_token_supply as u128)?,
-        )
-        .ok()
+        Ratio::new(self.pool_token_supply, self.total_stake_lamports)
+            .checked_apply_ceil(stake_lamports)
+    }
+
+    /// calculate the lamports backing `pool_tokens`, rounded down, which is
+    /// what the pool pays out when they are burned
+    pub fn calc_lamports_withdraw_amount_floor(&self, pool_tokens: u64) -> Option<u64> {
+        Ratio::new(self.total_stake_lamports, self.pool_token_supply)
+            .checked_apply_floor(pool_tokens)
+    }
+    /// calculate the lamports backing `pool_tokens`, rounded up
+    pub fn calc_lamports_withdraw_amount_ceil(&self, pool_tokens: u64) -> Option<u64> {
+        Ratio::new(self.total_stake_lamports, self.pool_token_supply)
+            .checked_apply_ceil(pool_tokens)
     }
-    /// calculate the fee in pool tokens that goes to the manager
+    /// calculate the epoch fee in pool tokens that goes to the manager, rounding
+    /// once over the conversion of `reward_lamports` and the fee
     pub fn calc_fee_amount(&self, reward_lamports: u64) -> Option<u64> {
-        if self.fee.denominator == 0 {
+        if self.epoch_fee.denominator == 0 || reward_lamports == 0 {
             return Some(0);
         }
-        let pool_amount = self.calc_pool_tokens_for_deposit(reward_lamports)?;
-        u64::try_from(
-            (pool_amount as u128)
-                .checked_mul(self.fee.numerator as u128)?
-                .checked_div(self.fee.denominator as u128)?,
-        )
-        .ok()
+        self.pool_tokens_per_lamport()?
+            .checked_mul(&self.epoch_fee.ratio())?
+            .checked_apply_floor(reward_lamports)
+    }
+
+    /// How current the pool's balances are in `epoch`
+    pub fn freshness(&self, epoch: Epoch) -> Freshness {
+        Freshness::new(self.last_update_epoch, epoch)
+    }
+
+    /// Pool tokens a stake deposit of `stake_lamports` mints in `epoch`, split
+    /// into the depositor's portion and the manager's fee.  Fails with
+    /// `StakeListAndPoolOutOfDate` if the pool wasn't updated in `epoch`,
+    /// since the deposit would then be priced on balances missing rewards.
+    pub fn preview_stake_deposit(
+        &self,
+        epoch: Epoch,
+        stake_lamports: u64,
+    ) -> Result<(u64, u64), StakePoolError> {
+        if !self.freshness(epoch).is_fresh() {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate);
+        }
+        self.calc_pool_tokens_for_deposit_floor(stake_lamports)
+            .and_then(|pool_tokens| self.split_pool_tokens(FeeOperation::StakeDeposit, pool_tokens))
+            .ok_or(StakePoolError::CalculationFailure)
+    }
+
+    /// Lamports paid out for burning `pool_tokens` in `epoch`, along with the
+    /// manager's fee in pool tokens.  Fails with `StakeListAndPoolOutOfDate`
+    /// if the pool wasn't updated in `epoch`.
+    pub fn preview_withdrawal(
+        &self,
+        epoch: Epoch,
+        pool_tokens: u64,
+    ) -> Result<(u64, u64), StakePoolError> {
+        if !self.freshness(epoch).is_fresh() {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate);
+        }
+        let (burn_pool_tokens, fee_pool_tokens) = self
+            .split_pool_tokens(FeeOperation::Withdrawal, pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        let lamports = self
+            .calc_lamports_withdraw_amount_floor(burn_pool_tokens)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        Ok((lamports, fee_pool_tokens))
     }
 
     /// Checks that the withdraw or deposit authority is valid
@@ -175,6 +686,228 @@ impl StakePool {
         )
     }
 
+    /// Check the stake deposit authority, which must also sign the deposit unless
+    /// it is the program-derived deposit authority
+    pub(crate) fn check_stake_deposit_authority(
+        &self,
+        stake_deposit_authority_info: &AccountInfo,
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        if *stake_deposit_authority_info.key != self.stake_deposit_authority {
+            return Err(StakePoolError::InvalidStakeDepositAuthority.into());
+        }
+        if !stake_deposit_authority_info.is_signer
+            && self
+                .check_authority_deposit(
+                    stake_deposit_authority_info.key,
+                    program_id,
+                    stake_pool_address,
+                )
+                .is_err()
+        {
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+        Ok(())
+    }
+
+    /// Check the SOL deposit authority, if the pool has one, and its signature
+    pub(crate) fn check_sol_deposit_authority(
+        &self,
+        sol_deposit_authority_info: Option<&AccountInfo>,
+    ) -> Result<(), ProgramError> {
+        if let Some(sol_deposit_authority) = self.sol_deposit_authority() {
+            let sol_deposit_authority_info =
+                sol_deposit_authority_info.ok_or(StakePoolError::InvalidSolDepositAuthority)?;
+            if *sol_deposit_authority_info.key != sol_deposit_authority {
+                return Err(StakePoolError::InvalidSolDepositAuthority.into());
+            }
+            if !sol_deposit_authority_info.is_signer {
+                return Err(StakePoolError::SignatureMissing.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the secondary fee account provided, if the pool has one
+    pub(crate) fn check_secondary_fee_info(
+        &self,
+        secondary_fee_info: Option<&AccountInfo>,
+    ) -> Result<(), ProgramError> {
+        if let Some(secondary_fee_account) = self.secondary_fee_account() {
+            let secondary_fee_info = secondary_fee_info.ok_or(StakePoolError::InvalidFeeAccount)?;
+            if *secondary_fee_info.key != secondary_fee_account {
+                return Err(StakePoolError::InvalidFeeAccount.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the SOL deposit fee treasury provided, if the pool has one
+    pub(crate) fn check_sol_deposit_fee_treasury(
+        &self,
+        sol_deposit_fee_treasury_info: Option<&AccountInfo>,
+    ) -> Result<(), ProgramError> {
+        if let Some(sol_deposit_fee_treasury) = self.sol_deposit_fee_treasury() {
+            let sol_deposit_fee_treasury_info =
+                sol_deposit_fee_treasury_info.ok_or(StakePoolError::InvalidFeeAccount)?;
+            if *sol_deposit_fee_treasury_info.key != sol_deposit_fee_treasury {
+                return Err(StakePoolError::InvalidFeeAccount.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the deposit interceptor account passed after the other deposit
+    /// accounts, returning whether it must be invoked.  Non-strict pools let
+    /// depositors leave it out.
+    pub(crate) fn check_deposit_interceptor(
+        &self,
+        deposit_interceptor_info: Option<&AccountInfo>,
+    ) -> Result<bool, ProgramError> {
+        match (self.deposit_interceptor(), deposit_interceptor_info) {
+            (Some(deposit_interceptor), Some(deposit_interceptor_info)) => {
+                if *deposit_interceptor_info.key != deposit_interceptor {
+                    msg!(
+                        "Deposit interceptor {} expected, {} provided",
+                        deposit_interceptor,
+                        deposit_interceptor_info.key
+                    );
+                    return Err(StakePoolError::InvalidDepositInterceptor.into());
+                }
+                Ok(true)
+            }
+            (Some(deposit_interceptor), None) => {
+                if self.deposit_interceptor_strict {
+                    msg!("Deposit interceptor {} is required", deposit_interceptor);
+                    return Err(StakePoolError::InvalidDepositInterceptor.into());
+                }
+                Ok(false)
+            }
+            (None, _) => Ok(false),
+        }
+    }
+
+    /// Check that depositing `lamports` keeps the pool within its deposit cap
+    pub(crate) fn check_deposit_cap(&self, lamports: u64) -> Result<(), ProgramError> {
+        if let Some(max_total_lamports) = self.deposit_cap() {
+            let new_total_lamports = self
+                .total_stake_lamports
+                .checked_add(lamports)
+                .or_calculation_failure("total stake lamports with the deposit")?;
+            if new_total_lamports > max_total_lamports {
+                msg!(
+                    "Deposit would bring the pool to {} lamports, above the cap of {}",
+                    new_total_lamports,
+                    max_total_lamports
+                );
+                return Err(StakePoolError::DepositCapExceeded.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the pool is not paused
+    pub(crate) fn check_not_paused(&self) -> Result<(), ProgramError> {
+        if self.paused {
+            return Err(StakePoolError::StakePoolPaused.into());
+        }
+        Ok(())
+    }
+
+    /// Check that the pool is not being decommissioned
+    pub(crate) fn check_not_decommissioning(&self) -> Result<(), ProgramError> {
+        if self.decommissioning {
+            return Err(StakePoolError::StakePoolDecommissioning.into());
+        }
+        Ok(())
+    }
+
+    /// Count a deposit of `lamports` against the epoch's net outflow
+    pub(crate) fn record_epoch_inflow(&mut self, lamports: u64) -> Result<(), ProgramError> {
+        self.epoch_deposited_lamports = self
+            .epoch_deposited_lamports
+            .checked_add(lamports)
+            .or_calculation_failure("lamports deposited in the epoch")?;
+        Ok(())
+    }
+
+    /// Count a withdrawal of `lamports` against the epoch's net outflow,
+    /// checking that it stays within the cap.  Must be called before the
+    /// withdrawal is taken off `total_stake_lamports`.
+    pub(crate) fn record_epoch_outflow(&mut self, lamports: u64) -> Result<(), ProgramError> {
+        let withdrawn_lamports = self
+            .epoch_withdrawn_lamports
+            .checked_add(lamports)
+            .or_calculation_failure("lamports withdrawn in the epoch")?;
+        // the cap would only trap holders in a pool that is winding down
+        let epoch_net_outflow_cap = self
+            .epoch_net_outflow_cap()
+            .filter(|_| !self.decommissioning);
+        if let Some(max_net_outflow_bps) = epoch_net_outflow_cap {
+            // total stake before this epoch's deposits and withdrawals
+            let epoch_start_lamports = (self.total_stake_lamports as u128)
+                .checked_add(self.epoch_withdrawn_lamports as u128)
+                .and_then(|lamports| lamports.checked_sub(self.epoch_deposited_lamports as u128))
+                .unwrap_or(0);
+            let max_net_outflow = epoch_start_lamports
+                .checked_mul(max_net_outflow_bps as u128)
+                .or_calculation_failure("epoch net outflow cap")?
+                / MAX_EPOCH_NET_OUTFLOW_BPS as u128;
+            let net_outflow = withdrawn_lamports.saturating_sub(self.epoch_deposited_lamports);
+            if net_outflow as u128 > max_net_outflow {
+                msg!(
+                    "Withdrawal would bring the epoch's net outflow to {} lamports, above the cap of {}",
+                    net_outflow,
+                    max_net_outflow
+                );
+                return Err(StakePoolError::EpochNetOutflowExceeded.into());
+            }
+        }
+        self.epoch_withdrawn_lamports = withdrawn_lamports;
+        Ok(())
+    }
+
+    /// Add a deposit of `lamports`, for which `pool_tokens` are minted, to the
+    /// pool's balances
+    pub(crate) fn credit_deposit(
+        &mut self,
+        lamports: u64,
+        pool_tokens: u64,
+    ) -> Result<(), ProgramError> {
+        let pool_token_supply = self
+            .pool_token_supply
+            .checked_add(pool_tokens)
+            .or_calculation_failure("pool token supply with the deposit")?;
+        let total_stake_lamports = self
+            .total_stake_lamports
+            .checked_add(lamports)
+            .or_calculation_failure("total stake lamports with the deposit")?;
+        self.pool_token_supply = pool_token_supply;
+        self.total_stake_lamports = total_stake_lamports;
+        Ok(())
+    }
+
+    /// Take a withdrawal of `lamports`, for which `pool_tokens` are burned,
+    /// off the pool's balances
+    pub(crate) fn debit_withdrawal(
+        &mut self,
+        lamports: u64,
+        pool_tokens: u64,
+    ) -> Result<(), ProgramError> {
+        let pool_token_supply = self
+            .pool_token_supply
+            .checked_sub(pool_tokens)
+            .or_calculation_failure("pool token supply after the withdrawal")?;
+        let total_stake_lamports = self
+            .total_stake_lamports
+            .checked_sub(lamports)
+            .or_calculation_failure("total stake lamports after the withdrawal")?;
+        self.pool_token_supply = pool_token_supply;
+        self.total_stake_lamports = total_stake_lamports;
+        Ok(())
+    }
+
     /// Check staker validity and signature
     pub(crate) fn check_mint(&self, mint_info: &AccountInfo) -> Result<(), ProgramError> {
         if *mint_info.key != self.pool_mint {
@@ -184,6 +917,30 @@ impl StakePool {
         }
     }
 
+    /// Check the manager fee account matches the one stored in the pool
+    pub(crate) fn check_manager_fee_info(
+        &self,
+        manager_fee_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        if *manager_fee_info.key != self.manager_fee_account {
+            Err(StakePoolError::InvalidFeeAccount.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check the reserve stake account matches the one stored in the pool
+    pub(crate) fn check_reserve_stake(
+        &self,
+        reserve_stake_info: &AccountInfo,
+    ) -> Result<(), ProgramError> {
+        if *reserve_stake_info.key != self.reserve_stake {
+            Err(StakePoolError::InvalidReserveStakeAccount.into())
+        } else {
+            Ok(())
+        }
+    }
+
     /// Check manager validity and signature
     pub(crate) fn check_manager(&self, manager_info: &AccountInfo) -> Result<(), ProgramError> {
         if *manager_info.key != self.manager {
@@ -206,9 +963,71 @@ impl StakePool {
         Ok(())
     }
 
+    /// Deserialize an initialized stake pool, checking the account type and
+    /// layout version before decoding the rest of the data
+    pub fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let (account_type, version) = read_account_header(data)?;
+        check_account_header(
+            &account_type,
+            version,
+            AccountType::StakePool,
+            STAKE_POOL_VERSION,
+        )?;
+        check_data_len(data.len(), Self::LEN)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    /// Deserialize a stake pool written by this or a later version of the
+    /// program, for clients that only read it.  Later versions may bump the
+    /// layout version and append fields, which are skipped.
+    pub fn deserialize_any_version(data: &[u8]) -> Result<VersionedStakePool, ProgramError> {
+        let (account_type, version) = read_account_header(data)?;
+        // only layouts older than the current one are unsupported
+        check_account_header(
+            &account_type,
+            version.min(STAKE_POOL_VERSION),
+            AccountType::StakePool,
+            STAKE_POOL_VERSION,
+        )?;
+        if data.len() < Self::LEN {
+            check_data_len(data.len(), Self::LEN)?;
+        }
+        let stake_pool = try_from_slice_unchecked::<Self>(data)?;
+        if version == STAKE_POOL_VERSION && data.len() == Self::LEN {
+            Ok(VersionedStakePool::Current(stake_pool))
+        } else {
+            Ok(VersionedStakePool::Newer {
+                version,
+                stake_pool,
+            })
+        }
+    }
+
+    /// Deserialize a stake pool account that is yet to be initialized,
+    /// failing with `AlreadyInUse` if it already holds an account
+    pub fn deserialize_uninitialized(data: &[u8]) -> Result<Self, ProgramError> {
+        let (account_type, _) = read_account_header(data)?;
+        if account_type != AccountType::Uninitialized {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+        check_data_len(data.len(), Self::LEN)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
     /// Check if StakePool is actually initialized as a stake pool
     pub fn is_valid(&self) -> bool {
-        self.account_type == AccountType::StakePool
+        self.check_valid().is_ok()
+    }
+
+    /// Check that the account is a stake pool with the current layout,
+    /// failing with `WrongAccountType` or `UnsupportedAccountVersion`
+    pub fn check_valid(&self) -> Result<(), ProgramError> {
+        check_account_header(
+            &self.account_type,
+            self.version,
+            AccountType::StakePool,
+            STAKE_POOL_VERSION,
+        )
     }
 
     /// Check if StakePool is currently uninitialized
@@ -217,75 +1036,726 @@ impl StakePool {
     }
 }
 
-/// Storage list for all validator stake accounts in the pool.
-#[repr(C)]
-#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
-pub struct ValidatorList {
-    /// Account type, must be ValidatorList currently
-    pub account_type: AccountType,
-
-    /// Maximum allowable number of validators
-    pub max_validators: u32,
-
-    /// List of stake info for each validator in the pool
-    pub validators: Vec<ValidatorStakeInfo>,
+/// How current the balances of a stake pool, or the rate published from them,
+/// are in a given epoch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Freshness {
+    /// Updated in the epoch
+    Fresh,
+    /// Last updated `epochs_behind` epochs earlier, so the balances miss the
+    /// rewards earned since
+    Stale {
+        /// Epoch of the last update
+        last_update_epoch: Epoch,
+        /// Epochs since the last update
+        epochs_behind: u64,
+    },
 }
+impl Freshness {
+    /// Freshness in `epoch` of something last updated in `last_update_epoch`
+    pub fn new(last_update_epoch: Epoch, epoch: Epoch) -> Self {
+        if last_update_epoch >= epoch {
+            Freshness::Fresh
+        } else {
+            Freshness::Stale {
+                last_update_epoch,
+                epochs_behind: epoch - last_update_epoch,
+            }
+        }
+    }
 
-/// Information about the singe validator stake account
-#[repr(C)]
-#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
-pub struct ValidatorStakeInfo {
-    /// Validator vote account address
-    pub vote_account: Pubkey,
+    /// Check if it was updated in the epoch
+    pub fn is_fresh(&self) -> bool {
+        *self == Freshness::Fresh
+    }
 
-    /// Amount of stake delegated to this validator
-    /// Note that if `last_update_epoch` does not match the current epoch then this field may not
-    /// be accurate
-    pub stake_lamports: u64,
+    /// Epochs since the last update, 0 if fresh
+    pub fn epochs_behind(&self) -> u64 {
+        match self {
+            Freshness::Fresh => 0,
+            Freshness::Stale { epochs_behind, .. } => *epochs_behind,
+        }
+    }
+}
 
-    /// Last epoch the `stake_lamports` field was updated
-    pub last_update_epoch: u64,
+/// Stake pool read by `StakePool::deserialize_any_version`
+#[derive(Clone, Debug, PartialEq)]
+pub enum VersionedStakePool {
+    /// Stake pool with the current layout
+    Current(StakePool),
+    /// Stake pool written by a later version of the program, of which only
+    /// the fields known to this version were read.  The program only accepts
+    /// its own layout, so it must not be written back.
+    Newer {
+        /// Layout version found in the account
+        version: u8,
+        /// Fields known to this version
+        stake_pool: StakePool,
+    },
 }
 
-impl ValidatorList {
-    /// Create an empty instance containing space for `max_validators`
-    pub fn new(max_validators: u32) -> Self {
-        Self {
-            account_type: AccountType::ValidatorList,
-            max_validators,
-            validators: vec![ValidatorStakeInfo::default(); max_validators as usize],
+impl VersionedStakePool {
+    /// Fields of the stake pool known to this version
+    pub fn stake_pool(&self) -> &StakePool {
+        match self {
+            VersionedStakePool::Current(stake_pool) => stake_pool,
+            VersionedStakePool::Newer { stake_pool, .. } => stake_pool,
         }
     }
 
-    /// Calculate the number of validator entries that fit in the provided length
-    pub fn calculate_max_validators(buffer_length: usize) -> usize {
-        let header_size = 1 + 4 + 4;
-        buffer_length.saturating_sub(header_size) / 48
+    /// Take the fields of the stake pool known to this version
+    pub fn into_stake_pool(self) -> StakePool {
+        match self {
+            VersionedStakePool::Current(stake_pool) => stake_pool,
+            VersionedStakePool::Newer { stake_pool, .. } => stake_pool,
+        }
     }
+}
 
-    /// Check if contains validator with particular pubkey
-    pub fn contains(&self, vote_account: &Pubkey) -> bool {
-        self.validators
-            .iter()
-            .any(|x| x.vote_account == *vote_account)
+/// Change to the pool held back by the manager timelock
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum ManagerAction {
+    /// New fee, through `SetFee`
+    SetFee(FeeType),
+    /// New manager and manager fee account, through `SetManager`
+    SetManager {
+        /// Manager authority
+        manager: Pubkey,
+        /// Manager fee account
+        manager_fee_account: Pubkey,
+    },
+    /// New funding authority, through `SetFundingAuthority`, where the
+    /// default pubkey leaves SOL deposits permissionless
+    SetFundingAuthority(FundingType, Pubkey),
+    /// Shorter manager timelock, in epochs, through `SetManagerTimelock`
+    SetManagerTimelock(u64),
+}
+
+/// Kind of a pending manager action, including the kind of fee or funding
+/// authority it changes
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum PendingManagerActionType {
+    /// No action is pending
+    None,
+    /// `SetFee` of the epoch fee
+    EpochFee,
+    /// `SetFee` of the stake deposit fee
+    StakeDepositFee,
+    /// `SetFee` of the SOL deposit fee
+    SolDepositFee,
+    /// `SetFee` of the withdrawal fee
+    WithdrawalFee,
+    /// `SetManager`
+    Manager,
+    /// `SetFundingAuthority` of the stake deposit authority
+    StakeDepositAuthority,
+    /// `SetFundingAuthority` of the SOL deposit authority
+    SolDepositAuthority,
+    /// `SetManagerTimelock` decreasing the timelock
+    ManagerTimelock,
+}
+
+impl Default for PendingManagerActionType {
+    fn default() -> Self {
+        PendingManagerActionType::None
+    }
+}
+
+/// `ManagerAction` laid out with room for every kind of action, so that the
+/// stake pool keeps a fixed size
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct PendingManagerAction {
+    /// Kind of the pending action
+    pub action_type: PendingManagerActionType,
+    /// First epoch in which anyone can execute the action
+    pub executable_epoch: u64,
+    /// New fee of a fee change
+    pub fee: Fee,
+    /// New manager or funding authority
+    pub authority: Pubkey,
+    /// New manager fee account of a manager change
+    pub manager_fee_account: Pubkey,
+    /// New timelock, in epochs, of a timelock change
+    pub manager_timelock_epochs: u64,
+}
+
+impl PendingManagerAction {
+    /// Record `action`, executable from `executable_epoch`
+    pub fn new(action: &ManagerAction, executable_epoch: Epoch) -> Self {
+        let pending = Self {
+            executable_epoch,
+            ..Self::default()
+        };
+        match *action {
+            ManagerAction::SetFee(fee_type) => {
+                let (action_type, fee) = match fee_type {
+                    FeeType::Epoch(fee) => (PendingManagerActionType::EpochFee, fee),
+                    FeeType::StakeDeposit(fee) => (PendingManagerActionType::StakeDepositFee, fee),
+                    FeeType::SolDeposit(fee) => (PendingManagerActionType::SolDepositFee, fee),
+                    FeeType::Withdrawal(fee) => (PendingManagerActionType::WithdrawalFee, fee),
+                };
+                Self {
+                    action_type,
+                    fee,
+                    ..pending
+                }
+            }
+            ManagerAction::SetManager {
+                manager,
+                manager_fee_account,
+            } => Self {
+                action_type: PendingManagerActionType::Manager,
+                authority: manager,
+                manager_fee_account,
+                ..pending
+            },
+            ManagerAction::SetFundingAuthority(funding_type, authority) => Self {
+                action_type: match funding_type {
+                    FundingType::StakeDeposit => PendingManagerActionType::StakeDepositAuthority,
+                    FundingType::SolDeposit => PendingManagerActionType::SolDepositAuthority,
+                },
+                authority,
+                ..pending
+            },
+            ManagerAction::SetManagerTimelock(manager_timelock_epochs) => Self {
+                action_type: PendingManagerActionType::ManagerTimelock,
+                manager_timelock_epochs,
+                ..pending
+            },
+        }
+    }
+
+    /// Recorded action, if any
+    pub fn action(&self) -> Option<ManagerAction> {
+        match self.action_type {
+            PendingManagerActionType::None => None,
+            PendingManagerActionType::EpochFee => {
+                Some(ManagerAction::SetFee(FeeType::Epoch(self.fee)))
+            }
+            PendingManagerActionType::StakeDepositFee => {
+                Some(ManagerAction::SetFee(FeeType::StakeDeposit(self.fee)))
+            }
+            PendingManagerActionType::SolDepositFee => {
+                Some(ManagerAction::SetFee(FeeType::SolDeposit(self.fee)))
+            }
+            PendingManagerActionType::WithdrawalFee => {
+                Some(ManagerAction::SetFee(FeeType::Withdrawal(self.fee)))
+            }
+            PendingManagerActionType::Manager => Some(ManagerAction::SetManager {
+                manager: self.authority,
+                manager_fee_account: self.manager_fee_account,
+            }),
+            PendingManagerActionType::StakeDepositAuthority => Some(
+                ManagerAction::SetFundingAuthority(FundingType::StakeDeposit, self.authority),
+            ),
+            PendingManagerActionType::SolDepositAuthority => Some(
+                ManagerAction::SetFundingAuthority(FundingType::SolDeposit, self.authority),
+            ),
+            PendingManagerActionType::ManagerTimelock => Some(ManagerAction::SetManagerTimelock(
+                self.manager_timelock_epochs,
+            )),
+        }
+    }
+}
+
+/// Storage list for all validator stake accounts in the pool.
+///
+/// The account holds the Borsh serialization of this struct, which is the
+/// `ValidatorListHeader` followed by the `u32` number of validators and
+/// `ValidatorStakeInfo::LEN` bytes per validator.  The program reads and
+/// writes the entries in place, through `ValidatorListHeader::deserialize_vec`
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorList {
+    /// Account type, must be ValidatorList currently
+    pub account_type: AccountType,
+
+    /// Layout version, must be `VALIDATOR_LIST_VERSION` currently
+    pub version: u8,
+
+    /// Maximum allowable number of validators
+    pub max_validators: u32,
+
+    /// List of stake info for each validator in the pool
+    pub validators: Vec<ValidatorStakeInfo>,
+}
+
+/// Fixed-size start of a validator list account, before its entries
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorListHeader {
+    /// Account type, must be ValidatorList currently
+    pub account_type: AccountType,
+
+    /// Layout version, must be `VALIDATOR_LIST_VERSION` currently
+    pub version: u8,
+
+    /// Maximum allowable number of validators
+    pub max_validators: u32,
+}
+
+impl ValidatorListHeader {
+    /// Length of the serialized header
+    pub const LEN: usize = 1 + 1 + 4;
+
+    /// Check that the account is a validator list with the current layout,
+    /// failing with `WrongAccountType` or `UnsupportedAccountVersion`
+    pub fn check_valid(&self) -> Result<(), ProgramError> {
+        check_account_header(
+            &self.account_type,
+            self.version,
+            AccountType::ValidatorList,
+            VALIDATOR_LIST_VERSION,
+        )
+    }
+
+    /// Length of validator list data with room for `max_validators` entries
+    pub fn data_len(max_validators: u32) -> usize {
+        Self::LEN + 4 + max_validators as usize * ValidatorStakeInfo::LEN
+    }
+
+    /// Check that validator list data of `data_len` bytes has room for
+    /// exactly `max_validators` entries, allowing for less than an entry's
+    /// worth of unused bytes at the end
+    pub fn check_data_len(&self, data_len: usize) -> Result<(), ProgramError> {
+        let expected_len = Self::data_len(self.max_validators);
+        if data_len >= expected_len && data_len - expected_len < ValidatorStakeInfo::LEN {
+            return Ok(());
+        }
+        msg!(
+            "Validator list data holds {} bytes, expected {} for {} validators",
+            data_len,
+            expected_len,
+            self.max_validators
+        );
+        if data_len < expected_len {
+            Err(StakePoolError::AccountDataTooShort.into())
+        } else {
+            Err(StakePoolError::AccountDataTooLong.into())
+        }
+    }
+
+    /// Split the data of an initialized validator list into its header and
+    /// its entries, which are read and written in place
+    pub fn deserialize_vec(data: &mut [u8]) -> Result<(Self, BigVec), ProgramError> {
+        let header = Self::deserialize_checked(data)?;
+        let entries_data = &mut data[Self::LEN..];
+        Ok((header, BigVec::new(entries_data)?))
+    }
+
+    /// Read-only counterpart of `deserialize_vec`, giving the entries as a
+    /// slice
+    pub fn deserialize_slice(data: &[u8]) -> Result<(Self, &[ValidatorStakeInfo]), ProgramError> {
+        let header = Self::deserialize_checked(data)?;
+        Ok((header, big_vec::deserialize_slice(&data[Self::LEN..])?))
+    }
+
+    /// Deserialize the header of an initialized validator list, checking the
+    /// account type and layout version, then that the data fits its capacity
+    fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let header_data = data
+            .get(..Self::LEN)
+            .ok_or(StakePoolError::AccountDataTooShort)?;
+        let header = Self::try_from_slice(header_data)?;
+        header.check_valid()?;
+        header.check_data_len(data.len())?;
+        Ok(header)
+    }
+
+    /// Copy of the entry of the validator with the given vote account, read
+    /// in place from validator list data
+    pub fn find_entry(
+        data: &[u8],
+        vote_account: &Pubkey,
+    ) -> Result<Option<ValidatorStakeInfo>, ProgramError> {
+        let (_, validators) = Self::deserialize_slice(data)?;
+        Ok(validators.find(vote_account).copied())
+    }
+
+    /// Overwrite the entry of the validator with the same vote account as
+    /// `entry` in place, leaving the rest of the validator list data untouched
+    pub fn update_entry(data: &mut [u8], entry: &ValidatorStakeInfo) -> Result<(), ProgramError> {
+        let (_, mut validators) = Self::deserialize_vec(data)?;
+        let validator = validators
+            .as_mut_slice::<ValidatorStakeInfo>()?
+            .find_mut(&entry.vote_account)
+            .ok_or(StakePoolError::ValidatorNotFound)?;
+        *validator = *entry;
+        Ok(())
+    }
+}
+
+/// Status of a validator in the pool
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum StakeStatus {
+    /// Stake account is active, there may be a transient stake as well
+    Active,
+    /// Validator was removed by the staker and its stake account is
+    /// deactivating, the lamports go back to the reserve on the next update
+    /// after the deactivation completes
+    ReadyForRemoval,
+    /// Validator was removed by the staker while its transient stake account
+    /// was in use: both stake accounts are deactivating, and the entry moves
+    /// to `ReadyForRemoval` once the transient stake is back in the reserve
+    DeactivatingTransient,
+}
+
+impl Default for StakeStatus {
+    fn default() -> Self {
+        StakeStatus::Active
+    }
+}
+
+/// `StakeStatus` stored as its Borsh discriminant, so that any byte can be
+/// read in place.  Unknown values match no status
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct PodStakeStatus(u8);
+
+impl TryFrom<PodStakeStatus> for StakeStatus {
+    type Error = ProgramError;
+    fn try_from(pod: PodStakeStatus) -> Result<Self, Self::Error> {
+        match pod.0 {
+            0 => Ok(StakeStatus::Active),
+            1 => Ok(StakeStatus::ReadyForRemoval),
+            2 => Ok(StakeStatus::DeactivatingTransient),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl From<StakeStatus> for PodStakeStatus {
+    fn from(status: StakeStatus) -> Self {
+        Self(status as u8)
+    }
+}
+
+impl PartialEq<StakeStatus> for PodStakeStatus {
+    fn eq(&self, status: &StakeStatus) -> bool {
+        self.0 == *status as u8
+    }
+}
+
+impl fmt::Debug for PodStakeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match StakeStatus::try_from(*self) {
+            Ok(status) => fmt::Debug::fmt(&status, f),
+            Err(_) => write!(f, "Unknown({})", self.0),
+        }
+    }
+}
+
+/// `u16` stored as little-endian bytes, so that it can be read in place at
+/// any offset
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct PodU16([u8; 2]);
+
+impl From<u16> for PodU16 {
+    fn from(n: u16) -> Self {
+        Self(n.to_le_bytes())
+    }
+}
+
+impl From<PodU16> for u16 {
+    fn from(pod: PodU16) -> Self {
+        Self::from_le_bytes(pod.0)
+    }
+}
+
+impl PartialEq<u16> for PodU16 {
+    fn eq(&self, n: &u16) -> bool {
+        u16::from(*self) == *n
+    }
+}
+
+impl fmt::Debug for PodU16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&u16::from(*self), f)
+    }
+}
+
+/// `u64` stored as little-endian bytes, so that it can be read in place at
+/// any offset
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct PodU64([u8; 8]);
+
+impl From<u64> for PodU64 {
+    fn from(n: u64) -> Self {
+        Self(n.to_le_bytes())
+    }
+}
+
+impl From<PodU64> for u64 {
+    fn from(pod: PodU64) -> Self {
+        Self::from_le_bytes(pod.0)
+    }
+}
+
+impl PartialEq<u64> for PodU64 {
+    fn eq(&self, n: &u64) -> bool {
+        u64::from(*self) == *n
+    }
+}
+
+impl fmt::Debug for PodU64 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&u64::from(*self), f)
+    }
+}
+
+unsafe impl Zeroable for PodStakeStatus {}
+unsafe impl Pod for PodStakeStatus {}
+unsafe impl Zeroable for PodU16 {}
+unsafe impl Pod for PodU16 {}
+unsafe impl Zeroable for PodU64 {}
+unsafe impl Pod for PodU64 {}
+
+/// Information about the singe validator stake account
+///
+/// Every field has an alignment of 1 and the struct has no padding, so its
+/// memory layout is its Borsh serialization and entries can be cast straight
+/// from account data
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorStakeInfo {
+    /// Validator vote account address
+    pub vote_account: Pubkey,
+
+    /// Status of the validator stake account
+    pub status: PodStakeStatus,
+
+    /// Share of the pool's total stake targeted to this validator, in basis
+    /// points
+    pub target_weight_bps: PodU16,
+
+    /// Amount of lamports in the validator stake account, active unless the
+    /// validator is being removed
+    /// Note that if `last_update_epoch` does not match the current epoch then this field may not
+    /// be accurate
+    pub active_stake_lamports: PodU64,
+
+    /// Amount of lamports in the validator's transient stake account, which is
+    /// activating or deactivating as part of a rebalance
+    /// Note that if `last_update_epoch` does not match the current epoch then this field may not
+    /// be accurate
+    pub transient_stake_lamports: PodU64,
+
+    /// Last epoch the `active_stake_lamports` field was updated
+    pub last_update_epoch: PodU64,
+
+    /// First epoch the validator accepts deposits and stake increases, after
+    /// the pool's `validator_onboarding_epochs` since it was added
+    pub eligible_epoch: PodU64,
+}
+
+unsafe impl Zeroable for ValidatorStakeInfo {}
+unsafe impl Pod for ValidatorStakeInfo {}
+
+impl ValidatorStakeInfo {
+    /// Length of a validator list entry, in memory as well as serialized
+    pub const LEN: usize = 32 + 1 + 2 + 8 + 8 + 8 + 8;
+
+    /// Check if the validator accepts deposits and rebalancing
+    pub fn is_active(&self) -> bool {
+        self.status == StakeStatus::Active
+    }
+
+    /// Check if the entry was last updated before `epoch`, in which case its
+    /// balances may not include that epoch's rewards
+    pub fn is_stale(&self, epoch: u64) -> bool {
+        u64::from(self.last_update_epoch) < epoch
+    }
+
+    /// Check that the entry was updated in `epoch`, so that its balances can
+    /// back deposits and withdrawals
+    pub(crate) fn check_up_to_date(&self, epoch: u64) -> Result<(), ProgramError> {
+        if self.is_stale(epoch) {
+            msg!(
+                "Validator {} was last updated in epoch {}, run UpdateValidatorListBalance for it first",
+                self.vote_account,
+                u64::from(self.last_update_epoch)
+            );
+            return Err(StakePoolError::ValidatorEntryStale.into());
+        }
+        Ok(())
+    }
+
+    /// Check that the validator's onboarding delay is over in `epoch`, so that
+    /// it accepts deposits and stake increases
+    pub(crate) fn check_eligible(&self, epoch: u64) -> Result<(), ProgramError> {
+        if epoch < u64::from(self.eligible_epoch) {
+            msg!(
+                "Validator {} is onboarding and accepts stake from epoch {}",
+                self.vote_account,
+                u64::from(self.eligible_epoch)
+            );
+            return Err(StakePoolError::ValidatorOnboarding.into());
+        }
+        Ok(())
+    }
+
+    /// Total lamports held by the validator, in its stake account and its
+    /// transient stake account
+    pub fn total_lamports(&self) -> Option<u64> {
+        u64::from(self.active_stake_lamports).checked_add(u64::from(self.transient_stake_lamports))
+    }
+
+    /// Lamports held by the validator above its target share of
+    /// `total_lamports`, negative if the validator is below its target
+    pub fn overweight_lamports(&self, total_lamports: u64) -> Option<i128> {
+        let target_lamports = Ratio::new(
+            u16::from(self.target_weight_bps) as u64,
+            MAX_TARGET_WEIGHT_BPS as u64,
+        )
+        .checked_apply_floor(total_lamports)?;
+        (self.total_lamports()? as i128).checked_sub(target_lamports as i128)
+    }
+}
+
+/// Lookups over the entries of a validator list, shared by the owned
+/// `ValidatorList` and the entries read in place from the account
+pub trait ValidatorListEntries {
+    /// Find the entry of the validator with the given vote account
+    fn find(&self, vote_account: &Pubkey) -> Option<&ValidatorStakeInfo>;
+
+    /// Find the entry of the validator with the given vote account, for update
+    fn find_mut(&mut self, vote_account: &Pubkey) -> Option<&mut ValidatorStakeInfo>;
+
+    /// Sum of the target weights of all validators, in basis points
+    fn total_target_weight_bps(&self) -> u64;
+
+    /// Find the active validator furthest above its target share of
+    /// `total_lamports` among those that can give up `lamports` while keeping
+    /// at least `minimum_lamports` in their stake account
+    fn most_overweight(
+        &self,
+        total_lamports: u64,
+        lamports: u64,
+        minimum_lamports: u64,
+    ) -> Option<&ValidatorStakeInfo>;
+}
+
+impl ValidatorListEntries for [ValidatorStakeInfo] {
+    fn find(&self, vote_account: &Pubkey) -> Option<&ValidatorStakeInfo> {
+        self.iter().find(|x| x.vote_account == *vote_account)
+    }
+
+    fn find_mut(&mut self, vote_account: &Pubkey) -> Option<&mut ValidatorStakeInfo> {
+        self.iter_mut().find(|x| x.vote_account == *vote_account)
+    }
+
+    fn total_target_weight_bps(&self) -> u64 {
+        self.iter()
+            .map(|x| u16::from(x.target_weight_bps) as u64)
+            .sum()
+    }
+
+    fn most_overweight(
+        &self,
+        total_lamports: u64,
+        lamports: u64,
+        minimum_lamports: u64,
+    ) -> Option<&ValidatorStakeInfo> {
+        self.iter()
+            .filter(|x| {
+                x.is_active()
+                    && u64::from(x.active_stake_lamports)
+                        .checked_sub(lamports)
+                        .map_or(false, |remaining| remaining >= minimum_lamports)
+            })
+            .max_by_key(|x| x.overweight_lamports(total_lamports).unwrap_or(i128::MIN))
+    }
+}
+
+impl ValidatorList {
+    /// Create an empty instance containing space for `max_validators`
+    pub fn new(max_validators: u32) -> Self {
+        Self {
+            account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
+            max_validators,
+            validators: vec![ValidatorStakeInfo::default(); max_validators as usize],
+        }
+    }
+
+    /// Calculate the number of validator entries that fit in the provided length
+    pub fn calculate_max_validators(buffer_length: usize) -> usize {
+        let header_size = ValidatorListHeader::LEN + 4;
+        buffer_length.saturating_sub(header_size) / ValidatorStakeInfo::LEN
+    }
+
+    /// Check if contains validator with particular pubkey
+    pub fn contains(&self, vote_account: &Pubkey) -> bool {
+        self.validators.find(vote_account).is_some()
     }
 
     /// Check if contains validator with particular pubkey
     pub fn find_mut(&mut self, vote_account: &Pubkey) -> Option<&mut ValidatorStakeInfo> {
-        self.validators
-            .iter_mut()
-            .find(|x| x.vote_account == *vote_account)
+        self.validators.find_mut(vote_account)
     }
     /// Check if contains validator with particular pubkey
     pub fn find(&self, vote_account: &Pubkey) -> Option<&ValidatorStakeInfo> {
+        self.validators.find(vote_account)
+    }
+
+    /// Sum of the target weights of all validators, in basis points
+    pub fn total_target_weight_bps(&self) -> u64 {
+        self.validators.total_target_weight_bps()
+    }
+
+    /// Find the active validator furthest above its target share of
+    /// `total_lamports` among those that can give up `lamports` while keeping
+    /// at least `minimum_lamports` in their stake account
+    pub fn most_overweight(
+        &self,
+        total_lamports: u64,
+        lamports: u64,
+        minimum_lamports: u64,
+    ) -> Option<&ValidatorStakeInfo> {
         self.validators
-            .iter()
-            .find(|x| x.vote_account == *vote_account)
+            .most_overweight(total_lamports, lamports, minimum_lamports)
+    }
+
+    /// Deserialize an initialized validator list, checking the account type
+    /// and layout version, then that the data fits its capacity, before
+    /// decoding the entries
+    pub fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let (header, validators) = ValidatorListHeader::deserialize_slice(data)?;
+        Ok(Self {
+            account_type: header.account_type,
+            version: header.version,
+            max_validators: header.max_validators,
+            validators: validators.to_vec(),
+        })
+    }
+
+    /// Check that validator list data is yet to be initialized, failing with
+    /// `AlreadyInUse` if it already holds an account
+    pub fn check_uninitialized(data: &[u8]) -> Result<(), ProgramError> {
+        let (account_type, _) = read_account_header(data)?;
+        if account_type != AccountType::Uninitialized {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+        Ok(())
     }
 
     /// Check if validator stake list is actually initialized as a validator stake list
     pub fn is_valid(&self) -> bool {
-        self.account_type == AccountType::ValidatorList
+        self.check_valid().is_ok()
+    }
+
+    /// Check that the account is a validator list with the current layout,
+    /// failing with `WrongAccountType` or `UnsupportedAccountVersion`
+    pub fn check_valid(&self) -> Result<(), ProgramError> {
+        check_account_header(
+            &self.account_type,
+            self.version,
+            AccountType::ValidatorList,
+            VALIDATOR_LIST_VERSION,
+        )
     }
 
     /// Check if the validator stake list is uninitialized
@@ -294,12 +1764,280 @@ impl ValidatorList {
     }
 }
 
+/// Per-depositor record enforcing the pool's deposit lock, stored at the
+/// program address derived from the pool and the depositor.
+///
+/// While the pool has a deposit lock, deposits must be signed by the
+/// depositor and mint their pool tokens into an escrow: a token account owned
+/// by the record.  Only `ClaimLockedPoolTokens`, signed by the depositor, moves
+/// them out of it, once the lock is over.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct DepositRecord {
+    /// Account type, must be DepositRecord currently
+    pub account_type: AccountType,
+
+    /// Stake pool the deposits were made into
+    pub stake_pool: Pubkey,
+
+    /// Signer of the deposits, the only one who may claim the escrowed pool
+    /// tokens
+    pub depositor: Pubkey,
+
+    /// First epoch in which the depositor may claim the escrowed pool tokens.
+    /// Fixed at deposit time, so raising the pool's lock does not extend
+    /// existing locks, and every deposit extends the lock of the whole escrow
+    pub locked_until_epoch: u64,
+}
+impl DepositRecord {
+    /// Check if the deposit record is actually initialized as a deposit record
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::DepositRecord
+    }
+
+    /// Check if the depositor may claim the escrowed pool tokens in the given
+    /// epoch
+    pub fn is_unlocked(&self, epoch: u64) -> bool {
+        epoch >= self.locked_until_epoch
+    }
+}
+
+/// Number of pool tokens, in base units, that `PriceOracle::rate` is quoted for
+pub const PRICE_ORACLE_RATE_PRECISION: u64 = 1_000_000_000;
+
+/// Pool token to SOL exchange rate of a stake pool, published on every
+/// `UpdateStakePoolBalance` at the program address derived from the pool, so
+/// that other programs can price pool tokens without decoding the pool
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct PriceOracle {
+    /// Account type, must be PriceOracle currently
+    pub account_type: AccountType,
+
+    /// Stake pool the rate is published for
+    pub stake_pool: Pubkey,
+
+    /// Lamports backing `PRICE_ORACLE_RATE_PRECISION` pool tokens, rounded down
+    pub rate: u64,
+
+    /// Confidence interval around `rate`, always 0 since the rate comes from
+    /// the pool's own balances
+    pub confidence: u64,
+
+    /// Epoch of the `UpdateStakePoolBalance` that published the rate
+    pub last_update_epoch: u64,
+}
+impl PriceOracle {
+    /// Check if the price oracle is actually initialized as a price oracle
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::PriceOracle
+    }
+
+    /// Deserialize an initialized price oracle, failing with
+    /// `WrongAccountType` for accounts of another kind
+    pub fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let price_oracle = Self::try_from_slice(data)?;
+        if !price_oracle.is_valid() {
+            return Err(StakePoolError::WrongAccountType.into());
+        }
+        Ok(price_oracle)
+    }
+
+    /// Check if the rate was last published before `epoch`, in which case
+    /// it does not include that epoch's rewards yet
+    pub fn is_stale(&self, epoch: u64) -> bool {
+        self.last_update_epoch < epoch
+    }
+
+    /// How current the published rate is in `epoch`
+    pub fn freshness(&self, epoch: Epoch) -> Freshness {
+        Freshness::new(self.last_update_epoch, epoch)
+    }
+
+    /// Lamports backing `pool_tokens` at the published rate, rounded down
+    pub fn calc_lamports(&self, pool_tokens: u64) -> Option<u64> {
+        Ratio::new(self.rate, PRICE_ORACLE_RATE_PRECISION).checked_apply_floor(pool_tokens)
+    }
+}
+
+/// Number of updated epochs a `RateHistory` keeps
+pub const RATE_HISTORY_LEN: usize = 32;
+
+/// Balances of a stake pool at the end of an `UpdateStakePoolBalance`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct RateHistoryEntry {
+    /// Epoch of the update
+    pub epoch: u64,
+
+    /// Total stake under management
+    pub total_stake_lamports: u64,
+
+    /// Total supply of pool tokens, including the epoch fee
+    pub pool_token_supply: u64,
+}
+impl RateHistoryEntry {
+    /// Lamports backing `PRICE_ORACLE_RATE_PRECISION` pool tokens, rounded
+    /// down, at 1:1 while the pool has no pool tokens
+    pub fn rate(&self) -> Option<u64> {
+        if self.pool_token_supply == 0 {
+            Some(PRICE_ORACLE_RATE_PRECISION)
+        } else {
+            Ratio::new(self.total_stake_lamports, self.pool_token_supply)
+                .checked_apply_floor(PRICE_ORACLE_RATE_PRECISION)
+        }
+    }
+}
+
+/// Balances of a stake pool over its last `RATE_HISTORY_LEN` updated epochs,
+/// recorded on every `UpdateStakePoolBalance` at the program address derived
+/// from the pool, so that the pool's yield can be computed on chain data
+/// alone
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct RateHistory {
+    /// Account type, must be RateHistory currently
+    pub account_type: AccountType,
+
+    /// Stake pool the balances are recorded for
+    pub stake_pool: Pubkey,
+
+    /// Number of epochs recorded since the history was created.  The latest
+    /// entry is at index `(entries_recorded - 1) % RATE_HISTORY_LEN`, and the
+    /// entries wrap around once the history is full.
+    pub entries_recorded: u64,
+
+    /// Ring buffer of the recorded entries
+    pub entries: [RateHistoryEntry; RATE_HISTORY_LEN],
+}
+impl RateHistory {
+    /// Create an empty rate history for `stake_pool`
+    pub fn new(stake_pool: Pubkey) -> Self {
+        Self {
+            account_type: AccountType::RateHistory,
+            stake_pool,
+            ..Self::default()
+        }
+    }
+
+    /// Check if the rate history is actually initialized as a rate history
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::RateHistory
+    }
+
+    /// Deserialize an initialized rate history, failing with
+    /// `WrongAccountType` for accounts of another kind
+    pub fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let rate_history = Self::try_from_slice(data)?;
+        if !rate_history.is_valid() {
+            return Err(StakePoolError::WrongAccountType.into());
+        }
+        Ok(rate_history)
+    }
+
+    fn index(position: u64) -> usize {
+        (position % RATE_HISTORY_LEN as u64) as usize
+    }
+
+    /// Latest recorded entry, if any
+    pub fn latest(&self) -> Option<&RateHistoryEntry> {
+        self.iter().next()
+    }
+
+    /// Recorded entries, latest first
+    pub fn iter(&self) -> impl Iterator<Item = &RateHistoryEntry> {
+        let len = self.entries_recorded.min(RATE_HISTORY_LEN as u64);
+        (0..len).map(move |i| &self.entries[Self::index(self.entries_recorded - 1 - i)])
+    }
+
+    /// Record the balances of an update, replacing the latest entry if the
+    /// pool was already updated in the same epoch
+    pub fn record(&mut self, entry: RateHistoryEntry) {
+        if self
+            .latest()
+            .map_or(false, |latest| latest.epoch == entry.epoch)
+        {
+            self.entries[Self::index(self.entries_recorded - 1)] = entry;
+        } else {
+            self.entries[Self::index(self.entries_recorded)] = entry;
+            self.entries_recorded += 1;
+        }
+    }
+}
+
+/// Pool of the stake of a single validator, without a validator list,
+/// reserve, manager or fees, stored at the program address derived from the
+/// validator's vote account.  The pool's stake account, pool token mint and
+/// withdraw authority are all derived from that address.
+///
+/// The stake account is seeded with `MINIMUM_ACTIVE_STAKE` at
+/// initialization, which is never withdrawn so that deposits can always be
+/// merged into it.  Everything above the seed and the rent-exempt reserve
+/// backs the pool tokens, including the rewards earned by the seed.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct SingleValidatorPool {
+    /// Account type, must be SingleValidatorPool currently
+    pub account_type: AccountType,
+
+    /// Vote account of the validator all stake is delegated to
+    pub vote_account: Pubkey,
+}
+impl SingleValidatorPool {
+    /// Check if the account is actually initialized as a single-validator pool
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::SingleValidatorPool
+    }
+
+    /// Deserialize an initialized single-validator pool, failing with
+    /// `WrongAccountType` for accounts of another kind
+    pub fn deserialize_checked(data: &[u8]) -> Result<Self, ProgramError> {
+        let pool = Self::try_from_slice(data)?;
+        if !pool.is_valid() {
+            return Err(StakePoolError::WrongAccountType.into());
+        }
+        Ok(pool)
+    }
+
+    /// Lamports backing the pool tokens, out of the lamports of the pool's
+    /// stake account
+    pub fn calc_pool_lamports(stake_lamports: u64, rent_exempt_reserve: u64) -> Option<u64> {
+        stake_lamports
+            .checked_sub(rent_exempt_reserve)?
+            .checked_sub(MINIMUM_ACTIVE_STAKE)
+    }
+
+    /// Pool tokens minted for a deposit of `lamports`, rounded down, at 1:1
+    /// while the pool has no pool tokens, and not at all while its pool tokens
+    /// have nothing backing them
+    pub fn calc_pool_tokens_for_deposit(
+        pool_lamports: u64,
+        pool_token_supply: u64,
+        lamports: u64,
+    ) -> Option<u64> {
+        if pool_token_supply == 0 {
+            Some(lamports)
+        } else if pool_lamports == 0 {
+            None
+        } else {
+            Ratio::new(pool_token_supply, pool_lamports).checked_apply_floor(lamports)
+        }
+    }
+
+    /// Lamports paid out for burning `pool_tokens`, rounded down
+    pub fn calc_lamports_for_withdraw(
+        pool_lamports: u64,
+        pool_token_supply: u64,
+        pool_tokens: u64,
+    ) -> Option<u64> {
+        Ratio::new(pool_lamports, pool_token_supply).checked_apply_floor(pool_tokens)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use {
-        super::*,
-        crate::borsh::{get_instance_packed_len, try_from_slice_unchecked},
-        proptest::prelude::*,
+        super::*, crate::borsh::get_instance_packed_len, proptest::prelude::*,
         solana_program::borsh::get_packed_len,
     };
 
@@ -310,56 +2048,1019 @@ mod test {
         // Not initialized
         let stake_list = ValidatorList {
             account_type: AccountType::Uninitialized,
+            version: 0,
             max_validators: 0,
             validators: vec![],
         };
         let mut byte_vec = vec![0u8; size];
         let mut bytes = byte_vec.as_mut_slice();
         stake_list.serialize(&mut bytes).unwrap();
-        let stake_list_unpacked = try_from_slice_unchecked::<ValidatorList>(&byte_vec).unwrap();
+        let stake_list_unpacked = ValidatorList::deserialize(&mut byte_vec.as_slice()).unwrap();
         assert_eq!(stake_list_unpacked, stake_list);
 
         // Empty
         let stake_list = ValidatorList {
             account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
             max_validators: 0,
             validators: vec![],
         };
         let mut byte_vec = vec![0u8; size];
         let mut bytes = byte_vec.as_mut_slice();
         stake_list.serialize(&mut bytes).unwrap();
-        let stake_list_unpacked = try_from_slice_unchecked::<ValidatorList>(&byte_vec).unwrap();
+        let stake_list_unpacked = ValidatorList::deserialize(&mut byte_vec.as_slice()).unwrap();
         assert_eq!(stake_list_unpacked, stake_list);
 
         // With several accounts
         let stake_list = ValidatorList {
             account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
             max_validators,
             validators: vec![
                 ValidatorStakeInfo {
                     vote_account: Pubkey::new_from_array([1; 32]),
-                    stake_lamports: 123456789,
-                    last_update_epoch: 987654321,
+                    status: StakeStatus::Active.into(),
+                    target_weight_bps: 5000.into(),
+                    active_stake_lamports: 123456789.into(),
+                    transient_stake_lamports: 1111111.into(),
+                    last_update_epoch: 987654321.into(),
+                    eligible_epoch: 987654320.into(),
                 },
                 ValidatorStakeInfo {
                     vote_account: Pubkey::new_from_array([2; 32]),
-                    stake_lamports: 998877665544,
-                    last_update_epoch: 11223445566,
+                    status: StakeStatus::ReadyForRemoval.into(),
+                    target_weight_bps: 0.into(),
+                    active_stake_lamports: 998877665544.into(),
+                    transient_stake_lamports: 222222222.into(),
+                    last_update_epoch: 11223445566.into(),
+                    eligible_epoch: 11223445570.into(),
                 },
                 ValidatorStakeInfo {
                     vote_account: Pubkey::new_from_array([3; 32]),
-                    stake_lamports: 0,
-                    last_update_epoch: 999999999999999,
+                    status: StakeStatus::Active.into(),
+                    target_weight_bps: 10_000.into(),
+                    active_stake_lamports: 0.into(),
+                    transient_stake_lamports: 0.into(),
+                    last_update_epoch: 999999999999999.into(),
+                    eligible_epoch: 0.into(),
                 },
             ],
         };
         let mut byte_vec = vec![0u8; size];
         let mut bytes = byte_vec.as_mut_slice();
         stake_list.serialize(&mut bytes).unwrap();
-        let stake_list_unpacked = try_from_slice_unchecked::<ValidatorList>(&byte_vec).unwrap();
+        let stake_list_unpacked = ValidatorList::deserialize(&mut byte_vec.as_slice()).unwrap();
         assert_eq!(stake_list_unpacked, stake_list);
     }
 
+    #[test]
+    fn test_validator_list_in_place() {
+        assert_eq!(
+            std::mem::size_of::<ValidatorStakeInfo>(),
+            ValidatorStakeInfo::LEN
+        );
+        assert_eq!(
+            get_packed_len::<ValidatorStakeInfo>(),
+            ValidatorStakeInfo::LEN
+        );
+        assert_eq!(
+            get_instance_packed_len(&ValidatorList::new(0)).unwrap(),
+            ValidatorListHeader::LEN + 4
+        );
+
+        let entry = |index: u8, status: StakeStatus| ValidatorStakeInfo {
+            vote_account: Pubkey::new_from_array([index; 32]),
+            status: status.into(),
+            target_weight_bps: (index as u16 * 1_000).into(),
+            active_stake_lamports: (index as u64 * 1_000_000_000).into(),
+            transient_stake_lamports: 0.into(),
+            last_update_epoch: 42.into(),
+            eligible_epoch: 40.into(),
+        };
+        let mut validator_list = ValidatorList::new(4);
+        validator_list.validators = vec![
+            entry(1, StakeStatus::Active),
+            entry(2, StakeStatus::ReadyForRemoval),
+            entry(3, StakeStatus::Active),
+        ];
+        let mut data = vec![0u8; get_instance_packed_len(&ValidatorList::new(4)).unwrap()];
+        validator_list.serialize(&mut data.as_mut_slice()).unwrap();
+
+        // Reads in place match Borsh
+        let (header, validators) = ValidatorListHeader::deserialize_slice(&data).unwrap();
+        assert_eq!(header.max_validators, 4);
+        assert_eq!(validators, validator_list.validators.as_slice());
+        let vote_account = Pubkey::new_from_array([3; 32]);
+        let mut item = ValidatorListHeader::find_entry(&data, &vote_account)
+            .unwrap()
+            .unwrap();
+        assert_eq!(item, entry(3, StakeStatus::Active));
+        assert_eq!(
+            ValidatorListHeader::find_entry(&data, &Pubkey::new_unique()),
+            Ok(None)
+        );
+
+        // So do writes
+        item.transient_stake_lamports = 5.into();
+        ValidatorListHeader::update_entry(&mut data, &item).unwrap();
+        validator_list
+            .find_mut(&vote_account)
+            .unwrap()
+            .transient_stake_lamports = 5.into();
+        {
+            let (_, mut validators) = ValidatorListHeader::deserialize_vec(&mut data).unwrap();
+            validators.push(entry(4, StakeStatus::Active)).unwrap();
+            assert_eq!(
+                validators.push(entry(5, StakeStatus::Active)),
+                Err(ProgramError::AccountDataTooSmall)
+            );
+            validators
+                .retain(|item: &ValidatorStakeInfo| item.status != StakeStatus::ReadyForRemoval)
+                .unwrap();
+        }
+        validator_list
+            .validators
+            .push(entry(4, StakeStatus::Active));
+        validator_list
+            .validators
+            .retain(|item| item.status != StakeStatus::ReadyForRemoval);
+        assert_eq!(
+            ValidatorList::deserialize_checked(&data).unwrap(),
+            validator_list
+        );
+        assert_eq!(
+            ValidatorListHeader::update_entry(&mut data, &entry(2, StakeStatus::Active)),
+            Err(StakePoolError::ValidatorNotFound.into())
+        );
+
+        // Only initialized validator lists are read in place
+        let mut data = vec![0u8; data.len()];
+        assert_eq!(
+            ValidatorListHeader::deserialize_slice(&data).unwrap_err(),
+            StakePoolError::WrongAccountType.into()
+        );
+        assert!(ValidatorListHeader::deserialize_vec(&mut data).is_err());
+    }
+
+    #[test]
+    fn test_account_header() {
+        let stake_pool = StakePool {
+            account_type: AccountType::StakePool,
+            version: STAKE_POOL_VERSION,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.check_valid(), Ok(()));
+        assert!(stake_pool.is_valid());
+
+        let uninitialized = StakePool::default();
+        assert_eq!(
+            uninitialized.check_valid(),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+        assert!(uninitialized.is_uninitialized());
+
+        let validator_list = ValidatorList::new(1);
+        assert_eq!(validator_list.check_valid(), Ok(()));
+
+        // a validator list passed in place of a stake pool
+        let mut wrong_type = stake_pool.clone();
+        wrong_type.account_type = AccountType::ValidatorList;
+        assert_eq!(
+            wrong_type.check_valid(),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+        let mut wrong_type = validator_list.clone();
+        wrong_type.account_type = AccountType::StakePool;
+        assert_eq!(
+            wrong_type.check_valid(),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+
+        let mut wrong_version = stake_pool;
+        wrong_version.version = STAKE_POOL_VERSION + 1;
+        assert_eq!(
+            wrong_version.check_valid(),
+            Err(StakePoolError::UnsupportedAccountVersion.into())
+        );
+        assert!(!wrong_version.is_valid());
+        let mut wrong_version = validator_list;
+        wrong_version.version = 0;
+        assert_eq!(
+            wrong_version.check_valid(),
+            Err(StakePoolError::UnsupportedAccountVersion.into())
+        );
+
+        // the type and version are the first two bytes of both layouts
+        let data = ValidatorList::new(1).try_to_vec().unwrap();
+        assert_eq!(data[0], AccountType::ValidatorList as u8);
+        assert_eq!(data[1], VALIDATOR_LIST_VERSION);
+
+        // account confusion is caught before decoding the rest of the data
+        assert_eq!(
+            StakePool::deserialize_checked(&data),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+        assert_eq!(
+            ValidatorList::deserialize_checked(&data).unwrap(),
+            ValidatorList::new(1)
+        );
+        let data = StakePool {
+            account_type: AccountType::StakePool,
+            version: STAKE_POOL_VERSION,
+            ..StakePool::default()
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(
+            ValidatorList::deserialize_checked(&data),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+        assert!(StakePool::deserialize_checked(&data).is_ok());
+        assert_eq!(
+            StakePool::deserialize_checked(&[]),
+            Err(StakePoolError::AccountDataTooShort.into())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_any_version() {
+        let stake_pool = StakePool {
+            account_type: AccountType::StakePool,
+            version: STAKE_POOL_VERSION,
+            total_stake_lamports: 123,
+            ..StakePool::default()
+        };
+        let data = stake_pool.try_to_vec().unwrap();
+        assert_eq!(
+            StakePool::deserialize_any_version(&data),
+            Ok(VersionedStakePool::Current(stake_pool.clone()))
+        );
+
+        // a later version with an appended field
+        let mut newer_data = data.clone();
+        newer_data[1] = STAKE_POOL_VERSION + 1;
+        newer_data.extend_from_slice(&[7; 8]);
+        assert_eq!(
+            StakePool::deserialize_checked(&newer_data),
+            Err(StakePoolError::UnsupportedAccountVersion.into())
+        );
+        let versioned = StakePool::deserialize_any_version(&newer_data).unwrap();
+        assert_eq!(
+            versioned,
+            VersionedStakePool::Newer {
+                version: STAKE_POOL_VERSION + 1,
+                stake_pool: StakePool {
+                    version: STAKE_POOL_VERSION + 1,
+                    ..stake_pool.clone()
+                },
+            }
+        );
+        assert_eq!(versioned.stake_pool().total_stake_lamports, 123);
+
+        // appended fields without a version bump
+        let mut long_data = data.clone();
+        long_data.push(0);
+        assert_eq!(
+            StakePool::deserialize_any_version(&long_data),
+            Ok(VersionedStakePool::Newer {
+                version: STAKE_POOL_VERSION,
+                stake_pool: stake_pool.clone(),
+            })
+        );
+
+        assert_eq!(
+            StakePool::deserialize_any_version(&data[..StakePool::LEN - 1]),
+            Err(StakePoolError::AccountDataTooShort.into())
+        );
+        let mut older_data = data.clone();
+        older_data[1] = 0;
+        assert_eq!(
+            StakePool::deserialize_any_version(&older_data),
+            Err(StakePoolError::UnsupportedAccountVersion.into())
+        );
+        let validator_list_data = ValidatorList::new(1).try_to_vec().unwrap();
+        assert_eq!(
+            StakePool::deserialize_any_version(&validator_list_data),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+    }
+
+    #[test]
+    fn test_data_len() {
+        assert_eq!(StakePool::LEN, get_packed_len::<StakePool>());
+        let data = StakePool {
+            account_type: AccountType::StakePool,
+            version: STAKE_POOL_VERSION,
+            ..StakePool::default()
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(
+            StakePool::deserialize_checked(&data[..StakePool::LEN - 1]),
+            Err(StakePoolError::AccountDataTooShort.into())
+        );
+        let mut long_data = data.clone();
+        long_data.push(0);
+        assert_eq!(
+            StakePool::deserialize_checked(&long_data),
+            Err(StakePoolError::AccountDataTooLong.into())
+        );
+        assert_eq!(
+            StakePool::deserialize_uninitialized(&data),
+            Err(StakePoolError::AlreadyInUse.into())
+        );
+        assert_eq!(
+            StakePool::deserialize_uninitialized(&[0; StakePool::LEN]),
+            Ok(StakePool::default())
+        );
+        assert_eq!(
+            StakePool::deserialize_uninitialized(&[0; StakePool::LEN + 1]),
+            Err(StakePoolError::AccountDataTooLong.into())
+        );
+
+        // validator lists may end with less than an entry's worth of unused
+        // bytes
+        let data = ValidatorList::new(2).try_to_vec().unwrap();
+        assert_eq!(data.len(), ValidatorListHeader::data_len(2));
+        for extra_len in 0..ValidatorStakeInfo::LEN {
+            let mut long_data = data.clone();
+            long_data.resize(data.len() + extra_len, 0);
+            assert_eq!(
+                ValidatorList::deserialize_checked(&long_data).unwrap(),
+                ValidatorList::new(2)
+            );
+        }
+        let mut long_data = data.clone();
+        long_data.resize(data.len() + ValidatorStakeInfo::LEN, 0);
+        assert_eq!(
+            ValidatorList::deserialize_checked(&long_data),
+            Err(StakePoolError::AccountDataTooLong.into())
+        );
+        assert_eq!(
+            ValidatorList::deserialize_checked(&data[..data.len() - 1]),
+            Err(StakePoolError::AccountDataTooShort.into())
+        );
+        assert_eq!(
+            ValidatorList::deserialize_checked(&data[..3]),
+            Err(StakePoolError::AccountDataTooShort.into())
+        );
+        // more entries than the capacity is corrupt data
+        let mut corrupt_data = data.clone();
+        corrupt_data[ValidatorListHeader::LEN] = 3;
+        assert_eq!(
+            ValidatorList::deserialize_checked(&corrupt_data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        assert_eq!(ValidatorList::check_uninitialized(&[0; 10]), Ok(()));
+        assert_eq!(
+            ValidatorList::check_uninitialized(&data),
+            Err(StakePoolError::AlreadyInUse.into())
+        );
+    }
+
+    #[test]
+    fn test_most_overweight() {
+        let validator =
+            |seed: u8, target_weight_bps: u16, active_stake_lamports: u64| ValidatorStakeInfo {
+                vote_account: Pubkey::new_from_array([seed; 32]),
+                target_weight_bps: target_weight_bps.into(),
+                active_stake_lamports: active_stake_lamports.into(),
+                ..ValidatorStakeInfo::default()
+            };
+        let mut validator_list = ValidatorList {
+            account_type: AccountType::ValidatorList,
+            version: VALIDATOR_LIST_VERSION,
+            max_validators: 3,
+            validators: vec![
+                validator(1, 5_000, 500),
+                validator(2, 3_000, 300),
+                validator(3, 2_000, 200),
+            ],
+        };
+        assert_eq!(validator_list.total_target_weight_bps(), 10_000);
+        assert!(validator_list
+            .validators
+            .iter()
+            .all(|x| x.overweight_lamports(1_000) == Some(0)));
+
+        validator_list.validators[2].transient_stake_lamports = 100.into();
+        assert_eq!(
+            validator_list.validators[2].overweight_lamports(1_100),
+            Some(80)
+        );
+        assert_eq!(
+            validator_list.validators[0].overweight_lamports(1_100),
+            Some(-50)
+        );
+        assert_eq!(
+            validator_list
+                .most_overweight(1_100, 10, 0)
+                .unwrap()
+                .vote_account,
+            validator_list.validators[2].vote_account
+        );
+        // the most overweight validator cannot cover the withdrawal
+        assert_eq!(
+            validator_list
+                .most_overweight(1_100, 150, 100)
+                .unwrap()
+                .vote_account,
+            validator_list.validators[1].vote_account
+        );
+
+        validator_list.validators[2].status = StakeStatus::ReadyForRemoval.into();
+        assert_eq!(
+            validator_list
+                .most_overweight(1_100, 10, 0)
+                .unwrap()
+                .vote_account,
+            validator_list.validators[1].vote_account
+        );
+        assert!(validator_list.most_overweight(1_100, 501, 0).is_none());
+    }
+
+    #[test]
+    fn test_pool_token_conversions() {
+        let stake_pool = StakePool {
+            total_stake_lamports: 3,
+            pool_token_supply: 2,
+            ..StakePool::default()
+        };
+        // a 1-lamport deposit rounds down to nothing, in favor of the pool
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), Some(0));
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(2), Some(1));
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_ceil(1), Some(1));
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_ceil(2), Some(2));
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_ceil(3), Some(2));
+        assert_eq!(stake_pool.calc_pool_tokens_for_withdraw(1), Some(1));
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_floor(1), Some(1));
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_ceil(1), Some(2));
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_floor(2), Some(3));
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_ceil(2), Some(3));
+
+        // empty pools convert 1:1, even with lamports left in the reserve
+        let stake_pool = StakePool {
+            total_stake_lamports: 1_000,
+            pool_token_supply: 0,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), Some(1));
+        let stake_pool = StakePool::default();
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), Some(1));
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_ceil(1), Some(1));
+        assert_eq!(stake_pool.calc_pool_tokens_for_withdraw(1), None);
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_floor(1), None);
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_ceil(1), None);
+
+        // pool tokens without any stake backing them mint nothing more, and
+        // are worth nothing
+        let stake_pool = StakePool {
+            total_stake_lamports: 0,
+            pool_token_supply: 1_000,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), None);
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_ceil(1), None);
+        assert_eq!(
+            stake_pool.calc_lamports_withdraw_amount_floor(1_000),
+            Some(0)
+        );
+        assert_eq!(stake_pool.price_oracle_rate(), Some(0));
+    }
+
+    #[test]
+    fn test_balance_updates() {
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 3,
+            pool_token_supply: 2,
+            ..StakePool::default()
+        };
+        stake_pool.credit_deposit(3, 2).unwrap();
+        assert_eq!(stake_pool.total_stake_lamports, 6);
+        assert_eq!(stake_pool.pool_token_supply, 4);
+        stake_pool.debit_withdrawal(6, 4).unwrap();
+        assert_eq!(stake_pool.total_stake_lamports, 0);
+        assert_eq!(stake_pool.pool_token_supply, 0);
+
+        let calculation_failure: Result<(), ProgramError> =
+            Err(StakePoolError::CalculationFailure.into());
+        assert_eq!(stake_pool.debit_withdrawal(1, 0), calculation_failure);
+        assert_eq!(stake_pool.debit_withdrawal(0, 1), calculation_failure);
+
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 1,
+            pool_token_supply: u64::MAX,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.credit_deposit(0, 1), calculation_failure);
+        let mut stake_pool = StakePool {
+            total_stake_lamports: u64::MAX,
+            pool_token_supply: 1,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.credit_deposit(1, 0), calculation_failure);
+    }
+
+    #[test]
+    fn test_pool_token_conversions_extreme_supplies() {
+        let stake_pool = StakePool {
+            total_stake_lamports: u64::MAX,
+            pool_token_supply: u64::MAX,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), Some(1));
+        assert_eq!(
+            stake_pool.calc_pool_tokens_for_deposit_floor(u64::MAX),
+            Some(u64::MAX)
+        );
+        assert_eq!(
+            stake_pool.calc_lamports_withdraw_amount_floor(u64::MAX),
+            Some(u64::MAX)
+        );
+
+        // pool tokens worth a lot of lamports each
+        let stake_pool = StakePool {
+            total_stake_lamports: u64::MAX,
+            pool_token_supply: 1,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(1), Some(0));
+        assert_eq!(
+            stake_pool.calc_pool_tokens_for_deposit_floor(u64::MAX),
+            Some(1)
+        );
+        assert_eq!(stake_pool.calc_pool_tokens_for_withdraw(1), Some(1));
+        assert_eq!(
+            stake_pool.calc_lamports_withdraw_amount_floor(1),
+            Some(u64::MAX)
+        );
+        assert_eq!(stake_pool.calc_lamports_withdraw_amount_floor(2), None);
+
+        // pool tokens worth very little
+        let stake_pool = StakePool {
+            total_stake_lamports: 1,
+            pool_token_supply: u64::MAX,
+            ..StakePool::default()
+        };
+        assert_eq!(
+            stake_pool.calc_pool_tokens_for_deposit_floor(1),
+            Some(u64::MAX)
+        );
+        assert_eq!(stake_pool.calc_pool_tokens_for_deposit_floor(2), None);
+        assert_eq!(
+            stake_pool.calc_lamports_withdraw_amount_floor(u64::MAX - 1),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_calc_fee_amount() {
+        let stake_pool = StakePool {
+            total_stake_lamports: 3,
+            pool_token_supply: 2,
+            epoch_fee: Fee {
+                numerator: 3,
+                denominator: 4,
+            },
+            ..StakePool::default()
+        };
+        // 2 lamports are worth 4/3 pool tokens, 3/4 of which is exactly 1;
+        // rounding the pool tokens first would only give 0
+        assert_eq!(stake_pool.calc_fee_amount(2), Some(1));
+        assert_eq!(stake_pool.calc_fee_amount(1), Some(0));
+
+        let stake_pool = StakePool {
+            total_stake_lamports: u64::MAX,
+            pool_token_supply: u64::MAX,
+            epoch_fee: Fee {
+                numerator: u64::MAX,
+                denominator: u64::MAX,
+            },
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_fee_amount(u64::MAX), Some(u64::MAX));
+
+        let stake_pool = StakePool {
+            total_stake_lamports: 1_000,
+            pool_token_supply: 1_000,
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_fee_amount(1_000), Some(0));
+
+        // an update of a pool whose stake is all gone takes no fee
+        let stake_pool = StakePool {
+            total_stake_lamports: 0,
+            pool_token_supply: 1_000,
+            epoch_fee: Fee {
+                numerator: 1,
+                denominator: 10,
+            },
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.calc_fee_amount(0), Some(0));
+    }
+
+    #[test]
+    fn test_price_oracle_rate() {
+        let stake_pool = StakePool::default();
+        assert_eq!(
+            stake_pool.price_oracle_rate(),
+            Some(PRICE_ORACLE_RATE_PRECISION)
+        );
+
+        let stake_pool = StakePool {
+            total_stake_lamports: 3_000_000_000,
+            pool_token_supply: 2_000_000_000,
+            ..StakePool::default()
+        };
+        let rate = stake_pool.price_oracle_rate().unwrap();
+        assert_eq!(rate, 1_500_000_000);
+
+        let price_oracle = PriceOracle {
+            account_type: AccountType::PriceOracle,
+            rate,
+            last_update_epoch: 10,
+            ..PriceOracle::default()
+        };
+        assert_eq!(
+            price_oracle.calc_lamports(2_000_000_000),
+            stake_pool.calc_lamports_withdraw_amount_floor(2_000_000_000)
+        );
+        assert_eq!(price_oracle.calc_lamports(1), Some(1));
+        assert!(!price_oracle.is_stale(10));
+        assert!(price_oracle.is_stale(11));
+
+        let data = price_oracle.try_to_vec().unwrap();
+        assert_eq!(PriceOracle::deserialize_checked(&data), Ok(price_oracle));
+        let data = PriceOracle::default().try_to_vec().unwrap();
+        assert_eq!(
+            PriceOracle::deserialize_checked(&data),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+    }
+
+    #[test]
+    fn test_rate_history() {
+        let stake_pool = Pubkey::new_unique();
+        let mut rate_history = RateHistory::new(stake_pool);
+        assert_eq!(
+            get_instance_packed_len(&rate_history).unwrap(),
+            get_packed_len::<RateHistory>()
+        );
+        assert_eq!(rate_history.latest(), None);
+
+        let entry = |epoch| RateHistoryEntry {
+            epoch,
+            total_stake_lamports: 1_000 + epoch,
+            pool_token_supply: 1_000,
+        };
+        rate_history.record(entry(1));
+        rate_history.record(entry(2));
+        assert_eq!(
+            rate_history.iter().collect::<Vec<_>>(),
+            [&entry(2), &entry(1)]
+        );
+
+        // a second update in the same epoch replaces its entry
+        let updated = RateHistoryEntry {
+            pool_token_supply: 999,
+            ..entry(2)
+        };
+        rate_history.record(updated);
+        assert_eq!(rate_history.entries_recorded, 2);
+        assert_eq!(rate_history.latest(), Some(&updated));
+
+        // past the buffer length, the oldest entries are overwritten
+        for epoch in 3..=RATE_HISTORY_LEN as u64 + 5 {
+            rate_history.record(entry(epoch));
+        }
+        let epochs: Vec<u64> = rate_history.iter().map(|entry| entry.epoch).collect();
+        let expected: Vec<u64> = (6..=RATE_HISTORY_LEN as u64 + 5).rev().collect();
+        assert_eq!(epochs, expected);
+
+        assert_eq!(entry(0).rate(), Some(1_000_000_000));
+        assert_eq!(entry(500).rate(), Some(1_500_000_000));
+
+        let data = rate_history.try_to_vec().unwrap();
+        assert_eq!(RateHistory::deserialize_checked(&data), Ok(rate_history));
+        let data = RateHistory::default().try_to_vec().unwrap();
+        assert_eq!(
+            RateHistory::deserialize_checked(&data),
+            Err(StakePoolError::WrongAccountType.into())
+        );
+    }
+
+    #[test]
+    fn test_fee_splits() {
+        let stake_pool = StakePool {
+            secondary_fee_split: Fee {
+                numerator: 1,
+                denominator: 4,
+            },
+            sol_deposit_fee: Fee {
+                numerator: 1,
+                denominator: 100,
+            },
+            ..StakePool::default()
+        };
+        // without destinations, fees keep going to the manager in pool tokens
+        assert_eq!(stake_pool.split_epoch_fee(1_000), Some((1_000, 0)));
+        assert_eq!(
+            stake_pool.split_sol_deposit_lamports(1_000),
+            Some((1_000, 0))
+        );
+
+        let stake_pool = StakePool {
+            secondary_fee_account: Pubkey::new_unique(),
+            sol_deposit_fee_treasury: Pubkey::new_unique(),
+            ..stake_pool
+        };
+        assert_eq!(stake_pool.split_epoch_fee(1_000), Some((750, 250)));
+        assert_eq!(stake_pool.split_epoch_fee(3), Some((3, 0)));
+        assert_eq!(
+            stake_pool.split_sol_deposit_lamports(1_000),
+            Some((990, 10))
+        );
+        assert_eq!(stake_pool.split_sol_deposit_lamports(99), Some((99, 0)));
+    }
+
+    #[test]
+    fn test_epoch_net_outflow() {
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 1_000,
+            ..StakePool::default()
+        };
+        // uncapped, withdrawals are only counted
+        stake_pool.record_epoch_outflow(1_000).unwrap();
+        assert_eq!(stake_pool.epoch_withdrawn_lamports, 1_000);
+
+        let mut stake_pool = StakePool {
+            total_stake_lamports: 1_000,
+            max_epoch_net_outflow_bps: 1_000,
+            ..StakePool::default()
+        };
+        stake_pool.record_epoch_outflow(60).unwrap();
+        stake_pool.total_stake_lamports -= 60;
+        stake_pool.record_epoch_outflow(40).unwrap();
+        stake_pool.total_stake_lamports -= 40;
+        assert_eq!(
+            stake_pool.record_epoch_outflow(1),
+            Err(StakePoolError::EpochNetOutflowExceeded.into())
+        );
+        assert_eq!(stake_pool.epoch_withdrawn_lamports, 100);
+
+        // deposits make room for more withdrawals, but do not raise the cap
+        stake_pool.record_epoch_inflow(50).unwrap();
+        stake_pool.total_stake_lamports += 50;
+        stake_pool.record_epoch_outflow(50).unwrap();
+        stake_pool.total_stake_lamports -= 50;
+        assert_eq!(
+            stake_pool.record_epoch_outflow(1),
+            Err(StakePoolError::EpochNetOutflowExceeded.into())
+        );
+    }
+
+    #[test]
+    fn test_previews_need_fresh_pool() {
+        let stake_pool = StakePool {
+            total_stake_lamports: 2_000,
+            pool_token_supply: 1_000,
+            last_update_epoch: 10,
+            stake_deposit_fee: Fee {
+                numerator: 1,
+                denominator: 100,
+            },
+            withdrawal_fee: Fee {
+                numerator: 1,
+                denominator: 50,
+            },
+            ..StakePool::default()
+        };
+        assert_eq!(stake_pool.freshness(10), Freshness::Fresh);
+        assert_eq!(stake_pool.freshness(10).epochs_behind(), 0);
+        assert_eq!(stake_pool.preview_stake_deposit(10, 2_000), Ok((990, 10)));
+        assert_eq!(stake_pool.preview_withdrawal(10, 500), Ok((980, 10)));
+
+        assert_eq!(
+            stake_pool.freshness(12),
+            Freshness::Stale {
+                last_update_epoch: 10,
+                epochs_behind: 2,
+            }
+        );
+        assert_eq!(
+            stake_pool.preview_stake_deposit(12, 2_000),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+        assert_eq!(
+            stake_pool.preview_withdrawal(11, 500),
+            Err(StakePoolError::StakeListAndPoolOutOfDate)
+        );
+
+        let price_oracle = PriceOracle {
+            last_update_epoch: 10,
+            ..PriceOracle::default()
+        };
+        assert!(price_oracle.freshness(10).is_fresh());
+        assert_eq!(price_oracle.freshness(11).epochs_behind(), 1);
+    }
+
+    #[test]
+    fn test_validator_entry_stale() {
+        let validator = ValidatorStakeInfo {
+            last_update_epoch: 10.into(),
+            ..ValidatorStakeInfo::default()
+        };
+        assert!(!validator.is_stale(10));
+        assert_eq!(validator.check_up_to_date(10), Ok(()));
+        assert!(validator.is_stale(11));
+        assert_eq!(
+            validator.check_up_to_date(11),
+            Err(StakePoolError::ValidatorEntryStale.into())
+        );
+    }
+
+    #[test]
+    fn test_validator_eligible() {
+        let validator = ValidatorStakeInfo {
+            eligible_epoch: 10.into(),
+            ..ValidatorStakeInfo::default()
+        };
+        assert_eq!(
+            validator.check_eligible(9),
+            Err(StakePoolError::ValidatorOnboarding.into())
+        );
+        assert_eq!(validator.check_eligible(10), Ok(()));
+        assert_eq!(validator.check_eligible(11), Ok(()));
+    }
+
+    #[test]
+    fn test_pending_manager_action_round_trip() {
+        let fee = Fee {
+            numerator: 1,
+            denominator: 20,
+        };
+        let actions = [
+            ManagerAction::SetFee(FeeType::Epoch(fee)),
+            ManagerAction::SetFee(FeeType::StakeDeposit(fee)),
+            ManagerAction::SetFee(FeeType::SolDeposit(fee)),
+            ManagerAction::SetFee(FeeType::Withdrawal(fee)),
+            ManagerAction::SetManager {
+                manager: Pubkey::new_unique(),
+                manager_fee_account: Pubkey::new_unique(),
+            },
+            ManagerAction::SetFundingAuthority(FundingType::StakeDeposit, Pubkey::new_unique()),
+            ManagerAction::SetFundingAuthority(FundingType::SolDeposit, Pubkey::default()),
+            ManagerAction::SetManagerTimelock(3),
+        ];
+        for action in actions.iter() {
+            let pending = PendingManagerAction::new(action, 12);
+            assert_eq!(pending.action(), Some(*action));
+            assert_eq!(pending.executable_epoch, 12);
+            assert_eq!(
+                pending.try_to_vec().unwrap().len(),
+                get_packed_len::<PendingManagerAction>()
+            );
+        }
+        assert_eq!(PendingManagerAction::default().action(), None);
+    }
+
+    #[test]
+    fn test_manager_action_timelock() {
+        let mut stake_pool = StakePool {
+            manager_timelock_epochs: 2,
+            ..StakePool::default()
+        };
+        let action = ManagerAction::SetManagerTimelock(1);
+        assert_eq!(
+            stake_pool.take_executable_manager_action(10),
+            Err(StakePoolError::NoPendingManagerAction.into())
+        );
+        assert_eq!(stake_pool.queue_manager_action(&action, 10), Ok(12));
+        assert_eq!(
+            stake_pool.queue_manager_action(&ManagerAction::SetManagerTimelock(0), 10),
+            Err(StakePoolError::ManagerActionPending.into())
+        );
+        assert_eq!(stake_pool.pending_manager_action(), Some((action, 12)));
+
+        assert_eq!(
+            stake_pool.take_executable_manager_action(11),
+            Err(StakePoolError::ManagerActionTimelocked.into())
+        );
+        assert_eq!(stake_pool.take_executable_manager_action(12), Ok(action));
+        assert_eq!(stake_pool.pending_manager_action(), None);
+
+        stake_pool.apply_manager_action(&action).unwrap();
+        assert_eq!(stake_pool.manager_timelock(), Some(1));
+    }
+
+    #[test]
+    fn test_apply_manager_action_checks_fee() {
+        let mut stake_pool = StakePool::default();
+        let too_high = Fee {
+            numerator: 2,
+            denominator: 1,
+        };
+        assert_eq!(
+            stake_pool.apply_manager_action(&ManagerAction::SetFee(FeeType::Epoch(too_high))),
+            Err(StakePoolError::FeeTooHigh.into())
+        );
+        let withdrawal_fee = Fee {
+            numerator: 1,
+            denominator: 10,
+        };
+        assert_eq!(
+            stake_pool
+                .apply_manager_action(&ManagerAction::SetFee(FeeType::Withdrawal(withdrawal_fee))),
+            Err(StakePoolError::FeeIncreaseTooHigh.into())
+        );
+        let epoch_fee = Fee {
+            numerator: 1,
+            denominator: 10,
+        };
+        stake_pool
+            .apply_manager_action(&ManagerAction::SetFee(FeeType::Epoch(epoch_fee)))
+            .unwrap();
+        assert_eq!(stake_pool.next_epoch_fee, epoch_fee);
+    }
+
+    #[test]
+    fn test_is_validator_delinquent() {
+        let stake_pool = StakePool::default();
+        assert!(!stake_pool.is_validator_delinquent(None, 100));
+        assert!(!stake_pool.is_validator_delinquent(Some(0), 100));
+
+        let stake_pool = StakePool {
+            delinquent_validator_epochs: 3,
+            ..StakePool::default()
+        };
+        assert!(stake_pool.is_validator_delinquent(None, 0));
+        assert!(stake_pool.is_validator_delinquent(Some(96), 100));
+        assert!(!stake_pool.is_validator_delinquent(Some(97), 100));
+        assert!(!stake_pool.is_validator_delinquent(Some(100), 100));
+        // credits from the future never make a validator delinquent
+        assert!(!stake_pool.is_validator_delinquent(Some(101), 100));
+    }
+
+    #[test]
+    fn test_single_validator_pool_rates() {
+        let rent_exempt_reserve = 2_282_880;
+        assert_eq!(
+            SingleValidatorPool::calc_pool_lamports(rent_exempt_reserve, rent_exempt_reserve),
+            None
+        );
+        let pool_lamports = SingleValidatorPool::calc_pool_lamports(
+            rent_exempt_reserve + MINIMUM_ACTIVE_STAKE + 300,
+            rent_exempt_reserve,
+        )
+        .unwrap();
+        assert_eq!(pool_lamports, 300);
+
+        // the first deposit mints at 1:1, even with rewards on the seed
+        assert_eq!(
+            SingleValidatorPool::calc_pool_tokens_for_deposit(pool_lamports, 0, 1_000),
+            Some(1_000)
+        );
+        // 300 lamports back 200 pool tokens
+        assert_eq!(
+            SingleValidatorPool::calc_pool_tokens_for_deposit(pool_lamports, 200, 1_000),
+            Some(666)
+        );
+        assert_eq!(
+            SingleValidatorPool::calc_lamports_for_withdraw(pool_lamports, 200, 199),
+            Some(298)
+        );
+        assert_eq!(
+            SingleValidatorPool::calc_lamports_for_withdraw(pool_lamports, 200, 200),
+            Some(300)
+        );
+
+        // with the stake account down to its rent-exempt reserve and seed,
+        // outstanding pool tokens are worth nothing and take no deposits
+        let pool_lamports = SingleValidatorPool::calc_pool_lamports(
+            rent_exempt_reserve + MINIMUM_ACTIVE_STAKE,
+            rent_exempt_reserve,
+        )
+        .unwrap();
+        assert_eq!(pool_lamports, 0);
+        assert_eq!(
+            SingleValidatorPool::calc_pool_tokens_for_deposit(pool_lamports, 200, 1_000),
+            None
+        );
+        assert_eq!(
+            SingleValidatorPool::calc_lamports_for_withdraw(pool_lamports, 200, 200),
+            Some(0)
+        );
+        assert_eq!(
+            SingleValidatorPool::calc_pool_lamports(
+                rent_exempt_reserve + MINIMUM_ACTIVE_STAKE - 1,
+                rent_exempt_reserve
+            ),
+            None
+        );
+    }
+
     proptest! {
         #[test]
         fn stake_list_size_calculation(test_amount in 0..=100_000_u32) {
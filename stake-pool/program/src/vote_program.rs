@@ -0,0 +1,204 @@
+//! FIXME copied from the solana vote program, only the parts needed to read
+//! vote account state
+
+use serde_derive::Deserialize;
+use solana_program::{
+    clock::{Epoch, Slot, UnixTimestamp},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use std::collections::BTreeMap;
+
+solana_program::declare_id!("Vote111111111111111111111111111111111111111");
+
+/// Number of prior voters kept in a vote account
+pub const MAX_ITEMS: usize = 32;
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct Lockout {
+    /// Slot voted on
+    pub slot: Slot,
+    /// Number of confirmations stacked on top of the vote
+    pub confirmation_count: u32,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct BlockTimestamp {
+    /// Slot of the last timestamp
+    pub slot: Slot,
+    /// Last timestamp voted on
+    pub timestamp: UnixTimestamp,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct CircBuf<I> {
+    /// Items in the buffer
+    pub buf: [I; MAX_ITEMS],
+    /// Index of the most recent item
+    pub idx: u64,
+    /// True if nothing has been pushed yet
+    pub is_empty: bool,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct CircBuf0_23_5<I> {
+    /// Items in the buffer
+    pub buf: [I; MAX_ITEMS],
+    /// Index of the most recent item
+    pub idx: u64,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct VoteState0_23_5 {
+    /// The node that votes in this account
+    pub node_pubkey: Pubkey,
+    /// The signer for vote transactions
+    pub authorized_voter: Pubkey,
+    /// When the authorized voter was set
+    pub authorized_voter_epoch: Epoch,
+    /// History of prior authorized voters and the epoch ranges of their use
+    pub prior_voters: CircBuf0_23_5<(Pubkey, Epoch, Epoch, Slot)>,
+    /// The signer for withdrawals
+    pub authorized_withdrawer: Pubkey,
+    /// Percentage (0-100) of rewards taken by the validator
+    pub commission: u8,
+    /// Recent votes
+    pub votes: Vec<Lockout>,
+    /// Most recent rooted slot
+    pub root_slot: Option<Slot>,
+    /// History of (epoch, credits, prev_credits) earned
+    pub epoch_credits: Vec<(Epoch, u64, u64)>,
+    /// Most recent timestamp submitted with a vote
+    pub last_timestamp: BlockTimestamp,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct VoteState {
+    /// The node that votes in this account
+    pub node_pubkey: Pubkey,
+    /// The signer for withdrawals
+    pub authorized_withdrawer: Pubkey,
+    /// Percentage (0-100) of rewards taken by the validator
+    pub commission: u8,
+    /// Recent votes
+    pub votes: Vec<Lockout>,
+    /// Most recent rooted slot
+    pub root_slot: Option<Slot>,
+    /// Signers for vote transactions, by the epoch they take effect
+    pub authorized_voters: BTreeMap<Epoch, Pubkey>,
+    /// History of prior authorized voters and the epoch ranges of their use
+    pub prior_voters: CircBuf<(Pubkey, Epoch, Epoch)>,
+    /// History of (epoch, credits, prev_credits) earned
+    pub epoch_credits: Vec<(Epoch, u64, u64)>,
+    /// Most recent timestamp submitted with a vote
+    pub last_timestamp: BlockTimestamp,
+}
+
+/// FIXME copied from solana vote program
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub enum VoteStateVersions {
+    /// Layout used before 1.0
+    V0_23_5(Box<VoteState0_23_5>),
+    /// Current layout
+    Current(Box<VoteState>),
+}
+
+impl VoteStateVersions {
+    /// Deserialize the state stored in a vote account
+    pub fn deserialize(data: &[u8]) -> Result<Self, ProgramError> {
+        bincode::deserialize(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// History of (epoch, credits, prev_credits) earned by the validator
+    pub fn epoch_credits(&self) -> &[(Epoch, u64, u64)] {
+        match self {
+            VoteStateVersions::V0_23_5(vote_state) => &vote_state.epoch_credits,
+            VoteStateVersions::Current(vote_state) => &vote_state.epoch_credits,
+        }
+    }
+
+    /// The signer for withdrawals from the vote account
+    pub fn authorized_withdrawer(&self) -> &Pubkey {
+        match self {
+            VoteStateVersions::V0_23_5(vote_state) => &vote_state.authorized_withdrawer,
+            VoteStateVersions::Current(vote_state) => &vote_state.authorized_withdrawer,
+        }
+    }
+
+    /// Last epoch in which the validator earned vote credits, or None if it
+    /// has never voted
+    pub fn last_credited_epoch(&self) -> Option<Epoch> {
+        self.epoch_credits().last().map(|(epoch, _, _)| *epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_vote_program::vote_state;
+
+    #[test]
+    fn test_deserialize_current() {
+        let node_pubkey = Pubkey::new_unique();
+        let authorized_withdrawer = Pubkey::new_unique();
+        let mut vote_state = vote_state::VoteState::new(
+            &vote_state::VoteInit {
+                node_pubkey,
+                authorized_voter: Pubkey::new_unique(),
+                authorized_withdrawer,
+                commission: 7,
+            },
+            &Default::default(),
+        );
+        let mut data = vec![0; vote_state::VoteState::size_of()];
+        bincode::serialize_into(
+            &mut data[..],
+            &vote_state::VoteStateVersions::Current(Box::new(vote_state.clone())),
+        )
+        .unwrap();
+        let versions = VoteStateVersions::deserialize(&data).unwrap();
+        assert_eq!(versions.last_credited_epoch(), None);
+        assert_eq!(*versions.authorized_withdrawer(), authorized_withdrawer);
+
+        vote_state.increment_credits(3);
+        vote_state.increment_credits(5);
+        bincode::serialize_into(
+            &mut data[..],
+            &vote_state::VoteStateVersions::Current(Box::new(vote_state)),
+        )
+        .unwrap();
+        match VoteStateVersions::deserialize(&data).unwrap() {
+            VoteStateVersions::Current(vote_state) => {
+                assert_eq!(vote_state.node_pubkey, node_pubkey);
+                assert_eq!(vote_state.authorized_withdrawer, authorized_withdrawer);
+                assert_eq!(vote_state.commission, 7);
+                assert_eq!(vote_state.epoch_credits, vec![(3, 1, 0), (5, 2, 1)]);
+            }
+            _ => panic!("expected current vote state"),
+        }
+        assert_eq!(
+            VoteStateVersions::deserialize(&data)
+                .unwrap()
+                .last_credited_epoch(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_uninitialized() {
+        let data = vec![0; vote_state::VoteState::size_of()];
+        let versions = VoteStateVersions::deserialize(&data).unwrap();
+        assert_eq!(versions.last_credited_epoch(), None);
+
+        assert_eq!(
+            VoteStateVersions::deserialize(&[1, 0, 0]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
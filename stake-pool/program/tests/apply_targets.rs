@@ -0,0 +1,389 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{instruction::InstructionError, native_token::LAMPORTS_PER_SOL},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError, instruction::ValidatorTarget, stake_program, state,
+        MINIMUM_ACTIVE_STAKE,
+    },
+};
+
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+
+/// Sets up a pool with two validators holding one deposit each, and
+/// `RESERVE_AMOUNT` of SOL deposits in the reserve
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    Vec<ValidatorStakeAccount>,
+) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let mut validator_stake_accounts = vec![];
+    let mut deposit_infos = vec![];
+    for _ in 0..2 {
+        let validator_stake_account = simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        deposit_infos.push(
+            simple_deposit(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                &stake_pool_accounts,
+                &validator_stake_account,
+            )
+            .await,
+        );
+        validator_stake_accounts.push(validator_stake_account);
+    }
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &deposit_infos[0].user_pool_account,
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts, validator_stake_accounts)
+}
+
+/// Targets everything on the second validator, none on the first
+async fn set_targets(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_accounts: &[ValidatorStakeAccount],
+) {
+    stake_pool_accounts
+        .set_validator_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            validator_stake_accounts
+                .iter()
+                .zip(&[0, 10_000])
+                .map(|(validator_stake_account, weight_bps)| ValidatorTarget {
+                    vote_account: validator_stake_account.vote.pubkey(),
+                    weight_bps: *weight_bps,
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+}
+
+async fn apply_targets(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+) -> Option<TransportError> {
+    stake_pool_accounts
+        .apply_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+        )
+        .await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_decrease_overweight_validator() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+    set_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts,
+    )
+    .await;
+    let validator_stake_account = &validator_stake_accounts[0];
+
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    // anyone can rebalance, the payer is the only signer
+    let error = apply_targets(&mut context, &stake_pool_accounts, validator_stake_account).await;
+    assert!(error.is_none());
+
+    // with no target, everything above the minimum moves out
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let required_lamports = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>())
+        + MINIMUM_ACTIVE_STAKE;
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    assert_eq!(validator_stake.lamports, required_lamports);
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    assert_eq!(
+        transient_stake.lamports,
+        validator_lamports - required_lamports
+    );
+    let transient_stake_state =
+        deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    assert_ne!(
+        transient_stake_state
+            .delegation()
+            .unwrap()
+            .deactivation_epoch,
+        u64::MAX
+    );
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_entry = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_entry.transient_stake_lamports,
+        validator_lamports - required_lamports
+    );
+}
+
+#[tokio::test]
+async fn success_increase_underweight_validator() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+    set_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts,
+    )
+    .await;
+    let validator_stake_account = &validator_stake_accounts[1];
+
+    let reserve_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+
+    let error = apply_targets(&mut context, &stake_pool_accounts, validator_stake_account).await;
+    assert!(error.is_none());
+
+    // the validator is further below its target than the reserve can cover,
+    // so the whole reserve above rent-exemption moves out
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+    let reserve_stake = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve_stake.lamports, stake_rent);
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    assert_eq!(transient_stake.lamports, reserve_lamports - stake_rent);
+    let transient_stake_state =
+        deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    assert_eq!(
+        transient_stake_state.delegation().unwrap().voter_pubkey,
+        validator_stake_account.vote.pubkey()
+    );
+}
+
+#[tokio::test]
+async fn fail_without_targets() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+
+    let error = apply_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts[0],
+    )
+    .await
+    .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::TargetWeightsNotSet as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_with_transient_stake() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+    set_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts,
+    )
+    .await;
+
+    let error = apply_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts[0],
+    )
+    .await;
+    assert!(error.is_none());
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let error = stake_pool_accounts
+        .apply_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &validator_stake_accounts[0].stake_account,
+            &validator_stake_accounts[0].transient_stake_account,
+            &validator_stake_accounts[0].vote.pubkey(),
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::TransientAccountInUse as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_at_target() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+    set_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts,
+    )
+    .await;
+
+    let error = apply_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts[0],
+    )
+    .await;
+    assert!(error.is_none());
+
+    // merge the deactivated stake back into the reserve
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_accounts
+                .iter()
+                .map(|validator_stake_account| validator_stake_account.vote.pubkey())
+                .collect::<Vec<_>>(),
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // only the minimum is left, which can never move
+    let error = apply_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts[0],
+    )
+    .await
+    .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::ValidatorAtTarget as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_with_wrong_vote_account() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) = setup().await;
+    set_targets(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_accounts,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .apply_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_accounts[0].stake_account,
+            &validator_stake_accounts[0].transient_stake_account,
+            &validator_stake_accounts[1].vote.pubkey(),
+        )
+        .await
+        .unwrap();
+    check_error(error, InstructionError::InvalidArgument);
+}
@@ -0,0 +1,208 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error, id, instruction, state},
+    spl_token::error as token_error,
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+
+    let tokens_to_burn = DEPOSIT_AMOUNT / 2;
+    stake_pool_accounts
+        .burn_pool_tokens(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &user,
+            tokens_to_burn,
+        )
+        .await
+        .unwrap();
+
+    let user_token_balance = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT - tokens_to_burn);
+
+    // Lamports stay in the pool, so every remaining token is worth more
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply - tokens_to_burn
+    );
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports
+    );
+    assert!(
+        stake_pool
+            .calc_lamports_withdraw_amount_floor(1_000)
+            .unwrap()
+            > stake_pool_before
+                .calc_lamports_withdraw_amount_floor(1_000)
+                .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn fail_with_wrong_mint() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let wrong_mint = Keypair::new();
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &wrong_mint,
+        &stake_pool_accounts.withdraw_authority,
+    )
+    .await
+    .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::burn_pool_tokens(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &user_pool_account,
+            &user.pubkey(),
+            &wrong_mint.pubkey(),
+            &spl_token::id(),
+            DEPOSIT_AMOUNT,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongPoolMint as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to burn tokens of the wrong mint"),
+    }
+}
+
+#[tokio::test]
+async fn fail_with_wrong_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, user_pool_account) =
+        setup().await;
+
+    let wrong_authority = Keypair::new();
+    let transaction_error = stake_pool_accounts
+        .burn_pool_tokens(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &wrong_authority,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = token_error::TokenError::OwnerMismatch as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to burn someone else's tokens"),
+    }
+}
@@ -0,0 +1,386 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+/// Sets up a pool holding a single SOL deposit, with the user's pool tokens
+/// delegated to the pool for withdrawal
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    stake_pool_accounts
+        .decommission_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(stake_pool.data.as_slice()).unwrap();
+    assert!(stake_pool.decommissioning);
+
+    // no more deposits
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT / 2,
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakePoolDecommissioning as u32),
+    );
+
+    // the last holder redeems everything from the reserve
+    let recipient = Keypair::new();
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &recipient.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_account(&mut banks_client, &recipient.pubkey())
+            .await
+            .lamports,
+        DEPOSIT_AMOUNT
+    );
+
+    let mut closed_lamports = 0;
+    for address in [
+        stake_pool_accounts.stake_pool.pubkey(),
+        stake_pool_accounts.validator_list.pubkey(),
+        stake_pool_accounts.reserve_stake.pubkey(),
+    ]
+    .iter()
+    {
+        closed_lamports += get_account(&mut banks_client, address).await.lamports;
+    }
+
+    let lamports_to = Keypair::new();
+    stake_pool_accounts
+        .close_stake_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &lamports_to.pubkey(),
+        )
+        .await
+        .unwrap();
+
+    for address in [
+        stake_pool_accounts.stake_pool.pubkey(),
+        stake_pool_accounts.validator_list.pubkey(),
+        stake_pool_accounts.reserve_stake.pubkey(),
+    ]
+    .iter()
+    {
+        assert!(banks_client
+            .get_account(*address)
+            .await
+            .expect("get_account")
+            .is_none());
+    }
+    assert_eq!(
+        get_account(&mut banks_client, &lamports_to.pubkey())
+            .await
+            .lamports,
+        closed_lamports
+    );
+}
+
+#[tokio::test]
+async fn fail_close_with_pool_tokens() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, _user_pool_account) =
+        setup().await;
+
+    stake_pool_accounts
+        .decommission_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .close_stake_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &Pubkey::new_unique(),
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakePoolNotEmpty as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_close_without_decommissioning() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, user_pool_account) =
+        setup().await;
+
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &Pubkey::new_unique(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .close_stake_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &Pubkey::new_unique(),
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakePoolNotDecommissioning as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_manager_removes_validator() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, _user_pool_account) =
+        setup().await;
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    // the staker is still in charge until the pool is decommissioned
+    let error = stake_pool_accounts
+        .remove_validator_from_pool_with_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake_account.stake_account,
+            &stake_pool_accounts.manager,
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongStaker as u32),
+    );
+
+    stake_pool_accounts
+        .decommission_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .remove_validator_from_pool_with_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake_account.stake_account,
+            &stake_pool_accounts.manager,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_entry = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(validator_entry.status, state::StakeStatus::ReadyForRemoval);
+}
+
+#[tokio::test]
+async fn fail_add_validator_when_decommissioning() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, _user_pool_account) =
+        setup().await;
+
+    stake_pool_accounts
+        .decommission_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake_account = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    validator_stake_account
+        .create_and_delegate(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stake_pool_accounts.staker,
+        )
+        .await;
+    let pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &Keypair::new().pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .add_validator_to_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake_account.stake_account,
+            &pool_account.pubkey(),
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakePoolDecommissioning as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_decommission_with_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, _user_pool_account) =
+        setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::decommission_stake_pool(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer, &wrong_manager],
+        recent_blockhash,
+    );
+    let error = banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
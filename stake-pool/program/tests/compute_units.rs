@@ -0,0 +1,98 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*, solana_program::pubkey::Pubkey, solana_program_test::*,
+    solana_sdk::signature::Signer,
+};
+
+// Upper bounds on the compute units used by the hottest instructions, to
+// catch regressions.  Lower them when an optimization lands, and only raise
+// them deliberately.  The limit applies to the whole test, setup included.
+const DEPOSIT_COMPUTE_UNITS: u64 = 80_000;
+const UPDATE_VALIDATOR_LIST_BALANCE_COMPUTE_UNITS: u64 = 150_000;
+
+const VALIDATOR_COUNT: usize = 5;
+
+async fn setup(
+    max_compute_units: u64,
+) -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    Vec<ValidatorStakeAccount>,
+) {
+    let mut program_test = program_test();
+    program_test.set_bpf_compute_max_units(max_compute_units);
+    let mut context = program_test.start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let mut validator_stake_accounts = vec![];
+    for _ in 0..VALIDATOR_COUNT {
+        validator_stake_accounts.push(
+            simple_add_validator_to_pool(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                &stake_pool_accounts,
+            )
+            .await,
+        );
+    }
+
+    (context, stake_pool_accounts, validator_stake_accounts)
+}
+
+fn vote_accounts(validator_stake_accounts: &[ValidatorStakeAccount]) -> Vec<Pubkey> {
+    validator_stake_accounts
+        .iter()
+        .map(|validator_stake_account| validator_stake_account.vote.pubkey())
+        .collect()
+}
+
+#[tokio::test]
+async fn deposit() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) =
+        setup(DEPOSIT_COMPUTE_UNITS).await;
+
+    // the full validator list is deserialized on every deposit, so deposit
+    // into the last validator
+    simple_deposit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_accounts[VALIDATOR_COUNT - 1],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn update_validator_list_balance() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts) =
+        setup(UPDATE_VALIDATOR_LIST_BALANCE_COMPUTE_UNITS).await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &vote_accounts(&validator_stake_accounts),
+        )
+        .await;
+    assert!(error.is_none());
+}
@@ -0,0 +1,193 @@
+mod cpi_harness;
+
+use {
+    borsh::BorshSerialize,
+    cpi_harness::*,
+    solana_program::{
+        instruction::Instruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, system_program,
+    },
+    spl_stake_pool::{
+        id, instruction,
+        state::{StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo},
+        MINIMUM_ACTIVE_STAKE,
+    },
+};
+
+const REWARD_LAMPORTS: u64 = LAMPORTS_PER_SOL;
+
+/// Checks that the pool's own accounts hold the same data as `before`
+fn check_pool_unchanged(before: &Accounts, after: &Accounts, pool: &Pool) {
+    for address in &[pool.stake_pool, pool.validator_list] {
+        assert_eq!(before.data(address), after.data(address));
+    }
+}
+
+fn deposit_sol_instruction(accounts: &mut Accounts, pool: &Pool) -> Instruction {
+    let user = Pubkey::new_unique();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user,
+        account(2 * LAMPORTS_PER_SOL, vec![], &system_program::id()),
+    );
+    accounts.add(user_pool_account, token_account(&pool.pool_mint, &user, 0));
+    instruction::deposit_sol(
+        &id(),
+        &pool.stake_pool,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &user,
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+        LAMPORTS_PER_SOL,
+    )
+    .unwrap()
+}
+
+#[test]
+fn deposit_sol() {
+    let (mut accounts, pool) = setup_pool();
+    let instruction = deposit_sol_instruction(&mut accounts, &pool);
+    accounts.process(&instruction, None).unwrap();
+
+    let stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        RESERVE_LAMPORTS + VALIDATOR_LAMPORTS + LAMPORTS_PER_SOL
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        POOL_TOKEN_SUPPLY + LAMPORTS_PER_SOL
+    );
+}
+
+#[test]
+fn deposit_sol_fail_token_mint_to() {
+    let (mut accounts, pool) = setup_pool();
+    let instruction = deposit_sol_instruction(&mut accounts, &pool);
+    let before = Accounts(accounts.0.clone());
+
+    let error = accounts
+        .process(&instruction, Some(token_mint_to))
+        .unwrap_err();
+    assert_eq!(error, INJECTED_CPI_ERROR);
+    check_pool_unchanged(&before, &accounts, &pool);
+}
+
+fn decrease_instruction(pool: &Pool) -> Instruction {
+    instruction::decrease_validator_stake(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.validator_stake,
+        &pool.transient_stake,
+        VALIDATOR_LAMPORTS - MINIMUM_ACTIVE_STAKE - LAMPORTS_PER_SOL,
+    )
+    .unwrap()
+}
+
+#[test]
+fn decrease_validator_stake() {
+    let (mut accounts, pool) = setup_pool();
+    accounts
+        .process(&decrease_instruction(&pool), None)
+        .unwrap();
+
+    let validator_list =
+        ValidatorList::deserialize_checked(accounts.data(&pool.validator_list)).unwrap();
+    assert_eq!(
+        validator_list.validators[0].transient_stake_lamports,
+        VALIDATOR_LAMPORTS - MINIMUM_ACTIVE_STAKE - LAMPORTS_PER_SOL
+    );
+}
+
+#[test]
+fn decrease_validator_stake_fail_stake_split() {
+    let (mut accounts, pool) = setup_pool();
+    let before = Accounts(accounts.0.clone());
+
+    let error = accounts
+        .process(&decrease_instruction(&pool), Some(stake_split))
+        .unwrap_err();
+    assert_eq!(error, INJECTED_CPI_ERROR);
+    check_pool_unchanged(&before, &accounts, &pool);
+}
+
+#[test]
+fn decrease_validator_stake_fail_stake_deactivate() {
+    let (mut accounts, pool) = setup_pool();
+    let before = Accounts(accounts.0.clone());
+
+    let error = accounts
+        .process(&decrease_instruction(&pool), Some(stake_deactivate))
+        .unwrap_err();
+    assert_eq!(error, INJECTED_CPI_ERROR);
+    check_pool_unchanged(&before, &accounts, &pool);
+}
+
+/// Prepares the pool for an update with `REWARD_LAMPORTS` of rewards to take
+/// a fee on, and a removed validator to drop from the list
+fn update_balance_instruction(accounts: &mut Accounts, pool: &Pool) -> Instruction {
+    let mut stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    stake_pool.total_stake_lamports -= REWARD_LAMPORTS;
+    stake_pool.epoch_fee = instruction::Fee {
+        numerator: 1,
+        denominator: 10,
+    };
+    accounts.0.get_mut(&pool.stake_pool).unwrap().data = stake_pool.try_to_vec().unwrap();
+
+    let mut validator_list =
+        ValidatorList::deserialize_checked(accounts.data(&pool.validator_list)).unwrap();
+    validator_list.max_validators = 2;
+    validator_list.validators.push(ValidatorStakeInfo {
+        vote_account: Pubkey::new_unique(),
+        status: StakeStatus::ReadyForRemoval.into(),
+        last_update_epoch: EPOCH.into(),
+        ..ValidatorStakeInfo::default()
+    });
+    accounts.0.get_mut(&pool.validator_list).unwrap().data = validator_list.try_to_vec().unwrap();
+
+    instruction::update_stake_pool_balance(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.reserve_stake,
+        &pool.withdraw_authority,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+    )
+    .unwrap()
+}
+
+#[test]
+fn update_stake_pool_balance() {
+    let (mut accounts, pool) = setup_pool();
+    let instruction = update_balance_instruction(&mut accounts, &pool);
+    accounts.process(&instruction, None).unwrap();
+
+    let stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        RESERVE_LAMPORTS + VALIDATOR_LAMPORTS
+    );
+    assert!(stake_pool.pool_token_supply > POOL_TOKEN_SUPPLY);
+    let validator_list =
+        ValidatorList::deserialize_checked(accounts.data(&pool.validator_list)).unwrap();
+    assert_eq!(validator_list.validators.len(), 1);
+}
+
+#[test]
+fn update_stake_pool_balance_fail_token_mint_to() {
+    let (mut accounts, pool) = setup_pool();
+    let instruction = update_balance_instruction(&mut accounts, &pool);
+    let before = Accounts(accounts.0.clone());
+
+    let error = accounts
+        .process(&instruction, Some(token_mint_to))
+        .unwrap_err();
+    assert_eq!(error, INJECTED_CPI_ERROR);
+    check_pool_unchanged(&before, &accounts, &pool);
+}
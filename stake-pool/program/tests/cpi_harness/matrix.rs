@@ -0,0 +1,169 @@
+//! Table-driven negative paths: each account of an instruction is tampered
+//! with in turn, and the instruction must fail.
+//!
+//! The processor runs natively, so only its own checks are exercised.
+//! Program accounts are left alone, since the runtime fails any CPI into a
+//! program missing from the transaction, and so are sysvars, which are read
+//! through `Sysvar::get` or checked on deserialization.  The runtime also
+//! rejects any change to an account owned by another program, so a
+//! `WrongOwner` copy that the instruction writes to or debits counts as
+//! rejected.
+
+use {
+    super::Accounts,
+    solana_program::{
+        instruction::Instruction, program_error::ProgramError, pubkey::Pubkey, sysvar,
+    },
+    solana_sdk::account::Account,
+};
+
+/// Ways an account of an instruction is tampered with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mutation {
+    /// Account that doesn't exist, at a fresh address.  A signer is swapped
+    /// for another signer, as with a wrong authority.
+    WrongAddress,
+    /// Copy of the account, data and lamports included, at a fresh address
+    /// and owned by another program
+    WrongOwner,
+    /// Same account, without its signature
+    Unsigned,
+}
+
+const MUTATIONS: [Mutation; 3] = [
+    Mutation::WrongAddress,
+    Mutation::WrongOwner,
+    Mutation::Unsigned,
+];
+
+/// Instruction that succeeds against `accounts`, along with the tampering
+/// it lets through and the errors pinned for the rest
+pub struct Case {
+    name: &'static str,
+    accounts: Accounts,
+    instruction: Instruction,
+    allowed: Vec<(usize, Mutation)>,
+    expected: Vec<(usize, Mutation, ProgramError)>,
+}
+
+impl Case {
+    pub fn new(name: &'static str, accounts: Accounts, instruction: Instruction) -> Self {
+        Self {
+            name,
+            accounts,
+            instruction,
+            allowed: vec![],
+            expected: vec![],
+        }
+    }
+
+    /// Lets `mutation` of account `index` succeed, for accounts the
+    /// instruction doesn't constrain, like the receiver of a withdrawal
+    pub fn allow(mut self, index: usize, mutation: Mutation) -> Self {
+        self.allowed.push((index, mutation));
+        self
+    }
+
+    /// Pins the error of `mutation` of account `index`
+    pub fn expect_error<E: Into<ProgramError>>(
+        mut self,
+        index: usize,
+        mutation: Mutation,
+        error: E,
+    ) -> Self {
+        self.expected.push((index, mutation, error.into()));
+        self
+    }
+
+    /// Runs the instruction untouched, then once for each mutation of each
+    /// account
+    pub fn check(self) {
+        let mut accounts = Accounts(self.accounts.0.clone());
+        if let Err(error) = accounts.process(&self.instruction, None) {
+            panic!("{}: fails untouched with {:?}", self.name, error);
+        }
+
+        let mut checked_errors = 0;
+        for index in 0..self.instruction.accounts.len() {
+            for mutation in MUTATIONS.iter() {
+                let (mut accounts, instruction, impostor) = match self.mutate(index, *mutation) {
+                    Some(mutated) => mutated,
+                    None => continue,
+                };
+                let result = accounts.process(&instruction, None);
+                let expected = self
+                    .expected
+                    .iter()
+                    .find(|(i, m, _)| *i == index && m == mutation);
+                match (result, expected) {
+                    (Err(error), Some((_, _, expected_error))) => {
+                        assert_eq!(
+                            error, *expected_error,
+                            "{}: {:?} of account {}",
+                            self.name, mutation, index
+                        );
+                        checked_errors += 1;
+                    }
+                    (Err(_), None) => {}
+                    (Ok(()), _) => {
+                        let rejected_by_runtime = impostor.map_or(false, |(address, before)| {
+                            let after = &accounts.0[&address];
+                            after.data != before.data || after.lamports < before.lamports
+                        });
+                        assert!(
+                            rejected_by_runtime || self.allowed.contains(&(index, *mutation)),
+                            "{}: {:?} of account {} succeeds",
+                            self.name,
+                            mutation,
+                            index
+                        );
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            checked_errors,
+            self.expected.len(),
+            "{}: pinned errors not all reached",
+            self.name
+        );
+    }
+
+    /// Accounts and instruction with `mutation` applied to account `index`,
+    /// along with the impostor account of a `WrongOwner` mutation
+    fn mutate(
+        &self,
+        index: usize,
+        mutation: Mutation,
+    ) -> Option<(Accounts, Instruction, Option<(Pubkey, Account)>)> {
+        let meta = &self.instruction.accounts[index];
+        let existing = self.accounts.0.get(&meta.pubkey);
+        if sysvar::is_sysvar_id(&meta.pubkey) || existing.map_or(false, |a| a.executable) {
+            return None;
+        }
+
+        let mut accounts = Accounts(self.accounts.0.clone());
+        let mut instruction = self.instruction.clone();
+        let mut impostor = None;
+        match mutation {
+            Mutation::WrongAddress => {
+                instruction.accounts[index].pubkey = Pubkey::new_unique();
+            }
+            Mutation::WrongOwner => {
+                let mut account = existing?.clone();
+                account.owner = Pubkey::new_unique();
+                let address = Pubkey::new_unique();
+                accounts.add(address, account.clone());
+                instruction.accounts[index].pubkey = address;
+                impostor = Some((address, account));
+            }
+            Mutation::Unsigned => {
+                if !meta.is_signer {
+                    return None;
+                }
+                instruction.accounts[index].is_signer = false;
+            }
+        }
+        Some((accounts, instruction, impostor))
+    }
+}
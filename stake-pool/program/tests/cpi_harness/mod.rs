@@ -0,0 +1,459 @@
+#![allow(dead_code)]
+
+//! Runs the stake pool processor natively against in-memory accounts, with
+//! cross-program invocations handled by mock stubs that can be told to fail.
+//!
+//! The runtime rolls back every account of a failed transaction, so the
+//! processor only needs to be atomic if it never writes pool state before a
+//! CPI that can still fail.  Accounts are left exactly as the processor left
+//! them when the error surfaced, so tests can check that.
+
+pub mod matrix;
+
+use {
+    borsh::BorshSerialize,
+    solana_program::{
+        account_info::AccountInfo,
+        clock::Clock,
+        entrypoint::{ProgramResult, SUCCESS},
+        instruction::Instruction,
+        native_token::LAMPORTS_PER_SOL,
+        program_error::ProgramError,
+        program_option::COption,
+        program_pack::Pack,
+        program_stubs::{self, SyscallStubs},
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction::SystemInstruction,
+        system_program, sysvar,
+    },
+    solana_sdk::account::Account,
+    spl_stake_pool::{
+        find_stake_program_address, find_transient_stake_program_address,
+        find_withdraw_authority_program_address, id, instruction,
+        processor::Processor,
+        stake_program,
+        state::{
+            AccountType, StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo,
+            STAKE_POOL_VERSION,
+        },
+    },
+    std::{cell::RefCell, collections::HashMap, sync::Once},
+};
+
+/// Error returned by a CPI that was made to fail
+pub const INJECTED_CPI_ERROR: ProgramError = ProgramError::Custom(u32::MAX);
+
+/// Decides whether a CPI made by the processor fails
+pub type CpiFailure = fn(&Instruction) -> bool;
+
+thread_local! {
+    static CPI_FAILURE: RefCell<Option<CpiFailure>> = RefCell::new(None);
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+}
+
+static INSTALL_STUBS: Once = Once::new();
+
+/// Fails an spl-token `MintTo`
+pub fn token_mint_to(instruction: &Instruction) -> bool {
+    instruction.program_id == spl_token::id()
+        && matches!(
+            spl_token::instruction::TokenInstruction::unpack(&instruction.data),
+            Ok(spl_token::instruction::TokenInstruction::MintTo { .. })
+        )
+}
+
+/// Fails a stake program `Split`
+pub fn stake_split(instruction: &Instruction) -> bool {
+    instruction.program_id == stake_program::id()
+        && matches!(
+            bincode::deserialize(&instruction.data),
+            Ok(stake_program::StakeInstruction::Split(_))
+        )
+}
+
+/// Fails a stake program `Deactivate`
+pub fn stake_deactivate(instruction: &Instruction) -> bool {
+    instruction.program_id == stake_program::id()
+        && matches!(
+            bincode::deserialize(&instruction.data),
+            Ok(stake_program::StakeInstruction::Deactivate)
+        )
+}
+
+/// Syscall stubs for the processor running natively.  CPIs to the token
+/// program run the real token processor, system transfers move lamports,
+/// and anything else succeeds without effect, unless the thread's
+/// `CpiFailure` picks it.  `Clock::get` returns the clock account passed to
+/// the instruction, if any.
+struct MockCpiStubs;
+
+impl SyscallStubs for MockCpiStubs {
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let fail = CPI_FAILURE.with(|cpi_failure| match *cpi_failure.borrow() {
+            Some(cpi_failure) => cpi_failure(instruction),
+            None => false,
+        });
+        if fail {
+            return Err(INJECTED_CPI_ERROR);
+        }
+
+        // the runtime checks signer seeds, the mock trusts the processor
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut account_info = account_infos
+                    .iter()
+                    .find(|account_info| *account_info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                account_info.is_signer |= meta.is_signer;
+                Ok(account_info)
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+
+        if instruction.program_id == spl_token::id() {
+            spl_token::processor::Processor::process(&spl_token::id(), &accounts, &instruction.data)
+        } else if instruction.program_id == system_program::id() {
+            if let Ok(SystemInstruction::Transfer { lamports }) =
+                bincode::deserialize(&instruction.data)
+            {
+                **accounts[0].try_borrow_mut_lamports()? = accounts[0]
+                    .lamports()
+                    .checked_sub(lamports)
+                    .ok_or(ProgramError::InsufficientFunds)?;
+                **accounts[1].try_borrow_mut_lamports()? += lamports;
+            }
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = CLOCK.with(|clock| clock.borrow().clone());
+        unsafe {
+            *(var_addr as *mut Clock) = clock;
+        }
+        SUCCESS
+    }
+}
+
+/// Accounts the processor runs against, keyed by address
+#[derive(Default)]
+pub struct Accounts(pub HashMap<Pubkey, Account>);
+
+impl Accounts {
+    pub fn add(&mut self, address: Pubkey, account: Account) {
+        self.0.insert(address, account);
+    }
+
+    pub fn data(&self, address: &Pubkey) -> &[u8] {
+        &self.0[address].data
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.0[address].lamports
+    }
+
+    /// Runs `instruction` through the processor, failing the CPIs picked by
+    /// `cpi_failure`.  Accounts missing from the set are passed in empty.
+    pub fn process(
+        &mut self,
+        instruction: &Instruction,
+        cpi_failure: Option<CpiFailure>,
+    ) -> ProgramResult {
+        INSTALL_STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(MockCpiStubs));
+        });
+        CPI_FAILURE.with(|failure| *failure.borrow_mut() = cpi_failure);
+        let clock = self
+            .0
+            .get(&sysvar::clock::id())
+            .map(|account| bincode::deserialize(&account.data).unwrap())
+            .unwrap_or_default();
+        CLOCK.with(|current| *current.borrow_mut() = clock);
+
+        let mut addresses: Vec<Pubkey> = vec![];
+        for meta in &instruction.accounts {
+            if !addresses.contains(&meta.pubkey) {
+                addresses.push(meta.pubkey);
+            }
+        }
+        let mut accounts = addresses
+            .iter()
+            .map(|address| self.0.remove(address).unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        let result = {
+            let mut account_infos = addresses
+                .iter()
+                .zip(accounts.iter_mut())
+                .map(|(address, account)| {
+                    AccountInfo::new(
+                        address,
+                        false,
+                        false,
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        account.executable,
+                        account.rent_epoch,
+                    )
+                })
+                .collect::<Vec<_>>();
+            for meta in &instruction.accounts {
+                let index = addresses.iter().position(|a| *a == meta.pubkey).unwrap();
+                account_infos[index].is_signer |= meta.is_signer;
+                account_infos[index].is_writable |= meta.is_writable;
+            }
+            let account_infos = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let index = addresses.iter().position(|a| *a == meta.pubkey).unwrap();
+                    account_infos[index].clone()
+                })
+                .collect::<Vec<_>>();
+            Processor::process(&id(), &account_infos, &instruction.data)
+        };
+
+        CPI_FAILURE.with(|failure| *failure.borrow_mut() = None);
+        for (address, account) in addresses.into_iter().zip(accounts.into_iter()) {
+            self.0.insert(address, account);
+        }
+        result
+    }
+}
+
+/// Account holding `data`, owned by `owner`
+pub fn account(lamports: u64, data: Vec<u8>, owner: &Pubkey) -> Account {
+    Account {
+        lamports,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Executable account standing in for a program
+pub fn program_account() -> Account {
+    Account {
+        lamports: 1,
+        data: vec![],
+        owner: solana_program::bpf_loader::id(),
+        executable: true,
+        rent_epoch: 0,
+    }
+}
+
+/// Token account for `mint` holding `amount`
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    }
+    .pack_into_slice(&mut data);
+    account(
+        Rent::default().minimum_balance(data.len()),
+        data,
+        &spl_token::id(),
+    )
+}
+
+/// Mint of `supply` tokens, with `mint_authority`
+pub fn mint_account(mint_authority: &Pubkey, supply: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::Some(*mint_authority),
+        supply,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    account(
+        Rent::default().minimum_balance(data.len()),
+        data,
+        &spl_token::id(),
+    )
+}
+
+/// Stake account in `stake_state`, holding `lamports`
+pub fn stake_account(lamports: u64, stake_state: &stake_program::StakeState) -> Account {
+    let mut data = vec![0; std::mem::size_of::<stake_program::StakeState>()];
+    bincode::serialize_into(&mut data[..], stake_state).unwrap();
+    account(lamports, data, &stake_program::id())
+}
+
+/// Sysvar account holding `sysvar`
+pub fn sysvar_account<T: serde::Serialize>(sysvar: &T) -> Account {
+    account(
+        1,
+        bincode::serialize(sysvar).unwrap(),
+        &solana_program::sysvar::id(),
+    )
+}
+
+pub const EPOCH: u64 = 10;
+pub const RESERVE_LAMPORTS: u64 = 10 * LAMPORTS_PER_SOL;
+pub const VALIDATOR_LAMPORTS: u64 = 5 * LAMPORTS_PER_SOL;
+pub const POOL_TOKEN_SUPPLY: u64 = 15 * LAMPORTS_PER_SOL;
+
+/// Addresses of an in-memory pool with one active validator
+pub struct Pool {
+    pub stake_pool: Pubkey,
+    pub manager: Pubkey,
+    pub validator_list: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub pool_mint: Pubkey,
+    pub manager_fee_account: Pubkey,
+    pub withdraw_authority: Pubkey,
+    pub staker: Pubkey,
+    pub vote_account: Pubkey,
+    pub validator_stake: Pubkey,
+    pub transient_stake: Pubkey,
+}
+
+/// Sets up a pool at `EPOCH`, up to date, with `RESERVE_LAMPORTS` in its
+/// reserve and one active validator holding `VALIDATOR_LAMPORTS`
+pub fn setup_pool() -> (Accounts, Pool) {
+    let stake_pool_address = Pubkey::new_unique();
+    let (withdraw_authority, withdraw_bump_seed) =
+        find_withdraw_authority_program_address(&id(), &stake_pool_address);
+    let vote_account = Pubkey::new_unique();
+    let (validator_stake, _) =
+        find_stake_program_address(&id(), &vote_account, &stake_pool_address);
+    let (transient_stake, _) =
+        find_transient_stake_program_address(&id(), &vote_account, &stake_pool_address);
+    let pool = Pool {
+        stake_pool: stake_pool_address,
+        manager: Pubkey::new_unique(),
+        validator_list: Pubkey::new_unique(),
+        reserve_stake: Pubkey::new_unique(),
+        pool_mint: Pubkey::new_unique(),
+        manager_fee_account: Pubkey::new_unique(),
+        withdraw_authority,
+        staker: Pubkey::new_unique(),
+        vote_account,
+        validator_stake,
+        transient_stake,
+    };
+
+    let stake_pool = StakePool {
+        account_type: AccountType::StakePool,
+        version: STAKE_POOL_VERSION,
+        manager: pool.manager,
+        staker: pool.staker,
+        withdraw_bump_seed,
+        validator_list: pool.validator_list,
+        reserve_stake: pool.reserve_stake,
+        pool_mint: pool.pool_mint,
+        manager_fee_account: pool.manager_fee_account,
+        token_program_id: spl_token::id(),
+        total_stake_lamports: RESERVE_LAMPORTS + VALIDATOR_LAMPORTS,
+        pool_token_supply: POOL_TOKEN_SUPPLY,
+        last_update_epoch: EPOCH,
+        sol_deposit_fee: instruction::Fee {
+            numerator: 1,
+            denominator: 100,
+        },
+        ..StakePool::default()
+    };
+    let mut validator_list = ValidatorList::new(1);
+    validator_list.validators[0] = ValidatorStakeInfo {
+        vote_account,
+        status: StakeStatus::Active.into(),
+        target_weight_bps: 0.into(),
+        active_stake_lamports: VALIDATOR_LAMPORTS.into(),
+        transient_stake_lamports: 0.into(),
+        last_update_epoch: EPOCH.into(),
+        eligible_epoch: EPOCH.into(),
+    };
+
+    let rent = Rent::default();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+    let meta = stake_program::Meta {
+        rent_exempt_reserve: stake_rent,
+        authorized: stake_program::Authorized {
+            staker: withdraw_authority,
+            withdrawer: withdraw_authority,
+        },
+        ..stake_program::Meta::default()
+    };
+    let stake = stake_program::Stake {
+        delegation: stake_program::Delegation {
+            voter_pubkey: vote_account,
+            stake: VALIDATOR_LAMPORTS - stake_rent,
+            ..stake_program::Delegation::default()
+        },
+        ..stake_program::Stake::default()
+    };
+
+    let mut accounts = Accounts::default();
+    accounts.add(
+        pool.stake_pool,
+        account(LAMPORTS_PER_SOL, stake_pool.try_to_vec().unwrap(), &id()),
+    );
+    accounts.add(
+        pool.validator_list,
+        account(
+            LAMPORTS_PER_SOL,
+            validator_list.try_to_vec().unwrap(),
+            &id(),
+        ),
+    );
+    accounts.add(
+        pool.reserve_stake,
+        stake_account(
+            stake_rent + RESERVE_LAMPORTS,
+            &stake_program::StakeState::Initialized(meta),
+        ),
+    );
+    accounts.add(
+        pool.validator_stake,
+        stake_account(
+            VALIDATOR_LAMPORTS,
+            &stake_program::StakeState::Stake(meta, stake),
+        ),
+    );
+    accounts.add(
+        pool.pool_mint,
+        mint_account(&withdraw_authority, POOL_TOKEN_SUPPLY),
+    );
+    accounts.add(
+        pool.manager_fee_account,
+        token_account(&pool.pool_mint, &stake_pool.manager, 0),
+    );
+    accounts.add(
+        sysvar::clock::id(),
+        sysvar_account(&Clock {
+            epoch: EPOCH,
+            ..Clock::default()
+        }),
+    );
+    accounts.add(sysvar::rent::id(), sysvar_account(&rent));
+    accounts.add(system_program::id(), program_account());
+    accounts.add(stake_program::id(), program_account());
+    accounts.add(spl_token::id(), program_account());
+
+    (accounts, pool)
+}
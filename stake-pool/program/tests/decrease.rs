@@ -0,0 +1,352 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{instruction::InstructionError, native_token::LAMPORTS_PER_SOL},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state, MINIMUM_ACTIVE_STAKE},
+};
+
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const STAKE_AMOUNT: u64 = 5 * LAMPORTS_PER_SOL;
+const DECREASE_AMOUNT: u64 = 2 * LAMPORTS_PER_SOL;
+
+async fn update(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+    slot: u64,
+) {
+    context.warp_to_slot(slot).unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+/// Sets up a pool with one validator that has `STAKE_AMOUNT` of active stake
+/// moved over from the reserve
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, ValidatorStakeAccount) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            STAKE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    update(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        first_normal_slot + slots_per_epoch,
+    )
+    .await;
+
+    (context, stake_pool_accounts, validator_stake_account)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let reserve_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            DECREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Lamports moved to the transient account, which is deactivating
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    assert_eq!(
+        validator_stake.lamports,
+        validator_lamports - DECREASE_AMOUNT
+    );
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    assert_eq!(transient_stake.lamports, DECREASE_AMOUNT);
+    let transient_stake_state =
+        deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    assert_ne!(
+        transient_stake_state
+            .delegation()
+            .unwrap()
+            .deactivation_epoch,
+        u64::MAX
+    );
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.active_stake_lamports,
+        validator_stake.lamports
+    );
+    assert_eq!(
+        validator_stake_record.transient_stake_lamports,
+        DECREASE_AMOUNT
+    );
+
+    // Once deactivated, the update merges the transient stake into the reserve
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    update(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        first_normal_slot + 2 * slots_per_epoch,
+    )
+    .await;
+
+    let transient_stake = context
+        .banks_client
+        .get_account(validator_stake_account.transient_stake_account)
+        .await
+        .unwrap();
+    assert!(transient_stake.is_none());
+    let reserve = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve.lamports, reserve_lamports + DECREASE_AMOUNT);
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(validator_stake_record.transient_stake_lamports, 0);
+}
+
+#[tokio::test]
+async fn fail_twice_in_one_epoch() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            DECREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let transaction_error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            DECREASE_AMOUNT + 1,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::TransientAccountInUse as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_with_too_little_remaining() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    let transaction_error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            validator_lamports - MINIMUM_ACTIVE_STAKE,
+        )
+        .await
+        .unwrap();
+
+    check_error(transaction_error, InstructionError::InsufficientFunds);
+}
+
+#[tokio::test]
+async fn fail_not_rent_exempt() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+
+    let transaction_error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            stake_rent,
+        )
+        .await
+        .unwrap();
+
+    check_error(transaction_error, InstructionError::AccountNotRentExempt);
+}
+
+#[tokio::test]
+async fn fail_below_minimum_delegation() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+
+    let transaction_error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            stake_rent + MINIMUM_ACTIVE_STAKE - 1,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::StakeSplitBelowMinimum as u32),
+    );
+}
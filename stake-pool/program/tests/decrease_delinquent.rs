@@ -0,0 +1,385 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        clock::{Clock, Epoch},
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    solana_vote_program::vote_state::{VoteInit, VoteState, VoteStateVersions},
+    spl_stake_pool::{error::StakePoolError, stake_program, state, MINIMUM_ACTIVE_STAKE},
+};
+
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const STAKE_AMOUNT: u64 = 5 * LAMPORTS_PER_SOL;
+const DELINQUENT_VALIDATOR_EPOCHS: u64 = 10;
+
+/// Creates an initialized vote account that earned credits in the given epoch,
+/// or never voted if `None`
+fn vote_account(node_pubkey: &Pubkey, last_credited_epoch: Option<Epoch>) -> Account {
+    let mut vote_state = VoteState::new(
+        &VoteInit {
+            node_pubkey: *node_pubkey,
+            authorized_voter: *node_pubkey,
+            authorized_withdrawer: *node_pubkey,
+            commission: 0,
+        },
+        &Clock::default(),
+    );
+    if let Some(epoch) = last_credited_epoch {
+        vote_state.increment_credits(epoch);
+    }
+    let mut data = vec![0; VoteState::size_of()];
+    bincode::serialize_into(
+        &mut data[..],
+        &VoteStateVersions::Current(Box::new(vote_state)),
+    )
+    .unwrap();
+    Account {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        owner: solana_vote_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Sets up a pool with one validator that has `STAKE_AMOUNT` of active stake,
+/// whose vote account last earned credits in `last_credited_epoch`
+async fn setup(
+    last_credited_epoch: Option<Epoch>,
+) -> (ProgramTestContext, StakePoolAccounts, ValidatorStakeAccount) {
+    let stake_pool_accounts = StakePoolAccounts::new();
+    let validator_stake_account = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+
+    let mut program_test = program_test();
+    program_test.add_account(
+        validator_stake_account.vote.pubkey(),
+        vote_account(&Pubkey::new_unique(), last_credited_epoch),
+    );
+    let mut context = program_test.start_with_context().await;
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    create_validator_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.staker,
+        &validator_stake_account.stake_account,
+        &validator_stake_account.vote.pubkey(),
+    )
+    .await;
+    for stake_authorize in &[
+        stake_program::StakeAuthorize::Staker,
+        stake_program::StakeAuthorize::Withdrawer,
+    ] {
+        authorize_stake_account(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &stake_pool_accounts.staker,
+            &validator_stake_account.target_authority,
+            *stake_authorize,
+        )
+        .await;
+    }
+
+    let user = Keypair::new();
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    let error = stake_pool_accounts
+        .add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &user_pool_account.pubkey(),
+        )
+        .await;
+    assert!(error.is_none());
+
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            STAKE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    (context, stake_pool_accounts, validator_stake_account)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup(None).await;
+
+    stake_pool_accounts
+        .set_delinquent_validator_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.manager,
+            DELINQUENT_VALIDATOR_EPOCHS,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.delinquent_validator_epochs,
+        DELINQUENT_VALIDATOR_EPOCHS
+    );
+
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    // anyone can decrease, the payer is the only signer
+    let error = stake_pool_accounts
+        .decrease_delinquent_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.vote.pubkey(),
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // everything above the minimum moves to the transient account
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let required_lamports = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>())
+        + MINIMUM_ACTIVE_STAKE;
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    assert_eq!(validator_stake.lamports, required_lamports);
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    assert_eq!(
+        transient_stake.lamports,
+        validator_lamports - required_lamports
+    );
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.active_stake_lamports,
+        required_lamports
+    );
+    assert_eq!(
+        validator_stake_record.transient_stake_lamports,
+        validator_lamports - required_lamports
+    );
+}
+
+#[tokio::test]
+async fn fail_disabled() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup(None).await;
+
+    let error = stake_pool_accounts
+        .decrease_delinquent_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.vote.pubkey(),
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::DelinquentDecreaseDisabled as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_not_delinquent() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup(Some(0)).await;
+
+    stake_pool_accounts
+        .set_delinquent_validator_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.manager,
+            DELINQUENT_VALIDATOR_EPOCHS,
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .decrease_delinquent_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.vote.pubkey(),
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::ValidatorNotDelinquent as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_vote_account() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup(None).await;
+
+    stake_pool_accounts
+        .set_delinquent_validator_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.manager,
+            DELINQUENT_VALIDATOR_EPOCHS,
+        )
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .decrease_delinquent_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &Pubkey::new_unique(),
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+        )
+        .await
+        .unwrap();
+    check_error(error, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn fail_set_wrong_manager() {
+    let (mut context, stake_pool_accounts, _) = setup(None).await;
+
+    let error = stake_pool_accounts
+        .set_delinquent_validator_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.staker,
+            DELINQUENT_VALIDATOR_EPOCHS,
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
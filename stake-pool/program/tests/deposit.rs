@@ -18,9 +18,7 @@ use {
         transaction::TransactionError,
         transport::TransportError,
     },
-    spl_stake_pool::{
-        borsh::try_from_slice_unchecked, error, id, instruction, stake_program, state,
-    },
+    spl_stake_pool::{error, id, instruction, stake_program, state},
     spl_token::error as token_error,
 };
 
@@ -146,7 +144,7 @@ async fn test_stake_pool_deposit() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     let validator_stake_item_before = validator_list
         .find(&validator_stake_account.vote.pubkey())
         .unwrap();
@@ -196,13 +194,13 @@ async fn test_stake_pool_deposit() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     let validator_stake_item = validator_list
         .find(&validator_stake_account.vote.pubkey())
         .unwrap();
     assert_eq!(
-        validator_stake_item.stake_lamports,
-        validator_stake_item_before.stake_lamports + stake_lamports
+        validator_stake_item.active_stake_lamports,
+        u64::from(validator_stake_item_before.active_stake_lamports) + stake_lamports
     );
 
     // Check validator stake account actual SOL balance
@@ -210,7 +208,7 @@ async fn test_stake_pool_deposit() {
         get_account(&mut banks_client, &validator_stake_account.stake_account).await;
     assert_eq!(
         validator_stake_account.lamports,
-        validator_stake_item.stake_lamports
+        u64::from(validator_stake_item.active_stake_lamports)
     );
 }
 
@@ -324,6 +322,7 @@ async fn test_stake_pool_deposit_with_wrong_token_program_id() {
             &user_stake.pubkey(),
             &validator_stake_account.stake_account,
             &user_pool_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
             &stake_pool_accounts.pool_mint.pubkey(),
             &wrong_token_program.pubkey(),
         )
@@ -534,7 +533,7 @@ async fn test_stake_pool_deposit_with_wrong_deposit_authority() {
     .await
     .unwrap();
 
-    stake_pool_accounts.deposit_authority = Keypair::new().pubkey();
+    stake_pool_accounts.stake_deposit_authority = Keypair::new().pubkey();
 
     let transaction_error = stake_pool_accounts
         .deposit_stake(
@@ -554,7 +553,7 @@ async fn test_stake_pool_deposit_with_wrong_deposit_authority() {
             _,
             InstructionError::Custom(error_index),
         )) => {
-            let program_error = error::StakePoolError::InvalidProgramAddress as u32;
+            let program_error = error::StakePoolError::InvalidStakeDepositAuthority as u32;
             assert_eq!(error_index, program_error);
         }
         _ => panic!("Wrong error occurs while try to make a deposit with wrong deposit authority"),
@@ -760,8 +759,344 @@ async fn test_stake_pool_deposit_with_wrong_mint_for_receiver_acc() {
     }
 }
 
+#[tokio::test]
+async fn test_stake_pool_deposit_with_wrong_vote_account() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    let user = Keypair::new();
+    // make stake account delegated to a validator outside of the pool
+    let user_stake = Keypair::new();
+    let lockup = stake_program::Lockup::default();
+    let stake_authority = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_authority.pubkey(),
+        withdrawer: stake_authority.pubkey(),
+    };
+    create_independent_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake,
+        &authorized,
+        &lockup,
+    )
+    .await;
+
+    let wrong_vote = Keypair::new();
+    create_vote(&mut banks_client, &payer, &recent_blockhash, &wrong_vote).await;
+    delegate_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake.pubkey(),
+        &stake_authority,
+        &wrong_vote.pubkey(),
+    )
+    .await;
+
+    for stake_authorize in &[
+        stake_program::StakeAuthorize::Withdrawer,
+        stake_program::StakeAuthorize::Staker,
+    ] {
+        authorize_stake_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake.pubkey(),
+            &stake_authority,
+            &stake_pool_accounts.deposit_authority,
+            *stake_authorize,
+        )
+        .await;
+    }
+
+    // make pool token account
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::IncorrectDepositVoteAddress as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to deposit stake delegated to another validator"),
+    }
+}
+
+#[tokio::test]
+async fn test_stake_pool_deposit_with_private_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_deposit_authority = Keypair::new();
+    let stake_pool_accounts =
+        StakePoolAccounts::new_with_deposit_authority(stake_deposit_authority);
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    // deposit signed by the private deposit authority goes through
+    let deposit_info = simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+    assert_eq!(
+        get_token_balance(&mut banks_client, &deposit_info.user_pool_account).await,
+        deposit_info.pool_tokens
+    );
+
+    let user = Keypair::new();
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.deposit_authority,
+        withdrawer: stake_pool_accounts.deposit_authority,
+    };
+    create_independent_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // the program-derived deposit authority is no longer accepted
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.deposit_authority,
+            &stake_pool_accounts.withdraw_authority,
+            &user_stake.pubkey(),
+            &validator_stake_account.stake_account,
+            &user_pool_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::InvalidStakeDepositAuthority as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to deposit into private pool without authority"),
+    }
+
+    // the private deposit authority must sign
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.stake_deposit_authority,
+            &stake_pool_accounts.withdraw_authority,
+            &user_stake.pubkey(),
+            &validator_stake_account.stake_account,
+            &user_pool_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::SignatureMissing as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to deposit into private pool without signature"),
+    }
+}
+
 #[tokio::test]
 async fn test_deposit_with_uninitialized_validator_list() {} // TODO
 
 #[tokio::test]
-async fn test_deposit_with_out_of_dated_pool_balances() {} // TODO
+async fn test_deposit_with_out_of_dated_pool_balances() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user = Keypair::new();
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // A new epoch starts, and the pool still holds last epoch's balances
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::StakeListAndPoolOutOfDate as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to make a deposit into an out of date pool"),
+    }
+
+    // Once updated, the deposit goes through
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+    let latest_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    stake_pool_accounts
+        .deposit_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &latest_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .unwrap();
+}
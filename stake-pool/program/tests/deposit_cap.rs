@@ -0,0 +1,265 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, stake_program, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Pubkey) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 3,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    (
+        context,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+async fn get_stake_pool(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap()
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_deposit_sol_up_to_cap() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.deposit_cap(), None);
+    let max_total_lamports = stake_pool.total_stake_lamports + DEPOSIT_AMOUNT;
+    stake_pool_accounts
+        .set_deposit_cap(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            max_total_lamports,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.deposit_cap(), Some(max_total_lamports));
+
+    // Filling the pool right up to the cap is fine
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.total_stake_lamports, max_total_lamports);
+
+    // Going over it is not
+    let transaction_error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            1,
+        )
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::DepositCapExceeded as u32),
+    );
+
+    // Removing the cap opens deposits up again
+    stake_pool_accounts
+        .set_deposit_cap(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            0,
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.deposit_cap(), None);
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        max_total_lamports + DEPOSIT_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_stake_above_cap() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    let stake_lamports = create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    stake_pool_accounts
+        .set_deposit_cap(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            stake_pool.total_stake_lamports + stake_lamports - 1,
+        )
+        .await
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account,
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::DepositCapExceeded as u32),
+    );
+
+    // SOL deposits count against the same cap
+    let transaction_error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            stake_lamports,
+        )
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::DepositCapExceeded as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_set_deposit_cap_wrong_manager() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_deposit_cap(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
@@ -0,0 +1,429 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        hash::Hash,
+        instruction::{Instruction, InstructionError},
+        native_token::LAMPORTS_PER_SOL,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        find_interceptor_authority_program_address, id, instruction,
+        interceptor::{self, InterceptDeposit},
+        state,
+    },
+};
+
+const INTERCEPTOR_ERROR: u32 = 42;
+const COMPUTE_UNITS: u32 = 50_000;
+const MAX_INTERCEPTED_LAMPORTS: u64 = 5 * LAMPORTS_PER_SOL;
+
+/// Interceptor rejecting SOL deposits above `MAX_INTERCEPTED_LAMPORTS`, and
+/// any call not signed by the pool's interceptor authority
+fn process_interceptor(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let deposit = InterceptDeposit::try_from_slice(input)?;
+    if accounts.len() != 3
+        || deposit.vote_account.is_some()
+        || deposit.compute_units != COMPUTE_UNITS
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (interceptor_authority, _) =
+        find_interceptor_authority_program_address(&id(), accounts[0].key);
+    if !accounts[1].is_signer || *accounts[1].key != interceptor_authority {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // the pool is saved with the deposit before the call
+    let stake_pool = state::StakePool::deserialize_checked(&accounts[0].data.borrow())?;
+    if stake_pool.pool_token_supply < deposit.pool_tokens {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if deposit.lamports > MAX_INTERCEPTED_LAMPORTS {
+        return Err(ProgramError::Custom(INTERCEPTOR_ERROR));
+    }
+    Ok(())
+}
+
+struct Setup {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    stake_pool_accounts: StakePoolAccounts,
+    interceptor: Pubkey,
+    user: Keypair,
+    user_pool_account: Pubkey,
+}
+
+async fn setup() -> Setup {
+    let interceptor = Pubkey::new_unique();
+    let mut program_test = program_test();
+    program_test.add_program(
+        "deposit_interceptor",
+        interceptor,
+        processor!(process_interceptor),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        4 * MAX_INTERCEPTED_LAMPORTS,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    Setup {
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        interceptor,
+        user,
+        user_pool_account: user_pool_account.pubkey(),
+    }
+}
+
+impl Setup {
+    /// Sets the pool's deposit interceptor, signed by `manager` or the pool's
+    /// manager if `None`
+    async fn set_deposit_interceptor(
+        &mut self,
+        manager: Option<&Keypair>,
+        deposit_interceptor: Option<&Pubkey>,
+        strict: bool,
+    ) -> Result<(), TransportError> {
+        let manager = manager.unwrap_or(&self.stake_pool_accounts.manager);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_deposit_interceptor(
+                &id(),
+                &self.stake_pool_accounts.stake_pool.pubkey(),
+                &manager.pubkey(),
+                deposit_interceptor,
+                COMPUTE_UNITS,
+                strict,
+            )
+            .unwrap()],
+            Some(&self.payer.pubkey()),
+            &[&self.payer, manager],
+            self.recent_blockhash,
+        );
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    async fn stake_pool(&mut self) -> state::StakePool {
+        let stake_pool = get_account(
+            &mut self.banks_client,
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+        )
+        .await;
+        state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap()
+    }
+
+    fn deposit_sol_instruction(
+        &self,
+        lamports: u64,
+        deposit_interceptor: Option<&Pubkey>,
+    ) -> Instruction {
+        let mut deposit_instruction = instruction::deposit_sol(
+            &id(),
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+            &self.stake_pool_accounts.withdraw_authority,
+            &self.stake_pool_accounts.reserve_stake.pubkey(),
+            &self.user.pubkey(),
+            &self.user_pool_account,
+            &self.stake_pool_accounts.pool_fee_account.pubkey(),
+            &self.stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+            lamports,
+        )
+        .unwrap();
+        if let Some(deposit_interceptor) = deposit_interceptor {
+            instruction::add_deposit_interceptor_accounts(
+                &mut deposit_instruction,
+                &id(),
+                &self.stake_pool_accounts.stake_pool.pubkey(),
+                deposit_interceptor,
+            );
+        }
+        deposit_instruction
+    }
+
+    async fn process(&mut self, deposit_instruction: Instruction) -> Result<(), TransportError> {
+        self.recent_blockhash = self
+            .banks_client
+            .get_new_blockhash(&self.recent_blockhash)
+            .await
+            .unwrap()
+            .0;
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_instruction],
+            Some(&self.payer.pubkey()),
+            &[&self.payer, &self.user],
+            self.recent_blockhash,
+        );
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    async fn deposit_sol(
+        &mut self,
+        lamports: u64,
+        deposit_interceptor: Option<&Pubkey>,
+    ) -> Result<(), TransportError> {
+        let deposit_instruction = self.deposit_sol_instruction(lamports, deposit_interceptor);
+        self.process(deposit_instruction).await
+    }
+}
+
+fn check_error(transport_error: TransportError, expected_error: InstructionError) {
+    match transport_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_strict() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    setup
+        .set_deposit_interceptor(None, Some(&interceptor), true)
+        .await
+        .unwrap();
+
+    let stake_pool = setup.stake_pool().await;
+    assert_eq!(stake_pool.deposit_interceptor(), Some(interceptor));
+    assert_eq!(stake_pool.deposit_interceptor_compute_units, COMPUTE_UNITS);
+    assert!(stake_pool.deposit_interceptor_strict);
+
+    setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS, Some(&interceptor))
+        .await
+        .unwrap();
+    assert!(get_token_balance(&mut setup.banks_client, &setup.user_pool_account).await > 0);
+
+    // the interceptor fails the deposit
+    let error = setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS + 1, Some(&interceptor))
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::Custom(INTERCEPTOR_ERROR));
+
+    // and cannot be left out
+    let error = setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS + 1, None)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::InvalidDepositInterceptor as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_optional_left_out() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    setup
+        .set_deposit_interceptor(None, Some(&interceptor), false)
+        .await
+        .unwrap();
+
+    let error = setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS + 1, Some(&interceptor))
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::Custom(INTERCEPTOR_ERROR));
+
+    setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS + 1, None)
+        .await
+        .unwrap();
+    assert!(get_token_balance(&mut setup.banks_client, &setup.user_pool_account).await > 0);
+}
+
+#[tokio::test]
+async fn success_unset() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    setup
+        .set_deposit_interceptor(None, Some(&interceptor), true)
+        .await
+        .unwrap();
+    setup
+        .set_deposit_interceptor(None, None, false)
+        .await
+        .unwrap();
+
+    let stake_pool = setup.stake_pool().await;
+    assert_eq!(stake_pool.deposit_interceptor(), None);
+    setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS + 1, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fail_wrong_interceptor() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    setup
+        .set_deposit_interceptor(None, Some(&interceptor), false)
+        .await
+        .unwrap();
+
+    let error = setup
+        .deposit_sol(MAX_INTERCEPTED_LAMPORTS, Some(&spl_token::id()))
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::InvalidDepositInterceptor as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_authority_as_interceptor_authority() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    setup
+        .set_deposit_interceptor(None, Some(&interceptor), true)
+        .await
+        .unwrap();
+
+    // the interceptor is never handed a signer over the pool's funds
+    let mut deposit_instruction =
+        setup.deposit_sol_instruction(MAX_INTERCEPTED_LAMPORTS, Some(&interceptor));
+    deposit_instruction.accounts.last_mut().unwrap().pubkey =
+        setup.stake_pool_accounts.withdraw_authority;
+    let error = setup.process(deposit_instruction).await.unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::InvalidProgramAddress as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_set_not_a_program() {
+    let mut setup = setup().await;
+    let user_pool_account = setup.user_pool_account;
+    let error = setup
+        .set_deposit_interceptor(None, Some(&user_pool_account), false)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::InvalidDepositInterceptor as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_set_wrong_manager() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    let error = setup
+        .set_deposit_interceptor(Some(&Keypair::new()), Some(&interceptor), false)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_interceptor_called_directly() {
+    let mut setup = setup().await;
+    let interceptor = setup.interceptor;
+    let stake_pool_address = setup.stake_pool_accounts.stake_pool.pubkey();
+    let deposit = InterceptDeposit {
+        vote_account: None,
+        lamports: MAX_INTERCEPTED_LAMPORTS,
+        pool_tokens: 0,
+        compute_units: COMPUTE_UNITS,
+    };
+
+    // the interceptor authority cannot sign outside the pool
+    let (interceptor_authority, _) =
+        find_interceptor_authority_program_address(&id(), &stake_pool_address);
+    let mut instruction = interceptor::intercept_deposit(
+        &interceptor,
+        &stake_pool_address,
+        &interceptor_authority,
+        &setup.user_pool_account,
+        &deposit,
+    )
+    .unwrap();
+    instruction.accounts[1].is_signer = false;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.recent_blockhash,
+    );
+    let error = setup
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::MissingRequiredSignature);
+
+    // and no other signer stands in for it
+    let impostor = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[interceptor::intercept_deposit(
+            &interceptor,
+            &stake_pool_address,
+            &impostor.pubkey(),
+            &setup.user_pool_account,
+            &deposit,
+        )
+        .unwrap()],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &impostor],
+        setup.recent_blockhash,
+    );
+    let error = setup
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::MissingRequiredSignature);
+}
@@ -0,0 +1,613 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, find_deposit_record_address, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+const DEPOSIT_LOCK_EPOCHS: u64 = 1;
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Pubkey) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .set_deposit_lock(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            DEPOSIT_LOCK_EPOCHS,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let user_pool_account = create_user(&mut context, &stake_pool_accounts, &user).await;
+    (context, stake_pool_accounts, user, user_pool_account)
+}
+
+/// Funds `user` and creates a pool token account it owns
+async fn create_user(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    user: &Keypair,
+) -> Pubkey {
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 3,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    user_pool_account.pubkey()
+}
+
+/// Creates a pool token account owned by the deposit record of `depositor`
+async fn create_escrow(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    depositor: &Pubkey,
+) -> Pubkey {
+    let (deposit_record_address, _) =
+        find_deposit_record_address(&id(), &stake_pool_accounts.stake_pool.pubkey(), depositor);
+    let escrow = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &escrow,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &deposit_record_address,
+    )
+    .await
+    .unwrap();
+    escrow.pubkey()
+}
+
+async fn deposit_sol_with_record(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    depositor: &Keypair,
+    pool_tokens_to: &Pubkey,
+) -> Result<(), TransportError> {
+    let mut deposit_sol = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &depositor.pubkey(),
+        pool_tokens_to,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &spl_token::id(),
+        DEPOSIT_AMOUNT,
+    )
+    .unwrap();
+    instruction::add_deposit_record_accounts(
+        &mut deposit_sol,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &depositor.pubkey(),
+    );
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_sol],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, depositor],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+async fn claim(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    depositor: &Keypair,
+    escrow: &Pubkey,
+    pool_tokens_to: &Pubkey,
+) -> Result<(), TransportError> {
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::claim_locked_pool_tokens(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &depositor.pubkey(),
+            escrow,
+            pool_tokens_to,
+            &spl_token::id(),
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, depositor],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Moves to the epoch the lock of a deposit made now expires in, and updates
+/// the pool
+async fn wait_for_lock(context: &mut ProgramTestContext, stake_pool_accounts: &StakePoolAccounts) {
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + DEPOSIT_LOCK_EPOCHS * slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_claim_after_lock() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+    let escrow = create_escrow(&mut context, &stake_pool_accounts, &user.pubkey()).await;
+
+    deposit_sol_with_record(&mut context, &stake_pool_accounts, &user, &escrow)
+        .await
+        .unwrap();
+
+    let (deposit_record_address, _) = find_deposit_record_address(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &user.pubkey(),
+    );
+    let deposit_record = get_account(&mut context.banks_client, &deposit_record_address).await;
+    assert_eq!(deposit_record.owner, id());
+    let deposit_record = state::DepositRecord::try_from_slice(&deposit_record.data).unwrap();
+    assert!(deposit_record.is_valid());
+    assert_eq!(deposit_record.depositor, user.pubkey());
+    assert_eq!(deposit_record.locked_until_epoch, DEPOSIT_LOCK_EPOCHS);
+    let pool_tokens = get_token_balance(&mut context.banks_client, &escrow).await;
+    assert!(pool_tokens > 0);
+
+    let transaction_error = claim(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &escrow,
+        &user_pool_account,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::DepositLocked as u32),
+    );
+
+    wait_for_lock(&mut context, &stake_pool_accounts).await;
+    claim(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &escrow,
+        &user_pool_account,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &escrow).await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &user_pool_account).await,
+        pool_tokens
+    );
+
+    // claimed pool tokens are free to withdraw
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_pool_account,
+            &user.pubkey(),
+            pool_tokens,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fail_transfer_then_withdraw() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+    let escrow = create_escrow(&mut context, &stake_pool_accounts, &user.pubkey()).await;
+    deposit_sol_with_record(&mut context, &stake_pool_accounts, &user, &escrow)
+        .await
+        .unwrap();
+    let pool_tokens = get_token_balance(&mut context.banks_client, &escrow).await;
+
+    // the depositor cannot move locked pool tokens to a fresh wallet
+    let fresh_wallet = Keypair::new();
+    let fresh_wallet_pool_account =
+        create_user(&mut context, &stake_pool_accounts, &fresh_wallet).await;
+    let transaction = Transaction::new_signed_with_payer(
+        &[spl_token::instruction::transfer(
+            &spl_token::id(),
+            &escrow,
+            &fresh_wallet_pool_account,
+            &user.pubkey(),
+            &[],
+            pool_tokens,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(spl_token::error::TokenError::OwnerMismatch as u32),
+    );
+
+    // nor have them minted anywhere but in the escrow
+    let transaction_error = deposit_sol_with_record(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &fresh_wallet_pool_account,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidDepositEscrow as u32),
+    );
+
+    // nor claim them into the fresh wallet before the lock is over
+    let transaction_error = claim(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &escrow,
+        &fresh_wallet_pool_account,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::DepositLocked as u32),
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &fresh_wallet_pool_account).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn fail_third_party_deposit() {
+    let (mut context, stake_pool_accounts, victim, victim_pool_account) = setup().await;
+    let victim_escrow = create_escrow(&mut context, &stake_pool_accounts, &victim.pubkey()).await;
+    deposit_sol_with_record(&mut context, &stake_pool_accounts, &victim, &victim_escrow)
+        .await
+        .unwrap();
+
+    let attacker = Keypair::new();
+    let attacker_escrow =
+        create_escrow(&mut context, &stake_pool_accounts, &attacker.pubkey()).await;
+    create_user(&mut context, &stake_pool_accounts, &attacker).await;
+
+    // deposits by someone else reach neither the victim's escrow nor their
+    // plain token account
+    for pool_tokens_to in &[victim_escrow, victim_pool_account] {
+        let transaction_error = deposit_sol_with_record(
+            &mut context,
+            &stake_pool_accounts,
+            &attacker,
+            pool_tokens_to,
+        )
+        .await
+        .unwrap_err();
+        check_error(
+            transaction_error,
+            InstructionError::Custom(StakePoolError::InvalidDepositEscrow as u32),
+        );
+    }
+
+    // and a deposit in their own escrow leaves the victim's lock alone
+    wait_for_lock(&mut context, &stake_pool_accounts).await;
+    deposit_sol_with_record(
+        &mut context,
+        &stake_pool_accounts,
+        &attacker,
+        &attacker_escrow,
+    )
+    .await
+    .unwrap();
+    claim(
+        &mut context,
+        &stake_pool_accounts,
+        &victim,
+        &victim_escrow,
+        &victim_pool_account,
+    )
+    .await
+    .unwrap();
+    assert!(get_token_balance(&mut context.banks_client, &victim_pool_account).await > 0);
+
+    // the attacker cannot claim the victim's escrow either
+    deposit_sol_with_record(&mut context, &stake_pool_accounts, &victim, &victim_escrow)
+        .await
+        .unwrap();
+    let transaction_error = claim(
+        &mut context,
+        &stake_pool_accounts,
+        &attacker,
+        &victim_escrow,
+        &victim_pool_account,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidDepositEscrow as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_lock_disabled() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+    let escrow = create_escrow(&mut context, &stake_pool_accounts, &user.pubkey()).await;
+
+    deposit_sol_with_record(&mut context, &stake_pool_accounts, &user, &escrow)
+        .await
+        .unwrap();
+
+    stake_pool_accounts
+        .set_deposit_lock(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            0,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.deposit_lock_epochs, 0);
+
+    // The earlier deposit is released, and no deposit record is needed anymore
+    claim(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &escrow,
+        &user_pool_account,
+    )
+    .await
+    .unwrap();
+    assert!(get_token_balance(&mut context.banks_client, &user_pool_account).await > 0);
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT / 2,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fail_deposit_without_record() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    check_error(transaction_error, InstructionError::NotEnoughAccountKeys);
+}
+
+#[tokio::test]
+async fn fail_deposit_with_wrong_record() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+    let escrow = create_escrow(&mut context, &stake_pool_accounts, &user.pubkey()).await;
+
+    let mut deposit_sol = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &user.pubkey(),
+        &escrow,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &spl_token::id(),
+        DEPOSIT_AMOUNT,
+    )
+    .unwrap();
+    instruction::add_deposit_record_accounts(
+        &mut deposit_sol,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &user.pubkey(),
+    );
+    let record_index = deposit_sol.accounts.len() - 3;
+    deposit_sol.accounts[record_index].pubkey = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_sol],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidDepositRecord as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_depositor_not_signer() {
+    let (mut context, stake_pool_accounts, user, _) = setup().await;
+    let depositor = Pubkey::new_unique();
+    let escrow = create_escrow(&mut context, &stake_pool_accounts, &depositor).await;
+
+    let mut deposit_sol = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &user.pubkey(),
+        &escrow,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &spl_token::id(),
+        DEPOSIT_AMOUNT,
+    )
+    .unwrap();
+    instruction::add_deposit_record_accounts(
+        &mut deposit_sol,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &depositor,
+    );
+    let depositor_index = deposit_sol.accounts.len() - 2;
+    deposit_sol.accounts[depositor_index].is_signer = false;
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_sol],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::SignatureMissing as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_deposit_lock(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            0,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_account,
+    )
+}
+
+/// Deposits a new stake account with `lockup`, returning the receiving pool
+/// token account
+async fn deposit_with_lockup(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+    lockup: &stake_program::Lockup,
+) -> Result<Pubkey, TransportError> {
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    create_independent_stake_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &user_stake,
+        &authorized,
+        lockup,
+    )
+    .await;
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    stake_pool_accounts
+        .deposit_stake(
+            banks_client,
+            payer,
+            recent_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &validator_stake_account.stake_account,
+        )
+        .await?;
+    Ok(user_pool_account.pubkey())
+}
+
+fn check_lockup_error(transport_error: TransportError) {
+    match transport_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            assert_eq!(error_index, StakePoolError::StakeLockupInForce as u32);
+        }
+        _ => panic!("Wrong error occurs while depositing stake with a lockup in force"),
+    }
+}
+
+#[tokio::test]
+async fn success_expired_lockup() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    let lockup = stake_program::Lockup {
+        unix_timestamp: 1,
+        epoch: 0,
+        custodian: Pubkey::new_unique(),
+    };
+    let user_pool_account = deposit_with_lockup(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        &lockup,
+    )
+    .await
+    .unwrap();
+
+    assert!(get_token_balance(&mut banks_client, &user_pool_account).await > 0);
+}
+
+#[tokio::test]
+async fn fail_epoch_lockup_in_force() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    let lockup = stake_program::Lockup {
+        unix_timestamp: 0,
+        epoch: 1_000,
+        custodian: Pubkey::new_unique(),
+    };
+    let error = deposit_with_lockup(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        &lockup,
+    )
+    .await
+    .unwrap_err();
+
+    check_lockup_error(error);
+}
+
+#[tokio::test]
+async fn fail_timestamp_lockup_in_force() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    let lockup = stake_program::Lockup {
+        unix_timestamp: i64::MAX,
+        epoch: 0,
+        custodian: Pubkey::new_unique(),
+    };
+    let error = deposit_with_lockup(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        &lockup,
+    )
+    .await
+    .unwrap_err();
+
+    check_lockup_error(error);
+}
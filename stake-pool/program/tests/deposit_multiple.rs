@@ -0,0 +1,352 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Vec<ValidatorStakeAccount>,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let mut validator_stake_accounts = vec![];
+    for _ in 0..2 {
+        validator_stake_accounts.push(
+            simple_add_validator_to_pool(
+                &mut banks_client,
+                &payer,
+                &recent_blockhash,
+                &stake_pool_accounts,
+            )
+            .await,
+        );
+    }
+
+    let user = Keypair::new();
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_accounts,
+        user_pool_account.pubkey(),
+    )
+}
+
+/// Creates a stake account ready to be deposited, returning its address and
+/// lamports
+async fn create_user_stake(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> (Pubkey, u64) {
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    let stake_lamports = create_independent_stake_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    (user_stake.pubkey(), stake_lamports)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_accounts,
+        user_pool_account,
+    ) = setup().await;
+
+    // two deposits on the first validator, one on the second
+    let mut stakes = vec![];
+    let mut stake_lamports = vec![];
+    for validator_stake_account in [0, 0, 1]
+        .iter()
+        .map(|index| &validator_stake_accounts[*index])
+    {
+        let (user_stake, lamports) = create_user_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        stakes.push((user_stake, validator_stake_account.stake_account));
+        stake_lamports.push(lamports);
+    }
+    let total_lamports: u64 = stake_lamports.iter().sum();
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(stake_pool_before.data.as_slice()).unwrap();
+    let validator_list_before = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list_before =
+        state::ValidatorList::deserialize_checked(validator_list_before.data.as_slice()).unwrap();
+
+    stake_pool_accounts
+        .deposit_stakes(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stakes,
+            &user_pool_account,
+        )
+        .await
+        .unwrap();
+
+    // every deposited stake account is merged away
+    for (user_stake, _) in stakes.iter() {
+        assert!(banks_client
+            .get_account(*user_stake)
+            .await
+            .expect("get_account")
+            .is_none());
+    }
+
+    // pool tokens are minted once for the whole batch, 1:1 with no fee
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports + total_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply + total_lamports
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account).await,
+        total_lamports
+    );
+
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let deposited_lamports = [stake_lamports[0] + stake_lamports[1], stake_lamports[2]];
+    for (validator_stake_account, deposited_lamports) in
+        validator_stake_accounts.iter().zip(&deposited_lamports)
+    {
+        let vote_account = validator_stake_account.vote.pubkey();
+        let item_before = validator_list_before.find(&vote_account).unwrap();
+        let item = validator_list.find(&vote_account).unwrap();
+        assert_eq!(
+            u64::from(item.active_stake_lamports),
+            u64::from(item_before.active_stake_lamports) + deposited_lamports
+        );
+        let validator_stake =
+            get_account(&mut banks_client, &validator_stake_account.stake_account).await;
+        assert_eq!(
+            validator_stake.lamports,
+            u64::from(item.active_stake_lamports)
+        );
+    }
+}
+
+#[tokio::test]
+async fn fail_without_stake_accounts() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        _validator_stake_accounts,
+        user_pool_account,
+    ) = setup().await;
+
+    let error = stake_pool_accounts
+        .deposit_stakes(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &[],
+            &user_pool_account,
+        )
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn fail_with_unknown_validator() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_accounts,
+        user_pool_account,
+    ) = setup().await;
+
+    let unknown_validator_stake_account = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    unknown_validator_stake_account
+        .create_and_delegate(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stake_pool_accounts.staker,
+        )
+        .await;
+
+    let mut stakes = vec![];
+    for validator_stake_account in [
+        validator_stake_accounts[0].stake_account,
+        unknown_validator_stake_account.stake_account,
+    ]
+    .iter()
+    {
+        let (user_stake, _) = create_user_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        stakes.push((user_stake, *validator_stake_account));
+    }
+
+    let error = stake_pool_accounts
+        .deposit_stakes(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stakes,
+            &user_pool_account,
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::ValidatorNotFound as u32),
+    );
+
+    // the whole batch is rejected
+    assert!(banks_client
+        .get_account(stakes[0].0)
+        .await
+        .expect("get_account")
+        .is_some());
+}
+
+#[tokio::test]
+async fn success_with_private_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new_with_deposit_authority(Keypair::new());
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &Keypair::new().pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let mut stakes = vec![];
+    let mut total_lamports = 0;
+    for _ in 0..2 {
+        let (user_stake, lamports) = create_user_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        stakes.push((user_stake, validator_stake_account.stake_account));
+        total_lamports += lamports;
+    }
+
+    stake_pool_accounts
+        .deposit_stakes(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &stakes,
+            &user_pool_account.pubkey(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await,
+        total_lamports
+    );
+}
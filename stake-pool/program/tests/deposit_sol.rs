@@ -0,0 +1,392 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+
+    // make pool token account
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let reserve_before = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    // Empty pool mints 1:1
+    let user_token_balance = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT);
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.total_stake_lamports, DEPOSIT_AMOUNT);
+    assert_eq!(stake_pool.pool_token_supply, DEPOSIT_AMOUNT);
+
+    // Lamports landed in the reserve
+    let reserve = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve.lamports, reserve_before.lamports + DEPOSIT_AMOUNT);
+}
+
+#[tokio::test]
+async fn success_with_fee() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let sol_deposit_fee = instruction::Fee {
+        numerator: 1,
+        denominator: 100,
+    };
+    stake_pool_accounts
+        .set_fee(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::FeeType::SolDeposit(sol_deposit_fee),
+        )
+        .await
+        .unwrap();
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    let fee = sol_deposit_fee.apply(DEPOSIT_AMOUNT).unwrap();
+    assert!(fee > 0);
+    let user_token_balance = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT - fee);
+    let manager_token_balance = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(manager_token_balance, fee);
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.pool_token_supply, DEPOSIT_AMOUNT);
+}
+
+#[tokio::test]
+async fn success_with_rewards() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    simple_deposit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+
+    // Add extra funds, simulating rewards
+    const EXTRA_STAKE_AMOUNT: u64 = 1_000_000;
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &validator_stake_account.stake_account,
+        EXTRA_STAKE_AMOUNT,
+    )
+    .await;
+
+    context.warp_to_slot(50_000).unwrap();
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let stake_pool_before = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+    assert!(stake_pool_before.total_stake_lamports > stake_pool_before.pool_token_supply);
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    // Tokens are minted at the post-reward exchange rate
+    let expected_tokens = stake_pool_before
+        .calc_pool_tokens_for_deposit_floor(DEPOSIT_AMOUNT)
+        .unwrap();
+    assert!(expected_tokens < DEPOSIT_AMOUNT);
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_token_balance, expected_tokens);
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports + DEPOSIT_AMOUNT
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply + expected_tokens
+    );
+
+    // Deposited lamports sit in the reserve above its rent-exempt minimum
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let reserve = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(
+        reserve.lamports,
+        rent.minimum_balance(reserve.data.len()) + DEPOSIT_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn fail_with_wrong_token_program_id() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let wrong_token_program = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit_sol(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &user.pubkey(),
+            &user_pool_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &wrong_token_program.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error occurs while try to deposit sol with wrong token program ID"),
+    }
+}
+
+#[tokio::test]
+async fn success_with_sol_deposit_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let sol_deposit_authority = Keypair::new();
+    stake_pool_accounts
+        .set_funding_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            Some(&sol_deposit_authority.pubkey()),
+            instruction::FundingType::SolDeposit,
+        )
+        .await
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::InvalidSolDepositAuthority as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to deposit sol without deposit authority"),
+    }
+
+    stake_pool_accounts
+        .deposit_sol_with_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account,
+            &sol_deposit_authority,
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    let user_token_balance = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT);
+}
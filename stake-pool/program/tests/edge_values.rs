@@ -0,0 +1,142 @@
+mod cpi_harness;
+
+use {
+    borsh::BorshSerialize,
+    cpi_harness::*,
+    solana_program::{
+        instruction::Instruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, rent::Rent,
+        system_program,
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id, instruction, stake_program,
+        state::{StakePool, ValidatorList},
+    },
+};
+
+/// Overwrites the pool's balances, keeping the pool mint's supply in line
+fn set_balances(accounts: &mut Accounts, pool: &Pool, total_stake_lamports: u64, supply: u64) {
+    let mut stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    stake_pool.total_stake_lamports = total_stake_lamports;
+    stake_pool.pool_token_supply = supply;
+    accounts.0.get_mut(&pool.stake_pool).unwrap().data = stake_pool.try_to_vec().unwrap();
+    accounts.add(
+        pool.pool_mint,
+        mint_account(&pool.withdraw_authority, supply),
+    );
+}
+
+fn deposit_sol_instruction(accounts: &mut Accounts, pool: &Pool) -> Instruction {
+    let user = Pubkey::new_unique();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user,
+        account(2 * LAMPORTS_PER_SOL, vec![], &system_program::id()),
+    );
+    accounts.add(user_pool_account, token_account(&pool.pool_mint, &user, 0));
+    instruction::deposit_sol(
+        &id(),
+        &pool.stake_pool,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &user,
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+        LAMPORTS_PER_SOL,
+    )
+    .unwrap()
+}
+
+fn update_balance_instruction(pool: &Pool) -> Instruction {
+    instruction::update_stake_pool_balance(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.reserve_stake,
+        &pool.withdraw_authority,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+    )
+    .unwrap()
+}
+
+fn stake_rent() -> u64 {
+    Rent::default().minimum_balance(std::mem::size_of::<stake_program::StakeState>())
+}
+
+#[test]
+fn deposit_sol_fail_unbacked_supply() {
+    let (mut accounts, pool) = setup_pool();
+    set_balances(&mut accounts, &pool, 0, POOL_TOKEN_SUPPLY);
+    let instruction = deposit_sol_instruction(&mut accounts, &pool);
+    let before = Accounts(accounts.0.clone());
+
+    let error = accounts.process(&instruction, None).unwrap_err();
+    assert_eq!(error, StakePoolError::CalculationFailure.into());
+    assert_eq!(before.0, accounts.0);
+}
+
+#[test]
+fn deposit_sol_fail_supply_overflow() {
+    let (mut accounts, pool) = setup_pool();
+    set_balances(
+        &mut accounts,
+        &pool,
+        RESERVE_LAMPORTS + VALIDATOR_LAMPORTS,
+        u64::MAX - 1,
+    );
+    let instruction = deposit_sol_instruction(&mut accounts, &pool);
+    let before = Accounts(accounts.0.clone());
+
+    // fails before any pool token is minted
+    let error = accounts.process(&instruction, None).unwrap_err();
+    assert_eq!(error, StakePoolError::CalculationFailure.into());
+    assert_eq!(before.0, accounts.0);
+}
+
+#[test]
+fn update_stake_pool_balance_fail_reserve_below_rent_exemption() {
+    let (mut accounts, pool) = setup_pool();
+    accounts.0.get_mut(&pool.reserve_stake).unwrap().lamports = stake_rent() - 1;
+
+    let error = accounts
+        .process(&update_balance_instruction(&pool), None)
+        .unwrap_err();
+    assert_eq!(error, StakePoolError::CalculationFailure.into());
+}
+
+#[test]
+fn update_stake_pool_balance_at_rent_exemption() {
+    let (mut accounts, pool) = setup_pool();
+    accounts.0.get_mut(&pool.reserve_stake).unwrap().lamports = stake_rent();
+    accounts
+        .process(&update_balance_instruction(&pool), None)
+        .unwrap();
+
+    let stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    assert_eq!(stake_pool.total_stake_lamports, VALIDATOR_LAMPORTS);
+}
+
+#[test]
+fn update_stake_pool_balance_unbacked_supply() {
+    // every lamport is gone, but the pool tokens are still out
+    let (mut accounts, pool) = setup_pool();
+    set_balances(&mut accounts, &pool, 0, POOL_TOKEN_SUPPLY);
+    accounts.0.get_mut(&pool.reserve_stake).unwrap().lamports = stake_rent();
+    let mut validator_list =
+        ValidatorList::deserialize_checked(accounts.data(&pool.validator_list)).unwrap();
+    validator_list.validators[0].active_stake_lamports = 0.into();
+    accounts.0.get_mut(&pool.validator_list).unwrap().data = validator_list.try_to_vec().unwrap();
+
+    // the update still goes through, without a fee, so that the pool does
+    // not get stuck out of date
+    accounts
+        .process(&update_balance_instruction(&pool), None)
+        .unwrap();
+    let stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    assert_eq!(stake_pool.total_stake_lamports, 0);
+    assert_eq!(stake_pool.pool_token_supply, POOL_TOKEN_SUPPLY);
+    assert_eq!(stake_pool.price_oracle_rate(), Some(0));
+}
@@ -0,0 +1,320 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = 4 * LAMPORTS_PER_SOL;
+
+/// Pool holding `DEPOSIT_AMOUNT` from a user, updated in a fresh epoch so
+/// that the deposit does not count against the epoch's net outflow
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Pubkey) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+
+    next_epoch(&mut context, &stake_pool_accounts, 1).await;
+
+    (
+        context,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+/// Warps to the `epoch`-th normal epoch and updates the pool
+async fn next_epoch(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    epoch: u64,
+) {
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + epoch * slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+async fn get_stake_pool(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap()
+}
+
+async fn withdraw_sol(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    user_pool_account: &Pubkey,
+    pool_tokens: u64,
+) -> Result<(), TransportError> {
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            user_pool_account,
+            &Keypair::new().pubkey(),
+            pool_tokens,
+        )
+        .await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_withdraw_up_to_cap() {
+    let (mut context, stake_pool_accounts, user, user_pool_account) = setup().await;
+
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.epoch_net_outflow_cap(), None);
+    assert_eq!(stake_pool.epoch_deposited_lamports, 0);
+    stake_pool_accounts
+        .set_max_epoch_net_outflow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            2_500,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.epoch_net_outflow_cap(), Some(2_500));
+
+    // a quarter of the pool may leave this epoch
+    withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 5,
+    )
+    .await
+    .unwrap();
+    let transaction_error = withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 10,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::EpochNetOutflowExceeded as u32),
+    );
+
+    // new deposits can leave again
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            DEPOSIT_AMOUNT / 10,
+        )
+        .await
+        .unwrap();
+    withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 10,
+    )
+    .await
+    .unwrap();
+
+    // and the next epoch starts afresh
+    next_epoch(&mut context, &stake_pool_accounts, 2).await;
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.epoch_withdrawn_lamports, 0);
+    assert_eq!(stake_pool.epoch_deposited_lamports, 0);
+    withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 10,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn success_remove_cap() {
+    let (mut context, stake_pool_accounts, _, user_pool_account) = setup().await;
+
+    stake_pool_accounts
+        .set_max_epoch_net_outflow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            1,
+        )
+        .await
+        .unwrap();
+    let transaction_error = withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 2,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::EpochNetOutflowExceeded as u32),
+    );
+
+    stake_pool_accounts
+        .set_max_epoch_net_outflow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            0,
+        )
+        .await
+        .unwrap();
+    withdraw_sol(
+        &mut context,
+        &stake_pool_accounts,
+        &user_pool_account,
+        DEPOSIT_AMOUNT / 2,
+    )
+    .await
+    .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.epoch_net_outflow_cap(), None);
+}
+
+#[tokio::test]
+async fn fail_set_above_maximum() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_max_epoch_net_outflow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            10_001,
+        )
+        .await
+        .unwrap_err();
+    check_error(transaction_error, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn fail_set_wrong_manager() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_max_epoch_net_outflow(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            2_500,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
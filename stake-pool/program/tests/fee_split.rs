@@ -0,0 +1,453 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts, Keypair) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let secondary_fee_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &secondary_fee_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &Pubkey::new_unique(),
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        secondary_fee_account,
+    )
+}
+
+async fn set_fee_split(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    manager: &Keypair,
+    secondary_fee_account: Option<&Pubkey>,
+    split: instruction::Fee,
+) -> Result<(), TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_fee_split(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &manager.pubkey(),
+            secondary_fee_account,
+            split,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer, manager],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+async fn set_sol_deposit_fee_treasury(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    treasury: Option<&Pubkey>,
+) -> Result<(), TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_sol_deposit_fee_treasury(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            treasury,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer, &stake_pool_accounts.manager],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+async fn update_with_secondary_fee_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    secondary_fee_account: Option<&Pubkey>,
+) -> Result<(), TransportError> {
+    let mut update_instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .unwrap();
+    if let Some(secondary_fee_account) = secondary_fee_account {
+        instruction::add_secondary_fee_account(&mut update_instruction, secondary_fee_account);
+    }
+    let transaction = Transaction::new_signed_with_payer(
+        &[update_instruction],
+        Some(&payer.pubkey()),
+        &[payer],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+async fn get_stake_pool(
+    banks_client: &mut BanksClient,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn success_split_epoch_fee() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, secondary_fee_account) =
+        setup().await;
+    let split = instruction::Fee {
+        numerator: 1,
+        denominator: 4,
+    };
+    set_fee_split(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(&secondary_fee_account.pubkey()),
+        split,
+    )
+    .await
+    .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    assert_eq!(
+        stake_pool.secondary_fee_account(),
+        Some(secondary_fee_account.pubkey())
+    );
+    assert_eq!(stake_pool.secondary_fee_split, split);
+
+    // rewards landing in the reserve are charged the epoch fee
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    let pool_token_supply_before = get_stake_pool(&mut banks_client, &stake_pool_accounts)
+        .await
+        .pool_token_supply;
+    let manager_fee_before = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    update_with_secondary_fee_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&secondary_fee_account.pubkey()),
+    )
+    .await
+    .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    let fee = stake_pool.pool_token_supply - pool_token_supply_before;
+    let manager_fee = get_token_balance(
+        &mut banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await
+        - manager_fee_before;
+    let secondary_fee = get_token_balance(&mut banks_client, &secondary_fee_account.pubkey()).await;
+    assert!(secondary_fee > 0);
+    assert_eq!(manager_fee + secondary_fee, fee);
+    assert_eq!(
+        stake_pool.split_epoch_fee(fee),
+        Some((manager_fee, secondary_fee))
+    );
+}
+
+#[tokio::test]
+async fn success_sol_deposit_fee_treasury() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+    let sol_deposit_fee = instruction::Fee {
+        numerator: 1,
+        denominator: 100,
+    };
+    stake_pool_accounts
+        .set_fee(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::FeeType::SolDeposit(sol_deposit_fee),
+        )
+        .await
+        .unwrap();
+    let treasury = Pubkey::new_unique();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &treasury,
+        LAMPORTS_PER_SOL,
+    )
+    .await;
+    set_sol_deposit_fee_treasury(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&treasury),
+    )
+    .await
+    .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    // the treasury is required once set
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+    );
+
+    let mut deposit_instruction = instruction::deposit_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &user.pubkey(),
+        &user_pool_account.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &spl_token::id(),
+        DEPOSIT_AMOUNT,
+    )
+    .unwrap();
+    instruction::add_sol_deposit_fee_treasury_account(&mut deposit_instruction, &treasury);
+    let recent_blockhash = banks_client
+        .get_new_blockhash(&recent_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let transaction = Transaction::new_signed_with_payer(
+        &[deposit_instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let fee_lamports = sol_deposit_fee.apply(DEPOSIT_AMOUNT).unwrap();
+    assert!(fee_lamports > 0);
+    let treasury_lamports = get_account(&mut banks_client, &treasury).await.lamports;
+    assert_eq!(treasury_lamports, LAMPORTS_PER_SOL + fee_lamports);
+
+    // the depositor receives every token minted, and the manager none
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    let user_pool_tokens = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_pool_tokens, stake_pool.pool_token_supply);
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            &stake_pool_accounts.pool_fee_account.pubkey()
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        DEPOSIT_AMOUNT - fee_lamports
+    );
+}
+
+#[tokio::test]
+async fn fail_update_wrong_secondary_fee_account() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, secondary_fee_account) =
+        setup().await;
+    set_fee_split(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(&secondary_fee_account.pubkey()),
+        instruction::Fee {
+            numerator: 1,
+            denominator: 2,
+        },
+    )
+    .await
+    .unwrap();
+
+    let error = update_with_secondary_fee_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        Some(&Pubkey::new_unique()),
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidFeeAccount as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_set_fee_split_too_high() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, secondary_fee_account) =
+        setup().await;
+    let error = set_fee_split(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        Some(&secondary_fee_account.pubkey()),
+        instruction::Fee {
+            numerator: 3,
+            denominator: 2,
+        },
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::FeeTooHigh as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_set_fee_split_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, secondary_fee_account) =
+        setup().await;
+    let error = set_fee_split(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        Some(&secondary_fee_account.pubkey()),
+        instruction::Fee {
+            numerator: 1,
+            denominator: 2,
+        },
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::WrongManager as u32)
+        )
+    );
+}
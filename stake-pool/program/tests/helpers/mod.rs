@@ -13,8 +13,8 @@ use {
     },
     solana_vote_program::{self, vote_state::VoteState},
     spl_stake_pool::{
-        borsh::{get_instance_packed_len, try_from_slice_unchecked},
-        find_stake_program_address, id, instruction, processor, stake_program, state,
+        borsh::get_instance_packed_len, find_stake_program_address,
+        find_transient_stake_program_address, id, instruction, processor, stake_program, state,
     },
 };
 
@@ -199,10 +199,12 @@ pub async fn create_stake_pool(
     recent_blockhash: &Hash,
     stake_pool: &Keypair,
     validator_list: &Keypair,
+    reserve_stake: &Pubkey,
     pool_mint: &Pubkey,
     pool_token_account: &Pubkey,
     manager: &Keypair,
     staker: &Pubkey,
+    deposit_authority: Option<Pubkey>,
     fee: &instruction::Fee,
     max_validators: u32,
 ) -> Result<(), TransportError> {
@@ -234,9 +236,11 @@ pub async fn create_stake_pool(
                 &manager.pubkey(),
                 staker,
                 &validator_list.pubkey(),
+                reserve_stake,
                 pool_mint,
                 pool_token_account,
                 &spl_token::id(),
+                deposit_authority,
                 fee.clone(),
                 max_validators,
             )
@@ -303,6 +307,35 @@ pub async fn create_independent_stake_account(
     lamports
 }
 
+pub async fn create_reserve_stake_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake: &Keypair,
+    authority: &Pubkey,
+) -> u64 {
+    let rent = banks_client.get_rent().await.unwrap();
+    let lamports = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+
+    let mut transaction = Transaction::new_with_payer(
+        &stake_program::create_account(
+            &payer.pubkey(),
+            &stake.pubkey(),
+            &stake_program::Authorized {
+                staker: *authority,
+                withdrawer: *authority,
+            },
+            &stake_program::Lockup::default(),
+            lamports,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, stake], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    lamports
+}
+
 pub async fn create_blank_stake_account(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -400,6 +433,7 @@ pub async fn authorize_stake_account(
 
 pub struct ValidatorStakeAccount {
     pub stake_account: Pubkey,
+    pub transient_stake_account: Pubkey,
     pub target_authority: Pubkey,
     pub vote: Keypair,
     pub stake_pool: Pubkey,
@@ -409,8 +443,11 @@ impl ValidatorStakeAccount {
     pub fn new_with_target_authority(authority: &Pubkey, stake_pool: &Pubkey) -> Self {
         let validator = Keypair::new();
         let (stake_account, _) = find_stake_program_address(&id(), &validator.pubkey(), stake_pool);
+        let (transient_stake_account, _) =
+            find_transient_stake_program_address(&id(), &validator.pubkey(), stake_pool);
         ValidatorStakeAccount {
             stake_account,
+            transient_stake_account,
             target_authority: *authority,
             vote: validator,
             stake_pool: *stake_pool,
@@ -464,12 +501,15 @@ impl ValidatorStakeAccount {
 pub struct StakePoolAccounts {
     pub stake_pool: Keypair,
     pub validator_list: Keypair,
+    pub reserve_stake: Keypair,
     pub pool_mint: Keypair,
     pub pool_fee_account: Keypair,
     pub manager: Keypair,
     pub staker: Keypair,
     pub withdraw_authority: Pubkey,
     pub deposit_authority: Pubkey,
+    pub stake_deposit_authority: Pubkey,
+    pub stake_deposit_authority_keypair: Option<Keypair>,
     pub fee: instruction::Fee,
     pub max_validators: u32,
 }
@@ -478,6 +518,7 @@ impl StakePoolAccounts {
     pub fn new() -> Self {
         let stake_pool = Keypair::new();
         let validator_list = Keypair::new();
+        let reserve_stake = Keypair::new();
         let stake_pool_address = &stake_pool.pubkey();
         let (withdraw_authority, _) = Pubkey::find_program_address(
             &[&stake_pool_address.to_bytes()[..32], b"withdraw"],
@@ -495,12 +536,15 @@ impl StakePoolAccounts {
         Self {
             stake_pool,
             validator_list,
+            reserve_stake,
             pool_mint,
             pool_fee_account,
             manager,
             staker,
             withdraw_authority,
             deposit_authority,
+            stake_deposit_authority: deposit_authority,
+            stake_deposit_authority_keypair: None,
             fee: instruction::Fee {
                 numerator: 1,
                 denominator: 100,
@@ -509,6 +553,13 @@ impl StakePoolAccounts {
         }
     }
 
+    pub fn new_with_deposit_authority(stake_deposit_authority: Keypair) -> Self {
+        let mut stake_pool_accounts = Self::new();
+        stake_pool_accounts.stake_deposit_authority = stake_deposit_authority.pubkey();
+        stake_pool_accounts.stake_deposit_authority_keypair = Some(stake_deposit_authority);
+        stake_pool_accounts
+    }
+
     pub fn calculate_fee(&self, amount: u64) -> u64 {
         amount * self.fee.numerator / self.fee.denominator
     }
@@ -536,16 +587,28 @@ impl StakePoolAccounts {
             &self.manager.pubkey(),
         )
         .await?;
+        create_reserve_stake_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &self.reserve_stake,
+            &self.withdraw_authority,
+        )
+        .await;
         create_stake_pool(
             &mut banks_client,
             &payer,
             &recent_blockhash,
             &self.stake_pool,
             &self.validator_list,
+            &self.reserve_stake.pubkey(),
             &self.pool_mint.pubkey(),
             &self.pool_fee_account.pubkey(),
             &self.manager,
             &self.staker.pubkey(),
+            self.stake_deposit_authority_keypair
+                .as_ref()
+                .map(|keypair| keypair.pubkey()),
             &self.fee,
             self.max_validators,
         )
@@ -562,22 +625,144 @@ impl StakePoolAccounts {
         pool_account: &Pubkey,
         validator_stake_account: &Pubkey,
     ) -> Result<(), TransportError> {
-        let transaction = Transaction::new_signed_with_payer(
-            &[instruction::deposit(
+        let mut signers = vec![payer];
+        let instruction = match &self.stake_deposit_authority_keypair {
+            Some(stake_deposit_authority) => {
+                signers.push(stake_deposit_authority);
+                instruction::deposit_with_authority(
+                    &id(),
+                    &self.stake_pool.pubkey(),
+                    &self.validator_list.pubkey(),
+                    &self.stake_deposit_authority,
+                    &self.withdraw_authority,
+                    stake,
+                    validator_stake_account,
+                    pool_account,
+                    &self.pool_fee_account.pubkey(),
+                    &self.pool_mint.pubkey(),
+                    &spl_token::id(),
+                )
+            }
+            None => instruction::deposit(
                 &id(),
                 &self.stake_pool.pubkey(),
                 &self.validator_list.pubkey(),
-                &self.deposit_authority,
+                &self.stake_deposit_authority,
                 &self.withdraw_authority,
                 stake,
                 validator_stake_account,
                 pool_account,
+                &self.pool_fee_account.pubkey(),
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+            ),
+        }
+        .unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &signers,
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn deposit_stakes(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stakes: &[(Pubkey, Pubkey)],
+        pool_account: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let mut signers = vec![payer];
+        let mut instruction = instruction::deposit_multiple(
+            &id(),
+            &self.stake_pool.pubkey(),
+            &self.validator_list.pubkey(),
+            &self.stake_deposit_authority,
+            &self.withdraw_authority,
+            stakes,
+            pool_account,
+            &self.pool_fee_account.pubkey(),
+            &self.pool_mint.pubkey(),
+            &spl_token::id(),
+        )
+        .unwrap();
+        if let Some(stake_deposit_authority) = &self.stake_deposit_authority_keypair {
+            instruction.accounts[2].is_signer = true;
+            signers.push(stake_deposit_authority);
+        }
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &signers,
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn deposit_sol(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        lamports_from: &Keypair,
+        pool_account: &Pubkey,
+        amount: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::deposit_sol(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                &lamports_from.pubkey(),
+                pool_account,
+                &self.pool_fee_account.pubkey(),
                 &self.pool_mint.pubkey(),
                 &spl_token::id(),
+                amount,
             )
             .unwrap()],
             Some(&payer.pubkey()),
-            &[payer],
+            &[payer, lamports_from],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn deposit_sol_with_authority(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        lamports_from: &Keypair,
+        pool_account: &Pubkey,
+        sol_deposit_authority: &Keypair,
+        amount: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::deposit_sol_with_authority(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                &lamports_from.pubkey(),
+                pool_account,
+                &self.pool_fee_account.pubkey(),
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                &sol_deposit_authority.pubkey(),
+                amount,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, lamports_from, sol_deposit_authority],
             *recent_blockhash,
         );
         banks_client.process_transaction(transaction).await?;
@@ -605,6 +790,7 @@ impl StakePoolAccounts {
                 stake_recipient,
                 recipient_new_authority,
                 pool_account,
+                &self.pool_fee_account.pubkey(),
                 &self.pool_mint.pubkey(),
                 &spl_token::id(),
                 amount,
@@ -617,104 +803,707 @@ impl StakePoolAccounts {
         Ok(())
     }
 
-    pub async fn update_validator_list_balance(
+    pub async fn withdraw_sol(
         &self,
         banks_client: &mut BanksClient,
         payer: &Keypair,
         recent_blockhash: &Hash,
-        validator_list: &[Pubkey],
-    ) -> Option<TransportError> {
+        pool_account: &Pubkey,
+        lamports_to: &Pubkey,
+        amount: u64,
+    ) -> Result<(), TransportError> {
         let transaction = Transaction::new_signed_with_payer(
-            &[instruction::update_validator_list_balance(
+            &[instruction::withdraw_sol(
                 &id(),
-                &self.validator_list.pubkey(),
-                validator_list,
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                pool_account,
+                &self.pool_fee_account.pubkey(),
+                &self.reserve_stake.pubkey(),
+                lamports_to,
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                amount,
             )
             .unwrap()],
             Some(&payer.pubkey()),
             &[payer],
             *recent_blockhash,
         );
-        banks_client.process_transaction(transaction).await.err()
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
     }
 
-    pub async fn update_stake_pool_balance(
+    pub async fn burn_pool_tokens(
         &self,
         banks_client: &mut BanksClient,
         payer: &Keypair,
         recent_blockhash: &Hash,
-    ) -> Option<TransportError> {
+        burn_from: &Pubkey,
+        burn_authority: &Keypair,
+        amount: u64,
+    ) -> Result<(), TransportError> {
         let transaction = Transaction::new_signed_with_payer(
-            &[instruction::update_stake_pool_balance(
+            &[instruction::burn_pool_tokens(
                 &id(),
                 &self.stake_pool.pubkey(),
-                &self.validator_list.pubkey(),
-                &self.withdraw_authority,
-                &self.pool_fee_account.pubkey(),
+                burn_from,
+                &burn_authority.pubkey(),
                 &self.pool_mint.pubkey(),
+                &spl_token::id(),
+                amount,
             )
             .unwrap()],
             Some(&payer.pubkey()),
-            &[payer],
+            &[payer, burn_authority],
             *recent_blockhash,
         );
-        banks_client.process_transaction(transaction).await.err()
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
     }
 
-    pub async fn add_validator_to_pool(
+    pub async fn set_fee(
         &self,
         banks_client: &mut BanksClient,
         payer: &Keypair,
         recent_blockhash: &Hash,
-        stake: &Pubkey,
-        pool_account: &Pubkey,
-    ) -> Option<TransportError> {
-        let mut transaction = Transaction::new_with_payer(
-            &[instruction::add_validator_to_pool(
+        fee: instruction::FeeType,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_fee(
                 &id(),
                 &self.stake_pool.pubkey(),
-                &self.staker.pubkey(),
-                &self.deposit_authority,
-                &self.withdraw_authority,
-                &self.validator_list.pubkey(),
-                stake,
-                pool_account,
-                &self.pool_mint.pubkey(),
-                &spl_token::id(),
+                &self.manager.pubkey(),
+                fee,
             )
             .unwrap()],
             Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
         );
-        transaction.sign(&[payer, &self.staker], *recent_blockhash);
-        banks_client.process_transaction(transaction).await.err()
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
     }
 
-    pub async fn remove_validator_from_pool(
+    pub async fn set_preferred_validator(
         &self,
         banks_client: &mut BanksClient,
         payer: &Keypair,
         recent_blockhash: &Hash,
-        stake: &Pubkey,
-        pool_account: &Pubkey,
-        new_authority: &Pubkey,
-    ) -> Option<TransportError> {
-        let mut transaction = Transaction::new_with_payer(
-            &[instruction::remove_validator_from_pool(
+        validator_type: instruction::PreferredValidatorType,
+        validator_vote_address: Option<Pubkey>,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_preferred_validator(
                 &id(),
                 &self.stake_pool.pubkey(),
                 &self.staker.pubkey(),
-                &self.withdraw_authority,
-                &new_authority,
                 &self.validator_list.pubkey(),
-                stake,
-                pool_account,
-                &self.pool_mint.pubkey(),
-                &spl_token::id(),
+                validator_type,
+                validator_vote_address,
             )
             .unwrap()],
             Some(&payer.pubkey()),
+            &[payer, &self.staker],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_funding_authority(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        new_funding_authority: Option<&Pubkey>,
+        funding_type: instruction::FundingType,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_funding_authority(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                new_funding_authority,
+                funding_type,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_deposit_lock(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        deposit_lock_epochs: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_deposit_lock(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                deposit_lock_epochs,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_deposit_cap(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        max_total_lamports: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_deposit_cap(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                max_total_lamports,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_max_epoch_net_outflow(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        max_epoch_net_outflow_bps: u16,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_max_epoch_net_outflow(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                max_epoch_net_outflow_bps,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_paused(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        paused: bool,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_paused(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                paused,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn decommission_stake_pool(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::decommission_stake_pool(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn close_stake_pool(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        lamports_to: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::close_stake_pool(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                &self.validator_list.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                lamports_to,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_validator_onboarding_epochs(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        manager: &Keypair,
+        validator_onboarding_epochs: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_validator_onboarding_epochs(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &manager.pubkey(),
+                validator_onboarding_epochs,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_manager_timelock(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        manager_timelock_epochs: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_manager_timelock(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                manager_timelock_epochs,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn execute_manager_action(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::execute_manager_action(&id(), &self.stake_pool.pubkey()).unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn cancel_manager_action(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::cancel_manager_action(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_delinquent_validator_epochs(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        manager: &Keypair,
+        delinquent_validator_epochs: u64,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_delinquent_validator_epochs(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &manager.pubkey(),
+                delinquent_validator_epochs,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_validator_targets(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        targets: Vec<instruction::ValidatorTarget>,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_validator_targets(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.validator_list.pubkey(),
+                targets,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.staker],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn set_proportional_withdrawals(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        enabled: bool,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::set_proportional_withdrawals(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.manager.pubkey(),
+                enabled,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.manager],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    pub async fn update_validator_list_balance(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_vote_accounts: &[Pubkey],
+    ) -> Option<TransportError> {
+        self.update_validator_list_balance_chunk(
+            banks_client,
+            payer,
+            recent_blockhash,
+            validator_vote_accounts,
+            0,
+            false,
+        )
+        .await
+    }
+
+    pub async fn update_validator_list_balance_chunk(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_vote_accounts: &[Pubkey],
+        start_index: u32,
+        no_merge: bool,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::update_validator_list_balance(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                &self.reserve_stake.pubkey(),
+                validator_vote_accounts,
+                start_index,
+                no_merge,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn update_stake_pool_balance(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::update_stake_pool_balance(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.validator_list.pubkey(),
+                &self.reserve_stake.pubkey(),
+                &self.withdraw_authority,
+                &self.pool_fee_account.pubkey(),
+                &self.pool_mint.pubkey(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn add_validator_to_pool(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Pubkey,
+        pool_account: &Pubkey,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::add_validator_to_pool(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.deposit_authority,
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                stake,
+                pool_account,
+                &self.pool_mint.pubkey(),
+                &spl_token::id(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, &self.staker], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn remove_validator_from_pool(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Pubkey,
+    ) -> Option<TransportError> {
+        self.remove_validator_from_pool_with_authority(
+            banks_client,
+            payer,
+            recent_blockhash,
+            stake,
+            &self.staker,
+        )
+        .await
+    }
+
+    pub async fn remove_validator_from_pool_with_authority(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Pubkey,
+        authority: &Keypair,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::remove_validator_from_pool(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &authority.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                stake,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, authority], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn remove_validator_by_vote_withdrawer(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Pubkey,
+        vote_account: &Pubkey,
+        vote_withdrawer: &Keypair,
+    ) -> Option<TransportError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::remove_validator_by_vote_withdrawer(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &vote_withdrawer.pubkey(),
+                &self.withdraw_authority,
+                &self.validator_list.pubkey(),
+                stake,
+                vote_account,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, vote_withdrawer], *recent_blockhash);
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn decrease_validator_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_stake: &Pubkey,
+        transient_stake: &Pubkey,
+        lamports: u64,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::decrease_validator_stake(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.validator_list.pubkey(),
+                &self.withdraw_authority,
+                validator_stake,
+                transient_stake,
+                lamports,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.staker],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn decrease_delinquent_validator_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        vote_account: &Pubkey,
+        validator_stake: &Pubkey,
+        transient_stake: &Pubkey,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::decrease_delinquent_validator_stake(
+                &id(),
+                &self.stake_pool.pubkey(),
+                vote_account,
+                &self.validator_list.pubkey(),
+                &self.withdraw_authority,
+                validator_stake,
+                transient_stake,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn increase_validator_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        transient_stake: &Pubkey,
+        validator_vote: &Pubkey,
+        lamports: u64,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::increase_validator_stake(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.staker.pubkey(),
+                &self.validator_list.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                transient_stake,
+                validator_vote,
+                lamports,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, &self.staker],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.err()
+    }
+
+    pub async fn apply_targets(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        validator_stake: &Pubkey,
+        transient_stake: &Pubkey,
+        validator_vote: &Pubkey,
+    ) -> Option<TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::apply_targets(
+                &id(),
+                &self.stake_pool.pubkey(),
+                &self.validator_list.pubkey(),
+                &self.withdraw_authority,
+                &self.reserve_stake.pubkey(),
+                validator_stake,
+                transient_stake,
+                validator_vote,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
         );
-        transaction.sign(&[payer, &self.staker], *recent_blockhash);
         banks_client.process_transaction(transaction).await.err()
     }
 }
@@ -783,8 +1572,8 @@ pub async fn simple_deposit(
     let user_stake = Keypair::new();
     let lockup = stake_program::Lockup::default();
     let authorized = stake_program::Authorized {
-        staker: stake_pool_accounts.deposit_authority,
-        withdrawer: stake_pool_accounts.deposit_authority,
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
     };
     let stake_lamports = create_independent_stake_account(
         banks_client,
@@ -841,11 +1630,11 @@ pub async fn get_validator_list_sum(
         .unwrap()
         .unwrap();
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
 
     validator_list
         .validators
         .iter()
-        .map(|info| info.stake_lamports)
+        .map(|info| info.total_lamports().unwrap())
         .sum()
 }
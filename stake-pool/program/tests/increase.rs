@@ -0,0 +1,386 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError, id, instruction, stake_program, state, MINIMUM_ACTIVE_STAKE,
+    },
+};
+
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const INCREASE_AMOUNT: u64 = 2 * LAMPORTS_PER_SOL;
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, ValidatorStakeAccount) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    // Fund the reserve through a SOL deposit
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts, validator_stake_account)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let reserve_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // Reserve lamports moved to the transient account, delegated to the validator
+    let reserve = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve.lamports, reserve_lamports - INCREASE_AMOUNT);
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    assert_eq!(transient_stake.lamports, INCREASE_AMOUNT);
+    let transient_stake_state =
+        deserialize::<stake_program::StakeState>(&transient_stake.data).unwrap();
+    assert_eq!(
+        transient_stake_state.delegation().unwrap().voter_pubkey,
+        validator_stake_account.vote.pubkey()
+    );
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.transient_stake_lamports,
+        INCREASE_AMOUNT
+    );
+
+    // The pool's total is unchanged by the rebalance
+    let stake_pool_before = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+
+    // Once the stake is active, the update merges it into the validator stake
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let transient_stake = context
+        .banks_client
+        .get_account(validator_stake_account.transient_stake_account)
+        .await
+        .unwrap();
+    assert!(transient_stake.is_none());
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    assert_eq!(
+        validator_stake.lamports,
+        validator_lamports + INCREASE_AMOUNT
+    );
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(validator_stake_record.transient_stake_lamports, 0);
+    assert_eq!(
+        validator_stake_record.active_stake_lamports,
+        validator_stake.lamports
+    );
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_staker() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::increase_validator_stake(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_staker.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_staker],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongStaker as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_with_unknown_validator() {
+    let (mut context, stake_pool_accounts, _) = setup().await;
+
+    let unknown_validator = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &unknown_validator.transient_stake_account,
+            &unknown_validator.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::ValidatorNotFound as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_with_wrong_transient_stake_account() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &Pubkey::new_unique(),
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidStakeAccountAddress as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_twice_in_one_epoch() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT + 1,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::TransientAccountInUse as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_below_minimum_active_stake() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            MINIMUM_ACTIVE_STAKE,
+        )
+        .await
+        .unwrap();
+
+    check_error(transaction_error, InstructionError::AccountNotRentExempt);
+}
+
+#[tokio::test]
+async fn fail_with_insufficient_reserve() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            RESERVE_AMOUNT + 1,
+        )
+        .await
+        .unwrap();
+
+    check_error(transaction_error, InstructionError::InsufficientFunds);
+}
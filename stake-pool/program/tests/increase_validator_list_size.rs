@@ -0,0 +1,317 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        borsh::get_instance_packed_len, hash::Hash, instruction::InstructionError, pubkey::Pubkey,
+        system_instruction,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, state},
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts, Pubkey) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts.max_validators = 1;
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake.vote.pubkey(),
+    )
+}
+
+async fn create_validator_list_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    validator_list: &Keypair,
+    max_validators: u32,
+    owner: &Pubkey,
+) {
+    let rent = banks_client.get_rent().await.unwrap();
+    let validator_list_size =
+        get_instance_packed_len(&state::ValidatorList::new(max_validators)).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &validator_list.pubkey(),
+            rent.minimum_balance(validator_list_size),
+            validator_list_size as u64,
+            owner,
+        )],
+        Some(&payer.pubkey()),
+        &[payer, validator_list],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn increase_validator_list_size(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    authority: &Keypair,
+    new_validator_list: &Pubkey,
+    funder: &Pubkey,
+    max_validators: u32,
+) -> Result<(), TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::increase_validator_list_size(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &authority.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            new_validator_list,
+            funder,
+            max_validators,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer, authority],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, mut stake_pool_accounts, vote_account) =
+        setup().await;
+
+    let new_max_validators = 3;
+    let new_validator_list = Keypair::new();
+    create_validator_list_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &new_validator_list,
+        new_max_validators,
+        &id(),
+    )
+    .await;
+
+    let old_validator_list_lamports = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await
+    .lamports;
+    let funder = Pubkey::new_unique();
+    increase_validator_list_size(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &new_validator_list.pubkey(),
+        &funder,
+        new_max_validators,
+    )
+    .await
+    .unwrap();
+
+    // the old list is emptied and its rent refunded
+    assert!(banks_client
+        .get_account(stake_pool_accounts.validator_list.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        get_account(&mut banks_client, &funder).await.lamports,
+        old_validator_list_lamports
+    );
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.validator_list, new_validator_list.pubkey());
+
+    let validator_list = get_account(&mut banks_client, &new_validator_list.pubkey()).await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    assert!(validator_list.is_valid());
+    assert_eq!(validator_list.max_validators, new_max_validators);
+    assert_eq!(validator_list.validators.len(), 1);
+    assert!(validator_list.contains(&vote_account));
+
+    // there is now room for another validator
+    stake_pool_accounts.validator_list = new_validator_list;
+    let validator_stake = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    assert_eq!(validator_list.validators.len(), 2);
+    assert!(validator_list.contains(&validator_stake.vote.pubkey()));
+}
+
+#[tokio::test]
+async fn fail_not_larger() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+
+    let new_validator_list = Keypair::new();
+    create_validator_list_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &new_validator_list,
+        stake_pool_accounts.max_validators,
+        &id(),
+    )
+    .await;
+
+    let error = increase_validator_list_size(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &new_validator_list.pubkey(),
+        &payer.pubkey(),
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::UnexpectedValidatorListAccountSize as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_size() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+
+    let new_validator_list = Keypair::new();
+    create_validator_list_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &new_validator_list,
+        3,
+        &id(),
+    )
+    .await;
+
+    let error = increase_validator_list_size(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &new_validator_list.pubkey(),
+        &payer.pubkey(),
+        4,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::UnexpectedValidatorListAccountSize as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_owner() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+
+    let new_validator_list = Keypair::new();
+    create_validator_list_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &new_validator_list,
+        3,
+        &Pubkey::new_unique(),
+    )
+    .await;
+
+    let error = increase_validator_list_size(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &stake_pool_accounts.manager,
+        &new_validator_list.pubkey(),
+        &payer.pubkey(),
+        3,
+    )
+    .await
+    .unwrap_err();
+    check_error(error, InstructionError::IncorrectProgramId);
+}
+
+#[tokio::test]
+async fn fail_wrong_authority() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _) = setup().await;
+
+    let new_validator_list = Keypair::new();
+    create_validator_list_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &new_validator_list,
+        3,
+        &id(),
+    )
+    .await;
+
+    let error = increase_validator_list_size(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &Keypair::new(),
+        &new_validator_list.pubkey(),
+        &payer.pubkey(),
+        3,
+    )
+    .await
+    .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::SignatureMissing as u32),
+    );
+}
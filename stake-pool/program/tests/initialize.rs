@@ -17,10 +17,7 @@ use {
         instruction::InstructionError, signature::Keypair, signature::Signer,
         transaction::Transaction, transaction::TransactionError, transport::TransportError,
     },
-    spl_stake_pool::{
-        borsh::{get_instance_packed_len, try_from_slice_unchecked},
-        error, id, instruction, state,
-    },
+    spl_stake_pool::{borsh::get_instance_packed_len, error, id, instruction, state},
 };
 
 async fn create_mint_and_token_account(
@@ -49,6 +46,15 @@ async fn create_mint_and_token_account(
     )
     .await
     .unwrap();
+
+    create_reserve_stake_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &stake_pool_accounts.withdraw_authority,
+    )
+    .await;
 }
 
 #[tokio::test]
@@ -72,8 +78,15 @@ async fn success_initialize() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     assert_eq!(validator_list.is_valid(), true);
+
+    // Reserve stake account recorded
+    let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.reserve_stake,
+        stake_pool_accounts.reserve_stake.pubkey()
+    );
 }
 
 #[tokio::test]
@@ -207,6 +220,7 @@ async fn fail_initialize_with_wrong_max_validators() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -275,10 +289,12 @@ async fn fail_initialize_with_wrong_mint_authority() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &wrong_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
         &stake_pool_accounts.staker.pubkey(),
+        None,
         &stake_pool_accounts.fee,
         stake_pool_accounts.max_validators,
     )
@@ -363,6 +379,7 @@ async fn fail_initialize_with_wrong_token_program_id() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &wrong_token_program.pubkey(),
@@ -437,10 +454,12 @@ async fn fail_initialize_with_wrong_fee_account() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &stake_pool_accounts.pool_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
         &stake_pool_accounts.staker.pubkey(),
+        None,
         &stake_pool_accounts.fee,
         stake_pool_accounts.max_validators,
     )
@@ -524,6 +543,7 @@ async fn fail_initialize_with_not_rent_exempt_pool() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -597,6 +617,7 @@ async fn fail_initialize_with_not_rent_exempt_validator_list() {
                 &stake_pool_accounts.manager.pubkey(),
                 &stake_pool_accounts.staker.pubkey(),
                 &stake_pool_accounts.validator_list.pubkey(),
+                &stake_pool_accounts.reserve_stake.pubkey(),
                 &stake_pool_accounts.pool_mint.pubkey(),
                 &stake_pool_accounts.pool_fee_account.pubkey(),
                 &spl_token::id(),
@@ -661,6 +682,7 @@ async fn fail_initialize_without_manager_signature() {
         AccountMeta::new_readonly(stake_pool_accounts.manager.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.staker.pubkey(), false),
         AccountMeta::new(stake_pool_accounts.validator_list.pubkey(), false),
+        AccountMeta::new_readonly(stake_pool_accounts.reserve_stake.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.pool_mint.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.pool_fee_account.pubkey(), false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
@@ -766,10 +788,12 @@ async fn fail_initialize_with_pre_minted_pool_tokens() {
         &recent_blockhash,
         &stake_pool_accounts.stake_pool,
         &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
         &stake_pool_accounts.pool_mint.pubkey(),
         &stake_pool_accounts.pool_fee_account.pubkey(),
         &stake_pool_accounts.manager,
         &stake_pool_accounts.staker.pubkey(),
+        None,
         &stake_pool_accounts.fee,
         stake_pool_accounts.max_validators,
     )
@@ -788,3 +812,69 @@ async fn fail_initialize_with_pre_minted_pool_tokens() {
         _ => panic!("Wrong error occurs while try to initialize stake pool with wrong mint authority of pool fee account"),
     }
 }
+
+#[tokio::test]
+async fn fail_initialize_with_wrong_reserve_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.pool_mint,
+        &stake_pool_accounts.withdraw_authority,
+    )
+    .await
+    .unwrap();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.pool_fee_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    create_reserve_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &Keypair::new().pubkey(),
+    )
+    .await;
+
+    let transaction_error = create_stake_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.stake_pool,
+        &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.manager,
+        &stake_pool_accounts.staker.pubkey(),
+        None,
+        &stake_pool_accounts.fee,
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .err()
+    .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongStakeState as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to initialize stake pool with wrong reserve stake authority"),
+    }
+}
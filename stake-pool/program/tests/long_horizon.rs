@@ -0,0 +1,295 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::signature::{Keypair, Signer},
+    spl_stake_pool::state::{FeeOperation, StakePool},
+};
+
+/// Number of epochs to simulate
+const SIMULATED_EPOCHS: u64 = 100;
+/// Roughly two-day epochs
+const EPOCHS_PER_YEAR: f64 = 182.5;
+/// Inflation schedule parameters, matching the cluster's initial, taper and
+/// terminal rates
+const INITIAL_INFLATION_RATE: f64 = 0.08;
+const INFLATION_TAPER: f64 = 0.15;
+const TERMINAL_INFLATION_RATE: f64 = 0.015;
+/// Commission taken by each simulated validator, in percent
+const VALIDATOR_COMMISSIONS: [u64; 3] = [0, 5, 10];
+/// A SOL deposit or withdrawal happens every `USER_ACTIVITY_PERIOD` epochs
+const USER_ACTIVITY_PERIOD: u64 = 10;
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+/// Yearly inflation rate at the given epoch
+fn inflation_rate(epoch: u64) -> f64 {
+    let years = epoch as f64 / EPOCHS_PER_YEAR;
+    (INITIAL_INFLATION_RATE * (1.0 - INFLATION_TAPER).powf(years)).max(TERMINAL_INFLATION_RATE)
+}
+
+/// Lamports earned by delegators of `stake_lamports` over one epoch, after the
+/// validator's commission
+fn epoch_rewards(epoch: u64, stake_lamports: u64, commission: u64) -> u64 {
+    let validator_rewards = stake_lamports as f64 * inflation_rate(epoch) / EPOCHS_PER_YEAR;
+    (validator_rewards * (100 - commission) as f64 / 100.0) as u64
+}
+
+async fn get_stake_pool(banks_client: &mut BanksClient, stake_pool_address: &Pubkey) -> StakePool {
+    let stake_pool = get_account(banks_client, stake_pool_address).await;
+    StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn conservation_and_monotonic_exchange_rate_over_inflation_schedule() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let mut validator_stake_accounts = vec![];
+    let mut user_pool_accounts = vec![];
+    for _ in VALIDATOR_COMMISSIONS.iter() {
+        let validator_stake_account = simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        let deposit_info = simple_deposit(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+            &validator_stake_account,
+        )
+        .await;
+        user_pool_accounts.push(deposit_info.user_pool_account);
+        validator_stake_accounts.push(validator_stake_account);
+    }
+
+    // SOL depositor, who also withdraws half of every deposit later on
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * SIMULATED_EPOCHS / USER_ACTIVITY_PERIOD + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    user_pool_accounts.push(user_pool_account.pubkey());
+    let lamports_receiver = Pubkey::new_unique();
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let epoch_schedule = context.genesis_config().epoch_schedule;
+    let mut slot = epoch_schedule.first_normal_slot;
+
+    let mut stake_pool = get_stake_pool(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let mut manager_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+
+    for epoch in 1..=SIMULATED_EPOCHS {
+        // Pay out this epoch's rewards directly into the validator stake accounts
+        let mut epoch_reward_lamports = 0;
+        for (validator_stake_account, commission) in validator_stake_accounts
+            .iter()
+            .zip(VALIDATOR_COMMISSIONS.iter())
+        {
+            let stake_account = &validator_stake_account.stake_account;
+            let stake_lamports = get_account(&mut context.banks_client, stake_account)
+                .await
+                .lamports;
+            let rewards = epoch_rewards(epoch, stake_lamports, *commission);
+            transfer(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                stake_account,
+                rewards,
+            )
+            .await;
+            epoch_reward_lamports += rewards;
+        }
+
+        slot += epoch_schedule.slots_per_epoch;
+        context.warp_to_slot(slot).unwrap();
+        context.last_blockhash = context
+            .banks_client
+            .get_new_blockhash(&context.last_blockhash)
+            .await
+            .unwrap()
+            .0;
+
+        let error = stake_pool_accounts
+            .update_validator_list_balance(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                validator_stake_accounts
+                    .iter()
+                    .map(|v| v.vote.pubkey())
+                    .collect::<Vec<Pubkey>>()
+                    .as_slice(),
+            )
+            .await;
+        assert!(error.is_none());
+        let error = stake_pool_accounts
+            .update_stake_pool_balance(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+            )
+            .await;
+        assert!(error.is_none());
+
+        let mut expected_total_stake_lamports =
+            stake_pool.total_stake_lamports + epoch_reward_lamports;
+        if epoch % USER_ACTIVITY_PERIOD == 0 {
+            stake_pool_accounts
+                .deposit_sol(
+                    &mut context.banks_client,
+                    &context.payer,
+                    &context.last_blockhash,
+                    &user,
+                    &user_pool_account.pubkey(),
+                    DEPOSIT_AMOUNT,
+                )
+                .await
+                .unwrap();
+            expected_total_stake_lamports += DEPOSIT_AMOUNT;
+        } else if epoch % USER_ACTIVITY_PERIOD == USER_ACTIVITY_PERIOD / 2 {
+            let pool_tokens =
+                get_token_balance(&mut context.banks_client, &user_pool_account.pubkey()).await / 2;
+            if pool_tokens > 0 {
+                let stake_pool_before = get_stake_pool(
+                    &mut context.banks_client,
+                    &stake_pool_accounts.stake_pool.pubkey(),
+                )
+                .await;
+                let (burned_pool_tokens, _) = stake_pool_before
+                    .split_pool_tokens(FeeOperation::Withdrawal, pool_tokens)
+                    .unwrap();
+                let withdrawn_lamports = stake_pool_before
+                    .calc_lamports_withdraw_amount_floor(burned_pool_tokens)
+                    .unwrap();
+                delegate_tokens(
+                    &mut context.banks_client,
+                    &context.payer,
+                    &context.last_blockhash,
+                    &user_pool_account.pubkey(),
+                    &user,
+                    &stake_pool_accounts.withdraw_authority,
+                    pool_tokens,
+                )
+                .await;
+                stake_pool_accounts
+                    .withdraw_sol(
+                        &mut context.banks_client,
+                        &context.payer,
+                        &context.last_blockhash,
+                        &user_pool_account.pubkey(),
+                        &lamports_receiver,
+                        pool_tokens,
+                    )
+                    .await
+                    .unwrap();
+                expected_total_stake_lamports -= withdrawn_lamports;
+            }
+        }
+
+        let new_stake_pool = get_stake_pool(
+            &mut context.banks_client,
+            &stake_pool_accounts.stake_pool.pubkey(),
+        )
+        .await;
+
+        // No lamports are created or lost by the pool
+        assert_eq!(
+            new_stake_pool.total_stake_lamports,
+            expected_total_stake_lamports
+        );
+        let reserve = get_account(
+            &mut context.banks_client,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+        )
+        .await;
+        let validator_list_lamports = get_validator_list_sum(
+            &mut context.banks_client,
+            &stake_pool_accounts.validator_list.pubkey(),
+        )
+        .await;
+        assert_eq!(
+            new_stake_pool.total_stake_lamports,
+            validator_list_lamports + reserve.lamports - rent.minimum_balance(reserve.data.len())
+        );
+
+        // The pool's token supply matches the mint and every token is accounted for
+        let mint_supply = get_token_supply(
+            &mut context.banks_client,
+            &stake_pool_accounts.pool_mint.pubkey(),
+        )
+        .await;
+        assert_eq!(new_stake_pool.pool_token_supply, mint_supply);
+        let new_manager_fee_balance = get_token_balance(
+            &mut context.banks_client,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+        )
+        .await;
+        let mut token_balances = new_manager_fee_balance;
+        for pool_account in &user_pool_accounts {
+            token_balances += get_token_balance(&mut context.banks_client, pool_account).await;
+        }
+        assert_eq!(token_balances, mint_supply);
+
+        // Rewards always accrue to the manager, never away from it
+        assert!(new_manager_fee_balance >= manager_fee_balance);
+        if epoch_reward_lamports > 0 {
+            assert!(new_manager_fee_balance > manager_fee_balance);
+        }
+
+        // Lamports per pool token never decreases
+        assert!(
+            new_stake_pool.total_stake_lamports as u128 * stake_pool.pool_token_supply as u128
+                >= stake_pool.total_stake_lamports as u128
+                    * new_stake_pool.pool_token_supply as u128,
+            "exchange rate decreased in epoch {}",
+            epoch
+        );
+
+        stake_pool = new_stake_pool;
+        manager_fee_balance = new_manager_fee_balance;
+    }
+
+    // Over the whole run, pool tokens gained value
+    assert!(stake_pool.total_stake_lamports > stake_pool.pool_token_supply);
+}
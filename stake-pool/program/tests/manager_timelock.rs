@@ -0,0 +1,280 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id,
+        instruction::{self, Fee, FeeType, FundingType},
+        state::{ManagerAction, StakePool},
+    },
+};
+
+const TIMELOCK_EPOCHS: u64 = 2;
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .set_manager_timelock(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            TIMELOCK_EPOCHS,
+        )
+        .await
+        .unwrap();
+    (context, stake_pool_accounts)
+}
+
+async fn get_stake_pool(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> StakePool {
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap()
+}
+
+async fn set_sol_deposit_authority(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    sol_deposit_authority: &Pubkey,
+) -> Result<(), TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_funding_authority(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            Some(sol_deposit_authority),
+            FundingType::SolDeposit,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: StakePoolError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::Custom(expected_error as u32));
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+fn warp_to_epoch(context: &mut ProgramTestContext, epoch: u64) {
+    let slot = context
+        .genesis_config()
+        .epoch_schedule
+        .get_first_slot_in_epoch(epoch);
+    context.warp_to_slot(slot).unwrap();
+}
+
+#[tokio::test]
+async fn success_execute_after_timelock() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let stake_deposit_fee = Fee {
+        numerator: 1,
+        denominator: 100,
+    };
+    stake_pool_accounts
+        .set_fee(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            FeeType::StakeDeposit(stake_deposit_fee),
+        )
+        .await
+        .unwrap();
+
+    // the fee is only recorded
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.stake_deposit_fee, Fee::default());
+    let (action, executable_epoch) = stake_pool.pending_manager_action().unwrap();
+    assert_eq!(
+        action,
+        ManagerAction::SetFee(FeeType::StakeDeposit(stake_deposit_fee))
+    );
+
+    let error = stake_pool_accounts
+        .execute_manager_action(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap_err();
+    check_error(error, StakePoolError::ManagerActionTimelocked);
+
+    // anyone can apply it once the timelock has elapsed
+    warp_to_epoch(&mut context, executable_epoch);
+    stake_pool_accounts
+        .execute_manager_action(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.stake_deposit_fee, stake_deposit_fee);
+    assert_eq!(stake_pool.pending_manager_action(), None);
+}
+
+#[tokio::test]
+async fn success_cancel() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let sol_deposit_authority = Pubkey::new_unique();
+    set_sol_deposit_authority(&mut context, &stake_pool_accounts, &sol_deposit_authority)
+        .await
+        .unwrap();
+    let (_, executable_epoch) = get_stake_pool(&mut context, &stake_pool_accounts)
+        .await
+        .pending_manager_action()
+        .unwrap();
+
+    stake_pool_accounts
+        .cancel_manager_action(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    warp_to_epoch(&mut context, executable_epoch);
+    let error = stake_pool_accounts
+        .execute_manager_action(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap_err();
+    check_error(error, StakePoolError::NoPendingManagerAction);
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.sol_deposit_authority(), None);
+}
+
+#[tokio::test]
+async fn fail_second_pending_action() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    stake_pool_accounts
+        .set_fee(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            FeeType::SolDeposit(Fee {
+                numerator: 1,
+                denominator: 100,
+            }),
+        )
+        .await
+        .unwrap();
+
+    let error =
+        set_sol_deposit_authority(&mut context, &stake_pool_accounts, &Pubkey::new_unique())
+            .await
+            .unwrap_err();
+    check_error(error, StakePoolError::ManagerActionPending);
+}
+
+#[tokio::test]
+async fn fail_invalid_fee_not_recorded() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let error = stake_pool_accounts
+        .set_fee(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            FeeType::Epoch(Fee {
+                numerator: 2,
+                denominator: 1,
+            }),
+        )
+        .await
+        .unwrap_err();
+    check_error(error, StakePoolError::FeeTooHigh);
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.pending_manager_action(), None);
+}
+
+#[tokio::test]
+async fn success_timelock_changes() {
+    let (mut context, stake_pool_accounts) = setup().await;
+
+    // a longer timelock applies right away
+    stake_pool_accounts
+        .set_manager_timelock(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            TIMELOCK_EPOCHS + 1,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.manager_timelock(), Some(TIMELOCK_EPOCHS + 1));
+    assert_eq!(stake_pool.pending_manager_action(), None);
+
+    // a shorter one waits for the current timelock
+    stake_pool_accounts
+        .set_manager_timelock(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            0,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.manager_timelock(), Some(TIMELOCK_EPOCHS + 1));
+    let (action, executable_epoch) = stake_pool.pending_manager_action().unwrap();
+    assert_eq!(action, ManagerAction::SetManagerTimelock(0));
+
+    warp_to_epoch(&mut context, executable_epoch);
+    stake_pool_accounts
+        .execute_manager_action(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    // without a timelock, changes apply right away again
+    let sol_deposit_authority = Pubkey::new_unique();
+    set_sol_deposit_authority(&mut context, &stake_pool_accounts, &sol_deposit_authority)
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.manager_timelock(), None);
+    assert_eq!(
+        stake_pool.sol_deposit_authority(),
+        Some(sol_deposit_authority)
+    );
+}
@@ -0,0 +1,825 @@
+mod cpi_harness;
+
+use {
+    borsh::BorshSerialize,
+    cpi_harness::{
+        matrix::{Case, Mutation::*},
+        *,
+    },
+    solana_program::{
+        clock::Clock, native_token::LAMPORTS_PER_SOL, program_error::ProgramError,
+        program_option::COption, program_pack::Pack, pubkey::Pubkey, rent::Rent, system_program,
+        sysvar,
+    },
+    solana_sdk::account::Account,
+    spl_stake_pool::{
+        error::StakePoolError,
+        find_deposit_authority_program_address, find_deposit_record_address,
+        find_stake_program_address, find_withdraw_authority_program_address, id,
+        instruction::{self, Fee, FeeType, FundingType, PreferredValidatorType},
+        stake_program,
+        state::{
+            AccountType, DepositRecord, StakePool, ValidatorList, ValidatorListHeader,
+            ValidatorStakeInfo,
+        },
+        MINIMUM_ACTIVE_STAKE,
+    },
+};
+
+/// Pool token account of `owner` holding `amount`, all of it delegated to
+/// the pool's withdraw authority
+fn delegated_token_account(pool: &Pool, owner: &Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: pool.pool_mint,
+        owner: *owner,
+        amount,
+        delegate: COption::Some(pool.withdraw_authority),
+        delegated_amount: amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    }
+    .pack_into_slice(&mut data);
+    account(
+        Rent::default().minimum_balance(data.len()),
+        data,
+        &spl_token::id(),
+    )
+}
+
+fn stake_rent() -> u64 {
+    Rent::default().minimum_balance(std::mem::size_of::<stake_program::StakeState>())
+}
+
+fn update_stake_pool(accounts: &mut Accounts, pool: &Pool, update: impl FnOnce(&mut StakePool)) {
+    let mut stake_pool = StakePool::deserialize_checked(accounts.data(&pool.stake_pool)).unwrap();
+    update(&mut stake_pool);
+    accounts.0.get_mut(&pool.stake_pool).unwrap().data = stake_pool.try_to_vec().unwrap();
+}
+
+fn update_validator_list(
+    accounts: &mut Accounts,
+    pool: &Pool,
+    update: impl FnOnce(&mut ValidatorList),
+) {
+    let mut validator_list =
+        ValidatorList::deserialize_checked(accounts.data(&pool.validator_list)).unwrap();
+    update(&mut validator_list);
+    accounts.0.get_mut(&pool.validator_list).unwrap().data = validator_list.try_to_vec().unwrap();
+}
+
+/// Hands stake deposits to the pool's deposit authority, the way
+/// `Initialize` does without an explicit one, and returns its address
+fn set_deposit_authority(accounts: &mut Accounts, pool: &Pool) -> Pubkey {
+    let (deposit_authority, deposit_bump_seed) =
+        find_deposit_authority_program_address(&id(), &pool.stake_pool);
+    update_stake_pool(accounts, pool, |stake_pool| {
+        stake_pool.stake_deposit_authority = deposit_authority;
+        stake_pool.deposit_bump_seed = deposit_bump_seed;
+    });
+    deposit_authority
+}
+
+/// Stake account holding `lamports`, delegated to `vote_account`
+fn delegated_stake_account(vote_account: &Pubkey, lamports: u64) -> Account {
+    let meta = stake_program::Meta {
+        rent_exempt_reserve: stake_rent(),
+        ..stake_program::Meta::default()
+    };
+    let stake = stake_program::Stake {
+        delegation: stake_program::Delegation {
+            voter_pubkey: *vote_account,
+            stake: lamports - stake_rent(),
+            ..stake_program::Delegation::default()
+        },
+        ..stake_program::Stake::default()
+    };
+    stake_account(lamports, &stake_program::StakeState::Stake(meta, stake))
+}
+
+#[test]
+fn set_fee() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::set_fee(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        FeeType::Epoch(Fee {
+            numerator: 1,
+            denominator: 10,
+        }),
+    )
+    .unwrap();
+    Case::new("set_fee", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn set_manager() {
+    let (mut accounts, pool) = setup_pool();
+    let new_manager = Pubkey::new_unique();
+    let new_manager_fee_account = Pubkey::new_unique();
+    accounts.add(
+        new_manager_fee_account,
+        token_account(&pool.pool_mint, &new_manager, 0),
+    );
+    let instruction = instruction::set_manager(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        &new_manager,
+        &new_manager_fee_account,
+    )
+    .unwrap();
+    Case::new("set_manager", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .allow(2, WrongAddress)
+        .expect_error(3, WrongOwner, ProgramError::IncorrectProgramId)
+        .check();
+}
+
+#[test]
+fn set_staker() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::set_staker(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        &Pubkey::new_unique(),
+    )
+    .unwrap();
+    Case::new("set_staker", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::SignatureMissing)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .allow(2, WrongAddress)
+        .check();
+}
+
+#[test]
+fn set_funding_authority() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::set_funding_authority(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        Some(&Pubkey::new_unique()),
+        FundingType::SolDeposit,
+    )
+    .unwrap();
+    Case::new("set_funding_authority", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .allow(2, WrongAddress)
+        .check();
+}
+
+#[test]
+fn set_preferred_validator() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::set_preferred_validator(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &pool.validator_list,
+        PreferredValidatorType::Deposit,
+        Some(pool.vote_account),
+    )
+    .unwrap();
+    Case::new("set_preferred_validator", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongStaker)
+        .check();
+}
+
+#[test]
+fn set_paused() {
+    let (accounts, pool) = setup_pool();
+    let instruction =
+        instruction::set_paused(&id(), &pool.stake_pool, &pool.manager, true).unwrap();
+    Case::new("set_paused", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .check();
+}
+
+#[test]
+fn update_stake_pool_balance() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.reserve_stake,
+        &pool.withdraw_authority,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+    )
+    .unwrap();
+    Case::new("update_stake_pool_balance", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(4, WrongAddress, StakePoolError::InvalidFeeAccount)
+        .check();
+}
+
+#[test]
+fn update_stake_pool_balance_uninitialized_validator_list() {
+    let (mut accounts, pool) = setup_pool();
+    let validator_list = accounts.0.get_mut(&pool.validator_list).unwrap();
+    validator_list.data = vec![0; validator_list.data.len()];
+    let instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.reserve_stake,
+        &pool.withdraw_authority,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+    )
+    .unwrap();
+    assert_eq!(
+        accounts.process(&instruction, None),
+        Err(StakePoolError::WrongAccountType.into())
+    );
+}
+
+#[test]
+fn decrease_validator_stake() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::decrease_validator_stake(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.validator_stake,
+        &pool.transient_stake,
+        VALIDATOR_LAMPORTS - MINIMUM_ACTIVE_STAKE - LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    Case::new("decrease_validator_stake", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongStaker)
+        .check();
+}
+
+#[test]
+fn deposit_sol() {
+    let (mut accounts, pool) = setup_pool();
+    let user = Pubkey::new_unique();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user,
+        account(2 * LAMPORTS_PER_SOL, vec![], &system_program::id()),
+    );
+    accounts.add(user_pool_account, token_account(&pool.pool_mint, &user, 0));
+    let instruction = instruction::deposit_sol(
+        &id(),
+        &pool.stake_pool,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &user,
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+        LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    Case::new("deposit_sol", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .expect_error(3, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn withdraw_sol() {
+    let (mut accounts, pool) = setup_pool();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user_pool_account,
+        delegated_token_account(&pool, &Pubkey::new_unique(), LAMPORTS_PER_SOL),
+    );
+    let instruction = instruction::withdraw_sol(
+        &id(),
+        &pool.stake_pool,
+        &pool.withdraw_authority,
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.reserve_stake,
+        &Pubkey::new_unique(),
+        &pool.pool_mint,
+        &spl_token::id(),
+        LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    Case::new("withdraw_sol", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(4, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .allow(5, WrongAddress)
+        .check();
+}
+
+#[test]
+fn initialize() {
+    // every pool account is fresh, only the sysvars and programs are reused
+    let (mut accounts, _) = setup_pool();
+    let stake_pool = Pubkey::new_unique();
+    let manager = Pubkey::new_unique();
+    let validator_list = Pubkey::new_unique();
+    let reserve_stake = Pubkey::new_unique();
+    let pool_mint = Pubkey::new_unique();
+    let manager_fee_account = Pubkey::new_unique();
+    let (withdraw_authority, _) = find_withdraw_authority_program_address(&id(), &stake_pool);
+    let rent = Rent::default();
+    accounts.add(
+        stake_pool,
+        account(
+            rent.minimum_balance(StakePool::LEN),
+            vec![0; StakePool::LEN],
+            &id(),
+        ),
+    );
+    let validator_list_len = ValidatorListHeader::data_len(1);
+    accounts.add(
+        validator_list,
+        account(
+            rent.minimum_balance(validator_list_len),
+            vec![0; validator_list_len],
+            &id(),
+        ),
+    );
+    accounts.add(
+        reserve_stake,
+        stake_account(
+            stake_rent(),
+            &stake_program::StakeState::Initialized(stake_program::Meta {
+                rent_exempt_reserve: stake_rent(),
+                authorized: stake_program::Authorized {
+                    staker: withdraw_authority,
+                    withdrawer: withdraw_authority,
+                },
+                ..stake_program::Meta::default()
+            }),
+        ),
+    );
+    accounts.add(pool_mint, mint_account(&withdraw_authority, 0));
+    accounts.add(manager_fee_account, token_account(&pool_mint, &manager, 0));
+    let instruction = instruction::initialize(
+        &id(),
+        &stake_pool,
+        &manager,
+        &Pubkey::new_unique(),
+        &validator_list,
+        &reserve_stake,
+        &pool_mint,
+        &manager_fee_account,
+        &spl_token::id(),
+        None,
+        Fee {
+            numerator: 1,
+            denominator: 100,
+        },
+        1,
+    )
+    .unwrap();
+    // the pool account only signs its own creation
+    Case::new("initialize", accounts, instruction)
+        .allow(0, Unsigned)
+        .allow(1, WrongAddress)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .allow(2, WrongAddress)
+        .expect_error(4, WrongAddress, ProgramError::IncorrectProgramId)
+        .expect_error(5, WrongAddress, ProgramError::IncorrectProgramId)
+        .expect_error(6, WrongAddress, ProgramError::IncorrectProgramId)
+        .check();
+}
+
+#[test]
+fn add_validator_to_pool() {
+    let (mut accounts, pool) = setup_pool();
+    let deposit_authority = set_deposit_authority(&mut accounts, &pool);
+    // room for a second validator
+    update_validator_list(&mut accounts, &pool, |validator_list| {
+        validator_list.max_validators = 2;
+    });
+    accounts
+        .0
+        .get_mut(&pool.validator_list)
+        .unwrap()
+        .data
+        .extend_from_slice(&[0; ValidatorStakeInfo::LEN]);
+    let vote_account = Pubkey::new_unique();
+    let (validator_stake, _) = find_stake_program_address(&id(), &vote_account, &pool.stake_pool);
+    accounts.add(
+        validator_stake,
+        delegated_stake_account(&vote_account, VALIDATOR_LAMPORTS),
+    );
+    let pool_token_receiver = Pubkey::new_unique();
+    accounts.add(
+        pool_token_receiver,
+        token_account(&pool.pool_mint, &pool.staker, 0),
+    );
+    let instruction = instruction::add_validator_to_pool(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &deposit_authority,
+        &pool.withdraw_authority,
+        &pool.validator_list,
+        &validator_stake,
+        &pool_token_receiver,
+        &pool.pool_mint,
+        &spl_token::id(),
+    )
+    .unwrap();
+    Case::new("add_validator_to_pool", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongStaker)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(4, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(5, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(7, WrongAddress, StakePoolError::WrongPoolMint)
+        .check();
+}
+
+#[test]
+fn remove_validator_from_pool() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::remove_validator_from_pool(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &pool.withdraw_authority,
+        &pool.validator_list,
+        &pool.validator_stake,
+    )
+    .unwrap();
+    Case::new("remove_validator_from_pool", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongStaker)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(4, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .check();
+}
+
+#[test]
+fn increase_validator_stake() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::increase_validator_stake(
+        &id(),
+        &pool.stake_pool,
+        &pool.staker,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &pool.transient_stake,
+        &pool.vote_account,
+        2 * LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    // the stake program checks the stake config account
+    Case::new("increase_validator_stake", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongStaker)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(4, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .expect_error(5, WrongAddress, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(6, WrongAddress, StakePoolError::ValidatorNotFound)
+        .allow(10, WrongAddress)
+        .check();
+}
+
+#[test]
+fn update_validator_list_balance() {
+    let (mut accounts, pool) = setup_pool();
+    accounts.add(
+        sysvar::clock::id(),
+        sysvar_account(&Clock {
+            epoch: EPOCH + 1,
+            ..Clock::default()
+        }),
+    );
+    let instruction = instruction::update_validator_list_balance(
+        &id(),
+        &pool.stake_pool,
+        &pool.withdraw_authority,
+        &pool.validator_list,
+        &pool.reserve_stake,
+        &[pool.vote_account],
+        0,
+        false,
+    )
+    .unwrap();
+    Case::new("update_validator_list_balance", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(7, WrongAddress, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(8, WrongAddress, StakePoolError::InvalidStakeAccountAddress)
+        .check();
+}
+
+#[test]
+fn apply_targets() {
+    // a target of half the pool, 7.5 SOL, leaves the validator 2.5 SOL
+    // short, which the reserve funds
+    let (mut accounts, pool) = setup_pool();
+    update_validator_list(&mut accounts, &pool, |validator_list| {
+        validator_list.validators[0].target_weight_bps = 5000.into();
+    });
+    let instruction = instruction::apply_targets(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &pool.validator_stake,
+        &pool.transient_stake,
+        &pool.vote_account,
+    )
+    .unwrap();
+    Case::new("apply_targets", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .expect_error(4, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(5, WrongAddress, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(6, WrongAddress, ProgramError::InvalidArgument)
+        .allow(10, WrongAddress)
+        .check();
+}
+
+#[test]
+fn deposit() {
+    let (mut accounts, pool) = setup_pool();
+    let deposit_authority = set_deposit_authority(&mut accounts, &pool);
+    let user = Pubkey::new_unique();
+    let user_stake = Pubkey::new_unique();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user_stake,
+        delegated_stake_account(&pool.vote_account, 2 * LAMPORTS_PER_SOL),
+    );
+    accounts.add(user_pool_account, token_account(&pool.pool_mint, &user, 0));
+    let instruction = instruction::deposit(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &deposit_authority,
+        &pool.withdraw_authority,
+        &user_stake,
+        &pool.validator_stake,
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+    )
+    .unwrap();
+    // the stake program refuses to authorize a stake account it doesn't own
+    Case::new("deposit", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(
+            2,
+            WrongAddress,
+            StakePoolError::InvalidStakeDepositAuthority,
+        )
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .allow(4, WrongOwner)
+        .expect_error(5, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .expect_error(7, WrongAddress, StakePoolError::InvalidFeeAccount)
+        .expect_error(8, WrongAddress, StakePoolError::WrongPoolMint)
+        .check();
+}
+
+#[test]
+fn deposit_multiple() {
+    let (mut accounts, pool) = setup_pool();
+    let deposit_authority = set_deposit_authority(&mut accounts, &pool);
+    let user = Pubkey::new_unique();
+    let user_stake = Pubkey::new_unique();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user_stake,
+        delegated_stake_account(&pool.vote_account, 2 * LAMPORTS_PER_SOL),
+    );
+    accounts.add(user_pool_account, token_account(&pool.pool_mint, &user, 0));
+    let instruction = instruction::deposit_multiple(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &deposit_authority,
+        &pool.withdraw_authority,
+        &[(user_stake, pool.validator_stake)],
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+    )
+    .unwrap();
+    Case::new("deposit_multiple", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(
+            2,
+            WrongAddress,
+            StakePoolError::InvalidStakeDepositAuthority,
+        )
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(5, WrongAddress, StakePoolError::InvalidFeeAccount)
+        .expect_error(6, WrongAddress, StakePoolError::WrongPoolMint)
+        .allow(11, WrongOwner)
+        .expect_error(12, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .check();
+}
+
+#[test]
+fn withdraw() {
+    let (mut accounts, pool) = setup_pool();
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user_pool_account,
+        delegated_token_account(&pool, &Pubkey::new_unique(), LAMPORTS_PER_SOL),
+    );
+    let instruction = instruction::withdraw(
+        &id(),
+        &pool.stake_pool,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.validator_stake,
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &user_pool_account,
+        &pool.manager_fee_account,
+        &pool.pool_mint,
+        &spl_token::id(),
+        LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    Case::new("withdraw", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(3, WrongOwner, StakePoolError::InvalidStakeAccountAddress)
+        .allow(4, WrongAddress)
+        .allow(5, WrongAddress)
+        .expect_error(7, WrongAddress, StakePoolError::InvalidFeeAccount)
+        .expect_error(8, WrongAddress, StakePoolError::WrongPoolMint)
+        .check();
+}
+
+#[test]
+fn set_deposit_lock() {
+    // the pool's owner isn't checked, but the runtime refuses the write
+    let (accounts, pool) = setup_pool();
+    let instruction =
+        instruction::set_deposit_lock(&id(), &pool.stake_pool, &pool.manager, 2).unwrap();
+    Case::new("set_deposit_lock", accounts, instruction)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn set_deposit_cap() {
+    let (accounts, pool) = setup_pool();
+    let instruction = instruction::set_deposit_cap(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        100 * LAMPORTS_PER_SOL,
+    )
+    .unwrap();
+    Case::new("set_deposit_cap", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn set_deposit_interceptor() {
+    let (mut accounts, pool) = setup_pool();
+    let deposit_interceptor = Pubkey::new_unique();
+    accounts.add(deposit_interceptor, program_account());
+    let instruction = instruction::set_deposit_interceptor(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        Some(&deposit_interceptor),
+        100_000,
+        true,
+    )
+    .unwrap();
+    Case::new("set_deposit_interceptor", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn set_max_epoch_net_outflow() {
+    let (accounts, pool) = setup_pool();
+    let instruction =
+        instruction::set_max_epoch_net_outflow(&id(), &pool.stake_pool, &pool.manager, 1000)
+            .unwrap();
+    Case::new("set_max_epoch_net_outflow", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn claim_locked_pool_tokens() {
+    let (mut accounts, pool) = setup_pool();
+    update_stake_pool(&mut accounts, &pool, |stake_pool| {
+        stake_pool.deposit_lock_epochs = 1;
+    });
+    let depositor = Pubkey::new_unique();
+    let (deposit_record, _) = find_deposit_record_address(&id(), &pool.stake_pool, &depositor);
+    let data = DepositRecord {
+        account_type: AccountType::DepositRecord,
+        stake_pool: pool.stake_pool,
+        depositor,
+        locked_until_epoch: EPOCH,
+    }
+    .try_to_vec()
+    .unwrap();
+    accounts.add(
+        deposit_record,
+        account(Rent::default().minimum_balance(data.len()), data, &id()),
+    );
+    let escrow = Pubkey::new_unique();
+    accounts.add(
+        escrow,
+        token_account(&pool.pool_mint, &deposit_record, LAMPORTS_PER_SOL),
+    );
+    let user_pool_account = Pubkey::new_unique();
+    accounts.add(
+        user_pool_account,
+        token_account(&pool.pool_mint, &depositor, 0),
+    );
+    let instruction = instruction::claim_locked_pool_tokens(
+        &id(),
+        &pool.stake_pool,
+        &depositor,
+        &escrow,
+        &user_pool_account,
+        &spl_token::id(),
+    )
+    .unwrap();
+    Case::new("claim_locked_pool_tokens", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::InvalidDepositRecord)
+        .expect_error(1, WrongOwner, StakePoolError::InvalidDepositRecord)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidDepositRecord)
+        .expect_error(2, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn decommission_stake_pool() {
+    let (accounts, pool) = setup_pool();
+    let instruction =
+        instruction::decommission_stake_pool(&id(), &pool.stake_pool, &pool.manager).unwrap();
+    Case::new("decommission_stake_pool", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .check();
+}
+
+#[test]
+fn close_stake_pool() {
+    // decommissioned, every validator removed and every pool token burned
+    let (mut accounts, pool) = setup_pool();
+    update_stake_pool(&mut accounts, &pool, |stake_pool| {
+        stake_pool.decommissioning = true;
+        stake_pool.pool_token_supply = 0;
+    });
+    accounts.0.get_mut(&pool.validator_list).unwrap().data =
+        ValidatorList::new(0).try_to_vec().unwrap();
+    let instruction = instruction::close_stake_pool(
+        &id(),
+        &pool.stake_pool,
+        &pool.manager,
+        &pool.validator_list,
+        &pool.withdraw_authority,
+        &pool.reserve_stake,
+        &Pubkey::new_unique(),
+    )
+    .unwrap();
+    Case::new("close_stake_pool", accounts, instruction)
+        .expect_error(0, WrongOwner, ProgramError::IncorrectProgramId)
+        .expect_error(1, WrongAddress, StakePoolError::WrongManager)
+        .expect_error(1, Unsigned, StakePoolError::SignatureMissing)
+        .expect_error(2, WrongAddress, StakePoolError::InvalidValidatorStakeList)
+        .expect_error(3, WrongAddress, StakePoolError::InvalidProgramAddress)
+        .expect_error(4, WrongAddress, StakePoolError::InvalidReserveStakeAccount)
+        .allow(5, WrongAddress)
+        .check();
+}
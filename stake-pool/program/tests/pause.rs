@@ -0,0 +1,288 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    Keypair,
+    Pubkey,
+) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    (
+        context,
+        stake_pool_accounts,
+        validator_stake_account,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+async fn get_stake_pool(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap()
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+fn check_paused(transaction_error: TransportError) {
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::StakePoolPaused as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_pause_and_resume() {
+    let (mut context, stake_pool_accounts, validator_stake_account, user, user_pool_account) =
+        setup().await;
+
+    stake_pool_accounts
+        .set_paused(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            true,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert!(stake_pool.paused);
+
+    let transaction_error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            LAMPORTS_PER_SOL,
+        )
+        .await
+        .unwrap_err();
+    check_paused(transaction_error);
+
+    let transaction_error = stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_pool_account,
+            &Keypair::new().pubkey(),
+            LAMPORTS_PER_SOL,
+        )
+        .await
+        .unwrap_err();
+    check_paused(transaction_error);
+
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            LAMPORTS_PER_SOL * 2,
+        )
+        .await
+        .unwrap();
+    check_paused(transaction_error);
+
+    // updates keep going through
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    stake_pool_accounts
+        .set_paused(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            false,
+        )
+        .await
+        .unwrap();
+    let stake_pool = get_stake_pool(&mut context, &stake_pool_accounts).await;
+    assert!(!stake_pool.paused);
+
+    // the same deposit and withdrawal as above, in new transactions
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account,
+            LAMPORTS_PER_SOL,
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_pool_account,
+            &Keypair::new().pubkey(),
+            LAMPORTS_PER_SOL,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fail_deposit_stake_while_paused() {
+    let (mut context, stake_pool_accounts, validator_stake_account, _, user_pool_account) =
+        setup().await;
+
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+
+    stake_pool_accounts
+        .set_paused(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            true,
+        )
+        .await
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account,
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .unwrap_err();
+    check_paused(transaction_error);
+}
@@ -0,0 +1,400 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        program::invoke_signed,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id,
+        instruction::{self, Fee, FeeType, PreferredValidatorType},
+        stake_program, state,
+    },
+};
+
+const PROXY_AUTHORITY_SEED: &[u8] = b"authority";
+
+/// Minimal stand-in for a DAO program: forwards its instruction data to the
+/// program in the first account, signing for its authority address with
+/// `invoke_signed`
+fn process_proxy_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    let (authority, bump_seed) = Pubkey::find_program_address(&[PROXY_AUTHORITY_SEED], program_id);
+    let (target_program_info, target_accounts) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let instruction = Instruction {
+        program_id: *target_program_info.key,
+        accounts: target_accounts
+            .iter()
+            .map(|account_info| AccountMeta {
+                pubkey: *account_info.key,
+                is_signer: account_info.is_signer || *account_info.key == authority,
+                is_writable: account_info.is_writable,
+            })
+            .collect(),
+        data: input.to_vec(),
+    };
+    invoke_signed(
+        &instruction,
+        accounts,
+        &[&[PROXY_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+struct Proxy {
+    program_id: Pubkey,
+    authority: Pubkey,
+}
+
+impl Proxy {
+    fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let (authority, _) = Pubkey::find_program_address(&[PROXY_AUTHORITY_SEED], &program_id);
+        Self {
+            program_id,
+            authority,
+        }
+    }
+
+    /// Wrap an instruction so the proxy program signs for its authority
+    fn wrap(&self, instruction: Instruction) -> Instruction {
+        let mut accounts = vec![AccountMeta::new_readonly(instruction.program_id, false)];
+        accounts.extend(instruction.accounts.into_iter().map(|mut account_meta| {
+            if account_meta.pubkey == self.authority {
+                account_meta.is_signer = false;
+            }
+            account_meta
+        }));
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: instruction.data,
+        }
+    }
+
+    async fn process(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        instruction: Instruction,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[self.wrap(instruction)],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await
+    }
+}
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts, Proxy) {
+    let proxy = Proxy::new();
+    let mut program_test = program_test();
+    program_test.add_builtin_program(
+        "spl_stake_pool_proxy",
+        proxy.program_id,
+        processor!(process_proxy_instruction).unwrap(),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        proxy,
+    )
+}
+
+async fn get_stake_pool(
+    banks_client: &mut BanksClient,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap()
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_manager_pda() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, proxy) = setup().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_manager(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &proxy.authority,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let stake_deposit_fee = Fee {
+        numerator: 1,
+        denominator: 100,
+    };
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::set_fee(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                FeeType::StakeDeposit(stake_deposit_fee),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let max_total_lamports = 1_000_000_000_000;
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::set_deposit_cap(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                max_total_lamports,
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.manager, proxy.authority);
+    assert_eq!(stake_pool.stake_deposit_fee, stake_deposit_fee);
+    assert_eq!(stake_pool.deposit_cap(), Some(max_total_lamports));
+
+    // the program can hand the pool back to a keypair
+    let new_manager = Keypair::new();
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::set_manager(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                &new_manager.pubkey(),
+                &stake_pool_accounts.pool_fee_account.pubkey(),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.manager, new_manager.pubkey());
+}
+
+#[tokio::test]
+async fn success_staker_pda() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, proxy) = setup().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_staker(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &proxy.authority,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer, &stake_pool_accounts.manager],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let validator_stake = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    create_vote(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &validator_stake.vote,
+    )
+    .await;
+
+    // the stake account is created and delegated with the proxy authority as
+    // staker and withdrawer
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::create_validator_stake_account(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                &payer.pubkey(),
+                &validator_stake.stake_account,
+                &validator_stake.vote.pubkey(),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    for stake_authorize in &[
+        stake_program::StakeAuthorize::Staker,
+        stake_program::StakeAuthorize::Withdrawer,
+    ] {
+        proxy
+            .process(
+                &mut banks_client,
+                &payer,
+                &recent_blockhash,
+                stake_program::authorize(
+                    &validator_stake.stake_account,
+                    &proxy.authority,
+                    &stake_pool_accounts.deposit_authority,
+                    *stake_authorize,
+                ),
+            )
+            .await
+            .unwrap();
+    }
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &payer.pubkey(),
+    )
+    .await
+    .unwrap();
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::add_validator_to_pool(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                &stake_pool_accounts.deposit_authority,
+                &stake_pool_accounts.withdraw_authority,
+                &stake_pool_accounts.validator_list.pubkey(),
+                &validator_stake.stake_account,
+                &user_pool_account.pubkey(),
+                &stake_pool_accounts.pool_mint.pubkey(),
+                &spl_token::id(),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::set_preferred_validator(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                &stake_pool_accounts.validator_list.pubkey(),
+                PreferredValidatorType::Deposit,
+                Some(validator_stake.vote.pubkey()),
+            )
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let validator_list = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    assert!(validator_list.contains(&validator_stake.vote.pubkey()));
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    assert_eq!(stake_pool.staker, proxy.authority);
+    assert_eq!(
+        stake_pool.preferred_deposit_validator_vote_address,
+        validator_stake.vote.pubkey()
+    );
+}
+
+#[tokio::test]
+async fn fail_pda_not_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, proxy) = setup().await;
+
+    let transaction_error = proxy
+        .process(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::set_deposit_cap(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &proxy.authority,
+                1,
+            )
+            .unwrap(),
+        )
+        .await
+        .err()
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
@@ -0,0 +1,209 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, find_price_oracle_address, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+async fn update_with_price_oracle(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    price_oracle: &Pubkey,
+) -> Result<(), TransportError> {
+    let mut update_instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .unwrap();
+    instruction::add_price_oracle_accounts(
+        &mut update_instruction,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &payer.pubkey(),
+    );
+    update_instruction.accounts[8].pubkey = *price_oracle;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[update_instruction],
+        Some(&payer.pubkey()),
+        &[payer],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+async fn get_stake_pool(
+    banks_client: &mut BanksClient,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::StakePool {
+    let stake_pool = get_account(banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    state::StakePool::try_from_slice(stake_pool.data.as_slice()).unwrap()
+}
+
+async fn get_price_oracle(
+    banks_client: &mut BanksClient,
+    price_oracle: &Pubkey,
+) -> state::PriceOracle {
+    let price_oracle = get_account(banks_client, price_oracle).await;
+    state::PriceOracle::deserialize_checked(price_oracle.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let (price_oracle_address, _) =
+        find_price_oracle_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+
+    // the first update creates the oracle
+    update_with_price_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &price_oracle_address,
+    )
+    .await
+    .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    let price_oracle = get_price_oracle(&mut banks_client, &price_oracle_address).await;
+    assert_eq!(
+        price_oracle,
+        state::PriceOracle {
+            account_type: state::AccountType::PriceOracle,
+            stake_pool: stake_pool_accounts.stake_pool.pubkey(),
+            rate: state::PRICE_ORACLE_RATE_PRECISION,
+            confidence: 0,
+            last_update_epoch: stake_pool.last_update_epoch,
+        }
+    );
+
+    // rewards landing in the reserve raise the published rate
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        DEPOSIT_AMOUNT / 2,
+    )
+    .await;
+    let recent_blockhash = banks_client
+        .get_new_blockhash(&recent_blockhash)
+        .await
+        .unwrap()
+        .0;
+    update_with_price_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &price_oracle_address,
+    )
+    .await
+    .unwrap();
+
+    let stake_pool = get_stake_pool(&mut banks_client, &stake_pool_accounts).await;
+    let price_oracle = get_price_oracle(&mut banks_client, &price_oracle_address).await;
+    assert!(price_oracle.rate > state::PRICE_ORACLE_RATE_PRECISION);
+    assert_eq!(Some(price_oracle.rate), stake_pool.price_oracle_rate());
+    // the published rate is rounded down, by less than a lamport per
+    // `PRICE_ORACLE_RATE_PRECISION` pool tokens
+    let lamports = price_oracle
+        .calc_lamports(stake_pool.pool_token_supply)
+        .unwrap();
+    let exact_lamports = stake_pool
+        .calc_lamports_withdraw_amount_floor(stake_pool.pool_token_supply)
+        .unwrap();
+    assert!(lamports <= exact_lamports);
+    assert!(
+        exact_lamports - lamports
+            <= 1 + stake_pool.pool_token_supply / state::PRICE_ORACLE_RATE_PRECISION
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_price_oracle() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let error = update_with_price_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &Pubkey::new_unique(),
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidPriceOracle as u32)
+        )
+    );
+}
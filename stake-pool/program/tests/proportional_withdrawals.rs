@@ -0,0 +1,329 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError,
+        id,
+        instruction::{self, ValidatorTarget},
+        state,
+    },
+};
+
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    Vec<ValidatorStakeAccount>,
+    DepositInfo,
+) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let mut validator_stake_accounts = vec![];
+    let mut deposit_infos = vec![];
+    for _ in 0..3 {
+        let validator_stake_account = simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        deposit_infos.push(
+            simple_deposit(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                &stake_pool_accounts,
+                &validator_stake_account,
+            )
+            .await,
+        );
+        validator_stake_accounts.push(validator_stake_account);
+    }
+
+    stake_pool_accounts
+        .set_validator_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            validator_stake_accounts
+                .iter()
+                .zip(&[5_000, 3_000, 2_000])
+                .map(|(validator_stake_account, weight_bps)| ValidatorTarget {
+                    vote_account: validator_stake_account.vote.pubkey(),
+                    weight_bps: *weight_bps,
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .set_proportional_withdrawals(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            true,
+        )
+        .await
+        .unwrap();
+
+    (
+        context,
+        stake_pool_accounts,
+        validator_stake_accounts,
+        deposit_infos.remove(0),
+    )
+}
+
+async fn withdraw_from(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    deposit_info: &DepositInfo,
+    validator_stake_account: &ValidatorStakeAccount,
+) -> Result<(), TransportError> {
+    let pool_tokens = deposit_info.pool_tokens / 4;
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &deposit_info.user_pool_account,
+        &deposit_info.user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+    stake_pool_accounts
+        .withdraw_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake_recipient.pubkey(),
+            &deposit_info.user_pool_account,
+            &validator_stake_account.stake_account,
+            &Pubkey::new_unique(),
+            pool_tokens,
+        )
+        .await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_withdraw_from_most_overweight() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts, deposit_info) = setup().await;
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert!(stake_pool.proportional_withdrawals);
+
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let weights: Vec<u16> = validator_list
+        .validators
+        .iter()
+        .map(|item| u16::from(item.target_weight_bps))
+        .collect();
+    assert_eq!(weights, vec![5_000, 3_000, 2_000]);
+
+    // All validators hold the same stake, so the one with the lowest target
+    // is the furthest above it
+    withdraw_from(
+        &mut context,
+        &stake_pool_accounts,
+        &deposit_info,
+        &validator_stake_accounts[2],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_withdraw_from_underweight() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts, deposit_info) = setup().await;
+
+    let transaction_error = withdraw_from(
+        &mut context,
+        &stake_pool_accounts,
+        &deposit_info,
+        &validator_stake_accounts[0],
+    )
+    .await
+    .err()
+    .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::IncorrectWithdrawVoteAddress as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_withdraw_after_disabling() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts, deposit_info) = setup().await;
+
+    stake_pool_accounts
+        .set_proportional_withdrawals(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            false,
+        )
+        .await
+        .unwrap();
+
+    withdraw_from(
+        &mut context,
+        &stake_pool_accounts,
+        &deposit_info,
+        &validator_stake_accounts[0],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_targets_above_maximum() {
+    let (mut context, stake_pool_accounts, validator_stake_accounts, _) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_validator_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            vec![ValidatorTarget {
+                vote_account: validator_stake_accounts[1].vote.pubkey(),
+                weight_bps: 5_001,
+            }],
+        )
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidTargetWeights as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_target_for_unknown_validator() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_validator_targets(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            vec![ValidatorTarget {
+                vote_account: Pubkey::new_unique(),
+                weight_bps: 0,
+            }],
+        )
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::ValidatorNotFound as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_targets_wrong_staker() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_validator_targets(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_staker.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            vec![],
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_staker],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongStaker as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_proportional_withdrawals_wrong_manager() {
+    let (mut context, stake_pool_accounts, _, _) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_proportional_withdrawals(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            false,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
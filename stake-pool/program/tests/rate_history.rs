@@ -0,0 +1,192 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, find_rate_history_address, id, instruction, state},
+};
+
+const RESERVE_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    (context, stake_pool_accounts)
+}
+
+async fn update_with_rate_history(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    rate_history: &Pubkey,
+) -> Result<(), TransportError> {
+    let mut update_instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .unwrap();
+    instruction::add_price_oracle_accounts(
+        &mut update_instruction,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &context.payer.pubkey(),
+    );
+    instruction::add_rate_history_accounts(
+        &mut update_instruction,
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &context.payer.pubkey(),
+    );
+    update_instruction.accounts[11].pubkey = *rate_history;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[update_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+async fn get_rate_history(
+    context: &mut ProgramTestContext,
+    rate_history: &Pubkey,
+) -> state::RateHistory {
+    let rate_history = get_account(&mut context.banks_client, rate_history).await;
+    state::RateHistory::deserialize_checked(rate_history.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let (rate_history_address, _) =
+        find_rate_history_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+
+    // the first update creates the history
+    update_with_rate_history(&mut context, &stake_pool_accounts, &rate_history_address)
+        .await
+        .unwrap();
+    let rate_history = get_rate_history(&mut context, &rate_history_address).await;
+    assert_eq!(
+        rate_history.stake_pool,
+        stake_pool_accounts.stake_pool.pubkey()
+    );
+    assert_eq!(rate_history.entries_recorded, 1);
+    let first_entry = *rate_history.latest().unwrap();
+
+    // rewards landing in the reserve in the next epoch get a new entry
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    update_with_rate_history(&mut context, &stake_pool_accounts, &rate_history_address)
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+    let rate_history = get_rate_history(&mut context, &rate_history_address).await;
+    assert_eq!(rate_history.entries_recorded, 2);
+    assert_eq!(
+        rate_history.iter().copied().collect::<Vec<_>>(),
+        vec![stake_pool.rate_history_entry(), first_entry]
+    );
+    assert!(first_entry.epoch < stake_pool.last_update_epoch);
+    assert_eq!(
+        stake_pool.rate_history_entry().total_stake_lamports,
+        first_entry.total_stake_lamports + RESERVE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn success_same_epoch_replaces_entry() {
+    let (mut context, stake_pool_accounts) = setup().await;
+    let (rate_history_address, _) =
+        find_rate_history_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+
+    update_with_rate_history(&mut context, &stake_pool_accounts, &rate_history_address)
+        .await
+        .unwrap();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    update_with_rate_history(&mut context, &stake_pool_accounts, &rate_history_address)
+        .await
+        .unwrap();
+
+    let rate_history = get_rate_history(&mut context, &rate_history_address).await;
+    assert_eq!(rate_history.entries_recorded, 1);
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        rate_history.latest(),
+        Some(&stake_pool.rate_history_entry())
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_rate_history() {
+    let (mut context, stake_pool_accounts) = setup().await;
+
+    let error = update_with_rate_history(&mut context, &stake_pool_accounts, &Pubkey::new_unique())
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidRateHistory as u32)
+        )
+    );
+}
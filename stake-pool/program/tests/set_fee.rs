@@ -0,0 +1,193 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::hash::Hash,
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError, signature::Keypair, signature::Signer,
+        transaction::TransactionError, transport::TransportError,
+    },
+    spl_stake_pool::{
+        error,
+        instruction::{Fee, FeeType},
+        state,
+    },
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let epoch_fee = Fee {
+        numerator: 1,
+        denominator: 10,
+    };
+    let stake_deposit_fee = Fee {
+        numerator: 2,
+        denominator: 100,
+    };
+    let sol_deposit_fee = Fee {
+        numerator: 3,
+        denominator: 100,
+    };
+    let withdrawal_fee = Fee {
+        numerator: 1,
+        denominator: 1000,
+    };
+    for fee in &[
+        FeeType::Epoch(epoch_fee),
+        FeeType::StakeDeposit(stake_deposit_fee),
+        FeeType::SolDeposit(sol_deposit_fee),
+        FeeType::Withdrawal(withdrawal_fee),
+    ] {
+        stake_pool_accounts
+            .set_fee(&mut banks_client, &payer, &recent_blockhash, *fee)
+            .await
+            .unwrap();
+    }
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.stake_deposit_fee, stake_deposit_fee);
+    assert_eq!(stake_pool.sol_deposit_fee, sol_deposit_fee);
+
+    // Epoch and withdrawal fees wait for the next epoch
+    assert_eq!(stake_pool.epoch_fee, stake_pool_accounts.fee);
+    assert_eq!(stake_pool.next_epoch_fee, epoch_fee);
+    assert_eq!(stake_pool.withdrawal_fee, Fee::default());
+    assert_eq!(stake_pool.next_withdrawal_fee, withdrawal_fee);
+}
+
+#[tokio::test]
+async fn success_activated_next_epoch() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let epoch_fee = Fee {
+        numerator: 1,
+        denominator: 10,
+    };
+    let withdrawal_fee = Fee {
+        numerator: 1,
+        denominator: 1000,
+    };
+    for fee in &[
+        FeeType::Epoch(epoch_fee),
+        FeeType::Withdrawal(withdrawal_fee),
+    ] {
+        stake_pool_accounts
+            .set_fee(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                *fee,
+            )
+            .await
+            .unwrap();
+    }
+
+    context.warp_to_slot(50_000).unwrap();
+
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.epoch_fee, epoch_fee);
+    assert_eq!(stake_pool.next_epoch_fee, epoch_fee);
+    assert_eq!(stake_pool.withdrawal_fee, withdrawal_fee);
+    assert_eq!(stake_pool.next_withdrawal_fee, withdrawal_fee);
+}
+
+#[tokio::test]
+async fn fail_high_fee() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_fee(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            FeeType::SolDeposit(Fee {
+                numerator: 11,
+                denominator: 10,
+            }),
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::FeeTooHigh as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs when setting fee too high"),
+    }
+}
+
+#[tokio::test]
+async fn fail_withdrawal_fee_increase_too_high() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_fee(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            FeeType::Withdrawal(Fee {
+                numerator: 1,
+                denominator: 100,
+            }),
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::FeeIncreaseTooHigh as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs when increasing withdrawal fee too much"),
+    }
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{hash::Hash, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::signature::{Keypair, Signer},
+    spl_stake_pool::{instruction::FundingType, state},
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success_stake_deposit() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.stake_deposit_authority,
+        stake_pool_accounts.deposit_authority
+    );
+
+    let new_authority = Pubkey::new_unique();
+    stake_pool_accounts
+        .set_funding_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            Some(&new_authority),
+            FundingType::StakeDeposit,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.stake_deposit_authority, new_authority);
+
+    stake_pool_accounts
+        .set_funding_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            None,
+            FundingType::StakeDeposit,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.stake_deposit_authority,
+        stake_pool_accounts.deposit_authority
+    );
+}
+
+#[tokio::test]
+async fn success_sol_deposit() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.sol_deposit_authority(), None);
+
+    let new_authority = Pubkey::new_unique();
+    stake_pool_accounts
+        .set_funding_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            Some(&new_authority),
+            FundingType::SolDeposit,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.sol_deposit_authority(), Some(new_authority));
+
+    stake_pool_accounts
+        .set_funding_authority(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            None,
+            FundingType::SolDeposit,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.sol_deposit_authority(), None);
+}
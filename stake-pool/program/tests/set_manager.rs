@@ -8,10 +8,12 @@ use {
     solana_program::{
         hash::Hash,
         instruction::{AccountMeta, Instruction},
+        program_pack::Pack,
+        pubkey::Pubkey,
     },
     solana_program_test::*,
     solana_sdk::{
-        instruction::InstructionError, signature::Keypair, signature::Signer,
+        account::Account, instruction::InstructionError, signature::Keypair, signature::Signer,
         transaction::Transaction, transaction::TransactionError, transport::TransportError,
     },
     spl_stake_pool::{error, id, instruction, state},
@@ -78,6 +80,37 @@ async fn test_set_manager() {
     let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
 
     assert_eq!(stake_pool.manager, new_manager.pubkey());
+    assert_eq!(stake_pool.manager_fee_account, new_pool_fee.pubkey());
+
+    // The previous manager lost its rights
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_manager(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongManager as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while previous manager try to set manager"),
+    }
 }
 
 #[tokio::test]
@@ -218,3 +251,62 @@ async fn test_set_manager_with_wrong_mint_for_pool_fee_acc() {
         _ => panic!("Wrong error occurs while try to set new manager with wrong mint"),
     }
 }
+
+#[tokio::test]
+async fn test_set_manager_with_fee_acc_not_owned_by_token_program() {
+    let stake_pool_accounts = StakePoolAccounts::new();
+    let new_manager = Keypair::new();
+
+    // Looks like a pool token account, but isn't owned by the token program
+    let new_pool_fee = Pubkey::new_unique();
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: stake_pool_accounts.pool_mint.pubkey(),
+        owner: new_manager.pubkey(),
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    }
+    .pack_into_slice(&mut data);
+    let mut program_test = program_test();
+    program_test.add_account(
+        new_pool_fee,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_manager(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &new_manager.pubkey(),
+            &new_pool_fee,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, InstructionError::IncorrectProgramId);
+        }
+        _ => panic!("Wrong error occurs while try to set new manager with fake fee account"),
+    }
+}
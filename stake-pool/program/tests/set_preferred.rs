@@ -0,0 +1,328 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error, instruction::PreferredValidatorType, stake_program, state},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    ValidatorStakeAccount,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let preferred_validator = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let other_validator = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        preferred_validator,
+        other_validator,
+    )
+}
+
+fn check_error(transaction_error: TransportError, expected_error: error::StakePoolError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            assert_eq!(error_index, expected_error as u32);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        preferred_validator,
+        other_validator,
+    ) = setup().await;
+
+    stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Deposit,
+            Some(preferred_validator.vote.pubkey()),
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Withdraw,
+            Some(other_validator.vote.pubkey()),
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.preferred_deposit_validator(),
+        Some(preferred_validator.vote.pubkey())
+    );
+    assert_eq!(
+        stake_pool.preferred_withdraw_validator(),
+        Some(other_validator.vote.pubkey())
+    );
+
+    stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Deposit,
+            None,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.preferred_deposit_validator(), None);
+    assert_eq!(
+        stake_pool.preferred_withdraw_validator(),
+        Some(other_validator.vote.pubkey())
+    );
+}
+
+#[tokio::test]
+async fn fail_not_present_validator() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _, _) = setup().await;
+
+    let transaction_error = stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Withdraw,
+            Some(Pubkey::new_unique()),
+        )
+        .await
+        .err()
+        .unwrap();
+
+    check_error(transaction_error, error::StakePoolError::ValidatorNotFound);
+}
+
+#[tokio::test]
+async fn fail_deposit_to_other_validator() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        preferred_validator,
+        other_validator,
+    ) = setup().await;
+
+    stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Deposit,
+            Some(preferred_validator.vote.pubkey()),
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.deposit_authority,
+        withdrawer: stake_pool_accounts.deposit_authority,
+    };
+    create_independent_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .deposit_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &other_validator.stake_account,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        error::StakePoolError::IncorrectDepositVoteAddress,
+    );
+
+    // Depositing into the preferred validator still works
+    stake_pool_accounts
+        .deposit_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &preferred_validator.stake_account,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fail_withdraw_from_other_validator() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        preferred_validator,
+        other_validator,
+    ) = setup().await;
+
+    simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &preferred_validator,
+    )
+    .await;
+    let deposit_info = simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &other_validator,
+    )
+    .await;
+
+    stake_pool_accounts
+        .set_preferred_validator(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            PreferredValidatorType::Withdraw,
+            Some(preferred_validator.vote.pubkey()),
+        )
+        .await
+        .unwrap();
+
+    let tokens_to_burn = deposit_info.pool_tokens / 4;
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &deposit_info.user_pool_account,
+        &deposit_info.user,
+        &stake_pool_accounts.withdraw_authority,
+        tokens_to_burn,
+    )
+    .await;
+
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+
+    let new_authority = Pubkey::new_unique();
+    let transaction_error = stake_pool_accounts
+        .withdraw_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake_recipient.pubkey(),
+            &deposit_info.user_pool_account,
+            &other_validator.stake_account,
+            &new_authority,
+            tokens_to_burn,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        error::StakePoolError::IncorrectWithdrawVoteAddress,
+    );
+
+    // Withdrawing from the preferred validator still works
+    stake_pool_accounts
+        .withdraw_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake_recipient.pubkey(),
+            &deposit_info.user_pool_account,
+            &preferred_validator.stake_account,
+            &new_authority,
+            tokens_to_burn,
+        )
+        .await
+        .unwrap();
+}
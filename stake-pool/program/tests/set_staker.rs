@@ -174,3 +174,37 @@ async fn fail_set_staker_without_signature() {
         _ => panic!("Wrong error occurs while try to set new manager without signature"),
     }
 }
+
+#[tokio::test]
+async fn fail_validator_list_as_stake_pool() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, new_staker) =
+        setup().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_staker(
+            &id(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &new_staker.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongAccountType as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while passing the validator list as the stake pool"),
+    }
+}
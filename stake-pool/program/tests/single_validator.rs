@@ -0,0 +1,387 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        borsh::get_packed_len, hash::Hash, instruction::InstructionError, program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError, find_single_validator_pool_address,
+        find_single_validator_pool_mint_address, find_stake_program_address,
+        find_withdraw_authority_program_address, id, instruction, stake_program, state,
+        MINIMUM_ACTIVE_STAKE,
+    },
+};
+
+struct SingleValidatorPoolAccounts {
+    vote: Keypair,
+    pool: Pubkey,
+    stake_account: Pubkey,
+    pool_mint: Pubkey,
+    withdraw_authority: Pubkey,
+}
+
+impl SingleValidatorPoolAccounts {
+    fn new() -> Self {
+        let vote = Keypair::new();
+        let (pool, _) = find_single_validator_pool_address(&id(), &vote.pubkey());
+        let (stake_account, _) = find_stake_program_address(&id(), &vote.pubkey(), &pool);
+        let (pool_mint, _) = find_single_validator_pool_mint_address(&id(), &pool);
+        let (withdraw_authority, _) = find_withdraw_authority_program_address(&id(), &pool);
+        Self {
+            vote,
+            pool,
+            stake_account,
+            pool_mint,
+            withdraw_authority,
+        }
+    }
+
+    async fn initialize(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        funder: &Keypair,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::initialize_single_validator_pool(
+                &id(),
+                &self.vote.pubkey(),
+                &funder.pubkey(),
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer, funder],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+
+    /// Creates a stake account delegated to `vote` and hands it over to the
+    /// pool, returning its lamports
+    async fn create_deposit_stake(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Keypair,
+        vote: &Pubkey,
+    ) -> u64 {
+        let user = Keypair::new();
+        let lamports = create_independent_stake_account(
+            banks_client,
+            payer,
+            recent_blockhash,
+            stake,
+            &stake_program::Authorized {
+                staker: user.pubkey(),
+                withdrawer: user.pubkey(),
+            },
+            &stake_program::Lockup::default(),
+        )
+        .await;
+        delegate_stake_account(
+            banks_client,
+            payer,
+            recent_blockhash,
+            &stake.pubkey(),
+            &user,
+            vote,
+        )
+        .await;
+        for stake_authorize in &[
+            stake_program::StakeAuthorize::Staker,
+            stake_program::StakeAuthorize::Withdrawer,
+        ] {
+            authorize_stake_account(
+                banks_client,
+                payer,
+                recent_blockhash,
+                &stake.pubkey(),
+                &user,
+                &self.withdraw_authority,
+                *stake_authorize,
+            )
+            .await;
+        }
+        lamports
+    }
+
+    async fn deposit(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        stake: &Pubkey,
+        pool_tokens_to: &Pubkey,
+    ) -> Result<(), TransportError> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::deposit_single_validator(
+                &id(),
+                &self.vote.pubkey(),
+                stake,
+                pool_tokens_to,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+            &[payer],
+            *recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await?;
+        Ok(())
+    }
+}
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    SingleValidatorPoolAccounts,
+    Keypair,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let pool_accounts = SingleValidatorPoolAccounts::new();
+    create_vote(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &pool_accounts.vote,
+    )
+    .await;
+    pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash, &payer)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &pool_accounts.pool_mint,
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_initialize() {
+    let (mut banks_client, _payer, _recent_blockhash, pool_accounts, _user, _user_pool_account) =
+        setup().await;
+
+    let pool = get_account(&mut banks_client, &pool_accounts.pool).await;
+    assert_eq!(pool.owner, id());
+    assert_eq!(
+        pool.data.len(),
+        get_packed_len::<state::SingleValidatorPool>()
+    );
+    let pool = state::SingleValidatorPool::try_from_slice(pool.data.as_slice()).unwrap();
+    assert_eq!(
+        pool,
+        state::SingleValidatorPool {
+            account_type: state::AccountType::SingleValidatorPool,
+            vote_account: pool_accounts.vote.pubkey(),
+        }
+    );
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>());
+    let stake = get_account(&mut banks_client, &pool_accounts.stake_account).await;
+    assert_eq!(stake.lamports, stake_rent + MINIMUM_ACTIVE_STAKE);
+    let stake_state = deserialize::<stake_program::StakeState>(&stake.data).unwrap();
+    match stake_state {
+        stake_program::StakeState::Stake(meta, stake) => {
+            assert_eq!(meta.authorized.staker, pool_accounts.withdraw_authority);
+            assert_eq!(meta.authorized.withdrawer, pool_accounts.withdraw_authority);
+            assert_eq!(stake.delegation.voter_pubkey, pool_accounts.vote.pubkey());
+        }
+        _ => panic!("Pool stake account not delegated"),
+    }
+
+    let pool_mint = get_account(&mut banks_client, &pool_accounts.pool_mint).await;
+    let pool_mint = spl_token::state::Mint::unpack(pool_mint.data.as_slice()).unwrap();
+    assert_eq!(
+        pool_mint.mint_authority,
+        Some(pool_accounts.withdraw_authority).into()
+    );
+    assert_eq!(pool_mint.supply, 0);
+}
+
+#[tokio::test]
+async fn success_deposit_and_withdraw() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, user, user_pool_account) =
+        setup().await;
+
+    let deposit_stake = Keypair::new();
+    let deposit_lamports = pool_accounts
+        .create_deposit_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &deposit_stake,
+            &pool_accounts.vote.pubkey(),
+        )
+        .await;
+    pool_accounts
+        .deposit(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &deposit_stake.pubkey(),
+            &user_pool_account,
+        )
+        .await
+        .unwrap();
+
+    // the first deposit mints at 1:1
+    let pool_tokens = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(pool_tokens, deposit_lamports);
+    assert!(banks_client
+        .get_account(deposit_stake.pubkey())
+        .await
+        .expect("get_account")
+        .is_none());
+
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &user,
+        &pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+    let withdraw_stake = Keypair::new();
+    let initial_withdraw_lamports = create_blank_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &withdraw_stake,
+    )
+    .await;
+    let withdrawer = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::withdraw_single_validator(
+            &id(),
+            &pool_accounts.vote.pubkey(),
+            &withdraw_stake.pubkey(),
+            &withdrawer,
+            &user_pool_account,
+            pool_tokens / 2,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account).await,
+        pool_tokens - pool_tokens / 2
+    );
+    let withdraw_stake = get_account(&mut banks_client, &withdraw_stake.pubkey()).await;
+    assert_eq!(
+        withdraw_stake.lamports,
+        initial_withdraw_lamports + pool_tokens / 2
+    );
+    let stake_state = deserialize::<stake_program::StakeState>(&withdraw_stake.data).unwrap();
+    let meta = stake_state.meta().unwrap();
+    assert_eq!(meta.authorized.staker, withdrawer);
+    assert_eq!(meta.authorized.withdrawer, withdrawer);
+}
+
+#[tokio::test]
+async fn fail_deposit_to_other_validator() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _user, user_pool_account) =
+        setup().await;
+
+    let other_vote = Keypair::new();
+    create_vote(&mut banks_client, &payer, &recent_blockhash, &other_vote).await;
+    let deposit_stake = Keypair::new();
+    pool_accounts
+        .create_deposit_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &deposit_stake,
+            &other_vote.pubkey(),
+        )
+        .await;
+
+    // the stake program refuses to merge stake delegated elsewhere
+    pool_accounts
+        .deposit(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &deposit_stake.pubkey(),
+            &user_pool_account,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn fail_initialize_twice() {
+    let (mut banks_client, payer, recent_blockhash, pool_accounts, _user, _user_pool_account) =
+        setup().await;
+
+    let funder = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &funder.pubkey(),
+        10 * MINIMUM_ACTIVE_STAKE,
+    )
+    .await;
+    let error = pool_accounts
+        .initialize(&mut banks_client, &payer, &recent_blockhash, &funder)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::AlreadyInUse as u32),
+    );
+}
@@ -0,0 +1,296 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, stake_program},
+};
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_account,
+    )
+}
+
+async fn deposit_with_slippage(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+    minimum_pool_tokens_out: u64,
+) -> (Result<(), TransportError>, u64, Pubkey) {
+    let user = Keypair::new();
+    let user_stake = Keypair::new();
+    let authorized = stake_program::Authorized {
+        staker: stake_pool_accounts.stake_deposit_authority,
+        withdrawer: stake_pool_accounts.stake_deposit_authority,
+    };
+    let stake_lamports = create_independent_stake_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &user_stake,
+        &authorized,
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::deposit_with_slippage(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.stake_deposit_authority,
+            &stake_pool_accounts.withdraw_authority,
+            &user_stake.pubkey(),
+            &validator_stake_account.stake_account,
+            &user_pool_account.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+            minimum_pool_tokens_out,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer],
+        *recent_blockhash,
+    );
+    (
+        banks_client.process_transaction(transaction).await,
+        stake_lamports,
+        user_pool_account.pubkey(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn withdraw_with_slippage(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+    deposit_info: &DepositInfo,
+    pool_tokens_in: u64,
+    minimum_lamports_out: u64,
+) -> Result<(), TransportError> {
+    delegate_tokens(
+        banks_client,
+        payer,
+        recent_blockhash,
+        &deposit_info.user_pool_account,
+        &deposit_info.user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens_in,
+    )
+    .await;
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(banks_client, payer, recent_blockhash, &user_stake_recipient).await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::withdraw_with_slippage(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &validator_stake_account.stake_account,
+            &user_stake_recipient.pubkey(),
+            &Pubkey::new_unique(),
+            &deposit_info.user_pool_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+            pool_tokens_in,
+            minimum_lamports_out,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+        &[payer],
+        *recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_deposit_within_slippage() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    // Pools start at one pool token per lamport, without deposit fees
+    let (result, stake_lamports, user_pool_account) = deposit_with_slippage(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        0,
+    )
+    .await;
+    result.unwrap();
+    let pool_tokens = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(pool_tokens, stake_lamports);
+
+    let (result, _, user_pool_account) = deposit_with_slippage(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        pool_tokens,
+    )
+    .await;
+    result.unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account).await,
+        pool_tokens
+    );
+}
+
+#[tokio::test]
+async fn fail_deposit_exceeding_slippage() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+
+    let (result, stake_lamports, user_pool_account) = deposit_with_slippage(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        u64::MAX,
+    )
+    .await;
+    assert!(stake_lamports > 0);
+    check_error(
+        result.err().unwrap(),
+        InstructionError::Custom(StakePoolError::ExceededSlippage as u32),
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &user_pool_account).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn success_withdraw_within_slippage() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+    let deposit_info = simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+
+    // Without a withdrawal fee, every pool token is still worth one lamport
+    let pool_tokens_in = deposit_info.pool_tokens / 4;
+    withdraw_with_slippage(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        &deposit_info,
+        pool_tokens_in,
+        pool_tokens_in,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &deposit_info.user_pool_account).await,
+        deposit_info.pool_tokens - pool_tokens_in
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_exceeding_slippage() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, validator_stake_account) =
+        setup().await;
+    let deposit_info = simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+
+    // Every pool token is worth one lamport
+    let pool_tokens_in = deposit_info.pool_tokens / 4;
+    let transaction_error = withdraw_with_slippage(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        &deposit_info,
+        pool_tokens_in,
+        pool_tokens_in + 1,
+    )
+    .await
+    .err()
+    .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::ExceededSlippage as u32),
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, &deposit_info.user_pool_account).await,
+        deposit_info.pool_tokens
+    );
+}
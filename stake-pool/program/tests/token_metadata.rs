@@ -0,0 +1,130 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{hash::Hash, instruction::AccountMeta},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError, id, inline_mpl_token_metadata::pda::find_metadata_account,
+        instruction,
+    },
+};
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn fail_create_metadata_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::create_token_metadata(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &payer.pubkey(),
+            "Stake Pool Token".to_string(),
+            "POOL".to_string(),
+            "https://example.com/pool.json".to_string(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_create_metadata_wrong_metadata_account() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let mut instruction = instruction::create_token_metadata(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &payer.pubkey(),
+        "Stake Pool Token".to_string(),
+        "POOL".to_string(),
+        "https://example.com/pool.json".to_string(),
+    )
+    .unwrap();
+    let (wrong_metadata, _) = find_metadata_account(&stake_pool_accounts.stake_pool.pubkey());
+    instruction.accounts[5] = AccountMeta::new(wrong_metadata, false);
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &stake_pool_accounts.manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidMetadataAccount as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_update_metadata_wrong_manager() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::update_token_metadata(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            "Stake Pool Token".to_string(),
+            "POOL".to_string(),
+            "https://example.com/pool.json".to_string(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_manager], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
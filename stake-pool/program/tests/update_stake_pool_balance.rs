@@ -7,10 +7,7 @@ use {
     helpers::*,
     solana_program::{instruction::InstructionError, pubkey::Pubkey},
     solana_program_test::*,
-    solana_sdk::{
-        signature::{Keypair, Signer},
-        transaction::TransactionError,
-    },
+    solana_sdk::{signature::Signer, transaction::TransactionError},
     spl_stake_pool::{error::StakePoolError, state::StakePool},
 };
 
@@ -91,7 +88,7 @@ async fn success() {
             &context.last_blockhash,
             stake_accounts
                 .iter()
-                .map(|v| v.stake_account)
+                .map(|v| v.vote.pubkey())
                 .collect::<Vec<Pubkey>>()
                 .as_slice(),
         )
@@ -138,65 +135,43 @@ async fn success() {
 }
 
 #[tokio::test]
-async fn fail_with_wrong_validator_list() {
-    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
-    let mut stake_pool_accounts = StakePoolAccounts::new();
-    stake_pool_accounts
-        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
-        .await
-        .unwrap();
-
-    let wrong_validator_list = Keypair::new();
-    stake_pool_accounts.validator_list = wrong_validator_list;
-    let error = stake_pool_accounts
-        .update_stake_pool_balance(&mut banks_client, &payer, &recent_blockhash)
-        .await
-        .unwrap()
-        .unwrap();
+async fn fail_with_out_of_date_validator_list() {
+    let (mut context, stake_pool_accounts, stake_accounts) = setup().await;
 
-    match error {
-        TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        ) => {
-            let program_error = StakePoolError::InvalidValidatorStakeList as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => panic!("Wrong error occurs while try to update pool balance with wrong validator stake list account"),
-    }
-}
+    context.warp_to_slot(50_000).unwrap();
 
-#[tokio::test]
-async fn fail_with_wrong_pool_fee_account() {
-    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
-    let mut stake_pool_accounts = StakePoolAccounts::new();
-    stake_pool_accounts
-        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
-        .await
-        .unwrap();
+    // Leave out the last validator
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            stake_accounts[..stake_accounts.len() - 1]
+                .iter()
+                .map(|v| v.vote.pubkey())
+                .collect::<Vec<Pubkey>>()
+                .as_slice(),
+        )
+        .await;
+    assert!(error.is_none());
 
-    let wrong_fee_account = Keypair::new();
-    stake_pool_accounts.pool_fee_account = wrong_fee_account;
     let error = stake_pool_accounts
-        .update_stake_pool_balance(&mut banks_client, &payer, &recent_blockhash)
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
         .await
         .unwrap()
         .unwrap();
 
     match error {
-        TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        ) => {
-            let program_error = StakePoolError::InvalidFeeAccount as u32;
+        TransactionError::InstructionError(_, InstructionError::Custom(error_index)) => {
+            let program_error = StakePoolError::StakeListOutOfDate as u32;
             assert_eq!(error_index, program_error);
         }
-        _ => panic!("Wrong error occurs while try to update pool balance with wrong validator stake list account"),
+        _ => panic!(
+            "Wrong error occurs while try to update pool balance with out of date validators"
+        ),
     }
 }
-
-#[tokio::test]
-async fn test_update_stake_pool_balance_with_uninitialized_validator_list() {} // TODO
-
-#[tokio::test]
-async fn test_update_stake_pool_balance_with_out_of_dated_validators_balances() {} // TODO
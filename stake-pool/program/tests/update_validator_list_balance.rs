@@ -5,12 +5,39 @@ mod helpers;
 use {
     crate::helpers::TEST_STAKE_AMOUNT,
     helpers::*,
-    solana_program::{native_token, pubkey::Pubkey},
+    solana_program::{
+        instruction::InstructionError, native_token, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
     solana_program_test::*,
-    solana_sdk::signature::Signer,
-    spl_stake_pool::stake_program,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state},
 };
 
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+async fn get_validator_list(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::ValidatorList {
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap()
+}
+
 #[tokio::test]
 async fn success() {
     let mut context = program_test().start_with_context().await;
@@ -77,7 +104,7 @@ async fn success() {
             &context.last_blockhash,
             stake_accounts
                 .iter()
-                .map(|v| v.stake_account)
+                .map(|v| v.vote.pubkey())
                 .collect::<Vec<Pubkey>>()
                 .as_slice(),
         )
@@ -94,6 +121,289 @@ async fn success() {
     );
 }
 
+#[tokio::test]
+async fn success_with_many_validators_in_chunks() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    const STAKE_ACCOUNTS: usize = 22;
+    const CHUNK_SIZE: usize = 5;
+    const EXTRA_STAKE_AMOUNT: u64 = 1_000_000;
+    let mut stake_accounts: Vec<ValidatorStakeAccount> = vec![];
+    for _ in 0..STAKE_ACCOUNTS {
+        let stake_account = simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        transfer(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_account.stake_account,
+            EXTRA_STAKE_AMOUNT,
+        )
+        .await;
+        stake_accounts.push(stake_account);
+    }
+    let list_sum_before = get_validator_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let vote_accounts: Vec<Pubkey> = stake_accounts.iter().map(|v| v.vote.pubkey()).collect();
+    let chunks: Vec<&[Pubkey]> = vote_accounts.chunks(CHUNK_SIZE).collect();
+    let (last_chunk, first_chunks) = chunks.split_last().unwrap();
+    for (i, chunk) in first_chunks.iter().enumerate() {
+        let error = stake_pool_accounts
+            .update_validator_list_balance_chunk(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                chunk,
+                (i * CHUNK_SIZE) as u32,
+                false,
+            )
+            .await;
+        assert!(error.is_none());
+    }
+
+    // The pool balance can't be updated until every validator is
+    let transaction_error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::StakeListOutOfDate as u32),
+    );
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance_chunk(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            last_chunk,
+            (first_chunks.len() * CHUNK_SIZE) as u32,
+            false,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let latest_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(&mut context.banks_client, &context.payer, &latest_blockhash)
+        .await;
+    assert!(error.is_none());
+
+    assert_eq!(
+        get_validator_list_sum(
+            &mut context.banks_client,
+            &stake_pool_accounts.validator_list.pubkey()
+        )
+        .await,
+        list_sum_before + STAKE_ACCOUNTS as u64 * EXTRA_STAKE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn fail_with_wrong_start_index() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let mut vote_accounts = vec![];
+    for _ in 0..3 {
+        let stake_account = simple_add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts,
+        )
+        .await;
+        vote_accounts.push(stake_account.vote.pubkey());
+    }
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .update_validator_list_balance_chunk(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &vote_accounts[1..],
+            0,
+            false,
+        )
+        .await
+        .unwrap();
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidStakeAccountAddress as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_no_merge() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    // Fund the reserve and move some of it to the validator
+    const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+    const INCREASE_AMOUNT: u64 = 2 * LAMPORTS_PER_SOL;
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    // The transient stake is active, but only its balance gets recorded
+    let error = stake_pool_accounts
+        .update_validator_list_balance_chunk(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+            0,
+            true,
+        )
+        .await;
+    assert!(error.is_none());
+    let transient_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.transient_stake_account,
+    )
+    .await;
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.transient_stake_lamports,
+        transient_stake.lamports
+    );
+
+    // Merged on the next regular update
+    context
+        .warp_to_slot(first_normal_slot + 2 * slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let transient_stake = context
+        .banks_client
+        .get_account(validator_stake_account.transient_stake_account)
+        .await
+        .unwrap();
+    assert!(transient_stake.is_none());
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(validator_stake_record.transient_stake_lamports, 0);
+}
+
 #[tokio::test]
 async fn test_update_validator_list_balance_with_uninitialized_validator_list() {} // TODO
 
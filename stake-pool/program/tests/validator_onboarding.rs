@@ -0,0 +1,278 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::InstructionError, native_token::LAMPORTS_PER_SOL},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state},
+};
+
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const STAKE_AMOUNT: u64 = 5 * LAMPORTS_PER_SOL;
+const ONBOARDING_EPOCHS: u64 = 2;
+
+/// Sets up a pool with `RESERVE_AMOUNT` in its reserve and the given
+/// validator onboarding delay
+async fn setup(validator_onboarding_epochs: u64) -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    stake_pool_accounts
+        .set_validator_onboarding_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.manager,
+            validator_onboarding_epochs,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+async fn get_validator_entry(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+) -> state::ValidatorStakeInfo {
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    *validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap()
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts) = setup(ONBOARDING_EPOCHS).await;
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(stake_pool.validator_onboarding_epochs, ONBOARDING_EPOCHS);
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let validator_entry =
+        get_validator_entry(&mut context, &stake_pool_accounts, &validator_stake_account).await;
+    let eligible_epoch = u64::from(validator_entry.eligible_epoch);
+    assert_eq!(
+        eligible_epoch,
+        u64::from(validator_entry.last_update_epoch) + ONBOARDING_EPOCHS
+    );
+
+    // the staker can't move stake to the validator yet
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            STAKE_AMOUNT,
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::ValidatorOnboarding as u32),
+    );
+
+    // nor can depositors
+    let user = Keypair::new();
+    let user_stake = Keypair::new();
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake,
+        &stake_program::Authorized {
+            staker: stake_pool_accounts.stake_deposit_authority,
+            withdrawer: stake_pool_accounts.stake_deposit_authority,
+        },
+        &stake_program::Lockup::default(),
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    let error = stake_pool_accounts
+        .deposit_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake.pubkey(),
+            &user_pool_account.pubkey(),
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::ValidatorOnboarding as u32),
+    );
+
+    // once the delay is over, the validator takes stake
+    let eligible_slot = context
+        .genesis_config()
+        .epoch_schedule
+        .get_first_slot_in_epoch(eligible_epoch);
+    context.warp_to_slot(eligible_slot).unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            STAKE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+#[tokio::test]
+async fn success_without_delay() {
+    let (mut context, stake_pool_accounts) = setup(0).await;
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let validator_entry =
+        get_validator_entry(&mut context, &stake_pool_accounts, &validator_stake_account).await;
+    assert_eq!(
+        validator_entry.eligible_epoch,
+        validator_entry.last_update_epoch
+    );
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            STAKE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+#[tokio::test]
+async fn fail_set_with_wrong_manager() {
+    let (mut context, stake_pool_accounts) = setup(0).await;
+
+    let error = stake_pool_accounts
+        .set_validator_onboarding_epochs(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.staker,
+            ONBOARDING_EPOCHS,
+        )
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongManager as u32),
+    );
+}
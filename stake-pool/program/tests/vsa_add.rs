@@ -18,9 +18,7 @@ use {
         transaction::{Transaction, TransactionError},
         transport::TransportError,
     },
-    spl_stake_pool::{
-        borsh::try_from_slice_unchecked, error, id, instruction, stake_program, state,
-    },
+    spl_stake_pool::{error, id, instruction, stake_program, state},
 };
 
 async fn setup() -> (
@@ -122,16 +120,21 @@ async fn test_add_validator_to_pool() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     assert_eq!(
         validator_list,
         state::ValidatorList {
             account_type: state::AccountType::ValidatorList,
+            version: state::VALIDATOR_LIST_VERSION,
             max_validators: stake_pool_accounts.max_validators,
             validators: vec![state::ValidatorStakeInfo {
                 vote_account: user_stake.vote.pubkey(),
-                last_update_epoch: 0,
-                stake_lamports,
+                status: state::StakeStatus::Active.into(),
+                target_weight_bps: 0.into(),
+                last_update_epoch: 0.into(),
+                active_stake_lamports: stake_lamports.into(),
+                transient_stake_lamports: 0.into(),
+                eligible_epoch: 0.into(),
             }]
         }
     );
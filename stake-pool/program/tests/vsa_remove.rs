@@ -4,11 +4,11 @@ mod helpers;
 
 use {
     bincode::deserialize,
-    borsh::BorshSerialize,
+    borsh::{BorshDeserialize, BorshSerialize},
     helpers::*,
     solana_program::{
-        hash::Hash,
         instruction::{AccountMeta, Instruction, InstructionError},
+        native_token::LAMPORTS_PER_SOL,
         pubkey::Pubkey,
         sysvar,
     },
@@ -18,173 +18,257 @@ use {
         transaction::{Transaction, TransactionError},
         transport::TransportError,
     },
-    spl_stake_pool::{
-        borsh::try_from_slice_unchecked, error, id, instruction, stake_program, state,
-    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, stake_program, state},
 };
 
-async fn setup() -> (
-    BanksClient,
-    Keypair,
-    Hash,
-    StakePoolAccounts,
-    ValidatorStakeAccount,
-    Keypair,
-    Keypair,
+const RESERVE_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+
+async fn update(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
 ) {
-    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+async fn get_validator_list(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::ValidatorList {
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap()
+}
+
+/// Sets up a pool with one validator and a funded reserve, updated in the
+/// middle of the first normal epoch, once the validator stake is active
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, ValidatorStakeAccount) {
+    let mut context = program_test().start_with_context().await;
     let stake_pool_accounts = StakePoolAccounts::new();
     stake_pool_accounts
-        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
         .await
         .unwrap();
 
-    let user = Keypair::new();
-
-    let user_stake = ValidatorStakeAccount::new_with_target_authority(
-        &stake_pool_accounts.deposit_authority,
-        &stake_pool_accounts.stake_pool.pubkey(),
-    );
-    user_stake
-        .create_and_delegate(
-            &mut banks_client,
-            &payer,
-            &recent_blockhash,
-            &stake_pool_accounts.staker,
-        )
-        .await;
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
 
-    // make pool token account
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        RESERVE_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
     let user_pool_account = Keypair::new();
     create_token_account(
-        &mut banks_client,
-        &payer,
-        &recent_blockhash,
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
         &user_pool_account,
         &stake_pool_accounts.pool_mint.pubkey(),
         &user.pubkey(),
     )
     .await
     .unwrap();
-
-    let error = stake_pool_accounts
-        .add_validator_to_pool(
-            &mut banks_client,
-            &payer,
-            &recent_blockhash,
-            &user_stake.stake_account,
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
             &user_pool_account.pubkey(),
+            RESERVE_AMOUNT,
         )
-        .await;
-    assert!(error.is_none());
+        .await
+        .unwrap();
 
-    (
-        banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        user,
-    )
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch + slots_per_epoch / 2)
+        .unwrap();
+    update(&mut context, &stake_pool_accounts, &validator_stake_account).await;
+
+    (context, stake_pool_accounts, validator_stake_account)
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        user,
-    ) = setup().await;
-
-    let tokens_to_burn = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
-    delegate_tokens(
-        &mut banks_client,
-        &payer,
-        &recent_blockhash,
-        &user_pool_account.pubkey(),
-        &user,
-        &stake_pool_accounts.withdraw_authority,
-        tokens_to_burn,
+async fn success_mid_epoch() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let stake_pool_before = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
     )
     .await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+    let reserve_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
 
-    let new_authority = Pubkey::new_unique();
     let error = stake_pool_accounts
         .remove_validator_from_pool(
-            &mut banks_client,
-            &payer,
-            &recent_blockhash,
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &new_authority,
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
         )
         .await;
     assert!(error.is_none());
 
-    // Check if all tokens were burned
-    let tokens_left = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
-    assert_eq!(tokens_left, 0);
+    // Stake is deactivating, still owned by the pool and still in the list
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    let validator_stake_state =
+        deserialize::<stake_program::StakeState>(&validator_stake.data).unwrap();
+    match validator_stake_state {
+        stake_program::StakeState::Stake(meta, stake) => {
+            assert_eq!(
+                meta.authorized.withdrawer,
+                stake_pool_accounts.withdraw_authority
+            );
+            assert_ne!(stake.delegation.deactivation_epoch, u64::MAX);
+        }
+        _ => panic!(),
+    }
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.status,
+        state::StakeStatus::ReadyForRemoval
+    );
+    assert_eq!(
+        validator_stake_record.active_stake_lamports,
+        validator_lamports
+    );
 
-    // Check if account was removed from the list of stake accounts
-    let validator_list = get_account(
-        &mut banks_client,
-        &stake_pool_accounts.validator_list.pubkey(),
+    // Nothing happens until the stake is deactivated
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    assert!(validator_list.contains(&validator_stake_account.vote.pubkey()));
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + 2 * slots_per_epoch)
+        .unwrap();
+    update(&mut context, &stake_pool_accounts, &validator_stake_account).await;
+
+    // The lamports are back in the reserve and the validator is gone
+    let validator_stake = context
+        .banks_client
+        .get_account(validator_stake_account.stake_account)
+        .await
+        .unwrap();
+    assert!(validator_stake.is_none());
+    let reserve = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
     )
     .await;
-    let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+    assert_eq!(reserve.lamports, reserve_lamports + validator_lamports);
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
     assert_eq!(
         validator_list,
         state::ValidatorList {
             account_type: state::AccountType::ValidatorList,
+            version: state::VALIDATOR_LIST_VERSION,
             max_validators: stake_pool_accounts.max_validators,
             validators: vec![]
         }
     );
 
-    // Check of stake account authority has changed
-    let stake = get_account(&mut banks_client, &user_stake.stake_account).await;
-    let stake_state = deserialize::<stake_program::StakeState>(&stake.data).unwrap();
-    match stake_state {
-        stake_program::StakeState::Stake(meta, _) => {
-            assert_eq!(&meta.authorized.staker, &new_authority);
-            assert_eq!(&meta.authorized.withdrawer, &new_authority);
-        }
-        _ => panic!(),
-    }
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply
+    );
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool_with_wrong_stake_program_id() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
+async fn fail_with_wrong_stake_program_id() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
 
     let wrong_stake_program = Pubkey::new_unique();
-
-    let new_authority = Pubkey::new_unique();
     let accounts = vec![
         AccountMeta::new(stake_pool_accounts.stake_pool.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.staker.pubkey(), true),
         AccountMeta::new_readonly(stake_pool_accounts.withdraw_authority, false),
-        AccountMeta::new_readonly(new_authority, false),
         AccountMeta::new(stake_pool_accounts.validator_list.pubkey(), false),
-        AccountMeta::new(user_stake.stake_account, false),
-        AccountMeta::new(user_pool_account.pubkey(), false),
-        AccountMeta::new(stake_pool_accounts.pool_mint.pubkey(), false),
+        AccountMeta::new(validator_stake_account.stake_account, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(wrong_stake_program, false),
     ];
     let instruction = Instruction {
@@ -195,313 +279,345 @@ async fn test_remove_validator_from_pool_with_wrong_stake_program_id() {
             .unwrap(),
     };
 
-    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
-    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
-    let transaction_error = banks_client
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
         .process_transaction(transaction)
         .await
         .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            error,
-        )) => {
-            assert_eq!(error, InstructionError::IncorrectProgramId);
-        }
-        _ => panic!("Wrong error occurs while try to remove validator stake address with wrong stake program ID"),
-    }
+    check_error(transaction_error, InstructionError::IncorrectProgramId);
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool_with_wrong_token_program_id() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
-
-    let wrong_token_program = Keypair::new();
-
-    let new_authority = Pubkey::new_unique();
-    let mut transaction = Transaction::new_with_payer(
+async fn fail_with_wrong_validator_list_account() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let wrong_validator_list = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
         &[instruction::remove_validator_from_pool(
             &id(),
             &stake_pool_accounts.stake_pool.pubkey(),
             &stake_pool_accounts.staker.pubkey(),
             &stake_pool_accounts.withdraw_authority,
-            &new_authority,
-            &stake_pool_accounts.validator_list.pubkey(),
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &stake_pool_accounts.pool_mint.pubkey(),
-            &wrong_token_program.pubkey(),
+            &wrong_validator_list.pubkey(),
+            &validator_stake_account.stake_account,
         )
         .unwrap()],
-        Some(&payer.pubkey()),
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
     );
-    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
-    let transaction_error = banks_client
+    let transaction_error = context
+        .banks_client
         .process_transaction(transaction)
         .await
         .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
-            assert_eq!(error, InstructionError::IncorrectProgramId);
-        }
-        _ => panic!("Wrong error occurs while try to remove validator stake address with wrong token program ID"),
-    }
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidValidatorStakeList as u32),
+    );
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool_with_wrong_pool_mint_account() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
-
-    let wrong_pool_mint = Keypair::new();
-
-    let new_authority = Pubkey::new_unique();
-    let mut transaction = Transaction::new_with_payer(
-        &[instruction::remove_validator_from_pool(
-            &id(),
-            &stake_pool_accounts.stake_pool.pubkey(),
-            &stake_pool_accounts.staker.pubkey(),
-            &stake_pool_accounts.withdraw_authority,
-            &new_authority,
-            &stake_pool_accounts.validator_list.pubkey(),
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &wrong_pool_mint.pubkey(),
-            &spl_token::id(),
+async fn fail_remove_twice() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let latest_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let transaction_error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &latest_blockhash,
+            &validator_stake_account.stake_account,
         )
-        .unwrap()],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
-    let transaction_error = banks_client
-        .process_transaction(transaction)
         .await
-        .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        )) => {
-            let program_error = error::StakePoolError::WrongPoolMint as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => panic!("Wrong error occurs while try to remove validator stake address with wrong pool mint account"),
-    }
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::ValidatorNotActive as u32),
+    );
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool_with_wrong_validator_list_account() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
+async fn fail_increase_after_remove() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
 
-    let wrong_validator_list = Keypair::new();
+    let error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+        )
+        .await;
+    assert!(error.is_none());
 
-    let new_authority = Pubkey::new_unique();
-    let mut transaction = Transaction::new_with_payer(
-        &[instruction::remove_validator_from_pool(
-            &id(),
-            &stake_pool_accounts.stake_pool.pubkey(),
-            &stake_pool_accounts.staker.pubkey(),
-            &stake_pool_accounts.withdraw_authority,
-            &new_authority,
-            &wrong_validator_list.pubkey(),
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &stake_pool_accounts.pool_mint.pubkey(),
-            &spl_token::id(),
+    let transaction_error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            RESERVE_AMOUNT / 2,
         )
-        .unwrap()],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
-    let transaction_error = banks_client
-        .process_transaction(transaction)
         .await
-        .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        )) => {
-            let program_error = error::StakePoolError::InvalidValidatorStakeList as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => panic!("Wrong error occurs while try to remove validator stake address with wrong validator stake list account"),
-    }
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::ValidatorNotActive as u32),
+    );
 }
 
 #[tokio::test]
-async fn test_remove_already_removed_validator_stake_account() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        user,
-    ) = setup().await;
-
-    let tokens_to_burn = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
-    delegate_tokens(
-        &mut banks_client,
-        &payer,
-        &recent_blockhash,
-        &user_pool_account.pubkey(),
-        &user,
-        &stake_pool_accounts.withdraw_authority,
-        tokens_to_burn,
-    )
-    .await;
+async fn fail_with_transient_stake_in_use() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
 
-    let new_authority = Pubkey::new_unique();
     let error = stake_pool_accounts
-        .remove_validator_from_pool(
-            &mut banks_client,
-            &payer,
-            &recent_blockhash,
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &new_authority,
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            RESERVE_AMOUNT / 2,
         )
         .await;
     assert!(error.is_none());
 
-    let latest_blockhash = banks_client.get_recent_blockhash().await.unwrap();
-
     let transaction_error = stake_pool_accounts
         .remove_validator_from_pool(
-            &mut banks_client,
-            &payer,
-            &latest_blockhash,
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &new_authority,
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
         )
         .await
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        )) => {
-            let program_error = error::StakePoolError::ValidatorNotFound as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => {
-            panic!("Wrong error occurs while try to remove already removed validator stake address")
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::TransientAccountInUse as u32),
+    );
+}
+
+#[tokio::test]
+async fn success_with_transient_stake_in_use() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+    let reserve_lamports = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+    let validator_lamports = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await
+    .lamports;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            RESERVE_AMOUNT / 2,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let mut remove_instruction = instruction::remove_validator_from_pool(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &validator_stake_account.stake_account,
+    )
+    .unwrap();
+    instruction::add_transient_stake_account(
+        &mut remove_instruction,
+        &validator_stake_account.transient_stake_account,
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[remove_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // Both stake accounts are deactivating
+    for stake_address in &[
+        validator_stake_account.stake_account,
+        validator_stake_account.transient_stake_account,
+    ] {
+        let stake = get_account(&mut context.banks_client, stake_address).await;
+        match deserialize::<stake_program::StakeState>(&stake.data).unwrap() {
+            stake_program::StakeState::Stake(_, stake) => {
+                assert_ne!(stake.delegation.deactivation_epoch, u64::MAX);
+            }
+            _ => panic!(),
         }
     }
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.status,
+        state::StakeStatus::DeactivatingTransient
+    );
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + 2 * slots_per_epoch)
+        .unwrap();
+    update(&mut context, &stake_pool_accounts, &validator_stake_account).await;
+
+    // Everything is back in the reserve and the validator is gone
+    for stake_address in &[
+        validator_stake_account.stake_account,
+        validator_stake_account.transient_stake_account,
+    ] {
+        let stake = context
+            .banks_client
+            .get_account(*stake_address)
+            .await
+            .unwrap();
+        assert!(stake.is_none());
+    }
+    let reserve = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve.lamports, reserve_lamports + validator_lamports);
+    let validator_list = get_validator_list(&mut context, &stake_pool_accounts).await;
+    assert!(validator_list.validators.is_empty());
 }
 
 #[tokio::test]
-async fn test_not_staker_try_to_remove_validator_from_pool() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
+async fn fail_with_wrong_transient_stake_account() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
 
-    let malicious = Keypair::new();
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            RESERVE_AMOUNT / 2,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let mut remove_instruction = instruction::remove_validator_from_pool(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &validator_stake_account.stake_account,
+    )
+    .unwrap();
+    instruction::add_transient_stake_account(&mut remove_instruction, &Pubkey::new_unique());
+    let transaction = Transaction::new_signed_with_payer(
+        &[remove_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::InvalidStakeAccountAddress as u32),
+    );
+}
 
-    let new_authority = Pubkey::new_unique();
-    let mut transaction = Transaction::new_with_payer(
+#[tokio::test]
+async fn fail_not_staker() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let malicious = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
         &[instruction::remove_validator_from_pool(
             &id(),
             &stake_pool_accounts.stake_pool.pubkey(),
             &malicious.pubkey(),
             &stake_pool_accounts.withdraw_authority,
-            &new_authority,
             &stake_pool_accounts.validator_list.pubkey(),
-            &user_stake.stake_account,
-            &user_pool_account.pubkey(),
-            &stake_pool_accounts.pool_mint.pubkey(),
-            &spl_token::id(),
+            &validator_stake_account.stake_account,
         )
         .unwrap()],
-        Some(&payer.pubkey()),
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &malicious],
+        context.last_blockhash,
     );
-    transaction.sign(&[&payer, &malicious], recent_blockhash);
-    let transaction_error = banks_client
+    let transaction_error = context
+        .banks_client
         .process_transaction(transaction)
         .await
         .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        )) => {
-            let program_error = error::StakePoolError::WrongStaker as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => {
-            panic!("Wrong error occurs while not an staker try to remove validator stake address")
-        }
-    }
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::WrongStaker as u32),
+    );
 }
 
 #[tokio::test]
-async fn test_not_staker_try_to_remove_validator_from_pool_without_signature() {
-    let (
-        mut banks_client,
-        payer,
-        recent_blockhash,
-        stake_pool_accounts,
-        user_stake,
-        user_pool_account,
-        _,
-    ) = setup().await;
-
-    let new_authority = Pubkey::new_unique();
+async fn fail_not_staker_without_signature() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
 
     let accounts = vec![
         AccountMeta::new(stake_pool_accounts.stake_pool.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.staker.pubkey(), false),
         AccountMeta::new_readonly(stake_pool_accounts.withdraw_authority, false),
-        AccountMeta::new_readonly(new_authority, false),
         AccountMeta::new(stake_pool_accounts.validator_list.pubkey(), false),
-        AccountMeta::new(user_stake.stake_account, false),
-        AccountMeta::new(user_pool_account.pubkey(), false),
-        AccountMeta::new(stake_pool_accounts.pool_mint.pubkey(), false),
+        AccountMeta::new(validator_stake_account.stake_account, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(stake_program::id(), false),
     ];
     let instruction = Instruction {
@@ -512,28 +628,50 @@ async fn test_not_staker_try_to_remove_validator_from_pool_without_signature() {
             .unwrap(),
     };
 
-    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
-    transaction.sign(&[&payer], recent_blockhash);
-    let transaction_error = banks_client
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
         .process_transaction(transaction)
         .await
         .err()
         .unwrap();
 
-    match transaction_error {
-        TransportError::TransactionError(TransactionError::InstructionError(
-            _,
-            InstructionError::Custom(error_index),
-        )) => {
-            let program_error = error::StakePoolError::SignatureMissing as u32;
-            assert_eq!(error_index, program_error);
-        }
-        _ => panic!("Wrong error occurs while malicious try to remove validator stake account without signing transaction"),
-    }
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::SignatureMissing as u32),
+    );
 }
 
 #[tokio::test]
-async fn test_remove_validator_from_pool_from_unupdated_stake_pool() {} // TODO
+async fn fail_from_unupdated_stake_pool() {
+    let (mut context, stake_pool_accounts, validator_stake_account) = setup().await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + 2 * slots_per_epoch)
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+        )
+        .await
+        .unwrap();
+
+    check_error(
+        transaction_error,
+        InstructionError::Custom(StakePoolError::StakeListAndPoolOutOfDate as u32),
+    );
+}
 
 #[tokio::test]
 async fn test_remove_validator_from_pool_with_uninitialized_validator_list_account() {} // TODO
@@ -0,0 +1,312 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{
+        clock::Clock, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    solana_vote_program::vote_state::{VoteInit, VoteState, VoteStateVersions},
+    spl_stake_pool::{error::StakePoolError, id, instruction, stake_program, state},
+};
+
+/// Creates an initialized vote account withdrawable by `authorized_withdrawer`
+fn vote_account(authorized_withdrawer: &Pubkey) -> Account {
+    let node_pubkey = Pubkey::new_unique();
+    let vote_state = VoteState::new(
+        &VoteInit {
+            node_pubkey,
+            authorized_voter: node_pubkey,
+            authorized_withdrawer: *authorized_withdrawer,
+            commission: 0,
+        },
+        &Clock::default(),
+    );
+    let mut data = vec![0; VoteState::size_of()];
+    bincode::serialize_into(
+        &mut data[..],
+        &VoteStateVersions::Current(Box::new(vote_state)),
+    )
+    .unwrap();
+    Account {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        owner: solana_vote_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Sets up a pool with one validator whose vote account is withdrawable by
+/// the returned keypair, updated once the validator stake is active
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    Keypair,
+) {
+    let stake_pool_accounts = StakePoolAccounts::new();
+    let validator_stake_account = ValidatorStakeAccount::new_with_target_authority(
+        &stake_pool_accounts.deposit_authority,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    let vote_withdrawer = Keypair::new();
+
+    let mut program_test = program_test();
+    program_test.add_account(
+        validator_stake_account.vote.pubkey(),
+        vote_account(&vote_withdrawer.pubkey()),
+    );
+    let mut context = program_test.start_with_context().await;
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    create_validator_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.staker,
+        &validator_stake_account.stake_account,
+        &validator_stake_account.vote.pubkey(),
+    )
+    .await;
+    for stake_authorize in &[
+        stake_program::StakeAuthorize::Staker,
+        stake_program::StakeAuthorize::Withdrawer,
+    ] {
+        authorize_stake_account(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &stake_pool_accounts.staker,
+            &validator_stake_account.target_authority,
+            *stake_authorize,
+        )
+        .await;
+    }
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &Keypair::new().pubkey(),
+    )
+    .await
+    .unwrap();
+    let error = stake_pool_accounts
+        .add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &user_pool_account.pubkey(),
+        )
+        .await;
+    assert!(error.is_none());
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    (
+        context,
+        stake_pool_accounts,
+        validator_stake_account,
+        vote_withdrawer,
+    )
+}
+
+fn check_error(transaction_error: TransportError, expected_error: InstructionError) {
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, validator_stake_account, vote_withdrawer) =
+        setup().await;
+
+    // the staker doesn't sign
+    let error = stake_pool_accounts
+        .remove_validator_by_vote_withdrawer(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.vote.pubkey(),
+            &vote_withdrawer,
+        )
+        .await;
+    assert!(error.is_none());
+
+    // same flow as a removal by the staker: the stake deactivates in the pool
+    let validator_stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    match deserialize::<stake_program::StakeState>(&validator_stake.data).unwrap() {
+        stake_program::StakeState::Stake(meta, stake) => {
+            assert_eq!(
+                meta.authorized.withdrawer,
+                stake_pool_accounts.withdraw_authority
+            );
+            assert_ne!(stake.delegation.deactivation_epoch, u64::MAX);
+        }
+        _ => panic!(),
+    }
+    let validator_list = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(
+        validator_stake_record.status,
+        state::StakeStatus::ReadyForRemoval
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_withdrawer() {
+    let (mut context, stake_pool_accounts, validator_stake_account, _) = setup().await;
+
+    let error = stake_pool_accounts
+        .remove_validator_by_vote_withdrawer(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.vote.pubkey(),
+            &Keypair::new(),
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongVoteWithdrawer as u32),
+    );
+
+    // not even the staker can remove a validator this way
+    let error = stake_pool_accounts
+        .remove_validator_by_vote_withdrawer(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.vote.pubkey(),
+            &stake_pool_accounts.staker,
+        )
+        .await
+        .unwrap();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::WrongVoteWithdrawer as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_vote_account() {
+    let (mut context, stake_pool_accounts, validator_stake_account, _) = setup().await;
+
+    // a vote account the signer does control, but of another validator
+    let other_vote = Keypair::new();
+    let other_withdrawer = Keypair::new();
+    create_vote(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &other_vote,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .remove_validator_by_vote_withdrawer(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &other_vote.pubkey(),
+            &other_withdrawer,
+        )
+        .await
+        .unwrap();
+    check_error(error, InstructionError::InvalidArgument);
+}
+
+#[tokio::test]
+async fn fail_without_signature() {
+    let (mut context, stake_pool_accounts, validator_stake_account, vote_withdrawer) =
+        setup().await;
+
+    let mut instruction = instruction::remove_validator_by_vote_withdrawer(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &vote_withdrawer.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &validator_stake_account.stake_account,
+        &validator_stake_account.vote.pubkey(),
+    )
+    .unwrap();
+    instruction.accounts[1].is_signer = false;
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+    transaction.sign(&[&context.payer], context.last_blockhash);
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::SignatureMissing as u32),
+    );
+}
@@ -17,9 +17,7 @@ use {
         transaction::{Transaction, TransactionError},
         transport::TransportError,
     },
-    spl_stake_pool::{
-        borsh::try_from_slice_unchecked, error, id, instruction, stake_program, state,
-    },
+    spl_stake_pool::{error, id, instruction, stake_program, state, MINIMUM_ACTIVE_STAKE},
     spl_token::error::TokenError,
 };
 
@@ -116,7 +114,7 @@ async fn test_stake_pool_withdraw() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     let validator_stake_item_before = validator_list
         .find(&validator_stake_account.vote.pubkey())
         .unwrap();
@@ -159,13 +157,13 @@ async fn test_stake_pool_withdraw() {
     )
     .await;
     let validator_list =
-        try_from_slice_unchecked::<state::ValidatorList>(validator_list.data.as_slice()).unwrap();
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
     let validator_stake_item = validator_list
         .find(&validator_stake_account.vote.pubkey())
         .unwrap();
     assert_eq!(
-        validator_stake_item.stake_lamports,
-        validator_stake_item_before.stake_lamports - tokens_to_burn
+        validator_stake_item.active_stake_lamports,
+        u64::from(validator_stake_item_before.active_stake_lamports) - tokens_to_burn
     );
 
     // Check tokens burned
@@ -181,7 +179,7 @@ async fn test_stake_pool_withdraw() {
         get_account(&mut banks_client, &validator_stake_account.stake_account).await;
     assert_eq!(
         validator_stake_account.lamports,
-        validator_stake_item.stake_lamports
+        u64::from(validator_stake_item.active_stake_lamports)
     );
 
     // Check user recipient stake account balance
@@ -321,6 +319,7 @@ async fn test_stake_pool_withdraw_with_wrong_token_program_id() {
             &user_stake_recipient.pubkey(),
             &new_authority,
             &deposit_info.user_pool_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
             &stake_pool_accounts.pool_mint.pubkey(),
             &wrong_token_program.pubkey(),
             tokens_to_burn,
@@ -726,3 +725,248 @@ async fn test_stake_pool_withdraw_with_low_delegation() {
         ),
     }
 }
+
+#[tokio::test]
+async fn test_stake_pool_withdraw_with_out_of_dated_pool_balances() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let deposit_info = simple_deposit(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+
+    let tokens_to_burn = deposit_info.pool_tokens / 4;
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &deposit_info.user_pool_account,
+        &deposit_info.user,
+        &stake_pool_accounts.withdraw_authority,
+        tokens_to_burn,
+    )
+    .await;
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+
+    // A new epoch starts, and the pool still holds last epoch's balances
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+
+    let new_authority = Pubkey::new_unique();
+    let transaction_error = stake_pool_accounts
+        .withdraw_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_stake_recipient.pubkey(),
+            &deposit_info.user_pool_account,
+            &validator_stake_account.stake_account,
+            &new_authority,
+            tokens_to_burn,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::StakeListAndPoolOutOfDate as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to withdraw from an out of date pool"),
+    }
+}
+
+#[tokio::test]
+async fn success_withdraw_from_reserve() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let deposit_lamports = 2 * MINIMUM_ACTIVE_STAKE;
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        deposit_lamports,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            deposit_lamports,
+        )
+        .await
+        .unwrap();
+
+    let tokens_to_burn =
+        get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await / 2;
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        tokens_to_burn,
+    )
+    .await;
+    let user_stake_recipient = Keypair::new();
+    let initial_stake_lamports = create_blank_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+    let reserve_lamports_before = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+
+    // No validator holds any stake, so the reserve backs the withdrawal
+    stake_pool_accounts
+        .withdraw_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake_recipient.pubkey(),
+            &user_pool_account.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &Pubkey::new_unique(),
+            tokens_to_burn,
+        )
+        .await
+        .unwrap();
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    let withdrawn_lamports =
+        stake_pool_before.total_stake_lamports - stake_pool.total_stake_lamports;
+    assert!(withdrawn_lamports > 0);
+
+    let reserve_lamports = get_account(
+        &mut banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await
+    .lamports;
+    assert_eq!(
+        reserve_lamports,
+        reserve_lamports_before - withdrawn_lamports
+    );
+
+    let user_stake_recipient_account =
+        get_account(&mut banks_client, &user_stake_recipient.pubkey()).await;
+    assert_eq!(
+        user_stake_recipient_account.lamports,
+        initial_stake_lamports + withdrawn_lamports
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_from_reserve_with_validator_stake() {
+    let (
+        mut banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        _validator_stake_account,
+        deposit_info,
+        tokens_to_burn,
+    ) = setup().await;
+
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+
+    let transaction_error = stake_pool_accounts
+        .withdraw_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_stake_recipient.pubkey(),
+            &deposit_info.user_pool_account,
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &Pubkey::new_unique(),
+            tokens_to_burn,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::StakeLamportsNotEqualToMinimum as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while withdrawing from the reserve"),
+    }
+}
@@ -0,0 +1,292 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, stake_program, state, MINIMUM_ACTIVE_STAKE},
+};
+
+const DEPOSIT_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const TRANSIENT_AMOUNT: u64 = 3 * LAMPORTS_PER_SOL;
+
+struct Setup {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    stake_pool_accounts: StakePoolAccounts,
+    validator_stake_account: ValidatorStakeAccount,
+    user_pool_account: Pubkey,
+    minimum_lamports: u64,
+}
+
+/// Sets up a pool with one validator holding `TEST_STAKE_AMOUNT` above its
+/// minimum, `TRANSIENT_AMOUNT` activating in its transient stake account,
+/// and the rest of a user's SOL deposit in the reserve
+async fn setup() -> Setup {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    let pool_tokens = get_token_balance(&mut banks_client, &user_pool_account.pubkey()).await;
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            TRANSIENT_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let minimum_lamports = rent.minimum_balance(std::mem::size_of::<stake_program::StakeState>())
+        + MINIMUM_ACTIVE_STAKE;
+
+    Setup {
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        validator_stake_account,
+        user_pool_account: user_pool_account.pubkey(),
+        minimum_lamports,
+    }
+}
+
+impl Setup {
+    /// Withdraws `lamports` from `stake_account` into a new stake account
+    async fn withdraw(
+        &mut self,
+        stake_account: &Pubkey,
+        lamports: u64,
+    ) -> Result<(), TransportError> {
+        let stake_pool = get_account(
+            &mut self.banks_client,
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+        )
+        .await;
+        let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+        let pool_tokens = stake_pool.calc_pool_tokens_for_withdraw(lamports).unwrap();
+
+        let user_stake_recipient = Keypair::new();
+        create_blank_stake_account(
+            &mut self.banks_client,
+            &self.payer,
+            &self.recent_blockhash,
+            &user_stake_recipient,
+        )
+        .await;
+        self.stake_pool_accounts
+            .withdraw_stake(
+                &mut self.banks_client,
+                &self.payer,
+                &self.recent_blockhash,
+                &user_stake_recipient.pubkey(),
+                &self.user_pool_account,
+                stake_account,
+                &Pubkey::new_unique(),
+                pool_tokens,
+            )
+            .await
+    }
+
+    async fn validator_list_item(&mut self) -> state::ValidatorStakeInfo {
+        let validator_list = get_account(
+            &mut self.banks_client,
+            &self.stake_pool_accounts.validator_list.pubkey(),
+        )
+        .await;
+        let validator_list =
+            state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+        *validator_list
+            .find(&self.validator_stake_account.vote.pubkey())
+            .unwrap()
+    }
+}
+
+fn check_error(transport_error: TransportError, expected_error: InstructionError) {
+    match transport_error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(error, expected_error);
+        }
+        _ => panic!("Wrong error, expected {:?}", expected_error),
+    }
+}
+
+#[tokio::test]
+async fn success_drain_pool() {
+    let mut setup = setup().await;
+    let validator_stake = setup.validator_stake_account.stake_account;
+    let transient_stake = setup.validator_stake_account.transient_stake_account;
+    let reserve_stake = setup.stake_pool_accounts.reserve_stake.pubkey();
+
+    // the validator goes first, down to its minimum
+    setup
+        .withdraw(&validator_stake, TEST_STAKE_AMOUNT)
+        .await
+        .unwrap();
+    let item = setup.validator_list_item().await;
+    assert_eq!(item.active_stake_lamports, setup.minimum_lamports);
+    assert_eq!(
+        get_account(&mut setup.banks_client, &validator_stake)
+            .await
+            .lamports,
+        setup.minimum_lamports
+    );
+
+    // then all of the transient stake
+    setup
+        .withdraw(&transient_stake, TRANSIENT_AMOUNT)
+        .await
+        .unwrap();
+    let item = setup.validator_list_item().await;
+    assert_eq!(item.transient_stake_lamports, 0);
+    assert!(setup
+        .banks_client
+        .get_account(transient_stake)
+        .await
+        .unwrap()
+        .is_none());
+
+    // and the reserve backs the rest of the user's tokens
+    let reserve_lamports = DEPOSIT_AMOUNT - TRANSIENT_AMOUNT - TEST_STAKE_AMOUNT;
+    setup
+        .withdraw(&reserve_stake, reserve_lamports)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut setup.banks_client, &setup.user_pool_account).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_validator_below_minimum() {
+    let mut setup = setup().await;
+    let validator_stake = setup.validator_stake_account.stake_account;
+
+    let error = setup
+        .withdraw(&validator_stake, TEST_STAKE_AMOUNT + 1)
+        .await
+        .unwrap_err();
+    check_error(error, InstructionError::InsufficientFunds);
+}
+
+#[tokio::test]
+async fn fail_withdraw_transient_before_validator() {
+    let mut setup = setup().await;
+    let transient_stake = setup.validator_stake_account.transient_stake_account;
+
+    let error = setup
+        .withdraw(&transient_stake, TRANSIENT_AMOUNT)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakeLamportsNotEqualToMinimum as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_reserve_before_transient() {
+    let mut setup = setup().await;
+    let validator_stake = setup.validator_stake_account.stake_account;
+    let reserve_stake = setup.stake_pool_accounts.reserve_stake.pubkey();
+
+    setup
+        .withdraw(&validator_stake, TEST_STAKE_AMOUNT)
+        .await
+        .unwrap();
+    let error = setup
+        .withdraw(&reserve_stake, LAMPORTS_PER_SOL)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakeLamportsNotEqualToMinimum as u32),
+    );
+}
+
+#[tokio::test]
+async fn fail_withdraw_transient_below_minimum() {
+    let mut setup = setup().await;
+    let validator_stake = setup.validator_stake_account.stake_account;
+    let transient_stake = setup.validator_stake_account.transient_stake_account;
+
+    setup
+        .withdraw(&validator_stake, TEST_STAKE_AMOUNT)
+        .await
+        .unwrap();
+    let error = setup
+        .withdraw(&transient_stake, TRANSIENT_AMOUNT - MINIMUM_ACTIVE_STAKE)
+        .await
+        .unwrap_err();
+    check_error(
+        error,
+        InstructionError::Custom(StakePoolError::StakeSplitBelowMinimum as u32),
+    );
+}
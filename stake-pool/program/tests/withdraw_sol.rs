@@ -0,0 +1,346 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    borsh::BorshDeserialize,
+    helpers::*,
+    solana_program::{
+        hash::Hash, instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+        transport::TransportError,
+    },
+    spl_stake_pool::{error, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    Hash,
+    StakePoolAccounts,
+    Keypair,
+    Pubkey,
+) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(&mut banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+    transfer(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    stake_pool_accounts
+        .deposit_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        stake_pool_accounts,
+        user,
+        user_pool_account.pubkey(),
+    )
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, user_pool_account) =
+        setup().await;
+
+    let recipient = Keypair::new();
+    let tokens_to_burn = DEPOSIT_AMOUNT / 4;
+
+    let stake_pool_before =
+        get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool_before =
+        state::StakePool::try_from_slice(&stake_pool_before.data.as_slice()).unwrap();
+    let expected_lamports = stake_pool_before
+        .calc_lamports_withdraw_amount_floor(tokens_to_burn)
+        .unwrap();
+
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &recipient.pubkey(),
+            tokens_to_burn,
+        )
+        .await
+        .unwrap();
+
+    // Recipient received the lamports
+    let recipient_account = get_account(&mut banks_client, &recipient.pubkey()).await;
+    assert_eq!(recipient_account.lamports, expected_lamports);
+
+    // Pool tokens were burned
+    let user_token_balance = get_token_balance(&mut banks_client, &user_pool_account).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT - tokens_to_burn);
+
+    let stake_pool = get_account(&mut banks_client, &stake_pool_accounts.stake_pool.pubkey()).await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.total_stake_lamports,
+        stake_pool_before.total_stake_lamports - expected_lamports
+    );
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        stake_pool_before.pool_token_supply - tokens_to_burn
+    );
+}
+
+#[tokio::test]
+async fn success_with_fee() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+
+    let withdrawal_fee = instruction::Fee {
+        numerator: 1,
+        denominator: 1000,
+    };
+    stake_pool_accounts
+        .set_fee(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            instruction::FeeType::Withdrawal(withdrawal_fee),
+        )
+        .await
+        .unwrap();
+
+    // The withdrawal fee only applies from the next epoch
+    context.warp_to_slot(50_000).unwrap();
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT * 2,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    let recipient = Keypair::new();
+    let tokens_to_withdraw = DEPOSIT_AMOUNT / 2;
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user_pool_account.pubkey(),
+            &recipient.pubkey(),
+            tokens_to_withdraw,
+        )
+        .await
+        .unwrap();
+
+    // Fee goes to the manager, the rest is burned for lamports at 1:1
+    let fee = withdrawal_fee.apply(tokens_to_withdraw).unwrap();
+    assert!(fee > 0);
+    let manager_token_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(manager_token_balance, fee);
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &user_pool_account.pubkey()).await;
+    assert_eq!(user_token_balance, DEPOSIT_AMOUNT - tokens_to_withdraw);
+    let recipient_account = get_account(&mut context.banks_client, &recipient.pubkey()).await;
+    assert_eq!(recipient_account.lamports, tokens_to_withdraw - fee);
+
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = state::StakePool::try_from_slice(&stake_pool.data.as_slice()).unwrap();
+    assert_eq!(
+        stake_pool.pool_token_supply,
+        DEPOSIT_AMOUNT - tokens_to_withdraw + fee
+    );
+}
+
+#[tokio::test]
+async fn fail_with_too_large_withdrawal() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts, _user, user_pool_account) =
+        setup().await;
+
+    // Deposit some stake, which is not held in the reserve
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    let deposit_info = simple_deposit(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &validator_stake_account,
+    )
+    .await;
+    delegate_tokens(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &deposit_info.user_pool_account,
+        &deposit_info.user,
+        &stake_pool_accounts.withdraw_authority,
+        deposit_info.pool_tokens,
+    )
+    .await;
+
+    // Drain most of the reserve
+    const LEFT_IN_RESERVE: u64 = 1_000;
+    let recipient = Keypair::new();
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &recipient.pubkey(),
+            DEPOSIT_AMOUNT - LEFT_IN_RESERVE,
+        )
+        .await
+        .unwrap();
+
+    let transaction_error = stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &deposit_info.user_pool_account,
+            &recipient.pubkey(),
+            deposit_info.pool_tokens,
+        )
+        .await
+        .err()
+        .unwrap();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::SolWithdrawalTooLarge as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while try to withdraw more sol than the reserve holds"),
+    }
+
+    // Withdrawing what is left in the reserve still works
+    stake_pool_accounts
+        .withdraw_sol(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &user_pool_account,
+            &recipient.pubkey(),
+            LEFT_IN_RESERVE,
+        )
+        .await
+        .unwrap();
+    let recipient_account = get_account(&mut banks_client, &recipient.pubkey()).await;
+    assert_eq!(recipient_account.lamports, DEPOSIT_AMOUNT);
+}
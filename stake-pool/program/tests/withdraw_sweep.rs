@@ -0,0 +1,330 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        instruction::InstructionError, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error::StakePoolError, id, instruction, state},
+};
+
+const DEPOSIT_AMOUNT: u64 = 10 * LAMPORTS_PER_SOL;
+const INCREASE_AMOUNT: u64 = 4 * LAMPORTS_PER_SOL;
+const DECREASE_AMOUNT: u64 = 2 * LAMPORTS_PER_SOL;
+const WITHDRAW_AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+struct Setup {
+    context: ProgramTestContext,
+    stake_pool_accounts: StakePoolAccounts,
+    validator_stake_account: ValidatorStakeAccount,
+    user_pool_account: Pubkey,
+}
+
+async fn update(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    validator_stake_account: &ValidatorStakeAccount,
+    no_merge: bool,
+) {
+    let error = stake_pool_accounts
+        .update_validator_list_balance_chunk(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &[validator_stake_account.vote.pubkey()],
+            0,
+            no_merge,
+        )
+        .await;
+    assert!(error.is_none());
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none());
+}
+
+/// Sets up a pool whose single validator has `DECREASE_AMOUNT` fully
+/// deactivated in its transient stake account, left there by an update that
+/// did not merge it
+async fn setup() -> Setup {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+
+    let user = Keypair::new();
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user.pubkey(),
+        DEPOSIT_AMOUNT + LAMPORTS_PER_SOL,
+    )
+    .await;
+    let user_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user.pubkey(),
+    )
+    .await
+    .unwrap();
+    stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &user_pool_account.pubkey(),
+            DEPOSIT_AMOUNT,
+        )
+        .await
+        .unwrap();
+    let pool_tokens =
+        get_token_balance(&mut context.banks_client, &user_pool_account.pubkey()).await;
+    delegate_tokens(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_pool_account.pubkey(),
+        &user,
+        &stake_pool_accounts.withdraw_authority,
+        pool_tokens,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .increase_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.transient_stake_account,
+            &validator_stake_account.vote.pubkey(),
+            INCREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    update(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        false,
+    )
+    .await;
+
+    let error = stake_pool_accounts
+        .decrease_validator_stake(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+            DECREASE_AMOUNT,
+        )
+        .await;
+    assert!(error.is_none());
+
+    context
+        .warp_to_slot(first_normal_slot + 2 * slots_per_epoch)
+        .unwrap();
+    update(
+        &mut context,
+        &stake_pool_accounts,
+        &validator_stake_account,
+        true,
+    )
+    .await;
+
+    Setup {
+        context,
+        stake_pool_accounts,
+        validator_stake_account,
+        user_pool_account: user_pool_account.pubkey(),
+    }
+}
+
+impl Setup {
+    /// Withdraws `WITHDRAW_AMOUNT` from the validator stake account, sweeping
+    /// the transient stake account of `vote_account` along the way
+    async fn withdraw_with_sweep(&mut self, vote_account: &Pubkey) -> Result<(), TransportError> {
+        let stake_pool = get_account(
+            &mut self.context.banks_client,
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+        )
+        .await;
+        let stake_pool = state::StakePool::deserialize_checked(stake_pool.data.as_slice()).unwrap();
+        let pool_tokens = stake_pool
+            .calc_pool_tokens_for_withdraw(WITHDRAW_AMOUNT)
+            .unwrap();
+
+        let user_stake_recipient = Keypair::new();
+        create_blank_stake_account(
+            &mut self.context.banks_client,
+            &self.context.payer,
+            &self.context.last_blockhash,
+            &user_stake_recipient,
+        )
+        .await;
+        let mut withdraw_instruction = instruction::withdraw(
+            &id(),
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+            &self.stake_pool_accounts.validator_list.pubkey(),
+            &self.stake_pool_accounts.withdraw_authority,
+            &self.validator_stake_account.stake_account,
+            &user_stake_recipient.pubkey(),
+            &Pubkey::new_unique(),
+            &self.user_pool_account,
+            &self.stake_pool_accounts.pool_fee_account.pubkey(),
+            &self.stake_pool_accounts.pool_mint.pubkey(),
+            &spl_token::id(),
+            pool_tokens,
+        )
+        .unwrap();
+        instruction::add_withdraw_sweep_accounts(
+            &mut withdraw_instruction,
+            &id(),
+            &self.stake_pool_accounts.stake_pool.pubkey(),
+            &self.stake_pool_accounts.reserve_stake.pubkey(),
+            vote_account,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw_instruction],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let mut setup = setup().await;
+    let transient_stake = get_account(
+        &mut setup.context.banks_client,
+        &setup.validator_stake_account.transient_stake_account,
+    )
+    .await;
+    let reserve_stake_before = get_account(
+        &mut setup.context.banks_client,
+        &setup.stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+
+    let vote_account = setup.validator_stake_account.vote.pubkey();
+    setup.withdraw_with_sweep(&vote_account).await.unwrap();
+
+    // The transient stake is back in the reserve
+    let transient_stake_after = setup
+        .context
+        .banks_client
+        .get_account(setup.validator_stake_account.transient_stake_account)
+        .await
+        .unwrap();
+    assert!(transient_stake_after.is_none());
+    let reserve_stake = get_account(
+        &mut setup.context.banks_client,
+        &setup.stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(
+        reserve_stake.lamports,
+        reserve_stake_before.lamports + transient_stake.lamports
+    );
+
+    let validator_list = get_account(
+        &mut setup.context.banks_client,
+        &setup.stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let validator_list =
+        state::ValidatorList::deserialize_checked(validator_list.data.as_slice()).unwrap();
+    let validator_stake_record = validator_list.find(&vote_account).unwrap();
+    assert_eq!(
+        u64::from(validator_stake_record.transient_stake_lamports),
+        0
+    );
+}
+
+#[tokio::test]
+async fn success_nothing_to_sweep() {
+    let mut setup = setup().await;
+    let reserve_stake_before = get_account(
+        &mut setup.context.banks_client,
+        &setup.stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+
+    // A validator without transient stake leaves the withdrawal as it was
+    setup
+        .withdraw_with_sweep(&Pubkey::new_unique())
+        .await
+        .unwrap();
+    let reserve_stake = get_account(
+        &mut setup.context.banks_client,
+        &setup.stake_pool_accounts.reserve_stake.pubkey(),
+    )
+    .await;
+    assert_eq!(reserve_stake.lamports, reserve_stake_before.lamports);
+    let transient_stake = setup
+        .context
+        .banks_client
+        .get_account(setup.validator_stake_account.transient_stake_account)
+        .await
+        .unwrap();
+    assert!(transient_stake.is_some());
+}
+
+#[tokio::test]
+async fn fail_wrong_reserve() {
+    let mut setup = setup().await;
+    setup.stake_pool_accounts.reserve_stake = Keypair::new();
+    let vote_account = setup.validator_stake_account.vote.pubkey();
+    let error = setup.withdraw_with_sweep(&vote_account).await.unwrap_err();
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(_, error)) => {
+            assert_eq!(
+                error,
+                InstructionError::Custom(StakePoolError::InvalidReserveStakeAccount as u32)
+            );
+        }
+        _ => panic!("Wrong error"),
+    }
+}
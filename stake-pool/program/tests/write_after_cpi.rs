@@ -0,0 +1,97 @@
+//! Lint-style check over the processor source: every function must write
+//! account data only after its last cross-program invocation, so that a
+//! failing CPI can never leave pool state half-updated.
+//!
+//! A CPI is a direct `invoke` / `invoke_signed`, or a call to a processor
+//! function that makes one.  A write is a `serialize` into account data or
+//! any `borrow_mut()` of an account's data or lamports.
+
+const PROCESSOR: &str = include_str!("../src/processor.rs");
+
+/// Splits the processor's `impl` into its functions, as (name, lines)
+fn functions(source: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut functions: Vec<(&str, Vec<&str>)> = vec![];
+    for line in source.lines() {
+        let signature = line
+            .strip_prefix("    fn ")
+            .or_else(|| line.strip_prefix("    pub fn "));
+        if let Some(signature) = signature {
+            let name_end = signature
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or_else(|| signature.len());
+            functions.push((&signature[..name_end], vec![]));
+        } else if let Some((_, lines)) = functions.last_mut() {
+            lines.push(line);
+        }
+    }
+    functions
+}
+
+fn is_code(line: &str) -> bool {
+    !line.trim_start().starts_with("//")
+}
+
+fn invokes_directly(line: &str) -> bool {
+    is_code(line) && (line.contains("invoke(") || line.contains("invoke_signed("))
+}
+
+fn writes_account(line: &str) -> bool {
+    is_code(line) && (line.contains(".serialize(&mut") || line.contains("borrow_mut()"))
+}
+
+/// Names of the functions that make a CPI, directly or through another
+/// processor function
+fn cpi_functions<'a>(functions: &[(&'a str, Vec<&str>)]) -> Vec<&'a str> {
+    let mut cpi_functions: Vec<&str> = functions
+        .iter()
+        .filter(|(_, lines)| lines.iter().any(|line| invokes_directly(line)))
+        .map(|(name, _)| *name)
+        .collect();
+    loop {
+        let callers: Vec<&str> = functions
+            .iter()
+            .filter(|(name, lines)| {
+                !cpi_functions.contains(name)
+                    && lines.iter().any(|line| calls_any(line, &cpi_functions))
+            })
+            .map(|(name, _)| *name)
+            .collect();
+        if callers.is_empty() {
+            return cpi_functions;
+        }
+        cpi_functions.extend(callers);
+    }
+}
+
+fn calls_any(line: &str, cpi_functions: &[&str]) -> bool {
+    is_code(line)
+        && cpi_functions
+            .iter()
+            .any(|name| line.contains(&format!("Self::{}(", name)))
+}
+
+#[test]
+fn processor_writes_after_last_cpi() {
+    let functions = functions(PROCESSOR);
+    assert!(functions.len() > 50, "failed to parse the processor");
+    let cpi_functions = cpi_functions(&functions);
+    assert!(cpi_functions.contains(&"token_mint_to"));
+    assert!(cpi_functions.contains(&"process_deposit_sol"));
+
+    let mut violations = vec![];
+    for (name, lines) in &functions {
+        let last_cpi = lines
+            .iter()
+            .rposition(|line| invokes_directly(line) || calls_any(line, &cpi_functions));
+        if let Some(last_cpi) = last_cpi {
+            for line in lines[..last_cpi].iter().filter(|line| writes_account(line)) {
+                violations.push(format!("{}: {}", name, line.trim()));
+            }
+        }
+    }
+    assert!(
+        violations.is_empty(),
+        "account data written before a later CPI:\n{}",
+        violations.join("\n")
+    );
+}
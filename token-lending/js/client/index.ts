@@ -30,7 +30,9 @@ export const LendingMarketLayout: typeof BufferLayout.Structure = BufferLayout.s
     Layout.publicKey("owner"),
     Layout.publicKey("quoteTokenMint"),
     Layout.publicKey("tokenProgramId"),
-    BufferLayout.blob(62, "padding"),
+    Layout.uint64("liquidationAuctionSlots"),
+    Layout.publicKey("riskAuthority"),
+    BufferLayout.blob(22, "padding"),
   ]
 );
 
@@ -136,6 +136,20 @@ pub enum LendingError {
     /// Obligation collateral cannot be withdrawn below required amount
     #[error("Obligation collateral cannot be withdrawn below required amount")]
     ObligationCollateralWithdrawBelowRequired,
+    /// Token set authority failed
+    #[error("Token set authority failed")]
+    TokenSetAuthorityFailed,
+    /// Non-fungible obligation used without holding its obligation token
+    #[error("User transfer authority must hold the obligation token")]
+    ObligationTokenHolderRequired,
+
+    // 40
+    /// Expected the market owner or its risk authority
+    #[error("Signer is neither the market owner nor its risk authority")]
+    InvalidRiskAuthority,
+    /// Bad debt can only be written off once all collateral is liquidated
+    #[error("Obligation still has collateral to liquidate")]
+    ObligationHasCollateral,
 }
 
 impl From<LendingError> for ProgramError {
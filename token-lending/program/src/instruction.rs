@@ -2,7 +2,7 @@
 
 use crate::{
     error::LendingError,
-    state::{ReserveConfig, ReserveFees},
+    state::{ReserveConfig, ReserveFees, ReservePriceMode},
 };
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::{FromPrimitive, ToPrimitive};
@@ -145,12 +145,14 @@ pub enum LendingInstruction {
     ///   6. `[writable]` Borrow reserve liquidity supply SPL Token account
     ///   7. `[writable]` Obligation
     ///   8. `[writable]` Obligation token mint
-    ///   9. `[writable]` Obligation token output
+    ///   9. `[writable]` Obligation token output. For a non-fungible obligation, the account
+    ///                     holding the obligation token, owned or delegated to $authority.
     ///   10 `[]` Lending market account.
     ///   11 `[]` Derived lending market authority.
     ///   12 `[signer]` User transfer authority ($authority).
     ///   13 `[]` Dex market
-    ///   14 `[]` Dex market order book side
+    ///   14 `[]` Dex market order book side, not read when the non-quote
+    ///                     reserves are in stable price mode
     ///   15 `[]` Temporary memory
     ///   16 `[]` Clock sysvar
     ///   17 '[]` Token program id
@@ -178,7 +180,9 @@ pub enum LendingInstruction {
     ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account
     ///   6. `[writable]` Obligation - initialized
     ///   7. `[writable]` Obligation token mint
-    ///   8. `[writable]` Obligation token input, $authority can transfer calculated amount
+    ///   8. `[writable]` Obligation token input, $authority can transfer calculated amount.
+    ///                     For a non-fungible obligation, the account holding the obligation
+    ///                     token, owned or delegated to $authority.
     ///   9. `[]` Lending market account.
     ///   10 `[]` Derived lending market authority.
     ///   11 `[signer]` User transfer authority ($authority).
@@ -192,6 +196,11 @@ pub enum LendingInstruction {
     // 7
     /// Purchase collateral tokens at a discount rate if the chosen obligation is unhealthy.
     ///
+    /// If the lending market has a liquidation auction configured, the first call on an
+    /// unhealthy obligation only starts the auction and the discount ramps up from zero
+    /// over the configured number of slots. A call on a healthy obligation with a running
+    /// auction ends the auction. Neither of these calls transfers any tokens.
+    ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Source liquidity token account, minted by repay reserve liquidity mint
@@ -206,7 +215,8 @@ pub enum LendingInstruction {
     ///   8. `[]` Derived lending market authority.
     ///   9. `[signer]` User transfer authority ($authority).
     ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
+    ///   11 `[]` Dex market order book side, not read when the non-quote
+    ///                     reserves are in stable price mode
     ///   12 `[]` Temporary memory
     ///   13 `[]` Clock sysvar
     ///   14 `[]` Token program id
@@ -236,7 +246,7 @@ pub enum LendingInstruction {
     ///   2. `[]` Deposit reserve account.
     ///   3. `[writable]` Obligation
     ///   4. `[writable]` Obligation token mint
-    ///   5. `[writable]` Obligation token output
+    ///   5. `[writable]` Obligation token output, not minted to for a non-fungible obligation
     ///   6. `[]` Lending market account.
     ///   7. `[]` Derived lending market authority.
     ///   8. `[signer]` User transfer authority ($authority).
@@ -258,12 +268,14 @@ pub enum LendingInstruction {
     ///   3. `[]` Borrow reserve account.
     ///   4. `[writable]` Obligation
     ///   5. `[writable]` Obligation token mint
-    ///   6. `[writable]` Obligation token input
+    ///   6. `[writable]` Obligation token input. For a non-fungible obligation, the account
+    ///                     holding the obligation token, owned or delegated to $authority.
     ///   7. `[]` Lending market account.
     ///   8. `[]` Derived lending market authority.
     ///   9. `[signer]` User transfer authority ($authority).
     ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
+    ///   11 `[]` Dex market order book side, not read when the non-quote
+    ///                     reserves are in stable price mode
     ///   12 `[]` Temporary memory
     ///   13 `[]` Clock sysvar
     ///   14 '[]` Token program id
@@ -283,6 +295,71 @@ pub enum LendingInstruction {
         /// The new owner
         new_owner: Pubkey,
     },
+
+    // 12
+    /// Sets the liquidation auction length of a lending market.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The lending market to configure.
+    ///   1. `[signer]` The lending market owner or risk authority.
+    SetLendingMarketLiquidationAuction {
+        /// Number of slots over which the liquidation bonus ramps up, zero to
+        /// always pay the full liquidation bonus
+        auction_slots: u64,
+    },
+
+    // 13
+    /// Sets how a reserve's liquidity is priced. Reserves pegged 1:1 to the
+    /// quote currency can skip reading their dex market in `Stable` mode, and
+    /// be switched back to `Oracle` mode if the asset depegs.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account, must have a dex market.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` The lending market owner or risk authority.
+    SetReservePriceMode {
+        /// The new price mode
+        price_mode: ReservePriceMode,
+    },
+
+    // 14
+    /// Initializes a new loan obligation owned by a single, non-fungible obligation token.
+    /// The obligation token mint is created with no decimals and one token is minted to the
+    /// output account before the mint authority is removed. Whoever holds the token can
+    /// borrow against, repay and withdraw collateral from the obligation, so transferring the
+    /// token transfers the whole position.
+    ///
+    /// Accounts expected by this instruction are the same as `InitObligation`.
+    InitObligationNft,
+
+    // 15
+    /// Sets the risk authority of a lending market, which can change the
+    /// liquidation auction and reserve price modes without the owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The lending market to change the risk authority of.
+    ///   1. `[signer]` The lending market owner.
+    SetRiskAuthority {
+        /// The new risk authority
+        new_risk_authority: Pubkey,
+    },
+
+    // 16
+    /// Writes off the remaining debt of an obligation whose collateral has been fully
+    /// liquidated. The debt is removed from the borrow reserve's total borrows, so the
+    /// loss is shared by all collateral token holders through a lower exchange rate.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[writable]` Borrow reserve account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` The lending market owner or risk authority.
+    ///   4. `[]` Clock sysvar
+    WriteOffBadDebt,
 }
 
 impl LendingInstruction {
@@ -364,6 +441,22 @@ impl LendingInstruction {
                 let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
                 Self::SetLendingMarketOwner { new_owner }
             }
+            12 => {
+                let (auction_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetLendingMarketLiquidationAuction { auction_slots }
+            }
+            13 => {
+                let (price_mode, _rest) = Self::unpack_u8(rest)?;
+                let price_mode = ReservePriceMode::from_u8(price_mode)
+                    .ok_or(LendingError::InstructionUnpackError)?;
+                Self::SetReservePriceMode { price_mode }
+            }
+            14 => Self::InitObligationNft,
+            15 => {
+                let (new_risk_authority, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetRiskAuthority { new_risk_authority }
+            }
+            16 => Self::WriteOffBadDebt,
             _ => return Err(LendingError::InstructionUnpackError.into()),
         })
     }
@@ -486,6 +579,24 @@ impl LendingInstruction {
                 buf.push(11);
                 buf.extend_from_slice(new_owner.as_ref());
             }
+            Self::SetLendingMarketLiquidationAuction { auction_slots } => {
+                buf.push(12);
+                buf.extend_from_slice(&auction_slots.to_le_bytes());
+            }
+            Self::SetReservePriceMode { price_mode } => {
+                buf.push(13);
+                buf.extend_from_slice(&price_mode.to_u8().unwrap().to_le_bytes());
+            }
+            Self::InitObligationNft => {
+                buf.push(14);
+            }
+            Self::SetRiskAuthority { new_risk_authority } => {
+                buf.push(15);
+                buf.extend_from_slice(new_risk_authority.as_ref());
+            }
+            Self::WriteOffBadDebt => {
+                buf.push(16);
+            }
         }
         buf
     }
@@ -914,3 +1025,106 @@ pub fn set_lending_market_owner(
         data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
     }
 }
+
+/// Creates a 'SetLendingMarketLiquidationAuction' instruction, signed by the
+/// lending market owner or risk authority.
+pub fn set_lending_market_liquidation_auction(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    risk_authority: Pubkey,
+    auction_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(risk_authority, true),
+        ],
+        data: LendingInstruction::SetLendingMarketLiquidationAuction { auction_slots }.pack(),
+    }
+}
+
+/// Creates a 'SetReservePriceMode' instruction, signed by the lending market
+/// owner or risk authority.
+pub fn set_reserve_price_mode(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    risk_authority: Pubkey,
+    price_mode: ReservePriceMode,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(risk_authority, true),
+        ],
+        data: LendingInstruction::SetReservePriceMode { price_mode }.pack(),
+    }
+}
+
+/// Creates an 'InitObligationNft' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_obligation_nft(
+    program_id: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_token_mint_pubkey: Pubkey,
+    obligation_token_output_pubkey: Pubkey,
+    obligation_token_owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        data: LendingInstruction::InitObligationNft.pack(),
+        ..init_obligation(
+            program_id,
+            deposit_reserve_pubkey,
+            borrow_reserve_pubkey,
+            lending_market_pubkey,
+            obligation_pubkey,
+            obligation_token_mint_pubkey,
+            obligation_token_output_pubkey,
+            obligation_token_owner_pubkey,
+        )
+    }
+}
+
+/// Creates a 'SetRiskAuthority' instruction.
+pub fn set_risk_authority(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    new_risk_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetRiskAuthority { new_risk_authority }.pack(),
+    }
+}
+
+/// Creates a 'WriteOffBadDebt' instruction.
+pub fn write_off_bad_debt(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    risk_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(borrow_reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(risk_authority, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::WriteOffBadDebt.pack(),
+    }
+}
@@ -0,0 +1,141 @@
+//! Token converters choosing between dex market and stable prices
+
+use crate::{
+    dex_market::TradeSimulator,
+    error::LendingError,
+    math::{Decimal, TryDiv, TryMul},
+    state::TokenConverter,
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Converts tokens pegged 1:1 to each other, only accounting for the
+/// difference in mint decimals
+pub struct StablePriceConverter<'a> {
+    buy_token_mint: &'a Pubkey,
+    buy_token_decimals: u8,
+    sell_token_decimals: u8,
+}
+
+impl<'a> StablePriceConverter<'a> {
+    /// Create a new StablePriceConverter
+    pub fn new(
+        buy_token_mint: &'a Pubkey,
+        buy_token_decimals: u8,
+        sell_token_decimals: u8,
+    ) -> Self {
+        Self {
+            buy_token_mint,
+            buy_token_decimals,
+            sell_token_decimals,
+        }
+    }
+
+    /// Return (from, to) decimals when converting from the given token
+    fn decimals(&self, from_token_mint: &Pubkey) -> (u8, u8) {
+        if from_token_mint == self.buy_token_mint {
+            (self.buy_token_decimals, self.sell_token_decimals)
+        } else {
+            (self.sell_token_decimals, self.buy_token_decimals)
+        }
+    }
+
+    /// Scale `amount` from `from_decimals` to `to_decimals`
+    fn scale(amount: Decimal, from_decimals: u8, to_decimals: u8) -> Result<Decimal, ProgramError> {
+        let decimals_factor = |decimals: u8| {
+            10u64
+                .checked_pow(decimals as u32)
+                .ok_or(LendingError::MathOverflow)
+        };
+        if to_decimals >= from_decimals {
+            amount.try_mul(decimals_factor(to_decimals - from_decimals)?)
+        } else {
+            amount.try_div(decimals_factor(from_decimals - to_decimals)?)
+        }
+    }
+}
+
+impl<'a> TokenConverter for StablePriceConverter<'a> {
+    fn best_price(&mut self, token_mint: &Pubkey) -> Result<Decimal, ProgramError> {
+        let (token_decimals, other_decimals) = self.decimals(token_mint);
+        Self::scale(Decimal::one(), token_decimals, other_decimals)
+    }
+
+    fn convert(
+        self,
+        from_amount: Decimal,
+        from_token_mint: &Pubkey,
+    ) -> Result<Decimal, ProgramError> {
+        let (from_decimals, to_decimals) = self.decimals(from_token_mint);
+        Self::scale(from_amount, from_decimals, to_decimals)
+    }
+}
+
+/// Token converter for a pair of reserves, depending on their price mode
+pub enum ReserveConverter<'a> {
+    /// Prices are read from the dex market order book
+    Dex(TradeSimulator<'a>),
+    /// Both reserves are pegged to the quote currency
+    Stable(StablePriceConverter<'a>),
+}
+
+impl<'a> TokenConverter for ReserveConverter<'a> {
+    fn best_price(&mut self, token_mint: &Pubkey) -> Result<Decimal, ProgramError> {
+        match self {
+            Self::Dex(trade_simulator) => trade_simulator.best_price(token_mint),
+            Self::Stable(stable_price_converter) => stable_price_converter.best_price(token_mint),
+        }
+    }
+
+    fn convert(
+        self,
+        from_amount: Decimal,
+        from_token_mint: &Pubkey,
+    ) -> Result<Decimal, ProgramError> {
+        match self {
+            Self::Dex(trade_simulator) => trade_simulator.convert(from_amount, from_token_mint),
+            Self::Stable(stable_price_converter) => {
+                stable_price_converter.convert(from_amount, from_token_mint)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stable_price_converter_scales_decimals() {
+        let usdc_mint = Pubkey::new_unique();
+        let usdt_mint = Pubkey::new_unique();
+        let sol_like_mint = Pubkey::new_unique();
+
+        // same decimals convert 1:1
+        let converter = StablePriceConverter::new(&usdc_mint, 6, 6);
+        assert_eq!(
+            converter.convert(Decimal::from(1_000_000u64), &usdt_mint),
+            Ok(Decimal::from(1_000_000u64))
+        );
+
+        // 9 decimals to 6 decimals and back
+        let mut converter = StablePriceConverter::new(&usdc_mint, 6, 9);
+        assert_eq!(
+            converter.best_price(&usdc_mint),
+            Ok(Decimal::from(1_000u64))
+        );
+        assert_eq!(
+            converter.best_price(&sol_like_mint),
+            Decimal::one().try_div(1_000u64)
+        );
+        let converter = StablePriceConverter::new(&usdc_mint, 6, 9);
+        assert_eq!(
+            converter.convert(Decimal::from(2_000_000_000u64), &sol_like_mint),
+            Ok(Decimal::from(2_000_000u64))
+        );
+        let converter = StablePriceConverter::new(&usdc_mint, 6, 9);
+        assert_eq!(
+            converter.convert(Decimal::from(2_000_000u64), &usdc_mint),
+            Ok(Decimal::from(2_000_000_000u64))
+        );
+    }
+}
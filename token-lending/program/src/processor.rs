@@ -4,10 +4,12 @@ use crate::{
     dex_market::{DexMarket, TradeSimulator, BASE_MINT_OFFSET, QUOTE_MINT_OFFSET},
     error::LendingError,
     instruction::{BorrowAmountType, LendingInstruction},
-    math::{Decimal, TryAdd, WAD},
+    math::{Decimal, Rate, TryAdd, WAD},
+    price::{ReserveConverter, StablePriceConverter},
     state::{
         LendingMarket, LiquidateResult, NewObligationParams, NewReserveParams, Obligation,
-        RepayResult, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity, PROGRAM_VERSION,
+        ObligationTokenType, RepayResult, Reserve, ReserveCollateral, ReserveConfig,
+        ReserveLiquidity, ReservePriceMode, PROGRAM_VERSION,
     },
 };
 use num_traits::FromPrimitive;
@@ -24,7 +26,7 @@ use solana_program::{
     pubkey::Pubkey,
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
-use spl_token::state::Account as Token;
+use spl_token::{instruction::AuthorityType, state::Account as Token};
 
 /// Processes an instruction
 pub fn process_instruction(
@@ -47,7 +49,7 @@ pub fn process_instruction(
         }
         LendingInstruction::InitObligation => {
             msg!("Instruction: Init Obligation");
-            process_init_obligation(program_id, accounts)
+            process_init_obligation(program_id, ObligationTokenType::Fungible, accounts)
         }
         LendingInstruction::DepositReserveLiquidity { liquidity_amount } => {
             msg!("Instruction: Deposit");
@@ -88,6 +90,26 @@ pub fn process_instruction(
             msg!("Instruction: Set Lending Market Owner");
             process_set_lending_market_owner(program_id, new_owner, accounts)
         }
+        LendingInstruction::SetLendingMarketLiquidationAuction { auction_slots } => {
+            msg!("Instruction: Set Lending Market Liquidation Auction");
+            process_set_lending_market_liquidation_auction(program_id, auction_slots, accounts)
+        }
+        LendingInstruction::SetReservePriceMode { price_mode } => {
+            msg!("Instruction: Set Reserve Price Mode");
+            process_set_reserve_price_mode(program_id, price_mode, accounts)
+        }
+        LendingInstruction::InitObligationNft => {
+            msg!("Instruction: Init Obligation NFT");
+            process_init_obligation(program_id, ObligationTokenType::NonFungible, accounts)
+        }
+        LendingInstruction::SetRiskAuthority { new_risk_authority } => {
+            msg!("Instruction: Set Risk Authority");
+            process_set_risk_authority(program_id, new_risk_authority, accounts)
+        }
+        LendingInstruction::WriteOffBadDebt => {
+            msg!("Instruction: Write Off Bad Debt");
+            process_write_off_bad_debt(program_id, accounts)
+        }
     }
 }
 
@@ -113,6 +135,7 @@ fn process_init_lending_market(
     new_lending_market.version = PROGRAM_VERSION;
     new_lending_market.bump_seed = bump_seed;
     new_lending_market.owner = market_owner;
+    new_lending_market.risk_authority = market_owner;
     new_lending_market.quote_token_mint = *quote_token_mint_info.key;
     new_lending_market.token_program_id = *token_program_id.key;
     LendingMarket::pack(
@@ -328,7 +351,11 @@ fn process_init_reserve(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+fn process_init_obligation(
+    program_id: &Pubkey,
+    token_type: ObligationTokenType,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let deposit_reserve_info = next_account_info(account_info_iter)?;
     let borrow_reserve_info = next_account_info(account_info_iter)?;
@@ -383,12 +410,16 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     assert_last_update_slot(&borrow_reserve, clock.slot)?;
 
     let cumulative_borrow_rate = borrow_reserve.cumulative_borrow_rate_wads;
-    let obligation_mint_decimals = deposit_reserve.liquidity.mint_decimals;
+    let obligation_mint_decimals = match token_type {
+        ObligationTokenType::Fungible => deposit_reserve.liquidity.mint_decimals,
+        ObligationTokenType::NonFungible => 0,
+    };
     let obligation = Obligation::new(NewObligationParams {
         collateral_reserve: *deposit_reserve_info.key,
         cumulative_borrow_rate_wads: cumulative_borrow_rate,
         borrow_reserve: *borrow_reserve_info.key,
         token_mint: *obligation_token_mint_info.key,
+        token_type,
     });
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -420,6 +451,26 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         token_program: token_program_id.clone(),
     })?;
 
+    if token_type == ObligationTokenType::NonFungible {
+        // mint the single obligation token and fix the supply
+        spl_token_mint_to(TokenMintToParams {
+            mint: obligation_token_mint_info.clone(),
+            destination: obligation_token_output_info.clone(),
+            amount: 1,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+        spl_token_set_authority(TokenSetAuthorityParams {
+            mint: obligation_token_mint_info.clone(),
+            new_authority: None,
+            authority_type: AuthorityType::MintTokens,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
     Ok(())
 }
 
@@ -738,24 +789,28 @@ fn process_borrow(
     if &obligation_token_output.mint != obligation_token_mint_info.key {
         return Err(LendingError::InvalidTokenMint.into());
     }
+    let is_non_fungible = obligation.is_non_fungible();
+    if is_non_fungible {
+        assert_obligation_token_holder(&obligation_token_output, user_transfer_authority_info)?;
+    }
 
     assert_last_update_slot(&borrow_reserve, clock.slot)?;
     assert_last_update_slot(&deposit_reserve, clock.slot)?;
     obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
 
-    let trade_simulator = TradeSimulator::new(
+    let token_converter = reserve_converter(
         dex_market_info,
         dex_market_orders_info,
         memory,
         &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &deposit_reserve.liquidity.mint_pubkey,
+        &borrow_reserve,
+        &deposit_reserve,
     )?;
 
     let loan = deposit_reserve.create_loan(
         token_amount,
         token_amount_type,
-        trade_simulator,
+        token_converter,
         &borrow_reserve.liquidity.mint_pubkey,
     )?;
 
@@ -827,14 +882,16 @@ fn process_borrow(
     })?;
 
     // mint obligation tokens to output account
-    spl_token_mint_to(TokenMintToParams {
-        mint: obligation_token_mint_info.clone(),
-        destination: obligation_token_output_info.clone(),
-        amount: loan.collateral_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
+    if !is_non_fungible {
+        spl_token_mint_to(TokenMintToParams {
+            mint: obligation_token_mint_info.clone(),
+            destination: obligation_token_output_info.clone(),
+            amount: loan.collateral_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
 
     Ok(())
 }
@@ -895,6 +952,18 @@ fn process_repay(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
+    let is_non_fungible = obligation.is_non_fungible();
+    if is_non_fungible {
+        let obligation_token_input = Token::unpack(&obligation_token_input_info.data.borrow())?;
+        if obligation_token_input_info.owner != token_program_id.key {
+            return Err(LendingError::InvalidTokenOwner.into());
+        }
+        if &obligation_token_input.mint != obligation_token_mint_info.key {
+            return Err(LendingError::InvalidTokenMint.into());
+        }
+        assert_obligation_token_holder(&obligation_token_input, user_transfer_authority_info)?;
+    }
+
     let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
     if repay_reserve_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -963,14 +1032,16 @@ fn process_repay(
     }
 
     // burn obligation tokens
-    spl_token_burn(TokenBurnParams {
-        mint: obligation_token_mint_info.clone(),
-        source: obligation_token_input_info.clone(),
-        amount: obligation_token_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
+    if !is_non_fungible {
+        spl_token_burn(TokenBurnParams {
+            mint: obligation_token_mint_info.clone(),
+            source: obligation_token_input_info.clone(),
+            amount: obligation_token_amount,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
 
     // deposit repaid liquidity
     spl_token_transfer(TokenTransferParams {
@@ -1114,13 +1185,58 @@ fn process_liquidate(
     assert_last_update_slot(&withdraw_reserve, clock.slot)?;
     obligation.accrue_interest(repay_reserve.cumulative_borrow_rate_wads)?;
 
-    let trade_simulator = TradeSimulator::new(
+    let liquidation_bonus_rate = if lending_market.liquidation_auction_slots == 0 {
+        Rate::from_percent(withdraw_reserve.config.liquidation_bonus)
+    } else {
+        let token_converter = reserve_converter(
+            dex_market_info,
+            dex_market_orders_info,
+            memory,
+            &lending_market.quote_token_mint,
+            &withdraw_reserve,
+            &repay_reserve,
+        )?;
+        let is_unhealthy = match withdraw_reserve.check_obligation_unhealthy(
+            &obligation,
+            &repay_reserve.liquidity.mint_pubkey,
+            token_converter,
+        ) {
+            Ok(()) => true,
+            Err(err) if err == LendingError::HealthyObligation.into() => false,
+            Err(err) => return Err(err),
+        };
+
+        match (
+            obligation.liquidation_auction_elapsed_slots(clock.slot),
+            is_unhealthy,
+        ) {
+            (None, true) => {
+                msg!("Liquidation auction started");
+                obligation.start_liquidation_auction(clock.slot);
+                Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+                return Ok(());
+            }
+            (Some(_), false) => {
+                msg!("Obligation is healthy, liquidation auction ended");
+                obligation.reset_liquidation_auction();
+                Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+                return Ok(());
+            }
+            (None, false) => return Err(LendingError::HealthyObligation.into()),
+            (Some(elapsed_slots), true) => withdraw_reserve.config.auction_liquidation_bonus_rate(
+                elapsed_slots,
+                lending_market.liquidation_auction_slots,
+            )?,
+        }
+    };
+
+    let token_converter = reserve_converter(
         dex_market_info,
         dex_market_orders_info,
         memory,
         &lending_market.quote_token_mint,
-        &withdraw_reserve.liquidity.mint_pubkey,
-        &repay_reserve.liquidity.mint_pubkey,
+        &withdraw_reserve,
+        &repay_reserve,
     )?;
 
     let LiquidateResult {
@@ -1131,7 +1247,8 @@ fn process_liquidate(
         &obligation,
         liquidity_amount,
         &repay_reserve.liquidity.mint_pubkey,
-        trade_simulator,
+        liquidation_bonus_rate,
+        token_converter,
     )?;
 
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
@@ -1263,6 +1380,7 @@ fn process_deposit_obligation_collateral(
         return Err(LendingError::InvalidTokenMint.into());
     }
 
+    let is_non_fungible = obligation.is_non_fungible();
     obligation.deposited_collateral_tokens = obligation
         .deposited_collateral_tokens
         .checked_add(collateral_amount)
@@ -1291,14 +1409,16 @@ fn process_deposit_obligation_collateral(
     })?;
 
     // mint obligation tokens to output account
-    spl_token_mint_to(TokenMintToParams {
-        mint: obligation_token_mint_info.clone(),
-        destination: obligation_token_output_info.clone(),
-        amount: collateral_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
+    if !is_non_fungible {
+        spl_token_mint_to(TokenMintToParams {
+            mint: obligation_token_mint_info.clone(),
+            destination: obligation_token_output_info.clone(),
+            amount: collateral_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
 
     Ok(())
 }
@@ -1421,6 +1541,10 @@ fn process_withdraw_obligation_collateral(
     if &obligation_token_input.mint != obligation_token_mint_info.key {
         return Err(LendingError::InvalidTokenMint.into());
     }
+    let is_non_fungible = obligation.is_non_fungible();
+    if is_non_fungible {
+        assert_obligation_token_holder(&obligation_token_input, user_transfer_authority_info)?;
+    }
 
     // accrue interest and update rates
     assert_last_update_slot(&borrow_reserve, clock.slot)?;
@@ -1436,19 +1560,19 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidObligationCollateral.into());
     }
 
-    let trade_simulator = TradeSimulator::new(
+    let token_converter = reserve_converter(
         dex_market_info,
         dex_market_orders_info,
         memory,
         &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &withdraw_reserve.liquidity.mint_pubkey,
+        &borrow_reserve,
+        &withdraw_reserve,
     )?;
 
     let required_collateral = withdraw_reserve.required_collateral_for_borrow(
         obligation.borrowed_liquidity_wads.try_ceil_u64()?,
         &borrow_reserve.liquidity.mint_pubkey,
-        trade_simulator,
+        token_converter,
     )?;
     if obligation_collateral_amount < required_collateral {
         return Err(LendingError::ObligationCollateralBelowRequired.into());
@@ -1479,14 +1603,16 @@ fn process_withdraw_obligation_collateral(
     }
 
     // burn obligation tokens
-    spl_token_burn(TokenBurnParams {
-        mint: obligation_token_mint_info.clone(),
-        source: obligation_token_input_info.clone(),
-        amount: obligation_token_amount,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
+    if !is_non_fungible {
+        spl_token_burn(TokenBurnParams {
+            mint: obligation_token_mint_info.clone(),
+            source: obligation_token_input_info.clone(),
+            amount: obligation_token_amount,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
 
     // withdraw collateral
     spl_token_transfer(TokenTransferParams {
@@ -1528,6 +1654,195 @@ fn process_set_lending_market_owner(
     Ok(())
 }
 
+#[inline(never)] // avoid stack frame limit
+fn process_set_lending_market_liquidation_auction(
+    program_id: &Pubkey,
+    auction_slots: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let risk_authority_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_risk_authority(&lending_market, risk_authority_info)?;
+
+    lending_market.liquidation_auction_slots = auction_slots;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_reserve_price_mode(
+    program_id: &Pubkey,
+    price_mode: ReservePriceMode,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let risk_authority_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_risk_authority(&lending_market, risk_authority_info)?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.dex_market.is_none() {
+        msg!("Quote currency reserve has no price to set the mode of");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.price_mode = price_mode;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_risk_authority(
+    program_id: &Pubkey,
+    new_risk_authority: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.risk_authority = new_risk_authority;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_write_off_bad_debt(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let risk_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_risk_authority(&lending_market, risk_authority_info)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.borrow_reserve != borrow_reserve_info.key {
+        msg!("Invalid borrow reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // accrue interest and update rates
+    assert_last_update_slot(&borrow_reserve, clock.slot)?;
+    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
+
+    // the reserve total can lag behind the obligation by rounding of accrued interest
+    let write_off_amount = obligation
+        .write_off_debt()?
+        .min(borrow_reserve.liquidity.borrowed_amount_wads);
+    let exchange_rate_before = Rate::from(borrow_reserve.collateral_exchange_rate()?);
+    borrow_reserve.liquidity.write_off(write_off_amount)?;
+
+    // an empty reserve prices collateral at the initial rate, which would
+    // hand the written off value back to outstanding collateral tokens
+    if borrow_reserve.liquidity.total_supply()? == Decimal::zero()
+        && borrow_reserve.collateral.mint_total_supply > 0
+    {
+        msg!("Writing off this debt would leave no liquidity for outstanding collateral");
+        return Err(LendingError::InsufficientLiquidity.into());
+    }
+    let exchange_rate_after = Rate::from(borrow_reserve.collateral_exchange_rate()?);
+
+    msg!(
+        "Bad debt written off: obligation {}, reserve {}, liquidity {}",
+        obligation_info.key,
+        borrow_reserve_info.key,
+        write_off_amount
+    );
+    msg!(
+        "Collateral exchange rate: {} -> {}",
+        exchange_rate_before,
+        exchange_rate_after
+    );
+
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+/// Token converter for the pair of reserves, only reading the dex market if one
+/// of the reserves priced through it isn't pegged to the quote currency
+fn reserve_converter<'a>(
+    dex_market_info: &AccountInfo,
+    dex_market_orders_info: &AccountInfo,
+    memory: &'a AccountInfo,
+    quote_token_mint: &'a Pubkey,
+    buy_reserve: &'a Reserve,
+    sell_reserve: &'a Reserve,
+) -> Result<ReserveConverter<'a>, ProgramError> {
+    let is_stable = [buy_reserve, sell_reserve]
+        .iter()
+        .filter(|reserve| reserve.dex_market.is_some())
+        .all(|reserve| reserve.is_stable());
+    if is_stable {
+        Ok(ReserveConverter::Stable(StablePriceConverter::new(
+            &buy_reserve.liquidity.mint_pubkey,
+            buy_reserve.liquidity.mint_decimals,
+            sell_reserve.liquidity.mint_decimals,
+        )))
+    } else {
+        Ok(ReserveConverter::Dex(TradeSimulator::new(
+            dex_market_info,
+            dex_market_orders_info,
+            memory,
+            quote_token_mint,
+            &buy_reserve.liquidity.mint_pubkey,
+            &sell_reserve.liquidity.mint_pubkey,
+        )?))
+    }
+}
+
 fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
     if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
         msg!(&rent.minimum_balance(account_info.data_len()).to_string());
@@ -1556,6 +1871,42 @@ fn assert_uninitialized<T: Pack + IsInitialized>(
     }
 }
 
+/// Checks that the user transfer authority holds the token of a non-fungible obligation,
+/// as the owner or delegate of the token account
+fn assert_obligation_token_holder(
+    obligation_token_account: &Token,
+    user_transfer_authority_info: &AccountInfo,
+) -> ProgramResult {
+    if !user_transfer_authority_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+    let authority = user_transfer_authority_info.key;
+    let is_owner = &obligation_token_account.owner == authority;
+    let is_delegate = obligation_token_account.delegate == COption::Some(*authority)
+        && obligation_token_account.delegated_amount > 0;
+    if obligation_token_account.amount == 0 || !(is_owner || is_delegate) {
+        msg!("User transfer authority doesn't hold the obligation token");
+        return Err(LendingError::ObligationTokenHolderRequired.into());
+    }
+    Ok(())
+}
+
+/// Checks that the signer is the lending market owner or its risk authority
+fn assert_risk_authority(
+    lending_market: &LendingMarket,
+    risk_authority_info: &AccountInfo,
+) -> ProgramResult {
+    if &lending_market.owner != risk_authority_info.key
+        && &lending_market.risk_authority != risk_authority_info.key
+    {
+        return Err(LendingError::InvalidRiskAuthority.into());
+    }
+    if !risk_authority_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(())
+}
+
 /// Unpacks a spl_token `Mint`.
 fn unpack_mint(data: &[u8]) -> Result<spl_token::state::Mint, LendingError> {
     spl_token::state::Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
@@ -1679,6 +2030,32 @@ fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
     result.map_err(|_| LendingError::TokenBurnFailed.into())
 }
 
+/// Issue a spl_token `SetAuthority` instruction on a mint.
+#[inline(always)]
+fn spl_token_set_authority(params: TokenSetAuthorityParams<'_, '_>) -> ProgramResult {
+    let TokenSetAuthorityParams {
+        mint,
+        new_authority,
+        authority_type,
+        authority,
+        token_program,
+        authority_signer_seeds,
+    } = params;
+    let result = invoke_signed(
+        &spl_token::instruction::set_authority(
+            token_program.key,
+            mint.key,
+            new_authority,
+            authority_type,
+            authority.key,
+            &[],
+        )?,
+        &[mint, authority, token_program],
+        &[authority_signer_seeds],
+    );
+    result.map_err(|_| LendingError::TokenSetAuthorityFailed.into())
+}
+
 struct TokenInitializeMintParams<'a: 'b, 'b> {
     mint: AccountInfo<'a>,
     rent: AccountInfo<'a>,
@@ -1713,6 +2090,15 @@ struct TokenMintToParams<'a: 'b, 'b> {
     token_program: AccountInfo<'a>,
 }
 
+struct TokenSetAuthorityParams<'a: 'b, 'b> {
+    mint: AccountInfo<'a>,
+    new_authority: Option<&'b Pubkey>,
+    authority_type: AuthorityType,
+    authority: AccountInfo<'a>,
+    authority_signer_seeds: &'b [&'b [u8]],
+    token_program: AccountInfo<'a>,
+}
+
 struct TokenBurnParams<'a: 'b, 'b> {
     mint: AccountInfo<'a>,
     source: AccountInfo<'a>,
@@ -19,6 +19,13 @@ pub struct LendingMarket {
     pub quote_token_mint: Pubkey,
     /// Token program id
     pub token_program_id: Pubkey,
+    /// Number of slots over which the liquidation bonus ramps up from zero to
+    /// the reserve's configured bonus. Zero disables the auction and always
+    /// pays the full bonus.
+    pub liquidation_auction_slots: u64,
+    /// Risk authority which can change the liquidation auction and reserve
+    /// price modes, alongside the owner
+    pub risk_authority: Pubkey,
 }
 
 impl Sealed for LendingMarket {}
@@ -36,8 +43,16 @@ impl Pack for LendingMarket {
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
         let input = array_ref![input, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_token_mint, token_program_id, _padding) =
-            array_refs![input, 1, 1, 32, 32, 32, 62];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_token_mint,
+            token_program_id,
+            liquidation_auction_slots,
+            risk_authority,
+            _padding,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 8, 32, 22];
         let version = u8::from_le_bytes(*version);
         if version > PROGRAM_VERSION {
             return Err(ProgramError::InvalidAccountData);
@@ -49,18 +64,30 @@ impl Pack for LendingMarket {
             owner: Pubkey::new_from_array(*owner),
             quote_token_mint: Pubkey::new_from_array(*quote_token_mint),
             token_program_id: Pubkey::new_from_array(*token_program_id),
+            liquidation_auction_slots: u64::from_le_bytes(*liquidation_auction_slots),
+            risk_authority: Pubkey::new_from_array(*risk_authority),
         })
     }
 
     fn pack_into_slice(&self, output: &mut [u8]) {
         let output = array_mut_ref![output, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_token_mint, token_program_id, _padding) =
-            mut_array_refs![output, 1, 1, 32, 32, 32, 62];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_token_mint,
+            token_program_id,
+            liquidation_auction_slots,
+            risk_authority,
+            _padding,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 8, 32, 22];
         *version = self.version.to_le_bytes();
         *bump_seed = self.bump_seed.to_le_bytes();
         owner.copy_from_slice(self.owner.as_ref());
         quote_token_mint.copy_from_slice(self.quote_token_mint.as_ref());
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        *liquidation_auction_slots = self.liquidation_auction_slots.to_le_bytes();
+        risk_authority.copy_from_slice(self.risk_authority.as_ref());
     }
 }
@@ -4,7 +4,10 @@ use crate::{
     math::{Decimal, Rate, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
 use solana_program::{
+    clock::Slot,
     entrypoint::ProgramResult,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -29,6 +32,11 @@ pub struct Obligation {
     pub borrow_reserve: Pubkey,
     /// Mint address of the tokens for this obligation
     pub token_mint: Pubkey,
+    /// Slot at which a liquidation auction was started for this obligation,
+    /// or zero if no auction is running
+    pub liquidation_auction_start_slot: Slot,
+    /// How the obligation tokens represent ownership of the obligation
+    pub token_type: ObligationTokenType,
 }
 
 impl Obligation {
@@ -38,6 +46,7 @@ impl Obligation {
             collateral_reserve,
             borrow_reserve,
             token_mint,
+            token_type,
             cumulative_borrow_rate_wads,
         } = params;
 
@@ -49,9 +58,16 @@ impl Obligation {
             borrowed_liquidity_wads: Decimal::zero(),
             borrow_reserve,
             token_mint,
+            liquidation_auction_start_slot: 0,
+            token_type,
         }
     }
 
+    /// Check if the obligation is owned by the holder of a single obligation token
+    pub fn is_non_fungible(&self) -> bool {
+        self.token_type == ObligationTokenType::NonFungible
+    }
+
     /// Maximum amount of loan that can be closed out by a liquidator due
     /// to the remaining balance being too small to be liquidated normally.
     pub fn max_closeable_amount(&self) -> Result<u64, ProgramError> {
@@ -113,6 +129,26 @@ impl Obligation {
         Ok(())
     }
 
+    /// Start a liquidation auction at the given slot
+    pub fn start_liquidation_auction(&mut self, slot: Slot) {
+        // slot 0 is reserved to mark that no auction is running
+        self.liquidation_auction_start_slot = slot.max(1);
+    }
+
+    /// End any running liquidation auction
+    pub fn reset_liquidation_auction(&mut self) {
+        self.liquidation_auction_start_slot = 0;
+    }
+
+    /// Number of slots elapsed since the liquidation auction started
+    pub fn liquidation_auction_elapsed_slots(&self, slot: Slot) -> Option<Slot> {
+        if self.liquidation_auction_start_slot == 0 {
+            None
+        } else {
+            Some(slot.saturating_sub(self.liquidation_auction_start_slot))
+        }
+    }
+
     /// Liquidate part of obligation
     pub fn liquidate(&mut self, repay_amount: Decimal, withdraw_amount: u64) -> ProgramResult {
         self.borrowed_liquidity_wads = self.borrowed_liquidity_wads.try_sub(repay_amount)?;
@@ -120,9 +156,28 @@ impl Obligation {
             .deposited_collateral_tokens
             .checked_sub(withdraw_amount)
             .ok_or(LendingError::MathOverflow)?;
+        if self.borrowed_liquidity_wads == Decimal::zero() {
+            self.reset_liquidation_auction();
+        }
         Ok(())
     }
 
+    /// Write off the debt of an obligation with no collateral left to liquidate,
+    /// returning the amount written off
+    pub fn write_off_debt(&mut self) -> Result<Decimal, ProgramError> {
+        if self.deposited_collateral_tokens > 0 {
+            return Err(LendingError::ObligationHasCollateral.into());
+        }
+        if self.borrowed_liquidity_wads == Decimal::zero() {
+            return Err(LendingError::ObligationEmpty.into());
+        }
+
+        let write_off_amount = self.borrowed_liquidity_wads;
+        self.borrowed_liquidity_wads = Decimal::zero();
+        self.reset_liquidation_auction();
+        Ok(write_off_amount)
+    }
+
     /// Repay borrowed tokens
     pub fn repay(
         &mut self,
@@ -158,6 +213,24 @@ impl Obligation {
     }
 }
 
+/// How the obligation tokens represent ownership of an obligation
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+pub enum ObligationTokenType {
+    /// Obligation tokens are minted and burned along with the deposited
+    /// collateral, each token is a claim on a share of it
+    Fungible,
+    /// A single obligation token with no decimals is minted when the
+    /// obligation is created. Whoever holds it owns the whole position, so
+    /// transferring the token transfers the obligation.
+    NonFungible,
+}
+
+impl Default for ObligationTokenType {
+    fn default() -> Self {
+        Self::Fungible
+    }
+}
+
 /// Obligation repay result
 pub struct RepayResult {
     /// Amount of collateral to withdraw
@@ -178,6 +251,8 @@ pub struct NewObligationParams {
     pub borrow_reserve: Pubkey,
     /// Obligation token mint address
     pub token_mint: Pubkey,
+    /// How the obligation tokens represent ownership of the obligation
+    pub token_type: ObligationTokenType,
     /// Borrow rate used for calculating interest.
     pub cumulative_borrow_rate_wads: Decimal,
 }
@@ -205,8 +280,10 @@ impl Pack for Obligation {
             borrowed_liquidity_wads,
             borrow_reserve,
             token_mint,
+            liquidation_auction_start_slot,
+            token_type,
             _padding,
-        ) = array_refs![input, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = array_refs![input, 1, 8, 32, 16, 16, 32, 32, 8, 1, 119];
         Ok(Self {
             version: u8::from_le_bytes(*version),
             deposited_collateral_tokens: u64::from_le_bytes(*deposited_collateral_tokens),
@@ -215,6 +292,9 @@ impl Pack for Obligation {
             borrowed_liquidity_wads: unpack_decimal(borrowed_liquidity_wads),
             borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
             token_mint: Pubkey::new_from_array(*token_mint),
+            liquidation_auction_start_slot: u64::from_le_bytes(*liquidation_auction_start_slot),
+            token_type: ObligationTokenType::from_u8(u8::from_le_bytes(*token_type))
+                .ok_or(ProgramError::InvalidAccountData)?,
         })
     }
 
@@ -228,8 +308,10 @@ impl Pack for Obligation {
             borrowed_liquidity_wads,
             borrow_reserve,
             token_mint,
+            liquidation_auction_start_slot,
+            token_type,
             _padding,
-        ) = mut_array_refs![output, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = mut_array_refs![output, 1, 8, 32, 16, 16, 32, 32, 8, 1, 119];
 
         *version = self.version.to_le_bytes();
         *deposited_collateral_tokens = self.deposited_collateral_tokens.to_le_bytes();
@@ -238,6 +320,8 @@ impl Pack for Obligation {
         pack_decimal(self.borrowed_liquidity_wads, borrowed_liquidity_wads);
         borrow_reserve.copy_from_slice(self.borrow_reserve.as_ref());
         token_mint.copy_from_slice(self.token_mint.as_ref());
+        *liquidation_auction_start_slot = self.liquidation_auction_start_slot.to_le_bytes();
+        *token_type = self.token_type.to_u8().unwrap().to_le_bytes();
     }
 }
 
@@ -280,6 +364,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn pack_obligation_token_type() {
+        let obligation = Obligation::new(NewObligationParams {
+            collateral_reserve: Pubkey::new_unique(),
+            borrow_reserve: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            token_type: ObligationTokenType::NonFungible,
+            cumulative_borrow_rate_wads: Decimal::one(),
+        });
+        let mut packed = [0u8; Obligation::LEN];
+        obligation.pack_into_slice(&mut packed);
+        assert_eq!(Obligation::unpack(&packed), Ok(obligation));
+
+        let token_type_offset = Obligation::LEN - 120;
+        assert_eq!(packed[token_type_offset], 1);
+        packed[token_type_offset] = 2;
+        assert_eq!(
+            Obligation::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -5,6 +5,8 @@ use crate::{
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
 use solana_program::{
     clock::Slot,
     entrypoint::ProgramResult,
@@ -40,6 +42,8 @@ pub struct Reserve {
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// How the reserve liquidity is priced in the quote currency
+    pub price_mode: ReservePriceMode,
 }
 
 impl Reserve {
@@ -63,9 +67,16 @@ impl Reserve {
             liquidity: liquidity_info,
             dex_market,
             config,
+            price_mode: ReservePriceMode::Oracle,
         }
     }
 
+    /// Whether the reserve liquidity is priced at a fixed 1:1 peg to the quote
+    /// currency instead of through its dex market
+    pub fn is_stable(&self) -> bool {
+        self.price_mode == ReservePriceMode::Stable
+    }
+
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
@@ -98,6 +109,7 @@ impl Reserve {
         obligation: &Obligation,
         liquidate_amount: u64,
         liquidity_token_mint: &Pubkey,
+        liquidation_bonus_rate: Rate,
         token_converter: impl TokenConverter,
     ) -> Result<LiquidateResult, ProgramError> {
         Self::_liquidate_obligation(
@@ -106,19 +118,34 @@ impl Reserve {
             liquidity_token_mint,
             self.collateral_exchange_rate()?,
             &self.config,
+            liquidation_bonus_rate,
             token_converter,
         )
     }
 
-    fn _liquidate_obligation(
+    /// Check that an obligation is unhealthy and can be liquidated
+    pub fn check_obligation_unhealthy(
+        &self,
+        obligation: &Obligation,
+        liquidity_token_mint: &Pubkey,
+        mut token_converter: impl TokenConverter,
+    ) -> ProgramResult {
+        Self::_check_obligation_unhealthy(
+            obligation,
+            liquidity_token_mint,
+            self.collateral_exchange_rate()?,
+            &self.config,
+            &mut token_converter,
+        )
+    }
+
+    fn _check_obligation_unhealthy(
         obligation: &Obligation,
-        liquidity_amount: u64,
         liquidity_token_mint: &Pubkey,
         collateral_exchange_rate: CollateralExchangeRate,
         collateral_reserve_config: &ReserveConfig,
-        mut token_converter: impl TokenConverter,
-    ) -> Result<LiquidateResult, ProgramError> {
-        // Check obligation health
+        token_converter: &mut impl TokenConverter,
+    ) -> ProgramResult {
         let borrow_token_price = token_converter.best_price(liquidity_token_mint)?;
         let liquidation_threshold =
             Rate::from_percent(collateral_reserve_config.liquidation_threshold);
@@ -127,6 +154,25 @@ impl Reserve {
         if obligation_loan_to_value < liquidation_threshold.into() {
             return Err(LendingError::HealthyObligation.into());
         }
+        Ok(())
+    }
+
+    fn _liquidate_obligation(
+        obligation: &Obligation,
+        liquidity_amount: u64,
+        liquidity_token_mint: &Pubkey,
+        collateral_exchange_rate: CollateralExchangeRate,
+        collateral_reserve_config: &ReserveConfig,
+        liquidation_bonus_rate: Rate,
+        mut token_converter: impl TokenConverter,
+    ) -> Result<LiquidateResult, ProgramError> {
+        Self::_check_obligation_unhealthy(
+            obligation,
+            liquidity_token_mint,
+            collateral_exchange_rate,
+            collateral_reserve_config,
+            &mut token_converter,
+        )?;
 
         // Special handling for small, closeable obligations
         let max_closeable_amount = obligation.max_closeable_amount()?;
@@ -150,8 +196,7 @@ impl Reserve {
                 token_converter.convert(decimal_repay_amount, liquidity_token_mint)?;
             let collateral_amount = collateral_exchange_rate
                 .decimal_liquidity_to_collateral(receive_liquidity_amount)?;
-            let bonus_rate = Rate::from_percent(collateral_reserve_config.liquidation_bonus);
-            let bonus_amount = collateral_amount.try_mul(bonus_rate)?;
+            let bonus_amount = collateral_amount.try_mul(liquidation_bonus_rate)?;
             let withdraw_amount = collateral_amount.try_add(bonus_amount)?;
             let withdraw_amount =
                 withdraw_amount.min(obligation.deposited_collateral_tokens.into());
@@ -336,6 +381,23 @@ impl Reserve {
     }
 }
 
+/// Source of the price of a reserve's liquidity in the quote currency
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+pub enum ReservePriceMode {
+    /// Price is read from the reserve's dex market order book
+    Oracle,
+    /// Liquidity is pegged 1:1 to the quote currency, so the dex market is not
+    /// read. The lending market owner can switch back to `Oracle` if the
+    /// asset depegs.
+    Stable,
+}
+
+impl Default for ReservePriceMode {
+    fn default() -> Self {
+        Self::Oracle
+    }
+}
+
 /// Create new reserve
 pub struct NewReserveParams {
     /// Current slot
@@ -432,6 +494,13 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Remove unrecoverable debt from total borrows, which lowers the value of
+    /// every collateral token in proportion
+    pub fn write_off(&mut self, write_off_amount: Decimal) -> ProgramResult {
+        self.borrowed_amount_wads = self.borrowed_amount_wads.try_sub(write_off_amount)?;
+        Ok(())
+    }
+
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
         let total_supply = self.total_supply()?;
@@ -543,6 +612,25 @@ pub struct ReserveConfig {
     pub fees: ReserveFees,
 }
 
+impl ReserveConfig {
+    /// Liquidation bonus paid out after `elapsed_slots` of a liquidation auction
+    /// lasting `auction_slots`. The bonus ramps linearly from zero up to the
+    /// configured liquidation bonus, which is paid in full once the auction
+    /// has run its course or when `auction_slots` is zero.
+    pub fn auction_liquidation_bonus_rate(
+        &self,
+        elapsed_slots: Slot,
+        auction_slots: u64,
+    ) -> Result<Rate, ProgramError> {
+        let bonus_rate = Rate::from_percent(self.liquidation_bonus);
+        if elapsed_slots >= auction_slots {
+            Ok(bonus_rate)
+        } else {
+            bonus_rate.try_mul(elapsed_slots)?.try_div(auction_slots)
+        }
+    }
+}
+
 /// Additional fee information on a reserve
 ///
 /// These exist separately from interest accrual fees, and are specifically for
@@ -638,10 +726,11 @@ impl Pack for Reserve {
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            price_mode,
             __padding,
         ) = array_refs![
-            input, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            input, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8, 1,
+            299
         ];
         Ok(Self {
             version: u8::from_le_bytes(*version),
@@ -675,6 +764,8 @@ impl Pack for Reserve {
                     host_fee_percentage: u8::from_le_bytes(*host_fee_percentage),
                 },
             },
+            price_mode: ReservePriceMode::from_u8(u8::from_le_bytes(*price_mode))
+                .ok_or(ProgramError::InvalidAccountData)?,
         })
     }
 
@@ -704,10 +795,11 @@ impl Pack for Reserve {
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            price_mode,
             _padding,
         ) = mut_array_refs![
             output, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            1, 299
         ];
         *version = self.version.to_le_bytes();
         *last_update_slot = self.last_update_slot.to_le_bytes();
@@ -738,6 +830,8 @@ impl Pack for Reserve {
         *max_borrow_rate = self.config.max_borrow_rate.to_le_bytes();
         *borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
         *host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+
+        *price_mode = self.price_mode.to_u8().unwrap().to_le_bytes();
     }
 }
 
@@ -871,6 +965,7 @@ mod test {
                 &Pubkey::default(),
                 collateral_exchange_rate,
                 collateral_reserve_config,
+                Rate::from_percent(collateral_reserve_config.liquidation_bonus),
                 MockConverter(token_conversion_rate)
             );
 
@@ -1227,6 +1322,7 @@ mod test {
             &Pubkey::default(),
             collateral_exchange_rate,
             collateral_reserve_config,
+            Rate::from_percent(collateral_reserve_config.liquidation_bonus),
             MockConverter(conversion_rate),
         );
 
@@ -1260,6 +1356,7 @@ mod test {
             &Pubkey::default(),
             collateral_exchange_rate,
             collateral_reserve_config,
+            Rate::from_percent(collateral_reserve_config.liquidation_bonus),
             MockConverter(conversion_rate),
         )
         .unwrap();
@@ -1346,4 +1443,57 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn auction_liquidation_bonus_ramps_up() {
+        let config = ReserveConfig {
+            liquidation_bonus: 10u8,
+            ..ReserveConfig::default()
+        };
+
+        assert_eq!(
+            config.auction_liquidation_bonus_rate(0, 100).unwrap(),
+            Rate::zero()
+        );
+        assert_eq!(
+            config.auction_liquidation_bonus_rate(50, 100).unwrap(),
+            Rate::from_percent(5)
+        );
+        assert_eq!(
+            config.auction_liquidation_bonus_rate(100, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+        assert_eq!(
+            config.auction_liquidation_bonus_rate(1_000, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+        assert_eq!(
+            config.auction_liquidation_bonus_rate(0, 0).unwrap(),
+            Rate::from_percent(10)
+        );
+    }
+
+    #[test]
+    fn pack_reserve_price_mode() {
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            dex_market: COption::Some(Pubkey::new_unique()),
+            price_mode: ReservePriceMode::Stable,
+            ..Reserve::default()
+        };
+        let mut packed = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut packed).unwrap();
+        let unpacked = Reserve::unpack(&packed).unwrap();
+        assert_eq!(unpacked, reserve);
+        assert!(unpacked.is_stable());
+
+        // price mode byte follows the collateral mint supply
+        let price_mode_offset = Reserve::LEN - 300;
+        assert_eq!(packed[price_mode_offset], 1);
+        packed[price_mode_offset] = 2;
+        assert_eq!(
+            Reserve::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
 }
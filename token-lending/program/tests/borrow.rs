@@ -6,8 +6,10 @@ use helpers::*;
 use solana_program_test::*;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use spl_token_lending::{
-    instruction::BorrowAmountType, math::Decimal, processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
+    instruction::BorrowAmountType,
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReservePriceMode, INITIAL_COLLATERAL_RATIO},
 };
 
 const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
@@ -323,3 +325,105 @@ async fn test_borrow_base_currency() {
     let host_fee_balance = get_token_balance(&mut banks_client, usdc_reserve.collateral_host).await;
     assert_eq!(host_fee_balance, host_fee);
 }
+
+#[tokio::test]
+async fn test_borrow_stable_price_mode() {
+    // USDT reserve is in stable price mode, so the SOL/USDC order book is not read
+    //
+    // Collateral amount = 1000 * 0.8 (LTV) = 800 USDT
+    // Borrow amount = 800 USDC
+    const USDT_COLLATERAL_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 800 * FRACTIONAL_TO_USDC;
+    const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 20_000 * FRACTIONAL_TO_USDC;
+    const INITIAL_USDT_RESERVE_SUPPLY_FRACTIONAL: u64 = 2 * USDT_COLLATERAL_AMOUNT_FRACTIONAL;
+
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(80_000);
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 80;
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdt_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            liquidity_amount: INITIAL_USDT_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            config: reserve_config,
+            price_mode: ReservePriceMode::Stable,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &usdt_reserve,
+            collateral_amount: 0,
+            borrowed_liquidity_wads: Decimal::zero(),
+        },
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    let collateral_deposit_amount = INITIAL_COLLATERAL_RATIO * USDT_COLLATERAL_AMOUNT_FRACTIONAL;
+    lending_market
+        .borrow(
+            &mut banks_client,
+            &payer,
+            BorrowArgs {
+                deposit_reserve: &usdt_reserve,
+                borrow_reserve: &usdc_reserve,
+                dex_market: &sol_usdc_dex_market,
+                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
+                amount: collateral_deposit_amount,
+                user_accounts_owner: &user_accounts_owner,
+                obligation: &usdc_obligation,
+            },
+        )
+        .await;
+
+    let borrow_amount =
+        get_token_balance(&mut banks_client, usdc_reserve.user_liquidity_account).await;
+    assert_eq!(borrow_amount, USDC_BORROW_AMOUNT_FRACTIONAL);
+
+    let borrow_fees = TEST_RESERVE_CONFIG
+        .fees
+        .calculate_borrow_fees(collateral_deposit_amount)
+        .unwrap()
+        .0;
+
+    let collateral_supply =
+        get_token_balance(&mut banks_client, usdt_reserve.collateral_supply).await;
+    assert_eq!(collateral_supply, collateral_deposit_amount - borrow_fees);
+}
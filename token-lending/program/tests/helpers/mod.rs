@@ -16,13 +16,14 @@ use spl_token::{
 use spl_token_lending::{
     instruction::{
         borrow_reserve_liquidity, deposit_reserve_liquidity, init_lending_market, init_obligation,
-        init_reserve, liquidate_obligation, BorrowAmountType,
+        init_obligation_nft, init_reserve, liquidate_obligation, BorrowAmountType,
     },
     math::{Decimal, Rate, TryAdd, TryMul},
     processor::process_instruction,
     state::{
-        LendingMarket, NewReserveParams, Obligation, Reserve, ReserveCollateral, ReserveConfig,
-        ReserveFees, ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+        LendingMarket, NewReserveParams, Obligation, ObligationTokenType, Reserve,
+        ReserveCollateral, ReserveConfig, ReserveFees, ReserveLiquidity, ReservePriceMode,
+        INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
     },
 };
 use std::str::FromStr;
@@ -132,6 +133,8 @@ pub fn add_lending_market(test: &mut ProgramTest, quote_token_mint: Pubkey) -> T
             owner: owner.pubkey(),
             quote_token_mint,
             token_program_id: spl_token::id(),
+            liquidation_auction_slots: 0,
+            risk_authority: owner.pubkey(),
         },
         &spl_token_lending::id(),
     );
@@ -205,6 +208,8 @@ pub fn add_obligation(
             borrowed_liquidity_wads,
             borrow_reserve: borrow_reserve.pubkey,
             token_mint: token_mint_pubkey,
+            liquidation_auction_start_slot: 0,
+            token_type: ObligationTokenType::Fungible,
         },
         &spl_token_lending::id(),
     );
@@ -213,6 +218,7 @@ pub fn add_obligation(
         pubkey: obligation_pubkey,
         token_mint: token_mint_pubkey,
         token_account: token_account_pubkey,
+        token_type: ObligationTokenType::Fungible,
         borrow_reserve: borrow_reserve.pubkey,
         collateral_reserve: collateral_reserve.pubkey,
     }
@@ -232,6 +238,7 @@ pub struct AddReserveArgs {
     pub collateral_amount: u64,
     pub fees_amount: u64,
     pub dex_market_pubkey: Option<Pubkey>,
+    pub price_mode: ReservePriceMode,
 }
 
 pub fn add_reserve(
@@ -253,6 +260,7 @@ pub fn add_reserve(
         collateral_amount,
         fees_amount,
         dex_market_pubkey,
+        price_mode,
     } = args;
 
     let is_native = if liquidity_mint_pubkey == spl_token::native_mint::id() {
@@ -359,6 +367,7 @@ pub fn add_reserve(
         collateral: reserve_collateral,
         config,
     });
+    reserve.price_mode = price_mode;
     reserve.deposit_liquidity(liquidity_amount).unwrap();
     reserve.liquidity.borrow(borrow_amount).unwrap();
     let borrow_rate_multiplier = Rate::one()
@@ -645,49 +654,62 @@ impl TestLendingMarket {
             get_token_balance(banks_client, deposit_reserve.user_collateral_account).await
         };
 
-        let mut transaction = Transaction::new_with_payer(
-            &[
+        let mut instructions = vec![
+            approve(
+                &spl_token::id(),
+                &deposit_reserve.user_collateral_account,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                approve_amount,
+            )
+            .unwrap(),
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            borrow_reserve_liquidity(
+                spl_token_lending::id(),
+                amount,
+                borrow_amount_type,
+                deposit_reserve.user_collateral_account,
+                borrow_reserve.user_liquidity_account,
+                deposit_reserve.pubkey,
+                deposit_reserve.collateral_supply,
+                deposit_reserve.collateral_fees_receiver,
+                borrow_reserve.pubkey,
+                borrow_reserve.liquidity_supply,
+                self.pubkey,
+                self.authority,
+                user_transfer_authority.pubkey(),
+                obligation.pubkey,
+                obligation.token_mint,
+                obligation.token_account,
+                dex_market.pubkey,
+                dex_market_orders_pubkey,
+                memory_keypair.pubkey(),
+                Some(deposit_reserve.collateral_host),
+            ),
+        ];
+        if obligation.token_type == ObligationTokenType::NonFungible {
+            instructions.insert(
+                1,
                 approve(
                     &spl_token::id(),
-                    &deposit_reserve.user_collateral_account,
+                    &obligation.token_account,
                     &user_transfer_authority.pubkey(),
                     &user_accounts_owner.pubkey(),
                     &[],
-                    approve_amount,
+                    1,
                 )
                 .unwrap(),
-                create_account(
-                    &payer.pubkey(),
-                    &memory_keypair.pubkey(),
-                    0,
-                    65548,
-                    &spl_token_lending::id(),
-                ),
-                borrow_reserve_liquidity(
-                    spl_token_lending::id(),
-                    amount,
-                    borrow_amount_type,
-                    deposit_reserve.user_collateral_account,
-                    borrow_reserve.user_liquidity_account,
-                    deposit_reserve.pubkey,
-                    deposit_reserve.collateral_supply,
-                    deposit_reserve.collateral_fees_receiver,
-                    borrow_reserve.pubkey,
-                    borrow_reserve.liquidity_supply,
-                    self.pubkey,
-                    self.authority,
-                    user_transfer_authority.pubkey(),
-                    obligation.pubkey,
-                    obligation.token_mint,
-                    obligation.token_account,
-                    dex_market.pubkey,
-                    dex_market_orders_pubkey,
-                    memory_keypair.pubkey(),
-                    Some(deposit_reserve.collateral_host),
-                ),
-            ],
-            Some(&payer.pubkey()),
-        );
+            );
+        }
+
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
         let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
         transaction.sign(
@@ -993,6 +1015,7 @@ pub struct TestObligation {
     pub pubkey: Pubkey,
     pub token_mint: Pubkey,
     pub token_account: Pubkey,
+    pub token_type: ObligationTokenType,
     pub collateral_reserve: Pubkey,
     pub borrow_reserve: Pubkey,
 }
@@ -1006,6 +1029,7 @@ impl TestObligation {
         borrow_reserve: &TestReserve,
         payer: &Keypair,
         user_accounts_owner: &Keypair,
+        token_type: ObligationTokenType,
     ) -> Result<Self, TransactionError> {
         let obligation_keypair = Keypair::new();
         let obligation_token_mint_keypair = Keypair::new();
@@ -1014,6 +1038,7 @@ impl TestObligation {
             pubkey: obligation_keypair.pubkey(),
             token_mint: obligation_token_mint_keypair.pubkey(),
             token_account: obligation_token_account_keypair.pubkey(),
+            token_type,
             collateral_reserve: deposit_reserve.pubkey,
             borrow_reserve: borrow_reserve.pubkey,
         };
@@ -1042,7 +1067,10 @@ impl TestObligation {
                     Obligation::LEN as u64,
                     &spl_token_lending::id(),
                 ),
-                init_obligation(
+                match token_type {
+                    ObligationTokenType::Fungible => init_obligation,
+                    ObligationTokenType::NonFungible => init_obligation_nft,
+                }(
                     spl_token_lending::id(),
                     deposit_reserve.pubkey,
                     borrow_reserve.pubkey,
@@ -1091,6 +1119,7 @@ impl TestObligation {
         assert!(obligation.cumulative_borrow_rate_wads >= Decimal::one());
         assert_eq!(obligation.borrow_reserve, self.borrow_reserve);
         assert_eq!(obligation.token_mint, self.token_mint);
+        assert_eq!(obligation.token_type, self.token_type);
     }
 }
 
@@ -31,6 +31,10 @@ async fn test_success() {
     let lending_market_info = lending_market.get_state(&mut banks_client).await;
     assert_eq!(lending_market_info.version, PROGRAM_VERSION);
     assert_eq!(lending_market_info.quote_token_mint, usdc_mint.pubkey);
+    assert_eq!(
+        lending_market_info.risk_authority,
+        lending_market.owner.pubkey()
+    );
 }
 
 #[tokio::test]
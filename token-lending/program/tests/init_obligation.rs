@@ -12,7 +12,7 @@ use solana_sdk::{
 };
 use spl_token_lending::{
     error::LendingError, instruction::init_obligation, math::Decimal,
-    processor::process_instruction,
+    processor::process_instruction, state::ObligationTokenType,
 };
 
 #[tokio::test]
@@ -64,6 +64,7 @@ async fn test_success() {
         &usdc_reserve,
         &payer,
         &user_accounts_owner,
+        ObligationTokenType::Fungible,
     )
     .await
     .unwrap();
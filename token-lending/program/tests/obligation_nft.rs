@@ -0,0 +1,328 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_option::COption;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::{
+    instruction::{approve, transfer},
+    state::Mint,
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{
+        deposit_obligation_collateral, withdraw_obligation_collateral, BorrowAmountType,
+    },
+    processor::process_instruction,
+    state::{ObligationTokenType, ReservePriceMode, INITIAL_COLLATERAL_RATIO},
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(80_000);
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 80;
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: 20_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // stable price mode keeps the dex market out of the way
+    let usdt_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            liquidity_amount: 2_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            config: reserve_config,
+            price_mode: ReservePriceMode::Stable,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let obligation = TestObligation::init(
+        &mut banks_client,
+        &lending_market,
+        &usdt_reserve,
+        &usdc_reserve,
+        &payer,
+        &user_accounts_owner,
+        ObligationTokenType::NonFungible,
+    )
+    .await
+    .unwrap();
+    obligation.validate_state(&mut banks_client).await;
+
+    let obligation_token_mint = banks_client
+        .get_account(obligation.token_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let obligation_token_mint = Mint::unpack(&obligation_token_mint.data[..]).unwrap();
+    assert_eq!(obligation_token_mint.supply, 1);
+    assert_eq!(obligation_token_mint.decimals, 0);
+    assert_eq!(obligation_token_mint.mint_authority, COption::None);
+    assert_eq!(
+        get_token_balance(&mut banks_client, obligation.token_account).await,
+        1
+    );
+
+    let collateral_deposit_amount = INITIAL_COLLATERAL_RATIO * 1_000 * FRACTIONAL_TO_USDC;
+    lending_market
+        .borrow(
+            &mut banks_client,
+            &payer,
+            BorrowArgs {
+                deposit_reserve: &usdt_reserve,
+                borrow_reserve: &usdc_reserve,
+                dex_market: &sol_usdc_dex_market,
+                borrow_amount_type: BorrowAmountType::CollateralDepositAmount,
+                amount: collateral_deposit_amount,
+                user_accounts_owner: &user_accounts_owner,
+                obligation: &obligation,
+            },
+        )
+        .await;
+
+    // no obligation tokens are minted for the deposited collateral
+    assert_eq!(
+        get_token_balance(&mut banks_client, obligation.token_account).await,
+        1
+    );
+    let borrow_fees = reserve_config
+        .fees
+        .calculate_borrow_fees(collateral_deposit_amount)
+        .unwrap()
+        .0;
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation_state.deposited_collateral_tokens,
+        collateral_deposit_amount - borrow_fees
+    );
+
+    // top up the collateral so that some of it can be withdrawn later
+    let extra_collateral_amount = INITIAL_COLLATERAL_RATIO * 500 * FRACTIONAL_TO_USDC;
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdt_reserve.user_collateral_account,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                extra_collateral_amount,
+            )
+            .unwrap(),
+            deposit_obligation_collateral(
+                spl_token_lending::id(),
+                extra_collateral_amount,
+                usdt_reserve.user_collateral_account,
+                usdt_reserve.collateral_supply,
+                usdt_reserve.pubkey,
+                obligation.pubkey,
+                obligation.token_mint,
+                obligation.token_account,
+                lending_market.pubkey,
+                lending_market.authority,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, obligation.token_account).await,
+        1
+    );
+
+    // transfer the position to a new owner
+    let new_owner = Keypair::new();
+    let new_owner_token_account = create_token_account(
+        &mut banks_client,
+        obligation.token_mint,
+        &payer,
+        Some(new_owner.pubkey()),
+        None,
+    )
+    .await;
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer(
+            &spl_token::id(),
+            &obligation.token_account,
+            &new_owner_token_account,
+            &user_accounts_owner.pubkey(),
+            &[],
+            1,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the previous owner can no longer withdraw collateral
+    let withdraw_amount = INITIAL_COLLATERAL_RATIO * 100 * FRACTIONAL_TO_USDC;
+    let memory_keypair = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            withdraw_obligation_collateral(
+                spl_token_lending::id(),
+                withdraw_amount,
+                usdt_reserve.collateral_supply,
+                usdt_reserve.user_collateral_account,
+                usdt_reserve.pubkey,
+                usdc_reserve.pubkey,
+                obligation.pubkey,
+                obligation.token_mint,
+                obligation.token_account,
+                lending_market.pubkey,
+                lending_market.authority,
+                user_accounts_owner.pubkey(),
+                sol_usdc_dex_market.pubkey,
+                sol_usdc_dex_market.bids_pubkey,
+                memory_keypair.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &memory_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationTokenHolderRequired as u32)
+        )
+    );
+
+    // the new owner can, through a delegate
+    let user_transfer_authority = Keypair::new();
+    let new_owner_collateral_account = create_token_account(
+        &mut banks_client,
+        usdt_reserve.collateral_mint,
+        &payer,
+        Some(new_owner.pubkey()),
+        None,
+    )
+    .await;
+    let memory_keypair = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            approve(
+                &spl_token::id(),
+                &new_owner_token_account,
+                &user_transfer_authority.pubkey(),
+                &new_owner.pubkey(),
+                &[],
+                1,
+            )
+            .unwrap(),
+            withdraw_obligation_collateral(
+                spl_token_lending::id(),
+                withdraw_amount,
+                usdt_reserve.collateral_supply,
+                new_owner_collateral_account,
+                usdt_reserve.pubkey,
+                usdc_reserve.pubkey,
+                obligation.pubkey,
+                obligation.token_mint,
+                new_owner_token_account,
+                lending_market.pubkey,
+                lending_market.authority,
+                user_transfer_authority.pubkey(),
+                sol_usdc_dex_market.pubkey,
+                sol_usdc_dex_market.bids_pubkey,
+                memory_keypair.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &memory_keypair,
+            &new_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, new_owner_collateral_account).await,
+        withdraw_amount
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, new_owner_token_account).await,
+        1
+    );
+    let obligation_state = obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation_state.deposited_collateral_tokens,
+        collateral_deposit_amount - borrow_fees + extra_collateral_amount - withdraw_amount
+    );
+}
@@ -0,0 +1,175 @@
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError, instruction::set_reserve_price_mode, processor::process_instruction,
+    state::ReservePriceMode,
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(6_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdt_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            dex_market_pubkey: Some(Pubkey::new_unique()),
+            liquidity_mint_pubkey: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for price_mode in &[ReservePriceMode::Stable, ReservePriceMode::Oracle] {
+        let mut transaction = Transaction::new_with_payer(
+            &[set_reserve_price_mode(
+                spl_token_lending::id(),
+                usdt_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                *price_mode,
+            )],
+            Some(&payer.pubkey()),
+        );
+
+        transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+            .unwrap();
+
+        let reserve = usdt_reserve.get_state(&mut banks_client).await;
+        assert_eq!(reserve.price_mode, *price_mode);
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_risk_authority() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(6_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdt_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            dex_market_pubkey: Some(Pubkey::new_unique()),
+            liquidity_mint_pubkey: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_risk_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_price_mode(
+            spl_token_lending::id(),
+            usdt_reserve.pubkey,
+            lending_market.pubkey,
+            invalid_risk_authority.pubkey(),
+            ReservePriceMode::Stable,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_risk_authority], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidRiskAuthority as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_quote_currency_reserve() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(6_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_price_mode(
+            spl_token_lending::id(),
+            usdc_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            ReservePriceMode::Stable,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -0,0 +1,233 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::write_off_bad_debt,
+    math::{Decimal, Rate, TrySub},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+const USDC_LOAN: u64 = 12 * FRACTIONAL_TO_USDC;
+const USDC_LOAN_SOL_COLLATERAL: u64 = INITIAL_COLLATERAL_RATIO * LAMPORTS_TO_SOL;
+
+struct TestBadDebt {
+    lending_market: TestLendingMarket,
+    usdc_reserve: TestReserve,
+    usdc_obligation: TestObligation,
+}
+
+fn setup(test: &mut ProgramTest, collateral_amount: u64) -> TestBadDebt {
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(test);
+    let lending_market = add_lending_market(test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: TEST_RESERVE_CONFIG,
+            initial_borrow_rate: 1,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: USDC_LOAN * 101 / 100,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: TEST_RESERVE_CONFIG,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(Pubkey::new_unique()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_obligation = add_obligation(
+        test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    TestBadDebt {
+        lending_market,
+        usdc_reserve,
+        usdc_obligation,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(40_000);
+
+    let TestBadDebt {
+        lending_market,
+        usdc_reserve,
+        usdc_obligation,
+    } = setup(&mut test, 0);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_reserve = usdc_reserve.get_state(&mut banks_client).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[write_off_bad_debt(
+            spl_token_lending::id(),
+            usdc_obligation.pubkey,
+            usdc_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let obligation = usdc_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrowed_liquidity_wads, Decimal::zero());
+    assert_eq!(obligation.liquidation_auction_start_slot, 0);
+
+    // the written off debt includes the interest accrued by the obligation
+    let reserve = usdc_reserve.get_state(&mut banks_client).await;
+    let written_off = initial_reserve
+        .liquidity
+        .borrowed_amount_wads
+        .try_sub(reserve.liquidity.borrowed_amount_wads)
+        .unwrap();
+    assert_eq!(written_off, Decimal::from(USDC_LOAN * 101 / 100));
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        initial_reserve.liquidity.available_amount
+    );
+
+    // every collateral token is now worth less liquidity
+    assert_eq!(
+        reserve.collateral.mint_total_supply,
+        initial_reserve.collateral.mint_total_supply
+    );
+    assert!(
+        Rate::from(reserve.collateral_exchange_rate().unwrap())
+            > Rate::from(initial_reserve.collateral_exchange_rate().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn test_obligation_has_collateral() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let TestBadDebt {
+        lending_market,
+        usdc_reserve,
+        usdc_obligation,
+    } = setup(&mut test, USDC_LOAN_SOL_COLLATERAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[write_off_bad_debt(
+            spl_token_lending::id(),
+            usdc_obligation.pubkey,
+            usdc_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ObligationHasCollateral as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_risk_authority() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let TestBadDebt {
+        lending_market,
+        usdc_reserve,
+        usdc_obligation,
+    } = setup(&mut test, 0);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let not_risk_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[write_off_bad_debt(
+            spl_token_lending::id(),
+            usdc_obligation.pubkey,
+            usdc_reserve.pubkey,
+            lending_market.pubkey,
+            not_risk_authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_risk_authority], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidRiskAuthority as u32)
+        )
+    );
+}
@@ -0,0 +1,12 @@
+//! Reference external curve for the token-swap program, pricing swaps with
+//! the constant product invariant through the interface in
+//! `spl_token_swap::curve::external`
+#![deny(missing_docs)]
+
+mod entrypoint;
+pub mod processor;
+
+// Export current SDK types for downstream users building with a different SDK version
+pub use solana_program;
+
+solana_program::declare_id!("BpB8FZ4mSxEGyeKdy1thZTvnp1Z2TDcNNXWkCJkd6tJ1");
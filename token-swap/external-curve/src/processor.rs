@@ -0,0 +1,21 @@
+//! Program state processor
+
+use {
+    solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey},
+    spl_token_swap::curve::{constant_product, external},
+};
+
+/// Instruction processor
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    external::process_swap_without_fees(program_id, accounts, input, |request| {
+        constant_product::swap(
+            request.source_amount,
+            request.swap_source_amount,
+            request.swap_destination_amount,
+        )
+    })
+}
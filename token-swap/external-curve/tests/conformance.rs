@@ -0,0 +1,189 @@
+// Mark this test as BPF-only, since the compute budget is only enforced on BPF programs
+#![cfg(feature = "test-bpf")]
+
+use {
+    solana_program::{
+        instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+        system_instruction, system_program,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_token_swap::{
+        curve::{
+            calculator::{CurveCalculator, TradeDirection},
+            constant_product::ConstantProductCurve,
+            external::{
+                self, ExternalSwapRequest, ExternalSwapResult, EXTERNAL_CURVE_MAX_COMPUTE_UNITS,
+            },
+        },
+        error::SwapError,
+    },
+    spl_token_swap_external_curve::{id, processor::process_instruction},
+};
+
+fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new(
+        "spl_token_swap_external_curve",
+        id(),
+        processor!(process_instruction),
+    );
+    // every swap must be priced within the budget of the interface
+    program_test.set_bpf_compute_max_units(EXTERNAL_CURVE_MAX_COMPUTE_UNITS);
+    program_test
+}
+
+async fn create_result_account(context: &mut ProgramTestContext, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::create_account(
+            &context.payer.pubkey(),
+            &account.pubkey(),
+            1.max(Rent::default().minimum_balance(ExternalSwapResult::LEN)),
+            ExternalSwapResult::LEN as u64,
+            owner,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &account],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    account.pubkey()
+}
+
+async fn swap_without_fees(
+    context: &mut ProgramTestContext,
+    result_pubkey: &Pubkey,
+    request: ExternalSwapRequest,
+) -> Result<ExternalSwapResult, TransportError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[external::swap_without_fees(&id(), result_pubkey, request)],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await?;
+    let account = context
+        .banks_client
+        .get_account(*result_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    Ok(ExternalSwapResult::unpack_from_slice(&account.data).unwrap())
+}
+
+fn requests() -> Vec<ExternalSwapRequest> {
+    let mut requests = vec![];
+    for (source_amount, swap_source_amount, swap_destination_amount) in &[
+        (100, 1_000, 50_000),
+        (1, 1_000_000, 1_000_000),
+        (999_999, 1_000_000, 1_000_000),
+        (u64::MAX as u128, u64::MAX as u128, u64::MAX as u128),
+        (1_000_000_000, 5, u64::MAX as u128),
+    ] {
+        for trade_direction in &[TradeDirection::AtoB, TradeDirection::BtoA] {
+            requests.push(ExternalSwapRequest {
+                source_amount: *source_amount,
+                swap_source_amount: *swap_source_amount,
+                swap_destination_amount: *swap_destination_amount,
+                trade_direction: *trade_direction,
+            });
+        }
+    }
+    requests
+}
+
+#[tokio::test]
+async fn success_matches_builtin_curve() {
+    let mut context = program_test().start_with_context().await;
+    let result_pubkey = create_result_account(&mut context, &id()).await;
+
+    for request in requests() {
+        let result = swap_without_fees(&mut context, &result_pubkey, request)
+            .await
+            .unwrap();
+        assert_eq!(result.request, request);
+        assert_eq!(
+            result.checked_result(&request),
+            ConstantProductCurve {}.swap_without_fees(
+                request.source_amount,
+                request.swap_source_amount,
+                request.swap_destination_amount,
+                request.trade_direction,
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn success_deterministic() {
+    let mut context = program_test().start_with_context().await;
+    let result_pubkey = create_result_account(&mut context, &id()).await;
+    let requests = requests();
+
+    let first_result = swap_without_fees(&mut context, &result_pubkey, requests[0])
+        .await
+        .unwrap();
+    swap_without_fees(&mut context, &result_pubkey, requests[1])
+        .await
+        .unwrap();
+
+    // the same request in a later transaction gets the same result
+    context.last_blockhash = context
+        .banks_client
+        .get_new_blockhash(&context.last_blockhash)
+        .await
+        .unwrap()
+        .0;
+    let second_result = swap_without_fees(&mut context, &result_pubkey, requests[0])
+        .await
+        .unwrap();
+    assert_eq!(first_result, second_result);
+}
+
+#[tokio::test]
+async fn fail_result_account_not_owned_by_curve() {
+    let mut context = program_test().start_with_context().await;
+    let result_pubkey = create_result_account(&mut context, &system_program::id()).await;
+
+    let error = swap_without_fees(&mut context, &result_pubkey, requests()[0])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+}
+
+#[tokio::test]
+async fn fail_empty_pool() {
+    let mut context = program_test().start_with_context().await;
+    let result_pubkey = create_result_account(&mut context, &id()).await;
+
+    let request = ExternalSwapRequest {
+        swap_destination_amount: 0,
+        ..requests()[0]
+    };
+    let error = swap_without_fees(&mut context, &result_pubkey, request)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(SwapError::CalculationFailure as u32)
+        )
+    );
+}
@@ -73,6 +73,8 @@ const OWNER_SWAP_FEE = SWAP_FEE - HOST_SWAP_FEE;
 
 // Pool token amount minted on init
 const DEFAULT_POOL_TOKEN_AMOUNT = 1000000000;
+// Pool token amount locked forever on init, out of the amount minted
+const MINIMUM_LIQUIDITY = 1000;
 // Pool token amount to withdraw / deposit
 const POOL_TOKEN_AMOUNT = 10000000;
 
@@ -117,6 +119,13 @@ export async function createTokenSwap(): Promise<void> {
 
   console.log('creating pool account');
   tokenAccountPool = await tokenPool.createAccount(owner.publicKey);
+  console.log('creating locked liquidity account');
+  const lockedLiquidityAccount = await tokenPool.createAccount(
+    await TokenSwap.getLockedLiquidityOwner(
+      tokenSwapAccount.publicKey,
+      TOKEN_SWAP_PROGRAM_ID,
+    ),
+  );
   const ownerKey = SWAP_PROGRAM_OWNER_FEE_ADDRESS || owner.publicKey.toString();
   feeAccount = await tokenPool.createAccount(new PublicKey(ownerKey));
 
@@ -164,6 +173,7 @@ export async function createTokenSwap(): Promise<void> {
     mintB.publicKey,
     feeAccount,
     tokenAccountPool,
+    lockedLiquidityAccount,
     TOKEN_SWAP_PROGRAM_ID,
     TOKEN_PROGRAM_ID,
     nonce,
@@ -336,7 +346,8 @@ export async function withdrawAllTokenTypes(): Promise<void> {
 
   let info = await tokenPool.getAccountInfo(tokenAccountPool);
   assert(
-    info.amount.toNumber() == DEFAULT_POOL_TOKEN_AMOUNT - POOL_TOKEN_AMOUNT,
+    info.amount.toNumber() ==
+      DEFAULT_POOL_TOKEN_AMOUNT - MINIMUM_LIQUIDITY - POOL_TOKEN_AMOUNT,
   );
   assert(swapTokenA.amount.toNumber() == currentSwapTokenA - tokenA);
   currentSwapTokenA -= tokenA;
@@ -479,7 +490,8 @@ export async function swap(): Promise<void> {
 
   info = await tokenPool.getAccountInfo(tokenAccountPool);
   assert(
-    info.amount.toNumber() == DEFAULT_POOL_TOKEN_AMOUNT - POOL_TOKEN_AMOUNT,
+    info.amount.toNumber() ==
+      DEFAULT_POOL_TOKEN_AMOUNT - MINIMUM_LIQUIDITY - POOL_TOKEN_AMOUNT,
   );
 
   info = await tokenPool.getAccountInfo(feeAccount);
@@ -83,6 +83,11 @@ export const TokenSwapLayout: typeof BufferLayout.Structure = BufferLayout.struc
     Layout.uint64('hostFeeDenominator'),
     BufferLayout.u8('curveType'),
     BufferLayout.blob(32, 'curveParameters'),
+    BufferLayout.u8('isImmutable'),
+    Layout.uint64('loyaltyFullRebateSlots'),
+    Layout.uint64('loyaltyRebateNumerator'),
+    Layout.uint64('loyaltyRebateDenominator'),
+    Layout.publicKey('swapConfig'),
   ],
 );
 
@@ -90,6 +95,7 @@ export const CurveType = Object.freeze({
   ConstantProduct: 0, // Constant product curve, Uniswap-style
   ConstantPrice: 1, // Constant price curve, always X amount of A token for 1 B token, where X is defined at init
   Offset: 3, // Offset curve, like Uniswap, but with an additional offset on the token B side
+  External: 4, // Curve priced by an external program, whose id is the curve parameters
 });
 
 /**
@@ -275,6 +281,27 @@ export class TokenSwap {
     );
   }
 
+  /**
+   * Get the owner of the account locking the minimum liquidity of a swap
+   *
+   * The token-swap program never signs for this address, so pool tokens sent
+   * to an account it owns can never be moved.
+   *
+   * @param tokenSwap The token swap account
+   * @param swapProgramId The program ID of the token-swap program
+   * @return Address to create the swap's locked liquidity account for
+   */
+  static async getLockedLiquidityOwner(
+    tokenSwap: PublicKey,
+    swapProgramId: PublicKey,
+  ): Promise<PublicKey> {
+    const [owner] = await PublicKey.findProgramAddress(
+      [tokenSwap.toBuffer(), Buffer.from('locked_liquidity')],
+      swapProgramId,
+    );
+    return owner;
+  }
+
   static createInitSwapInstruction(
     tokenSwapAccount: Account,
     authority: PublicKey,
@@ -283,6 +310,7 @@ export class TokenSwap {
     tokenPool: PublicKey,
     feeAccount: PublicKey,
     tokenAccountPool: PublicKey,
+    lockedLiquidityAccount: PublicKey,
     tokenProgramId: PublicKey,
     swapProgramId: PublicKey,
     nonce: number,
@@ -295,6 +323,10 @@ export class TokenSwap {
     hostFeeNumerator: number,
     hostFeeDenominator: number,
     curveType: number,
+    isImmutable: boolean = false,
+    loyaltyFullRebateSlots: number = 0,
+    loyaltyRebateNumerator: number = 0,
+    loyaltyRebateDenominator: number = 0,
   ): TransactionInstruction {
     const keys = [
       {pubkey: tokenSwapAccount.publicKey, isSigner: false, isWritable: true},
@@ -304,6 +336,7 @@ export class TokenSwap {
       {pubkey: tokenPool, isSigner: false, isWritable: true},
       {pubkey: feeAccount, isSigner: false, isWritable: false},
       {pubkey: tokenAccountPool, isSigner: false, isWritable: true},
+      {pubkey: lockedLiquidityAccount, isSigner: false, isWritable: true},
       {pubkey: tokenProgramId, isSigner: false, isWritable: false},
     ];
     const commandDataLayout = BufferLayout.struct([
@@ -319,6 +352,10 @@ export class TokenSwap {
       BufferLayout.nu64('hostFeeDenominator'),
       BufferLayout.u8('curveType'),
       BufferLayout.blob(32, 'curveParameters'),
+      BufferLayout.u8('isImmutable'),
+      BufferLayout.nu64('loyaltyFullRebateSlots'),
+      BufferLayout.nu64('loyaltyRebateNumerator'),
+      BufferLayout.nu64('loyaltyRebateDenominator'),
     ]);
     let data = Buffer.alloc(1024);
     {
@@ -335,6 +372,10 @@ export class TokenSwap {
           hostFeeNumerator,
           hostFeeDenominator,
           curveType,
+          isImmutable: isImmutable ? 1 : 0,
+          loyaltyFullRebateSlots,
+          loyaltyRebateNumerator,
+          loyaltyRebateDenominator,
         },
         data,
       );
@@ -435,6 +476,7 @@ export class TokenSwap {
    * @param tokenAccountB: The token swap's Token B account
    * @param poolToken The pool token
    * @param tokenAccountPool The token swap's pool token account
+   * @param lockedLiquidityAccount Pool token account owned by `getLockedLiquidityOwner`, where the minimum liquidity is locked
    * @param tokenProgramId The program ID of the token program
    * @param swapProgramId The program ID of the token-swap program
    * @param feeNumerator Numerator of the fee ratio
@@ -453,6 +495,7 @@ export class TokenSwap {
     mintB: PublicKey,
     feeAccount: PublicKey,
     tokenAccountPool: PublicKey,
+    lockedLiquidityAccount: PublicKey,
     swapProgramId: PublicKey,
     tokenProgramId: PublicKey,
     nonce: number,
@@ -514,6 +557,7 @@ export class TokenSwap {
       poolToken,
       feeAccount,
       tokenAccountPool,
+      lockedLiquidityAccount,
       tokenProgramId,
       swapProgramId,
       nonce,
@@ -48,6 +48,11 @@ declare module '@solana/spl-token-swap' {
       connection: Connection,
     ): Promise<number>;
 
+    static getLockedLiquidityOwner(
+      tokenSwap: PublicKey,
+      swapProgramId: PublicKey,
+    ): Promise<PublicKey>;
+
     static createInitSwapInstruction(
       tokenSwapAccount: Account,
       authority: PublicKey,
@@ -56,6 +61,7 @@ declare module '@solana/spl-token-swap' {
       tokenPool: PublicKey,
       feeAccount: PublicKey,
       tokenAccountPool: PublicKey,
+      lockedLiquidityAccount: PublicKey,
       tokenProgramId: PublicKey,
       swapProgramId: PublicKey,
       nonce: number,
@@ -68,6 +74,10 @@ declare module '@solana/spl-token-swap' {
       hostFeeNumerator: number,
       hostFeeDenominator: number,
       curveType: number,
+      isImmutable?: boolean,
+      loyaltyFullRebateSlots?: number,
+      loyaltyRebateNumerator?: number,
+      loyaltyRebateDenominator?: number,
     ): TransactionInstruction;
 
     static loadTokenSwap(
@@ -89,6 +99,7 @@ declare module '@solana/spl-token-swap' {
       mintB: PublicKey,
       feeAccount: PublicKey,
       tokenAccountPool: PublicKey,
+      lockedLiquidityAccount: PublicKey,
       swapProgramId: PublicKey,
       tokenProgramId: PublicKey,
       nonce: number,
@@ -45,6 +45,11 @@ declare module '@solana/spl-token-swap' {
       connection: Connection,
     ): Promise<number>;
 
+    static getLockedLiquidityOwner(
+      tokenSwap: PublicKey,
+      swapProgramId: PublicKey,
+    ): Promise<PublicKey>;
+
     static createInitSwapInstruction(
       tokenSwapAccount: Account,
       authority: PublicKey,
@@ -53,6 +58,7 @@ declare module '@solana/spl-token-swap' {
       tokenPool: PublicKey,
       feeAccount: PublicKey,
       tokenAccountPool: PublicKey,
+      lockedLiquidityAccount: PublicKey,
       tokenProgramId: PublicKey,
       swapProgramId: PublicKey,
       nonce: number,
@@ -65,6 +71,10 @@ declare module '@solana/spl-token-swap' {
       hostFeeNumerator: number,
       hostFeeDenominator: number,
       curveType: number,
+      isImmutable?: boolean,
+      loyaltyFullRebateSlots?: number,
+      loyaltyRebateNumerator?: number,
+      loyaltyRebateDenominator?: number,
     ): TransactionInstruction;
 
     static loadTokenSwap(
@@ -86,6 +96,7 @@ declare module '@solana/spl-token-swap' {
       mintB: PublicKey,
       feeAccount: PublicKey,
       tokenAccountPool: PublicKey,
+      lockedLiquidityAccount: PublicKey,
       swapProgramId: PublicKey,
       tokenProgramId: PublicKey,
       nonce: number,
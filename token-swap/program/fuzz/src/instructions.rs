@@ -181,10 +181,14 @@ fn run_fuzz_instructions(fuzz_instructions: Vec<FuzzInstruction>) {
         }
     }
 
-    let pool_tokens = [&token_swap.pool_token_account, &token_swap.pool_fee_account]
-        .iter()
-        .map(|&x| get_token_balance(x))
-        .sum::<u64>() as u128;
+    let pool_tokens = [
+        &token_swap.pool_token_account,
+        &token_swap.pool_fee_account,
+        &token_swap.locked_liquidity_account,
+    ]
+    .iter()
+    .map(|&x| get_token_balance(x))
+    .sum::<u64>() as u128;
     let initial_pool_token_amount =
         pool_tokens + pool_accounts.values().map(get_token_balance).sum::<u64>() as u128;
     let initial_swap_token_a_amount = get_token_balance(&token_swap.token_a_account) as u128;
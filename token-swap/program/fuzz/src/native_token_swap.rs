@@ -5,11 +5,15 @@ use crate::native_processor::do_process_instruction;
 use crate::native_token;
 
 use spl_token_swap::{
-    curve::{base::SwapCurve, fees::Fees},
+    curve::{
+        base::SwapCurve,
+        fees::{Fees, LoyaltySchedule},
+    },
     instruction::{
         self, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Swap,
         WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
     },
+    processor::Processor,
     state::SwapVersion,
 };
 
@@ -27,6 +31,7 @@ pub struct NativeTokenSwap {
     pub pool_mint_account: NativeAccountData,
     pub pool_fee_account: NativeAccountData,
     pub pool_token_account: NativeAccountData,
+    pub locked_liquidity_account: NativeAccountData,
     pub token_a_account: NativeAccountData,
     pub token_a_mint_account: NativeAccountData,
     pub token_b_account: NativeAccountData,
@@ -63,6 +68,11 @@ impl NativeTokenSwap {
             native_token::create_token_account(&mut pool_mint_account, &user_account.key, 0);
         let mut pool_fee_account =
             native_token::create_token_account(&mut pool_mint_account, &user_account.key, 0);
+        let mut locked_liquidity_account = native_token::create_token_account(
+            &mut pool_mint_account,
+            &Processor::locked_liquidity_owner_id(&spl_token_swap::id(), &swap_account.key),
+            0,
+        );
         let mut token_a_mint_account = native_token::create_mint(&user_account.key);
         let mut token_a_account = native_token::create_token_account(
             &mut token_a_mint_account,
@@ -86,9 +96,12 @@ impl NativeTokenSwap {
             &pool_mint_account.key,
             &pool_fee_account.key,
             &pool_token_account.key,
+            &locked_liquidity_account.key,
             nonce,
             fees.clone(),
             swap_curve.clone(),
+            false,
+            LoyaltySchedule::default(),
         )
         .unwrap();
 
@@ -102,6 +115,7 @@ impl NativeTokenSwap {
                 pool_mint_account.as_account_info(),
                 pool_fee_account.as_account_info(),
                 pool_token_account.as_account_info(),
+                locked_liquidity_account.as_account_info(),
                 token_program_account.as_account_info(),
             ],
         )
@@ -117,6 +131,7 @@ impl NativeTokenSwap {
             pool_mint_account,
             pool_fee_account,
             pool_token_account,
+            locked_liquidity_account,
             token_a_account,
             token_a_mint_account,
             token_b_account,
@@ -9,6 +9,7 @@ use crate::curve::{
     calculator::{CurveCalculator, RoundDirection, SwapWithoutFeesResult, TradeDirection},
     constant_price::ConstantPriceCurve,
     constant_product::ConstantProductCurve,
+    external::ExternalCurve,
     fees::Fees,
     offset::OffsetCurve,
     stable::StableCurve,
@@ -29,6 +30,8 @@ pub enum CurveType {
     Stable,
     /// Offset curve, like Uniswap, but the token B side has a faked offset
     Offset,
+    /// Curve priced by an external program, see `curve::external`
+    External,
 }
 
 /// Encodes all results of swapping from a source token to a destination token
@@ -48,6 +51,17 @@ pub struct SwapResult {
     pub owner_fee: u128,
 }
 
+/// Fees debited from the source amount of a swap
+#[derive(Debug, PartialEq)]
+pub struct SwapFees {
+    /// Amount of source tokens going to pool holders
+    pub trade_fee: u128,
+    /// Amount of source tokens going to owner
+    pub owner_fee: u128,
+    /// Amount of source token left to swap
+    pub source_amount_less_fees: u128,
+}
+
 /// Concrete struct to wrap around the trait object which performs calculation.
 #[repr(C)]
 #[derive(Debug)]
@@ -71,23 +85,48 @@ impl SwapCurve {
         trade_direction: TradeDirection,
         fees: &Fees,
     ) -> Option<SwapResult> {
-        // debit the fee to calculate the amount swapped
+        let swap_fees = Self::swap_fees(source_amount, fees)?;
+        let swap_without_fees = self.calculator.swap_without_fees(
+            swap_fees.source_amount_less_fees,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )?;
+        Self::swap_result(
+            &swap_fees,
+            swap_without_fees,
+            swap_source_amount,
+            swap_destination_amount,
+        )
+    }
+
+    /// Debit the fees from the source amount of a swap
+    pub fn swap_fees(source_amount: u128, fees: &Fees) -> Option<SwapFees> {
         let trade_fee = fees.trading_fee(source_amount)?;
         let owner_fee = fees.owner_trading_fee(source_amount)?;
 
         let total_fees = trade_fee.checked_add(owner_fee)?;
         let source_amount_less_fees = source_amount.checked_sub(total_fees)?;
+        Some(SwapFees {
+            trade_fee,
+            owner_fee,
+            source_amount_less_fees,
+        })
+    }
 
+    /// Add the fees back to a swap calculated without them
+    pub fn swap_result(
+        swap_fees: &SwapFees,
+        swap_without_fees: SwapWithoutFeesResult,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+    ) -> Option<SwapResult> {
         let SwapWithoutFeesResult {
             source_amount_swapped,
             destination_amount_swapped,
-        } = self.calculator.swap_without_fees(
-            source_amount_less_fees,
-            swap_source_amount,
-            swap_destination_amount,
-            trade_direction,
-        )?;
+        } = swap_without_fees;
 
+        let total_fees = swap_fees.trade_fee.checked_add(swap_fees.owner_fee)?;
         let source_amount_swapped = source_amount_swapped.checked_add(total_fees)?;
         Some(SwapResult {
             new_swap_source_amount: swap_source_amount.checked_add(source_amount_swapped)?,
@@ -95,8 +134,8 @@ impl SwapCurve {
                 .checked_sub(destination_amount_swapped)?,
             source_amount_swapped,
             destination_amount_swapped,
-            trade_fee,
-            owner_fee,
+            trade_fee: swap_fees.trade_fee,
+            owner_fee: swap_fees.owner_fee,
         })
     }
 
@@ -194,6 +233,7 @@ impl Pack for SwapCurve {
                 }
                 CurveType::Stable => Box::new(StableCurve::unpack_from_slice(calculator)?),
                 CurveType::Offset => Box::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::External => Box::new(ExternalCurve::unpack_from_slice(calculator)?),
             },
         })
     }
@@ -224,6 +264,7 @@ impl TryFrom<u8> for CurveType {
             1 => Ok(CurveType::ConstantPrice),
             2 => Ok(CurveType::Stable),
             3 => Ok(CurveType::Offset),
+            4 => Ok(CurveType::External),
             _ => Err(ProgramError::InvalidAccountData),
         }
     }
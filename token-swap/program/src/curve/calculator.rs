@@ -1,6 +1,9 @@
 //! Swap calculations
 
-use {crate::error::SwapError, spl_math::precise_number::PreciseNumber, std::fmt::Debug};
+use {
+    crate::error::SwapError, solana_program::pubkey::Pubkey,
+    spl_math::precise_number::PreciseNumber, std::fmt::Debug,
+};
 
 /// Initial amount of pool tokens for swap contract, hard-coded to something
 /// "sensible" given a maximum of u128.
@@ -8,6 +11,12 @@ use {crate::error::SwapError, spl_math::precise_number::PreciseNumber, std::fmt:
 /// input amounts, and Balancer uses 100 * 10 ^ 18.
 pub const INITIAL_SWAP_POOL_AMOUNT: u128 = 1_000_000_000;
 
+/// Amount of the initial pool tokens locked forever when a swap is
+/// initialized, as Uniswap does, so that the pool token supply can never get
+/// small enough for the first liquidity provider to inflate the value of a
+/// pool token and round later deposits down to nothing.
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+
 /// Hardcode the number of token types in a pool, used to calculate the
 /// equivalent pool tokens for the owner trading fee.
 pub const TOKENS_IN_POOL: u128 = 2;
@@ -151,6 +160,13 @@ pub trait CurveCalculator: Debug + DynPack {
         true
     }
 
+    /// Program that prices swaps on this curve, for curves calculated outside
+    /// of the swap program, see `curve::external`.  Single-sided deposits and
+    /// withdrawals are not supported on these curves.
+    fn external_program_id(&self) -> Option<Pubkey> {
+        None
+    }
+
     /// Calculates the total normalized value of the curve given the liquidity
     /// parameters.
     ///
@@ -0,0 +1,436 @@
+//! Curve calculated by an external program through cross-program invocation
+//!
+//! Swaps on a pool with an external curve are priced by the curve program
+//! named in the curve parameters.  The swap program debits the fees, then
+//! invokes the curve program with a `SwapWithoutFees` instruction carrying an
+//! `ExternalSwapRequest`.  The curve program writes an `ExternalSwapResult`
+//! into the result account it owns, and the swap program reads it back once
+//! the invocation returns.
+//!
+//! Curve programs must follow these rules, or swaps through them can fail or
+//! misprice:
+//!
+//! * The result must only depend on the request.  The curve may not read
+//!   sysvars, the clock, or any account other than the result account, so
+//!   that the same request always gives the same result.
+//! * The whole instruction must fit in `EXTERNAL_CURVE_MAX_COMPUTE_UNITS`,
+//!   since it is paid out of the budget of the swap instruction.
+//! * The result must echo the request, and may not swap more than the source
+//!   amount, nor all of the destination liquidity.  The swap program rejects
+//!   any other result.
+//!
+//! `process_swap_without_fees` takes care of the accounts and the encoding
+//! for curve programs, which only need to supply the calculation.
+
+use {
+    crate::{
+        curve::calculator::{
+            CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult, TradeDirection,
+            TradingTokenResult,
+        },
+        curve::constant_product::{pool_tokens_to_trading_tokens, trading_tokens_to_pool_tokens},
+        error::SwapError,
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+        pubkey::Pubkey,
+    },
+    spl_math::precise_number::PreciseNumber,
+};
+
+/// Maximum compute units an external curve may use to price a swap
+pub const EXTERNAL_CURVE_MAX_COMPUTE_UNITS: u64 = 25_000;
+
+/// Curve whose swaps are priced by an external program
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExternalCurve {
+    /// Program implementing the external curve interface
+    pub curve_program_id: Pubkey,
+}
+
+impl CurveCalculator for ExternalCurve {
+    /// Swaps can only be priced by invoking the curve program, see
+    /// `Processor::process_swap`
+    fn swap_without_fees(
+        &self,
+        _source_amount: u128,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        None
+    }
+
+    /// Deposits and withdrawals of both tokens are proportional to the pool,
+    /// whatever the curve
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// Only used to convert the owner trading fee into pool tokens, valuing
+    /// both sides of the pool equally.  Single-sided deposits and withdrawals
+    /// are not supported on external curves.
+    fn trading_tokens_to_pool_tokens(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        trading_tokens_to_pool_tokens(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            round_direction,
+        )
+    }
+
+    fn validate(&self) -> Result<(), SwapError> {
+        if self.curve_program_id == Pubkey::default() {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn external_program_id(&self) -> Option<Pubkey> {
+        Some(self.curve_program_id)
+    }
+
+    /// The value of the pool is only known to the curve program
+    fn normalized_value(
+        &self,
+        _swap_token_a_amount: u128,
+        _swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        None
+    }
+}
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ExternalCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ExternalCurve {}
+impl Pack for ExternalCurve {
+    const LEN: usize = 32;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ExternalCurve, ProgramError> {
+        let curve_program_id = array_ref![input, 0, 32];
+        Ok(Self {
+            curve_program_id: Pubkey::new_from_array(*curve_program_id),
+        })
+    }
+}
+
+impl DynPack for ExternalCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let curve_program_id = array_mut_ref![output, 0, 32];
+        curve_program_id.copy_from_slice(self.curve_program_id.as_ref());
+    }
+}
+
+/// Swap priced by an external curve, once fees are debited
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExternalSwapRequest {
+    /// Amount of source token to swap
+    pub source_amount: u128,
+    /// Amount of source token in the pool
+    pub swap_source_amount: u128,
+    /// Amount of destination token in the pool
+    pub swap_destination_amount: u128,
+    /// Whether token A or token B is the source
+    pub trade_direction: TradeDirection,
+}
+
+impl Sealed for ExternalSwapRequest {}
+impl Pack for ExternalSwapRequest {
+    const LEN: usize = 49;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 49];
+        let (source_amount, swap_source_amount, swap_destination_amount, trade_direction) =
+            mut_array_refs![output, 16, 16, 16, 1];
+        *source_amount = self.source_amount.to_le_bytes();
+        *swap_source_amount = self.swap_source_amount.to_le_bytes();
+        *swap_destination_amount = self.swap_destination_amount.to_le_bytes();
+        trade_direction[0] = self.trade_direction as u8;
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ExternalSwapRequest, ProgramError> {
+        let input = array_ref![input, 0, 49];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (source_amount, swap_source_amount, swap_destination_amount, trade_direction) =
+            array_refs![input, 16, 16, 16, 1];
+        Ok(Self {
+            source_amount: u128::from_le_bytes(*source_amount),
+            swap_source_amount: u128::from_le_bytes(*swap_source_amount),
+            swap_destination_amount: u128::from_le_bytes(*swap_destination_amount),
+            trade_direction: match trade_direction[0] {
+                0 => TradeDirection::AtoB,
+                1 => TradeDirection::BtoA,
+                _ => return Err(ProgramError::InvalidInstructionData),
+            },
+        })
+    }
+}
+
+/// Result written by an external curve into its result account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExternalSwapResult {
+    /// The request this result answers
+    pub request: ExternalSwapRequest,
+    /// Amount of source token swapped
+    pub source_amount_swapped: u128,
+    /// Amount of destination token swapped
+    pub destination_amount_swapped: u128,
+}
+
+impl ExternalSwapResult {
+    /// The result as a swap without fees, if it is a valid answer to the
+    /// request
+    pub fn checked_result(&self, request: &ExternalSwapRequest) -> Option<SwapWithoutFeesResult> {
+        if self.request != *request
+            || self.source_amount_swapped == 0
+            || self.source_amount_swapped > request.source_amount
+            || self.destination_amount_swapped == 0
+            || self.destination_amount_swapped >= request.swap_destination_amount
+        {
+            return None;
+        }
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: self.source_amount_swapped,
+            destination_amount_swapped: self.destination_amount_swapped,
+        })
+    }
+}
+
+impl Sealed for ExternalSwapResult {}
+impl Pack for ExternalSwapResult {
+    const LEN: usize = 81;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 81];
+        let (request, source_amount_swapped, destination_amount_swapped) =
+            mut_array_refs![output, 49, 16, 16];
+        self.request.pack_into_slice(&mut request[..]);
+        *source_amount_swapped = self.source_amount_swapped.to_le_bytes();
+        *destination_amount_swapped = self.destination_amount_swapped.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ExternalSwapResult, ProgramError> {
+        let input = array_ref![input, 0, 81];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (request, source_amount_swapped, destination_amount_swapped) =
+            array_refs![input, 49, 16, 16];
+        Ok(Self {
+            request: ExternalSwapRequest::unpack_from_slice(request)?,
+            source_amount_swapped: u128::from_le_bytes(*source_amount_swapped),
+            destination_amount_swapped: u128::from_le_bytes(*destination_amount_swapped),
+        })
+    }
+}
+
+/// Instructions every external curve program must support
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExternalCurveInstruction {
+    ///   Price a swap, writing an `ExternalSwapResult` into the result account
+    ///
+    ///   0. `[writable]` Result account, owned by the curve program, of
+    ///   `ExternalSwapResult::LEN` bytes
+    SwapWithoutFees(ExternalSwapRequest),
+}
+
+impl ExternalCurveInstruction {
+    /// Unpacks a byte buffer into an [ExternalCurveInstruction](enum.ExternalCurveInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input.split_first().ok_or(SwapError::InvalidInstruction)?;
+        Ok(match tag {
+            0 if rest.len() >= ExternalSwapRequest::LEN => {
+                Self::SwapWithoutFees(ExternalSwapRequest::unpack_from_slice(rest)?)
+            }
+            _ => return Err(SwapError::InvalidInstruction.into()),
+        })
+    }
+
+    /// Packs an [ExternalCurveInstruction](enum.ExternalCurveInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            Self::SwapWithoutFees(request) => {
+                buf.push(0);
+                let mut packed_request = [0u8; ExternalSwapRequest::LEN];
+                request.pack_into_slice(&mut packed_request);
+                buf.extend_from_slice(&packed_request);
+            }
+        }
+        buf
+    }
+}
+
+/// Creates a 'SwapWithoutFees' instruction for an external curve program.
+pub fn swap_without_fees(
+    curve_program_id: &Pubkey,
+    result_pubkey: &Pubkey,
+    request: ExternalSwapRequest,
+) -> Instruction {
+    Instruction {
+        program_id: *curve_program_id,
+        accounts: vec![AccountMeta::new(*result_pubkey, false)],
+        data: ExternalCurveInstruction::SwapWithoutFees(request).pack(),
+    }
+}
+
+/// Processes an external curve instruction for a curve program, pricing
+/// swaps with `calculate`
+pub fn process_swap_without_fees<F>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+    calculate: F,
+) -> ProgramResult
+where
+    F: Fn(&ExternalSwapRequest) -> Option<SwapWithoutFeesResult>,
+{
+    let ExternalCurveInstruction::SwapWithoutFees(request) =
+        ExternalCurveInstruction::unpack(input)?;
+    let account_info_iter = &mut accounts.iter();
+    let result_info = next_account_info(account_info_iter)?;
+    if result_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if result_info.data_len() != ExternalSwapResult::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let SwapWithoutFeesResult {
+        source_amount_swapped,
+        destination_amount_swapped,
+    } = calculate(&request).ok_or(SwapError::CalculationFailure)?;
+    let result = ExternalSwapResult {
+        request,
+        source_amount_swapped,
+        destination_amount_swapped,
+    };
+    result.pack_into_slice(&mut result_info.data.borrow_mut());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ExternalSwapRequest {
+        ExternalSwapRequest {
+            source_amount: 100,
+            swap_source_amount: 1_000,
+            swap_destination_amount: 50_000,
+            trade_direction: TradeDirection::BtoA,
+        }
+    }
+
+    #[test]
+    fn pack_external_curve() {
+        let curve = ExternalCurve {
+            curve_program_id: Pubkey::new_unique(),
+        };
+
+        let mut packed = [0u8; ExternalCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed[..]);
+        let unpacked = ExternalCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+
+        let packed = curve.curve_program_id.to_bytes();
+        let unpacked = ExternalCurve::unpack(&packed).unwrap();
+        assert_eq!(curve, unpacked);
+    }
+
+    #[test]
+    fn pack_external_curve_instruction() {
+        let check = ExternalCurveInstruction::SwapWithoutFees(request());
+        let packed = check.pack();
+        let mut expect = vec![0u8];
+        expect.extend_from_slice(&100u128.to_le_bytes());
+        expect.extend_from_slice(&1_000u128.to_le_bytes());
+        expect.extend_from_slice(&50_000u128.to_le_bytes());
+        expect.push(1);
+        assert_eq!(packed, expect);
+        let unpacked = ExternalCurveInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        expect[49] = 2;
+        assert!(ExternalCurveInstruction::unpack(&expect).is_err());
+        assert!(ExternalCurveInstruction::unpack(&expect[..10]).is_err());
+    }
+
+    #[test]
+    fn checked_result() {
+        let request = request();
+        let result = ExternalSwapResult {
+            request,
+            source_amount_swapped: 100,
+            destination_amount_swapped: 4_545,
+        };
+        let mut packed = [0u8; ExternalSwapResult::LEN];
+        result.pack_into_slice(&mut packed);
+        assert_eq!(
+            ExternalSwapResult::unpack_from_slice(&packed).unwrap(),
+            result
+        );
+        assert_eq!(
+            result.checked_result(&request),
+            Some(SwapWithoutFeesResult {
+                source_amount_swapped: 100,
+                destination_amount_swapped: 4_545,
+            })
+        );
+
+        // stale results, for another request, are rejected
+        let other_request = ExternalSwapRequest {
+            source_amount: 99,
+            ..request
+        };
+        assert_eq!(result.checked_result(&other_request), None);
+        // as are results taking too much or emptying the pool
+        let greedy = ExternalSwapResult {
+            source_amount_swapped: 101,
+            ..result
+        };
+        assert_eq!(greedy.checked_result(&request), None);
+        let draining = ExternalSwapResult {
+            destination_amount_swapped: 50_000,
+            ..result
+        };
+        assert_eq!(draining.checked_result(&request), None);
+        let empty = ExternalSwapResult {
+            destination_amount_swapped: 0,
+            ..result
+        };
+        assert_eq!(empty.checked_result(&request), None);
+    }
+}
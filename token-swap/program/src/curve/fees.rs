@@ -125,6 +125,92 @@ impl Fees {
     }
 }
 
+/// Optional loyalty schedule, rebating part of the owner withdraw fee to
+/// liquidity providers depending on how long their pool tokens were deposited.
+/// The rebate grows linearly with the number of slots since the deposit, up to
+/// the full rebate after `full_rebate_slots`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoyaltySchedule {
+    /// Number of slots after which the full rebate applies, 0 disables the
+    /// schedule
+    pub full_rebate_slots: u64,
+    /// Full rebate numerator, as a proportion of the owner withdraw fee
+    pub rebate_numerator: u64,
+    /// Full rebate denominator
+    pub rebate_denominator: u64,
+}
+
+impl LoyaltySchedule {
+    /// Whether withdrawal fees decay with the time pool tokens were deposited
+    pub fn is_enabled(&self) -> bool {
+        self.full_rebate_slots > 0 && self.rebate_numerator > 0
+    }
+
+    /// Calculate the part of the owner withdraw fee `fee`, charged on
+    /// `pool_tokens`, that is rebated to a liquidity provider who deposited
+    /// `deposited_pool_tokens` of them `slots_deposited` slots ago
+    pub fn withdraw_fee_rebate(
+        &self,
+        fee: u128,
+        pool_tokens: u128,
+        deposited_pool_tokens: u128,
+        slots_deposited: u64,
+    ) -> Option<u128> {
+        if !self.is_enabled() || fee == 0 || pool_tokens == 0 {
+            return Some(0);
+        }
+        let slots_deposited = std::cmp::min(slots_deposited, self.full_rebate_slots);
+        let deposited_pool_tokens = std::cmp::min(deposited_pool_tokens, pool_tokens);
+        fee.checked_mul(deposited_pool_tokens)?
+            .checked_div(pool_tokens)?
+            .checked_mul(u128::try_from(self.rebate_numerator).ok()?)?
+            .checked_div(u128::try_from(self.rebate_denominator).ok()?)?
+            .checked_mul(u128::try_from(slots_deposited).ok()?)?
+            .checked_div(u128::try_from(self.full_rebate_slots).ok()?)
+    }
+
+    /// Validate that the schedule never rebates more than the fee
+    pub fn validate(&self) -> Result<(), SwapError> {
+        if self.rebate_numerator > self.rebate_denominator
+            || (self.rebate_numerator > 0 && self.full_rebate_slots == 0)
+        {
+            Err(SwapError::InvalidLoyaltySchedule)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl IsInitialized for LoyaltySchedule {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for LoyaltySchedule {}
+impl Pack for LoyaltySchedule {
+    const LEN: usize = 24;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 24];
+        let (full_rebate_slots, rebate_numerator, rebate_denominator) =
+            mut_array_refs![output, 8, 8, 8];
+        *full_rebate_slots = self.full_rebate_slots.to_le_bytes();
+        *rebate_numerator = self.rebate_numerator.to_le_bytes();
+        *rebate_denominator = self.rebate_denominator.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<LoyaltySchedule, ProgramError> {
+        let input = array_ref![input, 0, 24];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (full_rebate_slots, rebate_numerator, rebate_denominator) = array_refs![input, 8, 8, 8];
+        Ok(Self {
+            full_rebate_slots: u64::from_le_bytes(*full_rebate_slots),
+            rebate_numerator: u64::from_le_bytes(*rebate_numerator),
+            rebate_denominator: u64::from_le_bytes(*rebate_denominator),
+        })
+    }
+}
+
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
 impl IsInitialized for Fees {
     fn is_initialized(&self) -> bool {
@@ -225,4 +311,72 @@ mod tests {
         let unpacked = Fees::unpack_from_slice(&packed).unwrap();
         assert_eq!(fees, unpacked);
     }
+
+    #[test]
+    fn loyalty_schedule_rebate() {
+        let schedule = LoyaltySchedule {
+            full_rebate_slots: 1_000,
+            rebate_numerator: 1,
+            rebate_denominator: 2,
+        };
+        assert!(schedule.validate().is_ok());
+
+        // nothing is rebated right after a deposit
+        assert_eq!(schedule.withdraw_fee_rebate(100, 1_000, 1_000, 0), Some(0));
+        // the rebate grows linearly until the full rebate
+        assert_eq!(
+            schedule.withdraw_fee_rebate(100, 1_000, 1_000, 500),
+            Some(25)
+        );
+        assert_eq!(
+            schedule.withdraw_fee_rebate(100, 1_000, 1_000, 1_000),
+            Some(50)
+        );
+        assert_eq!(
+            schedule.withdraw_fee_rebate(100, 1_000, 1_000, 5_000),
+            Some(50)
+        );
+        // only deposited pool tokens get the rebate
+        assert_eq!(
+            schedule.withdraw_fee_rebate(100, 1_000, 200, 1_000),
+            Some(10)
+        );
+        assert_eq!(
+            schedule.withdraw_fee_rebate(100, 1_000, 5_000, 1_000),
+            Some(50)
+        );
+
+        let disabled = LoyaltySchedule::default();
+        assert!(disabled.validate().is_ok());
+        assert_eq!(
+            disabled.withdraw_fee_rebate(100, 1_000, 1_000, 1_000),
+            Some(0)
+        );
+
+        let too_large = LoyaltySchedule {
+            full_rebate_slots: 1_000,
+            rebate_numerator: 3,
+            rebate_denominator: 2,
+        };
+        assert_eq!(too_large.validate(), Err(SwapError::InvalidLoyaltySchedule));
+        let no_slots = LoyaltySchedule {
+            full_rebate_slots: 0,
+            rebate_numerator: 1,
+            rebate_denominator: 2,
+        };
+        assert_eq!(no_slots.validate(), Err(SwapError::InvalidLoyaltySchedule));
+    }
+
+    #[test]
+    fn pack_loyalty_schedule() {
+        let schedule = LoyaltySchedule {
+            full_rebate_slots: 216_000,
+            rebate_numerator: 3,
+            rebate_denominator: 4,
+        };
+        let mut packed = [0u8; LoyaltySchedule::LEN];
+        Pack::pack_into_slice(&schedule, &mut packed[..]);
+        let unpacked = LoyaltySchedule::unpack_from_slice(&packed).unwrap();
+        assert_eq!(schedule, unpacked);
+    }
 }
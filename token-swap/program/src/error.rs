@@ -91,6 +91,42 @@ pub enum SwapError {
     /// The operation cannot be performed on the given curve
     #[error("The operation cannot be performed on the given curve")]
     UnsupportedCurveOperation,
+    /// The provided loyalty schedule is invalid
+    #[error("The provided loyalty schedule is invalid")]
+    InvalidLoyaltySchedule,
+    /// The provided liquidity position does not belong to the swap or owner
+    #[error("The provided liquidity position does not belong to the swap or owner")]
+    IncorrectLiquidityPosition,
+    /// The provided swap config does not match the swap's
+    #[error("The provided swap config does not match the swap's")]
+    IncorrectSwapConfig,
+    /// Swaps are paused by the guardian of the swap config
+    #[error("Swaps are paused by the guardian of the swap config")]
+    SwapPaused,
+    /// The signer is not the guardian of the swap config
+    #[error("The signer is not the guardian of the swap config")]
+    InvalidGuardian,
+    /// The provided locked liquidity account is not owned by the swap's
+    /// locked liquidity address
+    #[error(
+        "The provided locked liquidity account is not owned by the swap's locked liquidity address"
+    )]
+    InvalidLockedLiquidityAccount,
+    /// The provided external curve program or result account does not
+    /// match the swap's curve
+    #[error(
+        "The provided external curve program or result account does not match the swap's curve"
+    )]
+    IncorrectExternalCurve,
+    /// The external curve did not give a valid result for the swap
+    #[error("The external curve did not give a valid result for the swap")]
+    InvalidExternalCurveResult,
+    /// An immutable swap cannot be paused by the guardian of a swap config
+    #[error("An immutable swap cannot be paused by the guardian of a swap config")]
+    ImmutableSwapConfig,
+    /// An immutable swap cannot be priced by an external curve program
+    #[error("An immutable swap cannot be priced by an external curve program")]
+    ImmutableExternalCurve,
 }
 impl From<SwapError> for ProgramError {
     fn from(e: SwapError) -> Self {
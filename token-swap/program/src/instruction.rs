@@ -2,7 +2,10 @@
 
 #![allow(clippy::too_many_arguments)]
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::curve::{
+    base::SwapCurve,
+    fees::{Fees, LoyaltySchedule},
+};
 use crate::error::SwapError;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -27,6 +30,10 @@ pub struct Initialize {
     /// swap curve info for pool, including CurveType and anything
     /// else that may be required
     pub swap_curve: SwapCurve,
+    /// if true, the swap refuses a swap config, so no guardian can pause it
+    pub immutable: bool,
+    /// rebate of the owner withdraw fee for long-term liquidity providers
+    pub loyalty_schedule: LoyaltySchedule,
 }
 
 /// Swap instruction data
@@ -92,6 +99,15 @@ pub struct WithdrawSingleTokenTypeExactAmountOut {
     pub maximum_pool_token_amount: u64,
 }
 
+/// SetSwapPaused instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetSwapPaused {
+    /// if true, swaps are rejected on all swaps using the config
+    pub paused: bool,
+}
+
 /// Instructions supported by the token swap program.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -106,8 +122,21 @@ pub enum SwapInstruction {
     ///   5. `[]` Pool Token Account to deposit trading and withdraw fees.
     ///   Must be empty, not owned by swap authority
     ///   6. `[writable]` Pool Token Account to deposit the initial pool token
-    ///   supply.  Must be empty, not owned by swap authority.
-    ///   7. '[]` Token program id
+    ///   supply, less `MINIMUM_LIQUIDITY`.  Must be empty, not owned by swap
+    ///   authority.
+    ///   7. `[writable]` Pool Token Account to lock `MINIMUM_LIQUIDITY` pool
+    ///   tokens in forever.  Must be owned by
+    ///   `Processor::locked_liquidity_owner_id`, without delegate or close
+    ///   authority.
+    ///   8. '[]` Token program id
+    ///   9. `[optional]` Swap config whose guardian can pause the swap, not
+    ///   allowed for immutable swaps
+    ///
+    ///   Immutable swaps cannot use an external curve either.
+    ///
+    ///   The `immutable` flag and the loyalty schedule are optional in the
+    ///   instruction data for compatibility with older clients, and default
+    ///   to `false` and no schedule.
     Initialize(Initialize),
 
     ///   Swap the tokens in the pool.
@@ -122,7 +151,13 @@ pub enum SwapInstruction {
     ///   7. `[writable]` Pool token mint, to generate trading fees
     ///   8. `[writable]` Fee account, to receive trading fees
     ///   9. '[]` Token program id
-    ///   10 `[optional, writable]` Host fee account to receive additional trading fees
+    ///   10 `[]` Swap config, only if the swap was initialized with one
+    ///   11. `[]` External curve program, only if the swap has an external curve
+    ///   12. `[writable]` External curve result account, owned by the curve program
+    ///   13. `[optional, writable]` Host fee account to receive additional trading fees
+    ///
+    ///   The swap config and external curve accounts are left out on swaps
+    ///   without them, moving the accounts after them up.
     Swap(Swap),
 
     ///   Deposit both types of tokens into the pool.  The output is a "pool"
@@ -139,6 +174,9 @@ pub enum SwapInstruction {
     ///   7. `[writable]` Pool MINT account, swap authority is the owner.
     ///   8. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
     ///   9. '[]` Token program id
+    ///   10 `[optional, writable]` Liquidity position of the owner of the pool
+    ///   account, recording the deposit for the loyalty schedule.  Created by
+    ///   the user with the swap program as owner, initialized on first use.
     DepositAllTokenTypes(DepositAllTokenTypes),
 
     ///   Withdraw both types of tokens from the pool at the current ratio, given
@@ -156,6 +194,8 @@ pub enum SwapInstruction {
     ///   8. `[writable]` token_b user Account to credit.
     ///   9. `[writable]` Fee account, to receive withdrawal fees
     ///   10 '[]` Token program id
+    ///   11 `[optional, writable]` Liquidity position of the owner of the
+    ///   SOURCE pool account, to rebate the withdraw fee
     WithdrawAllTokenTypes(WithdrawAllTokenTypes),
 
     ///   Deposit one type of tokens into the pool.  The output is a "pool" token
@@ -171,6 +211,9 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool MINT account, swap authority is the owner.
     ///   7. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
     ///   8. '[]` Token program id
+    ///   9. `[optional, writable]` Liquidity position of the owner of the pool
+    ///   account, recording the deposit for the loyalty schedule.  Created by
+    ///   the user with the swap program as owner, initialized on first use.
     DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
 
     ///   Withdraw one token type from the pool at the current ratio given the
@@ -186,7 +229,23 @@ pub enum SwapInstruction {
     ///   7. `[writable]` token_(A|B) User Account to credit
     ///   8. `[writable]` Fee account, to receive withdrawal fees
     ///   9. '[]` Token program id
+    ///   10 `[optional, writable]` Liquidity position of the owner of the
+    ///   SOURCE pool account, to rebate the withdraw fee
     WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Initializes a new swap config, which swaps can opt into at
+    ///   initialization so that its guardian can pause them all at once
+    ///
+    ///   0. `[writable]` New swap config, owned by the swap program.
+    ///   1. `[signer]` Guardian, allowed to pause and unpause swaps
+    InitializeSwapConfig,
+
+    ///   Pauses or unpauses swaps on all swaps using the swap config.
+    ///   Deposits and withdrawals are not affected.
+    ///
+    ///   0. `[writable]` Swap config
+    ///   1. `[signer]` Guardian of the swap config
+    SetSwapPaused(SetSwapPaused),
 }
 
 impl SwapInstruction {
@@ -196,14 +255,30 @@ impl SwapInstruction {
         Ok(match tag {
             0 => {
                 let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
-                if rest.len() >= Fees::LEN {
+                if rest.len() >= Fees::LEN + SwapCurve::LEN {
                     let (fees, rest) = rest.split_at(Fees::LEN);
                     let fees = Fees::unpack_unchecked(fees)?;
-                    let swap_curve = SwapCurve::unpack_unchecked(rest)?;
+                    let (swap_curve, rest) = rest.split_at(SwapCurve::LEN);
+                    let swap_curve = SwapCurve::unpack_unchecked(swap_curve)?;
+                    let (immutable, rest) = match rest.split_first() {
+                        None => (false, rest),
+                        Some((&0, rest)) => (false, rest),
+                        Some((&1, rest)) => (true, rest),
+                        _ => return Err(SwapError::InvalidInstruction.into()),
+                    };
+                    let loyalty_schedule = if rest.is_empty() {
+                        LoyaltySchedule::default()
+                    } else if rest.len() == LoyaltySchedule::LEN {
+                        LoyaltySchedule::unpack_unchecked(rest)?
+                    } else {
+                        return Err(SwapError::InvalidInstruction.into());
+                    };
                     Self::Initialize(Initialize {
                         nonce,
                         fees,
                         swap_curve,
+                        immutable,
+                        loyalty_schedule,
                     })
                 } else {
                     return Err(SwapError::InvalidInstruction.into());
@@ -253,6 +328,15 @@ impl SwapInstruction {
                     maximum_pool_token_amount,
                 })
             }
+            6 => Self::InitializeSwapConfig,
+            7 => {
+                let paused = match rest.first() {
+                    Some(&0) => false,
+                    Some(&1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetSwapPaused(SetSwapPaused { paused })
+            }
             _ => return Err(SwapError::InvalidInstruction.into()),
         })
     }
@@ -279,6 +363,8 @@ impl SwapInstruction {
                 nonce,
                 fees,
                 swap_curve,
+                immutable,
+                loyalty_schedule,
             }) => {
                 buf.push(0);
                 buf.push(*nonce);
@@ -288,6 +374,10 @@ impl SwapInstruction {
                 let mut swap_curve_slice = [0u8; SwapCurve::LEN];
                 Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
                 buf.extend_from_slice(&swap_curve_slice);
+                buf.push(*immutable as u8);
+                let mut loyalty_schedule_slice = [0u8; LoyaltySchedule::LEN];
+                Pack::pack_into_slice(loyalty_schedule, &mut loyalty_schedule_slice[..]);
+                buf.extend_from_slice(&loyalty_schedule_slice);
             }
             Self::Swap(Swap {
                 amount_in,
@@ -335,6 +425,13 @@ impl SwapInstruction {
                 buf.extend_from_slice(&destination_token_amount.to_le_bytes());
                 buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
             }
+            Self::InitializeSwapConfig => {
+                buf.push(6);
+            }
+            Self::SetSwapPaused(SetSwapPaused { paused }) => {
+                buf.push(7);
+                buf.push(*paused as u8);
+            }
         }
         buf
     }
@@ -351,14 +448,19 @@ pub fn initialize(
     pool_pubkey: &Pubkey,
     fee_pubkey: &Pubkey,
     destination_pubkey: &Pubkey,
+    locked_liquidity_pubkey: &Pubkey,
     nonce: u8,
     fees: Fees,
     swap_curve: SwapCurve,
+    immutable: bool,
+    loyalty_schedule: LoyaltySchedule,
 ) -> Result<Instruction, ProgramError> {
     let init_data = SwapInstruction::Initialize(Initialize {
         nonce,
         fees,
         swap_curve,
+        immutable,
+        loyalty_schedule,
     });
     let data = init_data.pack();
 
@@ -370,6 +472,7 @@ pub fn initialize(
         AccountMeta::new(*pool_pubkey, false),
         AccountMeta::new_readonly(*fee_pubkey, false),
         AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*locked_liquidity_pubkey, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
@@ -528,6 +631,96 @@ pub fn withdraw_single_token_type_exact_amount_out(
     })
 }
 
+/// Adds the user's liquidity position to a deposit or withdraw instruction,
+/// recording deposits or rebating withdraw fees under the loyalty schedule
+pub fn add_liquidity_position_account(instruction: &mut Instruction, position_pubkey: &Pubkey) {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*position_pubkey, false));
+}
+
+/// Adds the swap config to an 'initialize' instruction, opting the new swap
+/// into it, or to a 'swap' instruction on a swap that opted in.  The swap
+/// config goes right after the token program, ahead of any host fee account.
+pub fn add_swap_config_account(instruction: &mut Instruction, swap_config_pubkey: &Pubkey) {
+    // index of the account after the token program in 'swap' instructions,
+    // 'initialize' instructions end with the token program
+    const SWAP_CONFIG_INDEX: usize = 10;
+    let index = std::cmp::min(instruction.accounts.len(), SWAP_CONFIG_INDEX);
+    instruction
+        .accounts
+        .insert(index, AccountMeta::new_readonly(*swap_config_pubkey, false));
+}
+
+/// Adds the external curve program and its result account to a 'swap'
+/// instruction on a swap with an external curve.  They go after the token
+/// program and any swap config, ahead of any host fee account, so this must
+/// be called after `add_swap_config_account`.
+pub fn add_external_curve_accounts(
+    instruction: &mut Instruction,
+    curve_program_id: &Pubkey,
+    curve_result_pubkey: &Pubkey,
+) {
+    // index of the account after the token program
+    const EXTERNAL_CURVE_INDEX: usize = 10;
+    let mut index = std::cmp::min(instruction.accounts.len(), EXTERNAL_CURVE_INDEX);
+    // the swap config is read-only, unlike the host fee account
+    if instruction
+        .accounts
+        .get(index)
+        .map_or(false, |account| !account.is_writable)
+    {
+        index += 1;
+    }
+    instruction
+        .accounts
+        .insert(index, AccountMeta::new_readonly(*curve_program_id, false));
+    instruction
+        .accounts
+        .insert(index + 1, AccountMeta::new(*curve_result_pubkey, false));
+}
+
+/// Creates an 'initialize_swap_config' instruction.
+pub fn initialize_swap_config(
+    program_id: &Pubkey,
+    swap_config_pubkey: &Pubkey,
+    guardian_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::InitializeSwapConfig.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_config_pubkey, false),
+        AccountMeta::new_readonly(*guardian_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_swap_paused' instruction.
+pub fn set_swap_paused(
+    program_id: &Pubkey,
+    swap_config_pubkey: &Pubkey,
+    guardian_pubkey: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetSwapPaused(SetSwapPaused { paused }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_config_pubkey, false),
+        AccountMeta::new_readonly(*guardian_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
 /// Creates a 'swap' instruction.
 pub fn swap(
     program_id: &Pubkey,
@@ -614,10 +807,21 @@ mod tests {
             curve_type,
             calculator,
         };
+        let immutable = true;
+        let full_rebate_slots: u64 = 216_000;
+        let rebate_numerator: u64 = 1;
+        let rebate_denominator: u64 = 2;
+        let loyalty_schedule = LoyaltySchedule {
+            full_rebate_slots,
+            rebate_numerator,
+            rebate_denominator,
+        };
         let check = SwapInstruction::Initialize(Initialize {
             nonce,
             fees,
             swap_curve,
+            immutable,
+            loyalty_schedule,
         });
         let packed = check.pack();
         let mut expect = vec![0u8, nonce];
@@ -632,9 +836,36 @@ mod tests {
         expect.push(curve_type as u8);
         expect.extend_from_slice(&amp.to_le_bytes());
         expect.extend_from_slice(&[0u8; 24]);
+        expect.push(immutable as u8);
+        expect.extend_from_slice(&full_rebate_slots.to_le_bytes());
+        expect.extend_from_slice(&rebate_numerator.to_le_bytes());
+        expect.extend_from_slice(&rebate_denominator.to_le_bytes());
         assert_eq!(packed, expect);
         let unpacked = SwapInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        // older clients do not provide the loyalty schedule
+        expect.truncate(expect.len() - LoyaltySchedule::LEN);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        match unpacked {
+            SwapInstruction::Initialize(Initialize {
+                immutable,
+                loyalty_schedule,
+                ..
+            }) => {
+                assert!(immutable);
+                assert!(!loyalty_schedule.is_enabled());
+            }
+            _ => panic!("wrong instruction"),
+        }
+
+        // or the immutable flag
+        expect.pop();
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        match unpacked {
+            SwapInstruction::Initialize(Initialize { immutable, .. }) => assert!(!immutable),
+            _ => panic!("wrong instruction"),
+        }
     }
 
     #[test]
@@ -731,4 +962,29 @@ mod tests {
         let unpacked = SwapInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
     }
+
+    #[test]
+    fn pack_initialize_swap_config() {
+        let check = SwapInstruction::InitializeSwapConfig;
+        let packed = check.pack();
+        let expect = vec![6];
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_set_swap_paused() {
+        let check = SwapInstruction::SetSwapPaused(SetSwapPaused { paused: true });
+        let packed = check.pack();
+        let expect = vec![7, 1];
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        assert_eq!(
+            SwapInstruction::unpack(&[7, 2]),
+            Err(SwapError::InvalidInstruction.into())
+        );
+    }
 }
@@ -3,31 +3,37 @@
 use crate::constraints::{SwapConstraints, SWAP_CONSTRAINTS};
 use crate::{
     curve::{
-        base::SwapCurve,
-        calculator::{RoundDirection, TradeDirection},
-        fees::Fees,
+        base::{CurveType, SwapCurve},
+        calculator::{RoundDirection, SwapWithoutFeesResult, TradeDirection, MINIMUM_LIQUIDITY},
+        external::{self, ExternalSwapRequest, ExternalSwapResult},
+        fees::{Fees, LoyaltySchedule},
     },
     error::SwapError,
     instruction::{
-        DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Initialize, Swap,
+        DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Initialize, SetSwapPaused, Swap,
         SwapInstruction, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
     },
-    state::{SwapState, SwapV1, SwapVersion},
+    state::{LiquidityPosition, SwapConfig, SwapState, SwapV2, SwapVersion},
 };
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::{PrintProgramError, ProgramError},
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 use std::convert::TryInto;
 
+/// Seed of the address owning the pool tokens locked at initialization
+const LOCKED_LIQUIDITY_SEED: &[u8] = b"locked_liquidity";
+
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
@@ -67,6 +73,12 @@ impl Processor {
             .or(Err(SwapError::InvalidProgramAddress))
     }
 
+    /// Finds the owner of the account holding the swap's locked pool tokens.
+    /// The program never signs for this address, so the tokens can never move.
+    pub fn locked_liquidity_owner_id(program_id: &Pubkey, swap: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[&swap.to_bytes()[..32], LOCKED_LIQUIDITY_SEED], program_id).0
+    }
+
     /// Issue a spl_token `Burn` instruction.
     pub fn token_burn<'a>(
         swap: &Pubkey,
@@ -150,6 +162,25 @@ impl Processor {
         )
     }
 
+    /// Price a swap with an external curve, reading the result it writes
+    /// once the invocation returns
+    fn external_swap_without_fees<'a>(
+        curve_program: &AccountInfo<'a>,
+        curve_result: &AccountInfo<'a>,
+        request: ExternalSwapRequest,
+    ) -> Result<SwapWithoutFeesResult, ProgramError> {
+        let ix = external::swap_without_fees(curve_program.key, curve_result.key, request);
+        invoke(&ix, &[curve_result.clone(), curve_program.clone()])?;
+
+        let data = curve_result.data.borrow();
+        if data.len() < ExternalSwapResult::LEN {
+            return Err(SwapError::InvalidExternalCurveResult.into());
+        }
+        ExternalSwapResult::unpack_from_slice(&data)?
+            .checked_result(&request)
+            .ok_or_else(|| SwapError::InvalidExternalCurveResult.into())
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn check_accounts(
         token_swap: &dyn SwapState,
@@ -202,12 +233,113 @@ impl Processor {
         Ok(())
     }
 
+    /// Unpacks an initialized swap config
+    fn unpack_swap_config(
+        program_id: &Pubkey,
+        swap_config_info: &AccountInfo,
+    ) -> Result<SwapConfig, ProgramError> {
+        if swap_config_info.owner != program_id {
+            return Err(SwapError::IncorrectSwapConfig.into());
+        }
+        SwapConfig::unpack(&swap_config_info.data.borrow())
+    }
+
+    /// Unpacks the liquidity position of the owner of `token_account_info` in
+    /// the swap, starting a new position if it isn't initialized and
+    /// `allow_uninitialized` is set
+    fn unpack_liquidity_position(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        position_info: &AccountInfo,
+        token_account_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        allow_uninitialized: bool,
+    ) -> Result<LiquidityPosition, ProgramError> {
+        if position_info.owner != program_id {
+            return Err(SwapError::IncorrectLiquidityPosition.into());
+        }
+        let owner = Self::unpack_token_account(token_account_info, token_program_id)?.owner;
+        let position = LiquidityPosition::unpack_unchecked(&position_info.data.borrow())?;
+        if !position.is_initialized {
+            return if allow_uninitialized {
+                Ok(LiquidityPosition {
+                    is_initialized: true,
+                    swap: *swap_info.key,
+                    owner,
+                    ..LiquidityPosition::default()
+                })
+            } else {
+                Err(ProgramError::UninitializedAccount)
+            };
+        }
+        if position.swap != *swap_info.key || position.owner != owner {
+            return Err(SwapError::IncorrectLiquidityPosition.into());
+        }
+        Ok(position)
+    }
+
+    /// Records pool tokens minted to `destination_info` in the liquidity
+    /// position of its owner
+    fn record_liquidity_position_deposit(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        position_info: &AccountInfo,
+        destination_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        pool_token_amount: u64,
+    ) -> ProgramResult {
+        let mut position = Self::unpack_liquidity_position(
+            program_id,
+            swap_info,
+            position_info,
+            destination_info,
+            token_program_id,
+            true,
+        )?;
+        position
+            .deposit(pool_token_amount, Clock::get()?.slot)
+            .ok_or(SwapError::CalculationFailure)?;
+        LiquidityPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Calculates the owner withdraw fee on `pool_token_amount`, less the
+    /// rebate under the loyalty schedule for pool tokens deposited through
+    /// `position`
+    fn owner_withdraw_fee_after_rebate(
+        token_swap: &dyn SwapState,
+        position: Option<&LiquidityPosition>,
+        pool_token_amount: u128,
+    ) -> Result<u128, ProgramError> {
+        let withdraw_fee = token_swap
+            .fees()
+            .owner_withdraw_fee(pool_token_amount)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let rebate = match position {
+            Some(position) => token_swap
+                .loyalty_schedule()
+                .withdraw_fee_rebate(
+                    withdraw_fee,
+                    pool_token_amount,
+                    to_u128(position.pool_token_amount)?,
+                    Clock::get()?.slot.saturating_sub(position.deposit_slot),
+                )
+                .ok_or(SwapError::FeeCalculationFailure)?,
+            None => 0,
+        };
+        withdraw_fee
+            .checked_sub(rebate)
+            .ok_or_else(|| SwapError::FeeCalculationFailure.into())
+    }
+
     /// Processes an [Initialize](enum.Instruction.html).
     pub fn process_initialize(
         program_id: &Pubkey,
         nonce: u8,
         fees: Fees,
         swap_curve: SwapCurve,
+        immutable: bool,
+        loyalty_schedule: LoyaltySchedule,
         accounts: &[AccountInfo],
         swap_constraints: &Option<SwapConstraints>,
     ) -> ProgramResult {
@@ -219,6 +351,7 @@ impl Processor {
         let pool_mint_info = next_account_info(account_info_iter)?;
         let fee_account_info = next_account_info(account_info_iter)?;
         let destination_info = next_account_info(account_info_iter)?;
+        let locked_liquidity_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
 
         let token_program_id = *token_program_info.key;
@@ -233,6 +366,8 @@ impl Processor {
         let token_b = Self::unpack_token_account(token_b_info, &token_program_id)?;
         let fee_account = Self::unpack_token_account(fee_account_info, &token_program_id)?;
         let destination = Self::unpack_token_account(destination_info, &token_program_id)?;
+        let locked_liquidity =
+            Self::unpack_token_account(locked_liquidity_info, &token_program_id)?;
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
         if *authority_info.key != token_a.owner {
             return Err(SwapError::InvalidOwner.into());
@@ -278,6 +413,18 @@ impl Processor {
         if *pool_mint_info.key != fee_account.mint {
             return Err(SwapError::IncorrectPoolMint.into());
         }
+        if *pool_mint_info.key != locked_liquidity.mint {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if locked_liquidity.owner != Self::locked_liquidity_owner_id(program_id, swap_info.key) {
+            return Err(SwapError::InvalidLockedLiquidityAccount.into());
+        }
+        if locked_liquidity.delegate.is_some() {
+            return Err(SwapError::InvalidDelegate.into());
+        }
+        if locked_liquidity.close_authority.is_some() {
+            return Err(SwapError::InvalidCloseAuthority.into());
+        }
 
         if let Some(swap_constraints) = swap_constraints {
             let owner_key = swap_constraints
@@ -291,10 +438,39 @@ impl Processor {
             swap_constraints.validate_fees(&fees)?;
         }
         fees.validate()?;
+        loyalty_schedule.validate()?;
         swap_curve.calculator.validate()?;
+        // an upgrade of the curve program could reprice an immutable swap
+        if immutable && swap_curve.curve_type == CurveType::External {
+            return Err(SwapError::ImmutableExternalCurve.into());
+        }
+
+        let swap_config = match next_account_info(account_info_iter) {
+            Ok(swap_config_info) => {
+                // the guardian could halt an immutable swap at will
+                if immutable {
+                    return Err(SwapError::ImmutableSwapConfig.into());
+                }
+                Self::unpack_swap_config(program_id, swap_config_info)?;
+                *swap_config_info.key
+            }
+            Err(_) => Pubkey::default(),
+        };
 
         let initial_amount = swap_curve.calculator.new_pool_supply();
+        let destination_amount = initial_amount
+            .checked_sub(MINIMUM_LIQUIDITY)
+            .ok_or(SwapError::CalculationFailure)?;
 
+        Self::token_mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            locked_liquidity_info.clone(),
+            authority_info.clone(),
+            nonce,
+            to_u64(MINIMUM_LIQUIDITY)?,
+        )?;
         Self::token_mint_to(
             swap_info.key,
             token_program_info.clone(),
@@ -302,10 +478,10 @@ impl Processor {
             destination_info.clone(),
             authority_info.clone(),
             nonce,
-            to_u64(initial_amount)?,
+            to_u64(destination_amount)?,
         )?;
 
-        let obj = SwapVersion::SwapV1(SwapV1 {
+        let obj = SwapVersion::SwapV2(SwapV2 {
             is_initialized: true,
             nonce,
             token_program_id,
@@ -317,6 +493,9 @@ impl Processor {
             pool_fee_account: *fee_account_info.key,
             fees,
             swap_curve,
+            is_immutable: immutable,
+            loyalty_schedule,
+            swap_config,
         });
         SwapVersion::pack(obj, &mut swap_info.data.borrow_mut())?;
         Ok(())
@@ -378,6 +557,15 @@ impl Processor {
         if *token_program_info.key != *token_swap.token_program_id() {
             return Err(SwapError::IncorrectTokenProgramId.into());
         }
+        if let Some(swap_config) = token_swap.swap_config() {
+            let swap_config_info = next_account_info(account_info_iter)?;
+            if swap_config_info.key != swap_config {
+                return Err(SwapError::IncorrectSwapConfig.into());
+            }
+            if Self::unpack_swap_config(program_id, swap_config_info)?.is_paused {
+                return Err(SwapError::SwapPaused.into());
+            }
+        }
 
         let source_account =
             Self::unpack_token_account(swap_source_info, &token_swap.token_program_id())?;
@@ -390,16 +578,44 @@ impl Processor {
         } else {
             TradeDirection::BtoA
         };
-        let result = token_swap
-            .swap_curve()
-            .swap(
+        let swap_curve = token_swap.swap_curve();
+        let result = match swap_curve.calculator.external_program_id() {
+            Some(curve_program_id) => {
+                let curve_program_info = next_account_info(account_info_iter)?;
+                let curve_result_info = next_account_info(account_info_iter)?;
+                if *curve_program_info.key != curve_program_id
+                    || *curve_result_info.owner != curve_program_id
+                {
+                    return Err(SwapError::IncorrectExternalCurve.into());
+                }
+                let swap_fees = SwapCurve::swap_fees(to_u128(amount_in)?, token_swap.fees())
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                let swap_without_fees = Self::external_swap_without_fees(
+                    curve_program_info,
+                    curve_result_info,
+                    ExternalSwapRequest {
+                        source_amount: swap_fees.source_amount_less_fees,
+                        swap_source_amount: to_u128(source_account.amount)?,
+                        swap_destination_amount: to_u128(dest_account.amount)?,
+                        trade_direction,
+                    },
+                )?;
+                SwapCurve::swap_result(
+                    &swap_fees,
+                    swap_without_fees,
+                    to_u128(source_account.amount)?,
+                    to_u128(dest_account.amount)?,
+                )
+            }
+            None => swap_curve.swap(
                 to_u128(amount_in)?,
                 to_u128(source_account.amount)?,
                 to_u128(dest_account.amount)?,
                 trade_direction,
                 token_swap.fees(),
-            )
-            .ok_or(SwapError::ZeroTradingTokens)?;
+            ),
+        }
+        .ok_or(SwapError::ZeroTradingTokens)?;
         if result.destination_amount_swapped < to_u128(minimum_amount_out)? {
             return Err(SwapError::ExceededSlippage.into());
         }
@@ -425,8 +641,7 @@ impl Processor {
             to_u64(result.source_amount_swapped)?,
         )?;
 
-        let mut pool_token_amount = token_swap
-            .swap_curve()
+        let mut pool_token_amount = swap_curve
             .trading_tokens_to_pool_tokens(
                 result.owner_fee,
                 swap_token_a_amount,
@@ -590,6 +805,17 @@ impl Processor {
             pool_token_amount,
         )?;
 
+        if let Ok(position_info) = next_account_info(account_info_iter) {
+            Self::record_liquidity_position_deposit(
+                program_id,
+                swap_info,
+                position_info,
+                dest_info,
+                token_swap.token_program_id(),
+                pool_token_amount,
+            )?;
+        }
+
         Ok(())
     }
 
@@ -635,15 +861,35 @@ impl Processor {
 
         let calculator = &token_swap.swap_curve().calculator;
 
+        let position = match next_account_info(account_info_iter) {
+            Ok(position_info) => Some((
+                position_info,
+                Self::unpack_liquidity_position(
+                    program_id,
+                    swap_info,
+                    position_info,
+                    source_info,
+                    token_swap.token_program_id(),
+                    false,
+                )?,
+            )),
+            Err(_) => None,
+        };
+
         let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
             // withdrawing from the fee account, don't assess withdraw fee
             0
         } else {
-            token_swap
-                .fees()
-                .owner_withdraw_fee(to_u128(pool_token_amount)?)
-                .ok_or(SwapError::FeeCalculationFailure)?
+            Self::owner_withdraw_fee_after_rebate(
+                token_swap.as_ref(),
+                position.as_ref().map(|(_, position)| position),
+                to_u128(pool_token_amount)?,
+            )?
         };
+        if let Some((position_info, mut position)) = position {
+            position.withdraw(pool_token_amount);
+            LiquidityPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        }
         let pool_token_amount = to_u128(pool_token_amount)?
             .checked_sub(withdraw_fee)
             .ok_or(SwapError::CalculationFailure)?;
@@ -773,6 +1019,15 @@ impl Processor {
             None,
         )?;
 
+        if token_swap
+            .swap_curve()
+            .calculator
+            .external_program_id()
+            .is_some()
+        {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
         let pool_mint_supply = to_u128(pool_mint.supply)?;
 
@@ -831,6 +1086,17 @@ impl Processor {
             pool_token_amount,
         )?;
 
+        if let Ok(position_info) = next_account_info(account_info_iter) {
+            Self::record_liquidity_position_deposit(
+                program_id,
+                swap_info,
+                position_info,
+                destination_info,
+                token_swap.token_program_id(),
+                pool_token_amount,
+            )?;
+        }
+
         Ok(())
     }
 
@@ -887,6 +1153,15 @@ impl Processor {
             Some(pool_fee_account_info),
         )?;
 
+        if token_swap
+            .swap_curve()
+            .calculator
+            .external_program_id()
+            .is_some()
+        {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+
         let pool_mint = Self::unpack_mint(pool_mint_info, &token_swap.token_program_id())?;
         let pool_mint_supply = to_u128(pool_mint.supply)?;
         let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
@@ -923,18 +1198,38 @@ impl Processor {
             )
             .ok_or(SwapError::ZeroTradingTokens)?;
 
+        let position = match next_account_info(account_info_iter) {
+            Ok(position_info) => Some((
+                position_info,
+                Self::unpack_liquidity_position(
+                    program_id,
+                    swap_info,
+                    position_info,
+                    source_info,
+                    token_swap.token_program_id(),
+                    false,
+                )?,
+            )),
+            Err(_) => None,
+        };
+
         let withdraw_fee: u128 = if *pool_fee_account_info.key == *source_info.key {
             // withdrawing from the fee account, don't assess withdraw fee
             0
         } else {
-            token_swap
-                .fees()
-                .owner_withdraw_fee(burn_pool_token_amount)
-                .ok_or(SwapError::FeeCalculationFailure)?
+            Self::owner_withdraw_fee_after_rebate(
+                token_swap.as_ref(),
+                position.as_ref().map(|(_, position)| position),
+                burn_pool_token_amount,
+            )?
         };
         let pool_token_amount = burn_pool_token_amount
             .checked_add(withdraw_fee)
             .ok_or(SwapError::CalculationFailure)?;
+        if let Some((position_info, mut position)) = position {
+            position.withdraw(to_u64(pool_token_amount)?);
+            LiquidityPosition::pack(position, &mut position_info.data.borrow_mut())?;
+        }
 
         if to_u64(pool_token_amount)? > maximum_pool_token_amount {
             return Err(SwapError::ExceededSlippage.into());
@@ -992,6 +1287,58 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes an [InitializeSwapConfig](enum.Instruction.html).
+    pub fn process_initialize_swap_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_config_info = next_account_info(account_info_iter)?;
+        let guardian_info = next_account_info(account_info_iter)?;
+
+        if swap_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let swap_config = SwapConfig::unpack_unchecked(&swap_config_info.data.borrow())?;
+        if swap_config.is_initialized {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+        if !guardian_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let swap_config = SwapConfig {
+            is_initialized: true,
+            guardian: *guardian_info.key,
+            is_paused: false,
+        };
+        SwapConfig::pack(swap_config, &mut swap_config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    /// Processes a [SetSwapPaused](enum.Instruction.html).
+    pub fn process_set_swap_paused(
+        program_id: &Pubkey,
+        paused: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_config_info = next_account_info(account_info_iter)?;
+        let guardian_info = next_account_info(account_info_iter)?;
+
+        let mut swap_config = Self::unpack_swap_config(program_id, swap_config_info)?;
+        if *guardian_info.key != swap_config.guardian {
+            return Err(SwapError::InvalidGuardian.into());
+        }
+        if !guardian_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        swap_config.is_paused = paused;
+        SwapConfig::pack(swap_config, &mut swap_config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
     /// Processes an [Instruction](enum.Instruction.html).
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
@@ -1010,6 +1357,8 @@ impl Processor {
                 nonce,
                 fees,
                 swap_curve,
+                immutable,
+                loyalty_schedule,
             }) => {
                 msg!("Instruction: Init");
                 Self::process_initialize(
@@ -1017,6 +1366,8 @@ impl Processor {
                     nonce,
                     fees,
                     swap_curve,
+                    immutable,
+                    loyalty_schedule,
                     accounts,
                     swap_constraints,
                 )
@@ -1084,6 +1435,14 @@ impl Processor {
                     accounts,
                 )
             }
+            SwapInstruction::InitializeSwapConfig => {
+                msg!("Instruction: InitializeSwapConfig");
+                Self::process_initialize_swap_config(program_id, accounts)
+            }
+            SwapInstruction::SetSwapPaused(SetSwapPaused { paused }) => {
+                msg!("Instruction: SetSwapPaused");
+                Self::process_set_swap_paused(program_id, paused, accounts)
+            }
         }
     }
 }
@@ -1152,6 +1511,36 @@ impl PrintProgramError for SwapError {
             SwapError::UnsupportedCurveOperation => {
                 msg!("Error: The operation cannot be performed on the given curve")
             }
+            SwapError::InvalidLoyaltySchedule => {
+                msg!("Error: The provided loyalty schedule is invalid")
+            }
+            SwapError::IncorrectLiquidityPosition => {
+                msg!("Error: The provided liquidity position does not belong to the swap or owner")
+            }
+            SwapError::IncorrectSwapConfig => {
+                msg!("Error: The provided swap config does not match the swap's")
+            }
+            SwapError::SwapPaused => {
+                msg!("Error: Swaps are paused by the guardian of the swap config")
+            }
+            SwapError::InvalidGuardian => {
+                msg!("Error: The signer is not the guardian of the swap config")
+            }
+            SwapError::InvalidLockedLiquidityAccount => {
+                msg!("Error: The provided locked liquidity account is not owned by the swap's locked liquidity address")
+            }
+            SwapError::IncorrectExternalCurve => {
+                msg!("Error: The provided external curve program or result account does not match the swap's curve")
+            }
+            SwapError::InvalidExternalCurveResult => {
+                msg!("Error: The external curve did not give a valid result for the swap")
+            }
+            SwapError::ImmutableSwapConfig => {
+                msg!("Error: An immutable swap cannot be paused by the guardian of a swap config")
+            }
+            SwapError::ImmutableExternalCurve => {
+                msg!("Error: An immutable swap cannot be priced by an external curve program")
+            }
         }
     }
 }
@@ -1170,12 +1559,14 @@ mod tests {
     use crate::{
         curve::calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
         curve::{
-            base::CurveType, constant_price::ConstantPriceCurve,
-            constant_product::ConstantProductCurve, offset::OffsetCurve,
+            constant_price::ConstantPriceCurve, constant_product::ConstantProductCurve,
+            external::ExternalCurve, offset::OffsetCurve,
         },
         instruction::{
-            deposit_all_token_types, deposit_single_token_type_exact_amount_in, initialize, swap,
-            withdraw_all_token_types, withdraw_single_token_type_exact_amount_out,
+            add_external_curve_accounts, add_liquidity_position_account, add_swap_config_account,
+            deposit_all_token_types, deposit_single_token_type_exact_amount_in, initialize,
+            initialize_swap_config, set_swap_paused, swap, withdraw_all_token_types,
+            withdraw_single_token_type_exact_amount_out,
         },
     };
     use solana_program::{instruction::Instruction, program_stubs, rent::Rent};
@@ -1192,6 +1583,12 @@ mod tests {
     const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
     // Test program id for the token program.
     const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([1u8; 32]);
+    // Slot returned by the clock sysvar in tests.
+    const TEST_CLOCK_SLOT: u64 = 1_000_000;
+    // Test program id for an external constant product curve.
+    const EXTERNAL_CURVE_PROGRAM_ID: Pubkey = Pubkey::new_from_array([3u8; 32]);
+    // Test program id for an external curve that never writes a result.
+    const BROKEN_CURVE_PROGRAM_ID: Pubkey = Pubkey::new_from_array([4u8; 32]);
 
     struct TestSyscallStubs {}
     impl program_stubs::SyscallStubs for TestSyscallStubs {
@@ -1205,8 +1602,11 @@ mod tests {
 
             let mut new_account_infos = vec![];
 
-            // mimic check for token program in accounts
-            if !account_infos.iter().any(|x| *x.key == TOKEN_PROGRAM_ID) {
+            // mimic check for the invoked program in accounts
+            if !account_infos
+                .iter()
+                .any(|x| *x.key == instruction.program_id)
+            {
                 return Err(ProgramError::InvalidAccountData);
             }
 
@@ -1226,11 +1626,37 @@ mod tests {
                 }
             }
 
-            spl_token::processor::Processor::process(
-                &instruction.program_id,
-                &new_account_infos,
-                &instruction.data,
-            )
+            match instruction.program_id {
+                EXTERNAL_CURVE_PROGRAM_ID => external::process_swap_without_fees(
+                    &instruction.program_id,
+                    &new_account_infos,
+                    &instruction.data,
+                    |request| {
+                        ConstantProductCurve {}.swap_without_fees(
+                            request.source_amount,
+                            request.swap_source_amount,
+                            request.swap_destination_amount,
+                            request.trade_direction,
+                        )
+                    },
+                ),
+                BROKEN_CURVE_PROGRAM_ID => Ok(()),
+                _ => spl_token::processor::Processor::process(
+                    &instruction.program_id,
+                    &new_account_infos,
+                    &instruction.data,
+                ),
+            }
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Clock) = Clock {
+                    slot: TEST_CLOCK_SLOT,
+                    ..Clock::default()
+                };
+            }
+            solana_program::entrypoint::SUCCESS
         }
     }
 
@@ -1256,6 +1682,8 @@ mod tests {
         pool_fee_account: Account,
         pool_token_key: Pubkey,
         pool_token_account: Account,
+        locked_liquidity_key: Pubkey,
+        locked_liquidity_account: Account,
         token_a_key: Pubkey,
         token_a_account: Account,
         token_a_mint_key: Pubkey,
@@ -1264,6 +1692,9 @@ mod tests {
         token_b_account: Account,
         token_b_mint_key: Pubkey,
         token_b_mint_account: Account,
+        swap_config: Option<(Pubkey, Account)>,
+        immutable: bool,
+        external_curve_result: Option<(Pubkey, Account)>,
     }
 
     impl SwapAccountInfo {
@@ -1297,6 +1728,14 @@ mod tests {
                 &user_key,
                 0,
             );
+            let (locked_liquidity_key, locked_liquidity_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                &Processor::locked_liquidity_owner_id(&SWAP_PROGRAM_ID, &swap_key),
+                0,
+            );
             let (token_a_mint_key, mut token_a_mint_account) =
                 create_mint(&TOKEN_PROGRAM_ID, &user_key, None);
             let (token_a_key, token_a_account) = mint_token(
@@ -1331,6 +1770,8 @@ mod tests {
                 pool_fee_account,
                 pool_token_key,
                 pool_token_account,
+                locked_liquidity_key,
+                locked_liquidity_account,
                 token_a_key,
                 token_a_account,
                 token_a_mint_key,
@@ -1339,37 +1780,54 @@ mod tests {
                 token_b_account,
                 token_b_mint_key,
                 token_b_mint_account,
+                swap_config: None,
+                immutable: false,
+                external_curve_result: None,
             }
         }
 
         pub fn initialize_swap(&mut self) -> ProgramResult {
-            do_process_instruction(
-                initialize(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &self.pool_token_key,
-                    self.nonce,
-                    self.fees.clone(),
-                    self.swap_curve.clone(),
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut self.pool_token_account,
-                    &mut Account::default(),
-                ],
+            self.initialize_swap_with_loyalty_schedule(LoyaltySchedule::default())
+        }
+
+        pub fn initialize_swap_with_loyalty_schedule(
+            &mut self,
+            loyalty_schedule: LoyaltySchedule,
+        ) -> ProgramResult {
+            let mut instruction = initialize(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &self.swap_key,
+                &self.authority_key,
+                &self.token_a_key,
+                &self.token_b_key,
+                &self.pool_mint_key,
+                &self.pool_fee_key,
+                &self.pool_token_key,
+                &self.locked_liquidity_key,
+                self.nonce,
+                self.fees.clone(),
+                self.swap_curve.clone(),
+                self.immutable,
+                loyalty_schedule,
             )
+            .unwrap();
+            let mut accounts = vec![
+                &mut self.swap_account,
+                &mut Account::default(),
+                &mut self.token_a_account,
+                &mut self.token_b_account,
+                &mut self.pool_mint_account,
+                &mut self.pool_fee_account,
+                &mut self.pool_token_account,
+                &mut self.locked_liquidity_account,
+                &mut Account::default(),
+            ];
+            if let Some((swap_config_key, swap_config_account)) = &mut self.swap_config {
+                add_swap_config_account(&mut instruction, swap_config_key);
+                accounts.push(swap_config_account);
+            }
+            do_process_instruction(instruction, accounts)
         }
 
         pub fn setup_token_accounts(
@@ -1471,39 +1929,53 @@ mod tests {
             let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
 
             // perform the swap
-            do_process_instruction(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    &TOKEN_PROGRAM_ID,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_key,
-                    &user_source_key,
-                    &swap_source_key,
-                    &swap_destination_key,
-                    &user_destination_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    None,
-                    Swap {
-                        amount_in,
-                        minimum_amount_out,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                    &mut user_source_account,
-                    &mut swap_source_account,
-                    &mut swap_destination_account,
-                    &mut user_destination_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut Account::default(),
-                ],
-            )?;
+            let mut instruction = swap(
+                &SWAP_PROGRAM_ID,
+                &TOKEN_PROGRAM_ID,
+                &self.swap_key,
+                &self.authority_key,
+                &user_transfer_key,
+                &user_source_key,
+                &swap_source_key,
+                &swap_destination_key,
+                &user_destination_key,
+                &self.pool_mint_key,
+                &self.pool_fee_key,
+                None,
+                Swap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap();
+            let mut accounts = vec![
+                &mut self.swap_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut user_source_account,
+                &mut swap_source_account,
+                &mut swap_destination_account,
+                &mut user_destination_account,
+                &mut self.pool_mint_account,
+                &mut self.pool_fee_account,
+                &mut Account::default(),
+            ];
+            if let Some((swap_config_key, swap_config_account)) = &mut self.swap_config {
+                add_swap_config_account(&mut instruction, swap_config_key);
+                accounts.push(swap_config_account);
+            }
+            let mut curve_program_account = Account::default();
+            if let Some((curve_result_key, curve_result_account)) = &mut self.external_curve_result
+            {
+                add_external_curve_accounts(
+                    &mut instruction,
+                    &self.swap_curve.calculator.external_program_id().unwrap(),
+                    curve_result_key,
+                );
+                accounts.push(&mut curve_program_account);
+                accounts.push(curve_result_account);
+            }
+            do_process_instruction(instruction, accounts)?;
 
             self.set_token_account(swap_source_key, swap_source_account);
             self.set_token_account(swap_destination_key, swap_destination_account);
@@ -2290,28 +2762,108 @@ mod tests {
             accounts.pool_fee_account = old_account;
         }
 
-        // token A account is delegated
+        // locked liquidity account has wrong mint
         {
-            do_process_instruction(
-                approve(
-                    &TOKEN_PROGRAM_ID,
-                    &accounts.token_a_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_a_account,
-                    &mut Account::default(),
-                    &mut Account::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
+            let (_locked_liquidity_key, locked_liquidity_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &Processor::locked_liquidity_owner_id(&SWAP_PROGRAM_ID, &accounts.swap_key),
+                0,
+            );
+            let old_account = accounts.locked_liquidity_account;
+            accounts.locked_liquidity_account = locked_liquidity_account;
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.initialize_swap()
+            );
+            accounts.locked_liquidity_account = old_account;
+        }
+
+        // locked liquidity account owner is not the locked liquidity address
+        {
+            let (_locked_liquidity_key, locked_liquidity_account) = mint_token(
+                &TOKEN_PROGRAM_ID,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.locked_liquidity_account;
+            accounts.locked_liquidity_account = locked_liquidity_account;
+            assert_eq!(
+                Err(SwapError::InvalidLockedLiquidityAccount.into()),
+                accounts.initialize_swap()
+            );
+            accounts.locked_liquidity_account = old_account;
+        }
+
+        // locked liquidity account is delegated
+        {
+            let locked_liquidity_owner =
+                Processor::locked_liquidity_owner_id(&SWAP_PROGRAM_ID, &accounts.swap_key);
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.locked_liquidity_key,
+                    &user_key,
+                    &locked_liquidity_owner,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.locked_liquidity_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
+            );
+
+            do_process_instruction(
+                revoke(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.locked_liquidity_key,
+                    &locked_liquidity_owner,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.locked_liquidity_account,
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+        }
+
+        // token A account is delegated
+        {
+            do_process_instruction(
+                approve(
+                    &TOKEN_PROGRAM_ID,
+                    &accounts.token_a_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_a_account,
+                    &mut Account::default(),
+                    &mut Account::default(),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
             );
 
             do_process_instruction(
@@ -2450,9 +3002,12 @@ mod tests {
                         &accounts.pool_mint_key,
                         &accounts.pool_fee_key,
                         &accounts.pool_token_key,
+                        &accounts.locked_liquidity_key,
                         accounts.nonce,
                         accounts.fees.clone(),
                         accounts.swap_curve.clone(),
+                        false,
+                        LoyaltySchedule::default(),
                     )
                     .unwrap(),
                     vec![
@@ -2463,6 +3018,7 @@ mod tests {
                         &mut accounts.pool_mint_account,
                         &mut accounts.pool_fee_account,
                         &mut accounts.pool_token_account,
+                        &mut accounts.locked_liquidity_account,
                         &mut Account::default(),
                     ],
                 )
@@ -2636,9 +3192,12 @@ mod tests {
                         &accounts.pool_mint_key,
                         &accounts.pool_fee_key,
                         &accounts.pool_token_key,
+                        &accounts.locked_liquidity_key,
                         accounts.nonce,
                         accounts.fees.clone(),
                         accounts.swap_curve.clone(),
+                        false,
+                        LoyaltySchedule::default(),
                     )
                     .unwrap(),
                     vec![
@@ -2649,6 +3208,7 @@ mod tests {
                         &mut accounts.pool_mint_account,
                         &mut accounts.pool_fee_account,
                         &mut accounts.pool_token_account,
+                        &mut accounts.locked_liquidity_account,
                         &mut Account::default(),
                     ],
                     &constraints,
@@ -2708,9 +3268,12 @@ mod tests {
                         &accounts.pool_mint_key,
                         &accounts.pool_fee_key,
                         &accounts.pool_token_key,
+                        &accounts.locked_liquidity_key,
                         accounts.nonce,
                         accounts.fees.clone(),
                         accounts.swap_curve.clone(),
+                        false,
+                        LoyaltySchedule::default(),
                     )
                     .unwrap(),
                     vec![
@@ -2721,6 +3284,7 @@ mod tests {
                         &mut accounts.pool_mint_account,
                         &mut accounts.pool_fee_account,
                         &mut accounts.pool_token_account,
+                        &mut accounts.locked_liquidity_account,
                         &mut Account::default(),
                     ],
                     &constraints,
@@ -2776,9 +3340,12 @@ mod tests {
                     &accounts.pool_mint_key,
                     &accounts.pool_fee_key,
                     &accounts.pool_token_key,
+                    &accounts.locked_liquidity_key,
                     accounts.nonce,
                     accounts.fees,
                     accounts.swap_curve.clone(),
+                    false,
+                    LoyaltySchedule::default(),
                 )
                 .unwrap(),
                 vec![
@@ -2789,6 +3356,7 @@ mod tests {
                     &mut accounts.pool_mint_account,
                     &mut accounts.pool_fee_account,
                     &mut accounts.pool_token_account,
+                    &mut accounts.locked_liquidity_account,
                     &mut Account::default(),
                 ],
                 &constraints,
@@ -2816,14 +3384,25 @@ mod tests {
         assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
         assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
         assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
+        assert_eq!(swap_state.is_immutable(), false);
         let token_a = spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
         assert_eq!(token_a.amount, token_a_amount);
         let token_b = spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
         assert_eq!(token_b.amount, token_b_amount);
         let pool_account =
             spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+        let locked_liquidity =
+            spl_token::state::Account::unpack(&accounts.locked_liquidity_account.data).unwrap();
+        assert_eq!(locked_liquidity.amount, to_u64(MINIMUM_LIQUIDITY).unwrap());
         let pool_mint = spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-        assert_eq!(pool_mint.supply, pool_account.amount);
+        assert_eq!(
+            pool_mint.supply,
+            to_u64(accounts.swap_curve.calculator.new_pool_supply()).unwrap()
+        );
+        assert_eq!(
+            pool_mint.supply,
+            pool_account.amount + locked_liquidity.amount
+        );
     }
 
     #[test]
@@ -3430,11 +4009,13 @@ mod tests {
             let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
             let swap_pool_account =
                 spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            let locked_liquidity =
+                spl_token::state::Account::unpack(&accounts.locked_liquidity_account.data).unwrap();
             let pool_mint =
                 spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
             assert_eq!(
                 pool_mint.supply,
-                pool_account.amount + swap_pool_account.amount
+                pool_account.amount + swap_pool_account.amount + locked_liquidity.amount
             );
         }
     }
@@ -4104,146 +4685,881 @@ mod tests {
             );
         }
 
-        // correct withdrawal
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+        // correct withdrawal
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            accounts
+                .withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    withdraw_amount - withdraw_fee,
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            assert_eq!(
+                swap_token_a.amount,
+                token_a_amount - to_u64(results.token_a_amount).unwrap()
+            );
+            assert_eq!(
+                swap_token_b.amount,
+                token_b_amount - to_u64(results.token_b_amount).unwrap()
+            );
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(
+                token_a.amount,
+                initial_a + to_u64(results.token_a_amount).unwrap()
+            );
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.amount,
+                initial_b + to_u64(results.token_b_amount).unwrap()
+            );
+            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
+            assert_eq!(
+                pool_account.amount,
+                to_u64(initial_pool - withdraw_amount).unwrap()
+            );
+            let fee_account =
+                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(
+                fee_account.amount,
+                TryInto::<u64>::try_into(withdraw_fee).unwrap()
+            );
+        }
+
+        // correct withdrawal from fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.amount;
+
+            accounts
+                .withdraw_all_token_types(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    pool_fee_amount,
+                    0,
+                    0,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    pool_fee_amount.try_into().unwrap(),
+                    pool_mint.supply.try_into().unwrap(),
+                    swap_token_a.amount.try_into().unwrap(),
+                    swap_token_b.amount.try_into().unwrap(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
+            assert_eq!(
+                token_a.amount,
+                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+            );
+            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.amount,
+                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_swap_config_pause() {
+        let user_key = Pubkey::new_unique();
+        let guardian_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 1_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+
+        let swap_config_key = Pubkey::new_unique();
+        let mut swap_config_account = Account::new(0, SwapConfig::LEN, &SWAP_PROGRAM_ID);
+
+        // guardian must sign
+        {
+            let mut instruction =
+                initialize_swap_config(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key).unwrap();
+            instruction.accounts[1].is_signer = false;
+            assert_eq!(
+                Err(ProgramError::MissingRequiredSignature),
+                do_process_instruction(
+                    instruction,
+                    vec![&mut swap_config_account, &mut Account::default()],
+                )
+            );
+        }
+
+        do_process_instruction(
+            initialize_swap_config(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key).unwrap(),
+            vec![&mut swap_config_account, &mut Account::default()],
+        )
+        .unwrap();
+        let swap_config = SwapConfig::unpack(&swap_config_account.data).unwrap();
+        assert_eq!(swap_config.guardian, guardian_key);
+        assert!(!swap_config.is_paused);
+
+        // config already initialized
+        assert_eq!(
+            Err(SwapError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_swap_config(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key).unwrap(),
+                vec![&mut swap_config_account, &mut Account::default()],
+            )
+        );
+
+        // the swap opts into the config at initialization
+        accounts.swap_config = Some((swap_config_key, swap_config_account));
+        accounts.initialize_swap().unwrap();
+        let token_swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(token_swap.swap_config(), Some(&swap_config_key));
+
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &user_key, initial_a, initial_b, 0);
+        let a_to_b_amount = initial_a / 10;
+        let minimum_token_b_amount = 0;
+
+        accounts
+            .swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        // swap config missing
+        {
+            let swap_config = accounts.swap_config.take();
+            assert_eq!(
+                Err(ProgramError::NotEnoughAccountKeys),
+                accounts.swap(
+                    &user_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    a_to_b_amount,
+                    minimum_token_b_amount,
+                )
+            );
+            accounts.swap_config = swap_config;
+        }
+
+        // wrong swap config
+        {
+            let (swap_config_key, swap_config_account) = accounts.swap_config.take().unwrap();
+            accounts.swap_config = Some((Pubkey::new_unique(), swap_config_account.clone()));
+            assert_eq!(
+                Err(SwapError::IncorrectSwapConfig.into()),
+                accounts.swap(
+                    &user_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    a_to_b_amount,
+                    minimum_token_b_amount,
+                )
+            );
+            accounts.swap_config = Some((swap_config_key, swap_config_account));
+        }
+
+        // only the guardian can pause
+        {
+            let wrong_guardian_key = Pubkey::new_unique();
+            let (_, swap_config_account) = accounts.swap_config.as_mut().unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidGuardian.into()),
+                do_process_instruction(
+                    set_swap_paused(
+                        &SWAP_PROGRAM_ID,
+                        &swap_config_key,
+                        &wrong_guardian_key,
+                        true
+                    )
+                    .unwrap(),
+                    vec![swap_config_account, &mut Account::default()],
+                )
+            );
+        }
+
+        {
+            let (_, swap_config_account) = accounts.swap_config.as_mut().unwrap();
+            do_process_instruction(
+                set_swap_paused(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key, true).unwrap(),
+                vec![swap_config_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+
+        {
+            let (_, swap_config_account) = accounts.swap_config.as_mut().unwrap();
+            do_process_instruction(
+                set_swap_paused(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key, false).unwrap(),
+                vec![swap_config_account, &mut Account::default()],
+            )
+            .unwrap();
+        }
+        accounts
+            .swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_immutable_swap() {
+        let user_key = Pubkey::new_unique();
+        let guardian_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let mut accounts = SwapAccountInfo::new(&user_key, fees, swap_curve, 1_000, 2_000);
+        accounts.immutable = true;
+
+        let swap_config_key = Pubkey::new_unique();
+        let mut swap_config_account = Account::new(0, SwapConfig::LEN, &SWAP_PROGRAM_ID);
+        do_process_instruction(
+            initialize_swap_config(&SWAP_PROGRAM_ID, &swap_config_key, &guardian_key).unwrap(),
+            vec![&mut swap_config_account, &mut Account::default()],
+        )
+        .unwrap();
+
+        // a guardian could pause the swap
+        accounts.swap_config = Some((swap_config_key, swap_config_account));
+        assert_eq!(
+            Err(SwapError::ImmutableSwapConfig.into()),
+            accounts.initialize_swap()
+        );
+
+        accounts.swap_config = None;
+        accounts.initialize_swap().unwrap();
+        let token_swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(token_swap.is_immutable());
+        assert_eq!(token_swap.swap_config(), None);
+    }
+
+    #[test]
+    fn test_immutable_swap_external_curve() {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::External,
+            calculator: Box::new(ExternalCurve {
+                curve_program_id: EXTERNAL_CURVE_PROGRAM_ID,
+            }),
+        };
+        let mut accounts = SwapAccountInfo::new(&user_key, fees, swap_curve, 1_000, 2_000);
+
+        // an upgrade of the curve program could reprice the swap
+        accounts.immutable = true;
+        assert_eq!(
+            Err(SwapError::ImmutableExternalCurve.into()),
+            accounts.initialize_swap()
+        );
+
+        accounts.immutable = false;
+        accounts.initialize_swap().unwrap();
+    }
+
+    #[test]
+    fn test_external_curve() {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 100,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 100,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 5_000_000;
+
+        // the curve program must be set
+        {
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::External,
+                calculator: Box::new(ExternalCurve::default()),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+            );
+            assert_eq!(
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
+            );
+        }
+
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::External,
+            calculator: Box::new(ExternalCurve {
+                curve_program_id: EXTERNAL_CURVE_PROGRAM_ID,
+            }),
+        };
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees.clone(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+        );
+        accounts.initialize_swap().unwrap();
+
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &user_key, initial_a, initial_b, 0);
+        let a_to_b_amount = initial_a / 10;
+        let minimum_token_b_amount = 0;
+
+        // curve accounts missing
+        assert_eq!(
+            Err(ProgramError::NotEnoughAccountKeys),
+            accounts.swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+
+        // result account not owned by the curve program
+        let curve_result_key = Pubkey::new_unique();
+        accounts.external_curve_result = Some((
+            curve_result_key,
+            Account::new(0, ExternalSwapResult::LEN, &SWAP_PROGRAM_ID),
+        ));
+        assert_eq!(
+            Err(SwapError::IncorrectExternalCurve.into()),
+            accounts.swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+
+        // priced exactly as the built-in curve the external one implements
+        accounts.external_curve_result = Some((
+            curve_result_key,
+            Account::new(0, ExternalSwapResult::LEN, &EXTERNAL_CURVE_PROGRAM_ID),
+        ));
+        let expected = SwapCurve::default()
+            .swap(
+                a_to_b_amount.into(),
+                token_a_amount.into(),
+                token_b_amount.into(),
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+        accounts
+            .swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+        let swap_token_a =
+            spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(
+            swap_token_a.amount,
+            to_u64(expected.new_swap_source_amount).unwrap()
+        );
+        let swap_token_b =
+            spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(
+            swap_token_b.amount,
+            to_u64(expected.new_swap_destination_amount).unwrap()
+        );
+        let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.amount,
+            initial_b + to_u64(expected.destination_amount_swapped).unwrap()
+        );
+        let pool_fee = spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert!(pool_fee.amount > 0);
+
+        // single-sided deposits and withdrawals would need the curve
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveOperation.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &pool_key,
+                &mut pool_account,
+                a_to_b_amount,
+                0,
+            )
+        );
+        let pool_token_key = accounts.pool_token_key;
+        let mut pool_token_account = accounts.pool_token_account.clone();
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveOperation.into()),
+            accounts.withdraw_single_token_type_exact_amount_out(
+                &user_key,
+                &pool_token_key,
+                &mut pool_token_account,
+                &token_a_key,
+                &mut token_a_account,
+                a_to_b_amount,
+                u64::MAX,
+            )
+        );
+
+        // a curve that doesn't answer the request fails the swap
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::External,
+            calculator: Box::new(ExternalCurve {
+                curve_program_id: BROKEN_CURVE_PROGRAM_ID,
+            }),
+        };
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
+        accounts.initialize_swap().unwrap();
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &user_key, initial_a, initial_b, 0);
+        accounts.external_curve_result = Some((
+            curve_result_key,
+            Account::new(0, ExternalSwapResult::LEN, &BROKEN_CURVE_PROGRAM_ID),
+        ));
+        assert_eq!(
+            Err(SwapError::InvalidExternalCurveResult.into()),
+            accounts.swap(
+                &user_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+        );
+    }
+
+    #[test]
+    fn test_withdraw_with_loyalty_rebate() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let fees = Fees {
+            trade_fee_numerator: 0,
+            trade_fee_denominator: 0,
+            owner_trade_fee_numerator: 0,
+            owner_trade_fee_denominator: 0,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        };
+        let loyalty_schedule = LoyaltySchedule {
+            full_rebate_slots: 1_000,
+            rebate_numerator: 1,
+            rebate_denominator: 2,
+        };
+
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 1_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let pool_token_amount = to_u64(swap_curve.calculator.new_pool_supply() / 10).unwrap();
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
 
-            accounts
-                .withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
-                .unwrap();
+        let mut accounts =
+            SwapAccountInfo::new(&user_key, fees, swap_curve, token_a_amount, token_b_amount);
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    withdraw_amount - withdraw_fee,
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            assert_eq!(
-                swap_token_a.amount,
-                token_a_amount - to_u64(results.token_a_amount).unwrap()
-            );
-            assert_eq!(
-                swap_token_b.amount,
-                token_b_amount - to_u64(results.token_b_amount).unwrap()
-            );
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                initial_a + to_u64(results.token_a_amount).unwrap()
-            );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
-            assert_eq!(
-                token_b.amount,
-                initial_b + to_u64(results.token_b_amount).unwrap()
-            );
-            let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.amount,
-                to_u64(initial_pool - withdraw_amount).unwrap()
-            );
-            let fee_account =
-                spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
-            assert_eq!(
-                fee_account.amount,
-                TryInto::<u64>::try_into(withdraw_fee).unwrap()
-            );
-        }
+        // invalid schedule
+        assert_eq!(
+            Err(SwapError::InvalidLoyaltySchedule.into()),
+            accounts.initialize_swap_with_loyalty_schedule(LoyaltySchedule {
+                full_rebate_slots: 0,
+                rebate_numerator: 1,
+                rebate_denominator: 2,
+            })
+        );
+        accounts
+            .initialize_swap_with_loyalty_schedule(loyalty_schedule.clone())
+            .unwrap();
 
-        // correct withdrawal from fee account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+        let position_key = Pubkey::new_unique();
+        let mut position_account = Account::new(0, LiquidityPosition::LEN, &SWAP_PROGRAM_ID);
 
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account = spl_token::state::Account::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.amount;
+        // deposit, recording the position
+        let user_transfer_authority_key = Pubkey::new_unique();
+        do_process_instruction(
+            approve(
+                &TOKEN_PROGRAM_ID,
+                &token_a_key,
+                &user_transfer_authority_key,
+                &depositor_key,
+                &[],
+                deposit_a,
+            )
+            .unwrap(),
+            vec![
+                &mut token_a_account,
+                &mut Account::default(),
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            approve(
+                &TOKEN_PROGRAM_ID,
+                &token_b_key,
+                &user_transfer_authority_key,
+                &depositor_key,
+                &[],
+                deposit_b,
+            )
+            .unwrap(),
+            vec![
+                &mut token_b_account,
+                &mut Account::default(),
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        let mut instruction = deposit_all_token_types(
+            &SWAP_PROGRAM_ID,
+            &TOKEN_PROGRAM_ID,
+            &accounts.swap_key,
+            &accounts.authority_key,
+            &user_transfer_authority_key,
+            &token_a_key,
+            &token_b_key,
+            &accounts.token_a_key,
+            &accounts.token_b_key,
+            &accounts.pool_mint_key,
+            &pool_key,
+            DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount: deposit_a,
+                maximum_token_b_amount: deposit_b,
+            },
+        )
+        .unwrap();
+        add_liquidity_position_account(&mut instruction, &position_key);
+        do_process_instruction(
+            instruction,
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut token_a_account,
+                &mut token_b_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut pool_account,
+                &mut Account::default(),
+                &mut position_account,
+            ],
+        )
+        .unwrap();
 
-            accounts
-                .withdraw_all_token_types(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    pool_fee_amount,
-                    0,
-                    0,
-                )
-                .unwrap();
+        let mut position = LiquidityPosition::unpack(&position_account.data).unwrap();
+        assert_eq!(position.swap, accounts.swap_key);
+        assert_eq!(position.owner, depositor_key);
+        assert_eq!(position.pool_token_amount, pool_token_amount);
+        assert_eq!(position.deposit_slot, TEST_CLOCK_SLOT);
 
-            let swap_token_a =
-                spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    pool_fee_amount.try_into().unwrap(),
-                    pool_mint.supply.try_into().unwrap(),
-                    swap_token_a.amount.try_into().unwrap(),
-                    swap_token_b.amount.try_into().unwrap(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.amount,
-                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
-            );
-            let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
+        // half of the full rebate period has passed
+        position.deposit_slot = TEST_CLOCK_SLOT - loyalty_schedule.full_rebate_slots / 2;
+        LiquidityPosition::pack(position, &mut position_account.data).unwrap();
+
+        do_process_instruction(
+            approve(
+                &TOKEN_PROGRAM_ID,
+                &pool_key,
+                &user_transfer_authority_key,
+                &depositor_key,
+                &[],
+                pool_token_amount,
+            )
+            .unwrap(),
+            vec![
+                &mut pool_account,
+                &mut Account::default(),
+                &mut Account::default(),
+            ],
+        )
+        .unwrap();
+        let mut instruction = withdraw_all_token_types(
+            &SWAP_PROGRAM_ID,
+            &TOKEN_PROGRAM_ID,
+            &accounts.swap_key,
+            &accounts.authority_key,
+            &user_transfer_authority_key,
+            &accounts.pool_mint_key,
+            &accounts.pool_fee_key,
+            &pool_key,
+            &accounts.token_a_key,
+            &accounts.token_b_key,
+            &token_a_key,
+            &token_b_key,
+            WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        add_liquidity_position_account(&mut instruction, &position_key);
+
+        // position of someone else
+        {
+            let mut wrong_position_account = position_account.clone();
+            let mut wrong_position = LiquidityPosition::unpack(&position_account.data).unwrap();
+            wrong_position.owner = user_key;
+            LiquidityPosition::pack(wrong_position, &mut wrong_position_account.data).unwrap();
             assert_eq!(
-                token_b.amount,
-                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
+                Err(SwapError::IncorrectLiquidityPosition.into()),
+                do_process_instruction(
+                    instruction.clone(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut Account::default(),
+                        &mut Account::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
+                        &mut Account::default(),
+                        &mut wrong_position_account,
+                    ],
+                )
             );
         }
+
+        do_process_instruction(
+            instruction,
+            vec![
+                &mut accounts.swap_account,
+                &mut Account::default(),
+                &mut Account::default(),
+                &mut accounts.pool_mint_account,
+                &mut pool_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_a_account,
+                &mut token_b_account,
+                &mut accounts.pool_fee_account,
+                &mut Account::default(),
+                &mut position_account,
+            ],
+        )
+        .unwrap();
+
+        // a quarter of the fee is rebated, half of the full rebate
+        let withdraw_fee = accounts
+            .fees
+            .owner_withdraw_fee(to_u128(pool_token_amount).unwrap())
+            .unwrap();
+        let fee_account =
+            spl_token::state::Account::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(
+            to_u128(fee_account.amount).unwrap(),
+            withdraw_fee - withdraw_fee / 4
+        );
+        let position = LiquidityPosition::unpack(&position_account.data).unwrap();
+        assert_eq!(position.pool_token_amount, 0);
     }
 
     #[test]
@@ -4750,11 +6066,13 @@ mod tests {
             let pool_account = spl_token::state::Account::unpack(&pool_account.data).unwrap();
             let swap_pool_account =
                 spl_token::state::Account::unpack(&accounts.pool_token_account.data).unwrap();
+            let locked_liquidity =
+                spl_token::state::Account::unpack(&accounts.locked_liquidity_account.data).unwrap();
             let pool_mint =
                 spl_token::state::Mint::unpack(&accounts.pool_mint_account.data).unwrap();
             assert_eq!(
                 pool_mint.supply,
-                pool_account.amount + swap_pool_account.amount
+                pool_account.amount + swap_pool_account.amount + locked_liquidity.amount
             );
         }
     }
@@ -5751,9 +7069,12 @@ mod tests {
                 &accounts.pool_mint_key,
                 &accounts.pool_fee_key,
                 &accounts.pool_token_key,
+                &accounts.locked_liquidity_key,
                 accounts.nonce,
                 accounts.fees.clone(),
                 accounts.swap_curve.clone(),
+                false,
+                LoyaltySchedule::default(),
             )
             .unwrap(),
             vec![
@@ -5764,6 +7085,7 @@ mod tests {
                 &mut accounts.pool_mint_account,
                 &mut accounts.pool_fee_account,
                 &mut accounts.pool_token_account,
+                &mut accounts.locked_liquidity_account,
                 &mut Account::default(),
             ],
             &constraints,
@@ -6700,10 +8022,12 @@ mod tests {
         let pool_key = accounts.pool_token_key;
         let mut pool_account = accounts.pool_token_account.clone();
 
-        // WithdrawAllTokenTypes takes all tokens for A and B.
+        // WithdrawAllTokenTypes takes all tokens for A and B, except for the
+        // share of A backing the locked pool tokens.
         // The curve's calculation for token B will say to transfer
         // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
         // moved.
+        let withdrawable_pool = total_pool - MINIMUM_LIQUIDITY;
         accounts
             .withdraw_all_token_types(
                 &user_key,
@@ -6713,19 +8037,21 @@ mod tests {
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                total_pool.try_into().unwrap(),
+                withdrawable_pool.try_into().unwrap(),
                 0,
                 0,
             )
             .unwrap();
 
+        let locked_token_a_amount =
+            to_u64(token_a_amount as u128 * MINIMUM_LIQUIDITY / total_pool).unwrap();
         let token_a = spl_token::state::Account::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.amount, token_a_amount);
+        assert_eq!(token_a.amount, token_a_amount - locked_token_a_amount);
         let token_b = spl_token::state::Account::unpack(&token_b_account.data).unwrap();
         assert_eq!(token_b.amount, token_b_amount);
         let swap_token_a =
             spl_token::state::Account::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.amount, 0);
+        assert_eq!(swap_token_a.amount, locked_token_a_amount);
         let swap_token_b =
             spl_token::state::Account::unpack(&accounts.token_b_account.data).unwrap();
         assert_eq!(swap_token_b.amount, 0);
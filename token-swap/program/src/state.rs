@@ -1,6 +1,9 @@
 //! State transition types
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::curve::{
+    base::SwapCurve,
+    fees::{Fees, LoyaltySchedule},
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use enum_dispatch::enum_dispatch;
 use solana_program::{
@@ -8,6 +11,7 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use std::convert::TryFrom;
 
 /// Trait representing access to program state across all versions
 #[enum_dispatch]
@@ -37,13 +41,36 @@ pub trait SwapState {
     fn fees(&self) -> &Fees;
     /// Curve associated with swap
     fn swap_curve(&self) -> &SwapCurve;
+
+    /// Whether the swap was created as immutable, meaning that no key holds
+    /// any power over it.  No instruction changes the fees or the fee
+    /// account of any swap, and an immutable swap also refuses a swap config
+    /// whose guardian could pause it and an external curve whose program
+    /// could be upgraded to reprice it.
+    fn is_immutable(&self) -> bool;
+
+    /// Loyalty schedule rebating withdraw fees to long-term liquidity providers
+    fn loyalty_schedule(&self) -> &LoyaltySchedule;
+
+    /// Address of the swap config whose guardian can pause the swap, if the
+    /// swap opted into one
+    fn swap_config(&self) -> Option<&Pubkey>;
 }
 
+/// Loyalty schedule of swaps created before loyalty schedules existed
+const NO_LOYALTY_SCHEDULE: LoyaltySchedule = LoyaltySchedule {
+    full_rebate_slots: 0,
+    rebate_numerator: 0,
+    rebate_denominator: 0,
+};
+
 /// All versions of SwapState
 #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    /// Latest version, used for all new swaps
+    /// Original version, still supported for existing swaps
     SwapV1,
+    /// Latest version, used for all new swaps
+    SwapV2,
 }
 
 /// SwapVersion does not implement program_pack::Pack because there are size
@@ -51,7 +78,7 @@ pub enum SwapVersion {
 /// special implementations are provided here
 impl SwapVersion {
     /// Size of the latest version of the SwapState
-    pub const LATEST_LEN: usize = 1 + SwapV1::LEN; // add one for the version enum
+    pub const LATEST_LEN: usize = 1 + SwapV2::LEN; // add one for the version enum
 
     /// Pack a swap into a byte array, based on its version
     pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
@@ -60,6 +87,10 @@ impl SwapVersion {
                 dst[0] = 1;
                 SwapV1::pack(swap_info, &mut dst[1..])
             }
+            Self::SwapV2(swap_info) => {
+                dst[0] = 2;
+                SwapV2::pack(swap_info, &mut dst[1..])
+            }
         }
     }
 
@@ -71,6 +102,7 @@ impl SwapVersion {
             .ok_or(ProgramError::InvalidAccountData)?;
         match version {
             1 => Ok(Box::new(SwapV1::unpack(rest)?)),
+            2 => Ok(Box::new(SwapV2::unpack(rest)?)),
             _ => Err(ProgramError::UninitializedAccount),
         }
     }
@@ -170,6 +202,18 @@ impl SwapState for SwapV1 {
     fn swap_curve(&self) -> &SwapCurve {
         &self.swap_curve
     }
+
+    fn is_immutable(&self) -> bool {
+        false
+    }
+
+    fn loyalty_schedule(&self) -> &LoyaltySchedule {
+        &NO_LOYALTY_SCHEDULE
+    }
+
+    fn swap_config(&self) -> Option<&Pubkey> {
+        None
+    }
 }
 
 impl Sealed for SwapV1 {}
@@ -247,6 +291,351 @@ impl Pack for SwapV1 {
     }
 }
 
+/// Program states, with the immutable flag.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    /// The program address is created deterministically with the nonce,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Swap curve parameters, to be unpacked and used by the SwapCurve, which
+    /// calculates swaps, deposits, and withdrawals
+    pub swap_curve: SwapCurve,
+
+    /// If set at initialization, the swap has no swap config, so no guardian
+    /// can ever pause it, and like every swap its fees and pool fee account
+    /// are fixed forever
+    pub is_immutable: bool,
+
+    /// Rebate of the owner withdraw fee for long-term liquidity providers,
+    /// set at initialization
+    pub loyalty_schedule: LoyaltySchedule,
+
+    /// Swap config the swap opted into at initialization, whose guardian can
+    /// pause swaps, or the default pubkey if none
+    pub swap_config: Pubkey,
+}
+
+impl SwapState for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn is_immutable(&self) -> bool {
+        self.is_immutable
+    }
+
+    fn loyalty_schedule(&self) -> &LoyaltySchedule {
+        &self.loyalty_schedule
+    }
+
+    fn swap_config(&self) -> Option<&Pubkey> {
+        if self.swap_config == Pubkey::default() {
+            None
+        } else {
+            Some(&self.swap_config)
+        }
+    }
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 380;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 380];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            is_immutable,
+            loyalty_schedule,
+            swap_config,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 1, 24, 32];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        is_immutable[0] = self.is_immutable as u8;
+        self.loyalty_schedule
+            .pack_into_slice(&mut loyalty_schedule[..]);
+        swap_config.copy_from_slice(self.swap_config.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 380];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            is_immutable,
+            loyalty_schedule,
+            swap_config,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 1, 24, 32];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            is_immutable: match is_immutable {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            loyalty_schedule: LoyaltySchedule::unpack_from_slice(loyalty_schedule)?,
+            swap_config: Pubkey::new_from_array(*swap_config),
+        })
+    }
+}
+
+/// Pool tokens deposited by a liquidity provider, and since when, used to
+/// rebate withdraw fees under the swap's loyalty schedule.  Only pool tokens
+/// minted on deposits that recorded the position can get the rebate, so pool
+/// tokens transferred in from elsewhere always pay the full fee.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct LiquidityPosition {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Swap the pool tokens were deposited in
+    pub swap: Pubkey,
+    /// Owner of the pool token accounts the position applies to
+    pub owner: Pubkey,
+    /// Pool tokens minted on deposits and not withdrawn yet
+    pub pool_token_amount: u64,
+    /// Deposit slot, averaged over the deposits weighted by pool tokens
+    pub deposit_slot: u64,
+}
+
+impl LiquidityPosition {
+    /// Record a deposit of `pool_token_amount` new pool tokens at `slot`
+    pub fn deposit(&mut self, pool_token_amount: u64, slot: u64) -> Option<()> {
+        let total_amount =
+            u128::from(self.pool_token_amount).checked_add(u128::from(pool_token_amount))?;
+        if total_amount > 0 {
+            let deposit_slot = u128::from(self.deposit_slot)
+                .checked_mul(u128::from(self.pool_token_amount))?
+                .checked_add(u128::from(slot).checked_mul(u128::from(pool_token_amount))?)?
+                .checked_div(total_amount)?;
+            self.deposit_slot = u64::try_from(deposit_slot).ok()?;
+        }
+        self.pool_token_amount = u64::try_from(total_amount).ok()?;
+        Some(())
+    }
+
+    /// Record a withdrawal of `pool_token_amount` pool tokens, returning how
+    /// many of them were deposited through the position
+    pub fn withdraw(&mut self, pool_token_amount: u64) -> u64 {
+        let deposited_amount = std::cmp::min(self.pool_token_amount, pool_token_amount);
+        self.pool_token_amount -= deposited_amount;
+        deposited_amount
+    }
+}
+
+impl Sealed for LiquidityPosition {}
+impl IsInitialized for LiquidityPosition {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for LiquidityPosition {
+    const LEN: usize = 81;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 81];
+        let (is_initialized, swap, owner, pool_token_amount, deposit_slot) =
+            mut_array_refs![output, 1, 32, 32, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        swap.copy_from_slice(self.swap.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *pool_token_amount = self.pool_token_amount.to_le_bytes();
+        *deposit_slot = self.deposit_slot.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [LiquidityPosition](struct.LiquidityPosition.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 81];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, swap, owner, pool_token_amount, deposit_slot) =
+            array_refs![input, 1, 32, 32, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            swap: Pubkey::new_from_array(*swap),
+            owner: Pubkey::new_from_array(*owner),
+            pool_token_amount: u64::from_le_bytes(*pool_token_amount),
+            deposit_slot: u64::from_le_bytes(*deposit_slot),
+        })
+    }
+}
+
+/// Protocol-level config that swaps can opt into at initialization, letting
+/// its guardian pause swaps on all of them at once for incident response
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapConfig {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Key allowed to pause and unpause swaps
+    pub guardian: Pubkey,
+    /// Whether swaps are paused on all swaps using the config
+    pub is_paused: bool,
+}
+
+impl Sealed for SwapConfig {}
+impl IsInitialized for SwapConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapConfig {
+    const LEN: usize = 34;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 34];
+        let (is_initialized, guardian, is_paused) = mut_array_refs![output, 1, 32, 1];
+        is_initialized[0] = self.is_initialized as u8;
+        guardian.copy_from_slice(self.guardian.as_ref());
+        is_paused[0] = self.is_paused as u8;
+    }
+
+    /// Unpacks a byte buffer into a [SwapConfig](struct.SwapConfig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 34];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, guardian, is_paused) = array_refs![input, 1, 32, 1];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            guardian: Pubkey::new_from_array(*guardian),
+            is_paused: match is_paused {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,6 +663,14 @@ mod tests {
     const TEST_TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([6u8; 32]);
     const TEST_POOL_FEE_ACCOUNT: Pubkey = Pubkey::new_from_array([7u8; 32]);
 
+    const TEST_LOYALTY_SCHEDULE: LoyaltySchedule = LoyaltySchedule {
+        full_rebate_slots: 216_000,
+        rebate_numerator: 1,
+        rebate_denominator: 2,
+    };
+
+    const TEST_SWAP_CONFIG: Pubkey = Pubkey::new_from_array([8u8; 32]);
+
     const TEST_CURVE_TYPE: u8 = 2;
     const TEST_AMP: u64 = 1;
     const TEST_CURVE: StableCurve = StableCurve { amp: TEST_AMP };
@@ -286,7 +683,7 @@ mod tests {
             curve_type,
             calculator,
         };
-        let swap_info = SwapVersion::SwapV1(SwapV1 {
+        let swap_info = SwapVersion::SwapV2(SwapV2 {
             is_initialized: true,
             nonce: TEST_NONCE,
             token_program_id: TEST_TOKEN_PROGRAM_ID,
@@ -298,6 +695,9 @@ mod tests {
             pool_fee_account: TEST_POOL_FEE_ACCOUNT,
             fees: TEST_FEES,
             swap_curve: swap_curve.clone(),
+            is_immutable: true,
+            loyalty_schedule: TEST_LOYALTY_SCHEDULE,
+            swap_config: TEST_SWAP_CONFIG,
         });
 
         let mut packed = [0u8; SwapVersion::LATEST_LEN];
@@ -315,6 +715,44 @@ mod tests {
         assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
         assert_eq!(*unpacked.fees(), TEST_FEES);
         assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.is_immutable(), true);
+        assert_eq!(*unpacked.loyalty_schedule(), TEST_LOYALTY_SCHEDULE);
+        assert_eq!(unpacked.swap_config(), Some(&TEST_SWAP_CONFIG));
+    }
+
+    #[test]
+    fn swap_version_unpack_v1() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve: swap_curve.clone(),
+        });
+
+        let mut packed = [0u8; 1 + SwapV1::LEN];
+        SwapVersion::pack(swap_info, &mut packed).unwrap();
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+
+        assert_eq!(unpacked.is_initialized(), true);
+        assert_eq!(*unpacked.pool_fee_account(), TEST_POOL_FEE_ACCOUNT);
+        assert_eq!(*unpacked.fees(), TEST_FEES);
+        assert_eq!(*unpacked.swap_curve(), swap_curve);
+        assert_eq!(unpacked.is_immutable(), false);
+        assert!(!unpacked.loyalty_schedule().is_enabled());
+        assert_eq!(unpacked.swap_config(), None);
     }
 
     #[test]
@@ -373,4 +811,103 @@ mod tests {
         let err = SwapV1::unpack(&packed).unwrap_err();
         assert_eq!(err, ProgramError::UninitializedAccount);
     }
+
+    #[test]
+    fn swap_v2_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Box::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV2 {
+            is_initialized: true,
+            nonce: TEST_NONCE,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve,
+            is_immutable: true,
+            loyalty_schedule: TEST_LOYALTY_SCHEDULE,
+            swap_config: TEST_SWAP_CONFIG,
+        };
+
+        let mut packed = [0u8; SwapV2::LEN];
+        SwapV2::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV2::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+        assert_eq!(packed[SwapV2::LEN - 32 - LoyaltySchedule::LEN - 1], 1);
+        assert_eq!(&packed[SwapV2::LEN - 32..], TEST_SWAP_CONFIG.as_ref());
+
+        let packed = [0u8; SwapV2::LEN];
+        let swap_info: SwapV2 = Default::default();
+        let unpack_unchecked = SwapV2::unpack_unchecked(&packed).unwrap();
+        assert_eq!(unpack_unchecked, swap_info);
+        let err = SwapV2::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn liquidity_position_pack() {
+        let position = LiquidityPosition {
+            is_initialized: true,
+            swap: Pubkey::new_from_array([8u8; 32]),
+            owner: Pubkey::new_from_array([9u8; 32]),
+            pool_token_amount: 1_000,
+            deposit_slot: 42,
+        };
+        let mut packed = [0u8; LiquidityPosition::LEN];
+        LiquidityPosition::pack(position, &mut packed).unwrap();
+        let unpacked = LiquidityPosition::unpack(&packed).unwrap();
+        assert_eq!(unpacked.pool_token_amount, 1_000);
+        assert_eq!(unpacked.deposit_slot, 42);
+
+        let packed = [0u8; LiquidityPosition::LEN];
+        let err = LiquidityPosition::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
+
+    #[test]
+    fn liquidity_position_deposit_withdraw() {
+        let mut position = LiquidityPosition::default();
+        position.deposit(100, 1_000).unwrap();
+        assert_eq!(position.pool_token_amount, 100);
+        assert_eq!(position.deposit_slot, 1_000);
+
+        // deposit slots are averaged, weighted by pool tokens
+        position.deposit(300, 2_000).unwrap();
+        assert_eq!(position.pool_token_amount, 400);
+        assert_eq!(position.deposit_slot, 1_750);
+
+        assert_eq!(position.withdraw(150), 150);
+        assert_eq!(position.pool_token_amount, 250);
+        assert_eq!(position.deposit_slot, 1_750);
+        assert_eq!(position.withdraw(1_000), 250);
+        assert_eq!(position.pool_token_amount, 0);
+    }
+    #[test]
+    fn swap_config_pack() {
+        let swap_config = SwapConfig {
+            is_initialized: true,
+            guardian: Pubkey::new_from_array([9u8; 32]),
+            is_paused: true,
+        };
+        let mut packed = [0u8; SwapConfig::LEN];
+        SwapConfig::pack(swap_config, &mut packed).unwrap();
+        let mut expect = vec![1u8];
+        expect.extend_from_slice(&[9u8; 32]);
+        expect.push(1);
+        assert_eq!(packed.to_vec(), expect);
+        let unpacked = SwapConfig::unpack(&packed).unwrap();
+        assert!(unpacked.is_paused);
+
+        let packed = [0u8; SwapConfig::LEN];
+        let err = SwapConfig::unpack(&packed).unwrap_err();
+        assert_eq!(err, ProgramError::UninitializedAccount);
+    }
 }
@@ -3,6 +3,7 @@ use clap::{
     ArgMatches, SubCommand,
 };
 use console::Emoji;
+use serde_json::json;
 use solana_account_decoder::{
     parse_token::{TokenAccountType, UiAccountState},
     UiAccountData,
@@ -1284,6 +1285,166 @@ fn command_gc(config: &Config) -> CommandResult {
     Ok(Some((lamports_needed, instructions)))
 }
 
+fn command_audit(
+    config: &Config,
+    dust_threshold: f64,
+    output_format: OutputFormat,
+) -> CommandResult {
+    let accounts = config.rpc_client.get_token_accounts_by_owner(
+        &config.owner,
+        TokenAccountsFilter::ProgramId(spl_token::id()),
+    )?;
+    let (mint_accounts, unsupported_accounts, _, _) =
+        sort_and_parse_token_accounts(&config.owner, accounts);
+    let owner = config.owner.to_string();
+
+    let mut reports = vec![];
+    let (mut dust, mut duplicates, mut delegated, mut close_candidates) = (0, 0, 0, 0);
+    let mut reclaimable_lamports = 0;
+    for (mint, accounts_list) in mint_accounts.iter() {
+        for account in accounts_list {
+            let ui_token_account = &account.ui_token_account;
+            let amount = ui_token_account
+                .token_amount
+                .amount
+                .parse::<u64>()
+                .unwrap_or_else(|err| panic!("Invalid token amount: {}", err));
+            let dust_amount = spl_token::ui_amount_to_amount(
+                dust_threshold,
+                ui_token_account.token_amount.decimals,
+            );
+
+            let mut findings = vec![];
+            if amount > 0 && amount < dust_amount {
+                findings.push("dust");
+                dust += 1;
+            }
+            // Any account besides the associated one is a duplicate, as are
+            // all but one of several auxiliary accounts of the same mint
+            if !account.is_associated && accounts_list.len() > 1 {
+                findings.push("duplicate");
+                duplicates += 1;
+            }
+            let delegated_amount = ui_token_account
+                .delegated_amount
+                .as_ref()
+                .map(|delegated_amount| delegated_amount.amount.as_str())
+                .unwrap_or("0");
+            if ui_token_account.delegate.is_some() && delegated_amount != "0" {
+                findings.push("delegated");
+                delegated += 1;
+            }
+            if amount == 0
+                && ui_token_account.state != UiAccountState::Frozen
+                && ui_token_account
+                    .close_authority
+                    .as_ref()
+                    .map_or(true, |close_authority| *close_authority == owner)
+            {
+                findings.push("closeCandidate");
+                close_candidates += 1;
+                reclaimable_lamports += account.lamports;
+            }
+
+            reports.push(json!({
+                "address": account.address,
+                "mint": mint,
+                "balance": ui_token_account.token_amount.real_number_string_trimmed(),
+                "isAssociated": account.is_associated,
+                "state": match ui_token_account.state {
+                    UiAccountState::Uninitialized => "uninitialized",
+                    UiAccountState::Initialized => "initialized",
+                    UiAccountState::Frozen => "frozen",
+                },
+                "delegate": ui_token_account.delegate,
+                "delegatedAmount": delegated_amount,
+                "closeAuthority": ui_token_account.close_authority,
+                "lamports": account.lamports,
+                "findings": findings,
+            }));
+        }
+    }
+    let summary = json!({
+        "accounts": reports.len(),
+        "dust": dust,
+        "duplicates": duplicates,
+        "delegated": delegated,
+        "closeCandidates": close_candidates,
+        "reclaimableLamports": reclaimable_lamports,
+    });
+
+    match output_format {
+        OutputFormat::Json | OutputFormat::JsonCompact => {
+            let report = json!({
+                "owner": owner,
+                "dustThreshold": dust_threshold,
+                "accounts": reports,
+                "unsupportedAccounts": unsupported_accounts
+                    .iter()
+                    .map(|account| json!({ "address": account.address, "error": account.err }))
+                    .collect::<Vec<_>>(),
+                "summary": summary,
+            });
+            if output_format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{}", serde_json::to_string(&report)?);
+            }
+        }
+        _ => {
+            println!("Token accounts of {}:", owner);
+            for report in reports.iter().filter(|report| {
+                report["findings"]
+                    .as_array()
+                    .map_or(false, |findings| !findings.is_empty())
+            }) {
+                println!(
+                    "{:<44}  {:<44}  {:>20}  {}",
+                    report["address"].as_str().unwrap(),
+                    report["mint"].as_str().unwrap(),
+                    report["balance"].as_str().unwrap(),
+                    report["findings"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .filter_map(|finding| finding.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+            }
+            for unsupported_account in unsupported_accounts {
+                println!(
+                    "{:<44}  {}",
+                    unsupported_account.address, unsupported_account.err
+                );
+            }
+            println!();
+            println_name_value("Accounts:", &reports.len().to_string());
+            println_name_value(
+                &format!("Dust (below {}):", dust_threshold),
+                &dust.to_string(),
+            );
+            println_name_value("Duplicates:", &duplicates.to_string());
+            println_name_value("Active delegations:", &delegated.to_string());
+            println_name_value("Close candidates:", &close_candidates.to_string());
+            println_name_value(
+                "Reclaimable rent:",
+                &format!("{} SOL", lamports_to_sol(reclaimable_lamports)),
+            );
+            if duplicates > 0 || close_candidates > 0 {
+                println!();
+                println!("* Run `spl-token gc` to merge duplicates and close empty accounts");
+            }
+            if delegated > 0 {
+                println!(
+                    "* Run `spl-token revoke` to remove delegations that are no longer needed"
+                );
+            }
+        }
+    }
+    Ok(None)
+}
+
 struct SignOnlyNeedsFullMintSpec {}
 impl offline::ArgsConfig for SignOnlyNeedsFullMintSpec {
     fn sign_only_arg<'a, 'b>(&self, arg: Arg<'a, 'b>) -> Arg<'a, 'b> {
@@ -1916,6 +2077,28 @@ fn main() {
             SubCommand::with_name("gc")
                 .about("Cleanup unnecessary token accounts")
         )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("Report dust balances, duplicate accounts, active delegations and \
+                        close candidates among the token accounts of --owner")
+                .arg(
+                    Arg::with_name("dust_threshold")
+                        .long("dust-threshold")
+                        .validator(is_amount)
+                        .value_name("TOKEN_AMOUNT")
+                        .takes_value(true)
+                        .default_value("0.001")
+                        .help("Report non-zero balances below this amount as dust"),
+                )
+                .arg(
+                    Arg::with_name("output_format")
+                        .long("output")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["json", "json-compact"])
+                        .help("Return information in specified output format"),
+                ),
+        )
         .get_matches();
 
     let mut wallet_manager = None;
@@ -2294,6 +2477,15 @@ fn main() {
             command_multisig(&config, address)
         }
         ("gc", Some(_arg_matches)) => command_gc(&config),
+        ("audit", Some(arg_matches)) => {
+            let dust_threshold = value_t_or_exit!(arg_matches, "dust_threshold", f64);
+            let output_format = match arg_matches.value_of("output_format") {
+                Some("json") => OutputFormat::Json,
+                Some("json-compact") => OutputFormat::JsonCompact,
+                _ => OutputFormat::Display,
+            };
+            command_audit(&config, dust_threshold, output_format)
+        }
         _ => unreachable!(),
     }
     .and_then(|transaction_info| {
@@ -16,6 +16,7 @@ pub struct ParsedTokenAccount {
     pub address: String,
     pub ui_token_account: UiTokenAccount,
     pub is_associated: bool,
+    pub lamports: u64,
 }
 
 pub struct UnsupportedAccount {
@@ -33,6 +34,7 @@ pub fn sort_and_parse_token_accounts(
     let mut includes_aux = false;
     for keyed_account in accounts {
         let address = keyed_account.pubkey;
+        let lamports = keyed_account.account.lamports;
 
         if let UiAccountData::Json(parsed_account) = keyed_account.account.data {
             if parsed_account.program != "spl-token" {
@@ -59,6 +61,7 @@ pub fn sort_and_parse_token_accounts(
                             address,
                             ui_token_account,
                             is_associated,
+                            lamports,
                         };
                         let entry = mint_accounts.entry(mint);
                         match entry {
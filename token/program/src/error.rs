@@ -64,6 +64,12 @@ pub enum TokenError {
     /// Mint decimals mismatch between the client and mint
     #[error("The provided decimals value different from the Mint decimals")]
     MintDecimalsMismatch,
+    /// Ledger does not track the source or destination account
+    #[error("Ledger does not track the source or destination account")]
+    LedgerMismatch,
+    /// Mint authority changed since the proposal was made
+    #[error("Mint authority changed since the proposal was made")]
+    StaleMintAuthorityProposal,
 }
 impl From<TokenError> for ProgramError {
     fn from(e: TokenError) -> Self {
@@ -0,0 +1,166 @@
+//! Token movement events, for indexers
+//!
+//! Built with the `event-log` feature, the program logs one event for every
+//! transfer, mint and burn that changes balances, as a line of the form
+//! `Program log: TokenEvent: <data>`, where `<data>` is the base64 encoding
+//! of the packed `TokenEvent`.  Self-transfers leave balances untouched and
+//! log nothing.  The decoder below is always available to clients.
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_enum::TryFromPrimitive;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Prefix of the log lines carrying an event
+pub const EVENT_LOG_PREFIX: &str = "TokenEvent: ";
+
+/// Prefix added by the runtime to messages logged by a program
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// Kind of token movement
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum TokenEventKind {
+    /// Tokens moved between two accounts, through `Transfer` or
+    /// `TransferChecked`
+    Transfer,
+    /// Tokens minted to an account, through `MintTo` or `MintToChecked`
+    MintTo,
+    /// Tokens burned from an account, through `Burn` or `BurnChecked`
+    Burn,
+}
+
+/// Token movement logged by the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenEvent {
+    /// Kind of movement, the first byte of the packed event
+    pub kind: TokenEventKind,
+    /// Mint of the tokens
+    pub mint: Pubkey,
+    /// Account debited, the default pubkey for mints
+    pub source: Pubkey,
+    /// Account credited, the default pubkey for burns
+    pub destination: Pubkey,
+    /// Amount of tokens moved
+    pub amount: u64,
+    /// Signer of the instruction: owner, delegate, mint authority, or the
+    /// multisig account standing for one of them
+    pub authority: Pubkey,
+}
+
+impl TokenEvent {
+    /// Length of a packed event
+    pub const LEN: usize = 1 + 32 + 32 + 32 + 8 + 32;
+
+    /// Pack the event into its fixed-size representation
+    pub fn pack(&self) -> [u8; Self::LEN] {
+        let mut data = [0u8; Self::LEN];
+        let dst = array_mut_ref![data, 0, TokenEvent::LEN];
+        let (kind_dst, mint_dst, source_dst, destination_dst, amount_dst, authority_dst) =
+            mut_array_refs![dst, 1, 32, 32, 32, 8, 32];
+        kind_dst[0] = self.kind as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        source_dst.copy_from_slice(self.source.as_ref());
+        destination_dst.copy_from_slice(self.destination.as_ref());
+        *amount_dst = self.amount.to_le_bytes();
+        authority_dst.copy_from_slice(self.authority.as_ref());
+        data
+    }
+
+    /// Unpack an event from its fixed-size representation
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() != Self::LEN {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let src = array_ref![input, 0, TokenEvent::LEN];
+        let (kind, mint, source, destination, amount, authority) =
+            array_refs![src, 1, 32, 32, 32, 8, 32];
+        Ok(TokenEvent {
+            kind: TokenEventKind::try_from_primitive(kind[0])
+                .or(Err(ProgramError::InvalidArgument))?,
+            mint: Pubkey::new_from_array(*mint),
+            source: Pubkey::new_from_array(*source),
+            destination: Pubkey::new_from_array(*destination),
+            amount: u64::from_le_bytes(*amount),
+            authority: Pubkey::new_from_array(*authority),
+        })
+    }
+
+    /// Log the event
+    #[cfg(feature = "event-log")]
+    pub fn log(&self) {
+        solana_program::msg!("{}{}", EVENT_LOG_PREFIX, base64::encode(&self.pack()[..]));
+    }
+
+    /// Parse an event out of a transaction log line, returning `None` for
+    /// lines that do not carry an event
+    pub fn from_log(log: &str) -> Option<Self> {
+        let log = log.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(log);
+        let data = base64::decode(log.strip_prefix(EVENT_LOG_PREFIX)?).ok()?;
+        Self::unpack(&data).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log_round_trip() {
+        let event = TokenEvent {
+            kind: TokenEventKind::Burn,
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::default(),
+            amount: 42,
+            authority: Pubkey::new_unique(),
+        };
+        let data = event.pack();
+        // the first byte is the event kind
+        assert_eq!(data[0], 2);
+        assert_eq!(TokenEvent::unpack(&data), Ok(event));
+
+        let log = format!(
+            "{}{}{}",
+            PROGRAM_LOG_PREFIX,
+            EVENT_LOG_PREFIX,
+            base64::encode(&data[..])
+        );
+        assert_eq!(TokenEvent::from_log(&log), Some(event));
+        assert_eq!(
+            TokenEvent::from_log(&log[PROGRAM_LOG_PREFIX.len()..]),
+            Some(event)
+        );
+
+        assert_eq!(TokenEvent::from_log("Program log: Instruction: Burn"), None);
+        assert_eq!(
+            TokenEvent::from_log("Program log: TokenEvent: not base64"),
+            None
+        );
+        assert_eq!(
+            TokenEvent::from_log(&format!(
+                "{}{}",
+                EVENT_LOG_PREFIX,
+                base64::encode(&data[1..])
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unknown_event_kind() {
+        let mut data = TokenEvent {
+            kind: TokenEventKind::Transfer,
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount: 1,
+            authority: Pubkey::new_unique(),
+        }
+        .pack();
+        data[0] = 3;
+        assert_eq!(
+            TokenEvent::unpack(&data),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
@@ -102,6 +102,9 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The destination account.
     ///   2. `[]` The source account's multisignature owner/delegate.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    ///   * Optionally, after either set of accounts
+    ///   0. ..N `[writable]` Ledgers of the source and/or destination account.
     Transfer {
         /// The amount of tokens to transfer.
         amount: u64,
@@ -140,6 +143,10 @@ pub enum TokenInstruction {
     Revoke,
     /// Sets a new authority of a mint or account.
     ///
+    /// The change takes effect immediately.  `ProposeMintAuthority` offers a
+    /// two-step alternative for mint authorities, where the new authority must
+    /// accept before the mint changes.
+    ///
     /// Accounts expected by this instruction:
     ///
     ///   * Single authority
@@ -265,6 +272,9 @@ pub enum TokenInstruction {
     ///   2. `[writable]` The destination account.
     ///   3. `[]` The source account's multisignature owner/delegate.
     ///   4. ..4+M `[signer]` M signer accounts.
+    ///
+    ///   * Optionally, after either set of accounts
+    ///   0. ..N `[writable]` Ledgers of the source and/or destination account.
     TransferChecked {
         /// The amount of tokens to transfer.
         amount: u64,
@@ -363,6 +373,132 @@ pub enum TokenInstruction {
         /// The new account's owner/multisignature.
         owner: Pubkey,
     },
+    /// Initializes a ledger recording the cumulative amounts received and sent
+    /// by a token account, along with the slot of its last recorded transfer.
+    ///
+    /// Ledgers are opt-in: a `Transfer` or `TransferChecked` only updates the
+    /// ledgers passed after its regular accounts, see `add_ledger_accounts`.
+    ///
+    /// The `InitializeLedger` instruction MUST be included within the same
+    /// Transaction as the system program's `CreateAccount` instruction that
+    /// creates the ledger account being initialized.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[writable]` The ledger account to initialize.
+    ///   1. `[]` The token account tracked by the ledger.
+    ///   2. `[]` Rent sysvar
+    ///   3. `[signer]` The token account's owner.
+    ///
+    ///   * Multisignature owner
+    ///   0. `[writable]` The ledger account to initialize.
+    ///   1. `[]` The token account tracked by the ledger.
+    ///   2. `[]` Rent sysvar
+    ///   3. `[]` The token account's multisignature owner.
+    ///   4. ..4+M `[signer]` M signer accounts.
+    InitializeLedger,
+    /// Proposes a new minting or freeze authority for a mint.  Unlike
+    /// `SetAuthority`, the mint is left untouched until the new authority
+    /// accepts the proposal with `AcceptMintAuthority`, and the current
+    /// authority may withdraw it with `CancelMintAuthorityProposal`.
+    ///
+    /// The `ProposeMintAuthority` instruction MUST be included within the same
+    /// Transaction as the system program's `CreateAccount` instruction that
+    /// creates the proposal account being initialized.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single authority
+    ///   0. `[writable]` The proposal account to initialize.
+    ///   1. `[]` The mint.
+    ///   2. `[]` Rent sysvar
+    ///   3. `[signer]` The mint's current authority of the proposed type.
+    ///
+    ///   * Multisignature authority
+    ///   0. `[writable]` The proposal account to initialize.
+    ///   1. `[]` The mint.
+    ///   2. `[]` Rent sysvar
+    ///   3. `[]` The mint's current multisignature authority.
+    ///   4. ..4+M `[signer]` M signer accounts.
+    ProposeMintAuthority {
+        /// The type of authority to transfer, `MintTokens` or `FreezeAccount`.
+        authority_type: AuthorityType,
+        /// The proposed authority/multisignature.
+        new_authority: Pubkey,
+    },
+    /// Accepts a proposed mint authority, setting it on the mint and closing
+    /// the proposal account.  Fails if the mint's authority changed since the
+    /// proposal was made.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single authority
+    ///   0. `[writable]` The proposal account.
+    ///   1. `[writable]` The mint.
+    ///   2. `[writable]` The destination account for the proposal's lamports.
+    ///   3. `[signer]` The proposed authority.
+    ///
+    ///   * Multisignature authority
+    ///   0. `[writable]` The proposal account.
+    ///   1. `[writable]` The mint.
+    ///   2. `[writable]` The destination account for the proposal's lamports.
+    ///   3. `[]` The proposed multisignature authority.
+    ///   4. ..4+M `[signer]` M signer accounts.
+    AcceptMintAuthority,
+    /// Cancels a mint authority proposal by closing its account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single authority
+    ///   0. `[writable]` The proposal account.
+    ///   1. `[]` The mint.
+    ///   2. `[writable]` The destination account for the proposal's lamports.
+    ///   3. `[signer]` The mint's current authority of the proposed type.
+    ///
+    ///   * Multisignature authority
+    ///   0. `[writable]` The proposal account.
+    ///   1. `[]` The mint.
+    ///   2. `[writable]` The destination account for the proposal's lamports.
+    ///   3. `[]` The mint's current multisignature authority.
+    ///   4. ..4+M `[signer]` M signer accounts.
+    CancelMintAuthorityProposal,
+    /// Closes several accounts at once, transferring all their SOL to the
+    /// destination account.  Each account follows the rules of `CloseAccount`,
+    /// and all of them must share the same close authority.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[writable]` The destination account.
+    ///   1. `[signer]` The accounts' owner.
+    ///   2. ..2+N `[writable]` The N accounts to close.
+    ///
+    ///   * Multisignature owner
+    ///   0. `[writable]` The destination account.
+    ///   1. `[]` The accounts' multisignature owner.
+    ///   2. ..2+N `[writable]` The N accounts to close.
+    ///   2+N. ..2+N+M `[signer]` M signer accounts.
+    CloseAccountsBatch {
+        /// The number of accounts to close (N).
+        count: u8,
+    },
+    /// Proves the signer owns an account, without moving any tokens.  The
+    /// program logs the owner, the account, its mint and the current slot,
+    /// for off-chain verifiers to check in the transaction logs.  Frozen
+    /// accounts can be proven too.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The account.
+    ///   1. `[signer]` The account's owner.
+    ///
+    ///   * Multisignature owner
+    ///   0. `[]` The account.
+    ///   1. `[]` The account's multisignature owner.
+    ///   2. ..2+M `[signer]` M signer accounts.
+    VerifyOwnership,
 }
 impl TokenInstruction {
     /// Unpacks a byte buffer into a [TokenInstruction](enum.TokenInstruction.html).
@@ -464,6 +600,23 @@ impl TokenInstruction {
                 let (owner, _rest) = Self::unpack_pubkey(rest)?;
                 Self::InitializeAccount2 { owner }
             }
+            17 => Self::InitializeLedger,
+            18 => {
+                let (&authority_type, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let authority_type = AuthorityType::from(authority_type)?;
+                let (new_authority, _rest) = Self::unpack_pubkey(rest)?;
+                Self::ProposeMintAuthority {
+                    authority_type,
+                    new_authority,
+                }
+            }
+            19 => Self::AcceptMintAuthority,
+            20 => Self::CancelMintAuthorityProposal,
+            21 => {
+                let &count = rest.get(0).ok_or(InvalidInstruction)?;
+                Self::CloseAccountsBatch { count }
+            }
+            22 => Self::VerifyOwnership,
 
             _ => return Err(TokenError::InvalidInstruction.into()),
         })
@@ -540,6 +693,22 @@ impl TokenInstruction {
                 buf.push(16);
                 buf.extend_from_slice(owner.as_ref());
             }
+            Self::InitializeLedger => buf.push(17),
+            Self::ProposeMintAuthority {
+                authority_type,
+                ref new_authority,
+            } => {
+                buf.push(18);
+                buf.push(authority_type.into());
+                buf.extend_from_slice(new_authority.as_ref());
+            }
+            Self::AcceptMintAuthority => buf.push(19),
+            Self::CancelMintAuthorityProposal => buf.push(20),
+            &Self::CloseAccountsBatch { count } => {
+                buf.push(21);
+                buf.push(count);
+            }
+            Self::VerifyOwnership => buf.push(22),
         };
         buf
     }
@@ -579,7 +748,7 @@ impl TokenInstruction {
 
 /// Specifies the authority type for SetAuthority instructions
 #[repr(u8)]
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AuthorityType {
     /// Authority to mint new tokens
     MintTokens,
@@ -592,7 +761,7 @@ pub enum AuthorityType {
 }
 
 impl AuthorityType {
-    fn into(&self) -> u8 {
+    pub(crate) fn into(&self) -> u8 {
         match self {
             AuthorityType::MintTokens => 0,
             AuthorityType::FreezeAccount => 1,
@@ -601,7 +770,7 @@ impl AuthorityType {
         }
     }
 
-    fn from(index: u8) -> Result<Self, ProgramError> {
+    pub(crate) fn from(index: u8) -> Result<Self, ProgramError> {
         match index {
             0 => Ok(AuthorityType::MintTokens),
             1 => Ok(AuthorityType::FreezeAccount),
@@ -1102,6 +1271,202 @@ pub fn burn_checked(
     })
 }
 
+/// Creates an `InitializeLedger` instruction.
+pub fn initialize_ledger(
+    token_program_id: &Pubkey,
+    ledger_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializeLedger.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*ledger_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ProposeMintAuthority` instruction.
+pub fn propose_mint_authority(
+    token_program_id: &Pubkey,
+    proposal_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_type: AuthorityType,
+    new_authority_pubkey: &Pubkey,
+    current_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::ProposeMintAuthority {
+        authority_type,
+        new_authority: *new_authority_pubkey,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*proposal_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *current_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `AcceptMintAuthority` instruction.
+pub fn accept_mint_authority(
+    token_program_id: &Pubkey,
+    proposal_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    new_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::AcceptMintAuthority.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*proposal_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *new_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `CancelMintAuthorityProposal` instruction.
+pub fn cancel_mint_authority_proposal(
+    token_program_id: &Pubkey,
+    proposal_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    current_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::CancelMintAuthorityProposal.pack();
+
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*proposal_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *current_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `CloseAccountsBatch` instruction.
+pub fn close_accounts_batch(
+    token_program_id: &Pubkey,
+    account_pubkeys: &[&Pubkey],
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    if account_pubkeys.is_empty() || account_pubkeys.len() > u8::MAX as usize {
+        return Err(TokenError::InvalidInstruction.into());
+    }
+    let data = TokenInstruction::CloseAccountsBatch {
+        count: account_pubkeys.len() as u8,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 + account_pubkeys.len() + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for account_pubkey in account_pubkeys.iter() {
+        accounts.push(AccountMeta::new(**account_pubkey, false));
+    }
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `VerifyOwnership` instruction.
+pub fn verify_ownership(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::VerifyOwnership.pack();
+
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(*account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Appends ledger accounts to a `Transfer` or `TransferChecked` instruction so
+/// that the transfer is recorded in them.
+pub fn add_ledger_accounts(instruction: &mut Instruction, ledger_pubkeys: &[&Pubkey]) {
+    for ledger_pubkey in ledger_pubkeys.iter() {
+        instruction
+            .accounts
+            .push(AccountMeta::new(**ledger_pubkey, false));
+    }
+}
+
 /// Utility function that checks index is between MIN_SIGNERS and MAX_SIGNERS
 pub fn is_valid_signer_index(index: usize) -> bool {
     (MIN_SIGNERS..=MAX_SIGNERS).contains(&index)
@@ -1271,5 +1636,51 @@ mod test {
         assert_eq!(packed, expect);
         let unpacked = TokenInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeLedger;
+        let packed = check.pack();
+        let expect = vec![17u8];
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::ProposeMintAuthority {
+            authority_type: AuthorityType::FreezeAccount,
+            new_authority: Pubkey::new(&[3u8; 32]),
+        };
+        let packed = check.pack();
+        let mut expect = vec![18u8, 1];
+        expect.extend_from_slice(&[3u8; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::AcceptMintAuthority;
+        let packed = check.pack();
+        let expect = vec![19u8];
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::CancelMintAuthorityProposal;
+        let packed = check.pack();
+        let expect = vec![20u8];
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::CloseAccountsBatch { count: 3 };
+        let packed = check.pack();
+        let expect = vec![21u8, 3];
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::VerifyOwnership;
+        let packed = check.pack();
+        let expect = vec![22u8];
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
     }
 }
@@ -1,9 +1,11 @@
 //! Program state processor
 
+#[cfg(feature = "event-log")]
+use crate::event::{TokenEvent, TokenEventKind};
 use crate::{
     error::TokenError,
     instruction::{is_valid_signer_index, AuthorityType, TokenInstruction, MAX_SIGNERS},
-    state::{Account, AccountState, Mint, Multisig},
+    state::{Account, AccountLedger, AccountState, Mint, MintAuthorityProposal, Multisig},
 };
 use num_traits::FromPrimitive;
 use solana_program::{
@@ -15,7 +17,7 @@ use solana_program::{
     program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
 /// Program state handler.
@@ -253,6 +255,113 @@ impl Processor {
         Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
         Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
 
+        Self::record_transfer(
+            program_id,
+            account_info_iter.as_slice(),
+            source_account_info.key,
+            dest_account_info.key,
+            amount,
+        )?;
+
+        #[cfg(feature = "event-log")]
+        TokenEvent {
+            kind: TokenEventKind::Transfer,
+            mint: source_account.mint,
+            source: *source_account_info.key,
+            destination: *dest_account_info.key,
+            amount,
+            authority: *authority_info.key,
+        }
+        .log();
+
+        Ok(())
+    }
+
+    /// Updates the ledgers found among the trailing accounts of a transfer.
+    /// Multisig signer accounts are never owned by the token program, so they
+    /// are skipped along with any account that isn't a ledger.
+    fn record_transfer(
+        program_id: &Pubkey,
+        trailing_infos: &[AccountInfo],
+        source: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let mut slot = None;
+        for (i, ledger_info) in trailing_infos.iter().enumerate() {
+            if ledger_info.owner != program_id
+                || ledger_info.data_len() != AccountLedger::get_packed_len()
+                || trailing_infos[..i]
+                    .iter()
+                    .any(|info| info.key == ledger_info.key)
+            {
+                continue;
+            }
+
+            let mut ledger = AccountLedger::unpack(&ledger_info.data.borrow())?;
+            if ledger.account == *source {
+                ledger.total_sent = ledger
+                    .total_sent
+                    .checked_add(amount)
+                    .ok_or(TokenError::Overflow)?;
+            } else if ledger.account == *destination {
+                ledger.total_received = ledger
+                    .total_received
+                    .checked_add(amount)
+                    .ok_or(TokenError::Overflow)?;
+            } else {
+                return Err(TokenError::LedgerMismatch.into());
+            }
+            if slot.is_none() {
+                slot = Some(Clock::get()?.slot);
+            }
+            ledger.last_activity_slot = slot.unwrap();
+
+            AccountLedger::pack(ledger, &mut ledger_info.data.borrow_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Processes an [InitializeLedger](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_ledger(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let ledger_info = next_account_info(account_info_iter)?;
+        let ledger_info_data_len = ledger_info.data_len();
+        let account_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let mut ledger = AccountLedger::unpack_unchecked(&ledger_info.data.borrow())?;
+        if ledger.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        if !rent.is_exempt(ledger_info.lamports(), ledger_info_data_len) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        if account_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let account = Account::unpack(&account_info.data.borrow())?;
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        ledger.is_initialized = true;
+        ledger.account = *account_info.key;
+        ledger.total_received = 0;
+        ledger.total_sent = 0;
+        ledger.last_activity_slot = 0;
+
+        AccountLedger::pack(ledger, &mut ledger_info.data.borrow_mut())?;
+
         Ok(())
     }
 
@@ -426,6 +535,158 @@ impl Processor {
         Ok(())
     }
 
+    /// Returns the mint's current authority of a type that supports
+    /// two-step transfers
+    fn get_mint_authority(
+        mint: &Mint,
+        authority_type: AuthorityType,
+    ) -> Result<Pubkey, ProgramError> {
+        match authority_type {
+            AuthorityType::MintTokens => mint
+                .mint_authority
+                .ok_or_else(|| TokenError::FixedSupply.into()),
+            AuthorityType::FreezeAccount => mint
+                .freeze_authority
+                .ok_or_else(|| TokenError::MintCannotFreeze.into()),
+            _ => Err(TokenError::AuthorityTypeNotSupported.into()),
+        }
+    }
+
+    /// Closes a mint authority proposal, moving its lamports to the destination
+    fn close_mint_authority_proposal(
+        proposal_info: &AccountInfo,
+        mut proposal: MintAuthorityProposal,
+        dest_account_info: &AccountInfo,
+    ) -> ProgramResult {
+        let dest_starting_lamports = dest_account_info.lamports();
+        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+            .checked_add(proposal_info.lamports())
+            .ok_or(TokenError::Overflow)?;
+
+        **proposal_info.lamports.borrow_mut() = 0;
+        proposal.is_initialized = false;
+
+        MintAuthorityProposal::pack(proposal, &mut proposal_info.data.borrow_mut())
+    }
+
+    /// Processes a [ProposeMintAuthority](enum.TokenInstruction.html) instruction.
+    pub fn process_propose_mint_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        authority_type: AuthorityType,
+        new_authority: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let proposal_info = next_account_info(account_info_iter)?;
+        let proposal_info_data_len = proposal_info.data_len();
+        let mint_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut proposal = MintAuthorityProposal::unpack_unchecked(&proposal_info.data.borrow())?;
+        if proposal.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        if !rent.is_exempt(proposal_info.lamports(), proposal_info_data_len) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        if mint_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mint = Mint::unpack(&mint_info.data.borrow())?;
+        let current_authority = Self::get_mint_authority(&mint, authority_type)?;
+        Self::validate_owner(
+            program_id,
+            &current_authority,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        proposal.is_initialized = true;
+        proposal.mint = *mint_info.key;
+        proposal.authority_type = authority_type;
+        proposal.current_authority = current_authority;
+        proposal.new_authority = new_authority;
+
+        MintAuthorityProposal::pack(proposal, &mut proposal_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes an [AcceptMintAuthority](enum.TokenInstruction.html) instruction.
+    pub fn process_accept_mint_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let proposal_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if proposal_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let proposal = MintAuthorityProposal::unpack(&proposal_info.data.borrow())?;
+        if proposal.mint != *mint_info.key {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
+        if Self::get_mint_authority(&mint, proposal.authority_type)? != proposal.current_authority {
+            return Err(TokenError::StaleMintAuthorityProposal.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &proposal.new_authority,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        match proposal.authority_type {
+            AuthorityType::MintTokens => {
+                mint.mint_authority = COption::Some(proposal.new_authority)
+            }
+            _ => mint.freeze_authority = COption::Some(proposal.new_authority),
+        }
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Self::close_mint_authority_proposal(proposal_info, proposal, dest_account_info)
+    }
+
+    /// Processes a [CancelMintAuthorityProposal](enum.TokenInstruction.html) instruction.
+    pub fn process_cancel_mint_authority_proposal(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let proposal_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if proposal_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let proposal = MintAuthorityProposal::unpack(&proposal_info.data.borrow())?;
+        if proposal.mint != *mint_info.key {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mint = Mint::unpack(&mint_info.data.borrow())?;
+        let current_authority = Self::get_mint_authority(&mint, proposal.authority_type)?;
+        Self::validate_owner(
+            program_id,
+            &current_authority,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        Self::close_mint_authority_proposal(proposal_info, proposal, dest_account_info)
+    }
+
     /// Processes a [MintTo](enum.TokenInstruction.html) instruction.
     pub fn process_mint_to(
         program_id: &Pubkey,
@@ -480,6 +741,17 @@ impl Processor {
         Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
         Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
 
+        #[cfg(feature = "event-log")]
+        TokenEvent {
+            kind: TokenEventKind::MintTo,
+            mint: *mint_info.key,
+            source: Pubkey::default(),
+            destination: *dest_account_info.key,
+            amount,
+            authority: *owner_info.key,
+        }
+        .log();
+
         Ok(())
     }
 
@@ -558,6 +830,17 @@ impl Processor {
         Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
         Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
 
+        #[cfg(feature = "event-log")]
+        TokenEvent {
+            kind: TokenEventKind::Burn,
+            mint: *mint_info.key,
+            source: *source_account_info.key,
+            destination: Pubkey::default(),
+            amount,
+            authority: *authority_info.key,
+        }
+        .log();
+
         Ok(())
     }
 
@@ -568,6 +851,23 @@ impl Processor {
         let dest_account_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
 
+        Self::close_account(
+            program_id,
+            source_account_info,
+            dest_account_info,
+            authority_info,
+            account_info_iter.as_slice(),
+        )
+    }
+
+    /// Closes a token account, moving its lamports to the destination
+    fn close_account(
+        program_id: &Pubkey,
+        source_account_info: &AccountInfo,
+        dest_account_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
         let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
         if !source_account.is_native() && source_account.amount != 0 {
             return Err(TokenError::NonNativeHasBalance.into());
@@ -576,12 +876,7 @@ impl Processor {
         let authority = source_account
             .close_authority
             .unwrap_or(source_account.owner);
-        Self::validate_owner(
-            program_id,
-            &authority,
-            authority_info,
-            account_info_iter.as_slice(),
-        )?;
+        Self::validate_owner(program_id, &authority, authority_info, signers)?;
 
         let dest_starting_lamports = dest_account_info.lamports();
         **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
@@ -596,6 +891,71 @@ impl Processor {
         Ok(())
     }
 
+    /// Processes a [CloseAccountsBatch](enum.TokenInstruction.html) instruction.
+    pub fn process_close_accounts_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        count: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let count = count as usize;
+        if count == 0 || count > account_info_iter.as_slice().len() {
+            return Err(TokenError::InvalidInstruction.into());
+        }
+        let (source_account_infos, signers) = account_info_iter.as_slice().split_at(count);
+
+        for source_account_info in source_account_infos {
+            if source_account_info.key == dest_account_info.key {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Self::close_account(
+                program_id,
+                source_account_info,
+                dest_account_info,
+                authority_info,
+                signers,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes a [VerifyOwnership](enum.TokenInstruction.html) instruction.
+    pub fn process_verify_ownership(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        // a lookalike account of another program proves nothing
+        if account_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let account = Account::unpack(&account_info.data.borrow())?;
+
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        msg!(
+            "Ownership verified: owner {} account {} mint {} slot {}",
+            account.owner,
+            account_info.key,
+            account.mint,
+            Clock::get()?.slot
+        );
+
+        Ok(())
+    }
+
     /// Processes a [FreezeAccount](enum.TokenInstruction.html) or a
     /// [ThawAccount](enum.TokenInstruction.html) instruction.
     pub fn process_toggle_freeze_account(
@@ -721,6 +1081,38 @@ impl Processor {
                 msg!("Instruction: BurnChecked");
                 Self::process_burn(program_id, accounts, amount, Some(decimals))
             }
+            TokenInstruction::InitializeLedger => {
+                msg!("Instruction: InitializeLedger");
+                Self::process_initialize_ledger(program_id, accounts)
+            }
+            TokenInstruction::ProposeMintAuthority {
+                authority_type,
+                new_authority,
+            } => {
+                msg!("Instruction: ProposeMintAuthority");
+                Self::process_propose_mint_authority(
+                    program_id,
+                    accounts,
+                    authority_type,
+                    new_authority,
+                )
+            }
+            TokenInstruction::AcceptMintAuthority => {
+                msg!("Instruction: AcceptMintAuthority");
+                Self::process_accept_mint_authority(program_id, accounts)
+            }
+            TokenInstruction::CancelMintAuthorityProposal => {
+                msg!("Instruction: CancelMintAuthorityProposal");
+                Self::process_cancel_mint_authority_proposal(program_id, accounts)
+            }
+            TokenInstruction::CloseAccountsBatch { count } => {
+                msg!("Instruction: CloseAccountsBatch");
+                Self::process_close_accounts_batch(program_id, accounts, count)
+            }
+            TokenInstruction::VerifyOwnership => {
+                msg!("Instruction: VerifyOwnership");
+                Self::process_verify_ownership(program_id, accounts)
+            }
         }
     }
 
@@ -799,6 +1191,12 @@ impl PrintProgramError for TokenError {
             TokenError::MintDecimalsMismatch => {
                 msg!("Error: decimals different from the Mint decimals")
             }
+            TokenError::LedgerMismatch => {
+                msg!("Error: Ledger does not track the source or destination account")
+            }
+            TokenError::StaleMintAuthorityProposal => {
+                msg!("Error: Mint authority changed since the proposal was made")
+            }
         }
     }
 }
@@ -876,6 +1274,27 @@ mod tests {
         assert_ne!(Account::get_packed_len(), 0);
         assert_ne!(Account::get_packed_len(), Multisig::get_packed_len());
         assert_ne!(Multisig::get_packed_len(), 0);
+        assert_ne!(AccountLedger::get_packed_len(), 0);
+        assert_ne!(AccountLedger::get_packed_len(), Mint::get_packed_len());
+        assert_ne!(AccountLedger::get_packed_len(), Account::get_packed_len());
+        assert_ne!(AccountLedger::get_packed_len(), Multisig::get_packed_len());
+        assert_ne!(MintAuthorityProposal::get_packed_len(), 0);
+        assert_ne!(
+            MintAuthorityProposal::get_packed_len(),
+            Mint::get_packed_len()
+        );
+        assert_ne!(
+            MintAuthorityProposal::get_packed_len(),
+            Account::get_packed_len()
+        );
+        assert_ne!(
+            MintAuthorityProposal::get_packed_len(),
+            Multisig::get_packed_len()
+        );
+        assert_ne!(
+            MintAuthorityProposal::get_packed_len(),
+            AccountLedger::get_packed_len()
+        );
     }
 
     #[test]
@@ -981,6 +1400,58 @@ mod tests {
         assert_eq!(packed, expect);
         let unpacked = Multisig::unpack(&packed).unwrap();
         assert_eq!(unpacked, check);
+
+        // AccountLedger
+        let check = AccountLedger {
+            is_initialized: true,
+            account: Pubkey::new(&[1; 32]),
+            total_received: 2,
+            total_sent: 3,
+            last_activity_slot: 4,
+        };
+        let mut packed = vec![0; AccountLedger::get_packed_len() + 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            AccountLedger::pack(check, &mut packed)
+        );
+        let mut packed = vec![0; AccountLedger::get_packed_len()];
+        AccountLedger::pack(check, &mut packed).unwrap();
+        let expect = vec![
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+            1, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(packed, expect);
+        let unpacked = AccountLedger::unpack(&packed).unwrap();
+        assert_eq!(unpacked, check);
+
+        // MintAuthorityProposal
+        let check = MintAuthorityProposal {
+            is_initialized: true,
+            mint: Pubkey::new(&[1; 32]),
+            authority_type: AuthorityType::FreezeAccount,
+            current_authority: Pubkey::new(&[2; 32]),
+            new_authority: Pubkey::new(&[3; 32]),
+        };
+        let mut packed = vec![0; MintAuthorityProposal::get_packed_len() - 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            MintAuthorityProposal::pack(check, &mut packed)
+        );
+        let mut packed = vec![0; MintAuthorityProposal::get_packed_len()];
+        MintAuthorityProposal::pack(check, &mut packed).unwrap();
+        let mut expect = vec![1];
+        expect.extend_from_slice(&[1; 32]);
+        expect.push(1);
+        expect.extend_from_slice(&[2; 32]);
+        expect.extend_from_slice(&[3; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = MintAuthorityProposal::unpack(&packed).unwrap();
+        assert_eq!(unpacked, check);
+        packed[33] = 4;
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            MintAuthorityProposal::unpack(&packed)
+        );
     }
 
     #[test]
@@ -5729,4 +6200,1027 @@ mod tests {
 
         assert_eq!(account_account, account2_account);
     }
+
+    const LEDGER_TEST_SLOT: u64 = 42;
+
+    struct ClockSyscallStubs {}
+    impl solana_program::program_stubs::SyscallStubs for ClockSyscallStubs {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Clock) = Clock {
+                    slot: LEDGER_TEST_SLOT,
+                    ..Clock::default()
+                };
+            }
+            solana_program::entrypoint::SUCCESS
+        }
+    }
+
+    fn ledger_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(AccountLedger::get_packed_len())
+    }
+
+    #[test]
+    fn test_ledger() {
+        solana_program::program_stubs::set_syscall_stubs(Box::new(ClockSyscallStubs {}));
+
+        let program_id = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let ledger_key = Pubkey::new_unique();
+        let mut ledger_account = SolanaAccount::new(
+            ledger_minimum_balance(),
+            AccountLedger::get_packed_len(),
+            &program_id,
+        );
+        let ledger2_key = Pubkey::new_unique();
+        let mut ledger2_account = SolanaAccount::new(
+            ledger_minimum_balance(),
+            AccountLedger::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // create accounts
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner2_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner2_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // mint to account
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1000).unwrap(),
+            vec![&mut mint_account, &mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // missing owner signature
+        let mut instruction =
+            initialize_ledger(&program_id, &ledger_key, &account_key, &owner_key, &[]).unwrap();
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut ledger_account,
+                    &mut account_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // wrong owner
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                initialize_ledger(&program_id, &ledger_key, &account_key, &owner2_key, &[])
+                    .unwrap(),
+                vec![
+                    &mut ledger_account,
+                    &mut account_account,
+                    &mut rent_sysvar,
+                    &mut owner2_account,
+                ],
+            )
+        );
+
+        // not rent exempt
+        ledger_account.lamports -= 1;
+        assert_eq!(
+            Err(TokenError::NotRentExempt.into()),
+            do_process_instruction(
+                initialize_ledger(&program_id, &ledger_key, &account_key, &owner_key, &[]).unwrap(),
+                vec![
+                    &mut ledger_account,
+                    &mut account_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+        ledger_account.lamports += 1;
+
+        // create ledgers
+        do_process_instruction(
+            initialize_ledger(&program_id, &ledger_key, &account_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut ledger_account,
+                &mut account_account,
+                &mut rent_sysvar,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_ledger(&program_id, &ledger2_key, &account2_key, &owner2_key, &[]).unwrap(),
+            vec![
+                &mut ledger2_account,
+                &mut account2_account,
+                &mut rent_sysvar,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+        let ledger = AccountLedger::unpack(&ledger_account.data).unwrap();
+        assert_eq!(
+            ledger,
+            AccountLedger {
+                is_initialized: true,
+                account: account_key,
+                total_received: 0,
+                total_sent: 0,
+                last_activity_slot: 0,
+            }
+        );
+
+        // ledger already initialized
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_ledger(&program_id, &ledger_key, &account_key, &owner_key, &[]).unwrap(),
+                vec![
+                    &mut ledger_account,
+                    &mut account_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // transfer without ledgers is not recorded
+        do_process_instruction(
+            transfer(
+                &program_id,
+                &account_key,
+                &account2_key,
+                &owner_key,
+                &[],
+                100,
+            )
+            .unwrap(),
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let ledger = AccountLedger::unpack(&ledger_account.data).unwrap();
+        assert_eq!(ledger.total_sent, 0);
+
+        // transfer records into both ledgers
+        let mut instruction = transfer(
+            &program_id,
+            &account_key,
+            &account2_key,
+            &owner_key,
+            &[],
+            300,
+        )
+        .unwrap();
+        add_ledger_accounts(&mut instruction, &[&ledger_key, &ledger2_key]);
+        do_process_instruction(
+            instruction,
+            vec![
+                &mut account_account,
+                &mut account2_account,
+                &mut owner_account,
+                &mut ledger_account,
+                &mut ledger2_account,
+            ],
+        )
+        .unwrap();
+        let ledger = AccountLedger::unpack(&ledger_account.data).unwrap();
+        assert_eq!(ledger.total_sent, 300);
+        assert_eq!(ledger.total_received, 0);
+        assert_eq!(ledger.last_activity_slot, LEDGER_TEST_SLOT);
+        let ledger2 = AccountLedger::unpack(&ledger2_account.data).unwrap();
+        assert_eq!(ledger2.total_sent, 0);
+        assert_eq!(ledger2.total_received, 300);
+        assert_eq!(ledger2.last_activity_slot, LEDGER_TEST_SLOT);
+
+        // transfer back, checked, recording only the destination
+        let mut instruction = transfer_checked(
+            &program_id,
+            &account2_key,
+            &mint_key,
+            &account_key,
+            &owner2_key,
+            &[],
+            50,
+            2,
+        )
+        .unwrap();
+        add_ledger_accounts(&mut instruction, &[&ledger_key]);
+        do_process_instruction(
+            instruction,
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut account_account,
+                &mut owner2_account,
+                &mut ledger_account,
+            ],
+        )
+        .unwrap();
+        let ledger = AccountLedger::unpack(&ledger_account.data).unwrap();
+        assert_eq!(ledger.total_sent, 300);
+        assert_eq!(ledger.total_received, 50);
+        let ledger2 = AccountLedger::unpack(&ledger2_account.data).unwrap();
+        assert_eq!(ledger2.total_sent, 0);
+
+        // ledger of an unrelated account
+        let account3_key = Pubkey::new_unique();
+        let mut account3_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        do_process_instruction(
+            initialize_account(&program_id, &account3_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account3_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        let mut instruction = transfer(
+            &program_id,
+            &account_key,
+            &account3_key,
+            &owner_key,
+            &[],
+            10,
+        )
+        .unwrap();
+        add_ledger_accounts(&mut instruction, &[&ledger2_key]);
+        assert_eq!(
+            Err(TokenError::LedgerMismatch.into()),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut account_account,
+                    &mut account3_account,
+                    &mut owner_account,
+                    &mut ledger2_account,
+                ],
+            )
+        );
+    }
+
+    fn proposal_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(MintAuthorityProposal::get_packed_len())
+    }
+
+    #[test]
+    fn test_mint_authority_proposal() {
+        let program_id = Pubkey::new_unique();
+        let proposal_key = Pubkey::new_unique();
+        let mut proposal_account = SolanaAccount::new(
+            proposal_minimum_balance(),
+            MintAuthorityProposal::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let owner3_key = Pubkey::new_unique();
+        let mut owner3_account = SolanaAccount::default();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint without a freeze authority
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+
+        // mint cannot freeze
+        assert_eq!(
+            Err(TokenError::MintCannotFreeze.into()),
+            do_process_instruction(
+                propose_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    AuthorityType::FreezeAccount,
+                    &owner2_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // account authorities are not supported
+        assert_eq!(
+            Err(TokenError::AuthorityTypeNotSupported.into()),
+            do_process_instruction(
+                propose_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    AuthorityType::AccountOwner,
+                    &owner2_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // wrong current authority
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                propose_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    AuthorityType::MintTokens,
+                    &owner2_key,
+                    &owner2_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut rent_sysvar,
+                    &mut owner2_account,
+                ],
+            )
+        );
+
+        // propose
+        do_process_instruction(
+            propose_mint_authority(
+                &program_id,
+                &proposal_key,
+                &mint_key,
+                AuthorityType::MintTokens,
+                &owner2_key,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut proposal_account,
+                &mut mint_account,
+                &mut rent_sysvar,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        let proposal = MintAuthorityProposal::unpack(&proposal_account.data).unwrap();
+        assert_eq!(
+            proposal,
+            MintAuthorityProposal {
+                is_initialized: true,
+                mint: mint_key,
+                authority_type: AuthorityType::MintTokens,
+                current_authority: owner_key,
+                new_authority: owner2_key,
+            }
+        );
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.mint_authority, COption::Some(owner_key));
+
+        // proposal already in use
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                propose_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    AuthorityType::MintTokens,
+                    &owner3_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut rent_sysvar,
+                    &mut owner_account,
+                ],
+            )
+        );
+
+        // only the proposed authority can accept
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                accept_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    &owner3_key,
+                    &owner3_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut owner3_account,
+                    &mut owner3_account.clone(),
+                ],
+            )
+        );
+
+        // the proposed authority must sign
+        let mut instruction = accept_mint_authority(
+            &program_id,
+            &proposal_key,
+            &mint_key,
+            &owner2_key,
+            &owner2_key,
+            &[],
+        )
+        .unwrap();
+        instruction.accounts[3].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut owner2_account,
+                    &mut owner2_account.clone(),
+                ],
+            )
+        );
+
+        // accept
+        do_process_instruction(
+            accept_mint_authority(
+                &program_id,
+                &proposal_key,
+                &mint_key,
+                &owner2_key,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut proposal_account,
+                &mut mint_account,
+                &mut owner2_account,
+                &mut owner2_account.clone(),
+            ],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.mint_authority, COption::Some(owner2_key));
+        assert_eq!(proposal_account.lamports, 0);
+        assert_eq!(owner2_account.lamports, proposal_minimum_balance());
+        assert_eq!(
+            Err(ProgramError::UninitializedAccount),
+            MintAuthorityProposal::unpack(&proposal_account.data)
+        );
+
+        // propose again, then cancel
+        proposal_account.lamports = proposal_minimum_balance();
+        do_process_instruction(
+            propose_mint_authority(
+                &program_id,
+                &proposal_key,
+                &mint_key,
+                AuthorityType::MintTokens,
+                &owner3_key,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut proposal_account,
+                &mut mint_account,
+                &mut rent_sysvar,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+
+        // only the current authority can cancel
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                cancel_mint_authority_proposal(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    &owner3_key,
+                    &owner3_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut owner3_account,
+                    &mut owner3_account.clone(),
+                ],
+            )
+        );
+
+        do_process_instruction(
+            cancel_mint_authority_proposal(
+                &program_id,
+                &proposal_key,
+                &mint_key,
+                &owner_key,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut proposal_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+        let mint = Mint::unpack(&mint_account.data).unwrap();
+        assert_eq!(mint.mint_authority, COption::Some(owner2_key));
+        assert_eq!(proposal_account.lamports, 0);
+        assert_eq!(owner_account.lamports, proposal_minimum_balance());
+
+        // propose again, but the authority changes before acceptance
+        proposal_account.lamports = proposal_minimum_balance();
+        do_process_instruction(
+            propose_mint_authority(
+                &program_id,
+                &proposal_key,
+                &mint_key,
+                AuthorityType::MintTokens,
+                &owner3_key,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut proposal_account,
+                &mut mint_account,
+                &mut rent_sysvar,
+                &mut owner2_account,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            set_authority(
+                &program_id,
+                &mint_key,
+                Some(&owner_key),
+                AuthorityType::MintTokens,
+                &owner2_key,
+                &[],
+            )
+            .unwrap(),
+            vec![&mut mint_account, &mut owner2_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::StaleMintAuthorityProposal.into()),
+            do_process_instruction(
+                accept_mint_authority(
+                    &program_id,
+                    &proposal_key,
+                    &mint_key,
+                    &owner3_key,
+                    &owner3_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut proposal_account,
+                    &mut mint_account,
+                    &mut owner3_account,
+                    &mut owner3_account.clone(),
+                ],
+            )
+        );
+    }
+
+    #[test]
+    fn test_close_accounts_batch() {
+        let program_id = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account3_key = Pubkey::new_unique();
+        let mut account3_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let destination_key = Pubkey::new_unique();
+        let mut destination_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint and accounts
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account3_key, &mint_key, &owner2_key).unwrap(),
+            vec![
+                &mut account3_account,
+                &mut mint_account,
+                &mut owner2_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // no accounts to close
+        assert_eq!(
+            Err(TokenError::InvalidInstruction.into()),
+            close_accounts_batch(&program_id, &[], &destination_key, &owner_key, &[])
+        );
+        let mut instruction = close_accounts_batch(
+            &program_id,
+            &[&account_key],
+            &destination_key,
+            &owner_key,
+            &[],
+        )
+        .unwrap();
+        instruction.data = TokenInstruction::CloseAccountsBatch { count: 2 }.pack();
+        assert_eq!(
+            Err(TokenError::InvalidInstruction.into()),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut account_account,
+                ],
+            )
+        );
+
+        // the accounts that fail come first, since this harness keeps the
+        // changes of a failed instruction
+
+        // account owned by someone else
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                close_accounts_batch(
+                    &program_id,
+                    &[&account3_key, &account_key],
+                    &destination_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut account3_account,
+                    &mut account_account,
+                ],
+            )
+        );
+
+        // account with a balance
+        do_process_instruction(
+            mint_to(&program_id, &mint_key, &account2_key, &owner_key, &[], 42).unwrap(),
+            vec![&mut mint_account, &mut account2_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::NonNativeHasBalance.into()),
+            do_process_instruction(
+                close_accounts_batch(
+                    &program_id,
+                    &[&account2_key, &account_key],
+                    &destination_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut account2_account,
+                    &mut account_account,
+                ],
+            )
+        );
+        do_process_instruction(
+            burn(&program_id, &account2_key, &mint_key, &owner_key, &[], 42).unwrap(),
+            vec![&mut account2_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // destination among the accounts to close
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(
+                close_accounts_batch(
+                    &program_id,
+                    &[&account2_key, &account_key],
+                    &account2_key,
+                    &owner_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![
+                    &mut account2_account.clone(),
+                    &mut owner_account,
+                    &mut account2_account,
+                    &mut account_account,
+                ],
+            )
+        );
+
+        // missing signer
+        let mut instruction = close_accounts_batch(
+            &program_id,
+            &[&account_key, &account2_key],
+            &destination_key,
+            &owner_key,
+            &[],
+        )
+        .unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut account_account,
+                    &mut account2_account,
+                ],
+            )
+        );
+
+        // close both accounts
+        do_process_instruction(
+            close_accounts_batch(
+                &program_id,
+                &[&account_key, &account2_key],
+                &destination_key,
+                &owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut destination_account,
+                &mut owner_account,
+                &mut account_account,
+                &mut account2_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(account_account.lamports, 0);
+        assert_eq!(account2_account.lamports, 0);
+        assert_eq!(destination_account.lamports, 2 * account_minimum_balance());
+    }
+
+    #[test]
+    fn test_verify_ownership() {
+        solana_program::program_stubs::set_syscall_stubs(Box::new(ClockSyscallStubs {}));
+
+        let program_id = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let account_key = Pubkey::new_unique();
+        let mut account_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let account2_key = Pubkey::new_unique();
+        let mut account2_account = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &program_id,
+        );
+        let owner_key = Pubkey::new_unique();
+        let mut owner_account = SolanaAccount::default();
+        let owner2_key = Pubkey::new_unique();
+        let mut owner2_account = SolanaAccount::default();
+        let multisig_key = Pubkey::new_unique();
+        let mut multisig_account = SolanaAccount::new(
+            multisig_minimum_balance(),
+            Multisig::get_packed_len(),
+            &program_id,
+        );
+        let signer_key = Pubkey::new_unique();
+        let mut signer_account = SolanaAccount::default();
+        let mut rent_sysvar = rent_sysvar();
+
+        // create mint, an account and an account owned by a multisig
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &owner_key, Some(&owner_key), 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_multisig(&program_id, &multisig_key, &[&signer_key], 1).unwrap(),
+            vec![&mut multisig_account, &mut rent_sysvar, &mut signer_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&program_id, &account2_key, &mint_key, &multisig_key).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut mint_account,
+                &mut multisig_account,
+                &mut rent_sysvar,
+            ],
+        )
+        .unwrap();
+
+        // wrong owner
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                verify_ownership(&program_id, &account_key, &owner2_key, &[]).unwrap(),
+                vec![&mut account_account, &mut owner2_account],
+            )
+        );
+
+        // owner did not sign
+        let mut instruction = verify_ownership(&program_id, &account_key, &owner_key, &[]).unwrap();
+        instruction.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(instruction, vec![&mut account_account, &mut owner_account])
+        );
+
+        // lookalike account of another program
+        let mut lookalike_account = account_account.clone();
+        lookalike_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(
+                verify_ownership(&program_id, &account_key, &owner_key, &[]).unwrap(),
+                vec![&mut lookalike_account, &mut owner_account],
+            )
+        );
+
+        // success, leaving the account untouched
+        let before = account_account.clone();
+        do_process_instruction(
+            verify_ownership(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+        assert_eq!(account_account, before);
+
+        // frozen accounts can be proven too
+        do_process_instruction(
+            freeze_account(&program_id, &account_key, &mint_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut mint_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            verify_ownership(&program_id, &account_key, &owner_key, &[]).unwrap(),
+            vec![&mut account_account, &mut owner_account],
+        )
+        .unwrap();
+
+        // multisig owner
+        do_process_instruction(
+            verify_ownership(&program_id, &account2_key, &multisig_key, &[&signer_key]).unwrap(),
+            vec![
+                &mut account2_account,
+                &mut multisig_account,
+                &mut signer_account,
+            ],
+        )
+        .unwrap();
+        let mut instruction =
+            verify_ownership(&program_id, &account2_key, &multisig_key, &[&signer_key]).unwrap();
+        instruction.accounts[2].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(
+                instruction,
+                vec![
+                    &mut account2_account,
+                    &mut multisig_account,
+                    &mut signer_account,
+                ],
+            )
+        );
+    }
 }
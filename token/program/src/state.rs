@@ -1,6 +1,6 @@
 //! State transition types
 
-use crate::instruction::MAX_SIGNERS;
+use crate::instruction::{AuthorityType, MAX_SIGNERS};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use num_enum::TryFromPrimitive;
 use solana_program::{
@@ -245,6 +245,139 @@ impl Pack for Multisig {
     }
 }
 
+/// Ledger of the cumulative flows through a token account.
+///
+/// Ledgers are opt-in: a ledger is only updated by the transfers that include it
+/// in their trailing accounts, so it records a lower bound of the account's
+/// activity unless every transfer client is known to attach it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AccountLedger {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The token account tracked by this ledger
+    pub account: Pubkey,
+    /// Total amount of tokens received by the account
+    pub total_received: u64,
+    /// Total amount of tokens sent from the account
+    pub total_sent: u64,
+    /// Slot of the last transfer recorded in this ledger
+    pub last_activity_slot: u64,
+}
+impl Sealed for AccountLedger {}
+impl IsInitialized for AccountLedger {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for AccountLedger {
+    const LEN: usize = 57;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 57];
+        let (is_initialized, account, total_received, total_sent, last_activity_slot) =
+            array_refs![src, 1, 32, 8, 8, 8];
+        Ok(AccountLedger {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            account: Pubkey::new_from_array(*account),
+            total_received: u64::from_le_bytes(*total_received),
+            total_sent: u64::from_le_bytes(*total_sent),
+            last_activity_slot: u64::from_le_bytes(*last_activity_slot),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 57];
+        let (
+            is_initialized_dst,
+            account_dst,
+            total_received_dst,
+            total_sent_dst,
+            last_activity_slot_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8];
+        let &AccountLedger {
+            is_initialized,
+            ref account,
+            total_received,
+            total_sent,
+            last_activity_slot,
+        } = self;
+        is_initialized_dst[0] = is_initialized as u8;
+        account_dst.copy_from_slice(account.as_ref());
+        *total_received_dst = total_received.to_le_bytes();
+        *total_sent_dst = total_sent.to_le_bytes();
+        *last_activity_slot_dst = last_activity_slot.to_le_bytes();
+    }
+}
+
+/// Pending transfer of a mint's minting or freeze authority, waiting for the
+/// new authority to accept it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MintAuthorityProposal {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The mint whose authority is being transferred
+    pub mint: Pubkey,
+    /// The authority being transferred, `MintTokens` or `FreezeAccount`
+    pub authority_type: AuthorityType,
+    /// The authority that made the proposal. The proposal goes stale if the
+    /// mint's authority changes before it is accepted.
+    pub current_authority: Pubkey,
+    /// The proposed authority/multisignature, which must accept the proposal
+    pub new_authority: Pubkey,
+}
+impl Sealed for MintAuthorityProposal {}
+impl IsInitialized for MintAuthorityProposal {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for MintAuthorityProposal {
+    const LEN: usize = 98;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 98];
+        let (is_initialized, mint, authority_type, current_authority, new_authority) =
+            array_refs![src, 1, 32, 1, 32, 32];
+        Ok(MintAuthorityProposal {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            mint: Pubkey::new_from_array(*mint),
+            authority_type: AuthorityType::from(authority_type[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+            current_authority: Pubkey::new_from_array(*current_authority),
+            new_authority: Pubkey::new_from_array(*new_authority),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 98];
+        let (
+            is_initialized_dst,
+            mint_dst,
+            authority_type_dst,
+            current_authority_dst,
+            new_authority_dst,
+        ) = mut_array_refs![dst, 1, 32, 1, 32, 32];
+        let &MintAuthorityProposal {
+            is_initialized,
+            ref mint,
+            ref authority_type,
+            ref current_authority,
+            ref new_authority,
+        } = self;
+        is_initialized_dst[0] = is_initialized as u8;
+        mint_dst.copy_from_slice(mint.as_ref());
+        authority_type_dst[0] = authority_type.into();
+        current_authority_dst.copy_from_slice(current_authority.as_ref());
+        new_authority_dst.copy_from_slice(new_authority.as_ref());
+    }
+}
+
 // Helpers
 fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
     let (tag, body) = mut_array_refs![dst, 4, 32];